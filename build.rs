@@ -78,6 +78,10 @@ error: the `wasm32-wasi` target is not installed
         "wagi-benchmark.wasm",
         "crates/trigger-http/benches/wagi-benchmark",
     );
+    build_wasm_test_program(
+        "wasi-http-benchmark.wasm",
+        "crates/trigger-http/benches/wasi-http-benchmark",
+    );
     build_wasm_test_program("timer_app_example.wasm", "examples/spin-timer/app-example");
 
     cargo_build(TIMER_TRIGGER_INTEGRATION_TEST);
@@ -0,0 +1,81 @@
+use std::sync::Arc;
+
+use anyhow::anyhow;
+use spin_app::{AppComponent, DynamicHostComponent};
+use spin_core::HostComponent;
+
+use crate::{BlobstoreDispatch, Containers, BLOB_CONTAINERS_KEY};
+
+pub struct BlobstoreComponent {
+    containers: Arc<Containers>,
+}
+
+impl BlobstoreComponent {
+    pub fn new(containers: Containers) -> Self {
+        Self {
+            containers: Arc::new(containers),
+        }
+    }
+}
+
+impl HostComponent for BlobstoreComponent {
+    type Data = BlobstoreDispatch;
+
+    fn add_to_linker<T: Send>(
+        linker: &mut spin_core::Linker<T>,
+        get: impl Fn(&mut spin_core::Data<T>) -> &mut Self::Data + Send + Sync + Copy + 'static,
+    ) -> anyhow::Result<()> {
+        spin_world::v2::blobstore::add_to_linker(linker, get)
+    }
+
+    fn build_data(&self) -> Self::Data {
+        BlobstoreDispatch::new(self.containers.clone())
+    }
+
+    fn reset_data(&self, data: &mut Self::Data) -> bool {
+        data.reset();
+        true
+    }
+}
+
+impl DynamicHostComponent for BlobstoreComponent {
+    fn update_data(&self, data: &mut Self::Data, component: &AppComponent) -> anyhow::Result<()> {
+        let blob_containers = component
+            .get_metadata(BLOB_CONTAINERS_KEY)?
+            .unwrap_or_default();
+        data.init(blob_containers.into_iter().collect());
+        Ok(())
+    }
+
+    fn validate_app(&self, app: &spin_app::App) -> anyhow::Result<()> {
+        let mut errors = vec![];
+
+        for component in app.components() {
+            for allowed in component
+                .get_metadata(BLOB_CONTAINERS_KEY)?
+                .unwrap_or_default()
+            {
+                if !self.containers.contains_key(&allowed) {
+                    let err = format!("- Component {} uses container '{allowed}'", component.id());
+                    errors.push(err);
+                }
+            }
+        }
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            let prologue = vec![
+                "One or more components use blob containers which are not defined.",
+                "Check the spelling, or pass a runtime configuration file that defines these containers.",
+                "Details:",
+            ];
+            let lines: Vec<_> = prologue
+                .into_iter()
+                .map(|s| s.to_owned())
+                .chain(errors)
+                .collect();
+            Err(anyhow!(lines.join("\n")))
+        }
+    }
+}
@@ -0,0 +1,386 @@
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+
+use anyhow::{Context, Result};
+use futures::TryStreamExt;
+use object_store::{path::Path, MultipartId, ObjectMeta, ObjectStore};
+use spin_app::MetadataKey;
+use spin_core::{async_trait, wasmtime::component::Resource};
+use spin_world::v2::blobstore::{self, Container, ObjectMetadata, ObjectWriter};
+use table::Table;
+use tokio::io::{AsyncWrite, AsyncWriteExt};
+
+mod host_component;
+
+pub use host_component::BlobstoreComponent;
+pub use object_store;
+
+pub const BLOB_CONTAINERS_KEY: MetadataKey<Vec<String>> = MetadataKey::new("blob_containers");
+
+const DEFAULT_TABLE_CAPACITY: u32 = 256;
+
+pub use blobstore::Error;
+
+/// The containers configured for an app, by label.
+pub type Containers = HashMap<String, Arc<dyn ObjectStore>>;
+
+pub struct BlobstoreDispatch {
+    allowed_containers: HashSet<String>,
+    containers: Arc<Containers>,
+    open_containers: Table<Arc<dyn ObjectStore>>,
+    writers: Table<Writer>,
+}
+
+/// An object being written with a multipart upload.
+struct Writer {
+    store: Arc<dyn ObjectStore>,
+    path: Path,
+    id: MultipartId,
+    sink: Box<dyn AsyncWrite + Unpin + Send>,
+}
+
+impl BlobstoreDispatch {
+    pub fn new(containers: Arc<Containers>) -> Self {
+        Self {
+            allowed_containers: HashSet::new(),
+            containers,
+            open_containers: Table::new(DEFAULT_TABLE_CAPACITY),
+            writers: Table::new(DEFAULT_TABLE_CAPACITY),
+        }
+    }
+
+    pub fn init(&mut self, allowed_containers: HashSet<String>) {
+        self.allowed_containers = allowed_containers;
+    }
+
+    /// Closes all open containers and writers and forgets the allowed
+    /// containers, leaving the dispatch as if newly created.
+    pub fn reset(&mut self) {
+        self.allowed_containers.clear();
+        self.open_containers.clear();
+        self.writers.drain().for_each(Writer::abort);
+    }
+
+    fn get_container(&self, container: &Resource<Container>) -> Result<&Arc<dyn ObjectStore>> {
+        self.open_containers
+            .get(container.rep())
+            .context("invalid container")
+    }
+}
+
+impl Writer {
+    // Abandons the upload in the background, as resources are dropped
+    // synchronously.
+    fn abort(self) {
+        tokio::spawn(async move {
+            if let Err(err) = self.store.abort_multipart(&self.path, &self.id).await {
+                tracing::warn!("Failed to abort upload of {}: {err}", self.path);
+            }
+        });
+    }
+}
+
+#[async_trait]
+impl blobstore::Host for BlobstoreDispatch {}
+
+#[async_trait]
+impl blobstore::HostContainer for BlobstoreDispatch {
+    async fn open(&mut self, label: String) -> Result<Result<Resource<Container>, Error>> {
+        Ok(async {
+            if !self.allowed_containers.contains(&label) {
+                return Err(Error::AccessDenied);
+            }
+            let store = self.containers.get(&label).ok_or(Error::AccessDenied)?;
+            let rep = self
+                .open_containers
+                .push(store.clone())
+                .map_err(|()| Error::TableFull)?;
+            Ok(Resource::new_own(rep))
+        }
+        .await)
+    }
+
+    async fn get(
+        &mut self,
+        container: Resource<Container>,
+        name: String,
+    ) -> Result<Result<Vec<u8>, Error>> {
+        let store = self.get_container(&container)?;
+        Ok(async {
+            let path = parse_name(&name)?;
+            let result = store.get(&path).await.map_err(to_error)?;
+            let bytes = result.bytes().await.map_err(to_error)?;
+            Ok(bytes.to_vec())
+        }
+        .await)
+    }
+
+    async fn get_range(
+        &mut self,
+        container: Resource<Container>,
+        name: String,
+        offset: u64,
+        length: u64,
+    ) -> Result<Result<Vec<u8>, Error>> {
+        let store = self.get_container(&container)?;
+        Ok(async {
+            let path = parse_name(&name)?;
+            // Ranges past the end of the object are clamped to its size
+            let size = store.head(&path).await.map_err(to_error)?.size;
+            let start = usize::try_from(offset).unwrap_or(usize::MAX).min(size);
+            let end = start
+                .saturating_add(usize::try_from(length).unwrap_or(usize::MAX))
+                .min(size);
+            if start == end {
+                return Ok(vec![]);
+            }
+            let bytes = store.get_range(&path, start..end).await.map_err(to_error)?;
+            Ok(bytes.to_vec())
+        }
+        .await)
+    }
+
+    async fn put(
+        &mut self,
+        container: Resource<Container>,
+        name: String,
+        data: Vec<u8>,
+    ) -> Result<Result<(), Error>> {
+        let store = self.get_container(&container)?;
+        Ok(async {
+            let path = parse_name(&name)?;
+            store.put(&path, data.into()).await.map_err(to_error)?;
+            Ok(())
+        }
+        .await)
+    }
+
+    async fn write(
+        &mut self,
+        container: Resource<Container>,
+        name: String,
+    ) -> Result<Result<Resource<ObjectWriter>, Error>> {
+        let store = self.get_container(&container)?.clone();
+        Ok(async {
+            let path = parse_name(&name)?;
+            let (id, sink) = store.put_multipart(&path).await.map_err(to_error)?;
+            let writer = Writer {
+                store: store.clone(),
+                path: path.clone(),
+                id: id.clone(),
+                sink,
+            };
+            match self.writers.push(writer) {
+                Ok(rep) => Ok(Resource::new_own(rep)),
+                Err(()) => {
+                    _ = store.abort_multipart(&path, &id).await;
+                    Err(Error::TableFull)
+                }
+            }
+        }
+        .await)
+    }
+
+    async fn delete(
+        &mut self,
+        container: Resource<Container>,
+        name: String,
+    ) -> Result<Result<(), Error>> {
+        let store = self.get_container(&container)?;
+        Ok(async {
+            let path = parse_name(&name)?;
+            match store.delete(&path).await {
+                Ok(()) | Err(object_store::Error::NotFound { .. }) => Ok(()),
+                Err(err) => Err(to_error(err)),
+            }
+        }
+        .await)
+    }
+
+    async fn exists(
+        &mut self,
+        container: Resource<Container>,
+        name: String,
+    ) -> Result<Result<bool, Error>> {
+        let store = self.get_container(&container)?;
+        Ok(async {
+            let path = parse_name(&name)?;
+            match store.head(&path).await {
+                Ok(_) => Ok(true),
+                Err(object_store::Error::NotFound { .. }) => Ok(false),
+                Err(err) => Err(to_error(err)),
+            }
+        }
+        .await)
+    }
+
+    async fn metadata(
+        &mut self,
+        container: Resource<Container>,
+        name: String,
+    ) -> Result<Result<ObjectMetadata, Error>> {
+        let store = self.get_container(&container)?;
+        Ok(async {
+            let path = parse_name(&name)?;
+            let meta = store.head(&path).await.map_err(to_error)?;
+            Ok(to_metadata(meta))
+        }
+        .await)
+    }
+
+    async fn list(
+        &mut self,
+        container: Resource<Container>,
+        prefix: String,
+    ) -> Result<Result<Vec<ObjectMetadata>, Error>> {
+        let store = self.get_container(&container)?;
+        Ok(async {
+            // Stores list by whole segments, so list the deepest complete
+            // segments of the prefix and filter by the rest
+            let parent = match prefix.rsplit_once('/') {
+                Some((parent, _)) if !parent.is_empty() => Some(parse_name(parent)?),
+                _ => None,
+            };
+            let objects: Vec<ObjectMeta> = store
+                .list(parent.as_ref())
+                .try_collect()
+                .await
+                .map_err(to_error)?;
+            Ok(objects
+                .into_iter()
+                .filter(|meta| meta.location.as_ref().starts_with(&prefix))
+                .map(to_metadata)
+                .collect())
+        }
+        .await)
+    }
+
+    fn drop(&mut self, container: Resource<Container>) -> Result<()> {
+        self.open_containers.remove(container.rep());
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl blobstore::HostObjectWriter for BlobstoreDispatch {
+    async fn write(
+        &mut self,
+        writer: Resource<ObjectWriter>,
+        chunk: Vec<u8>,
+    ) -> Result<Result<(), Error>> {
+        let writer = self
+            .writers
+            .get_mut(writer.rep())
+            .context("invalid object writer")?;
+        Ok(writer.sink.write_all(&chunk).await.map_err(other_error))
+    }
+
+    async fn finish(&mut self, writer: Resource<ObjectWriter>) -> Result<Result<(), Error>> {
+        let mut writer = self
+            .writers
+            .remove(writer.rep())
+            .context("invalid object writer")?;
+        Ok(writer.sink.shutdown().await.map_err(other_error))
+    }
+
+    fn drop(&mut self, writer: Resource<ObjectWriter>) -> Result<()> {
+        // Finished writers have already been removed
+        if let Some(writer) = self.writers.remove(writer.rep()) {
+            writer.abort();
+        }
+        Ok(())
+    }
+}
+
+fn parse_name(name: &str) -> Result<Path, Error> {
+    Path::parse(name).map_err(|e| Error::InvalidName(e.to_string()))
+}
+
+fn to_metadata(meta: ObjectMeta) -> ObjectMetadata {
+    ObjectMetadata {
+        name: meta.location.to_string(),
+        size: meta.size as u64,
+        last_modified: meta.last_modified.timestamp().try_into().unwrap_or(0),
+    }
+}
+
+fn to_error(err: object_store::Error) -> Error {
+    match err {
+        object_store::Error::NotFound { .. } => Error::NoSuchObject,
+        err => other_error(err),
+    }
+}
+
+fn other_error(e: impl std::fmt::Display) -> Error {
+    Error::Other(e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use object_store::memory::InMemory;
+
+    use super::*;
+
+    async fn open(dispatch: &mut BlobstoreDispatch) -> Resource<Container> {
+        use blobstore::HostContainer;
+        dispatch.init(["default".to_owned()].into());
+        dispatch.open("default".into()).await.unwrap().unwrap()
+    }
+
+    fn dispatch() -> BlobstoreDispatch {
+        let store: Arc<dyn ObjectStore> = Arc::new(InMemory::new());
+        BlobstoreDispatch::new(Arc::new([("default".to_owned(), store)].into()))
+    }
+
+    #[tokio::test]
+    async fn only_allowed_containers_open() {
+        use blobstore::HostContainer;
+        let mut dispatch = dispatch();
+        let result = dispatch.open("default".into()).await.unwrap();
+        assert!(matches!(result, Err(Error::AccessDenied)));
+    }
+
+    #[tokio::test]
+    async fn list_filters_by_partial_segments() {
+        use blobstore::HostContainer;
+        let mut dispatch = dispatch();
+        for name in ["images/cat.png", "images/catalog.json", "images/dog.png"] {
+            let container = open(&mut dispatch).await;
+            dispatch
+                .put(container, name.into(), b"data".to_vec())
+                .await
+                .unwrap()
+                .unwrap();
+        }
+        let container = open(&mut dispatch).await;
+        let listed = dispatch
+            .list(container, "images/cat".into())
+            .await
+            .unwrap()
+            .unwrap();
+        let names: Vec<_> = listed.into_iter().map(|meta| meta.name).collect();
+        assert_eq!(names, ["images/cat.png", "images/catalog.json"]);
+    }
+
+    #[tokio::test]
+    async fn ranges_are_clamped_to_the_object() {
+        use blobstore::HostContainer;
+        let mut dispatch = dispatch();
+        let container = open(&mut dispatch).await;
+        dispatch
+            .put(container, "data".into(), b"0123456789".to_vec())
+            .await
+            .unwrap()
+            .unwrap();
+        let container = open(&mut dispatch).await;
+        let range = dispatch
+            .get_range(container, "data".into(), 8, 100)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(range, b"89");
+        let container = open(&mut dispatch).await;
+        let missing = dispatch.get(container, "nope".into()).await.unwrap();
+        assert!(matches!(missing, Err(Error::NoSuchObject)));
+    }
+}
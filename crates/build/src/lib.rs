@@ -3,20 +3,64 @@
 //! A library for building Spin components.
 
 mod manifest;
+mod prewizen;
 
 use anyhow::{anyhow, bail, Context, Result};
+use futures::{stream::FuturesUnordered, StreamExt};
 use manifest::ComponentBuildInfo;
 use spin_common::{paths::parent_dir, ui::quoted_path};
+use spin_manifest::schema::v2::ComponentBuildConfig;
 use std::{
-    collections::HashSet,
+    collections::{HashMap, HashSet},
     path::{Path, PathBuf},
+    process::Stdio,
 };
-use subprocess::{Exec, Redirection};
+use tokio::io::{AsyncBufReadExt, AsyncRead, BufReader};
 
 use crate::manifest::component_build_configs;
 
+/// Options controlling how components are built.
+#[derive(Clone, Debug)]
+pub struct BuildOptions {
+    /// The maximum number of build commands to run at once.
+    pub jobs: usize,
+    /// Whether to carry on building the other components after a build
+    /// fails, rather than stopping at the first failure.
+    pub keep_going: bool,
+    /// The build profile to use, for components which define it. Other
+    /// components use their default build command.
+    pub profile: Option<String>,
+}
+
+impl Default for BuildOptions {
+    fn default() -> Self {
+        Self {
+            jobs: std::thread::available_parallelism()
+                .map(|n| n.get())
+                .unwrap_or(1),
+            keep_going: false,
+            profile: None,
+        }
+    }
+}
+
 /// If present, run the build command of each component.
 pub async fn build(manifest_file: &Path, component_ids: &[String]) -> Result<()> {
+    build_with_options(manifest_file, component_ids, &BuildOptions::default()).await?;
+    Ok(())
+}
+
+/// If present, run the build command of each component, as directed by
+/// `options`.
+///
+/// Components are built in parallel, except that a component is not built
+/// until the components in its `depends_on` list have been. Returns the IDs
+/// of the components which were built, in the order they finished.
+pub async fn build_with_options(
+    manifest_file: &Path,
+    component_ids: &[String],
+    options: &BuildOptions,
+) -> Result<Vec<String>> {
     let components = component_build_configs(manifest_file)
         .await
         .with_context(|| {
@@ -26,6 +70,7 @@ pub async fn build(manifest_file: &Path, component_ids: &[String]) -> Result<()>
             )
         })?;
     let app_dir = parent_dir(manifest_file)?;
+    check_dependencies(&components)?;
 
     let components_to_build = if component_ids.is_empty() {
         components
@@ -48,62 +93,284 @@ pub async fn build(manifest_file: &Path, component_ids: &[String]) -> Result<()>
     };
 
     if components_to_build.iter().all(|c| c.build.is_none()) {
-        println!("None of the components have a build command.");
-        println!("For information on specifying a build command, see https://developer.fermyon.com/spin/build#setting-up-for-spin-build.");
-        return Ok(());
+        terminal::text!("None of the components have a build command.");
+        terminal::text!("For information on specifying a build command, see https://developer.fermyon.com/spin/build#setting-up-for-spin-build.");
+        return Ok(vec![]);
     }
 
-    components_to_build
+    let prewizen_paths = prewizen_paths(&components_to_build, &app_dir)?;
+    let mut components_to_build: Vec<_> = components_to_build
         .into_iter()
-        .map(|c| build_component(c, &app_dir))
-        .collect::<Result<Vec<_>, _>>()?;
+        .filter_map(|c| Some((c.id, c.build?)))
+        .collect();
+    if let Some(profile) = &options.profile {
+        apply_profile(&mut components_to_build, profile)?;
+    }
+    let built = build_components(components_to_build, &app_dir, &prewizen_paths, options).await?;
 
     terminal::step!("Finished", "building all Spin components");
-    Ok(())
+    Ok(built)
 }
 
-/// Run the build command of the component.
-fn build_component(build_info: ComponentBuildInfo, app_dir: &Path) -> Result<()> {
-    match build_info.build {
-        Some(b) => {
-            terminal::step!(
-                "Building",
-                "component {} with `{}`",
-                build_info.id,
-                b.command
-            );
-            let workdir = construct_workdir(app_dir, b.workdir.as_ref())?;
-            if b.workdir.is_some() {
-                println!("Working directory: {}", quoted_path(&workdir));
+/// Replaces the build command and workdir of each component which defines
+/// `profile` with those of the profile.
+fn apply_profile(components: &mut [(String, ComponentBuildConfig)], profile: &str) -> Result<()> {
+    if !components
+        .iter()
+        .any(|(_, build)| build.profiles.contains_key(profile))
+    {
+        bail!("None of the components have a build profile named '{profile}'");
+    }
+    for (_, build) in components {
+        if let Some(selected) = build.profiles.get(profile) {
+            build.command = selected.command.clone();
+            if selected.workdir.is_some() {
+                build.workdir = selected.workdir.clone();
             }
+        }
+    }
+    Ok(())
+}
+
+/// Returns the Wasm paths of the components to pre-initialize after building.
+/// These must have local sources.
+fn prewizen_paths(
+    components: &[ComponentBuildInfo],
+    app_dir: &Path,
+) -> Result<HashMap<String, PathBuf>> {
+    components
+        .iter()
+        .filter(|c| c.build.as_ref().is_some_and(|b| b.prewizen))
+        .map(|c| match c.source.as_ref().and_then(|s| s.as_str()) {
+            Some(path) => Ok((c.id.clone(), app_dir.join(path))),
+            None => bail!(
+                "Component {} has `prewizen = true`, but its source is not a local file",
+                c.id
+            ),
+        })
+        .collect()
+}
 
-            let exit_status = Exec::shell(&b.command)
-                .cwd(workdir)
-                .stdout(Redirection::None)
-                .stderr(Redirection::None)
-                .stdin(Redirection::None)
-                .popen()
-                .map_err(|err| {
-                    anyhow!(
-                        "Cannot spawn build process '{:?}' for component {}: {}",
-                        &b.command,
-                        build_info.id,
-                        err
-                    )
-                })?
-                .wait()?;
-
-            if !exit_status.success() {
+/// Checks that components only depend on components in the manifest, and
+/// not (directly or indirectly) on themselves.
+fn check_dependencies(components: &[ComponentBuildInfo]) -> Result<()> {
+    let dependencies: HashMap<&str, &[String]> = components
+        .iter()
+        .map(|c| {
+            let depends_on = c.build.as_ref().map(|b| b.depends_on.as_slice());
+            (c.id.as_str(), depends_on.unwrap_or_default())
+        })
+        .collect();
+    for component in components {
+        for dependency in dependencies[component.id.as_str()] {
+            if !dependencies.contains_key(dependency.as_str()) {
                 bail!(
-                    "Build command for component {} failed with status {:?}",
-                    build_info.id,
-                    exit_status,
+                    "Component {} depends on unknown component {}",
+                    component.id,
+                    dependency
                 );
             }
+        }
+    }
+
+    fn visit<'a>(
+        id: &'a str,
+        dependencies: &HashMap<&'a str, &'a [String]>,
+        path: &mut Vec<&'a str>,
+        checked: &mut HashSet<&'a str>,
+    ) -> Result<()> {
+        if checked.contains(id) {
+            return Ok(());
+        }
+        if let Some(start) = path.iter().position(|p| *p == id) {
+            let mut cycle = path[start..].to_vec();
+            cycle.push(id);
+            bail!(
+                "Components have circular build dependencies: {}",
+                cycle.join(" -> ")
+            );
+        }
+        path.push(id);
+        for dependency in dependencies[id] {
+            visit(dependency, dependencies, path, checked)?;
+        }
+        path.pop();
+        checked.insert(id);
+        Ok(())
+    }
+
+    let mut checked = HashSet::new();
+    for component in components {
+        visit(&component.id, &dependencies, &mut vec![], &mut checked)?;
+    }
+    Ok(())
+}
+
+/// Runs the build commands of the components, at most `options.jobs` at a
+/// time and each after those of its dependencies, pre-initializing those in
+/// `prewizen_paths` once built.
+async fn build_components(
+    mut pending: Vec<(String, ComponentBuildConfig)>,
+    app_dir: &Path,
+    prewizen_paths: &HashMap<String, PathBuf>,
+    options: &BuildOptions,
+) -> Result<Vec<String>> {
+    let jobs = options.jobs.max(1);
+    // Only interleaved output needs telling apart
+    let prefix_output = jobs > 1 && pending.len() > 1;
+    let building: HashSet<String> = pending.iter().map(|(id, _)| id.clone()).collect();
+    let mut succeeded = vec![];
+    let mut failed = HashSet::new();
+    let mut running = FuturesUnordered::new();
+
+    loop {
+        // With --keep-going, skip anything depending on a failed build. As
+        // dependencies are acyclic, this leaves something ready to build
+        // whenever nothing is running.
+        while let Some(index) = pending
+            .iter()
+            .position(|(_, build)| build.depends_on.iter().any(|d| failed.contains(d)))
+        {
+            let (id, _) = pending.remove(index);
+            terminal::warn!("Skipping component {id} as a component it depends on failed to build");
+            failed.insert(id);
+        }
+
+        while running.len() < jobs {
+            let Some(index) = pending.iter().position(|(_, build)| {
+                build
+                    .depends_on
+                    .iter()
+                    .all(|d| !building.contains(d) || succeeded.contains(d))
+            }) else {
+                break;
+            };
+            let (id, build) = pending.remove(index);
+            let prewizen_path = prewizen_paths.get(&id);
+            running.push(async move {
+                let result =
+                    build_component(&id, &build, app_dir, prewizen_path, prefix_output).await;
+                (id, result)
+            });
+        }
+
+        let Some((id, result)) = running.next().await else {
+            break;
+        };
+        match result {
+            Ok(()) => succeeded.push(id),
+            // Dropping the running builds kills them
+            Err(err) if !options.keep_going => return Err(err),
+            Err(err) => {
+                terminal::error!("{err:#}");
+                failed.insert(id);
+            }
+        }
+    }
+
+    if !failed.is_empty() {
+        let mut failed: Vec<_> = failed.into_iter().collect();
+        failed.sort();
+        bail!("Failed to build component(s) {}", failed.join(", "));
+    }
+    Ok(succeeded)
+}
+
+/// Run the build command of the component, then pre-initialize the Wasm at
+/// `prewizen_path` if given.
+async fn build_component(
+    id: &str,
+    build: &ComponentBuildConfig,
+    app_dir: &Path,
+    prewizen_path: Option<&PathBuf>,
+    prefix_output: bool,
+) -> Result<()> {
+    terminal::step!("Building", "component {} with `{}`", id, build.command);
+    let workdir = construct_workdir(app_dir, build.workdir.as_ref())?;
+    if build.workdir.is_some() {
+        terminal::text!("Working directory: {}", quoted_path(&workdir));
+    }
+
+    let mut command = shell_command(&build.command);
+    command.current_dir(&workdir).kill_on_drop(true);
+    // Build output is text, so must go to stderr in JSON mode
+    if prefix_output || terminal::output_format().is_json() {
+        command
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped());
+    }
+    let mut child = command.spawn().map_err(|err| {
+        anyhow!(
+            "Cannot spawn build process '{:?}' for component {}: {}",
+            &build.command,
+            id,
+            err
+        )
+    })?;
+
+    let prefix = if prefix_output {
+        format!("[{id}] ")
+    } else {
+        String::new()
+    };
+    let stdout = child.stdout.take();
+    let stderr = child.stderr.take();
+    let (exit_status, _, _) = tokio::join!(
+        child.wait(),
+        async {
+            if let Some(stdout) = stdout {
+                print_lines(stdout, &prefix, false).await;
+            }
+        },
+        async {
+            if let Some(stderr) = stderr {
+                print_lines(stderr, &prefix, true).await;
+            }
+        },
+    );
+    let exit_status = exit_status?;
+
+    if !exit_status.success() {
+        bail!(
+            "Build command for component {} failed with status {:?}",
+            id,
+            exit_status,
+        );
+    }
+
+    if let Some(wasm_path) = prewizen_path {
+        prewizen::prewizen(id, wasm_path, &workdir).await?;
+    }
 
-            Ok(())
+    Ok(())
+}
+
+/// Creates a command to run `command` in the platform shell.
+fn shell_command(command: &str) -> tokio::process::Command {
+    if cfg!(windows) {
+        let mut cmd = tokio::process::Command::new("cmd.exe");
+        cmd.arg("/C").arg(command);
+        cmd
+    } else {
+        let mut cmd = tokio::process::Command::new("sh");
+        cmd.arg("-c").arg(command);
+        cmd
+    }
+}
+
+/// Prints each line of a build's output, with a prefix showing which
+/// component it came from if needed.
+async fn print_lines(output: impl AsyncRead + Unpin, prefix: &str, stderr: bool) {
+    let mut lines = BufReader::new(output).split(b'\n');
+    while let Ok(Some(line)) = lines.next_segment().await {
+        let line = String::from_utf8_lossy(&line);
+        let line = line.trim_end_matches('\r');
+        if stderr {
+            eprintln!("{prefix}{line}");
+        } else {
+            terminal::text!("{prefix}{line}");
         }
-        _ => Ok(()),
     }
 }
 
@@ -127,6 +394,7 @@ fn construct_workdir(app_dir: &Path, workdir: Option<impl AsRef<Path>>) -> Resul
 #[cfg(test)]
 mod tests {
     use super::*;
+    use spin_manifest::schema::v2::ComponentBuildProfile;
 
     fn test_data_root() -> PathBuf {
         let crate_dir = env!("CARGO_MANIFEST_DIR");
@@ -138,4 +406,79 @@ mod tests {
         let bad_trigger_file = test_data_root().join("bad_trigger.toml");
         build(&bad_trigger_file, &[]).await.unwrap();
     }
+
+    fn component(id: &str, depends_on: &[&str]) -> ComponentBuildInfo {
+        ComponentBuildInfo {
+            id: id.to_owned(),
+            source: None,
+            build: Some(ComponentBuildConfig {
+                command: "true".to_owned(),
+                workdir: None,
+                watch: vec![],
+                depends_on: depends_on.iter().map(|d| d.to_string()).collect(),
+                prewizen: false,
+                profiles: Default::default(),
+            }),
+        }
+    }
+
+    #[test]
+    fn dependencies_must_be_known_and_acyclic() {
+        check_dependencies(&[
+            component("app", &["lib", "assets"]),
+            component("lib", &["assets"]),
+            component("assets", &[]),
+        ])
+        .unwrap();
+
+        let err = check_dependencies(&[component("app", &["lib"])]).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Component app depends on unknown component lib"
+        );
+
+        let err = check_dependencies(&[
+            component("app", &["lib"]),
+            component("lib", &["util"]),
+            component("util", &["lib"]),
+        ])
+        .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Components have circular build dependencies: lib -> util -> lib"
+        );
+    }
+
+    #[test]
+    fn profile_overrides_only_the_components_defining_it() {
+        let profiled = |id: &str| {
+            let mut c = component(id, &[]);
+            let build = c.build.as_mut().unwrap();
+            build.workdir = Some(id.to_owned());
+            build.profiles.insert(
+                "release".to_owned(),
+                ComponentBuildProfile {
+                    command: "make release".to_owned(),
+                    workdir: None,
+                },
+            );
+            (c.id, c.build.unwrap())
+        };
+        let plain = |id: &str| {
+            let c = component(id, &[]);
+            (c.id, c.build.unwrap())
+        };
+
+        let mut components = vec![profiled("api"), plain("web")];
+        apply_profile(&mut components, "release").unwrap();
+        assert_eq!(components[0].1.command, "make release");
+        assert_eq!(components[0].1.workdir.as_deref(), Some("api"));
+        assert_eq!(components[1].1.command, "true");
+
+        let err = apply_profile(&mut components, "relaese").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "None of the components have a build profile named 'relaese'"
+        );
+    }
 }
@@ -33,6 +33,9 @@ pub struct ComponentBuildInfo {
     #[serde(default)]
     pub id: String,
     pub build: Option<v2::ComponentBuildConfig>,
+    /// The component's source, which is a path if it is built locally
+    #[serde(default)]
+    pub source: Option<toml::Value>,
 }
 
 #[derive(Deserialize)]
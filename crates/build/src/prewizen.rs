@@ -0,0 +1,110 @@
+//! Pre-initializing components at build time with Wizer.
+//!
+//! A component built with `prewizen = true` exports `wizer.initialize`, which
+//! does its expensive start-up work, such as starting an interpreter or
+//! parsing configuration. After the component is built, `spin build` runs
+//! [Wizer](https://github.com/bytecodealliance/wizer) to call the function
+//! and snapshot the state it leaves into the Wasm file. The file is replaced
+//! in place, so `spin up` and `spin registry push` use the pre-initialized
+//! Wasm with no further configuration.
+//!
+//! Initialization runs with WASI, in the component's build working directory.
+//! Wizer pre-initializes core modules, which Spin adapts into components when
+//! it loads them, and must be installed and on the `PATH`.
+
+use std::path::Path;
+
+use anyhow::{anyhow, bail, Context, Result};
+use spin_common::ui::quoted_path;
+use wasmparser::{Encoding, Parser, Payload};
+
+/// The export Wizer calls to initialize a module, and removes once it has.
+const INIT_FUNC: &str = "wizer.initialize";
+
+/// Pre-initializes the Wasm module of component `id` at `wasm_path`, running
+/// its initialization in `workdir`.
+pub(crate) async fn prewizen(id: &str, wasm_path: &Path, workdir: &Path) -> Result<()> {
+    let wasm = tokio::fs::read(wasm_path)
+        .await
+        .with_context(|| format!("Failed to read {}", quoted_path(wasm_path)))?;
+    let has_init_func =
+        has_init_func(&wasm).with_context(|| format!("Cannot pre-initialize component {id}"))?;
+    if !has_init_func {
+        terminal::warn!("Not pre-initializing component {id}: it doesn't export `{INIT_FUNC}`, so it has been pre-initialized already or doesn't support it");
+        return Ok(());
+    }
+
+    terminal::step!("Pre-initializing", "component {id} with Wizer");
+    let output_path = wasm_path.with_extension("wizer.wasm");
+    let status = tokio::process::Command::new("wizer")
+        .arg(wasm_path)
+        .arg("-o")
+        .arg(&output_path)
+        .args([
+            "--allow-wasi",
+            "--inherit-stdio=true",
+            "--inherit-env=true",
+            "--wasm-bulk-memory=true",
+            "--dir",
+            ".",
+        ])
+        .current_dir(workdir)
+        .kill_on_drop(true)
+        .status()
+        .await
+        .map_err(|err| match err.kind() {
+            std::io::ErrorKind::NotFound => anyhow!(
+                "Component {id} has `prewizen = true`, but Wizer is not installed. Install it with `cargo install wizer --all-features`"
+            ),
+            _ => anyhow!("Cannot run Wizer for component {id}: {err}"),
+        })?;
+    if !status.success() {
+        _ = tokio::fs::remove_file(&output_path).await;
+        bail!("Pre-initializing component {id} failed with status {status:?}");
+    }
+    tokio::fs::rename(&output_path, wasm_path)
+        .await
+        .with_context(|| format!("Failed to replace {}", quoted_path(wasm_path)))
+}
+
+/// Returns whether a Wasm module exports the function Wizer initializes it
+/// with. Fails for components, which Wizer cannot pre-initialize.
+fn has_init_func(wasm: &[u8]) -> Result<bool> {
+    for payload in Parser::new(0).parse_all(wasm) {
+        match payload? {
+            Payload::Version {
+                encoding: Encoding::Component,
+                ..
+            } => bail!("Wizer can only pre-initialize core modules, and this is a component"),
+            Payload::ExportSection(reader) => {
+                for export in reader {
+                    if export?.name == INIT_FUNC {
+                        return Ok(true);
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+    Ok(false)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn init_func_is_found_in_modules() {
+        // (module (func (export "wizer.initialize")))
+        let module = b"\0asm\x01\0\0\0\
+            \x01\x04\x01\x60\0\0\
+            \x03\x02\x01\0\
+            \x07\x14\x01\x10wizer.initialize\0\0\
+            \x0a\x04\x01\x02\0\x0b";
+        assert!(has_init_func(module).unwrap());
+        assert!(!has_init_func(b"\0asm\x01\0\0\0").unwrap());
+
+        let component = b"\0asm\x0d\0\x01\0";
+        assert!(has_init_func(component).is_err());
+    }
+}
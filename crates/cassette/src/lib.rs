@@ -0,0 +1,225 @@
+//! Record and replay of components' outbound interactions, for
+//! deterministic tests.
+//!
+//! A [`Cassette`] either records the interactions of a run, such as
+//! outbound HTTP requests and Redis and Postgres calls, to a file, or
+//! replays a file recorded earlier, answering each interaction with its
+//! recorded response without touching the network.
+//!
+//! A cassette file has one interaction per line, as a JSON object with the
+//! `kind` of interaction, its `request` and its `response`. A replayed
+//! request is answered by the first interaction not yet replayed with the
+//! same kind and an equal request, so repeated requests are answered in the
+//! order they were recorded. A request with no such interaction fails.
+//!
+//! Host components for outbound backends record and replay their calls with
+//! a [`Recorder`], describing the backend with a [`Backend`].
+
+mod outbound_http;
+mod recorder;
+
+use std::{
+    fs::File,
+    io::{BufRead, BufReader, Write},
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+
+use anyhow::{anyhow, Context, Result};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serde_json::Value;
+
+pub use recorder::{Backend, Recorder};
+
+/// The kind of outbound HTTP interactions.
+pub const HTTP: &str = "http";
+/// The kind of outbound Redis interactions.
+pub const REDIS: &str = "redis";
+/// The kind of outbound Postgres interactions.
+pub const POSTGRES: &str = "postgres";
+
+/// Records outbound interactions to a file, or replays them from one.
+pub struct Cassette {
+    path: PathBuf,
+    state: Mutex<State>,
+}
+
+enum State {
+    Recording(File),
+    Replaying {
+        interactions: Vec<Interaction>,
+        replayed: Vec<bool>,
+    },
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+struct Interaction {
+    kind: String,
+    request: Value,
+    response: Value,
+}
+
+impl Cassette {
+    /// Records interactions to the file at `path`, replacing any interactions
+    /// it already holds.
+    pub fn recording(path: impl Into<PathBuf>) -> Result<Self> {
+        let path = path.into();
+        let file = File::create(&path)
+            .with_context(|| format!("Failed to create cassette {}", path.display()))?;
+        Ok(Self {
+            path,
+            state: Mutex::new(State::Recording(file)),
+        })
+    }
+
+    /// Replays the interactions recorded in the file at `path`.
+    pub fn replaying(path: impl Into<PathBuf>) -> Result<Self> {
+        let path = path.into();
+        let file = File::open(&path)
+            .with_context(|| format!("Failed to open cassette {}", path.display()))?;
+        let mut interactions = vec![];
+        for (index, line) in BufReader::new(file).lines().enumerate() {
+            let line =
+                line.with_context(|| format!("Failed to read cassette {}", path.display()))?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            let interaction = serde_json::from_str(&line).with_context(|| {
+                format!(
+                    "Invalid interaction on line {} of cassette {}",
+                    index + 1,
+                    path.display()
+                )
+            })?;
+            interactions.push(interaction);
+        }
+        let replayed = vec![false; interactions.len()];
+        Ok(Self {
+            path,
+            state: Mutex::new(State::Replaying {
+                interactions,
+                replayed,
+            }),
+        })
+    }
+
+    /// The path of the cassette file.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Whether the cassette is replaying, rather than recording.
+    pub fn is_replaying(&self) -> bool {
+        matches!(*self.state.lock().unwrap(), State::Replaying { .. })
+    }
+
+    /// Returns the recorded response to a `kind` request, if the cassette is
+    /// replaying, or `None` if it is recording and the request should be
+    /// made. Fails if a replaying cassette has no response to the request.
+    pub fn replay<T: DeserializeOwned>(
+        &self,
+        kind: &str,
+        request: &impl Serialize,
+    ) -> Result<Option<T>> {
+        let mut state = self.state.lock().unwrap();
+        let State::Replaying {
+            interactions,
+            replayed,
+        } = &mut *state
+        else {
+            return Ok(None);
+        };
+        let request = to_json(request)?;
+        let index = interactions
+            .iter()
+            .zip(replayed.iter())
+            .position(|(interaction, &replayed)| {
+                !replayed && interaction.kind == kind && interaction.request == request
+            })
+            .ok_or_else(|| {
+                anyhow!(
+                    "No {kind} interaction recorded in cassette {} for request {request}",
+                    self.path.display()
+                )
+            })?;
+        replayed[index] = true;
+        let response =
+            serde_json::from_value(interactions[index].response.clone()).with_context(|| {
+                format!(
+                    "Invalid {kind} response recorded in cassette {}",
+                    self.path.display()
+                )
+            })?;
+        Ok(Some(response))
+    }
+
+    /// Records the response to a `kind` request, if the cassette is
+    /// recording.
+    pub fn record(
+        &self,
+        kind: &str,
+        request: &impl Serialize,
+        response: &impl Serialize,
+    ) -> Result<()> {
+        let mut state = self.state.lock().unwrap();
+        let State::Recording(file) = &mut *state else {
+            return Ok(());
+        };
+        let interaction = Interaction {
+            kind: kind.to_owned(),
+            request: to_json(request)?,
+            response: to_json(response)?,
+        };
+        let mut line = serde_json::to_vec(&interaction)?;
+        line.push(b'\n');
+        // Each interaction is written whole, so that the cassette is usable
+        // however the run ends
+        file.write_all(&line)
+            .and_then(|_| file.flush())
+            .with_context(|| format!("Failed to write cassette {}", self.path.display()))
+    }
+}
+
+// Converts a value to JSON as it would be read back from a cassette file,
+// e.g. with 32-bit floats widened as their text is, so that replayed
+// requests compare equal to recorded ones.
+fn to_json(value: &impl Serialize) -> Result<Value> {
+    let text = serde_json::to_string(value)?;
+    Ok(serde_json::from_str(&text)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn replays_recorded_interactions_in_order() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("cassette.jsonl");
+
+        let cassette = Cassette::recording(&path)?;
+        assert!(cassette.replay::<u32>(REDIS, &("incr", "count"))?.is_none());
+        cassette.record(REDIS, &("incr", "count"), &1)?;
+        cassette.record(REDIS, &("incr", "count"), &2)?;
+        cassette.record(POSTGRES, &("query", 1.1f32), &"row")?;
+        drop(cassette);
+
+        let cassette = Cassette::replaying(&path)?;
+        assert!(cassette.is_replaying());
+        assert_eq!(cassette.replay(REDIS, &("incr", "count"))?, Some(1));
+        assert_eq!(cassette.replay(REDIS, &("incr", "count"))?, Some(2));
+        assert_eq!(
+            cassette.replay(POSTGRES, &("query", 1.1f32))?,
+            Some("row".to_owned())
+        );
+        // Each interaction is replayed once
+        let err = cassette
+            .replay::<u32>(REDIS, &("incr", "count"))
+            .unwrap_err();
+        assert!(err.to_string().contains("No redis interaction"));
+        // Replaying cassettes record nothing
+        cassette.record(REDIS, &("get", "key"), &"value")?;
+        assert_eq!(std::fs::read_to_string(&path)?.lines().count(), 3);
+        Ok(())
+    }
+}
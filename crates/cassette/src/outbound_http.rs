@@ -0,0 +1,172 @@
+use anyhow::{Context, Result};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use bytes::Bytes;
+use serde::{Deserialize, Serialize};
+use spin_core::{async_trait, OutboundHttpInterceptor};
+
+use crate::{Cassette, HTTP};
+
+// A request as recorded. Its headers are left out, so that credentials
+// aren't written to cassettes, and so that requests still match when
+// headers such as dates change from run to run.
+#[derive(Serialize)]
+struct RecordedRequest {
+    method: String,
+    url: String,
+    body: RecordedBody,
+}
+
+#[derive(Deserialize, Serialize)]
+struct RecordedResponse {
+    status: u16,
+    headers: Vec<(String, String)>,
+    body: RecordedBody,
+}
+
+// Bodies are recorded as text when they are UTF-8, for readable cassettes.
+#[derive(Deserialize, Serialize)]
+#[serde(untagged)]
+enum RecordedBody {
+    Text(String),
+    Binary { base64: String },
+}
+
+impl RecordedBody {
+    fn new(body: &[u8]) -> Self {
+        match std::str::from_utf8(body) {
+            Ok(text) => Self::Text(text.to_owned()),
+            Err(_) => Self::Binary {
+                base64: STANDARD.encode(body),
+            },
+        }
+    }
+
+    fn into_bytes(self) -> Result<Bytes> {
+        Ok(match self {
+            Self::Text(text) => text.into(),
+            Self::Binary { base64 } => STANDARD
+                .decode(base64)
+                .context("Invalid base64 body recorded in cassette")?
+                .into(),
+        })
+    }
+}
+
+impl RecordedResponse {
+    fn new(response: &http::Response<Bytes>) -> Self {
+        Self {
+            status: response.status().as_u16(),
+            headers: response
+                .headers()
+                .iter()
+                .map(|(name, value)| {
+                    let value = String::from_utf8_lossy(value.as_bytes()).into_owned();
+                    (name.to_string(), value)
+                })
+                .collect(),
+            body: RecordedBody::new(response.body()),
+        }
+    }
+
+    fn into_response(self) -> Result<http::Response<Bytes>> {
+        let mut builder = http::Response::builder().status(self.status);
+        for (name, value) in self.headers {
+            builder = builder.header(name, value);
+        }
+        Ok(builder.body(self.body.into_bytes()?)?)
+    }
+}
+
+/// Replays a replaying cassette's responses to outbound HTTP requests, or
+/// sends requests over the network and records their responses.
+#[async_trait]
+impl OutboundHttpInterceptor for Cassette {
+    async fn send(&self, request: http::Request<Bytes>) -> Result<http::Response<Bytes>> {
+        let recorded = RecordedRequest {
+            method: request.method().to_string(),
+            url: request.uri().to_string(),
+            body: RecordedBody::new(request.body()),
+        };
+        if let Some(response) = self.replay::<RecordedResponse>(HTTP, &recorded)? {
+            return response.into_response();
+        }
+        let response = send(request).await?;
+        self.record(HTTP, &recorded, &RecordedResponse::new(&response))?;
+        Ok(response)
+    }
+}
+
+async fn send(request: http::Request<Bytes>) -> Result<http::Response<Bytes>> {
+    // Responses are recorded as the server sent them, without following
+    // redirects or decompressing bodies
+    let client = reqwest::Client::builder()
+        .redirect(reqwest::redirect::Policy::none())
+        .no_gzip()
+        .build()?;
+
+    let (parts, body) = request.into_parts();
+    let url = parts.uri.to_string();
+    let method = reqwest::Method::from_bytes(parts.method.as_str().as_bytes())?;
+    let mut builder = client.request(method, &url).body(body);
+    for (name, value) in &parts.headers {
+        builder = builder.header(name.as_str(), value.as_bytes());
+    }
+    let response = builder
+        .send()
+        .await
+        .with_context(|| format!("Failed to send request to {url}"))?;
+
+    let mut builder = http::Response::builder().status(response.status().as_u16());
+    for (name, value) in response.headers() {
+        builder = builder.header(name.as_str(), value.as_bytes());
+    }
+    let body = response
+        .bytes()
+        .await
+        .with_context(|| format!("Failed to read response from {url}"))?;
+    Ok(builder.body(body)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn replays_recorded_responses() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("cassette.jsonl");
+        let request = RecordedRequest {
+            method: "POST".into(),
+            url: "https://example.com/users".into(),
+            body: RecordedBody::new(b"{}"),
+        };
+        let response = http::Response::builder()
+            .status(201)
+            .header("content-type", "application/octet-stream")
+            .body(Bytes::from_static(&[0xff, 0x00]))?;
+        Cassette::recording(&path)?.record(HTTP, &request, &RecordedResponse::new(&response))?;
+
+        let cassette = Cassette::replaying(&path)?;
+        let replayed = cassette
+            .send(
+                http::Request::post("https://example.com/users")
+                    .header("authorization", "Bearer not-recorded")
+                    .body(Bytes::from_static(b"{}"))?,
+            )
+            .await?;
+        assert_eq!(replayed.status(), 201);
+        assert_eq!(
+            replayed.headers()["content-type"],
+            "application/octet-stream"
+        );
+        assert_eq!(replayed.body().as_ref(), &[0xff, 0x00]);
+
+        // A request with a different body wasn't recorded
+        let err = cassette
+            .send(http::Request::post("https://example.com/users").body(Bytes::from_static(b"[]"))?)
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("https://example.com/users"));
+        Ok(())
+    }
+}
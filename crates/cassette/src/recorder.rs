@@ -0,0 +1,159 @@
+use std::{future::Future, marker::PhantomData, sync::Arc};
+
+use anyhow::Result;
+use serde::{de::DeserializeOwned, Serialize};
+
+use crate::Cassette;
+
+/// An outbound backend whose calls are recorded by a [`Recorder`].
+pub trait Backend {
+    /// The kind of the backend's interactions, such as [`crate::REDIS`].
+    const KIND: &'static str;
+    /// The error returned by the backend's calls.
+    type Error: From<Self::RecordedError>;
+    /// The backend's errors as recorded in a cassette.
+    type RecordedError: Serialize + DeserializeOwned + for<'a> From<&'a Self::Error>;
+}
+
+/// Records the calls of an outbound backend host component to a cassette,
+/// or replays them from one. Does neither without a cassette.
+pub struct Recorder<B> {
+    cassette: Option<Arc<Cassette>>,
+    backend: PhantomData<fn() -> B>,
+}
+
+impl<B> Default for Recorder<B> {
+    fn default() -> Self {
+        Self::new(None)
+    }
+}
+
+impl<B> Recorder<B> {
+    pub fn new(cassette: Option<Arc<Cassette>>) -> Self {
+        Self {
+            cassette,
+            backend: PhantomData,
+        }
+    }
+}
+
+impl<B: Backend> Recorder<B> {
+    /// Returns the recorded result of a call, if a cassette is replaying.
+    pub fn replay<T: DeserializeOwned>(
+        &self,
+        request: &impl Serialize,
+    ) -> Result<Option<Result<T, B::Error>>> {
+        let Some(cassette) = &self.cassette else {
+            return Ok(None);
+        };
+        let recorded: Option<Result<T, B::RecordedError>> = cassette.replay(B::KIND, request)?;
+        Ok(recorded.map(|result| result.map_err(Into::into)))
+    }
+
+    /// Records the result of a call, if a cassette is recording.
+    pub fn record<T: Serialize>(
+        &self,
+        request: &impl Serialize,
+        result: Result<&T, &B::Error>,
+    ) -> Result<()> {
+        if let Some(cassette) = &self.cassette {
+            let result = result.map_err(B::RecordedError::from);
+            cassette.record(B::KIND, request, &result)?;
+        }
+        Ok(())
+    }
+
+    /// Opens a connection with `connect`, recording whether it opened, or
+    /// replays its opening. Replayed connections are `None`, as they are
+    /// only used for calls which were recorded, and so are replayed too.
+    pub async fn open<C>(
+        &self,
+        request: &impl Serialize,
+        connect: impl Future<Output = Result<C, B::Error>>,
+    ) -> Result<Result<Option<C>, B::Error>> {
+        if let Some(result) = self.replay::<()>(request)? {
+            return Ok(result.map(|()| None));
+        }
+        let connection = connect.await;
+        self.record(request, connection.as_ref().map(|_| &()))?;
+        Ok(connection.map(Some))
+    }
+}
+
+/// Replays the result of a call with a [`Recorder`], if its cassette is
+/// replaying, or makes the call and records its result. Returns the result
+/// from the enclosing function.
+#[macro_export]
+macro_rules! recorded {
+    ($recorder:expr, $request:expr, $call:expr) => {{
+        let request = $request;
+        if let Some(result) = $recorder.replay(&request)? {
+            return Ok(result);
+        }
+        let result = $call.await;
+        $recorder.record(&request, result.as_ref())?;
+        Ok(result)
+    }};
+}
+
+#[cfg(test)]
+mod tests {
+    use serde::Deserialize;
+
+    use super::*;
+    use crate::REDIS;
+
+    struct TestBackend;
+
+    #[derive(Debug, PartialEq)]
+    struct TestError(String);
+
+    #[derive(Deserialize, Serialize)]
+    struct RecordedTestError(String);
+
+    impl From<&TestError> for RecordedTestError {
+        fn from(error: &TestError) -> Self {
+            Self(error.0.clone())
+        }
+    }
+
+    impl From<RecordedTestError> for TestError {
+        fn from(error: RecordedTestError) -> Self {
+            Self(error.0)
+        }
+    }
+
+    impl Backend for TestBackend {
+        const KIND: &'static str = REDIS;
+        type Error = TestError;
+        type RecordedError = RecordedTestError;
+    }
+
+    #[tokio::test]
+    async fn replays_connections_and_errors() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("cassette.jsonl");
+
+        let recorder = Recorder::<TestBackend>::new(Some(Arc::new(Cassette::recording(&path)?)));
+        let opened = recorder.open(&"open", async { Ok(7) }).await?;
+        assert_eq!(opened, Ok(Some(7)));
+        recorder.record::<u32>(&"get", Err(&TestError("missing".into())))?;
+        drop(recorder);
+
+        let recorder = Recorder::<TestBackend>::new(Some(Arc::new(Cassette::replaying(&path)?)));
+        let opened = recorder
+            .open(&"open", async { Err(TestError("not replayed".into())) })
+            .await?;
+        assert_eq!(opened, Ok(None::<u32>));
+        assert_eq!(
+            recorder.replay::<u32>(&"get")?,
+            Some(Err(TestError("missing".into())))
+        );
+
+        // Without a cassette, nothing is replayed
+        assert!(Recorder::<TestBackend>::default()
+            .replay::<u32>(&"get")?
+            .is_none());
+        Ok(())
+    }
+}
@@ -2,7 +2,7 @@ use std::{
     any::{type_name, Any, TypeId},
     collections::HashMap,
     marker::PhantomData,
-    sync::Arc,
+    sync::{Arc, Mutex},
 };
 
 use anyhow::{bail, Result};
@@ -59,6 +59,16 @@ pub trait HostComponent: Send + Sync + 'static {
 
     /// Builds new host component runtime data for [`HostComponentsData`].
     fn build_data(&self) -> Self::Data;
+
+    /// Resets runtime data left by a finished store so that it can be reused
+    /// by another, as if newly built with [`HostComponent::build_data`].
+    ///
+    /// Returns `false`, the default, if the data can't be reset; it is then
+    /// dropped and built again when next needed.
+    fn reset_data(&self, data: &mut Self::Data) -> bool {
+        let _ = data;
+        false
+    }
 }
 
 impl<HC: HostComponent> HostComponent for Arc<HC> {
@@ -74,6 +84,10 @@ impl<HC: HostComponent> HostComponent for Arc<HC> {
     fn build_data(&self) -> Self::Data {
         (**self).build_data()
     }
+
+    fn reset_data(&self, data: &mut Self::Data) -> bool {
+        (**self).reset_data(data)
+    }
 }
 
 /// An opaque handle which can be passed to [`HostComponentsData`] to access
@@ -121,6 +135,7 @@ impl<HC: HostComponent> From<HostComponentDataHandle<Arc<HC>>> for HostComponent
 #[doc(hidden)]
 pub trait DynSafeHostComponent {
     fn build_data_box(&self) -> AnyData;
+    fn reset_data_box(&self, data: &mut AnyData) -> bool;
 }
 
 impl<T: HostComponent> DynSafeHostComponent for T
@@ -130,6 +145,13 @@ where
     fn build_data_box(&self) -> AnyData {
         Box::new(self.build_data())
     }
+
+    fn reset_data_box(&self, data: &mut AnyData) -> bool {
+        match data.downcast_mut() {
+            Some(data) => self.reset_data(data),
+            None => false,
+        }
+    }
 }
 
 type BoxHostComponent = Box<dyn DynSafeHostComponent + Send + Sync>;
@@ -174,13 +196,22 @@ impl HostComponentsBuilder {
         HostComponents {
             handles: self.handles,
             host_components: Arc::new(self.host_components),
+            pool: Default::default(),
         }
     }
 }
 
+/// The most finished stores' data kept for reuse; beyond this, data is
+/// dropped.
+const MAX_POOLED_DATA: usize = 256;
+
+type DataPool = Arc<Mutex<Vec<Vec<Option<AnyData>>>>>;
+
 pub struct HostComponents {
     handles: HashMap<TypeId, AnyHostComponentDataHandle>,
     host_components: Arc<Vec<BoxHostComponent>>,
+    // Data returned by dropped `HostComponentsData`s
+    pool: DataPool,
 }
 
 impl HostComponents {
@@ -188,14 +219,24 @@ impl HostComponents {
         Default::default()
     }
 
+    /// Returns data for a new store, reusing that of a finished store if
+    /// there is any.
     pub fn new_data(&self) -> HostComponentsData {
-        // Fill with `None`
-        let data = std::iter::repeat_with(Default::default)
-            .take(self.host_components.len())
-            .collect();
+        let pooled = self
+            .pool
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .pop();
+        let data = pooled.unwrap_or_else(|| {
+            // Fill with `None`
+            std::iter::repeat_with(Default::default)
+                .take(self.host_components.len())
+                .collect()
+        });
         HostComponentsData {
             data,
             host_components: self.host_components.clone(),
+            pool: self.pool.clone(),
         }
     }
 
@@ -209,9 +250,13 @@ impl HostComponents {
 type AnyData = Box<dyn Any + Send>;
 
 /// Holds a heterogenous set of [`HostComponent::Data`]s.
+///
+/// When dropped, the data is reset and returned to the [`HostComponents`] it
+/// came from, to be reused by a later store.
 pub struct HostComponentsData {
     data: Vec<Option<AnyData>>,
     host_components: Arc<Vec<BoxHostComponent>>,
+    pool: DataPool,
 }
 
 impl HostComponentsData {
@@ -250,6 +295,27 @@ impl HostComponentsData {
     }
 }
 
+impl Drop for HostComponentsData {
+    fn drop(&mut self) {
+        // Reset outside the lock, as dropping data may take a while, e.g. to
+        // close connections
+        for (slot, host_component) in self.data.iter_mut().zip(self.host_components.iter()) {
+            if let Some(data) = slot {
+                if !host_component.reset_data_box(data) {
+                    *slot = None;
+                }
+            }
+        }
+        let mut pool = self
+            .pool
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        if pool.len() < MAX_POOLED_DATA {
+            pool.push(std::mem::take(&mut self.data));
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -271,6 +337,28 @@ mod tests {
         }
     }
 
+    struct ResettingHC;
+
+    impl HostComponent for ResettingHC {
+        type Data = Vec<u8>;
+
+        fn add_to_linker<T: Send>(
+            _linker: &mut Linker<T>,
+            _get: impl Fn(&mut Data<T>) -> &mut Self::Data + Send + Sync + Copy + 'static,
+        ) -> Result<()> {
+            Ok(())
+        }
+
+        fn build_data(&self) -> Self::Data {
+            Vec::new()
+        }
+
+        fn reset_data(&self, data: &mut Self::Data) -> bool {
+            data.clear();
+            true
+        }
+    }
+
     #[test]
     fn host_components_data() {
         let engine = wasmtime::Engine::default();
@@ -302,4 +390,45 @@ mod tests {
         let mut hc_data = host_components.new_data();
         assert_eq!(hc_data.get_or_insert(handle), &0);
     }
+
+    #[test]
+    fn dropped_data_is_reset_and_reused() {
+        let engine = wasmtime::Engine::default();
+        let mut linker: crate::Linker<()> = crate::Linker::new(&engine);
+
+        let mut builder = HostComponents::builder();
+        let handle = builder.add_host_component(&mut linker, TestHC).unwrap();
+        let resetting_handle = builder
+            .add_host_component(&mut linker, ResettingHC)
+            .unwrap();
+        let host_components = builder.build();
+
+        let mut hc_data = host_components.new_data();
+        hc_data.set(handle, 1);
+        hc_data.get_or_insert(resetting_handle).extend([1, 2, 3]);
+        let reused_ptr = hc_data.get_or_insert(resetting_handle).as_ptr();
+        drop(hc_data);
+
+        let mut hc_data = host_components.new_data();
+        // Data which can't be reset is built again
+        assert_eq!(hc_data.get_or_insert(handle), &0);
+        let reused = hc_data.get_or_insert(resetting_handle);
+        assert!(reused.is_empty());
+        assert_eq!(reused.as_ptr(), reused_ptr);
+    }
+
+    #[test]
+    fn data_vectors_are_reused() {
+        let engine = wasmtime::Engine::default();
+        let mut linker: crate::Linker<()> = crate::Linker::new(&engine);
+
+        let mut builder = HostComponents::builder();
+        builder.add_host_component(&mut linker, TestHC).unwrap();
+        let host_components = builder.build();
+
+        let hc_data = host_components.new_data();
+        let reused_ptr = hc_data.data.as_ptr();
+        drop(hc_data);
+        assert_eq!(host_components.new_data().data.as_ptr(), reused_ptr);
+    }
 }
@@ -10,21 +10,23 @@
 mod host_component;
 mod io;
 mod limits;
+mod outbound_http;
 mod preview1;
 mod store;
+mod ticker;
 pub mod wasi_2023_10_18;
 pub mod wasi_2023_11_10;
 
 use std::{path::PathBuf, time::Duration};
 
 use anyhow::Result;
-use crossbeam_channel::Sender;
 use tracing::instrument;
 use wasmtime::{InstanceAllocationStrategy, PoolingAllocationConfig};
 use wasmtime_wasi::preview2::ResourceTable;
-use wasmtime_wasi_http::types::{default_send_request, WasiHttpCtx, WasiHttpView};
+use wasmtime_wasi_http::types::{WasiHttpCtx, WasiHttpView};
 
 use self::host_component::{HostComponents, HostComponentsBuilder};
+use self::ticker::{Deadlines, EpochTicker};
 
 pub use async_trait::async_trait;
 pub use wasmtime::{
@@ -32,17 +34,21 @@ pub use wasmtime::{
     component::{Component, Instance},
     Instance as ModuleInstance, Module, Trap,
 };
-pub use wasmtime_wasi::preview2::I32Exit;
+pub use wasmtime_wasi::preview2::{HostMonotonicClock, HostWallClock, I32Exit};
 
 pub use host_component::{
     AnyHostComponentDataHandle, HostComponent, HostComponentDataHandle, HostComponentsData,
 };
 pub use io::OutputBuffer;
+pub use outbound_http::{send_outbound_request, HttpTrailers, OutboundHttpInterceptor};
 pub use store::{Store, StoreBuilder, Wasi, WasiVersion};
 
 /// The default [`EngineBuilder::epoch_tick_interval`].
 pub const DEFAULT_EPOCH_TICK_INTERVAL: Duration = Duration::from_millis(10);
 
+/// A suggested [`EngineBuilder::idle_epoch_tick_interval`].
+pub const DEFAULT_IDLE_EPOCH_TICK_INTERVAL: Duration = Duration::from_secs(1);
+
 const MB: u64 = 1 << 20;
 const GB: u64 = 1 << 30;
 const WASM_PAGE_SIZE: u64 = 64 * 1024;
@@ -50,8 +56,14 @@ const WASM_PAGE_SIZE: u64 = 64 * 1024;
 /// Global configuration for `EngineBuilder`.
 ///
 /// This is currently only used for advanced (undocumented) use cases.
+#[derive(Clone)]
 pub struct Config {
     inner: wasmtime::Config,
+    memory_init_cow: bool,
+    // The pooling allocator's configuration, if enabled
+    pooling: Option<PoolingAllocationConfig>,
+    // The Wasmtime engine shared by engines built with this config, if any
+    shared_engine: Option<wasmtime::Engine>,
 }
 
 impl Config {
@@ -77,12 +89,90 @@ impl Config {
         Ok(())
     }
 
+    /// Write a perf map naming the functions of JIT-compiled components, so
+    /// that profilers can symbolize frames in Wasm code.
+    pub fn enable_perf_map(&mut self) -> &mut Self {
+        self.inner.profiler(wasmtime::ProfilingStrategy::PerfMap);
+        self
+    }
+
     /// Disable the pooling instance allocator.
     pub fn disable_pooling(&mut self) -> &mut Self {
         self.inner
             .allocation_strategy(wasmtime::InstanceAllocationStrategy::OnDemand);
+        self.pooling = None;
+        self
+    }
+
+    /// Set how many bytes of each linear memory the pooling allocator keeps
+    /// resident when an instance is deallocated, to be reset in place
+    /// rather than faulted in again by the next instance in its slot.
+    ///
+    /// Has no effect if pooling is disabled.
+    pub fn pooling_memory_keep_resident(&mut self, bytes: usize) -> &mut Self {
+        self.update_pooling(|pooling| {
+            pooling.linear_memory_keep_resident(bytes);
+        })
+    }
+
+    /// Set the most unused slots the pooling allocator keeps "warm" for the
+    /// components which last used them. A component reusing its own slot
+    /// reuses the memory it already touched, on the NUMA node it was touched
+    /// from, rather than memory last touched by another component.
+    ///
+    /// Has no effect if pooling is disabled.
+    pub fn pooling_max_unused_warm_slots(&mut self, slots: u32) -> &mut Self {
+        self.update_pooling(|pooling| {
+            pooling.max_unused_warm_slots(slots);
+        })
+    }
+
+    fn update_pooling(&mut self, f: impl FnOnce(&mut PoolingAllocationConfig)) -> &mut Self {
+        if let Some(pooling) = &mut self.pooling {
+            f(pooling);
+            self.inner
+                .allocation_strategy(InstanceAllocationStrategy::Pooling(pooling.clone()));
+        }
         self
     }
+
+    /// Enable or disable copy-on-write memory initialization. When enabled,
+    /// as it is by default, the initial contents of each linear memory are
+    /// mapped from an image made when the component is compiled, rather than
+    /// copied in at each instantiation, so that instantiating a component
+    /// with a large initialized memory costs a mapping rather than a copy.
+    pub fn memory_init_cow(&mut self, enable: bool) -> &mut Self {
+        self.inner.memory_init_cow(enable);
+        self.memory_init_cow = enable;
+        self
+    }
+
+    /// Whether linear memories are initialized copy-on-write.
+    pub fn is_memory_init_cow(&self) -> bool {
+        self.memory_init_cow
+    }
+
+    /// Set the size, in bytes, of the guard region reserved after each
+    /// statically-allocated linear memory. A large guard region lets bounds
+    /// checks on memory accesses be elided, at the cost of address space;
+    /// it may not be smaller than Wasmtime's dynamic memory guard (64 KiB).
+    pub fn static_memory_guard_size(&mut self, bytes: u64) -> &mut Self {
+        self.inner.static_memory_guard_size(bytes);
+        self
+    }
+
+    /// Create the Wasmtime engine now, and have every [`Engine`] built with
+    /// this config, or with a clone of it, share it rather than creating its
+    /// own. Engines sharing a Wasmtime engine share its pooling allocator's
+    /// slots and its compiled code, e.g. to run several applications in one
+    /// process without reserving memory for each.
+    ///
+    /// Changes made to this config after sharing have no effect on the
+    /// shared engine.
+    pub fn share_engine(&mut self) -> Result<&mut Self> {
+        self.shared_engine = Some(wasmtime::Engine::new(&self.inner)?);
+        Ok(self)
+    }
 }
 
 impl Default for Config {
@@ -91,6 +181,9 @@ impl Default for Config {
         inner.async_support(true);
         inner.epoch_interruption(true);
         inner.wasm_component_model(true);
+        // Map initialized memory from images rather than copying it in at
+        // each instantiation; see `Config::memory_init_cow`.
+        inner.memory_init_cow(true);
 
         // By default enable the pooling instance allocator in Wasmtime. This
         // drastically reduces syscall/kernel overhead for wasm execution,
@@ -127,9 +220,14 @@ impl Default for Config {
             // These numbers are completely arbitrary at something above 0.
             .linear_memory_keep_resident((2 * MB) as usize)
             .table_keep_resident((MB / 2) as usize);
-        inner.allocation_strategy(InstanceAllocationStrategy::Pooling(pooling_config));
+        inner.allocation_strategy(InstanceAllocationStrategy::Pooling(pooling_config.clone()));
 
-        return Self { inner };
+        return Self {
+            inner,
+            memory_init_cow: true,
+            pooling: Some(pooling_config),
+            shared_engine: None,
+        };
 
         fn env(name: &str, default: u32) -> u32 {
             match std::env::var(name) {
@@ -149,6 +247,7 @@ pub struct Data<T> {
     host_components_data: HostComponentsData,
     store_limits: limits::StoreLimitsAsync,
     table: ResourceTable,
+    outbound_http_interceptor: Option<std::sync::Arc<dyn OutboundHttpInterceptor>>,
 }
 
 impl<T> Data<T> {
@@ -233,7 +332,7 @@ impl OutboundWasiHttpHandler for () {
     where
         Self: Sized,
     {
-        default_send_request(data, request)
+        send_outbound_request(data, request)
     }
 }
 
@@ -252,12 +351,16 @@ pub struct EngineBuilder<T> {
     module_linker: ModuleLinker<T>,
     host_components_builder: HostComponentsBuilder,
     epoch_tick_interval: Duration,
+    idle_epoch_tick_interval: Option<Duration>,
     epoch_ticker_thread: bool,
 }
 
 impl<T: Send + Sync + OutboundWasiHttpHandler> EngineBuilder<T> {
     fn new(config: &Config) -> Result<Self> {
-        let engine = wasmtime::Engine::new(&config.inner)?;
+        let engine = match &config.shared_engine {
+            Some(engine) => engine.clone(),
+            None => wasmtime::Engine::new(&config.inner)?,
+        };
         let linker: Linker<T> = Linker::new(&engine);
         let mut module_linker = ModuleLinker::new(&engine);
 
@@ -272,6 +375,7 @@ impl<T: Send + Sync + OutboundWasiHttpHandler> EngineBuilder<T> {
             module_linker,
             host_components_builder: HostComponents::builder(),
             epoch_tick_interval: DEFAULT_EPOCH_TICK_INTERVAL,
+            idle_epoch_tick_interval: None,
             epoch_ticker_thread: true,
         })
     }
@@ -321,8 +425,20 @@ impl<T: Send + Sync> EngineBuilder<T> {
         self.epoch_tick_interval = interval;
     }
 
-    /// Configures whether the epoch ticker thread will be spawned when this
-    /// [`Engine`] is built.
+    /// Sets a longer epoch tick interval for the built [`Engine`] to use
+    /// while none of its stores has a deadline set with
+    /// [`Store::set_deadline`], to save waking the epoch ticker thread when
+    /// there is nothing to interrupt. Ticks return to `epoch_tick_interval`
+    /// as soon as a deadline is set.
+    ///
+    /// Disabled by default, as deadlines set directly on the inner
+    /// [`wasmtime::Store`] are not seen.
+    pub fn idle_epoch_tick_interval(&mut self, interval: Option<Duration>) {
+        self.idle_epoch_tick_interval = interval;
+    }
+
+    /// Configures whether the built [`Engine`] is ticked by the epoch ticker
+    /// thread, which is shared by all engines in the process.
     ///
     /// Enabled by default; if disabled, the user must arrange to call
     /// `engine.as_ref().increment_epoch()` every `epoch_tick_interval` or
@@ -331,27 +447,17 @@ impl<T: Send + Sync> EngineBuilder<T> {
         self.epoch_ticker_thread = enable;
     }
 
-    fn maybe_spawn_epoch_ticker(&self) -> Option<Sender<()>> {
-        if !self.epoch_ticker_thread {
-            return None;
-        }
-        let engine = self.engine.clone();
-        let interval = self.epoch_tick_interval;
-        let (send, recv) = crossbeam_channel::bounded(0);
-        std::thread::spawn(move || loop {
-            match recv.recv_timeout(interval) {
-                Err(crossbeam_channel::RecvTimeoutError::Timeout) => (),
-                Err(crossbeam_channel::RecvTimeoutError::Disconnected) => break,
-                res => panic!("unexpected epoch_ticker_signal: {res:?}"),
-            }
-            engine.increment_epoch();
-        });
-        Some(send)
-    }
-
     /// Builds an [`Engine`] from this builder.
     pub fn build(self) -> Engine<T> {
-        let epoch_ticker_signal = self.maybe_spawn_epoch_ticker();
+        let deadlines = Deadlines::default();
+        let epoch_ticker = self.epoch_ticker_thread.then(|| {
+            EpochTicker::register(
+                &self.engine,
+                self.epoch_tick_interval,
+                self.idle_epoch_tick_interval,
+                &deadlines,
+            )
+        });
 
         let host_components = self.host_components_builder.build();
 
@@ -361,7 +467,8 @@ impl<T: Send + Sync> EngineBuilder<T> {
             module_linker: self.module_linker,
             host_components,
             epoch_tick_interval: self.epoch_tick_interval,
-            _epoch_ticker_signal: epoch_ticker_signal,
+            deadlines,
+            _epoch_ticker: epoch_ticker,
         }
     }
 }
@@ -374,8 +481,10 @@ pub struct Engine<T> {
     module_linker: ModuleLinker<T>,
     host_components: HostComponents,
     epoch_tick_interval: Duration,
-    // Matching receiver closes on drop
-    _epoch_ticker_signal: Option<Sender<()>>,
+    // Stores with deadlines, for an idle epoch tick interval
+    deadlines: Deadlines,
+    // Stops ticking the engine on drop
+    _epoch_ticker: Option<EpochTicker>,
 }
 
 impl<T: OutboundWasiHttpHandler + Send + Sync> Engine<T> {
@@ -389,6 +498,7 @@ impl<T: OutboundWasiHttpHandler + Send + Sync> Engine<T> {
         StoreBuilder::new(
             self.inner.clone(),
             self.epoch_tick_interval,
+            self.deadlines.clone(),
             &self.host_components,
             wasi_version,
         )
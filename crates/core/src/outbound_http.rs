@@ -0,0 +1,161 @@
+use std::sync::Arc;
+
+use anyhow::{anyhow, Result};
+use bytes::Bytes;
+use http::HeaderMap;
+use http_body::Frame;
+use http_body_util::{combinators::BoxBody, BodyExt, Full, StreamBody};
+use wasmtime::component::Resource;
+use wasmtime_wasi_http::{
+    bindings::http::types::ErrorCode,
+    body::{HyperIncomingBody, HyperOutgoingBody},
+    types::{
+        default_send_request, HostFutureIncomingResponse, IncomingResponseInternal, OutgoingRequest,
+    },
+    WasiHttpView,
+};
+
+use crate::{async_trait, Data, OutboundWasiHttpHandler};
+
+#[cfg(doc)]
+use crate::StoreBuilder;
+
+/// Handles a store's outbound wasi-http requests in place of the network,
+/// such as to return canned responses in tests.
+///
+/// Set with [`StoreBuilder::outbound_http_interceptor`]. Requests reach the
+/// interceptor only once the trigger has allowed them, so it sees the same
+/// requests the network would.
+#[async_trait]
+pub trait OutboundHttpInterceptor: Send + Sync {
+    /// Handle a request, returning its response. An error fails the
+    /// request as a network error would.
+    async fn send(&self, request: http::Request<Bytes>) -> Result<http::Response<Bytes>>;
+}
+
+/// The trailers of a request or response passed through an
+/// [`OutboundHttpInterceptor`], held in its extensions.
+///
+/// A request's trailers are set by the guest; trailers set on a response are
+/// sent on to the guest after its body.
+#[derive(Clone, Debug, Default)]
+pub struct HttpTrailers(pub HeaderMap);
+
+/// Sends an outbound wasi-http request over the network, or to the store's
+/// [`OutboundHttpInterceptor`] if it has one. [`OutboundWasiHttpHandler`]s
+/// should call this rather than [`default_send_request`].
+pub fn send_outbound_request<T: Send + OutboundWasiHttpHandler>(
+    data: &mut Data<T>,
+    request: OutgoingRequest,
+) -> wasmtime::Result<Resource<HostFutureIncomingResponse>> {
+    let Some(interceptor) = data.outbound_http_interceptor.clone() else {
+        return default_send_request(data, request);
+    };
+
+    let between_bytes_timeout = request.between_bytes_timeout;
+    // As for chained requests, the response has no connection to drive, but
+    // a worker is still required.
+    let worker = Arc::new(wasmtime_wasi::preview2::spawn(async {}));
+
+    let resp_fut = async move {
+        match intercept(interceptor.as_ref(), request.request).await {
+            Ok(resp) => Ok(Ok(IncomingResponseInternal {
+                resp,
+                between_bytes_timeout,
+                worker,
+            })),
+            Err(e) => Err(wasmtime::Error::msg(e)),
+        }
+    };
+
+    let handle = wasmtime_wasi::preview2::spawn(resp_fut);
+    Ok(data.table().push(HostFutureIncomingResponse::new(handle))?)
+}
+
+async fn intercept(
+    interceptor: &dyn OutboundHttpInterceptor,
+    request: http::Request<HyperOutgoingBody>,
+) -> Result<http::Response<HyperIncomingBody>> {
+    let (mut parts, body) = request.into_parts();
+    let collected = body
+        .collect()
+        .await
+        .map_err(|e| anyhow!("failed to read outbound request body: {e:?}"))?;
+    if let Some(trailers) = collected.trailers() {
+        parts.extensions.insert(HttpTrailers(trailers.clone()));
+    }
+    let response = interceptor
+        .send(http::Request::from_parts(parts, collected.to_bytes()))
+        .await?;
+
+    let (mut parts, body) = response.into_parts();
+    let body = match parts.extensions.remove::<HttpTrailers>() {
+        Some(HttpTrailers(trailers)) => {
+            let frames = [
+                Ok::<_, ErrorCode>(Frame::data(body)),
+                Ok(Frame::trailers(trailers)),
+            ];
+            BoxBody::new(StreamBody::new(futures::stream::iter(frames)))
+        }
+        None => Full::new(body).map_err(|_| unreachable!()).boxed(),
+    };
+    Ok(http::Response::from_parts(parts, body))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use super::*;
+
+    // Echoes the request's trailers back on its response.
+    #[derive(Default)]
+    struct Echo {
+        body: Mutex<Option<Bytes>>,
+    }
+
+    #[async_trait]
+    impl OutboundHttpInterceptor for Echo {
+        async fn send(&self, request: http::Request<Bytes>) -> Result<http::Response<Bytes>> {
+            let (parts, body) = request.into_parts();
+            *self.body.lock().unwrap() = Some(body);
+            let mut response = http::Response::new(Bytes::from_static(b"response"));
+            if let Some(trailers) = parts.extensions.get::<HttpTrailers>() {
+                response.extensions_mut().insert(trailers.clone());
+            }
+            Ok(response)
+        }
+    }
+
+    #[tokio::test]
+    async fn trailers_pass_through_interceptor() {
+        let mut trailers = HeaderMap::new();
+        trailers.insert("grpc-status", "0".parse().unwrap());
+        let frames = [
+            Ok::<_, ErrorCode>(Frame::data(Bytes::from_static(b"request"))),
+            Ok(Frame::trailers(trailers)),
+        ];
+        let request =
+            http::Request::new(BoxBody::new(StreamBody::new(futures::stream::iter(frames))));
+
+        let echo = Echo::default();
+        let response = intercept(&echo, request).await.unwrap();
+        assert_eq!(echo.body.lock().unwrap().as_deref(), Some(&b"request"[..]));
+
+        let collected = response.into_body().collect().await.unwrap();
+        assert_eq!(collected.trailers().unwrap()["grpc-status"], "0");
+        assert_eq!(collected.to_bytes(), "response");
+    }
+
+    #[tokio::test]
+    async fn responses_without_trailers_have_none() {
+        let request = http::Request::new(
+            Full::new(Bytes::from_static(b"request"))
+                .map_err(|_| unreachable!())
+                .boxed(),
+        );
+        let response = intercept(&Echo::default(), request).await.unwrap();
+        let collected = response.into_body().collect().await.unwrap();
+        assert!(collected.trailers().is_none());
+    }
+}
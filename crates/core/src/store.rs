@@ -24,7 +24,10 @@ use crate::{
     host_component::{HostComponents, HostComponentsData},
     io::OutputBuffer,
     limits::StoreLimitsAsync,
-    preview1, Data,
+    outbound_http::OutboundHttpInterceptor,
+    preview1,
+    ticker::{ArmedDeadline, Deadlines},
+    Data,
 };
 
 #[cfg(doc)]
@@ -74,6 +77,9 @@ pub enum WasiVersion {
 pub struct Store<T> {
     inner: wasmtime::Store<Data<T>>,
     epoch_tick_interval: Duration,
+    deadlines: Deadlines,
+    // Set once a deadline is, until the store is dropped
+    deadline: Option<ArmedDeadline>,
 }
 
 impl<T> Store<T> {
@@ -101,6 +107,9 @@ impl<T> Store<T> {
             ticks + 1 // Add one to allow for current partially-completed tick
         };
         self.inner.set_epoch_deadline(ticks);
+        if self.deadline.is_none() {
+            self.deadline = Some(self.deadlines.arm());
+        }
     }
 }
 
@@ -136,10 +145,12 @@ impl<T> wasmtime::AsContextMut for Store<T> {
 pub struct StoreBuilder {
     engine: wasmtime::Engine,
     epoch_tick_interval: Duration,
+    deadlines: Deadlines,
     wasi: std::result::Result<WasiCtxBuilder, String>,
     host_components_data: HostComponentsData,
     store_limits: StoreLimitsAsync,
     net_pool: Pool,
+    outbound_http_interceptor: Option<Arc<dyn OutboundHttpInterceptor>>,
 }
 
 impl StoreBuilder {
@@ -147,16 +158,19 @@ impl StoreBuilder {
     pub(crate) fn new(
         engine: wasmtime::Engine,
         epoch_tick_interval: Duration,
+        deadlines: Deadlines,
         host_components: &HostComponents,
         wasi: WasiVersion,
     ) -> Self {
         Self {
             engine,
             epoch_tick_interval,
+            deadlines,
             wasi: Ok(wasi.into()),
             host_components_data: host_components.new_data(),
             store_limits: StoreLimitsAsync::default(),
             net_pool: Pool::default(),
+            outbound_http_interceptor: None,
         }
     }
 
@@ -399,6 +413,32 @@ impl StoreBuilder {
         })
     }
 
+    /// Sets the clocks the guest reads, such as to freeze time in tests.
+    ///
+    /// Only supported with WASI Preview 2.
+    pub fn clocks(
+        &mut self,
+        wall_clock: impl wasi_preview2::HostWallClock + 'static,
+        monotonic_clock: impl wasi_preview2::HostMonotonicClock + 'static,
+    ) -> Result<()> {
+        self.try_with_wasi(|wasi| match wasi {
+            WasiCtxBuilder::Preview1(_) => Err(anyhow!(
+                "Setting clocks is only supported with WASI Preview 2"
+            )),
+            WasiCtxBuilder::Preview2(ctx) => {
+                ctx.wall_clock(wall_clock);
+                ctx.monotonic_clock(monotonic_clock);
+                Ok(())
+            }
+        })
+    }
+
+    /// Sends the guest's outbound wasi-http requests to `interceptor` instead
+    /// of the network.
+    pub fn outbound_http_interceptor(&mut self, interceptor: Arc<dyn OutboundHttpInterceptor>) {
+        self.outbound_http_interceptor = Some(interceptor);
+    }
+
     /// Returns a mutable reference to the built
     pub fn host_components_data(&mut self) -> &mut HostComponentsData {
         &mut self.host_components_data
@@ -425,7 +465,12 @@ impl StoreBuilder {
                 wasi,
                 host_components_data: self.host_components_data,
                 store_limits: self.store_limits,
+                // Unlike host component data, the resource table is not
+                // reused: wasmtime's `ResourceTable` has no way to be emptied,
+                // and a finished store may leave resources in it. A new table
+                // allocates nothing until its first resource is pushed.
                 table: wasi_preview2::ResourceTable::new(),
+                outbound_http_interceptor: self.outbound_http_interceptor,
             },
         );
 
@@ -440,6 +485,8 @@ impl StoreBuilder {
         Ok(Store {
             inner,
             epoch_tick_interval: self.epoch_tick_interval,
+            deadlines: self.deadlines,
+            deadline: None,
         })
     }
 
@@ -0,0 +1,250 @@
+//! The epoch ticker, which advances engines' epochs so that
+//! [`Store::set_deadline`](crate::Store::set_deadline) deadlines are enforced.
+//!
+//! A single thread ticks every engine in the process, sleeping until the next
+//! engine is due a tick, rather than each engine waking a thread of its own.
+//! An engine with an idle tick interval is ticked at that interval while
+//! none of its stores has a deadline set, and at its usual interval as soon
+//! as one does. Engines built on a shared Wasmtime engine are ticked
+//! together, so that sharing an engine doesn't tick it more often.
+
+use std::sync::{
+    atomic::{AtomicUsize, Ordering},
+    Arc, Condvar, Mutex, MutexGuard, OnceLock,
+};
+use std::time::{Duration, Instant};
+
+/// The ticker thread's shared state, which is created with the thread when
+/// the first engine registers.
+static TICKER: OnceLock<Ticker> = OnceLock::new();
+
+#[derive(Default)]
+struct Ticker {
+    engines: Mutex<Vec<TickedEngine>>,
+    // Notified when engines are registered, or when an idle engine's
+    // deadlines are armed
+    changed: Condvar,
+}
+
+struct TickedEngine {
+    id: usize,
+    engine: wasmtime::Engine,
+    interval: Duration,
+    idle_interval: Option<Duration>,
+    deadlines: Deadlines,
+    last_tick: Instant,
+}
+
+impl TickedEngine {
+    fn next_tick(&self) -> Instant {
+        let interval = match self.idle_interval {
+            Some(idle_interval) if !self.deadlines.any_armed() => idle_interval,
+            _ => self.interval,
+        };
+        self.last_tick + interval
+    }
+
+    fn shares_any(&self, engines: &[wasmtime::Engine]) -> bool {
+        engines
+            .iter()
+            .any(|engine| wasmtime::Engine::same(engine, &self.engine))
+    }
+}
+
+impl Ticker {
+    fn get() -> &'static Ticker {
+        let mut created = false;
+        let ticker = TICKER.get_or_init(|| {
+            created = true;
+            Ticker::default()
+        });
+        if created {
+            std::thread::Builder::new()
+                .name("spin-epoch-ticker".into())
+                .spawn(|| ticker.run())
+                .expect("failed to spawn epoch ticker thread");
+        }
+        ticker
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Vec<TickedEngine>> {
+        self.engines
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    fn run(&self) {
+        let mut engines = self.lock();
+        loop {
+            tick_due(&mut engines, Instant::now());
+            engines = match engines.iter().map(TickedEngine::next_tick).min() {
+                Some(next_tick) => {
+                    let timeout = next_tick.saturating_duration_since(Instant::now());
+                    self.changed.wait_timeout(engines, timeout).unwrap().0
+                }
+                None => self.changed.wait(engines).unwrap(),
+            };
+        }
+    }
+}
+
+/// Ticks the engines which are due a tick at `now`. Each Wasmtime engine is
+/// ticked at most once, and every registration sharing it counts the tick.
+fn tick_due(engines: &mut [TickedEngine], now: Instant) {
+    let mut ticked: Vec<wasmtime::Engine> = vec![];
+    for registration in engines.iter() {
+        if registration.next_tick() <= now && !registration.shares_any(&ticked) {
+            registration.engine.increment_epoch();
+            ticked.push(registration.engine.clone());
+        }
+    }
+    for registration in engines.iter_mut() {
+        if registration.shares_any(&ticked) {
+            registration.last_tick = now;
+        }
+    }
+}
+
+/// An engine's registration with the epoch ticker, which stops ticking it
+/// when dropped.
+pub(crate) struct EpochTicker {
+    id: usize,
+}
+
+impl EpochTicker {
+    /// Starts ticking `engine`'s epoch every `interval`, or every
+    /// `idle_interval`, if given, while `deadlines` has none armed.
+    pub fn register(
+        engine: &wasmtime::Engine,
+        interval: Duration,
+        idle_interval: Option<Duration>,
+        deadlines: &Deadlines,
+    ) -> Self {
+        static NEXT_ID: AtomicUsize = AtomicUsize::new(0);
+        let id = NEXT_ID.fetch_add(1, Ordering::Relaxed);
+        let ticker = Ticker::get();
+        ticker.lock().push(TickedEngine {
+            id,
+            engine: engine.clone(),
+            interval,
+            idle_interval,
+            deadlines: deadlines.clone(),
+            last_tick: Instant::now(),
+        });
+        ticker.changed.notify_one();
+        Self { id }
+    }
+}
+
+impl Drop for EpochTicker {
+    fn drop(&mut self) {
+        if let Some(ticker) = TICKER.get() {
+            ticker.lock().retain(|ticked| ticked.id != self.id);
+        }
+    }
+}
+
+/// The number of an engine's stores which have a deadline set.
+#[derive(Clone, Default)]
+pub(crate) struct Deadlines(Arc<AtomicUsize>);
+
+impl Deadlines {
+    /// Counts a deadline until the returned guard is dropped.
+    pub fn arm(&self) -> ArmedDeadline {
+        if self.0.fetch_add(1, Ordering::AcqRel) == 0 {
+            // An idle engine's next tick is now sooner. Notifying under the
+            // lock ensures the ticker isn't between checking and waiting.
+            if let Some(ticker) = TICKER.get() {
+                let _engines = ticker.lock();
+                ticker.changed.notify_one();
+            }
+        }
+        ArmedDeadline(self.clone())
+    }
+
+    fn disarm(&self) {
+        self.0.fetch_sub(1, Ordering::AcqRel);
+    }
+
+    fn any_armed(&self) -> bool {
+        self.0.load(Ordering::Acquire) > 0
+    }
+}
+
+/// A deadline counted by [`Deadlines`].
+pub(crate) struct ArmedDeadline(Deadlines);
+
+impl Drop for ArmedDeadline {
+    fn drop(&mut self) {
+        self.0.disarm();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn engines_are_unregistered_when_dropped() {
+        let registered = |id| TICKER.get().unwrap().lock().iter().any(|t| t.id == id);
+        let engine = wasmtime::Engine::default();
+        let deadlines = Deadlines::default();
+        let ticker = EpochTicker::register(&engine, Duration::from_millis(1), None, &deadlines);
+        let id = ticker.id;
+        assert!(registered(id));
+        drop(ticker);
+        assert!(!registered(id));
+    }
+
+    #[test]
+    fn idle_engines_tick_at_the_idle_interval() {
+        let deadlines = Deadlines::default();
+        let ticked = TickedEngine {
+            id: 0,
+            engine: wasmtime::Engine::default(),
+            interval: Duration::from_millis(10),
+            idle_interval: Some(Duration::from_secs(1)),
+            deadlines: deadlines.clone(),
+            last_tick: Instant::now(),
+        };
+        assert_eq!(
+            ticked.next_tick(),
+            ticked.last_tick + Duration::from_secs(1)
+        );
+        let armed = deadlines.arm();
+        assert_eq!(
+            ticked.next_tick(),
+            ticked.last_tick + Duration::from_millis(10)
+        );
+        drop(armed);
+        assert_eq!(
+            ticked.next_tick(),
+            ticked.last_tick + Duration::from_secs(1)
+        );
+    }
+
+    #[test]
+    fn shared_engines_tick_together() {
+        let shared = wasmtime::Engine::default();
+        let started = Instant::now();
+        let registration = |engine: &wasmtime::Engine, interval| TickedEngine {
+            id: 0,
+            engine: engine.clone(),
+            interval,
+            idle_interval: None,
+            deadlines: Deadlines::default(),
+            last_tick: started,
+        };
+        let mut engines = [
+            registration(&shared, Duration::from_millis(10)),
+            registration(&shared, Duration::from_secs(1)),
+            registration(&wasmtime::Engine::default(), Duration::from_secs(1)),
+        ];
+        let now = started + Duration::from_millis(10);
+        tick_due(&mut engines, now);
+        // The shared engine's tick is counted by both its registrations
+        assert_eq!(engines[0].last_tick, now);
+        assert_eq!(engines[1].last_tick, now);
+        assert_eq!(engines[2].last_tick, started);
+    }
+}
@@ -0,0 +1,96 @@
+//! Counts the allocations made for host component data in steady state.
+//!
+//! This is a test binary of its own, as it replaces the global allocator.
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::Cell;
+
+use spin_core::{Config, Data, Engine, HostComponent, Linker, WasiVersion};
+
+// Counts the allocations made by threads which have enabled counting
+struct CountingAllocator;
+
+thread_local! {
+    static ALLOCATIONS: Cell<Option<usize>> = const { Cell::new(None) };
+}
+
+fn count_allocation() {
+    _ = ALLOCATIONS.try_with(|count| {
+        if let Some(n) = count.get() {
+            count.set(Some(n + 1));
+        }
+    });
+}
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        count_allocation();
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        count_allocation();
+        System.realloc(ptr, layout, new_size)
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+fn allocations(f: impl FnOnce()) -> usize {
+    ALLOCATIONS.with(|count| count.set(Some(0)));
+    f();
+    ALLOCATIONS.with(|count| count.take()).unwrap()
+}
+
+struct ResettingHC;
+
+impl HostComponent for ResettingHC {
+    type Data = Vec<u8>;
+
+    fn add_to_linker<T: Send>(
+        _linker: &mut Linker<T>,
+        _get: impl Fn(&mut Data<T>) -> &mut Self::Data + Send + Sync + Copy + 'static,
+    ) -> anyhow::Result<()> {
+        Ok(())
+    }
+
+    fn build_data(&self) -> Self::Data {
+        Vec::new()
+    }
+
+    fn reset_data(&self, data: &mut Self::Data) -> bool {
+        data.clear();
+        true
+    }
+}
+
+#[test]
+fn steady_state_stores_do_not_allocate_host_component_data() {
+    let mut builder = Engine::<()>::builder(&Config::default()).unwrap();
+    let handle = builder.add_host_component(ResettingHC).unwrap();
+    let engine = builder.build();
+
+    let store_builder = || {
+        engine.store_builder(WasiVersion::Preview2);
+    };
+    let store_builder_using_data = || {
+        let mut store_builder = engine.store_builder(WasiVersion::Preview2);
+        store_builder
+            .host_components_data()
+            .get_or_insert(handle)
+            .extend([1, 2, 3]);
+    };
+    // The first store builds the data, and the pool it is returned to
+    store_builder_using_data();
+    store_builder();
+    // Using the data allocates nothing beyond what the store builder does
+    assert_eq!(
+        allocations(store_builder_using_data),
+        allocations(store_builder)
+    );
+}
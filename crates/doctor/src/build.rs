@@ -0,0 +1,2 @@
+/// Diagnose missing build tools.
+pub mod tools;
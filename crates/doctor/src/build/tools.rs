@@ -0,0 +1,171 @@
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+};
+
+use anyhow::Result;
+use async_trait::async_trait;
+
+use crate::{Diagnosis, Diagnostic, PatientApp};
+
+/// BuildToolDiagnostic detects component build commands whose program isn't
+/// installed.
+#[derive(Default)]
+pub struct BuildToolDiagnostic;
+
+#[async_trait]
+impl Diagnostic for BuildToolDiagnostic {
+    type Diagnosis = BuildToolMissing;
+
+    async fn diagnose(&self, patient: &PatientApp) -> Result<Vec<Self::Diagnosis>> {
+        let manifest_str = patient.manifest_doc.to_string();
+        let manifest = spin_manifest::manifest_from_str(&manifest_str)?;
+
+        // Components sharing a missing tool are one problem with one fix
+        let mut missing: BTreeMap<String, Vec<String>> = BTreeMap::new();
+        for (id, component) in &manifest.components {
+            let Some(build) = &component.build else {
+                continue;
+            };
+            let Some(program) = command_program(&build.command) else {
+                continue;
+            };
+            // The Rust toolchain is checked by rustlang::target
+            if program == "cargo" || is_installed(program) {
+                continue;
+            }
+            missing
+                .entry(program.to_owned())
+                .or_default()
+                .push(id.to_string());
+        }
+        Ok(missing
+            .into_iter()
+            .map(|(program, component_ids)| BuildToolMissing {
+                program,
+                component_ids,
+            })
+            .collect())
+    }
+}
+
+/// Returns the program run by a build command, skipping any leading
+/// environment variable assignments. Scripts given by path are not checked.
+fn command_program(command: &str) -> Option<&str> {
+    let program = command
+        .split_whitespace()
+        .find(|word| !word.contains('='))?;
+    if program.contains(['/', '\\']) {
+        None
+    } else {
+        Some(program)
+    }
+}
+
+/// Returns whether `program` is on the PATH.
+fn is_installed(program: &str) -> bool {
+    let Some(path) = std::env::var_os("PATH") else {
+        return false;
+    };
+    std::env::split_paths(&path)
+        .any(|dir| executable_candidates(&dir, program).any(|p| p.is_file()))
+}
+
+#[cfg(not(windows))]
+fn executable_candidates<'a>(
+    dir: &'a Path,
+    program: &'a str,
+) -> impl Iterator<Item = PathBuf> + 'a {
+    std::iter::once(dir.join(program))
+}
+
+#[cfg(windows)]
+fn executable_candidates<'a>(
+    dir: &'a Path,
+    program: &'a str,
+) -> impl Iterator<Item = PathBuf> + 'a {
+    let extensions = std::env::var("PATHEXT").unwrap_or_else(|_| ".COM;.EXE;.BAT;.CMD".into());
+    let with_extensions: Vec<_> = extensions
+        .split(';')
+        .map(|ext| dir.join(format!("{program}{ext}")))
+        .collect();
+    std::iter::once(dir.join(program)).chain(with_extensions)
+}
+
+/// BuildToolMissing represents a build tool which isn't installed.
+#[derive(Debug)]
+pub struct BuildToolMissing {
+    program: String,
+    component_ids: Vec<String>,
+}
+
+impl BuildToolMissing {
+    /// Where to get well-known build tools.
+    fn install_hint(&self) -> Option<&'static str> {
+        match self.program.as_str() {
+            "tinygo" => Some("https://tinygo.org/getting-started/install/"),
+            "npm" | "npx" | "node" => Some("https://nodejs.org/"),
+            "componentize-py" => Some("`pip install componentize-py`"),
+            "zig" => Some("https://ziglang.org/download/"),
+            "go" => Some("https://go.dev/doc/install"),
+            _ => None,
+        }
+    }
+}
+
+impl Diagnosis for BuildToolMissing {
+    fn description(&self) -> String {
+        let components = self
+            .component_ids
+            .iter()
+            .map(|id| format!("{id:?}"))
+            .collect::<Vec<_>>()
+            .join(", ");
+        let mut description = format!(
+            "The build tool {:?} isn't installed (needed to build {components})",
+            self.program
+        );
+        if let Some(hint) = self.install_hint() {
+            description.push_str(&format!("; install it from {hint}"));
+        }
+        description
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test::{assert_single_diagnosis, TestPatient};
+
+    use super::*;
+
+    #[test]
+    fn test_command_program() {
+        assert_eq!(command_program("tinygo build -o main.wasm"), Some("tinygo"));
+        assert_eq!(command_program("GOOS=wasip1 go build"), Some("go"));
+        assert_eq!(command_program("./build.sh"), None);
+        assert_eq!(command_program(""), None);
+    }
+
+    #[tokio::test]
+    async fn test_missing_tool() {
+        let patient = TestPatient::from_toml_str(
+            r#"
+            spin_manifest_version = 2
+            [application]
+            name = "build-tools-test"
+            [component.one]
+            source = "one.wasm"
+            build.command = "spin-doctor-no-such-tool build one"
+            [component.two]
+            source = "two.wasm"
+            build.command = "spin-doctor-no-such-tool build two"
+            [component.rust]
+            source = "rust.wasm"
+            build.command = "cargo build"
+            "#,
+        );
+        let diag = assert_single_diagnosis::<BuildToolDiagnostic>(&patient).await;
+        assert_eq!(diag.program, "spin-doctor-no-such-tool");
+        assert_eq!(diag.component_ids, ["one", "two"]);
+    }
+}
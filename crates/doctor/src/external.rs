@@ -0,0 +1,245 @@
+//! Diagnoses provided by external programs such as Spin plugins.
+//!
+//! A program which provides diagnoses is run as
+//! `<program> doctor diagnose <manifest-path>`, and must print JSON of the
+//! form:
+//!
+//! ```json
+//! {
+//!   "diagnoses": [
+//!     {
+//!       "id": "missing-config",
+//!       "description": "The app has no deployment config",
+//!       "critical": true,
+//!       "treatment": { "summary": "Create a default config", "dry_run": "Write deploy.toml" }
+//!     }
+//!   ]
+//! }
+//! ```
+//!
+//! `critical` (default true), `treatment` and the treatment's `dry_run` are
+//! optional. A diagnosis with a treatment is treated by running
+//! `<program> doctor treat <manifest-path> <id>`, which must exit
+//! successfully only if the problem is fixed.
+
+use std::{collections::HashMap, ffi::OsStr, path::PathBuf, sync::Arc};
+
+use anyhow::{ensure, Context, Result};
+use async_trait::async_trait;
+use serde::Deserialize;
+use tokio::process::Command;
+
+use crate::{Diagnosis, Diagnostic, DryRunNotSupported, PatientApp, Treatment};
+
+/// ExternalDiagnostic runs an external program to detect problems.
+pub struct ExternalDiagnostic {
+    program: ExternalProgram,
+}
+
+#[derive(Clone, Debug)]
+struct ExternalProgram {
+    name: String,
+    path: PathBuf,
+    envs: HashMap<String, String>,
+}
+
+impl ExternalDiagnostic {
+    /// Returns a diagnostic which runs the program at `path`. Its diagnoses
+    /// are attributed to `name`.
+    pub fn new(name: impl Into<String>, path: impl Into<PathBuf>) -> Self {
+        Self {
+            program: ExternalProgram {
+                name: name.into(),
+                path: path.into(),
+                envs: Default::default(),
+            },
+        }
+    }
+
+    /// Returns this diagnostic with environment variables to run the
+    /// program with.
+    pub fn with_envs(mut self, envs: HashMap<String, String>) -> Self {
+        self.program.envs = envs;
+        self
+    }
+}
+
+impl ExternalProgram {
+    fn command(&self, action: &str, patient: &PatientApp) -> Command {
+        let mut cmd = Command::new(&self.path);
+        cmd.envs(&self.envs)
+            .args([OsStr::new("doctor"), OsStr::new(action)])
+            .arg(&patient.manifest_path);
+        cmd
+    }
+}
+
+#[derive(Deserialize)]
+struct DiagnoseOutput {
+    diagnoses: Vec<ExternalDiagnosisOutput>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ExternalDiagnosisOutput {
+    id: String,
+    description: String,
+    #[serde(default = "default_critical")]
+    critical: bool,
+    treatment: Option<ExternalTreatmentOutput>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ExternalTreatmentOutput {
+    summary: String,
+    dry_run: Option<String>,
+}
+
+fn default_critical() -> bool {
+    true
+}
+
+#[async_trait]
+impl Diagnostic for ExternalDiagnostic {
+    type Diagnosis = ExternalDiagnosis;
+
+    async fn diagnose(&self, patient: &PatientApp) -> Result<Vec<Self::Diagnosis>> {
+        let program = Arc::new(self.program.clone());
+        let output = program
+            .command("diagnose", patient)
+            .stderr(std::process::Stdio::inherit())
+            .output()
+            .await
+            .with_context(|| format!("Couldn't run {:?} diagnoses", program.name))?;
+        ensure!(
+            output.status.success(),
+            "{:?} diagnoses failed: {}",
+            program.name,
+            output.status
+        );
+        let output: DiagnoseOutput = serde_json::from_slice(&output.stdout)
+            .with_context(|| format!("Invalid diagnoses from {:?}", program.name))?;
+        Ok(output
+            .diagnoses
+            .into_iter()
+            .map(|output| ExternalDiagnosis {
+                program: program.clone(),
+                output,
+            })
+            .collect())
+    }
+}
+
+/// ExternalDiagnosis represents a problem detected by an external program.
+#[derive(Debug)]
+pub struct ExternalDiagnosis {
+    program: Arc<ExternalProgram>,
+    output: ExternalDiagnosisOutput,
+}
+
+impl ExternalDiagnosis {
+    /// The name of the program which detected the problem.
+    pub fn source(&self) -> &str {
+        &self.program.name
+    }
+}
+
+impl Diagnosis for ExternalDiagnosis {
+    fn description(&self) -> String {
+        format!("[{}] {}", self.program.name, self.output.description)
+    }
+
+    fn is_critical(&self) -> bool {
+        self.output.critical
+    }
+
+    fn treatment(&self) -> Option<&dyn Treatment> {
+        self.output
+            .treatment
+            .as_ref()
+            .map(|_| self as &dyn Treatment)
+    }
+}
+
+#[async_trait]
+impl Treatment for ExternalDiagnosis {
+    fn summary(&self) -> String {
+        self.output
+            .treatment
+            .as_ref()
+            .map(|t| t.summary.clone())
+            .unwrap_or_default()
+    }
+
+    async fn dry_run(&self, _patient: &PatientApp) -> Result<String> {
+        self.output
+            .treatment
+            .as_ref()
+            .and_then(|t| t.dry_run.clone())
+            .ok_or_else(|| DryRunNotSupported.into())
+    }
+
+    async fn treat(&self, patient: &mut PatientApp) -> Result<()> {
+        let program = &self.program;
+        let mut cmd = program.command("treat", patient);
+        cmd.arg(&self.output.id);
+        let status = cmd
+            .status()
+            .await
+            .with_context(|| format!("Couldn't run {:?} treatment", program.name))?;
+        ensure!(
+            status.success(),
+            "{:?} treatment failed: {status}",
+            program.name
+        );
+        // The program may have changed the manifest
+        *patient = PatientApp::new(&patient.manifest_path)?;
+        Ok(())
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use crate::test::TestPatient;
+
+    use super::*;
+
+    const SCRIPT: &str = r#"#!/bin/sh
+if [ "$2" = "diagnose" ]; then
+  echo '{"diagnoses": [{"id": "one", "description": "Problem one", "treatment": {"summary": "Fix one"}}, {"id": "two", "description": "Problem two", "critical": false}]}'
+elif [ "$2" = "treat" ] && [ "$4" = "one" ] && [ "$TEST_ENV" = "set" ]; then
+  exit 0
+else
+  exit 1
+fi
+"#;
+
+    #[tokio::test]
+    async fn test_external_diagnoses() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempfile::tempdir().unwrap();
+        let script = dir.path().join("diagnose.sh");
+        std::fs::write(&script, SCRIPT).unwrap();
+        std::fs::set_permissions(&script, std::fs::Permissions::from_mode(0o755)).unwrap();
+
+        let mut patient = TestPatient::from_toml_str("spin_manifest_version = 2");
+        let diagnostic = ExternalDiagnostic::new("test-plugin", &script)
+            .with_envs([("TEST_ENV".to_owned(), "set".to_owned())].into());
+        let diags = diagnostic.diagnose(&patient).await.unwrap();
+        assert_eq!(diags.len(), 2, "{diags:?}");
+
+        assert_eq!(diags[0].description(), "[test-plugin] Problem one");
+        assert!(diags[0].is_critical());
+        let treatment = diags[0].treatment().unwrap();
+        assert_eq!(treatment.summary(), "Fix one");
+        assert!(treatment
+            .dry_run(&patient)
+            .await
+            .unwrap_err()
+            .is::<DryRunNotSupported>());
+        treatment.treat(&mut patient).await.unwrap();
+
+        assert!(!diags[1].is_critical());
+        assert!(diags[1].treatment().is_none());
+    }
+}
@@ -8,10 +8,17 @@ use async_trait::async_trait;
 use spin_common::ui::quoted_path;
 use toml_edit::Document;
 
+/// Diagnoses for component build problems.
+pub mod build;
+pub mod external;
 /// Diagnoses for app manifest format problems.
 pub mod manifest;
+/// Diagnose registry credential problems.
+pub mod registry;
 /// Diagnose for Rust-specific problems.
 pub mod rustlang;
+/// Diagnose app state directory problems.
+pub mod state;
 /// Test helpers.
 pub mod test;
 /// Diagnoses for Wasm source problems.
@@ -36,9 +43,14 @@ impl Checkup {
         checkup
             .add_diagnostic::<manifest::upgrade::UpgradeDiagnostic>()
             .add_diagnostic::<manifest::version::VersionDiagnostic>()
+            .add_diagnostic::<manifest::schema::SchemaDiagnostic>()
             .add_diagnostic::<manifest::trigger::TriggerDiagnostic>()
             .add_diagnostic::<rustlang::target::TargetDiagnostic>() // Do toolchain checks _before_ build check
-            .add_diagnostic::<wasm::missing::WasmMissingDiagnostic>();
+            .add_diagnostic::<build::tools::BuildToolDiagnostic>()
+            .add_diagnostic::<wasm::missing::WasmMissingDiagnostic>()
+            .add_diagnostic::<wasm::world::WorldVersionDiagnostic>()
+            .add_diagnostic::<state::StateDirDiagnostic>()
+            .add_diagnostic::<registry::RegistryAuthDiagnostic>();
         Ok(checkup)
     }
 
@@ -49,7 +61,14 @@ impl Checkup {
 
     /// Add a detectable problem to this checkup.
     pub fn add_diagnostic<D: Diagnostic + Default + 'static>(&mut self) -> &mut Self {
-        self.diagnostics.push_back(Box::<D>::default());
+        self.add_diagnostic_with(D::default())
+    }
+
+    /// Add a detectable problem to this checkup, checked by the given
+    /// instance. This allows diagnostics which need configuration, such as
+    /// [`external::ExternalDiagnostic`]s provided by plugins.
+    pub fn add_diagnostic_with(&mut self, diagnostic: impl Diagnostic + 'static) -> &mut Self {
+        self.diagnostics.push_back(Box::new(diagnostic));
         self
     }
 
@@ -7,6 +7,8 @@ use toml_edit::Document;
 
 use crate::Treatment;
 
+/// Diagnose app manifests which don't match the schema.
+pub mod schema;
 /// Diagnose app manifest trigger config problems.
 pub mod trigger;
 /// Diagnose old app manifest versions.
@@ -0,0 +1,85 @@
+use anyhow::Result;
+use async_trait::async_trait;
+use toml_edit::Item;
+
+use crate::{Diagnosis, Diagnostic, PatientApp};
+
+/// SchemaDiagnostic detects version 2 manifests which don't match the
+/// manifest schema.
+#[derive(Default)]
+pub struct SchemaDiagnostic;
+
+#[async_trait]
+impl Diagnostic for SchemaDiagnostic {
+    type Diagnosis = SchemaDiagnosis;
+
+    async fn diagnose(&self, patient: &PatientApp) -> Result<Vec<Self::Diagnosis>> {
+        let doc = &patient.manifest_doc;
+        if doc.get("spin_manifest_version").and_then(Item::as_integer) != Some(2) {
+            // Other versions are checked by VersionDiagnostic and friends
+            return Ok(vec![]);
+        }
+        match spin_manifest::manifest_from_str(&doc.to_string()) {
+            Ok(_) => Ok(vec![]),
+            Err(err) => Ok(vec![SchemaDiagnosis(format!("{err:#}"))]),
+        }
+    }
+}
+
+/// SchemaDiagnosis represents a manifest which doesn't match the schema.
+#[derive(Debug)]
+pub struct SchemaDiagnosis(String);
+
+impl Diagnosis for SchemaDiagnosis {
+    fn description(&self) -> String {
+        format!(
+            "Manifest doesn't match the Spin manifest schema: {}",
+            self.0
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test::{assert_single_diagnosis, TestPatient};
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_valid_manifest() {
+        let patient = TestPatient::from_toml_str(
+            r#"
+            spin_manifest_version = 2
+            [application]
+            name = "schema-test"
+            [[trigger.http]]
+            route = "/..."
+            component = "web"
+            [component.web]
+            source = "web.wasm"
+            "#,
+        );
+        let diags = SchemaDiagnostic.diagnose(&patient).await.unwrap();
+        assert!(diags.is_empty(), "{diags:?}");
+    }
+
+    #[tokio::test]
+    async fn test_unknown_field() {
+        let patient = TestPatient::from_toml_str(
+            r#"
+            spin_manifest_version = 2
+            [application]
+            name = "schema-test"
+            [component.web]
+            source = "web.wasm"
+            allowed_outbound_hsots = ["https://example.com"]
+            "#,
+        );
+        let diag = assert_single_diagnosis::<SchemaDiagnostic>(&patient).await;
+        assert!(
+            diag.description().contains("allowed_outbound_hsots"),
+            "{}",
+            diag.description()
+        );
+    }
+}
@@ -0,0 +1,242 @@
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use base64::Engine;
+use spin_common::ui::quoted_path;
+
+use crate::{Diagnosis, Diagnostic, PatientApp, Treatment};
+
+/// RegistryAuthDiagnostic detects registry credentials saved by
+/// `spin registry login` which Spin can't use.
+#[derive(Default)]
+pub struct RegistryAuthDiagnostic {
+    /// The credentials file to check, if not the default
+    /// ($XDG_CONFIG_HOME/fermyon/registry-auth.json).
+    path: Option<PathBuf>,
+}
+
+impl RegistryAuthDiagnostic {
+    /// Returns a diagnostic which checks the given credentials file.
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self {
+            path: Some(path.into()),
+        }
+    }
+
+    fn path(&self) -> Option<PathBuf> {
+        self.path.clone().or_else(|| {
+            Some(
+                dirs::config_dir()?
+                    .join("fermyon")
+                    .join("registry-auth.json"),
+            )
+        })
+    }
+}
+
+#[async_trait]
+impl Diagnostic for RegistryAuthDiagnostic {
+    type Diagnosis = RegistryAuthProblem;
+
+    async fn diagnose(&self, _patient: &PatientApp) -> Result<Vec<Self::Diagnosis>> {
+        let Some(path) = self.path() else {
+            return Ok(vec![]);
+        };
+        // No saved credentials is fine
+        let Ok(contents) = std::fs::read_to_string(&path) else {
+            return Ok(vec![]);
+        };
+        let Ok(auths) = parse_auths(&contents) else {
+            return Ok(vec![RegistryAuthProblem::InvalidFile(path)]);
+        };
+        let invalid_servers: Vec<String> = auths
+            .into_iter()
+            .filter(|(_, credentials)| decode_credentials(credentials).is_none())
+            .map(|(server, _)| server)
+            .collect();
+        if invalid_servers.is_empty() {
+            Ok(vec![])
+        } else {
+            Ok(vec![RegistryAuthProblem::InvalidCredentials {
+                path,
+                servers: invalid_servers,
+            }])
+        }
+    }
+}
+
+/// Parses the credentials file, returning the base64-encoded credentials by
+/// registry server.
+fn parse_auths(contents: &str) -> Result<BTreeMap<String, serde_json::Value>> {
+    let mut file: serde_json::Value = serde_json::from_str(contents)?;
+    let auths = file
+        .get_mut("auths")
+        .map(serde_json::Value::take)
+        .context("missing 'auths'")?;
+    Ok(serde_json::from_value(auths)?)
+}
+
+/// Returns the username and password from a server's saved credentials.
+fn decode_credentials(credentials: &serde_json::Value) -> Option<(String, String)> {
+    let bytes = base64::engine::general_purpose::STANDARD
+        .decode(credentials.as_str()?)
+        .ok()?;
+    let decoded = String::from_utf8(bytes).ok()?;
+    let (username, password) = decoded.split_once(':')?;
+    Some((username.to_owned(), password.to_owned()))
+}
+
+/// RegistryAuthProblem represents saved registry credentials which Spin
+/// can't use.
+#[derive(Debug)]
+pub enum RegistryAuthProblem {
+    /// The credentials file isn't valid.
+    InvalidFile(PathBuf),
+    /// The credentials for some servers aren't valid.
+    InvalidCredentials {
+        /// The credentials file.
+        path: PathBuf,
+        /// The servers with invalid credentials.
+        servers: Vec<String>,
+    },
+}
+
+impl RegistryAuthProblem {
+    fn path(&self) -> &Path {
+        match self {
+            Self::InvalidFile(path) | Self::InvalidCredentials { path, .. } => path,
+        }
+    }
+
+    fn backup_path(&self) -> PathBuf {
+        self.path().with_extension("json.bak")
+    }
+}
+
+impl Diagnosis for RegistryAuthProblem {
+    fn description(&self) -> String {
+        match self {
+            Self::InvalidFile(path) => format!(
+                "Registry credentials file {} is not valid; `spin registry` commands will not be authenticated",
+                quoted_path(path)
+            ),
+            Self::InvalidCredentials { servers, .. } => format!(
+                "Saved registry credentials are not valid for: {}",
+                servers.join(", ")
+            ),
+        }
+    }
+
+    fn is_critical(&self) -> bool {
+        // Only registry commands are affected, not the app
+        false
+    }
+
+    fn treatment(&self) -> Option<&dyn Treatment> {
+        Some(self)
+    }
+}
+
+#[async_trait]
+impl Treatment for RegistryAuthProblem {
+    fn summary(&self) -> String {
+        match self {
+            Self::InvalidFile(_) => "Move the credentials file aside".into(),
+            Self::InvalidCredentials { .. } => "Remove the invalid credentials".into(),
+        }
+    }
+
+    async fn dry_run(&self, _patient: &PatientApp) -> Result<String> {
+        let login = "Run `spin registry login` to save new credentials.";
+        match self {
+            Self::InvalidFile(path) => Ok(format!(
+                "Rename {} to {}. {login}",
+                quoted_path(path),
+                quoted_path(self.backup_path())
+            )),
+            Self::InvalidCredentials { path, servers } => Ok(format!(
+                "Remove the credentials for {} from {}. {login}",
+                servers.join(", "),
+                quoted_path(path)
+            )),
+        }
+    }
+
+    async fn treat(&self, _patient: &mut PatientApp) -> Result<()> {
+        match self {
+            Self::InvalidFile(path) => {
+                std::fs::rename(path, self.backup_path())
+                    .with_context(|| format!("Couldn't rename {}", quoted_path(path)))?;
+            }
+            Self::InvalidCredentials { path, servers } => {
+                let contents = std::fs::read_to_string(path)
+                    .with_context(|| format!("Couldn't read {}", quoted_path(path)))?;
+                let mut file: serde_json::Value = serde_json::from_str(&contents)?;
+                if let Some(auths) = file.get_mut("auths").and_then(|a| a.as_object_mut()) {
+                    for server in servers {
+                        auths.remove(server);
+                    }
+                }
+                std::fs::write(path, serde_json::to_vec_pretty(&file)?)
+                    .with_context(|| format!("Couldn't write {}", quoted_path(path)))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test::TestPatient;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_invalid_credentials_are_removed() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("registry-auth.json");
+        let good = base64::engine::general_purpose::STANDARD.encode("user:pass");
+        std::fs::write(
+            &path,
+            format!(r#"{{"auths": {{"good.example": "{good}", "bad.example": "not base64!"}}}}"#),
+        )
+        .unwrap();
+
+        let mut patient = TestPatient::from_toml_str("spin_manifest_version = 2");
+        let diagnostic = RegistryAuthDiagnostic::new(&path);
+        let diags = diagnostic.diagnose(&patient).await.unwrap();
+        assert_eq!(diags.len(), 1, "{diags:?}");
+        let RegistryAuthProblem::InvalidCredentials { servers, .. } = &diags[0] else {
+            panic!("unexpected diagnosis {diags:?}");
+        };
+        assert_eq!(servers, &["bad.example"]);
+
+        diags[0].treat(&mut patient).await.unwrap();
+        assert!(diagnostic.diagnose(&patient).await.unwrap().is_empty());
+        let auths = parse_auths(&std::fs::read_to_string(&path).unwrap()).unwrap();
+        assert!(auths.contains_key("good.example"));
+    }
+
+    #[tokio::test]
+    async fn test_invalid_file_is_moved_aside() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("registry-auth.json");
+        std::fs::write(&path, "{").unwrap();
+
+        let mut patient = TestPatient::from_toml_str("spin_manifest_version = 2");
+        let diagnostic = RegistryAuthDiagnostic::new(&path);
+        let diags = diagnostic.diagnose(&patient).await.unwrap();
+        assert!(
+            matches!(diags.as_slice(), [RegistryAuthProblem::InvalidFile(_)]),
+            "{diags:?}"
+        );
+
+        diags[0].treat(&mut patient).await.unwrap();
+        assert!(!path.exists());
+        assert!(dir.path().join("registry-auth.json.bak").exists());
+    }
+}
@@ -0,0 +1,130 @@
+use std::path::PathBuf;
+
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use spin_common::{paths::parent_dir, ui::quoted_path};
+
+use crate::{Diagnosis, Diagnostic, PatientApp, Treatment};
+
+/// The directory, relative to the app manifest, in which `spin up` keeps
+/// application state such as key-value stores and logs.
+const STATE_DIR: &str = ".spin";
+
+/// StateDirDiagnostic detects an app state directory Spin can't write to.
+#[derive(Default)]
+pub struct StateDirDiagnostic;
+
+#[async_trait]
+impl Diagnostic for StateDirDiagnostic {
+    type Diagnosis = StateDirProblem;
+
+    async fn diagnose(&self, patient: &PatientApp) -> Result<Vec<Self::Diagnosis>> {
+        let path = parent_dir(&patient.manifest_path)?.join(STATE_DIR);
+        // A missing state directory is created when it is needed
+        let Ok(metadata) = std::fs::metadata(&path) else {
+            return Ok(vec![]);
+        };
+        if !metadata.is_dir() {
+            return Ok(vec![StateDirProblem::NotADirectory(path)]);
+        }
+        if tempfile::tempfile_in(&path).is_err() {
+            return Ok(vec![StateDirProblem::NotWritable(path)]);
+        }
+        Ok(vec![])
+    }
+}
+
+/// StateDirProblem represents an app state directory Spin can't write to.
+#[derive(Debug)]
+pub enum StateDirProblem {
+    /// The state directory path exists but isn't a directory.
+    NotADirectory(PathBuf),
+    /// The current user doesn't have permission to write to the directory.
+    NotWritable(PathBuf),
+}
+
+impl Diagnosis for StateDirProblem {
+    fn description(&self) -> String {
+        match self {
+            Self::NotADirectory(path) => format!(
+                "State directory {} is not a directory; Spin can't store application state",
+                quoted_path(path)
+            ),
+            Self::NotWritable(path) => format!(
+                "State directory {} is not writable; Spin can't store application state",
+                quoted_path(path)
+            ),
+        }
+    }
+
+    fn treatment(&self) -> Option<&dyn Treatment> {
+        match self {
+            Self::NotWritable(_) if cfg!(unix) => Some(self),
+            _ => None,
+        }
+    }
+}
+
+#[async_trait]
+impl Treatment for StateDirProblem {
+    fn summary(&self) -> String {
+        "Give your user full permissions on the state directory".into()
+    }
+
+    async fn dry_run(&self, _patient: &PatientApp) -> Result<String> {
+        let (Self::NotADirectory(path) | Self::NotWritable(path)) = self;
+        Ok(format!("Run `chmod u+rwx {}`", quoted_path(path)))
+    }
+
+    #[cfg(unix)]
+    async fn treat(&self, _patient: &mut PatientApp) -> Result<()> {
+        use std::os::unix::fs::PermissionsExt;
+
+        let (Self::NotADirectory(path) | Self::NotWritable(path)) = self;
+        let mut permissions = std::fs::metadata(path)?.permissions();
+        permissions.set_mode(permissions.mode() | 0o700);
+        std::fs::set_permissions(path, permissions)
+            .with_context(|| format!("Couldn't change permissions of {}", quoted_path(path)))
+    }
+
+    #[cfg(not(unix))]
+    async fn treat(&self, _patient: &mut PatientApp) -> Result<()> {
+        anyhow::bail!("Changing state directory permissions is not supported on this platform")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_state_dir() {
+        let dir = tempfile::tempdir().unwrap();
+        let manifest_path = dir.path().join("spin.toml");
+        std::fs::write(&manifest_path, "spin_manifest_version = 2").unwrap();
+        let patient = PatientApp::new(&manifest_path).unwrap();
+        let state_path = dir.path().join(STATE_DIR);
+
+        assert!(StateDirDiagnostic
+            .diagnose(&patient)
+            .await
+            .unwrap()
+            .is_empty());
+
+        std::fs::create_dir(&state_path).unwrap();
+        assert!(StateDirDiagnostic
+            .diagnose(&patient)
+            .await
+            .unwrap()
+            .is_empty());
+
+        std::fs::remove_dir(&state_path).unwrap();
+        std::fs::write(&state_path, "").unwrap();
+        let diags = StateDirDiagnostic.diagnose(&patient).await.unwrap();
+        assert!(
+            matches!(diags.as_slice(), [StateDirProblem::NotADirectory(_)]),
+            "{diags:?}"
+        );
+        assert!(diags[0].treatment().is_none());
+    }
+}
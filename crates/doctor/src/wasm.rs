@@ -1,9 +1,14 @@
 /// Diagnose missing Wasm sources.
 pub mod missing;
+/// Diagnose Wasm components built against unsupported WIT worlds.
+pub mod world;
 
-use std::path::{Path, PathBuf};
+use std::{
+    path::{Path, PathBuf},
+    process::Command,
+};
 
-use anyhow::Result;
+use anyhow::{Context, Result};
 use async_trait::async_trait;
 use spin_common::paths::parent_dir;
 use spin_manifest::schema::v2;
@@ -56,6 +61,18 @@ impl PatientWasm {
     pub fn has_build(&self) -> bool {
         self.component.build.is_some()
     }
+
+    /// Returns a `spin build` command which builds only this component.
+    pub fn build_cmd(&self, patient: &PatientApp) -> Result<Command> {
+        let spin_bin = std::env::current_exe().context("Couldn't find spin executable")?;
+        let mut cmd = Command::new(spin_bin);
+        cmd.arg("build")
+            .arg("-f")
+            .arg(&patient.manifest_path)
+            .arg("--component-id")
+            .arg(self.component_id());
+        Ok(cmd)
+    }
 }
 
 /// WasmDiagnose helps implement [`Diagnose`] for Wasm source problems.
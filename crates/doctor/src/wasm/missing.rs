@@ -1,6 +1,6 @@
 use std::process::Command;
 
-use anyhow::{ensure, Context, Result};
+use anyhow::{ensure, Result};
 use async_trait::async_trait;
 use spin_common::ui::quoted_path;
 
@@ -36,14 +36,7 @@ pub struct WasmMissing(PatientWasm);
 
 impl WasmMissing {
     fn build_cmd(&self, patient: &PatientApp) -> Result<Command> {
-        let spin_bin = std::env::current_exe().context("Couldn't find spin executable")?;
-        let mut cmd = Command::new(spin_bin);
-        cmd.arg("build")
-            .arg("-f")
-            .arg(&patient.manifest_path)
-            .arg("--component-id")
-            .arg(self.0.component_id());
-        Ok(cmd)
+        self.0.build_cmd(patient)
     }
 }
 
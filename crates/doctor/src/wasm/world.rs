@@ -0,0 +1,173 @@
+use anyhow::{ensure, Context, Result};
+use async_trait::async_trait;
+use wasmparser::{Encoding, Parser, Payload};
+
+use crate::{Diagnosis, PatientApp, Treatment};
+
+use super::{PatientWasm, WasmDiagnostic};
+
+/// The versions of each WIT package namespace which Spin supports. Interfaces
+/// from other namespaces, or without versions, are not checked.
+const SUPPORTED_VERSIONS: &[(&str, &[&str])] = &[
+    (
+        "wasi",
+        &["0.2.0", "0.2.0-rc-2023-10-18", "0.2.0-rc-2023-11-10"],
+    ),
+    ("fermyon", &["2.0.0"]),
+];
+
+/// WorldVersionDiagnostic detects Wasm components which import or export
+/// WIT interfaces at versions Spin doesn't support.
+#[derive(Default)]
+pub struct WorldVersionDiagnostic;
+
+#[async_trait]
+impl WasmDiagnostic for WorldVersionDiagnostic {
+    type Diagnosis = WorldVersionMismatch;
+
+    async fn diagnose_wasm(
+        &self,
+        _app: &PatientApp,
+        wasm: PatientWasm,
+    ) -> anyhow::Result<Vec<Self::Diagnosis>> {
+        let Some(abs_path) = wasm.abs_source_path() else {
+            return Ok(vec![]);
+        };
+        // Missing sources are diagnosed by WasmMissingDiagnostic
+        let Ok(bytes) = std::fs::read(&abs_path) else {
+            return Ok(vec![]);
+        };
+        let unsupported = unsupported_interfaces(&interface_names(&bytes));
+        if unsupported.is_empty() {
+            Ok(vec![])
+        } else {
+            Ok(vec![WorldVersionMismatch { wasm, unsupported }])
+        }
+    }
+}
+
+/// Returns the names of the interfaces a Wasm component imports and exports.
+/// Core modules and invalid binaries have none.
+fn interface_names(bytes: &[u8]) -> Vec<String> {
+    let mut names = vec![];
+    for payload in Parser::new(0).parse_all(bytes) {
+        let Ok(payload) = payload else {
+            break;
+        };
+        match payload {
+            Payload::Version { encoding, .. } if encoding != Encoding::Component => break,
+            Payload::ComponentImportSection(reader) => {
+                names.extend(reader.into_iter().flatten().map(|i| i.name.0.to_owned()));
+            }
+            Payload::ComponentExportSection(reader) => {
+                names.extend(reader.into_iter().flatten().map(|e| e.name.0.to_owned()));
+            }
+            _ => {}
+        }
+    }
+    names
+}
+
+/// Returns the interface names, e.g. `wasi:http/types@0.2.0`, whose
+/// versions Spin doesn't support.
+fn unsupported_interfaces(names: &[String]) -> Vec<String> {
+    let mut unsupported: Vec<String> = names
+        .iter()
+        .filter(|name| {
+            let Some((namespace, rest)) = name.split_once(':') else {
+                return false;
+            };
+            let Some((_, version)) = rest.split_once('@') else {
+                return false;
+            };
+            SUPPORTED_VERSIONS
+                .iter()
+                .find(|(ns, _)| *ns == namespace)
+                .is_some_and(|(_, versions)| !versions.contains(&version))
+        })
+        .cloned()
+        .collect();
+    unsupported.sort();
+    unsupported.dedup();
+    unsupported
+}
+
+/// WorldVersionMismatch represents a Wasm component built against
+/// unsupported interface versions.
+#[derive(Debug)]
+pub struct WorldVersionMismatch {
+    wasm: PatientWasm,
+    unsupported: Vec<String>,
+}
+
+impl Diagnosis for WorldVersionMismatch {
+    fn description(&self) -> String {
+        format!(
+            "Component {:?} uses interface versions this version of Spin doesn't support: {}",
+            self.wasm.component_id(),
+            self.unsupported.join(", ")
+        )
+    }
+
+    fn treatment(&self) -> Option<&dyn Treatment> {
+        self.wasm.has_build().then_some(self)
+    }
+}
+
+#[async_trait]
+impl Treatment for WorldVersionMismatch {
+    fn summary(&self) -> String {
+        "Rebuild the component with `spin build`".into()
+    }
+
+    async fn dry_run(&self, patient: &PatientApp) -> anyhow::Result<String> {
+        let args = self
+            .wasm
+            .build_cmd(patient)?
+            .get_args()
+            .map(|arg| arg.to_string_lossy())
+            .collect::<Vec<_>>()
+            .join(" ");
+        Ok(format!(
+            "Run `spin {args}`. If the component's dependencies pin the unsupported versions, update them first."
+        ))
+    }
+
+    async fn treat(&self, patient: &mut PatientApp) -> anyhow::Result<()> {
+        let mut cmd = self.wasm.build_cmd(patient)?;
+        let status = cmd.status().context("Couldn't run build")?;
+        ensure!(status.success(), "Build command {cmd:?} failed: {status:?}");
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_unsupported_interfaces() {
+        let names = [
+            "wasi:http/types@0.2.0",
+            "wasi:http/types@0.2.0-rc-2023-11-10",
+            "wasi:io/streams@0.3.0",
+            "wasi:io/streams@0.3.0",
+            "fermyon:spin/key-value@2.0.0",
+            "fermyon:spin/llm@3.0.0",
+            "fermyon:spin/config",
+            "example:other/thing@9.9.9",
+            "plain-import",
+        ]
+        .map(String::from);
+        assert_eq!(
+            unsupported_interfaces(&names),
+            ["fermyon:spin/llm@3.0.0", "wasi:io/streams@0.3.0"]
+        );
+    }
+
+    #[test]
+    fn test_non_components_have_no_interfaces() {
+        assert!(interface_names(b"").is_empty());
+        assert!(interface_names(b"\0asm\x01\0\0\0").is_empty());
+    }
+}
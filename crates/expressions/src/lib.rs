@@ -1,11 +1,13 @@
 pub mod provider;
+mod secret;
 mod template;
 
-use std::{borrow::Cow, collections::HashMap, fmt::Debug};
+use std::{collections::HashMap, fmt::Debug};
 
 use spin_locked_app::Variable;
 
 pub use provider::Provider;
+pub use secret::SecretString;
 use template::Part;
 pub use template::Template;
 
@@ -14,6 +16,8 @@ pub use template::Template;
 pub struct Resolver {
     // variable key -> variable
     variables: HashMap<String, Variable>,
+    // variable key -> regex its values must match
+    patterns: HashMap<String, regex::Regex>,
     // component ID -> variable key -> variable value template
     component_configs: HashMap<String, HashMap<String, Template>>,
     providers: Vec<Box<dyn Provider>>,
@@ -21,7 +25,7 @@ pub struct Resolver {
 
 #[derive(Default)]
 pub struct PreparedResolver {
-    variables: HashMap<String, String>,
+    variables: HashMap<String, SecretString>,
 }
 
 pub type SharedPreparedResolver =
@@ -33,8 +37,18 @@ impl Resolver {
         let variables: HashMap<_, _> = variables.into_iter().collect();
         // Validate keys so that we can rely on them during resolution
         variables.keys().try_for_each(|key| Key::validate(key))?;
+        let patterns = variables
+            .iter()
+            .filter_map(|(key, var)| {
+                let pattern = var.pattern.as_ref()?;
+                let regex = regex::Regex::new(&format!("^(?:{pattern})$"))
+                    .map_err(|err| Error::InvalidPattern(format!("{key:?}: {pattern:?}: {err}")));
+                Some(regex.map(|regex| (key.clone(), regex)))
+            })
+            .collect::<Result<_>>()?;
         Ok(Self {
             variables,
+            patterns,
             component_configs: Default::default(),
             providers: Default::default(),
         })
@@ -68,7 +82,7 @@ impl Resolver {
     }
 
     /// Resolves a variable value for the given path.
-    pub async fn resolve(&self, component_id: &str, key: Key<'_>) -> Result<String> {
+    pub async fn resolve(&self, component_id: &str, key: Key<'_>) -> Result<SecretString> {
         let configs = self.component_configs.get(component_id).ok_or_else(|| {
             Error::Undefined(format!("no variable for component {component_id:?}"))
         })?;
@@ -78,18 +92,24 @@ impl Resolver {
             .get(key)
             .ok_or_else(|| Error::Undefined(format!("no variable for {component_id:?}.{key:?}")))?;
 
-        self.resolve_template(template).await
+        self.resolve_secret_template(template).await
     }
 
     pub async fn resolve_template(&self, template: &Template) -> Result<String> {
-        let mut resolved_parts: Vec<Cow<str>> = Vec::with_capacity(template.parts().len());
+        self.resolve_secret_template(template)
+            .await
+            .map(SecretString::into_exposed)
+    }
+
+    async fn resolve_secret_template(&self, template: &Template) -> Result<SecretString> {
+        let mut resolved_parts = Vec::with_capacity(template.parts().len());
         for part in template.parts() {
             resolved_parts.push(match part {
                 Part::Lit(lit) => lit.as_ref().into(),
-                Part::Expr(var) => self.resolve_variable(var).await?.into(),
+                Part::Expr(var) => self.resolve_variable(var).await?,
             });
         }
-        Ok(resolved_parts.concat())
+        Ok(SecretString::concat(&resolved_parts))
     }
 
     pub async fn prepare(&self) -> Result<PreparedResolver> {
@@ -101,24 +121,58 @@ impl Resolver {
         Ok(PreparedResolver { variables })
     }
 
-    async fn resolve_variable(&self, key: &str) -> Result<String> {
+    async fn resolve_variable(&self, key: &str) -> Result<SecretString> {
         let var = self
             .variables
             .get(key)
             // This should have been caught by validate_template
             .ok_or_else(|| Error::InvalidName(key.to_string()))?;
 
+        let mut value = None;
         for provider in &self.providers {
-            if let Some(value) = provider.get(&Key(key)).await.map_err(Error::Provider)? {
-                return Ok(value);
+            if let Some(provided) = provider.get(&Key(key)).await.map_err(Error::Provider)? {
+                value = Some(provided);
+                break;
             }
         }
 
-        var.default.clone().ok_or_else(|| {
-            Error::Provider(anyhow::anyhow!(
-                "no provider resolved required variable {key:?}"
-            ))
-        })
+        let mut value = value
+            .or_else(|| var.default.clone().map(SecretString::new))
+            .ok_or_else(|| {
+                Error::Provider(anyhow::anyhow!(
+                    "no provider resolved required variable {key:?}"
+                ))
+            })?;
+        self.check_pattern(key, var, value.expose())?;
+        if var.secret {
+            // Best-effort: the value is zeroized when dropped either way
+            value.lock_memory();
+        }
+        Ok(value)
+    }
+
+    // Fails if the variable has a pattern which the value doesn't match,
+    // describing what was expected without revealing secret values.
+    fn check_pattern(&self, key: &str, var: &Variable, value: &str) -> Result<()> {
+        let Some(regex) = self.patterns.get(key) else {
+            return Ok(());
+        };
+        if regex.is_match(value) {
+            return Ok(());
+        }
+        let pattern = var.pattern.as_deref().unwrap_or_default();
+        let mut message = if var.secret {
+            format!("secret variable {key:?} does not match pattern {pattern:?}")
+        } else {
+            format!("variable {key:?} value {value:?} does not match pattern {pattern:?}")
+        };
+        if let Some(description) = &var.description {
+            message.push_str(&format!(". {key:?} is: {description}"));
+        }
+        if let Some(example) = &var.example {
+            message.push_str(&format!(". For example: {example:?}"));
+        }
+        Err(Error::InvalidValue(message))
     }
 
     fn validate_template(&self, template: String) -> Result<Template> {
@@ -135,7 +189,7 @@ impl Resolver {
 }
 
 impl PreparedResolver {
-    fn resolve_variable(&self, key: &str) -> Result<String> {
+    fn resolve_variable(&self, key: &str) -> Result<SecretString> {
         self.variables
             .get(key)
             .cloned()
@@ -143,14 +197,14 @@ impl PreparedResolver {
     }
 
     pub fn resolve_template(&self, template: &Template) -> Result<String> {
-        let mut resolved_parts: Vec<Cow<str>> = Vec::with_capacity(template.parts().len());
+        let mut resolved_parts = Vec::with_capacity(template.parts().len());
         for part in template.parts() {
             resolved_parts.push(match part {
                 Part::Lit(lit) => lit.as_ref().into(),
-                Part::Expr(var) => self.resolve_variable(var)?.into(),
+                Part::Expr(var) => self.resolve_variable(var)?,
             });
         }
-        Ok(resolved_parts.concat())
+        Ok(SecretString::concat(&resolved_parts).into_exposed())
     }
 }
 
@@ -222,6 +276,14 @@ pub enum Error {
     /// Undefined variable.
     #[error("undefined variable: {0}")]
     Undefined(String),
+
+    /// Invalid variable pattern.
+    #[error("invalid variable pattern: {0}")]
+    InvalidPattern(String),
+
+    /// Variable value which doesn't match its pattern.
+    #[error("invalid variable value: {0}")]
+    InvalidValue(String),
 }
 
 #[cfg(test)]
@@ -235,9 +297,9 @@ mod tests {
 
     #[async_trait]
     impl Provider for TestProvider {
-        async fn get(&self, key: &Key) -> anyhow::Result<Option<String>> {
+        async fn get(&self, key: &Key) -> anyhow::Result<Option<SecretString>> {
             match key.as_ref() {
-                "required" => Ok(Some("provider-value".to_string())),
+                "required" => Ok(Some("provider-value".into())),
                 "broken" => anyhow::bail!("broken"),
                 _ => Ok(None),
             }
@@ -251,6 +313,7 @@ mod tests {
                 Variable {
                     default: None,
                     secret: false,
+                    ..Default::default()
                 },
             ),
             (
@@ -258,6 +321,17 @@ mod tests {
                 Variable {
                     default: Some("default-value".into()),
                     secret: false,
+                    ..Default::default()
+                },
+            ),
+            (
+                "region".into(),
+                Variable {
+                    default: Some("moon-1".into()),
+                    description: Some("Where to store data".into()),
+                    example: Some("eu-west".into()),
+                    pattern: Some("[a-z]+-[a-z]+".into()),
+                    ..Default::default()
                 },
             ),
         ])
@@ -266,7 +340,10 @@ mod tests {
             .add_component_variables("test-component", [("test_key".into(), template.into())])
             .unwrap();
         resolver.add_provider(Box::new(TestProvider));
-        resolver.resolve("test-component", Key("test_key")).await
+        resolver
+            .resolve("test-component", Key("test_key"))
+            .await
+            .map(SecretString::into_exposed)
     }
 
     #[tokio::test]
@@ -290,6 +367,15 @@ mod tests {
         );
     }
 
+    #[tokio::test]
+    async fn resolve_variable_not_matching_pattern() {
+        let err = test_resolve("{{ region }}").await.unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            r#"invalid variable value: variable "region" value "moon-1" does not match pattern "[a-z]+-[a-z]+". "region" is: Where to store data. For example: "eu-west""#
+        );
+    }
+
     #[test]
     fn keys_good() {
         for key in ["a", "abc", "a1b2c3", "a_1", "a_1_b_3"] {
@@ -2,11 +2,12 @@ use std::fmt::Debug;
 
 use async_trait::async_trait;
 
-use crate::Key;
+use crate::{Key, SecretString};
 
 /// A config provider.
 #[async_trait]
 pub trait Provider: Debug + Send + Sync {
-    /// Returns the value at the given config path, if it exists.
-    async fn get(&self, key: &Key) -> anyhow::Result<Option<String>>;
+    /// Returns the value at the given config path, if it exists. Errors
+    /// must not include the value.
+    async fn get(&self, key: &Key) -> anyhow::Result<Option<SecretString>>;
 }
@@ -0,0 +1,150 @@
+use zeroize::Zeroize;
+
+/// A variable value which may be secret.
+///
+/// The value is overwritten with zeros when dropped, and is never shown by
+/// `Debug`; there is no `Display`. Reading it requires an explicit call to
+/// [`SecretString::expose`], so that values don't find their way into logs
+/// or error messages by accident.
+#[derive(Default)]
+pub struct SecretString {
+    value: String,
+    locked: bool,
+}
+
+impl SecretString {
+    /// Wraps a value.
+    pub fn new(value: impl Into<String>) -> Self {
+        Self {
+            value: value.into(),
+            locked: false,
+        }
+    }
+
+    /// Returns the value.
+    pub fn expose(&self) -> &str {
+        &self.value
+    }
+
+    /// Returns the value, which is no longer zeroized when dropped.
+    pub fn into_exposed(mut self) -> String {
+        if self.locked {
+            munlock(self.value.as_bytes());
+            self.locked = false;
+        }
+        std::mem::take(&mut self.value)
+    }
+
+    /// Locks the value's memory so that it is never swapped to disk. This
+    /// is best-effort: it fails, for example, when the process's limit on
+    /// locked memory has been reached, and is unsupported on Windows.
+    pub fn lock_memory(&mut self) -> bool {
+        if !self.locked && !self.value.is_empty() {
+            self.locked = mlock(self.value.as_bytes());
+        }
+        self.locked
+    }
+
+    /// Joins values into one allocation, so that growing it leaves no
+    /// partial copies behind. The result is locked if any value was.
+    pub(crate) fn concat(parts: &[SecretString]) -> Self {
+        let mut value = String::with_capacity(parts.iter().map(|part| part.value.len()).sum());
+        for part in parts {
+            value.push_str(&part.value);
+        }
+        let mut joined = Self::new(value);
+        if parts.iter().any(|part| part.locked) {
+            joined.lock_memory();
+        }
+        joined
+    }
+}
+
+impl Clone for SecretString {
+    fn clone(&self) -> Self {
+        let mut clone = Self::new(self.value.clone());
+        if self.locked {
+            clone.lock_memory();
+        }
+        clone
+    }
+}
+
+impl PartialEq for SecretString {
+    fn eq(&self, other: &Self) -> bool {
+        self.value == other.value
+    }
+}
+
+impl Eq for SecretString {}
+
+impl Drop for SecretString {
+    fn drop(&mut self) {
+        if self.locked {
+            munlock(self.value.as_bytes());
+        }
+        self.value.zeroize();
+    }
+}
+
+impl std::fmt::Debug for SecretString {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("SecretString(<redacted>)")
+    }
+}
+
+impl From<String> for SecretString {
+    fn from(value: String) -> Self {
+        Self::new(value)
+    }
+}
+
+impl From<&str> for SecretString {
+    fn from(value: &str) -> Self {
+        Self::new(value)
+    }
+}
+
+#[cfg(unix)]
+fn mlock(bytes: &[u8]) -> bool {
+    // Safety: the range is a live allocation, which mlock doesn't modify
+    unsafe { libc::mlock(bytes.as_ptr().cast(), bytes.len()) == 0 }
+}
+
+#[cfg(unix)]
+fn munlock(bytes: &[u8]) {
+    // Safety: as for mlock
+    unsafe {
+        libc::munlock(bytes.as_ptr().cast(), bytes.len());
+    }
+}
+
+#[cfg(not(unix))]
+fn mlock(_bytes: &[u8]) -> bool {
+    false
+}
+
+#[cfg(not(unix))]
+fn munlock(_bytes: &[u8]) {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn debug_is_redacted() {
+        let secret = SecretString::new("hunter2");
+        assert_eq!(secret.expose(), "hunter2");
+        assert!(!format!("{secret:?}").contains("hunter2"));
+    }
+
+    #[test]
+    fn concat_keeps_lock() {
+        let mut secret = SecretString::new("hunter2");
+        let locked = secret.lock_memory();
+        let joined = SecretString::concat(&["password: ".into(), secret]);
+        assert_eq!(joined.expose(), "password: hunter2");
+        assert_eq!(joined.locked, locked);
+        assert_eq!(joined.into_exposed(), "password: hunter2");
+    }
+}
@@ -46,6 +46,14 @@ impl Template {
         self.parts.iter().all(|p| matches!(p, Part::Lit(_)))
     }
 
+    /// Returns the names of the variables the template refers to.
+    pub fn variables(&self) -> impl Iterator<Item = &str> {
+        self.parts.iter().filter_map(|part| match part {
+            Part::Expr(expr) => Some(expr.as_ref()),
+            Part::Lit(_) => None,
+        })
+    }
+
     pub(crate) fn parts(&self) -> std::slice::Iter<Part> {
         self.parts.iter()
     }
@@ -9,6 +9,20 @@ pub struct AppInfo {
     pub version: Option<String>,
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub oci_image_digest: Option<String>,
+    /// The app's verified signature, if the host requires signed apps.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub signature: Option<SignatureInfo>,
+}
+
+/// A verified signature of an app.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SignatureInfo {
+    /// The registry reference whose signature was verified.
+    pub reference: String,
+    /// The identity of the signer.
+    pub identity: String,
+    /// The OIDC issuer of the signer's identity.
+    pub issuer: String,
 }
 
 impl AppInfo {
@@ -24,6 +38,7 @@ impl AppInfo {
             name,
             version,
             oci_image_digest,
+            signature: None,
         }
     }
 }
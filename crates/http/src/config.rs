@@ -1,3 +1,5 @@
+use std::collections::BTreeMap;
+
 use serde::{Deserialize, Serialize};
 
 /// Configuration for the HTTP trigger
@@ -8,9 +10,28 @@ pub struct HttpTriggerConfig {
     pub component: String,
     /// HTTP route the component will be invoked for
     pub route: HttpTriggerRouteConfig,
+    /// HTTP methods the route accepts; any method if empty
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub methods: Vec<String>,
+    /// `Host` header values the route accepts, e.g. `example.com` or
+    /// `*.example.com`; any host if empty
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub hosts: Vec<String>,
     /// The HTTP executor the component requires
     #[serde(default)]
     pub executor: Option<HttpExecutorType>,
+    /// Serve the component's files directly instead of invoking the component
+    #[serde(default, rename = "static", skip_serializing_if = "Option::is_none")]
+    pub static_files: Option<StaticFilesConfig>,
+    /// CORS policy applied by the trigger
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cors: Option<CorsConfig>,
+    /// Header rewrite rules applied by the trigger
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub headers: Option<HeaderRulesConfig>,
+    /// Webhook signature verification applied by the trigger
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub webhook: Option<WebhookConfig>,
 }
 
 /// An HTTP trigger route
@@ -83,6 +104,143 @@ impl Default for WagiTriggerConfig {
     }
 }
 
+/// Configuration for serving a route directly from a component's files,
+/// without invoking the component.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct StaticFilesConfig {
+    /// The directory to serve, as a path within the component's files mounts.
+    pub dir: String,
+    /// The file to serve for requests to a directory, if any.
+    pub index: Option<String>,
+    /// The `Cache-Control` header to send with served files, if any.
+    pub cache_control: Option<String>,
+}
+
+impl Default for StaticFilesConfig {
+    fn default() -> Self {
+        Self {
+            dir: "/".into(),
+            index: Some("index.html".into()),
+            cache_control: None,
+        }
+    }
+}
+
+/// Header rewrite rules for requests to and responses from a component.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct HeaderRulesConfig {
+    /// Rules applied to requests before the component receives them.
+    pub request: HeaderRewriteConfig,
+    /// Rules applied to responses before the client receives them.
+    pub response: HeaderRewriteConfig,
+}
+
+/// A set of header rewrites. Removals are applied before values are set.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct HeaderRewriteConfig {
+    /// Headers to set, replacing any existing values.
+    pub set: BTreeMap<String, String>,
+    /// Headers to remove.
+    pub remove: Vec<String>,
+}
+
+/// Webhook signature verification, enforced by the trigger on behalf of the
+/// component. Requests without a valid signature are rejected with 401.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct WebhookConfig {
+    /// The signature scheme used by the webhook's sender.
+    pub scheme: WebhookScheme,
+    /// The signing secret shared with the sender. This is usually a template
+    /// referring to a secret variable, e.g. `"{{ webhook_secret }}"`.
+    pub secret: String,
+    /// How old, in seconds, a signature's timestamp may be, for schemes which
+    /// sign a timestamp.
+    #[serde(default = "default_webhook_tolerance_secs")]
+    pub tolerance_secs: u64,
+    /// The largest body, in bytes, which is read to check its signature.
+    /// Larger requests are rejected with 413.
+    #[serde(default = "default_webhook_max_body_size")]
+    pub max_body_size: usize,
+}
+
+fn default_webhook_tolerance_secs() -> u64 {
+    300
+}
+
+fn default_webhook_max_body_size() -> usize {
+    10 * 1024 * 1024
+}
+
+/// A webhook signature scheme.
+#[derive(Clone, Copy, Debug, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum WebhookScheme {
+    /// GitHub's `X-Hub-Signature-256` header.
+    Github,
+    /// Stripe's `Stripe-Signature` header.
+    Stripe,
+    /// Slack's `X-Slack-Signature` and `X-Slack-Request-Timestamp` headers.
+    Slack,
+}
+
+impl WebhookScheme {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Github => "github",
+            Self::Stripe => "stripe",
+            Self::Slack => "slack",
+        }
+    }
+}
+
+/// A CORS policy, enforced by the trigger on behalf of the component.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CorsConfig {
+    /// Origins allowed to make cross-origin requests, or `"*"` for any origin.
+    pub allowed_origins: Vec<String>,
+    /// Methods allowed in cross-origin requests.
+    pub allowed_methods: Vec<String>,
+    /// Request headers allowed in cross-origin requests, or `"*"` for any header.
+    pub allowed_headers: Vec<String>,
+    /// Response headers exposed to cross-origin callers.
+    pub exposed_headers: Vec<String>,
+    /// How long, in seconds, clients may cache preflight responses.
+    pub max_age: Option<u64>,
+    /// Whether cross-origin requests may include credentials.
+    pub allow_credentials: bool,
+}
+
+impl Default for CorsConfig {
+    fn default() -> Self {
+        Self {
+            allowed_origins: vec![],
+            allowed_methods: ["GET", "HEAD", "POST"].map(String::from).to_vec(),
+            allowed_headers: vec![],
+            exposed_headers: vec![],
+            max_age: None,
+            allow_credentials: false,
+        }
+    }
+}
+
+impl CorsConfig {
+    /// Checks that the policy can be honoured by browsers.
+    pub fn validate(&self) -> anyhow::Result<()> {
+        if self.allow_credentials
+            && (self.allowed_origins.iter().any(|o| o == "*")
+                || self.allowed_headers.iter().any(|h| h == "*"))
+        {
+            anyhow::bail!("CORS `allow_credentials` cannot be combined with a \"*\" wildcard");
+        }
+        Ok(())
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -96,4 +254,19 @@ mod tests {
         assert_eq!(config.entrypoint, "_start");
         assert_eq!(config.argv, "${SCRIPT_NAME} ${ARGS}");
     }
+
+    #[test]
+    fn static_files_config_defaults() {
+        let config: HttpTriggerConfig = toml::toml! {
+            component = "assets"
+            route = "/static/..."
+            static = { dir = "dist", cache_control = "max-age=3600" }
+        }
+        .try_into()
+        .unwrap();
+        let static_files = config.static_files.unwrap();
+        assert_eq!(static_files.dir, "dist");
+        assert_eq!(static_files.index.as_deref(), Some("index.html"));
+        assert_eq!(static_files.cache_control.as_deref(), Some("max-age=3600"));
+    }
 }
@@ -2,18 +2,18 @@
 
 #![deny(missing_docs)]
 
-use anyhow::{anyhow, Result};
-use http::Uri;
+use anyhow::{anyhow, bail, Context, Result};
+use http::{Method, Uri};
 use indexmap::IndexMap;
-use std::{borrow::Cow, fmt};
+use std::{borrow::Cow, collections::HashSet, fmt};
 
-use crate::config::HttpTriggerRouteConfig;
+use crate::config::{HttpTriggerConfig, HttpTriggerRouteConfig};
 
 /// Router for the HTTP trigger.
 #[derive(Clone, Debug)]
 pub struct Router {
-    /// Ordered map between a path and the component ID that should handle it.
-    pub(crate) routes: IndexMap<RoutePattern, String>,
+    /// Ordered map between a route and the component ID that should handle it.
+    pub(crate) routes: IndexMap<Route, String>,
 }
 
 /// A detected duplicate route.
@@ -27,22 +27,83 @@ pub struct DuplicateRoute {
     pub effective_id: String,
 }
 
+/// A successfully routed request.
+#[derive(Debug)]
+pub struct RouteMatch<'a> {
+    /// The ID of the component that should handle the request.
+    pub component_id: &'a str,
+    /// The pattern that matched the request path.
+    pub pattern: &'a RoutePattern,
+    /// The values of named path parameters, in pattern order.
+    pub params: Vec<(String, String)>,
+}
+
+/// The reason a request could not be routed.
+#[derive(Debug, PartialEq, Eq)]
+pub enum RouteError {
+    /// No route matches the request path and host.
+    NotFound,
+    /// Routes match the request path and host, but none accepts its method.
+    /// Contains the methods which are accepted.
+    MethodNotAllowed(Vec<String>),
+}
+
+type RouteItem<'a> = (
+    &'a str,
+    &'a HttpTriggerRouteConfig,
+    &'a [String],
+    &'a [String],
+);
+
 impl Router {
     /// Builds a router based on application configuration.
     pub fn build<'a>(
         base: &str,
         component_routes: impl IntoIterator<Item = (&'a str, &'a HttpTriggerRouteConfig)>,
     ) -> Result<(Self, Vec<DuplicateRoute>)> {
-        let mut routes = IndexMap::new();
+        Self::build_routes(
+            base,
+            component_routes
+                .into_iter()
+                .map(|(component_id, route)| (component_id, route, &[][..], &[][..])),
+        )
+    }
+
+    /// Builds a router from HTTP trigger configurations, including their
+    /// method and host restrictions.
+    pub fn build_from_triggers<'a>(
+        base: &str,
+        triggers: impl IntoIterator<Item = &'a HttpTriggerConfig>,
+    ) -> Result<(Self, Vec<DuplicateRoute>)> {
+        Self::build_routes(
+            base,
+            triggers.into_iter().map(|trigger| {
+                (
+                    trigger.component.as_str(),
+                    &trigger.route,
+                    trigger.methods.as_slice(),
+                    trigger.hosts.as_slice(),
+                )
+            }),
+        )
+    }
+
+    fn build_routes<'a>(
+        base: &str,
+        component_routes: impl IntoIterator<Item = RouteItem<'a>>,
+    ) -> Result<(Self, Vec<DuplicateRoute>)> {
+        let mut routes: IndexMap<Route, String> = IndexMap::new();
         let mut duplicates = vec![];
 
         let routes_iter = component_routes
             .into_iter()
-            .filter_map(|(component_id, route)| {
+            .filter_map(|(component_id, route, methods, hosts)| {
                 match route {
-                    HttpTriggerRouteConfig::Route(r) => {
-                        Some(Ok((RoutePattern::from(base, r), component_id.to_string())))
-                    }
+                    HttpTriggerRouteConfig::Route(r) => Some(
+                        Route::new(RoutePattern::from(base, r), methods, hosts)
+                            .with_context(|| format!("invalid route for component '{component_id}'"))
+                            .map(|route| (route, component_id.to_string())),
+                    ),
                     HttpTriggerRouteConfig::IsRoutable(false) => None,
                     HttpTriggerRouteConfig::IsRoutable(true) => Some(Err(anyhow!("route must be a string pattern or 'false': component '{component_id}' has route = 'true'"))),
                 }
@@ -50,10 +111,18 @@ impl Router {
             .collect::<Result<Vec<_>>>()?;
 
         for (route, component_id) in routes_iter {
+            if let Some((existing, existing_id)) = routes
+                .iter()
+                .find(|(existing, _)| **existing != route && existing.conflicts_with(&route))
+            {
+                bail!(
+                    "route {route} for component '{component_id}' conflicts with route {existing} for component '{existing_id}'"
+                );
+            }
             let replaced = routes.insert(route.clone(), component_id.clone());
             if let Some(replaced) = replaced {
                 duplicates.push(DuplicateRoute {
-                    route: route.clone(),
+                    route: route.pattern.clone(),
                     replaced_id: replaced,
                     effective_id: component_id.clone(),
                 });
@@ -65,34 +134,70 @@ impl Router {
 
     /// Returns the constructed routes.
     pub fn routes(&self) -> impl Iterator<Item = (&RoutePattern, &String)> {
-        self.routes.iter()
+        self.routes.iter().map(|(route, id)| (&route.pattern, id))
     }
 
-    /// This returns the component id and route pattern for a matched route.
-    pub fn route_full(&self, p: &str) -> Result<(&str, &RoutePattern)> {
-        let matches = self.routes.iter().filter(|(rp, _)| rp.matches(p));
-
-        let mut best_match: (Option<&str>, Option<&RoutePattern>, usize) = (None, None, 0); // matched id, pattern and length
-
-        for (rp, id) in matches {
-            match rp {
-                RoutePattern::Exact(_m) => {
-                    // Exact matching routes take precedence over wildcard matches.
-                    return Ok((id, rp));
-                }
-                RoutePattern::Wildcard(m) => {
-                    // Check and find longest matching prefix of wildcard pattern.
-                    let (_id_opt, _rp_opt, len) = best_match;
-                    if m.len() >= len {
-                        best_match = (Some(id), Some(rp), m.len());
-                    }
+    /// Routes a request by its method, `Host` header and path. Method or host
+    /// restrictions are not checked if `method` or `host` is `None`.
+    ///
+    /// If multiple routes match, exact routes take precedence over wildcards,
+    /// then routes with more segments, then routes with more literal (non-
+    /// parameter) segments, then routes restricted by host, then routes
+    /// restricted by method.
+    pub fn route_request(
+        &self,
+        method: Option<&str>,
+        host: Option<&str>,
+        path: &str,
+    ) -> Result<RouteMatch<'_>, RouteError> {
+        let mut best_match: Option<(Specificity, RouteMatch)> = None;
+        let mut allowed_methods = vec![];
+        let mut path_matched = false;
+
+        for (route, component_id) in &self.routes {
+            let Some(params) = route.pattern.match_params(path) else {
+                continue;
+            };
+            if host.is_some_and(|host| !route.accepts_host(host)) {
+                continue;
+            }
+            path_matched = true;
+            if let Some(method) = method {
+                if !route.accepts_method(method) {
+                    allowed_methods.extend(route.methods.iter().cloned());
+                    continue;
                 }
             }
+            let specificity = route.specificity();
+            if !matches!(&best_match, Some((best, _)) if specificity < *best) {
+                best_match = Some((
+                    specificity,
+                    RouteMatch {
+                        component_id,
+                        pattern: &route.pattern,
+                        params,
+                    },
+                ));
+            }
         }
 
-        let (id, rp, _) = best_match;
-        id.zip(rp)
-            .ok_or_else(|| anyhow!("Cannot match route for path {p}"))
+        match best_match {
+            Some((_, route_match)) => Ok(route_match),
+            None if path_matched => {
+                allowed_methods.sort();
+                allowed_methods.dedup();
+                Err(RouteError::MethodNotAllowed(allowed_methods))
+            }
+            None => Err(RouteError::NotFound),
+        }
+    }
+
+    /// This returns the component id and route pattern for a matched route,
+    /// ignoring any method or host restrictions.
+    pub fn route_full(&self, p: &str) -> Result<(&str, &RoutePattern)> {
+        self.route_request(None, None, p)
+            .map(|route_match| (route_match.component_id, route_match.pattern))
+            .map_err(|_| anyhow!("Cannot match route for path {p}"))
     }
 
     /// This returns the component ID that should handle the given path, or an error
@@ -106,6 +211,151 @@ impl Router {
     }
 }
 
+/// A route pattern, optionally restricted to some HTTP methods and hosts.
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct Route {
+    /// The path pattern.
+    pub pattern: RoutePattern,
+    /// The upper-case methods the route accepts, or empty for any method.
+    pub methods: Vec<String>,
+    /// The lower-case hosts the route accepts, or empty for any host. A
+    /// leading `*.` matches any subdomain.
+    pub hosts: Vec<String>,
+}
+
+// Exact beats wildcard, then segments, literal segments, hosts, methods
+type Specificity = (bool, usize, usize, bool, bool);
+
+impl Route {
+    /// Creates a route, validating and normalizing its restrictions.
+    pub fn new(pattern: RoutePattern, methods: &[String], hosts: &[String]) -> Result<Self> {
+        let mut param_names = HashSet::new();
+        for name in pattern.param_names() {
+            if name.is_empty()
+                || !name
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+            {
+                bail!("invalid path parameter name {name:?}: names may contain only letters, digits, '_' and '-'");
+            }
+            if !param_names.insert(name.to_ascii_lowercase()) {
+                bail!("path parameter {name:?} appears more than once");
+            }
+        }
+
+        let mut methods = methods
+            .iter()
+            .map(|method| {
+                Method::from_bytes(method.to_ascii_uppercase().as_bytes())
+                    .map(|method| method.to_string())
+                    .with_context(|| format!("invalid HTTP method {method:?}"))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        methods.sort();
+        methods.dedup();
+
+        let mut hosts = hosts
+            .iter()
+            .map(|host| {
+                let host = host.trim().to_ascii_lowercase();
+                if host.is_empty() || host == "*." || host.contains('/') {
+                    bail!("invalid host {host:?}");
+                }
+                Ok(host)
+            })
+            .collect::<Result<Vec<_>>>()?;
+        hosts.sort();
+        hosts.dedup();
+
+        Ok(Self {
+            pattern,
+            methods,
+            hosts,
+        })
+    }
+
+    /// Returns true if the route accepts the given method.
+    pub fn accepts_method(&self, method: &str) -> bool {
+        self.methods.is_empty() || self.methods.iter().any(|m| m.eq_ignore_ascii_case(method))
+    }
+
+    /// Returns true if the route accepts the given `Host` header value, which
+    /// may include a port.
+    pub fn accepts_host(&self, host: &str) -> bool {
+        if self.hosts.is_empty() {
+            return true;
+        }
+        let host = strip_port(host).to_ascii_lowercase();
+        self.hosts
+            .iter()
+            .any(|pattern| match pattern.strip_prefix("*.") {
+                Some(domain) => host
+                    .strip_suffix(domain)
+                    .is_some_and(|subdomain| subdomain.len() > 1 && subdomain.ends_with('.')),
+                None => *pattern == host,
+            })
+    }
+
+    // Two routes conflict if they could match the same requests with the
+    // same precedence.
+    fn conflicts_with(&self, other: &Route) -> bool {
+        fn overlap(a: &[String], b: &[String]) -> bool {
+            (a.is_empty() && b.is_empty()) || a.iter().any(|item| b.contains(item))
+        }
+        self.pattern.shape() == other.pattern.shape()
+            && overlap(&self.methods, &other.methods)
+            && overlap(&self.hosts, &other.hosts)
+    }
+
+    fn specificity(&self) -> Specificity {
+        let segments = self.pattern.path_or_prefix().split('/');
+        let (count, literal) = segments.fold((0, 0), |(count, literal), segment| {
+            (count + 1, literal + usize::from(!is_param(segment)))
+        });
+        (
+            matches!(self.pattern, RoutePattern::Exact(_)),
+            count,
+            literal,
+            !self.hosts.is_empty(),
+            !self.methods.is_empty(),
+        )
+    }
+}
+
+impl From<RoutePattern> for Route {
+    fn from(pattern: RoutePattern) -> Self {
+        Self {
+            pattern,
+            methods: vec![],
+            hosts: vec![],
+        }
+    }
+}
+
+impl fmt::Display for Route {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.pattern.full_pattern_non_empty())?;
+        if !self.methods.is_empty() {
+            write!(f, " [{}]", self.methods.join(", "))?;
+        }
+        if !self.hosts.is_empty() {
+            write!(f, " on {}", self.hosts.join(", "))?;
+        }
+        Ok(())
+    }
+}
+
+fn strip_port(host: &str) -> &str {
+    match host.rsplit_once(':') {
+        Some((host, port)) if port.bytes().all(|b| b.is_ascii_digit()) => host,
+        _ => host,
+    }
+}
+
+fn is_param(segment: &str) -> bool {
+    segment.len() > 1 && segment.starts_with(':')
+}
+
 /// Route patterns for HTTP components.
 #[derive(Clone, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
 pub enum RoutePattern {
@@ -128,27 +378,59 @@ impl RoutePattern {
     /// Returns true if the given path fragment can be handled
     /// by the route pattern.
     pub fn matches<S: Into<String>>(&self, p: S) -> bool {
+        self.match_params(&p.into()).is_some()
+    }
+
+    /// Matches a path against the pattern, returning the values of any named
+    /// parameters (`:name` segments), or `None` if the path does not match.
+    pub fn match_params(&self, p: &str) -> Option<Vec<(String, String)>> {
         let p = Self::sanitize(p);
-        match self {
-            RoutePattern::Exact(path) => &p == path,
-            RoutePattern::Wildcard(pattern) => {
-                &p == pattern || p.starts_with(&format!("{}/", pattern))
+        let mut path_segments = p.split('/');
+        let mut params = vec![];
+        for pattern_segment in self.path_or_prefix().split('/') {
+            let segment = path_segments.next()?;
+            if is_param(pattern_segment) {
+                if segment.is_empty() {
+                    return None;
+                }
+                params.push((pattern_segment[1..].to_owned(), segment.to_owned()));
+            } else if segment != pattern_segment {
+                return None;
             }
         }
+        match self {
+            Self::Exact(_) if path_segments.next().is_some() => None,
+            _ => Some(params),
+        }
     }
 
     /// Resolves a relative path from the end of the matched path to the end of the string.
     pub fn relative(&self, uri: &str) -> Result<String> {
-        let base = match self {
-            Self::Exact(path) => path,
-            Self::Wildcard(prefix) => prefix,
-        };
-        Ok(uri
-            .parse::<Uri>()?
-            .path()
-            .strip_prefix(base)
-            .unwrap_or_default()
-            .to_owned())
+        let uri = uri.parse::<Uri>()?;
+        let matched = self.path_or_prefix().split('/').count();
+        let rest = uri.path().split('/').skip(matched).collect::<Vec<_>>();
+        if rest.is_empty() {
+            return Ok(String::new());
+        }
+        Ok(format!("/{}", rest.join("/")))
+    }
+
+    /// The names of the pattern's path parameters.
+    pub fn param_names(&self) -> impl Iterator<Item = &str> {
+        self.path_or_prefix()
+            .split('/')
+            .filter(|segment| segment.starts_with(':'))
+            .map(|segment| &segment[1..])
+    }
+
+    // The pattern with parameter names erased, for detecting conflicts.
+    fn shape(&self) -> (bool, Vec<&str>) {
+        let segments = self
+            .path_or_prefix()
+            .split('/')
+            .map(|segment| if is_param(segment) { ":" } else { segment })
+            .collect();
+        (matches!(self, Self::Wildcard(_)), segments)
     }
 
     /// The full path (for Exact) or prefix (for Wildcard).
@@ -331,8 +613,11 @@ mod route_tests {
     fn test_router() -> Result<()> {
         let mut routes = IndexMap::new();
 
-        routes.insert(RoutePattern::from("/", "/foo"), "foo".to_string());
-        routes.insert(RoutePattern::from("/", "/foo/bar"), "foobar".to_string());
+        routes.insert(RoutePattern::from("/", "/foo").into(), "foo".to_string());
+        routes.insert(
+            RoutePattern::from("/", "/foo/bar").into(),
+            "foobar".to_string(),
+        );
 
         let r = Router { routes };
 
@@ -341,9 +626,12 @@ mod route_tests {
 
         let mut routes = IndexMap::new();
 
-        routes.insert(RoutePattern::from("/base", "/foo"), "foo".to_string());
         routes.insert(
-            RoutePattern::from("/base", "/foo/bar"),
+            RoutePattern::from("/base", "/foo").into(),
+            "foo".to_string(),
+        );
+        routes.insert(
+            RoutePattern::from("/base", "/foo/bar").into(),
             "foobar".to_string(),
         );
 
@@ -354,7 +642,7 @@ mod route_tests {
 
         let mut routes = IndexMap::new();
 
-        routes.insert(RoutePattern::from("/", "/..."), "all".to_string());
+        routes.insert(RoutePattern::from("/", "/...").into(), "all".to_string());
 
         let r = Router { routes };
 
@@ -369,15 +657,15 @@ mod route_tests {
         let mut routes = IndexMap::new();
 
         routes.insert(
-            RoutePattern::from("/", "/one/..."),
+            RoutePattern::from("/", "/one/...").into(),
             "one_wildcard".to_string(),
         );
         routes.insert(
-            RoutePattern::from("/", "/one/two/..."),
+            RoutePattern::from("/", "/one/two/...").into(),
             "onetwo_wildcard".to_string(),
         );
         routes.insert(
-            RoutePattern::from("/", "/one/two/three/..."),
+            RoutePattern::from("/", "/one/two/three/...").into(),
             "onetwothree_wildcard".to_string(),
         );
 
@@ -391,15 +679,15 @@ mod route_tests {
         let mut routes = IndexMap::new();
 
         routes.insert(
-            RoutePattern::from("/", "/one/two/three/..."),
+            RoutePattern::from("/", "/one/two/three/...").into(),
             "onetwothree_wildcard".to_string(),
         );
         routes.insert(
-            RoutePattern::from("/", "/one/two/..."),
+            RoutePattern::from("/", "/one/two/...").into(),
             "onetwo_wildcard".to_string(),
         );
         routes.insert(
-            RoutePattern::from("/", "/one/..."),
+            RoutePattern::from("/", "/one/...").into(),
             "one_wildcard".to_string(),
         );
 
@@ -413,9 +701,15 @@ mod route_tests {
         // Test routing rule "exact beats wildcard" ...
         let mut routes = IndexMap::new();
 
-        routes.insert(RoutePattern::from("/", "/one"), "one_exact".to_string());
+        routes.insert(
+            RoutePattern::from("/", "/one").into(),
+            "one_exact".to_string(),
+        );
 
-        routes.insert(RoutePattern::from("/", "/..."), "wildcard".to_string());
+        routes.insert(
+            RoutePattern::from("/", "/...").into(),
+            "wildcard".to_string(),
+        );
 
         let r = Router { routes };
 
@@ -527,4 +821,95 @@ mod route_tests {
 
         assert!(e.to_string().contains("bad component"));
     }
+
+    fn trigger(
+        component: &str,
+        route: &str,
+        methods: &[&str],
+        hosts: &[&str],
+    ) -> HttpTriggerConfig {
+        HttpTriggerConfig {
+            component: component.into(),
+            route: route.into(),
+            methods: methods.iter().map(|m| m.to_string()).collect(),
+            hosts: hosts.iter().map(|h| h.to_string()).collect(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn path_parameters_are_captured() {
+        let rp = RoutePattern::from("/", "/users/:id/posts/:post");
+        assert_eq!(
+            rp.match_params("/users/42/posts/hello/"),
+            Some(vec![
+                ("id".to_string(), "42".to_string()),
+                ("post".to_string(), "hello".to_string())
+            ])
+        );
+        assert!(!rp.matches("/users/42/posts"));
+        assert!(!rp.matches("/users//posts/hello"));
+
+        let rp = RoutePattern::from("/", "/files/:bucket/...");
+        assert!(rp.matches("/files/a/b/c"));
+        assert_eq!(rp.relative("/files/a/b/c").unwrap(), "/b/c");
+    }
+
+    #[test]
+    fn literal_segments_beat_parameters() {
+        let triggers = [
+            trigger("user", "/users/:id", &[], &[]),
+            trigger("me", "/users/me", &[], &[]),
+        ];
+        let (router, _) = Router::build_from_triggers("/", &triggers).unwrap();
+        assert_eq!(router.route("/users/me").unwrap(), "me");
+        assert_eq!(router.route("/users/42").unwrap(), "user");
+    }
+
+    #[test]
+    fn methods_and_hosts_are_matched() {
+        let triggers = [
+            trigger("read", "/items", &["get", "HEAD"], &[]),
+            trigger("write", "/items", &["POST"], &[]),
+            trigger("tenant", "/pages/...", &[], &["*.example.com"]),
+            trigger("fallback", "/pages/...", &[], &[]),
+        ];
+        let (router, _) = Router::build_from_triggers("/", &triggers).unwrap();
+        let route = |method, host, path| {
+            router
+                .route_request(Some(method), Some(host), path)
+                .map(|m| m.component_id)
+        };
+
+        assert_eq!(route("GET", "localhost:3000", "/items"), Ok("read"));
+        assert_eq!(route("POST", "localhost", "/items"), Ok("write"));
+        assert_eq!(
+            route("DELETE", "localhost", "/items"),
+            Err(RouteError::MethodNotAllowed(vec![
+                "GET".into(),
+                "HEAD".into(),
+                "POST".into()
+            ]))
+        );
+        assert_eq!(route("GET", "a.example.com:443", "/pages/x"), Ok("tenant"));
+        assert_eq!(route("GET", "example.com", "/pages/x"), Ok("fallback"));
+    }
+
+    #[test]
+    fn overlapping_routes_are_rejected() {
+        for triggers in [
+            [
+                trigger("a", "/users/:id", &[], &[]),
+                trigger("b", "/users/:name", &[], &[]),
+            ],
+            [
+                trigger("a", "/items", &["GET", "POST"], &[]),
+                trigger("b", "/items", &["GET"], &[]),
+            ],
+        ] {
+            let e = Router::build_from_triggers("/", &triggers).expect_err("should conflict");
+            assert!(e.to_string().contains("conflicts with"), "{e}");
+        }
+        assert!(Router::build_from_triggers("/", &[trigger("a", "/:x/:x", &[], &[])]).is_err());
+    }
 }
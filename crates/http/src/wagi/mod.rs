@@ -9,6 +9,7 @@ use http::{
     request::Parts,
     HeaderMap, HeaderValue, Response, StatusCode,
 };
+use hyper::body::Bytes;
 
 use crate::{body, routes::RoutePattern, Body};
 
@@ -213,33 +214,16 @@ fn parse_host_header_uri(
     (host, port)
 }
 
-pub fn compose_response(stdout: &[u8]) -> Result<Response<Body>, Error> {
+pub fn compose_response(stdout: Bytes) -> Result<Response<Body>, Error> {
     // Okay, once we get here, all the information we need to send back in the response
     // should be written to the STDOUT buffer. We fetch that, format it, and send
     // it back. In the process, we might need to alter the status code of the result.
     //
-    // This is a little janky, but basically we are looping through the output once,
-    // looking for the double-newline that distinguishes the headers from the body.
-    // The headers can then be parsed separately, while the body can be sent back
-    // to the client.
-    let mut last = 0;
-    let mut scan_headers = true;
-    let mut buffer: Vec<u8> = Vec::new();
-    let mut out_headers: Vec<u8> = Vec::new();
-    stdout.iter().for_each(|i| {
-        // Ignore CR in headers
-        if scan_headers && *i == 13 {
-            return;
-        } else if scan_headers && *i == 10 && last == 10 {
-            out_headers.append(&mut buffer);
-            buffer = Vec::new();
-            scan_headers = false;
-            return; // Consume the linefeed
-        }
-        last = *i;
-        buffer.push(*i)
-    });
-    let mut res = Response::new(body::full(buffer.into()));
+    // We look for the double-newline that distinguishes the headers from the body.
+    // The headers can then be parsed separately, while the body is sent back
+    // to the client as a slice of the output, without copying it.
+    let (out_headers, buffer) = split_output(stdout);
+    let mut res = Response::new(body::full(buffer));
     let mut sufficient_response = false;
     let mut explicit_status_code = false;
     parse_cgi_headers(String::from_utf8(out_headers)?)
@@ -304,6 +288,37 @@ pub fn compose_response(stdout: &[u8]) -> Result<Response<Body>, Error> {
     Ok(res)
 }
 
+// Splits the output into its headers, with CRs removed, and its body.
+fn split_output(stdout: Bytes) -> (Vec<u8>, Bytes) {
+    match headers_end(&stdout) {
+        Some(end) => {
+            // Ignore CR in headers
+            let headers = stdout[..end].iter().copied().filter(|b| *b != b'\r');
+            (headers.collect(), stdout.slice(end + 1..))
+        }
+        None if !stdout.contains(&b'\r') => (Vec::new(), stdout),
+        None => {
+            // Without a blank line, all of the output is scanned as headers,
+            // so its CRs are ignored too
+            let body: Vec<u8> = stdout.iter().copied().filter(|b| *b != b'\r').collect();
+            (Vec::new(), body.into())
+        }
+    }
+}
+
+// Returns the index of the linefeed which ends the headers, ignoring CRs, if any.
+fn headers_end(stdout: &[u8]) -> Option<usize> {
+    let mut last = 0;
+    stdout.iter().position(|b| {
+        if *b == b'\r' {
+            return false;
+        }
+        let end = *b == b'\n' && last == b'\n';
+        last = *b;
+        end
+    })
+}
+
 fn parse_cgi_headers(headers: String) -> HashMap<String, String> {
     let mut map = HashMap::new();
     headers.trim().split('\n').for_each(|h| {
@@ -325,3 +340,26 @@ fn internal_error(msg: impl std::string::ToString) -> Response<Body> {
     *res.status_mut() = StatusCode::INTERNAL_SERVER_ERROR;
     res
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn body_follows_headers() {
+        let stdout = b"Content-Type: text/plain\r\nStatus: 201\r\n\r\nline one\r\n\r\nline two";
+        let end = headers_end(stdout).unwrap();
+        assert_eq!(&stdout[end + 1..], b"line one\r\n\r\nline two");
+
+        let res = compose_response(Bytes::from_static(stdout)).unwrap();
+        assert_eq!(res.status(), StatusCode::CREATED);
+        assert_eq!(res.headers()["content-type"], "text/plain");
+    }
+
+    #[test]
+    fn output_without_headers_has_crs_removed() {
+        let (headers, body) = split_output(Bytes::from_static(b"line one\r\nline two"));
+        assert!(headers.is_empty());
+        assert_eq!(body, "line one\nline two");
+    }
+}
@@ -54,6 +54,11 @@ impl HostComponent for KeyValueComponent {
     fn build_data(&self) -> Self::Data {
         KeyValueDispatch::new_with_capacity(self.capacity)
     }
+
+    fn reset_data(&self, data: &mut Self::Data) -> bool {
+        data.reset();
+        true
+    }
 }
 
 impl DynamicHostComponent for KeyValueComponent {
@@ -9,7 +9,9 @@ mod host_component;
 mod util;
 
 pub use host_component::{manager, KeyValueComponent};
-pub use util::{CachingStoreManager, DelegatingStoreManager, EmptyStoreManager};
+pub use util::{
+    CachingStoreManager, DelegatingStoreManager, EmptyStoreManager, EncryptingStoreManager,
+};
 
 pub const KEY_VALUE_STORES_KEY: MetadataKey<Vec<String>> = MetadataKey::new("key_value_stores");
 
@@ -56,6 +58,14 @@ impl KeyValueDispatch {
         self.manager = manager;
     }
 
+    /// Closes all open stores and forgets the allowed stores, leaving the
+    /// dispatch as if newly created but keeping its allocations.
+    pub fn reset(&mut self) {
+        self.allowed_stores.clear();
+        self.manager = Arc::new(EmptyStoreManager);
+        self.stores.clear();
+    }
+
     pub fn get_store(&self, store: Resource<key_value::Store>) -> anyhow::Result<&Arc<dyn Store>> {
         self.stores.get(store.rep()).context("invalid store")
     }
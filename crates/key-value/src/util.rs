@@ -1,6 +1,7 @@
-use crate::{Error, Store, StoreManager};
+use crate::{log_error, Error, Store, StoreManager};
 use lru::LruCache;
 use spin_core::async_trait;
+use spin_keys::{DataKey, KeyProvider};
 use std::{
     collections::{HashMap, HashSet},
     future::Future,
@@ -8,7 +9,7 @@ use std::{
     sync::Arc,
 };
 use tokio::{
-    sync::Mutex as AsyncMutex,
+    sync::{Mutex as AsyncMutex, OnceCell},
     task::{self, JoinHandle},
 };
 use tracing::Instrument;
@@ -240,3 +241,90 @@ impl Store for CachingStore {
             .collect())
     }
 }
+
+/// Wrap each `Store` produced by the inner `StoreManager` so that values are encrypted at rest with the key from
+/// a [`KeyProvider`], which is fetched when a store is first opened.
+///
+/// Keys are not encrypted. Each value is bound to its store and key, so that a value copied to another key fails
+/// to decrypt rather than being read as that key's value.
+pub struct EncryptingStoreManager<T> {
+    inner: T,
+    provider: Arc<dyn KeyProvider>,
+    key: OnceCell<Arc<DataKey>>,
+}
+
+impl<T> EncryptingStoreManager<T> {
+    pub fn new(inner: T, provider: Arc<dyn KeyProvider>) -> Self {
+        Self {
+            inner,
+            provider,
+            key: OnceCell::new(),
+        }
+    }
+}
+
+#[async_trait]
+impl<T: StoreManager> StoreManager for EncryptingStoreManager<T> {
+    async fn get(&self, name: &str) -> Result<Arc<dyn Store>, Error> {
+        let key = self
+            .key
+            .get_or_try_init(|| async { self.provider.key().await.map(Arc::new) })
+            .await
+            .map_err(log_error)?;
+        Ok(Arc::new(EncryptingStore {
+            inner: self.inner.get(name).await?,
+            name: name.to_owned(),
+            key: key.clone(),
+        }))
+    }
+
+    fn is_defined(&self, store_name: &str) -> bool {
+        self.inner.is_defined(store_name)
+    }
+}
+
+struct EncryptingStore {
+    inner: Arc<dyn Store>,
+    name: String,
+    key: Arc<DataKey>,
+}
+
+impl EncryptingStore {
+    fn context(&self, key: &str) -> Vec<u8> {
+        [self.name.as_bytes(), b"\0", key.as_bytes()].concat()
+    }
+}
+
+#[async_trait]
+impl Store for EncryptingStore {
+    async fn get(&self, key: &str) -> Result<Option<Vec<u8>>, Error> {
+        match self.inner.get(key).await? {
+            Some(sealed) => self
+                .key
+                .open(&self.context(key), &sealed)
+                .map(Some)
+                .map_err(log_error),
+            None => Ok(None),
+        }
+    }
+
+    async fn set(&self, key: &str, value: &[u8]) -> Result<(), Error> {
+        let sealed = self
+            .key
+            .seal(&self.context(key), value)
+            .map_err(log_error)?;
+        self.inner.set(key, &sealed).await
+    }
+
+    async fn delete(&self, key: &str) -> Result<(), Error> {
+        self.inner.delete(key).await
+    }
+
+    async fn exists(&self, key: &str) -> Result<bool, Error> {
+        self.inner.exists(key).await
+    }
+
+    async fn get_keys(&self) -> Result<Vec<String>, Error> {
+        self.inner.get_keys().await
+    }
+}
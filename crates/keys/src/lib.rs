@@ -0,0 +1,118 @@
+//! Keys which encrypt data at rest.
+//!
+//! Features which encrypt data, such as encrypted key-value stores, get
+//! their keys from a [`KeyProvider`], so that where keys are kept is
+//! configured once, in the `[keys]` runtime config table, rather than by
+//! options of each feature's own.
+
+pub mod provider;
+
+use anyhow::{anyhow, bail, Result};
+use async_trait::async_trait;
+use ring::{
+    aead::{Aad, LessSafeKey, Nonce, UnboundKey, CHACHA20_POLY1305, NONCE_LEN},
+    rand::{SecureRandom, SystemRandom},
+};
+use zeroize::Zeroizing;
+
+/// A source of a [`DataKey`].
+#[async_trait]
+pub trait KeyProvider: std::fmt::Debug + Send + Sync {
+    /// Returns the key.
+    async fn key(&self) -> Result<DataKey>;
+}
+
+/// A key which encrypts data with ChaCha20-Poly1305.
+pub struct DataKey(LessSafeKey);
+
+impl DataKey {
+    /// The length of a key, in bytes.
+    pub const LEN: usize = 32;
+
+    /// Makes a key from its bytes.
+    pub fn new(bytes: &[u8]) -> Result<Self> {
+        if bytes.len() != Self::LEN {
+            bail!(
+                "Keys must be {} bytes long, not {} bytes",
+                Self::LEN,
+                bytes.len()
+            );
+        }
+        let key = UnboundKey::new(&CHACHA20_POLY1305, bytes).map_err(|_| anyhow!("Invalid key"))?;
+        Ok(Self(LessSafeKey::new(key)))
+    }
+
+    /// Generates the bytes of a new key.
+    pub fn generate() -> Result<Zeroizing<Vec<u8>>> {
+        let mut bytes = Zeroizing::new(vec![0; Self::LEN]);
+        SystemRandom::new()
+            .fill(&mut bytes)
+            .map_err(|_| anyhow!("Failed to generate key"))?;
+        Ok(bytes)
+    }
+
+    /// Encrypts data, binding it to `context`, such as the name it is saved
+    /// under, which must be given again to decrypt it. Returns the nonce
+    /// followed by the encrypted data.
+    pub fn seal(&self, context: &[u8], data: &[u8]) -> Result<Vec<u8>> {
+        let mut nonce = [0; NONCE_LEN];
+        SystemRandom::new()
+            .fill(&mut nonce)
+            .map_err(|_| anyhow!("Failed to generate nonce"))?;
+        let mut sealed = data.to_vec();
+        self.0
+            .seal_in_place_append_tag(
+                Nonce::assume_unique_for_key(nonce),
+                Aad::from(context),
+                &mut sealed,
+            )
+            .map_err(|_| anyhow!("Failed to encrypt data"))?;
+        Ok([&nonce[..], &sealed].concat())
+    }
+
+    /// Decrypts data encrypted by [`DataKey::seal`] with the same context.
+    pub fn open(&self, context: &[u8], sealed: &[u8]) -> Result<Vec<u8>> {
+        if sealed.len() < NONCE_LEN {
+            bail!("Encrypted data is truncated");
+        }
+        let (nonce, ciphertext) = sealed.split_at(NONCE_LEN);
+        let nonce =
+            Nonce::try_assume_unique_for_key(nonce).map_err(|_| anyhow!("Invalid nonce"))?;
+        let mut data = ciphertext.to_vec();
+        let len = self
+            .0
+            .open_in_place(nonce, Aad::from(context), &mut data)
+            .map_err(|_| anyhow!("Encrypted data does not match key"))?
+            .len();
+        data.truncate(len);
+        Ok(data)
+    }
+}
+
+impl std::fmt::Debug for DataKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("DataKey(<redacted>)")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sealed_data_is_bound_to_context() {
+        let key = DataKey::new(&DataKey::generate().unwrap()).unwrap();
+        let sealed = key.seal(b"name", b"hunter2").unwrap();
+        assert_ne!(&sealed[NONCE_LEN..], b"hunter2");
+        assert_eq!(key.open(b"name", &sealed).unwrap(), b"hunter2");
+        key.open(b"other-name", &sealed).unwrap_err();
+
+        let other_key = DataKey::new(&DataKey::generate().unwrap()).unwrap();
+        other_key.open(b"name", &sealed).unwrap_err();
+    }
+
+    #[test]
+    fn keys_must_be_full_length() {
+        DataKey::new(&[0; 16]).unwrap_err();
+    }
+}
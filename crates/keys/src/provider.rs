@@ -0,0 +1,4 @@
+pub mod aws_kms;
+pub mod azure_key_vault;
+pub mod env;
+pub mod file;
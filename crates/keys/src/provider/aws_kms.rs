@@ -0,0 +1,65 @@
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use aws_sdk_kms::primitives::Blob;
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+
+use crate::{DataKey, KeyProvider};
+
+/// A data key encrypted by an AWS KMS key, which KMS decrypts when the key
+/// is needed. The data key is made with
+/// `aws kms generate-data-key --key-spec AES_256`, and its base64-encoded
+/// `CiphertextBlob` configured.
+///
+/// AWS credentials are read from the usual places: the environment, the
+/// shared configuration files, or the instance's role.
+#[derive(Debug)]
+pub struct AwsKmsKeyProvider {
+    key_id: String,
+    ciphertext: String,
+    region: Option<String>,
+}
+
+impl AwsKmsKeyProvider {
+    /// Creates a new AwsKmsKeyProvider.
+    pub fn new(
+        key_id: impl Into<String>,
+        ciphertext: impl Into<String>,
+        region: Option<impl Into<String>>,
+    ) -> Self {
+        Self {
+            key_id: key_id.into(),
+            ciphertext: ciphertext.into(),
+            region: region.map(Into::into),
+        }
+    }
+}
+
+#[async_trait]
+impl KeyProvider for AwsKmsKeyProvider {
+    async fn key(&self) -> Result<DataKey> {
+        let mut loader = aws_config::defaults(aws_config::BehaviorVersion::latest());
+        if let Some(region) = &self.region {
+            loader = loader.region(aws_config::Region::new(region.clone()));
+        }
+        let client = aws_sdk_kms::Client::new(&loader.load().await);
+        let ciphertext = BASE64
+            .decode(self.ciphertext.trim())
+            .context("The AWS KMS data key ciphertext is not base64")?;
+        let output = client
+            .decrypt()
+            .key_id(&self.key_id)
+            .ciphertext_blob(Blob::new(ciphertext))
+            .send()
+            .await
+            .with_context(|| {
+                format!(
+                    "Failed to decrypt data key with AWS KMS key {}",
+                    self.key_id
+                )
+            })?;
+        let plaintext = output
+            .plaintext()
+            .context("AWS KMS did not return the data key")?;
+        DataKey::new(plaintext.as_ref()).context("Invalid AWS KMS data key")
+    }
+}
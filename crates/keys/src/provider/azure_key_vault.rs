@@ -0,0 +1,52 @@
+use std::sync::Arc;
+
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use azure_identity::DefaultAzureCredential;
+use azure_security_keyvault::SecretClient;
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use zeroize::Zeroizing;
+
+use crate::{DataKey, KeyProvider};
+
+/// A key kept in an Azure Key Vault secret, base64-encoded.
+///
+/// Azure credentials are read from the usual places: the environment, a
+/// managed identity, or the Azure CLI.
+#[derive(Debug)]
+pub struct AzureKeyVaultKeyProvider {
+    vault_url: String,
+    secret: String,
+}
+
+impl AzureKeyVaultKeyProvider {
+    /// Creates a new AzureKeyVaultKeyProvider.
+    pub fn new(vault_url: impl Into<String>, secret: impl Into<String>) -> Self {
+        Self {
+            vault_url: vault_url.into(),
+            secret: secret.into(),
+        }
+    }
+}
+
+#[async_trait]
+impl KeyProvider for AzureKeyVaultKeyProvider {
+    async fn key(&self) -> Result<DataKey> {
+        let credential = Arc::new(DefaultAzureCredential::default());
+        let client = SecretClient::new(&self.vault_url, credential)
+            .with_context(|| format!("Invalid Azure Key Vault URL {}", self.vault_url))?;
+        let secret = client.get(&self.secret).await.with_context(|| {
+            format!(
+                "Failed to read secret {} from Azure Key Vault {}",
+                self.secret, self.vault_url
+            )
+        })?;
+        let encoded = Zeroizing::new(secret.value);
+        let bytes = Zeroizing::new(
+            BASE64
+                .decode(encoded.trim())
+                .context("The key in Azure Key Vault is not base64")?,
+        );
+        DataKey::new(&bytes).context("Invalid key in Azure Key Vault")
+    }
+}
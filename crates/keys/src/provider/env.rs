@@ -0,0 +1,38 @@
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use zeroize::Zeroizing;
+
+use crate::{DataKey, KeyProvider};
+
+/// A key in an environment variable, base64-encoded.
+#[derive(Debug)]
+pub struct EnvKeyProvider {
+    variable: String,
+}
+
+impl EnvKeyProvider {
+    /// Creates a new EnvKeyProvider.
+    pub fn new(variable: impl Into<String>) -> Self {
+        Self {
+            variable: variable.into(),
+        }
+    }
+}
+
+#[async_trait]
+impl KeyProvider for EnvKeyProvider {
+    async fn key(&self) -> Result<DataKey> {
+        let variable = &self.variable;
+        let encoded = Zeroizing::new(
+            std::env::var(variable)
+                .with_context(|| format!("Failed to read key from env var {variable}"))?,
+        );
+        let bytes = Zeroizing::new(
+            BASE64
+                .decode(encoded.trim())
+                .with_context(|| format!("The key in env var {variable} is not base64"))?,
+        );
+        DataKey::new(&bytes).with_context(|| format!("Invalid key in env var {variable}"))
+    }
+}
@@ -0,0 +1,93 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use spin_common::ui::quoted_path;
+use zeroize::Zeroizing;
+
+use crate::{DataKey, KeyProvider};
+
+/// A key kept in a local file, as raw bytes.
+#[derive(Debug)]
+pub struct FileKeyProvider {
+    path: PathBuf,
+    create: bool,
+}
+
+impl FileKeyProvider {
+    /// Creates a new FileKeyProvider.
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self {
+            path: path.into(),
+            create: false,
+        }
+    }
+
+    /// Generates the key, in a file which only the user can read, if the
+    /// file doesn't exist.
+    pub fn create_if_missing(mut self) -> Self {
+        self.create = true;
+        self
+    }
+
+    /// Reads the key.
+    pub fn load(&self) -> Result<DataKey> {
+        let path = &self.path;
+        let bytes = match std::fs::read(path) {
+            Ok(bytes) => Zeroizing::new(bytes),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound && self.create => {
+                let bytes = DataKey::generate()?;
+                write_private(path, &bytes)?;
+                bytes
+            }
+            Err(err) => {
+                return Err(err).with_context(|| format!("Failed to read {}", quoted_path(path)))
+            }
+        };
+        DataKey::new(&bytes).with_context(|| format!("Invalid key file {}", quoted_path(path)))
+    }
+}
+
+#[async_trait]
+impl KeyProvider for FileKeyProvider {
+    async fn key(&self) -> Result<DataKey> {
+        self.load()
+    }
+}
+
+/// Writes a file which only the user can read.
+fn write_private(path: &Path, contents: &[u8]) -> Result<()> {
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)
+            .with_context(|| format!("Failed to create {}", quoted_path(dir)))?;
+    }
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    let mut file = options
+        .open(path)
+        .with_context(|| format!("Failed to create {}", quoted_path(path)))?;
+    std::io::Write::write_all(&mut file, contents)
+        .with_context(|| format!("Failed to write {}", quoted_path(path)))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn key_file_is_created_once() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("keys").join("data.key");
+        FileKeyProvider::new(&path).load().unwrap_err();
+
+        let created = FileKeyProvider::new(&path).create_if_missing();
+        let sealed = created.load().unwrap().seal(b"", b"data").unwrap();
+        let reloaded = FileKeyProvider::new(&path).load().unwrap();
+        assert_eq!(reloaded.open(b"", &sealed).unwrap(), b"data");
+    }
+}
@@ -75,6 +75,32 @@ impl Cache {
         Ok(path)
     }
 
+    /// Return the path to a wasm file given its `sha256:` digest, if it is in
+    /// the cache and its contents match the digest. A file which doesn't
+    /// match is removed, so that it can be fetched again.
+    pub fn verified_wasm_file(&self, digest: impl AsRef<str>) -> Option<PathBuf> {
+        let digest = digest.as_ref();
+        let expected = digest.strip_prefix("sha256:")?;
+        let path = self.wasm_file(digest).ok()?;
+        match spin_common::sha256::hex_digest_from_file(&path) {
+            Ok(actual) if actual == expected => Some(path),
+            Ok(actual) => {
+                tracing::warn!(
+                    "Removing cached file {} with digest sha256:{actual}, expected {digest}",
+                    path.display()
+                );
+                if let Err(err) = std::fs::remove_file(&path) {
+                    tracing::warn!("Failed to remove {}: {err}", path.display());
+                }
+                None
+            }
+            Err(err) => {
+                tracing::warn!("Failed to read cached file {}: {err}", path.display());
+                None
+            }
+        }
+    }
+
     /// Return the path to a data file given its digest.
     pub fn data_file(&self, digest: impl AsRef<str>) -> Result<PathBuf> {
         let path = self.data_path(&digest);
@@ -152,6 +178,28 @@ impl Cache {
 
         Ok(())
     }
+
+    #[tokio::test]
+    async fn corrupt_wasm_files_are_not_verified() -> anyhow::Result<()> {
+        let temp_dir = tempfile::tempdir()?;
+        let cache = Cache::new(Some(temp_dir.path().to_owned())).await?;
+
+        let wasm = "Wasm".as_bytes();
+        let digest = format!("sha256:{}", hex_digest_from_bytes(wasm));
+        assert_eq!(cache.verified_wasm_file(&digest), None);
+
+        cache.write_wasm(wasm, &digest).await?;
+        assert_eq!(
+            cache.verified_wasm_file(&digest),
+            Some(cache.wasm_path(&digest))
+        );
+
+        cache.write_wasm("Corrupt", &digest).await?;
+        assert_eq!(cache.verified_wasm_file(&digest), None);
+        assert!(!cache.wasm_path(&digest).exists());
+
+        Ok(())
+    }
 }
 
 #[cfg(windows)]
@@ -187,4 +235,26 @@ mod test {
 
         Ok(())
     }
+
+    #[tokio::test]
+    async fn corrupt_wasm_files_are_not_verified() -> anyhow::Result<()> {
+        let temp_dir = tempfile::tempdir()?;
+        let cache = Cache::new(Some(temp_dir.path().to_owned())).await?;
+
+        let wasm = "Wasm".as_bytes();
+        let digest = format!("sha256:{}", hex_digest_from_bytes(wasm));
+        assert_eq!(cache.verified_wasm_file(&digest), None);
+
+        cache.write_wasm(wasm, &digest).await?;
+        assert_eq!(
+            cache.verified_wasm_file(&digest),
+            Some(cache.wasm_path(&digest))
+        );
+
+        cache.write_wasm("Corrupt", &digest).await?;
+        assert_eq!(cache.verified_wasm_file(&digest), None);
+        assert!(!cache.wasm_path(&digest).exists());
+
+        Ok(())
+    }
 }
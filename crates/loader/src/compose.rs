@@ -0,0 +1,188 @@
+//! Composition of components with the components they depend on.
+//!
+//! A component may declare that some of its imports are satisfied by exports of
+//! other components of the app:
+//!
+//! ```toml
+//! [component.app.dependencies]
+//! "acme:widget/render@1.0.0" = { component = "widget" }
+//! ```
+//!
+//! The loader composes each such component with instances of its dependencies,
+//! so that the runtime sees a single component with those imports satisfied and
+//! no external tooling is needed. Each component gets its own instances of its
+//! dependencies, which may themselves have dependencies.
+
+use std::collections::{HashMap, HashSet};
+
+use anyhow::{bail, Context, Result};
+use indexmap::IndexMap;
+use spin_manifest::schema::v2::ComponentDependency;
+use wasm_compose::graph::{Component, ComponentId, CompositionGraph, EncodeOptions, InstanceId};
+
+/// The dependencies of the components which have any, by component ID and
+/// then by import name.
+pub(crate) type Dependencies = IndexMap<String, IndexMap<String, ComponentDependency>>;
+
+/// Returns the IDs of the components with dependencies in an order in which
+/// they can be composed: each after any of its dependencies which must be
+/// composed themselves.
+pub(crate) fn composition_order<'a>(
+    dependencies: &'a Dependencies,
+    component_ids: &HashSet<&str>,
+) -> Result<Vec<&'a str>> {
+    fn visit<'a>(
+        id: &'a str,
+        dependencies: &'a Dependencies,
+        component_ids: &HashSet<&str>,
+        visiting: &mut Vec<&'a str>,
+        order: &mut Vec<&'a str>,
+    ) -> Result<()> {
+        if order.contains(&id) {
+            return Ok(());
+        }
+        if let Some(start) = visiting.iter().position(|visited| *visited == id) {
+            let mut cycle = visiting[start..].to_vec();
+            cycle.push(id);
+            bail!("Components depend on each other: {}", cycle.join(" -> "));
+        }
+        let Some((id, imports)) = dependencies.get_key_value(id) else {
+            return Ok(());
+        };
+        visiting.push(id);
+        for (import, dependency) in imports {
+            let dependency_id = dependency.component.as_ref();
+            if !component_ids.contains(dependency_id) {
+                bail!("Component `{id}` import `{import}` depends on nonexistent component `{dependency_id}`");
+            }
+            if dependency_id == id {
+                bail!("Component `{id}` import `{import}` depends on the component itself");
+            }
+            visit(dependency_id, dependencies, component_ids, visiting, order)?;
+        }
+        visiting.pop();
+        order.push(id);
+        Ok(())
+    }
+
+    let mut order = vec![];
+    for id in dependencies.keys() {
+        visit(id, dependencies, component_ids, &mut vec![], &mut order)?;
+    }
+    Ok(order)
+}
+
+/// Composes the component `id` with its dependencies, given the Wasm of each
+/// component, returning the Wasm of the composed component.
+pub(crate) fn compose(
+    id: &str,
+    wasm: &[u8],
+    imports: &IndexMap<String, ComponentDependency>,
+    dependency_wasm: &HashMap<&str, Vec<u8>>,
+) -> Result<Vec<u8>> {
+    let mut graph = CompositionGraph::new();
+
+    let component = Component::from_bytes(id, wasm)
+        .with_context(|| format!("Failed to parse component `{id}`"))?;
+    let import_indexes = imports
+        .keys()
+        .map(|import| {
+            component
+                .import_by_name(import)
+                .map(|(index, _)| index)
+                .with_context(|| format!("Component `{id}` has no import `{import}`"))
+        })
+        .collect::<Result<Vec<_>>>()?;
+    let component = graph.add_component(component)?;
+    let instance = graph.instantiate(component)?;
+
+    let mut dependency_instances: HashMap<&str, (ComponentId, InstanceId)> = HashMap::new();
+    for ((import, dependency), import_index) in imports.iter().zip(import_indexes) {
+        let dependency_id = dependency.component.as_ref();
+        let (dependency_component, dependency_instance) =
+            match dependency_instances.get(dependency_id) {
+                Some(ids) => *ids,
+                None => {
+                    let wasm = dependency_wasm
+                        .get(dependency_id)
+                        .with_context(|| format!("No Wasm for component `{dependency_id}`"))?;
+                    let component = Component::from_bytes(dependency_id, wasm.as_slice())
+                        .with_context(|| format!("Failed to parse component `{dependency_id}`"))?;
+                    let component = graph.add_component(component)?;
+                    let ids = (component, graph.instantiate(component)?);
+                    dependency_instances.insert(dependency_id, ids);
+                    ids
+                }
+            };
+        let export = dependency.export.as_deref().unwrap_or(import);
+        let (export_index, ..) = graph
+            .get_component(dependency_component)
+            .and_then(|component| component.export_by_name(export))
+            .with_context(|| format!("Component `{dependency_id}` has no export `{export}`"))?;
+        graph
+            .connect(
+                dependency_instance,
+                Some(export_index),
+                instance,
+                import_index,
+            )
+            .with_context(|| {
+                format!(
+                    "Export `{export}` of component `{dependency_id}` cannot satisfy import `{import}` of component `{id}`"
+                )
+            })?;
+    }
+
+    graph
+        .encode(EncodeOptions {
+            define_components: true,
+            export: Some(instance),
+            validate: true,
+        })
+        .with_context(|| format!("Failed to compose component `{id}` with its dependencies"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn dependencies(edges: &[(&str, &str)]) -> Dependencies {
+        let mut dependencies = Dependencies::new();
+        for (id, dependency) in edges {
+            let import = format!("acme:{dependency}/api");
+            let dependency = ComponentDependency {
+                component: dependency.to_string().try_into().unwrap(),
+                export: None,
+            };
+            dependencies
+                .entry(id.to_string())
+                .or_default()
+                .insert(import, dependency);
+        }
+        dependencies
+    }
+
+    #[test]
+    fn dependencies_are_composed_first() {
+        let ids = HashSet::from(["app", "widget", "gadget"]);
+        let dependencies = dependencies(&[("app", "widget"), ("widget", "gadget")]);
+        assert_eq!(
+            composition_order(&dependencies, &ids).unwrap(),
+            ["widget", "app"]
+        );
+    }
+
+    #[test]
+    fn cycles_and_missing_components_are_rejected() {
+        let ids = HashSet::from(["app", "widget"]);
+        let err = composition_order(&dependencies(&[("app", "widget"), ("widget", "app")]), &ids)
+            .unwrap_err();
+        assert!(err.to_string().contains("app -> widget -> app"), "{err}");
+
+        let err = composition_order(&dependencies(&[("app", "gadget")]), &ids).unwrap_err();
+        assert!(
+            err.to_string().contains("nonexistent component `gadget`"),
+            "{err}"
+        );
+    }
+}
@@ -14,14 +14,22 @@ use std::path::{Path, PathBuf};
 
 use anyhow::{Context, Result};
 use local::LocalLoader;
-use spin_common::paths::parent_dir;
+use spin_common::{paths::parent_dir, ui::quoted_path};
 use spin_locked_app::locked::LockedApp;
+use spin_manifest::{deprecation::Deprecation, schema::v2::AppManifest};
 
 pub mod cache;
+mod compose;
 mod fs;
 #[cfg(feature = "async-io")]
 mod http;
 mod local;
+mod locked_sources;
+mod manifest;
+#[cfg(feature = "async-io")]
+pub mod registry;
+mod strict;
+pub mod world;
 
 /// Maximum number of files to copy (or download) concurrently
 pub(crate) const MAX_FILE_LOADING_CONCURRENCY: usize = 16;
@@ -33,11 +41,96 @@ pub async fn from_file(
     manifest_path: impl AsRef<Path>,
     files_mount_strategy: FilesMountStrategy,
     cache_root: Option<PathBuf>,
+) -> Result<LockedApp> {
+    from_file_in_environment(manifest_path, None, files_mount_strategy, cache_root).await
+}
+
+/// Load a Spin locked app from a spin.toml manifest file as for [`from_file`],
+/// applying the manifest's overlay for `environment` (`spin.<environment>.toml`)
+/// if one is given.
+pub async fn from_file_in_environment(
+    manifest_path: impl AsRef<Path>,
+    environment: Option<&str>,
+    files_mount_strategy: FilesMountStrategy,
+    cache_root: Option<PathBuf>,
+) -> Result<LockedApp> {
+    let options = LoadOptions {
+        environment,
+        ..Default::default()
+    };
+    from_file_with_options(manifest_path, files_mount_strategy, cache_root, options).await
+}
+
+/// Options for loading a Spin locked app from a spin.toml manifest file.
+#[derive(Debug, Default)]
+pub struct LoadOptions<'a> {
+    /// The environment whose overlay (`spin.<environment>.toml`) to apply,
+    /// if any.
+    pub environment: Option<&'a str>,
+    /// Fail rather than download remote component sources which aren't in
+    /// the cache. Registry sources without a pinned digest are found in the
+    /// cache by the digest recorded for them in the app's lock file.
+    pub offline: bool,
+}
+
+/// Load a Spin locked app from a spin.toml manifest file as for [`from_file`],
+/// with the given options.
+pub async fn from_file_with_options(
+    manifest_path: impl AsRef<Path>,
+    files_mount_strategy: FilesMountStrategy,
+    cache_root: Option<PathBuf>,
+    options: LoadOptions<'_>,
 ) -> Result<LockedApp> {
     let path = manifest_path.as_ref();
     let app_root = parent_dir(path).context("manifest path has no parent directory")?;
-    let loader = LocalLoader::new(&app_root, files_mount_strategy, cache_root).await?;
-    loader.load_file(path).await
+    let loader =
+        LocalLoader::new(&app_root, files_mount_strategy, cache_root, options.offline).await?;
+    loader.load_file(path, options.environment).await
+}
+
+/// Read a spin.toml manifest file, merging in the files it includes and, if
+/// `environment` is given, the manifest's overlay for that environment.
+pub fn manifest_from_file(
+    manifest_path: impl AsRef<Path>,
+    environment: Option<&str>,
+) -> Result<AppManifest> {
+    let path = manifest_path.as_ref();
+    let composed = manifest::read(path, environment).with_context(|| {
+        format!(
+            "Failed to read Spin app manifest from {}",
+            quoted_path(path)
+        )
+    })?;
+    Ok(composed.manifest)
+}
+
+/// Lists the deprecated constructs used by a spin.toml manifest file, with the files it
+/// includes and, if `environment` is given, the manifest's overlay for that environment.
+/// Loading the manifest warns about each one.
+pub fn manifest_deprecations(
+    manifest_path: impl AsRef<Path>,
+    environment: Option<&str>,
+) -> Result<Vec<Deprecation>> {
+    let path = manifest_path.as_ref();
+    let composed = manifest::read(path, environment).with_context(|| {
+        format!(
+            "Failed to read Spin app manifest from {}",
+            quoted_path(path)
+        )
+    })?;
+    Ok(composed.deprecations)
+}
+
+/// Checks a spin.toml manifest file, with the files it includes and, if `environment`
+/// is given, the manifest's overlay for that environment, for keys which Spin doesn't
+/// know. Fails with the location of each one, and the key it was likely meant to be.
+pub fn check_manifest_strictly(
+    manifest_path: impl AsRef<Path>,
+    environment: Option<&str>,
+) -> Result<()> {
+    let path = manifest_path.as_ref();
+    strict::check(path, environment)
+        .with_context(|| format!("Strict check of {} failed", quoted_path(path)))
 }
 
 /// The strategy to use for mounting WASI files into a guest.
@@ -1,3 +1,4 @@
+use std::collections::{HashMap, HashSet};
 use std::path::{Path, PathBuf};
 
 use anyhow::{bail, ensure, Context, Result};
@@ -15,13 +16,21 @@ use spin_manifest::schema::v2::{self, AppManifest, KebabId, WasiFilesMount};
 use spin_outbound_networking::SERVICE_CHAINING_DOMAIN_SUFFIX;
 use tokio::sync::Semaphore;
 
-use crate::{cache::Cache, FilesMountStrategy};
+use crate::{
+    cache::Cache,
+    compose::{composition_order, Dependencies},
+    locked_sources::LockedSources,
+    manifest::{ComposedManifest, Sources},
+    FilesMountStrategy,
+};
 
 #[derive(Debug)]
 pub struct LocalLoader {
     app_root: PathBuf,
     files_mount_strategy: FilesMountStrategy,
     cache: Cache,
+    locked_sources: LockedSources,
+    offline: bool,
     file_loading_permits: Semaphore,
 }
 
@@ -30,31 +39,49 @@ impl LocalLoader {
         app_root: &Path,
         files_mount_strategy: FilesMountStrategy,
         cache_root: Option<PathBuf>,
+        offline: bool,
     ) -> Result<Self> {
         let app_root = safe_canonicalize(app_root)
             .with_context(|| format!("Invalid manifest dir `{}`", app_root.display()))?;
         Ok(Self {
+            locked_sources: LockedSources::read(&app_root),
             app_root,
             files_mount_strategy,
             cache: Cache::new(cache_root).await?,
+            offline,
             // Limit concurrency to avoid hitting system resource limits
             file_loading_permits: Semaphore::new(crate::MAX_FILE_LOADING_CONCURRENCY),
         })
     }
 
-    // Load the manifest file (spin.toml) at the given path into a LockedApp,
+    // Load the manifest file (spin.toml) at the given path, with the files it
+    // includes and its overlay for the environment, into a LockedApp,
     // preparing all its content for execution.
-    pub async fn load_file(&self, path: impl AsRef<Path>) -> Result<LockedApp> {
+    pub async fn load_file(
+        &self,
+        path: impl AsRef<Path>,
+        environment: Option<&str>,
+    ) -> Result<LockedApp> {
         // Parse manifest
         let path = path.as_ref();
-        let manifest = spin_manifest::manifest_from_file(path).with_context(|| {
+        let ComposedManifest {
+            manifest,
+            sources,
+            deprecations,
+        } = crate::manifest::read(path, environment).with_context(|| {
             format!(
                 "Failed to read Spin app manifest from {}",
                 quoted_path(path)
             )
         })?;
+        for deprecation in deprecations {
+            match sources.describe(&deprecation.key) {
+                Some(files) => terminal::warn!("{} (in {files})", deprecation.message),
+                None => terminal::warn!("{}", deprecation.message),
+            }
+        }
         let mut locked = self
-            .load_manifest(manifest)
+            .load_manifest(manifest, &sources)
             .await
             .with_context(|| format!("Failed to load Spin app from {}", quoted_path(path)))?;
 
@@ -67,24 +94,46 @@ impl LocalLoader {
     }
 
     // Load the given manifest into a LockedApp, ready for execution.
-    async fn load_manifest(&self, mut manifest: AppManifest) -> Result<LockedApp> {
+    async fn load_manifest(
+        &self,
+        mut manifest: AppManifest,
+        sources: &Sources,
+    ) -> Result<LockedApp> {
         spin_manifest::normalize::normalize_manifest(&mut manifest);
 
         let AppManifest {
             spin_manifest_version: _,
+            min_spin_version: _,
             application,
             variables,
             triggers,
             components,
         } = manifest;
 
+        for (name, hook) in [
+            ("on_start", &application.on_start),
+            ("on_stop", &application.on_stop),
+        ] {
+            if let Some(hook) = hook {
+                ensure!(
+                    components.contains_key(&hook.component),
+                    "`application.{name}` refers to nonexistent component `{}`",
+                    hook.component
+                );
+            }
+        }
+
         let metadata = locked_metadata(application, triggers.keys().cloned())?;
 
         let app_requires_service_chaining = components.values().any(requires_service_chaining);
 
         let variables = variables
             .into_iter()
-            .map(|(name, v)| Ok((name.to_string(), locked_variable(v)?)))
+            .map(|(name, v)| {
+                let variable =
+                    locked_variable(v).with_context(|| format!("Invalid variable `{name}`"))?;
+                Ok((name.to_string(), variable))
+            })
             .collect::<Result<_>>()?;
 
         let triggers = triggers
@@ -97,16 +146,30 @@ impl LocalLoader {
             })
             .collect::<Result<Vec<_>>>()?;
 
+        let dependencies: Dependencies = components
+            .iter()
+            .filter(|(_, component)| !component.dependencies.is_empty())
+            .map(|(id, component)| (id.to_string(), component.dependencies.clone()))
+            .collect();
+        let component_ids: HashSet<&str> = components.keys().map(|id| id.as_ref()).collect();
+        let composition_order = composition_order(&dependencies, &component_ids)?;
+
         let sloth_guard = warn_if_component_load_slothful();
 
         // Load all components concurrently
-        let components = try_join_all(components.into_iter().map(|(id, c)| async move {
-            self.load_component(&id, c)
-                .await
-                .with_context(|| format!("Failed to load component `{id}`"))
+        let mut components = try_join_all(components.into_iter().map(|(id, c)| async move {
+            self.load_component(&id, c).await.with_context(|| {
+                match sources.describe(&format!("component.{id}")) {
+                    Some(files) => format!("Failed to load component `{id}` (from {files})"),
+                    None => format!("Failed to load component `{id}`"),
+                }
+            })
         }))
         .await?;
 
+        self.compose_dependencies(&mut components, &dependencies, &composition_order)
+            .await?;
+
         let mut host_requirements = ValuesMapBuilder::new();
         if app_requires_service_chaining {
             host_requirements.string(
@@ -123,7 +186,7 @@ impl LocalLoader {
 
         drop(sloth_guard);
 
-        Ok(LockedApp {
+        let locked = LockedApp {
             spin_lock_version: Default::default(),
             metadata,
             must_understand,
@@ -131,7 +194,48 @@ impl LocalLoader {
             variables,
             triggers,
             components,
-        })
+        };
+        crate::world::check_locked_app(&locked)?;
+        Ok(locked)
+    }
+
+    // Compose each component which has dependencies with them, replacing its
+    // source with the composed Wasm. Components are composed in the given
+    // order, so that dependencies with dependencies of their own are composed
+    // before the components which use them.
+    async fn compose_dependencies(
+        &self,
+        components: &mut [LockedComponent],
+        dependencies: &Dependencies,
+        order: &[&str],
+    ) -> Result<()> {
+        for id in order {
+            let imports = &dependencies[*id];
+            let mut dependency_wasm = HashMap::new();
+            for dependency in imports.values() {
+                let dependency_id = dependency.component.as_ref();
+                if !dependency_wasm.contains_key(dependency_id) {
+                    dependency_wasm
+                        .insert(dependency_id, component_wasm(components, dependency_id)?);
+                }
+            }
+            let wasm = component_wasm(components, id)?;
+            let composed = crate::compose::compose(id, &wasm, imports, &dependency_wasm)?;
+
+            let digest = format!(
+                "sha256:{}",
+                spin_common::sha256::hex_digest_from_bytes(&composed)
+            );
+            self.cache.write_data(&composed, &digest).await?;
+            let content = ContentRef {
+                digest: Some(digest.clone()),
+                ..file_content_ref(self.cache.data_path(&digest))?
+            };
+            if let Some(component) = components.iter_mut().find(|c| c.id == *id) {
+                component.source.content = content;
+            }
+        }
+        Ok(())
     }
 
     // Load the given component into a LockedComponent, ready for execution.
@@ -145,18 +249,35 @@ impl LocalLoader {
             .context("`allowed_http_hosts` is malformed")?;
         spin_outbound_networking::AllowedHostsConfig::validate(&allowed_outbound_hosts)
             .context("`allowed_outbound_hosts` is malformed")?;
+        if let Some(resources) = &component.resources {
+            validate_resources(resources).context("`resources` is malformed")?;
+        }
 
+        let source_reference = match &component.source {
+            v2::ComponentSource::Registry {
+                registry, version, ..
+            } => format!("{registry}:{version}"),
+            _ => String::new(),
+        };
         let metadata = ValuesMapBuilder::new()
             .string("description", component.description)
+            .string(locked::SOURCE_REFERENCE_KEY, source_reference)
             .string_array("allowed_outbound_hosts", allowed_outbound_hosts)
             .string_array("key_value_stores", component.key_value_stores)
             .string_array("databases", component.sqlite_databases)
+            .string_array("blob_containers", component.blob_containers)
+            .string_array("message_brokers", component.message_brokers)
             .string_array("ai_models", component.ai_models)
             .serializable("build", component.build)?
+            .serializable("resources", component.resources)?
+            .serializable(
+                "prepare",
+                (component.prepare != v2::ComponentPreparation::Eager).then_some(component.prepare),
+            )?
             .take();
 
         let source = self
-            .load_component_source(component.source.clone())
+            .load_component_source(id, component.source.clone())
             .await
             .with_context(|| format!("Failed to load Wasm source {}", component.source))?;
 
@@ -185,6 +306,14 @@ impl LocalLoader {
                         component.exclude_files.is_empty(),
                         "Cannot load a component with `exclude_files` using --direct-mounts"
                     );
+                    ensure!(
+                        component.files.iter().all(|mount| !matches!(
+                            mount,
+                            WasiFilesMount::Placement { exclude, skip_hidden, .. }
+                                if !exclude.is_empty() || *skip_hidden
+                        )),
+                        "Cannot load a component with files `exclude` or `skip_hidden` using --direct-mounts"
+                    );
                     let mut files = vec![];
                     for mount in &component.files {
                         // Validate (and canonicalize) direct mount directory
@@ -215,6 +344,7 @@ impl LocalLoader {
     // URL with an absolute path to the content.
     async fn load_component_source(
         &self,
+        id: &KebabId,
         source: v2::ComponentSource,
     ) -> Result<LockedComponentSource> {
         let content = match source {
@@ -222,6 +352,14 @@ impl LocalLoader {
             v2::ComponentSource::Remote { url, digest } => {
                 self.load_http_source(&url, &digest).await?
             }
+            v2::ComponentSource::Registry {
+                registry,
+                version,
+                digest,
+            } => {
+                self.load_registry_source(id, &registry, &version, digest.as_deref())
+                    .await?
+            }
         };
         Ok(LockedComponentSource {
             content_type: "application/wasm".into(),
@@ -236,9 +374,13 @@ impl LocalLoader {
             digest.starts_with("sha256:"),
             "invalid `digest` {digest:?}; must start with 'sha256:'"
         );
-        let path = if let Ok(cached_path) = self.cache.wasm_file(digest) {
+        let path = if let Some(cached_path) = self.cache.verified_wasm_file(digest) {
             cached_path
         } else {
+            ensure!(
+                !self.offline,
+                "{url:?} is not in the cache, and cannot be downloaded offline"
+            );
             let _loading_permit = self.file_loading_permits.acquire().await?;
 
             self.cache.ensure_dirs().await?;
@@ -251,6 +393,51 @@ impl LocalLoader {
         file_content_ref(path)
     }
 
+    // Load a Wasm source from the given registry reference and return a
+    // ContentRef to the local copy, with the digest it was verified against.
+    async fn load_registry_source(
+        &self,
+        id: &KebabId,
+        registry: &str,
+        version: &str,
+        digest: Option<&str>,
+    ) -> Result<ContentRef> {
+        if let Some(digest) = digest {
+            ensure!(
+                digest.starts_with("sha256:"),
+                "invalid `digest` {digest:?}; must start with 'sha256:'"
+            );
+        }
+        // A digest pinned in the manifest, or else recorded in the lock file,
+        // whose content is already in the cache needs no trip to the registry
+        let known_digest = digest.or_else(|| {
+            self.locked_sources
+                .digest(id.as_ref(), &format!("{registry}:{version}"))
+        });
+        let cached =
+            known_digest.and_then(|digest| Some((digest, self.cache.verified_wasm_file(digest)?)));
+        let (digest, path) = match cached {
+            Some((digest, path)) => (digest.to_owned(), path),
+            None => {
+                ensure!(
+                    !self.offline,
+                    "{registry}:{version} is not in the cache, and cannot be fetched offline{}",
+                    if digest.is_none() {
+                        ". Run `spin lock` while online to record its digest"
+                    } else {
+                        ""
+                    }
+                );
+                let _loading_permit = self.file_loading_permits.acquire().await?;
+                fetch_registry_source(registry, version, digest, &self.cache).await?
+            }
+        };
+        Ok(ContentRef {
+            digest: Some(digest),
+            ..file_content_ref(path)?
+        })
+    }
+
     // Copy content(s) from the given `mount`
     async fn copy_file_mounts(
         &self,
@@ -260,16 +447,19 @@ impl LocalLoader {
     ) -> Result<()> {
         match mount {
             WasiFilesMount::Pattern(pattern) => {
-                self.copy_glob_or_path(pattern, dest_root, exclude_files)
-                    .await
+                let filter = MountFilter::new(exclude_files, &[], false)?;
+                self.copy_glob_or_path(pattern, dest_root, &filter).await
             }
             WasiFilesMount::Placement {
                 source,
                 destination,
+                exclude,
+                skip_hidden,
             } => {
+                let filter = MountFilter::new(exclude_files, exclude, *skip_hidden)?;
                 let src = Path::new(source);
                 let dest = dest_root.join(destination.trim_start_matches('/'));
-                self.copy_file_or_directory(src, &dest, exclude_files).await
+                self.copy_file_or_directory(src, &dest, &filter).await
             }
         }
     }
@@ -279,7 +469,7 @@ impl LocalLoader {
         &self,
         glob_or_path: &str,
         dest_root: &Path,
-        exclude_files: &[String],
+        filter: &MountFilter,
     ) -> Result<()> {
         let path = self.app_root.join(glob_or_path);
         if path.exists() {
@@ -287,7 +477,7 @@ impl LocalLoader {
             if path.is_dir() {
                 // "single/dir"
                 let pattern = path.join("**/*");
-                self.copy_glob(&pattern, &self.app_root, &dest, exclude_files)
+                self.copy_glob(&pattern, &self.app_root, &dest, filter)
                     .await?;
             } else {
                 // "single/file.txt"
@@ -295,7 +485,7 @@ impl LocalLoader {
             }
         } else if looks_like_glob_pattern(glob_or_path) {
             // "glob/pattern/*"
-            self.copy_glob(&path, &self.app_root, dest_root, exclude_files)
+            self.copy_glob(&path, &self.app_root, dest_root, filter)
                 .await?;
         } else {
             bail!("{glob_or_path:?} does not exist and doesn't appear to be a glob pattern");
@@ -308,7 +498,7 @@ impl LocalLoader {
         &self,
         src: &Path,
         dest: &Path,
-        exclude_files: &[String],
+        filter: &MountFilter,
     ) -> Result<()> {
         let src_path = self.app_root.join(src);
         let meta = crate::fs::metadata(&src_path)
@@ -317,8 +507,7 @@ impl LocalLoader {
         if meta.is_dir() {
             // { source = "host/dir", destination = "guest/dir" }
             let pattern = src_path.join("**/*");
-            self.copy_glob(&pattern, &src_path, dest, exclude_files)
-                .await?;
+            self.copy_glob(&pattern, &src_path, dest, filter).await?;
         } else {
             // { source = "host/file.txt", destination = "guest/file.txt" }
             self.copy_single_file(&src_path, dest).await?;
@@ -332,7 +521,7 @@ impl LocalLoader {
         pattern: &Path,
         src_prefix: &Path,
         dest_root: &Path,
-        exclude_files: &[String],
+        filter: &MountFilter,
     ) -> Result<()> {
         let pattern = pattern
             .to_str()
@@ -341,14 +530,6 @@ impl LocalLoader {
         let paths = glob::glob(pattern)
             .with_context(|| format!("Failed to resolve glob pattern {pattern:?}"))?;
 
-        let exclude_patterns = exclude_files
-            .iter()
-            .map(|pattern| {
-                glob::Pattern::new(pattern)
-                    .with_context(|| format!("Invalid exclude_files glob pattern {pattern:?}"))
-            })
-            .collect::<Result<Vec<_>>>()?;
-
         for path_res in paths {
             let src = path_res?;
             if !src.is_file() {
@@ -356,17 +537,12 @@ impl LocalLoader {
             }
 
             let app_root_path = src.strip_prefix(&self.app_root)?;
-            if exclude_patterns
-                .iter()
-                .any(|pattern| pattern.matches_path(app_root_path))
-            {
-                tracing::debug!(
-                    "File {app_root_path:?} excluded by exclude_files {exclude_files:?}"
-                );
+            let relative_path = src.strip_prefix(src_prefix)?;
+            if filter.excludes(app_root_path, relative_path) {
+                tracing::debug!("File {app_root_path:?} excluded from files mount");
                 continue;
             }
 
-            let relative_path = src.strip_prefix(src_prefix)?;
             let dest = dest_root.join(relative_path);
             self.copy_single_file(&src, &dest).await?;
         }
@@ -413,6 +589,7 @@ impl LocalLoader {
             WasiFilesMount::Placement {
                 source,
                 destination,
+                ..
             } => (source, destination),
         };
         let path = self.app_root.join(src);
@@ -447,6 +624,88 @@ async fn verified_download(_url: &str, _digest: &str, _dest: &Path) -> Result<()
     panic!("async-io feature is required for downloading Wasm sources")
 }
 
+#[cfg(feature = "async-io")]
+async fn fetch_registry_source(
+    registry: &str,
+    version: &str,
+    digest: Option<&str>,
+    cache: &Cache,
+) -> Result<(String, PathBuf)> {
+    let reference = crate::registry::Reference::parse(registry, version)?;
+    crate::registry::fetch(&reference, digest, cache).await
+}
+
+#[cfg(not(feature = "async-io"))]
+async fn fetch_registry_source(
+    _registry: &str,
+    _version: &str,
+    _digest: Option<&str>,
+    _cache: &Cache,
+) -> Result<(String, PathBuf)> {
+    panic!("async-io feature is required for fetching Wasm sources from registries")
+}
+
+/// The files left out of a files mount: those matching the component's
+/// `exclude_files`, which are relative to the app root, or the mount's own
+/// `exclude`, which are relative to its source, and hidden files if the mount
+/// skips them.
+struct MountFilter {
+    exclude_files: Vec<glob::Pattern>,
+    exclude: Vec<glob::Pattern>,
+    skip_hidden: bool,
+}
+
+impl MountFilter {
+    fn new(exclude_files: &[String], exclude: &[String], skip_hidden: bool) -> Result<Self> {
+        let compile = |patterns: &[String], field: &str| {
+            patterns
+                .iter()
+                .map(|pattern| {
+                    glob::Pattern::new(pattern)
+                        .with_context(|| format!("Invalid {field} glob pattern {pattern:?}"))
+                })
+                .collect::<Result<Vec<_>>>()
+        };
+        Ok(Self {
+            exclude_files: compile(exclude_files, "exclude_files")?,
+            exclude: compile(exclude, "files exclude")?,
+            skip_hidden,
+        })
+    }
+
+    fn excludes(&self, app_root_path: &Path, source_path: &Path) -> bool {
+        self.exclude_files
+            .iter()
+            .any(|pattern| pattern.matches_path(app_root_path))
+            || self
+                .exclude
+                .iter()
+                .any(|pattern| pattern.matches_path(source_path))
+            || (self.skip_hidden && is_hidden(source_path))
+    }
+}
+
+fn is_hidden(path: &Path) -> bool {
+    path.components()
+        .any(|component| component.as_os_str().to_string_lossy().starts_with('.'))
+}
+
+/// Reads the Wasm of a loaded component.
+fn component_wasm(components: &[LockedComponent], id: &str) -> Result<Vec<u8>> {
+    let component = components
+        .iter()
+        .find(|c| c.id == id)
+        .with_context(|| format!("Component `{id}` not found"))?;
+    let source = component
+        .source
+        .content
+        .source
+        .as_deref()
+        .with_context(|| format!("Component `{id}` has no source"))?;
+    let path = spin_common::url::parse_file_url(source)?;
+    std::fs::read(&path).with_context(|| format!("Failed to read {}", quoted_path(&path)))
+}
+
 fn safe_canonicalize(path: &Path) -> std::io::Result<PathBuf> {
     use path_absolutize::Absolutize;
     Ok(path.absolutize()?.into_owned())
@@ -462,7 +721,9 @@ fn locked_metadata(
         .string("version", details.version)
         .string("description", details.description)
         .string_array("authors", details.authors)
-        .serializable("triggers", &details.trigger_global_configs)?;
+        .serializable("triggers", &details.trigger_global_configs)?
+        .serializable("on_start", details.on_start)?
+        .serializable("on_stop", details.on_stop)?;
 
     // Duplicate single-trigger global options into "trigger" with "type"
     // key to maintain backward compatibility for a while.
@@ -481,14 +742,47 @@ fn locked_metadata(
     Ok(builder.build())
 }
 
+fn validate_resources(resources: &v2::ComponentResources) -> Result<()> {
+    let limits = [
+        ("max_memory_mb", resources.max_memory_mb),
+        ("timeout_ms", resources.timeout_ms),
+        (
+            "max_concurrent",
+            resources.max_concurrent.map(|max| max as u64),
+        ),
+    ];
+    for (name, limit) in limits {
+        ensure!(limit != Some(0), "`{name}` must be greater than zero");
+    }
+    Ok(())
+}
+
 fn locked_variable(variable: v2::Variable) -> Result<locked::Variable> {
     ensure!(
         variable.required ^ variable.default.is_some(),
         "must be `required` OR have a `default`"
     );
+    if let Some(pattern) = &variable.pattern {
+        let regex = regex::Regex::new(&format!("^(?:{pattern})$"))
+            .with_context(|| format!("invalid `pattern` {pattern:?}"))?;
+        for (field, value) in [
+            ("default", &variable.default),
+            ("example", &variable.example),
+        ] {
+            if let Some(value) = value {
+                ensure!(
+                    regex.is_match(value),
+                    "`{field}` does not match `pattern` {pattern:?}"
+                );
+            }
+        }
+    }
     Ok(locked::Variable {
-        default: variable.default.clone(),
+        default: variable.default,
         secret: variable.secret,
+        description: variable.description,
+        example: variable.example,
+        pattern: variable.pattern,
     })
 }
 
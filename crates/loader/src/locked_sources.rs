@@ -0,0 +1,89 @@
+//! The registry sources recorded in an application's lock file.
+//!
+//! `spin lock` records the digest of each component's Wasm, along with the
+//! registry reference it was fetched from. A component whose source is a
+//! registry reference without a pinned digest can then be loaded from the
+//! cache by its locked digest, with no trip to the registry to resolve its
+//! version, as long as the reference hasn't changed since it was locked.
+
+use std::collections::HashMap;
+use std::path::Path;
+
+use serde::Deserialize;
+
+/// The lock file of an application, next to its manifest.
+const LOCK_FILE: &str = "spin.lock";
+
+/// The registry sources of the components in a lock file, by component ID.
+#[derive(Debug, Default)]
+pub(crate) struct LockedSources {
+    components: HashMap<String, LockedSource>,
+}
+
+#[derive(Debug, Deserialize)]
+struct LockedSource {
+    digest: String,
+    reference: Option<String>,
+}
+
+impl LockedSources {
+    /// Reads the lock file in `app_root`. A missing or unreadable lock file
+    /// records no sources, as it is only used to avoid fetches.
+    pub fn read(app_root: &Path) -> Self {
+        let path = app_root.join(LOCK_FILE);
+        let Ok(contents) = std::fs::read_to_string(&path) else {
+            return Self::default();
+        };
+        Self::parse(&contents).unwrap_or_else(|err| {
+            tracing::warn!("Ignoring invalid lock file {}: {err}", path.display());
+            Self::default()
+        })
+    }
+
+    fn parse(contents: &str) -> serde_json::Result<Self> {
+        #[derive(Deserialize)]
+        struct LockFile {
+            #[serde(default)]
+            components: HashMap<String, LockedSource>,
+        }
+        let lock: LockFile = serde_json::from_str(contents)?;
+        Ok(Self {
+            components: lock.components,
+        })
+    }
+
+    /// The locked digest of component `id`, if it was locked with the same
+    /// `<registry>:<version>` reference.
+    pub fn digest(&self, id: &str, reference: &str) -> Option<&str> {
+        let source = self.components.get(id)?;
+        (source.reference.as_deref() == Some(reference)).then_some(source.digest.as_str())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn digests_are_found_by_reference() {
+        let sources = LockedSources::parse(
+            r#"{
+                "lock_version": 1,
+                "components": {
+                    "api": { "digest": "sha256:0", "reference": "ghcr.io/acme/api:1.0.0" },
+                    "web": { "digest": "sha256:1" }
+                },
+                "variables": {},
+                "triggers": []
+            }"#,
+        )
+        .unwrap();
+        assert_eq!(
+            sources.digest("api", "ghcr.io/acme/api:1.0.0"),
+            Some("sha256:0")
+        );
+        assert_eq!(sources.digest("api", "ghcr.io/acme/api:1.1.0"), None);
+        assert_eq!(sources.digest("web", "ghcr.io/acme/web:1.0.0"), None);
+        assert_eq!(sources.digest("other", "ghcr.io/acme/api:1.0.0"), None);
+    }
+}
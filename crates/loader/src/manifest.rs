@@ -0,0 +1,510 @@
+//! Reading an app manifest which is split across several files.
+//!
+//! A manifest may include fragment files, given as glob patterns relative to the
+//! manifest directory:
+//!
+//! ```toml
+//! include = ["components/*.toml"]
+//! ```
+//!
+//! Fragments are merged into the manifest in the order their patterns are listed, and
+//! the files matching a pattern in path order. A fragment adds to the manifest but may
+//! not change it: its `[[trigger.<type>]]` entries are appended to those already defined,
+//! but setting any other key to a different value from the manifest or an earlier
+//! fragment is an error. Fragments may not include other fragments.
+//!
+//! If an environment is given, its overlay file, `spin.<environment>.toml` beside
+//! `spin.toml`, is then merged over the result. An overlay overrides: tables are merged
+//! key by key, and any other value, including an array, replaces the one it overlays.
+//!
+//! Relative paths in fragments and overlays are relative to the manifest directory, as in
+//! the manifest itself. The files that set each key are recorded, so that errors in a
+//! manifest composed from several files can say which to look at.
+//!
+//! A manifest may require a minimum version of Spin with `min_spin_version = "2.6"`. This
+//! is checked before the manifest is parsed, as a manifest written for a newer Spin may
+//! use keys this one can't parse.
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use anyhow::{anyhow, bail, Context, Result};
+use spin_common::ui::quoted_path;
+use spin_manifest::deprecation::{self, Deprecation};
+use spin_manifest::schema::v2::{self, AppManifest};
+use toml::{Table, Value};
+
+const INCLUDE_KEY: &str = "include";
+const MIN_SPIN_VERSION_KEY: &str = "min_spin_version";
+
+/// The version of Spin reading the manifest.
+const SPIN_VERSION: &str = env!("CARGO_PKG_VERSION");
+
+/// A manifest, and the files it was read from.
+pub(crate) struct ComposedManifest {
+    pub manifest: AppManifest,
+    pub sources: Sources,
+    /// The deprecated constructs the manifest uses
+    pub deprecations: Vec<Deprecation>,
+}
+
+/// The files which set each key of a composed manifest, by dotted key path.
+#[derive(Default)]
+pub(crate) struct Sources {
+    files: Vec<String>,
+    keys: HashMap<String, Vec<usize>>,
+}
+
+impl Sources {
+    /// Describes the files which set the key, e.g. `"spin.toml, overridden by
+    /// spin.prod.toml"`. Returns `None` if the manifest was read from a single file, as
+    /// there is then nowhere else to look.
+    pub fn describe(&self, key: &str) -> Option<String> {
+        if self.files.len() < 2 {
+            return None;
+        }
+        let (first, overrides) = self.keys.get(key)?.split_first()?;
+        let mut description = self.files[*first].clone();
+        if !overrides.is_empty() {
+            let overrides: Vec<_> = overrides.iter().map(|i| &*self.files[*i]).collect();
+            description.push_str(&format!(", overridden by {}", overrides.join(", ")));
+        }
+        Some(description)
+    }
+
+    /// Names the file which sets the key or, as for [`describe`](Self::describe), the
+    /// files if the manifest was composed from several.
+    pub fn locate(&self, key: &str) -> String {
+        self.describe(key)
+            .unwrap_or_else(|| self.files.first().cloned().unwrap_or_default())
+    }
+
+    fn add_file(&mut self, name: String) -> usize {
+        self.files.push(name);
+        self.files.len() - 1
+    }
+
+    fn record(&mut self, key: &str, file: usize) {
+        let files = self.keys.entry(key.to_owned()).or_default();
+        if files.last() != Some(&file) {
+            files.push(file);
+        }
+    }
+}
+
+/// How a file is merged into the manifest read so far.
+#[derive(Clone, Copy, PartialEq)]
+enum Merge {
+    Include,
+    Overlay,
+}
+
+/// Reads the manifest at `path` with its fragments and, if `environment` is given, the
+/// overlay for that environment.
+pub(crate) fn read(path: &Path, environment: Option<&str>) -> Result<ComposedManifest> {
+    let (manifest, sources) = compose(path, environment)?;
+    check_min_spin_version(&manifest, SPIN_VERSION)?;
+    let deprecations = deprecation::deprecations(&manifest);
+    let manifest = parse(manifest, &sources)?;
+    Ok(ComposedManifest {
+        manifest,
+        sources,
+        deprecations,
+    })
+}
+
+/// Merges the manifest at `path` with its fragments and overlay as for [`read`], returning
+/// the merged table without parsing it.
+pub(crate) fn compose(path: &Path, environment: Option<&str>) -> Result<(Table, Sources)> {
+    let manifest_dir = path.parent().unwrap_or(Path::new("."));
+    let mut sources = Sources::default();
+
+    let mut manifest = read_table(path)?;
+    let base = sources.add_file(display_name(path, manifest_dir));
+    record_all(&manifest, "", base, &mut sources);
+
+    let patterns = match manifest.remove(INCLUDE_KEY) {
+        Some(include) => include_patterns(include)?,
+        None => vec![],
+    };
+    for pattern in patterns {
+        for fragment_path in fragment_paths(manifest_dir, &pattern, path)? {
+            let mut fragment = read_table(&fragment_path)?;
+            if fragment.remove(INCLUDE_KEY).is_some() {
+                bail!(
+                    "{} cannot include other files: only the app manifest may use `{INCLUDE_KEY}`",
+                    quoted_path(&fragment_path)
+                );
+            }
+            let file = sources.add_file(display_name(&fragment_path, manifest_dir));
+            merge(
+                &mut manifest,
+                fragment,
+                Merge::Include,
+                "",
+                file,
+                &mut sources,
+            )?;
+        }
+    }
+
+    if let Some(environment) = environment {
+        let overlay_path = overlay_path(path, environment)?;
+        if !overlay_path.exists() {
+            bail!(
+                "No overlay for environment `{environment}`: expected {}",
+                quoted_path(&overlay_path)
+            );
+        }
+        let mut overlay = read_table(&overlay_path)?;
+        if overlay.remove(INCLUDE_KEY).is_some() {
+            bail!(
+                "{} cannot include other files: only the app manifest may use `{INCLUDE_KEY}`",
+                quoted_path(&overlay_path)
+            );
+        }
+        let file = sources.add_file(display_name(&overlay_path, manifest_dir));
+        merge(
+            &mut manifest,
+            overlay,
+            Merge::Overlay,
+            "",
+            file,
+            &mut sources,
+        )?;
+    }
+
+    Ok((manifest, sources))
+}
+
+/// Returns the path of the overlay for `environment`: for `spin.toml`, `spin.<environment>.toml`.
+fn overlay_path(path: &Path, environment: &str) -> Result<PathBuf> {
+    if environment.is_empty() || environment.contains(['/', '\\', '.']) {
+        bail!("Invalid environment name `{environment}`");
+    }
+    let stem = path
+        .file_stem()
+        .context("manifest path has no file name")?
+        .to_string_lossy();
+    let file_name = match path.extension() {
+        Some(extension) => format!("{stem}.{environment}.{}", extension.to_string_lossy()),
+        None => format!("{stem}.{environment}"),
+    };
+    Ok(path.with_file_name(file_name))
+}
+
+fn read_table(path: &Path) -> Result<Table> {
+    let contents = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read {}", quoted_path(path)))?;
+    toml::from_str(&contents).with_context(|| format!("Failed to parse {}", quoted_path(path)))
+}
+
+fn include_patterns(include: Value) -> Result<Vec<String>> {
+    let Value::Array(patterns) = include else {
+        bail!("`{INCLUDE_KEY}` must be a list of file patterns");
+    };
+    patterns
+        .into_iter()
+        .map(|pattern| match pattern {
+            Value::String(pattern) => Ok(pattern),
+            _ => Err(anyhow!("`{INCLUDE_KEY}` must be a list of file patterns")),
+        })
+        .collect()
+}
+
+/// Returns the files matching an include pattern, in path order. A pattern which is not a
+/// glob must name a file which exists.
+fn fragment_paths(
+    manifest_dir: &Path,
+    pattern: &str,
+    manifest_path: &Path,
+) -> Result<Vec<PathBuf>> {
+    let full_pattern = manifest_dir.join(pattern);
+    let full_pattern = full_pattern.to_string_lossy();
+    let mut paths = glob::glob(&full_pattern)
+        .with_context(|| format!("Invalid include pattern `{pattern}`"))?
+        .collect::<Result<Vec<_>, _>>()
+        .with_context(|| format!("Failed to read files matching `{pattern}`"))?;
+    paths.retain(|path| path != manifest_path);
+    paths.sort();
+    if paths.is_empty() && glob::Pattern::escape(pattern) == pattern {
+        bail!("Included file `{pattern}` does not exist");
+    }
+    Ok(paths)
+}
+
+/// Merges `from` into `into`, recording the keys `from` sets as coming from `file`.
+fn merge(
+    into: &mut Table,
+    from: Table,
+    mode: Merge,
+    prefix: &str,
+    file: usize,
+    sources: &mut Sources,
+) -> Result<()> {
+    for (key, value) in from {
+        let path = if prefix.is_empty() {
+            key.clone()
+        } else {
+            format!("{prefix}.{key}")
+        };
+        match (into.get_mut(&key), value) {
+            (Some(Value::Table(existing)), Value::Table(table)) => {
+                sources.record(&path, file);
+                merge(existing, table, mode, &path, file, sources)?;
+            }
+            (Some(Value::Array(existing)), Value::Array(array))
+                if mode == Merge::Include && is_array_of_tables(existing, &array) =>
+            {
+                sources.record(&path, file);
+                let offset = existing.len();
+                for (index, value) in array.iter().enumerate() {
+                    record_value(value, &format!("{path}.{}", offset + index), file, sources);
+                }
+                existing.extend(array);
+            }
+            (Some(existing), value) if mode == Merge::Include => {
+                if *existing == value {
+                    continue;
+                }
+                let defined_in = sources
+                    .keys
+                    .get(&path)
+                    .and_then(|files| files.first())
+                    .map(|i| sources.files[*i].as_str())
+                    .unwrap_or("the manifest");
+                bail!(
+                    "`{path}` is set in both {defined_in} and {}: included files may add to the manifest but not change it",
+                    sources.files[file]
+                );
+            }
+            (Some(existing), value) => {
+                record_value(&value, &path, file, sources);
+                *existing = value;
+            }
+            (None, value) => {
+                record_value(&value, &path, file, sources);
+                into.insert(key, value);
+            }
+        }
+    }
+    Ok(())
+}
+
+fn is_array_of_tables(existing: &[Value], added: &[Value]) -> bool {
+    existing.iter().chain(added).all(Value::is_table)
+}
+
+fn record_all(table: &Table, prefix: &str, file: usize, sources: &mut Sources) {
+    for (key, value) in table {
+        let path = if prefix.is_empty() {
+            key.clone()
+        } else {
+            format!("{prefix}.{key}")
+        };
+        record_value(value, &path, file, sources);
+    }
+}
+
+fn record_value(value: &Value, path: &str, file: usize, sources: &mut Sources) {
+    sources.record(path, file);
+    match value {
+        Value::Table(table) => record_all(table, path, file, sources),
+        Value::Array(array) => {
+            for (index, value) in array.iter().enumerate() {
+                if value.is_table() {
+                    record_value(value, &format!("{path}.{index}"), file, sources);
+                }
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Parses the composed manifest. If it is invalid and was composed from several files,
+/// the error names the files which define the first invalid component.
+fn parse(manifest: Table, sources: &Sources) -> Result<AppManifest> {
+    let components = manifest
+        .get("component")
+        .and_then(Value::as_table)
+        .cloned()
+        .unwrap_or_default();
+    let text = toml::to_string(&manifest).context("Failed to serialize composed manifest")?;
+    match spin_manifest::manifest_from_str(&text) {
+        Ok(manifest) => Ok(manifest),
+        Err(err) if sources.files.len() < 2 => Err(err.into()),
+        Err(err) => {
+            for (id, component) in components {
+                if let Err(component_err) = Value::Table(component).try_into::<v2::Component>() {
+                    let files = sources
+                        .describe(&format!("component.{id}"))
+                        .unwrap_or_default();
+                    return Err(anyhow::Error::new(component_err)
+                        .context(format!("Invalid component `{id}` (from {files})")));
+                }
+            }
+            Err(anyhow::Error::new(err).context(format!(
+                "Invalid manifest composed from {}",
+                sources.files.join(", ")
+            )))
+        }
+    }
+}
+
+/// Fails if the manifest requires a newer Spin than `spin_version`. Pre-releases count as
+/// the release they precede, so that development builds can run apps which require them.
+fn check_min_spin_version(manifest: &Table, spin_version: &str) -> Result<()> {
+    let Some(min_version) = manifest.get(MIN_SPIN_VERSION_KEY) else {
+        return Ok(());
+    };
+    let required = min_version
+        .as_str()
+        .and_then(parse_min_version)
+        .with_context(|| {
+            format!("Invalid `{MIN_SPIN_VERSION_KEY}` {min_version}: expected a version such as \"2.6\"")
+        })?;
+    let current = semver::Version::parse(spin_version)?;
+    if (current.major, current.minor, current.patch) < required {
+        bail!(
+            "This app requires Spin {} or later, but this is Spin {spin_version}. Run `spin self-update` to upgrade Spin.",
+            min_version.as_str().unwrap_or_default()
+        );
+    }
+    Ok(())
+}
+
+/// Parses a `major[.minor[.patch]]` version, with missing parts as zero.
+fn parse_min_version(version: &str) -> Option<(u64, u64, u64)> {
+    let mut parts = version.split('.').map(|part| part.parse::<u64>().ok());
+    let major = parts.next()??;
+    let minor = parts.next().unwrap_or(Some(0))?;
+    let patch = parts.next().unwrap_or(Some(0))?;
+    parts.next().is_none().then_some((major, minor, patch))
+}
+
+fn display_name(path: &Path, manifest_dir: &Path) -> String {
+    path.strip_prefix(manifest_dir)
+        .unwrap_or(path)
+        .display()
+        .to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write(dir: &Path, name: &str, contents: &str) {
+        let path = dir.join(name);
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(path, contents).unwrap();
+    }
+
+    const BASE: &str = r#"
+        spin_manifest_version = 2
+        include = ["components/*.toml"]
+
+        [application]
+        name = "app"
+
+        [[trigger.http]]
+        route = "/a"
+        component = "a"
+
+        [component.a]
+        source = "a.wasm"
+    "#;
+
+    #[test]
+    fn fragments_add_triggers_and_components() {
+        let dir = tempfile::tempdir().unwrap();
+        write(dir.path(), "spin.toml", BASE);
+        write(
+            dir.path(),
+            "components/b.toml",
+            r#"
+                [[trigger.http]]
+                route = "/b"
+                component = "b"
+
+                [component.b]
+                source = "b.wasm"
+            "#,
+        );
+
+        let composed = read(&dir.path().join("spin.toml"), None).unwrap();
+        assert_eq!(composed.manifest.triggers["http"].len(), 2);
+        assert_eq!(composed.manifest.components.len(), 2);
+        assert_eq!(
+            composed.sources.describe("component.b").as_deref(),
+            Some(format!("components{}b.toml", std::path::MAIN_SEPARATOR).as_str())
+        );
+    }
+
+    #[test]
+    fn fragments_cannot_change_the_manifest() {
+        let dir = tempfile::tempdir().unwrap();
+        write(dir.path(), "spin.toml", BASE);
+        write(
+            dir.path(),
+            "components/a.toml",
+            r#"
+                [component.a]
+                source = "other.wasm"
+            "#,
+        );
+
+        let err = read(&dir.path().join("spin.toml"), None)
+            .err()
+            .unwrap()
+            .to_string();
+        assert!(
+            err.contains("`component.a.source` is set in both spin.toml"),
+            "{err}"
+        );
+    }
+
+    #[test]
+    fn overlays_override_the_manifest() {
+        let dir = tempfile::tempdir().unwrap();
+        write(dir.path(), "spin.toml", BASE);
+        write(
+            dir.path(),
+            "spin.prod.toml",
+            r#"
+                [component.a]
+                source = "a-release.wasm"
+                environment = { LOG = "warn" }
+            "#,
+        );
+        let path = dir.path().join("spin.toml");
+
+        let composed = read(&path, Some("prod")).unwrap();
+        let component = &composed.manifest.components["a"];
+        assert!(
+            matches!(&component.source, v2::ComponentSource::Local(path) if path == "a-release.wasm")
+        );
+        assert_eq!(component.environment["LOG"], "warn");
+        assert_eq!(
+            composed.sources.describe("component.a.source").as_deref(),
+            Some("spin.toml, overridden by spin.prod.toml")
+        );
+
+        assert!(read(&path, Some("staging")).is_err());
+    }
+
+    #[test]
+    fn newer_spin_can_be_required() {
+        let manifest = |min: &str| Table::from_iter([(MIN_SPIN_VERSION_KEY.into(), min.into())]);
+        assert!(check_min_spin_version(&manifest("2.6"), "2.6.0").is_ok());
+        assert!(check_min_spin_version(&manifest("2.6"), "2.6.0-pre0").is_ok());
+        assert!(check_min_spin_version(&manifest("2"), "3.0.1").is_ok());
+        assert!(check_min_spin_version(&Table::new(), "1.0.0").is_ok());
+
+        let err = check_min_spin_version(&manifest("2.6.1"), "2.6.0").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "This app requires Spin 2.6.1 or later, but this is Spin 2.6.0. Run `spin self-update` to upgrade Spin."
+        );
+        assert!(check_min_spin_version(&manifest("2.x"), "2.6.0").is_err());
+    }
+}
@@ -0,0 +1,227 @@
+//! Fetching component Wasm from OCI registries.
+//!
+//! Components are pulled with the same OCI client as `spin registry pull`,
+//! using the credentials saved by `spin registry login`, or Docker's, and
+//! pulling anonymously otherwise. An image index is resolved to its Wasm
+//! image. The Wasm layer of the image is verified against the digest given
+//! for it by the registry, and against the digest pinned in the app manifest
+//! if there is one.
+
+use std::fmt::Display;
+use std::path::PathBuf;
+
+use anyhow::{ensure, Context, Result};
+use oci_distribution::{
+    client::{ClientConfig, ClientProtocol},
+    manifest::{ImageIndexEntry, OciDescriptor},
+};
+
+use crate::cache::Cache;
+
+pub mod auth;
+
+const DOCKER_HUB: &str = "registry-1.docker.io";
+
+/// The media types of Wasm layers: that of `wkg` and `oci-wasm`, and that of
+/// Spin's own application images.
+const WASM_LAYER_MEDIA_TYPES: &[&str] = &[
+    "application/wasm",
+    "application/vnd.wasm.content.layer.v1+wasm",
+];
+
+/// A version of a component in a registry.
+#[derive(Debug, PartialEq)]
+pub(crate) struct Reference {
+    host: String,
+    repository: String,
+    tag: String,
+}
+
+impl Reference {
+    /// Parses a `registry` reference such as `ghcr.io/acme/widget`, with the
+    /// given version as its tag. References without a registry host are to
+    /// Docker Hub.
+    pub fn parse(registry: &str, version: &str) -> Result<Self> {
+        let (host, repository) = match registry.split_once('/') {
+            Some((host, repository)) if host.contains(['.', ':']) || host == "localhost" => {
+                (host.to_owned(), repository.to_owned())
+            }
+            Some(_) => (DOCKER_HUB.to_owned(), registry.to_owned()),
+            None => (DOCKER_HUB.to_owned(), format!("library/{registry}")),
+        };
+        ensure!(
+            !repository.is_empty() && !repository.contains([':', '@']),
+            "invalid registry reference {registry:?}; expected e.g. \"ghcr.io/acme/widget\""
+        );
+        ensure!(
+            !version.is_empty() && !version.contains(['/', ':', '@']),
+            "invalid version {version:?}"
+        );
+        Ok(Self {
+            host,
+            repository,
+            tag: version.to_owned(),
+        })
+    }
+
+    fn oci_reference(&self) -> oci_distribution::Reference {
+        oci_distribution::Reference::with_tag(
+            self.host.clone(),
+            self.repository.clone(),
+            self.tag.clone(),
+        )
+    }
+
+    /// Local registries are served over plain HTTP.
+    fn protocol(&self) -> ClientProtocol {
+        if is_loopback(&self.host) {
+            ClientProtocol::Http
+        } else {
+            ClientProtocol::Https
+        }
+    }
+}
+
+/// Whether `host`, with an optional port, is `localhost` or a loopback
+/// address.
+fn is_loopback(host: &str) -> bool {
+    let name = match host.rsplit_once(':') {
+        Some((name, port)) if !port.is_empty() && port.bytes().all(|b| b.is_ascii_digit()) => name,
+        _ => host,
+    };
+    matches!(name, "localhost" | "127.0.0.1" | "[::1]")
+}
+
+impl Display for Reference {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}/{}:{}", self.host, self.repository, self.tag)
+    }
+}
+
+/// Fetches the Wasm of the component into the cache, unless it is already
+/// there, returning its digest and its path in the cache.
+pub(crate) async fn fetch(
+    reference: &Reference,
+    pinned_digest: Option<&str>,
+    cache: &Cache,
+) -> Result<(String, PathBuf)> {
+    let mut client = oci_distribution::Client::new(ClientConfig {
+        protocol: reference.protocol(),
+        platform_resolver: Some(Box::new(wasm_platform)),
+        ..Default::default()
+    });
+    let image = reference.oci_reference();
+    let credentials = auth::credentials(image.resolve_registry()).await;
+    let (manifest, _) = client
+        .pull_image_manifest(&image, &credentials)
+        .await
+        .with_context(|| format!("Failed to fetch manifest of {reference}"))?;
+    let layer = wasm_layer(&manifest.layers)
+        .with_context(|| format!("{reference} is not a Wasm component image"))?;
+    let digest = layer.digest.clone();
+
+    if let Some(pinned_digest) = pinned_digest {
+        ensure!(
+            digest == pinned_digest,
+            "{reference} has digest {digest}, but the manifest requires {pinned_digest}"
+        );
+    }
+    ensure!(
+        digest.starts_with("sha256:"),
+        "{reference} has unsupported digest {digest:?}"
+    );
+
+    if let Some(path) = cache.verified_wasm_file(&digest) {
+        return Ok((digest, path));
+    }
+    let mut bytes = Vec::with_capacity(layer.size.try_into().unwrap_or_default());
+    client
+        .pull_blob(&image, layer, &mut bytes)
+        .await
+        .with_context(|| format!("Failed to fetch Wasm of {reference}"))?;
+    let actual_digest = format!(
+        "sha256:{}",
+        spin_common::sha256::hex_digest_from_bytes(&bytes)
+    );
+    ensure!(
+        actual_digest == digest,
+        "invalid content digest for {reference}; expected {digest}, downloaded {actual_digest}"
+    );
+    cache.write_wasm(&bytes, &digest).await?;
+    Ok((digest.clone(), cache.wasm_path(&digest)))
+}
+
+/// Chooses the Wasm image from an image index: the one for the `wasm`
+/// architecture, or the only image there is.
+fn wasm_platform(manifests: &[ImageIndexEntry]) -> Option<String> {
+    manifests
+        .iter()
+        .find(|entry| {
+            entry
+                .platform
+                .as_ref()
+                .is_some_and(|platform| platform.architecture == "wasm")
+        })
+        .or(match manifests {
+            [entry] => Some(entry),
+            _ => None,
+        })
+        .map(|entry| entry.digest.clone())
+}
+
+fn wasm_layer(layers: &[OciDescriptor]) -> Option<&OciDescriptor> {
+    layers
+        .iter()
+        .find(|layer| WASM_LAYER_MEDIA_TYPES.contains(&layer.media_type.as_str()))
+        .or(match layers {
+            [layer] => Some(layer),
+            _ => None,
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn references_are_parsed() {
+        let reference = Reference::parse("ghcr.io/acme/widget", "1.2.3").unwrap();
+        assert_eq!(reference.to_string(), "ghcr.io/acme/widget:1.2.3");
+        assert!(matches!(reference.protocol(), ClientProtocol::Https));
+
+        let reference = Reference::parse("widget", "1.2.3").unwrap();
+        assert_eq!(
+            reference.to_string(),
+            "registry-1.docker.io/library/widget:1.2.3"
+        );
+
+        let reference = Reference::parse("localhost:5000/widget", "latest").unwrap();
+        assert_eq!(reference.to_string(), "localhost:5000/widget:latest");
+        assert!(matches!(reference.protocol(), ClientProtocol::Http));
+
+        assert!(Reference::parse("ghcr.io/acme/widget:1.2.3", "1.2.3").is_err());
+        assert!(Reference::parse("ghcr.io/acme/widget", "").is_err());
+    }
+
+    #[test]
+    fn only_loopback_hosts_are_local() {
+        for host in [
+            "localhost",
+            "localhost:5000",
+            "127.0.0.1:5000",
+            "[::1]",
+            "[::1]:5000",
+        ] {
+            assert!(is_loopback(host), "{host}");
+        }
+        for host in [
+            "localhost.attacker.com",
+            "localhost.attacker.com:5000",
+            "127.0.0.1.nip.io",
+            "localhost:",
+            "ghcr.io",
+        ] {
+            assert!(!is_loopback(host), "{host}");
+        }
+    }
+}
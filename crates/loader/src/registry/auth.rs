@@ -4,10 +4,12 @@ use std::{
 };
 
 use anyhow::{bail, Context, Result};
+use docker_credential::DockerCredential;
 use oci_distribution::secrets::RegistryAuth;
 use serde::{Deserialize, Serialize};
 use spin_common::ui::quoted_path;
 
+/// Registry credentials saved by `spin registry login`.
 #[derive(Serialize, Deserialize)]
 pub struct AuthConfig {
     /// Map between registry server and base64 encoded username:password credential set.
@@ -99,3 +101,28 @@ impl AuthConfig {
             .with_context(|| format!("cannot save authentication file {}", quoted_path(p)))
     }
 }
+
+/// Gets the credentials to use for the registry `server`: those saved by
+/// `spin registry login`, or else Docker's, or else anonymous access.
+pub async fn credentials(server: &str) -> RegistryAuth {
+    match AuthConfig::get_auth_from_default(server).await {
+        Ok(c) => c,
+        Err(_) => {
+            match docker_credential::get_credential(server) {
+                Err(e) => {
+                    tracing::trace!("Cannot retrieve credentials from Docker, attempting to use anonymous auth: {}", e);
+                    RegistryAuth::Anonymous
+                }
+
+                Ok(DockerCredential::UsernamePassword(username, password)) => {
+                    tracing::trace!("Found Docker credentials");
+                    RegistryAuth::Basic(username, password)
+                }
+                Ok(DockerCredential::IdentityToken(_)) => {
+                    tracing::trace!("Cannot use contents of Docker config, identity token not supported. Using anonymous auth");
+                    RegistryAuth::Anonymous
+                }
+            }
+        }
+    }
+}
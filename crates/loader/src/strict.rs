@@ -0,0 +1,310 @@
+//! Strict checking of app manifests for keys Spin doesn't know.
+//!
+//! Parsing a manifest already rejects unknown keys in most of its tables, but
+//! stops at the first, and cannot tell a misspelled `[[trigger.htpp]]` from a
+//! plugin trigger. Strict checking walks the whole manifest against its JSON
+//! Schema, reporting every unknown key with the file it is in and the key it
+//! was most likely meant to be, and rejects trigger types which are near
+//! misses for those built in to Spin.
+
+use std::fmt::Display;
+use std::path::Path;
+
+use anyhow::{bail, Result};
+use serde_json::Value as Json;
+use toml::Value;
+
+use crate::manifest::Sources;
+
+/// Checks the manifest at `path`, composed with its fragments and overlay,
+/// for unknown keys.
+pub(crate) fn check(path: &Path, environment: Option<&str>) -> Result<()> {
+    let (manifest, sources) = crate::manifest::compose(path, environment)?;
+    if manifest
+        .get("spin_manifest_version")
+        .and_then(Value::as_integer)
+        != Some(2)
+    {
+        bail!("Only version 2 manifests can be checked strictly. Run `spin doctor` to upgrade the manifest.");
+    }
+
+    let schema = serde_json::to_value(spin_manifest::json_schema())?;
+    let walker = Walker {
+        definitions: &schema["definitions"],
+    };
+    let manifest = Value::Table(manifest);
+    let mut unknown = vec![];
+    walker.walk(&schema, &manifest, "", &mut unknown);
+    unknown.extend(unknown_trigger_types(&manifest));
+
+    if !unknown.is_empty() {
+        let report: Vec<_> = unknown
+            .iter()
+            .map(|key| format!("  - {key} ({})", sources.locate(&key.path())))
+            .collect();
+        bail!("Unknown manifest keys:\n{}", report.join("\n"));
+    }
+    Ok(())
+}
+
+/// A key which the manifest schema doesn't allow.
+#[derive(Debug, PartialEq)]
+struct UnknownKey {
+    /// The dotted path of the table containing the key
+    table: String,
+    key: String,
+    suggestion: Option<String>,
+}
+
+impl UnknownKey {
+    fn path(&self) -> String {
+        join(&self.table, &self.key)
+    }
+}
+
+impl Display for UnknownKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.table.is_empty() {
+            write!(f, "`{}` at the top level", self.key)?;
+        } else {
+            write!(f, "`{}` in `{}`", self.key, self.table)?;
+        }
+        if let Some(suggestion) = &self.suggestion {
+            write!(f, ": did you mean `{suggestion}`?")?;
+        }
+        Ok(())
+    }
+}
+
+/// Walks a manifest along with its JSON Schema.
+struct Walker<'a> {
+    definitions: &'a Json,
+}
+
+impl Walker<'_> {
+    fn walk(&self, schema: &Json, value: &Value, path: &str, unknown: &mut Vec<UnknownKey>) {
+        let schema = self.resolve(schema);
+
+        if let Some(variants) = schema
+            .get("anyOf")
+            .or_else(|| schema.get("oneOf"))
+            .and_then(Json::as_array)
+        {
+            // Untagged enums: report the unknown keys for the variant the
+            // value fits best
+            let best = variants
+                .iter()
+                .filter(|variant| self.fits(variant, value))
+                .map(|variant| {
+                    let mut found = vec![];
+                    self.walk(variant, value, path, &mut found);
+                    found
+                })
+                .min_by_key(Vec::len);
+            unknown.extend(best.unwrap_or_default());
+            return;
+        }
+        if let Some([only]) = schema
+            .get("allOf")
+            .and_then(Json::as_array)
+            .map(Vec::as_slice)
+        {
+            return self.walk(only, value, path, unknown);
+        }
+
+        match value {
+            Value::Table(table) => {
+                let properties = schema.get("properties").and_then(Json::as_object);
+                let additional = schema.get("additionalProperties");
+                for (key, value) in table {
+                    let key_path = join(path, key);
+                    if let Some(property) = properties.and_then(|p| p.get(key)) {
+                        self.walk(property, value, &key_path, unknown);
+                    } else if let Some(additional @ Json::Object(_)) = additional {
+                        self.walk(additional, value, &key_path, unknown);
+                    } else if additional == Some(&Json::Bool(false)) {
+                        let known = properties.into_iter().flat_map(|p| p.keys());
+                        unknown.push(UnknownKey {
+                            table: path.to_owned(),
+                            key: key.clone(),
+                            suggestion: suggest(key, known.map(String::as_str)),
+                        });
+                    }
+                }
+            }
+            Value::Array(items) => {
+                if let Some(item) = schema.get("items") {
+                    for (index, value) in items.iter().enumerate() {
+                        self.walk(item, value, &join(path, &index.to_string()), unknown);
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Follows `$ref`s to the schema they refer to.
+    fn resolve<'s>(&'s self, mut schema: &'s Json) -> &'s Json {
+        while let Some(reference) = schema.get("$ref").and_then(Json::as_str) {
+            let Some(name) = reference.strip_prefix("#/definitions/") else {
+                break;
+            };
+            schema = &self.definitions[name];
+        }
+        schema
+    }
+
+    /// Returns whether the type of the value is one the schema allows.
+    fn fits(&self, schema: &Json, value: &Value) -> bool {
+        let schema = self.resolve(schema);
+        if let Some(variants) = schema.get("anyOf").and_then(Json::as_array) {
+            return variants.iter().any(|variant| self.fits(variant, value));
+        }
+        let value_type = match value {
+            Value::String(_) | Value::Datetime(_) => "string",
+            Value::Integer(_) => "integer",
+            Value::Float(_) => "number",
+            Value::Boolean(_) => "boolean",
+            Value::Array(_) => "array",
+            Value::Table(_) => "object",
+        };
+        let allows = |t: &Json| t == value_type || (t == "number" && value_type == "integer");
+        match schema.get("type") {
+            Some(Json::Array(types)) => types.iter().any(allows),
+            Some(t) => allows(t),
+            None => true,
+        }
+    }
+}
+
+/// Returns the trigger types which aren't built in to Spin, but are so
+/// close to one that they are most likely misspelled.
+fn unknown_trigger_types(manifest: &Value) -> Vec<UnknownKey> {
+    let Some(triggers) = manifest.get("trigger").and_then(Value::as_table) else {
+        return vec![];
+    };
+    triggers
+        .keys()
+        .filter(|trigger_type| !crate::world::builtin_trigger_types().any(|t| t == *trigger_type))
+        .filter_map(|trigger_type| {
+            Some(UnknownKey {
+                table: "trigger".into(),
+                key: trigger_type.clone(),
+                suggestion: Some(suggest(
+                    trigger_type,
+                    crate::world::builtin_trigger_types(),
+                )?),
+            })
+        })
+        .collect()
+}
+
+fn join(path: &str, key: &str) -> String {
+    if path.is_empty() {
+        key.to_owned()
+    } else {
+        format!("{path}.{key}")
+    }
+}
+
+/// Returns the candidate closest to `key`, if any is close enough to be
+/// what was meant.
+fn suggest<'a>(key: &str, candidates: impl Iterator<Item = &'a str>) -> Option<String> {
+    let max_distance = (key.chars().count() / 3).max(1);
+    candidates
+        .map(|candidate| (edit_distance(key, candidate), candidate))
+        .filter(|(distance, _)| *distance <= max_distance)
+        .min_by_key(|(distance, _)| *distance)
+        .map(|(_, candidate)| candidate.to_owned())
+}
+
+/// The Levenshtein distance between two strings.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+    for (i, a_char) in a.chars().enumerate() {
+        let mut diagonal = row[0];
+        row[0] = i + 1;
+        for (j, b_char) in b.iter().enumerate() {
+            let substitution = diagonal + usize::from(a_char != *b_char);
+            diagonal = row[j + 1];
+            row[j + 1] = substitution.min(row[j] + 1).min(diagonal + 1);
+        }
+    }
+    row[b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn unknown_keys(manifest: toml::Table) -> Vec<UnknownKey> {
+        let schema = serde_json::to_value(spin_manifest::json_schema()).unwrap();
+        let walker = Walker {
+            definitions: &schema["definitions"],
+        };
+        let manifest = Value::Table(manifest);
+        let mut unknown = vec![];
+        walker.walk(&schema, &manifest, "", &mut unknown);
+        unknown.extend(unknown_trigger_types(&manifest));
+        unknown
+    }
+
+    #[test]
+    fn unknown_keys_are_found_with_suggestions() {
+        let unknown = unknown_keys(toml::toml! {
+            spin_manifest_version = 2
+            [application]
+            name = "strict"
+            [[trigger.htpp]]
+            route = "/..."
+            component = "api"
+            [[trigger.cron]]
+            component = "api"
+            [component.api]
+            source = { registry = "ghcr.io/acme/api", version = "1.0.0", digets = "sha256:0" }
+            allowed_outbound_host = ["https://example.com"]
+            [component.api.build]
+            command = "make"
+            wrkdir = "api"
+        });
+        let found: Vec<_> = unknown.iter().map(ToString::to_string).collect();
+        assert_eq!(
+            found,
+            [
+                "`digets` in `component.api.source`: did you mean `digest`?",
+                "`allowed_outbound_host` in `component.api`: did you mean `allowed_outbound_hosts`?",
+                "`wrkdir` in `component.api.build`: did you mean `workdir`?",
+                "`htpp` in `trigger`: did you mean `http`?",
+            ]
+        );
+        assert_eq!(unknown[1].path(), "component.api.allowed_outbound_host");
+    }
+
+    #[test]
+    fn trigger_and_tool_settings_are_open() {
+        let unknown = unknown_keys(toml::toml! {
+            spin_manifest_version = 2
+            [application]
+            name = "strict"
+            [application.tool.lint]
+            level = "savage"
+            [[trigger.http]]
+            route = "/..."
+            component = "api"
+            [component.api]
+            source = "api.wasm"
+            [component.api.tool.clean]
+            command = "cargo clean"
+        });
+        assert_eq!(unknown, []);
+    }
+
+    #[test]
+    fn edit_distances() {
+        assert_eq!(edit_distance("http", "http"), 0);
+        assert_eq!(edit_distance("htpp", "http"), 1);
+        assert_eq!(edit_distance("workdir", "wrkdir"), 1);
+        assert_eq!(edit_distance("", "abc"), 3);
+    }
+}
@@ -0,0 +1,428 @@
+//! Checking components against the worlds Spin runs them in.
+//!
+//! A component which doesn't export the interface its trigger calls, or which
+//! imports interfaces Spin doesn't provide, otherwise fails when it is first
+//! instantiated, with a linker error which doesn't say what to do about it.
+//! These checks run when an app is loaded and after `spin build`, and report
+//! each mismatch with a hint at the fix.
+//!
+//! Only components called by triggers which Spin knows are checked: plugin
+//! triggers may provide other interfaces. Core modules are adapted into
+//! components when they are loaded, so are not checked either.
+
+use std::collections::{HashMap, HashSet};
+use std::fmt::Display;
+use std::path::{Path, PathBuf};
+
+use anyhow::{bail, Result};
+use serde_json::Value;
+use spin_common::paths::parent_dir;
+use spin_locked_app::locked::LockedApp;
+use spin_manifest::schema::v2::{ComponentSource, ComponentSpec};
+use wasmparser::{Encoding, Parser, Payload};
+
+/// The interfaces each trigger type calls its components through, any one of
+/// which will do.
+const TRIGGER_EXPORTS: &[(&str, &[&str])] = &[
+    (
+        "http",
+        &[
+            "wasi:http/incoming-handler@0.2.0",
+            "wasi:http/incoming-handler@0.2.0-rc-2023-11-10",
+            "wasi:http/incoming-handler@0.2.0-rc-2023-10-18",
+            "fermyon:spin/inbound-http",
+        ],
+    ),
+    (
+        "redis",
+        &[
+            "fermyon:spin/inbound-redis",
+            "fermyon:spin/inbound-redis-stream@2.0.0",
+        ],
+    ),
+    ("command", &["wasi:cli/run@0.2.0"]),
+    ("kafka", &["fermyon:spin/inbound-kafka@2.0.0"]),
+    ("nats", &["fermyon:spin/inbound-nats@2.0.0"]),
+    ("sqs", &["fermyon:spin/inbound-sqs@2.0.0"]),
+    ("grpc", &["fermyon:spin/inbound-grpc@2.0.0"]),
+    ("file-watch", &["fermyon:spin/inbound-file-watch@2.0.0"]),
+];
+
+/// Returns the trigger types built in to Spin.
+pub(crate) fn builtin_trigger_types() -> impl Iterator<Item = &'static str> {
+    TRIGGER_EXPORTS
+        .iter()
+        .map(|(trigger_type, _)| *trigger_type)
+}
+
+/// The WIT packages whose interfaces Spin provides to components.
+const HOST_PACKAGES: &[&str] = &[
+    "wasi:cli",
+    "wasi:clocks",
+    "wasi:filesystem",
+    "wasi:http",
+    "wasi:io",
+    "wasi:random",
+    "wasi:sockets",
+    "fermyon:spin",
+];
+
+/// The versions of each WIT package namespace which Spin supports.
+/// Unversioned `fermyon:spin` interfaces are those of Spin 1.x.
+const SUPPORTED_VERSIONS: &[(&str, &[&str])] = &[
+    (
+        "wasi",
+        &["0.2.0", "0.2.0-rc-2023-11-10", "0.2.0-rc-2023-10-18"],
+    ),
+    ("fermyon", &["2.0.0"]),
+];
+
+/// Checks the Wasm of the given components of the app at `manifest_path`, as
+/// built locally, against the worlds of the triggers which call them. All
+/// components are checked if `component_ids` is empty. Components whose Wasm
+/// is not a local file, or hasn't been built, are skipped.
+pub fn check_manifest_file(manifest_path: &Path, component_ids: &[String]) -> Result<()> {
+    let mut manifest = crate::manifest_from_file(manifest_path, None)?;
+    spin_manifest::normalize::normalize_manifest(&mut manifest);
+    let app_root = parent_dir(manifest_path)?;
+
+    let mut uses = Uses::default();
+    for (trigger_type, triggers) in &manifest.triggers {
+        for trigger in triggers {
+            let Some(ComponentSpec::Reference(id)) = &trigger.component else {
+                continue;
+            };
+            let config = serde_json::to_value(&trigger.config)?;
+            uses.add(id.as_ref(), trigger_type, &config);
+        }
+    }
+
+    let targets = manifest
+        .components
+        .iter()
+        .filter(|(id, _)| component_ids.is_empty() || component_ids.contains(&id.to_string()))
+        .filter_map(|(id, component)| {
+            let ComponentSource::Local(path) = &component.source else {
+                return None;
+            };
+            let satisfied = component.dependencies.keys().map(String::as_str).collect();
+            Some((id.as_ref(), app_root.join(path), satisfied))
+        });
+    check(targets, &uses)
+}
+
+/// Checks the Wasm of each component of a loaded app against the worlds of
+/// the triggers which call it.
+pub(crate) fn check_locked_app(app: &LockedApp) -> Result<()> {
+    let mut uses = Uses::default();
+    for trigger in &app.triggers {
+        if let Some(id) = trigger
+            .trigger_config
+            .get("component")
+            .and_then(Value::as_str)
+        {
+            uses.add(id, &trigger.trigger_type, &trigger.trigger_config);
+        }
+    }
+
+    let targets = app.components.iter().filter_map(|component| {
+        let source = component.source.content.source.as_deref()?;
+        let path = spin_common::url::parse_file_url(source).ok()?;
+        Some((component.id.as_str(), path, HashSet::new()))
+    });
+    check(targets, &uses)
+}
+
+/// The triggers which call each component.
+#[derive(Default)]
+struct Uses<'a> {
+    /// The trigger types calling each component through their exports.
+    exports: HashMap<&'a str, Vec<&'a str>>,
+    /// The components called by triggers which aren't known.
+    unknown: HashSet<&'a str>,
+}
+
+impl<'a> Uses<'a> {
+    fn add(&mut self, component_id: &'a str, trigger_type: &'a str, config: &Value) {
+        let called = self.exports.entry(component_id).or_default();
+        if !TRIGGER_EXPORTS.iter().any(|(t, _)| *t == trigger_type) {
+            self.unknown.insert(component_id);
+        } else if calls_exports(trigger_type, config) && !called.contains(&trigger_type) {
+            called.push(trigger_type);
+        }
+    }
+
+    /// The trigger types calling the component, if all are known.
+    fn known(&self, component_id: &str) -> Option<&[&'a str]> {
+        if self.unknown.contains(component_id) {
+            return None;
+        }
+        self.exports.get(component_id).map(Vec::as_slice)
+    }
+}
+
+/// Returns whether a trigger calls its component through the trigger's
+/// exports. HTTP triggers serving static files, or using the Wagi executor,
+/// don't.
+fn calls_exports(trigger_type: &str, config: &Value) -> bool {
+    if trigger_type != "http" {
+        return true;
+    }
+    let executor = config.pointer("/executor/type").and_then(Value::as_str);
+    config.get("static").is_none() && executor != Some("wagi")
+}
+
+/// Checks each component `(id, path, imports satisfied by its dependencies)`
+/// which is called by triggers, failing with all the mismatches found.
+fn check<'a>(
+    targets: impl Iterator<Item = (&'a str, PathBuf, HashSet<&'a str>)>,
+    uses: &Uses,
+) -> Result<()> {
+    let mut report = vec![];
+    for (id, path, satisfied) in targets {
+        let Some(trigger_types) = uses.known(id) else {
+            continue;
+        };
+        // Missing and invalid Wasm are reported when the app is loaded or run
+        let Ok(wasm) = std::fs::read(&path) else {
+            continue;
+        };
+        let Ok(Some(interfaces)) = Interfaces::read(&wasm) else {
+            continue;
+        };
+        let found = mismatches(&interfaces, trigger_types, &satisfied);
+        if !found.is_empty() {
+            report.push(format!(
+                "Component `{id}` doesn't match the world Spin runs it in:"
+            ));
+            report.extend(found.iter().map(|mismatch| format!("  - {mismatch}")));
+        }
+    }
+    if !report.is_empty() {
+        bail!("{}", report.join("\n"));
+    }
+    Ok(())
+}
+
+/// The names of the interfaces a component imports and exports.
+#[derive(Debug, Default)]
+struct Interfaces {
+    imports: Vec<String>,
+    exports: Vec<String>,
+}
+
+impl Interfaces {
+    /// Reads the interfaces of a Wasm component, or returns `None` if `wasm`
+    /// is a core module.
+    fn read(wasm: &[u8]) -> Result<Option<Self>> {
+        let mut interfaces = Self::default();
+        // Nested modules and components are parsed in turn, but only the
+        // outer component's sections describe its world.
+        let mut depth = 0;
+        for payload in Parser::new(0).parse_all(wasm) {
+            match payload? {
+                Payload::Version { encoding, .. } if depth == 0 => {
+                    if encoding != Encoding::Component {
+                        return Ok(None);
+                    }
+                }
+                Payload::ModuleSection { .. } | Payload::ComponentSection { .. } => depth += 1,
+                Payload::End(_) => depth -= 1,
+                Payload::ComponentImportSection(reader) if depth == 0 => {
+                    for import in reader {
+                        interfaces.imports.push(import?.name.0.to_owned());
+                    }
+                }
+                Payload::ComponentExportSection(reader) if depth == 0 => {
+                    for export in reader {
+                        interfaces.exports.push(export?.name.0.to_owned());
+                    }
+                }
+                _ => {}
+            }
+        }
+        Ok(Some(interfaces))
+    }
+}
+
+/// A way in which a component doesn't fit the world Spin runs it in.
+#[derive(Debug, PartialEq)]
+enum Mismatch {
+    /// The component doesn't export the interface a trigger calls it through.
+    MissingExport { trigger_type: String },
+    /// The component imports a host interface at a version Spin doesn't
+    /// provide.
+    UnsupportedVersion(String),
+    /// The component imports something Spin doesn't provide.
+    UnknownImport(String),
+}
+
+impl Display for Mismatch {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::MissingExport { trigger_type } => {
+                let expected = exports_for(trigger_type);
+                write!(
+                    f,
+                    "missing export `{}`, which its `{trigger_type}` trigger calls. ",
+                    short_name(expected[0])
+                )?;
+                write!(
+                    f,
+                    "Check that it was built from a `{trigger_type}` template or SDK, so that it exports one of: {}",
+                    expected.join(", ")
+                )
+            }
+            Self::UnsupportedVersion(name) => write!(
+                f,
+                "unsupported import `{name}`. Rebuild it against a version of its SDK or bindings targeting one of the WASI or Spin versions this Spin supports: {}",
+                SUPPORTED_VERSIONS
+                    .iter()
+                    .flat_map(|(namespace, versions)| versions.iter().map(move |v| format!("{namespace}@{v}")))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
+            Self::UnknownImport(name) => write!(
+                f,
+                "unknown import `{}`. Spin doesn't provide it; if another component of the app exports it, add it to the component's `dependencies`",
+                short_name(name)
+            ),
+        }
+    }
+}
+
+fn exports_for(trigger_type: &str) -> &'static [&'static str] {
+    TRIGGER_EXPORTS
+        .iter()
+        .find(|(t, _)| *t == trigger_type)
+        .map(|(_, exports)| *exports)
+        .unwrap_or_default()
+}
+
+/// Returns the name of an interface without its package and version, e.g.
+/// `incoming-handler` for `wasi:http/incoming-handler@0.2.0`, or the package
+/// name if that is all there is.
+fn short_name(name: &str) -> &str {
+    let name = name.split_once('@').map_or(name, |(name, _)| name);
+    name.rsplit_once('/')
+        .map_or(name, |(_, interface)| interface)
+}
+
+/// Returns the mismatches between the interfaces of a component and the
+/// world of the given triggers, ignoring imports which the component's
+/// dependencies satisfy.
+fn mismatches(
+    interfaces: &Interfaces,
+    trigger_types: &[&str],
+    satisfied: &HashSet<&str>,
+) -> Vec<Mismatch> {
+    let mut found: Vec<_> = trigger_types
+        .iter()
+        .filter(|trigger_type| {
+            !exports_for(trigger_type)
+                .iter()
+                .any(|export| interfaces.exports.iter().any(|e| e == export))
+        })
+        .map(|trigger_type| Mismatch::MissingExport {
+            trigger_type: trigger_type.to_string(),
+        })
+        .collect();
+
+    for import in &interfaces.imports {
+        if satisfied.contains(import.as_str()) {
+            continue;
+        }
+        let (package, version) = match import.split_once('/') {
+            Some((package, rest)) => (package, rest.split_once('@').map(|(_, v)| v)),
+            None => (import.as_str(), None),
+        };
+        if !HOST_PACKAGES.contains(&package) {
+            found.push(Mismatch::UnknownImport(import.clone()));
+            continue;
+        }
+        let namespace = package.split_once(':').map_or(package, |(ns, _)| ns);
+        let versions = SUPPORTED_VERSIONS.iter().find(|(ns, _)| *ns == namespace);
+        if let (Some((_, versions)), Some(version)) = (versions, version) {
+            if !versions.contains(&version) {
+                found.push(Mismatch::UnsupportedVersion(import.clone()));
+            }
+        }
+    }
+    found
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn interfaces(imports: &[&str], exports: &[&str]) -> Interfaces {
+        Interfaces {
+            imports: imports.iter().map(|s| s.to_string()).collect(),
+            exports: exports.iter().map(|s| s.to_string()).collect(),
+        }
+    }
+
+    #[test]
+    fn matching_components_have_no_mismatches() {
+        let http = interfaces(
+            &[
+                "wasi:io/streams@0.2.0",
+                "wasi:http/types@0.2.0",
+                "fermyon:spin/key-value@2.0.0",
+                "fermyon:spin/config",
+            ],
+            &["wasi:http/incoming-handler@0.2.0"],
+        );
+        assert_eq!(mismatches(&http, &["http"], &HashSet::new()), []);
+    }
+
+    #[test]
+    fn mismatches_are_reported() {
+        let component = interfaces(
+            &[
+                "wasi:keyvalue/store@0.2.0-draft",
+                "wasi:io/streams@0.3.0",
+                "acme:widget/render@1.0.0",
+            ],
+            &["wasi:cli/run@0.2.0"],
+        );
+        let satisfied = HashSet::from(["acme:widget/render@1.0.0"]);
+        let found = mismatches(&component, &["http", "command"], &satisfied);
+        assert_eq!(
+            found,
+            [
+                Mismatch::MissingExport {
+                    trigger_type: "http".into()
+                },
+                Mismatch::UnknownImport("wasi:keyvalue/store@0.2.0-draft".into()),
+                Mismatch::UnsupportedVersion("wasi:io/streams@0.3.0".into()),
+            ]
+        );
+        assert!(found[0]
+            .to_string()
+            .starts_with("missing export `incoming-handler`, which its `http` trigger calls"));
+        assert!(found[1].to_string().starts_with("unknown import `store`"));
+    }
+
+    #[test]
+    fn only_export_calling_triggers_are_checked() {
+        let mut uses = Uses::default();
+        let wagi = serde_json::json!({ "component": "wagi", "executor": { "type": "wagi" } });
+        uses.add("wagi", "http", &wagi);
+        uses.add("app", "http", &serde_json::json!({ "component": "app" }));
+        uses.add(
+            "plugin",
+            "cron",
+            &serde_json::json!({ "component": "plugin" }),
+        );
+        assert_eq!(uses.known("wagi"), Some(&[][..]));
+        assert_eq!(uses.known("app"), Some(&["http"][..]));
+        assert_eq!(uses.known("plugin"), None);
+        assert_eq!(uses.known("unused"), None);
+    }
+
+    #[test]
+    fn core_modules_are_not_checked() {
+        assert!(Interfaces::read(b"\0asm\x01\0\0\0").unwrap().is_none());
+    }
+}
@@ -18,6 +18,10 @@ pub type LockedMap<T> = std::collections::BTreeMap<String, T>;
 /// local service chaining (*.spin.internal) or reject the app.
 pub const SERVICE_CHAINING_KEY: &str = "local_service_chaining";
 
+/// Component metadata key of the registry reference a component's source was
+/// fetched from, as `<registry>:<version>`.
+pub const SOURCE_REFERENCE_KEY: &str = "source_reference";
+
 /// Indicates that a host feature is optional. This is the default and is
 /// equivalent to omitting the feature from `host_requirements`.
 pub const HOST_REQ_OPTIONAL: &str = "optional";
@@ -183,6 +187,19 @@ impl LockedApp {
         self.metadata.require_typed(key)
     }
 
+    /// Serializes typed metadata for this app, replacing any existing value
+    /// for the given `key`.
+    ///
+    /// Tools and plugin triggers which add their own metadata should use a
+    /// [`MetadataKey::namespaced`](crate::MetadataKey::namespaced) key.
+    pub fn set_metadata<T: Serialize>(
+        &mut self,
+        key: crate::MetadataKey<T>,
+        value: &T,
+    ) -> crate::Result<()> {
+        self.metadata.set_typed(key, value)
+    }
+
     /// Checks that the application does not have any host requirements
     /// outside the supported set. The error case returns a comma-separated
     /// list of unmet requirements.
@@ -280,7 +297,7 @@ pub struct LockedTrigger {
 }
 
 /// A Variable specifies a custom configuration variable.
-#[derive(Clone, Debug, Serialize, Deserialize)]
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
 pub struct Variable {
     /// The variable's default value. If unset, the variable is required.
     #[serde(default, skip_serializing_if = "Option::is_none")]
@@ -288,6 +305,15 @@ pub struct Variable {
     /// If set, the variable's value may be sensitive and e.g. shouldn't be logged.
     #[serde(default, skip_serializing_if = "std::ops::Not::not")]
     pub secret: bool,
+    /// A description of the variable for the app's users.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    /// An example value for the app's users.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub example: Option<String>,
+    /// A regular expression which the whole value must match.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pattern: Option<String>,
 }
 
 #[cfg(test)]
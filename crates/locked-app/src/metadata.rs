@@ -1,24 +1,85 @@
 use std::marker::PhantomData;
 
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 use serde_json::Value;
 
 use crate::{values::ValuesMap, Error, Result};
 
 /// MetadataKey is a handle to a typed metadata value.
+///
+/// Spin's own metadata has plain keys, such as `name`. Metadata belonging to
+/// anyone else, such as a plugin trigger, should have a key made with
+/// [`MetadataKey::namespaced`], so that it can't collide with Spin's or
+/// another party's.
 pub struct MetadataKey<T = String> {
     key: &'static str,
+    version: Option<u32>,
     _phantom: PhantomData<T>,
 }
 
 impl<T> MetadataKey<T> {
     /// Creates a new MetadataKey.
+    ///
+    /// Panics if `key` contains a `:`, which is reserved for namespaced keys;
+    /// for a `const` key, this fails compilation.
     pub const fn new(key: &'static str) -> Self {
+        assert!(
+            colon_position(key).is_none(),
+            "metadata keys may not contain ':'; use MetadataKey::namespaced"
+        );
         Self {
             key,
+            version: None,
             _phantom: PhantomData,
         }
     }
+
+    /// Creates a MetadataKey for metadata belonging to a third party, given
+    /// as `<namespace>:<name>`, e.g. `acme-cron:schedules`.
+    ///
+    /// Namespaced values are stored along with `version`, and reading a value
+    /// stored with a different version is an error rather than a possible
+    /// misreading, so the version should change whenever the format of the
+    /// value does.
+    ///
+    /// Panics if `key` has no namespace; for a `const` key, this fails
+    /// compilation.
+    pub const fn namespaced(key: &'static str, version: u32) -> Self {
+        assert!(
+            matches!(colon_position(key), Some(i) if i > 0 && i + 1 < key.len()),
+            "namespaced metadata keys must be given as `<namespace>:<name>`"
+        );
+        Self {
+            key,
+            version: Some(version),
+            _phantom: PhantomData,
+        }
+    }
+
+    /// Returns the namespace of the key, if it is namespaced.
+    pub fn namespace(&self) -> Option<&'static str> {
+        self.version?;
+        self.key.split_once(':').map(|(namespace, _)| namespace)
+    }
+}
+
+const fn colon_position(key: &str) -> Option<usize> {
+    let bytes = key.as_bytes();
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b':' {
+            return Some(i);
+        }
+        i += 1;
+    }
+    None
+}
+
+/// A namespaced metadata value, as stored in a locked app.
+#[derive(Serialize)]
+struct Versioned<'a, T> {
+    version: u32,
+    value: &'a T,
 }
 
 impl<T> Clone for MetadataKey<T> {
@@ -47,19 +108,32 @@ impl<T> std::fmt::Debug for MetadataKey<T> {
     }
 }
 
-/// Helper functions for reading LockedApp metadata
+/// Helper functions for reading and writing LockedApp metadata
 pub trait MetadataExt {
     /// Get a value from a metadata map
     fn get_value(&self, key: &str) -> Option<&Value>;
 
+    /// Set a value in a metadata map
+    fn set_value(&mut self, key: &str, value: Value);
+
     /// Get a typed value from a metadata map
     fn get_typed<'a, T: Deserialize<'a>>(&'a self, key: MetadataKey<T>) -> Result<Option<T>> {
-        self.get_value(key.as_ref())
-            .map(T::deserialize)
-            .transpose()
-            .map_err(|err| {
-                Error::MetadataError(format!("invalid metadata value for {key:?}: {err:?}"))
-            })
+        let Some(mut value) = self.get_value(key.as_ref()) else {
+            return Ok(None);
+        };
+        if let Some(version) = key.version {
+            let found = value.get("version").and_then(Value::as_u64);
+            if found != Some(version.into()) {
+                let found = found.map_or("no version".to_owned(), |v| format!("version {v}"));
+                return Err(Error::MetadataError(format!(
+                    "metadata {key:?} has {found}, but version {version} was expected"
+                )));
+            }
+            value = value.get("value").unwrap_or(&Value::Null);
+        }
+        T::deserialize(value).map(Some).map_err(|err| {
+            Error::MetadataError(format!("invalid metadata value for {key:?}: {err:?}"))
+        })
     }
 
     /// Get a required value from a metadata map, returning an error
@@ -68,10 +142,64 @@ pub trait MetadataExt {
         self.get_typed(key)?
             .ok_or_else(|| Error::MetadataError(format!("missing required metadata {key:?}")))
     }
+
+    /// Set a typed value in a metadata map, with its version if the key is
+    /// namespaced
+    fn set_typed<T: Serialize>(&mut self, key: MetadataKey<T>, value: &T) -> Result<()> {
+        let value = match key.version {
+            Some(version) => serde_json::to_value(Versioned { version, value })?,
+            None => serde_json::to_value(value)?,
+        };
+        self.set_value(key.as_ref(), value);
+        Ok(())
+    }
 }
 
 impl MetadataExt for ValuesMap {
     fn get_value(&self, key: &str) -> Option<&Value> {
         self.get(key)
     }
+
+    fn set_value(&mut self, key: &str, value: Value) {
+        self.insert(key.to_owned(), value);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SCHEDULES_KEY: MetadataKey<Vec<String>> =
+        MetadataKey::namespaced("acme-cron:schedules", 2);
+
+    #[test]
+    fn namespaced_metadata_is_versioned() {
+        let mut metadata = ValuesMap::new();
+        let schedules = vec!["@hourly".to_owned()];
+        metadata.set_typed(SCHEDULES_KEY, &schedules).unwrap();
+        assert_eq!(
+            metadata["acme-cron:schedules"],
+            serde_json::json!({ "version": 2, "value": ["@hourly"] })
+        );
+        assert_eq!(metadata.require_typed(SCHEDULES_KEY).unwrap(), schedules);
+        assert_eq!(SCHEDULES_KEY.namespace(), Some("acme-cron"));
+
+        const OLD_SCHEDULES_KEY: MetadataKey<Vec<String>> =
+            MetadataKey::namespaced("acme-cron:schedules", 1);
+        let err = metadata.get_typed(OLD_SCHEDULES_KEY).unwrap_err();
+        assert!(
+            err.to_string().contains("has version 2, but version 1"),
+            "{err}"
+        );
+    }
+
+    #[test]
+    fn plain_metadata_is_stored_as_is() {
+        let mut metadata = ValuesMap::new();
+        metadata
+            .set_typed(crate::APP_NAME_KEY, &"app".to_owned())
+            .unwrap();
+        assert_eq!(metadata["name"], "app");
+        assert_eq!(crate::APP_NAME_KEY.namespace(), None);
+    }
 }
@@ -21,6 +21,8 @@ pub fn v1_to_v2_app(manifest: v1::AppManifestV1) -> Result<v2::AppManifest, Erro
         description: manifest.description,
         authors: manifest.authors,
         trigger_global_configs,
+        on_start: None,
+        on_stop: None,
         tool: Default::default(),
     };
 
@@ -69,8 +71,14 @@ pub fn v1_to_v2_app(manifest: v1::AppManifestV1) -> Result<v2::AppManifest, Erro
                 exclude_files: component.exclude_files,
                 key_value_stores: component.key_value_stores,
                 sqlite_databases: component.sqlite_databases,
+                blob_containers: vec![],
+                message_brokers: vec![],
                 ai_models,
+                dependencies: Default::default(),
                 build: component.build,
+                test: None,
+                resources: None,
+                prepare: Default::default(),
                 tool: Default::default(),
                 allowed_outbound_hosts,
                 allowed_http_hosts: Vec::new(),
@@ -88,6 +96,7 @@ pub fn v1_to_v2_app(manifest: v1::AppManifestV1) -> Result<v2::AppManifest, Erro
     }
     Ok(v2::AppManifest {
         spin_manifest_version: Default::default(),
+        min_spin_version: None,
         application,
         variables: app_variables,
         triggers,
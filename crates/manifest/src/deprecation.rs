@@ -0,0 +1,105 @@
+//! Manifest constructs which Spin still accepts, but which are deprecated.
+
+use serde::Serialize;
+use toml::{Table, Value};
+
+use crate::compat::convert_allowed_http_to_allowed_hosts;
+
+/// A deprecated construct used in a manifest.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct Deprecation {
+    /// Identifies the kind of deprecation, e.g. `allowed-http-hosts`, for
+    /// tools to match on
+    pub code: &'static str,
+    /// The dotted path of the deprecated key, e.g.
+    /// `component.api.allowed_http_hosts`
+    pub key: String,
+    /// What is deprecated, and how to fix it
+    pub message: String,
+    /// What to use instead, as TOML, if it can be worked out
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub replacement: Option<String>,
+}
+
+/// Returns the deprecated constructs used in a manifest, V1 or V2, given as a
+/// TOML table.
+pub fn deprecations(manifest: &Table) -> Vec<Deprecation> {
+    if let Some((key, Value::String(version))) = manifest
+        .get_key_value("spin_manifest_version")
+        .or_else(|| manifest.get_key_value("spin_version"))
+    {
+        if version == "1" {
+            return vec![Deprecation {
+                code: "manifest-v1",
+                key: key.clone(),
+                message: "Version 1 manifests are deprecated - to fix, run `spin doctor` to upgrade the manifest to version 2".into(),
+                replacement: Some("spin_manifest_version = 2".into()),
+            }];
+        }
+    }
+
+    let components = manifest.get("component").and_then(Value::as_table);
+    components
+        .into_iter()
+        .flatten()
+        .filter_map(|(id, component)| {
+            let hosts = component.get("allowed_http_hosts")?.as_array()?;
+            let hosts: Vec<&str> = hosts.iter().filter_map(Value::as_str).collect();
+            let replacement = convert_allowed_http_to_allowed_hosts(&hosts, false)
+                .ok()
+                .map(|normalized| format!("allowed_outbound_hosts = {normalized:?}"));
+            let fix = match &replacement {
+                Some(replacement) => format!("replace `allowed_http_hosts` with `{replacement}`"),
+                None => "replace `allowed_http_hosts` with `allowed_outbound_hosts`".into(),
+            };
+            Some(Deprecation {
+                code: "allowed-http-hosts",
+                key: format!("component.{id}.allowed_http_hosts"),
+                message: format!(
+                    "Use of the deprecated field `allowed_http_hosts` - to fix, {fix}"
+                ),
+                replacement,
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn allowed_http_hosts_is_deprecated() {
+        let manifest = toml::toml! {
+            spin_manifest_version = 2
+            [application]
+            name = "app"
+            [component.api]
+            source = "api.wasm"
+            allowed_http_hosts = ["example.com"]
+            [component.web]
+            source = "web.wasm"
+            allowed_outbound_hosts = ["https://example.com"]
+        };
+        let found = deprecations(&manifest);
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].code, "allowed-http-hosts");
+        assert_eq!(found[0].key, "component.api.allowed_http_hosts");
+        assert_eq!(
+            found[0].replacement.as_deref(),
+            Some(r#"allowed_outbound_hosts = ["http://example.com", "https://example.com"]"#)
+        );
+    }
+
+    #[test]
+    fn v1_manifests_are_deprecated() {
+        let manifest = toml::toml! {
+            spin_manifest_version = "1"
+            name = "app"
+            trigger = { type = "http" }
+        };
+        let found = deprecations(&manifest);
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].code, "manifest-v1");
+    }
+}
@@ -3,6 +3,7 @@
 #![deny(missing_docs)]
 
 pub mod compat;
+pub mod deprecation;
 pub mod error;
 pub mod normalize;
 pub mod schema;
@@ -31,6 +32,12 @@ pub fn manifest_from_str(v1_or_v2_toml: &str) -> Result<AppManifest, Error> {
     }
 }
 
+/// Returns the JSON Schema of the current (V2) app manifest format, for
+/// editors to complete and validate manifests with.
+pub fn json_schema() -> schemars::schema::RootSchema {
+    schemars::schema_for!(AppManifest)
+}
+
 /// A Spin manifest schema version.
 #[derive(Debug, PartialEq)]
 pub enum ManifestVersion {
@@ -1,9 +1,10 @@
-use std::fmt::Display;
+use std::{collections::BTreeMap, fmt::Display};
 
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
 /// Variable definition
-#[derive(Clone, Debug, Serialize, Deserialize)]
+#[derive(Clone, Debug, Serialize, Deserialize, JsonSchema)]
 #[serde(deny_unknown_fields)]
 pub struct Variable {
     /// `required = true`
@@ -15,10 +16,20 @@ pub struct Variable {
     /// `secret = true`
     #[serde(default, skip_serializing_if = "is_false")]
     pub secret: bool,
+    /// `description = "The region to store data in"`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    /// `example = "eu-west"`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub example: Option<String>,
+    /// `pattern = "[a-z]+-[a-z]+"`: a regular expression which the whole
+    /// value must match
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pattern: Option<String>,
 }
 
 /// Component source
-#[derive(Clone, Debug, Serialize, Deserialize)]
+#[derive(Clone, Debug, Serialize, Deserialize, JsonSchema)]
 #[serde(deny_unknown_fields, untagged)]
 pub enum ComponentSource {
     /// `"local.wasm"`
@@ -30,6 +41,17 @@ pub enum ComponentSource {
         /// `digest = `"sha256:abc123..."`
         digest: String,
     },
+    /// `{ registry = "ghcr.io/acme/widget", version = "1.2.3" }`
+    Registry {
+        /// `registry = "ghcr.io/acme/widget"`
+        registry: String,
+        /// `version = "1.2.3"`
+        version: String,
+        /// `digest = "sha256:abc123..."`, to require that the version's Wasm
+        /// has this digest
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        digest: Option<String>,
+    },
 }
 
 impl Display for ComponentSource {
@@ -37,12 +59,22 @@ impl Display for ComponentSource {
         match self {
             ComponentSource::Local(path) => write!(f, "{path:?}"),
             ComponentSource::Remote { url, digest } => write!(f, "{url:?} with digest {digest:?}"),
+            ComponentSource::Registry {
+                registry,
+                version,
+                digest: None,
+            } => write!(f, "\"{registry}:{version}\""),
+            ComponentSource::Registry {
+                registry,
+                version,
+                digest: Some(digest),
+            } => write!(f, "\"{registry}:{version}\" with digest {digest:?}"),
         }
     }
 }
 
 /// WASI files mount
-#[derive(Clone, Debug, Serialize, Deserialize)]
+#[derive(Clone, Debug, Serialize, Deserialize, JsonSchema)]
 #[serde(deny_unknown_fields, untagged)]
 pub enum WasiFilesMount {
     /// `"images/*.png"`
@@ -53,11 +85,18 @@ pub enum WasiFilesMount {
         source: String,
         /// `destination = "/"`
         destination: String,
+        /// `exclude = ["**/*.map", "node_modules/**"]`, relative to `source`
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        exclude: Vec<String>,
+        /// `skip_hidden = true` to leave out files and directories whose
+        /// names start with `.`
+        #[serde(default, skip_serializing_if = "is_false")]
+        skip_hidden: bool,
     },
 }
 
 /// Component build configuration
-#[derive(Clone, Debug, Serialize, Deserialize)]
+#[derive(Clone, Debug, Serialize, Deserialize, JsonSchema)]
 #[serde(deny_unknown_fields)]
 pub struct ComponentBuildConfig {
     /// `command = "cargo build"`
@@ -68,6 +107,27 @@ pub struct ComponentBuildConfig {
     /// watch = ["src/**/*.rs"]
     #[serde(default, skip_serializing_if = "Vec::is_empty")]
     pub watch: Vec<String>,
+    /// depends_on = ["other-component"]
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub depends_on: Vec<String>,
+    /// `prewizen = true`: after building, run the component's
+    /// `wizer.initialize` export and snapshot the result with Wizer
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub prewizen: bool,
+    /// `[component.x.build.profiles.release]`
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub profiles: BTreeMap<String, ComponentBuildProfile>,
+}
+
+/// Component build profile, selected with `spin build --profile`
+#[derive(Clone, Debug, Serialize, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct ComponentBuildProfile {
+    /// `command = "cargo build --release"`
+    pub command: String,
+    /// `workdir = "components/main"`; defaults to the build's workdir
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub workdir: Option<String>,
 }
 
 fn is_false(v: &bool) -> bool {
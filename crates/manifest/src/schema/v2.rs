@@ -1,17 +1,26 @@
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use spin_serde::FixedVersion;
 pub use spin_serde::{KebabId, SnakeId};
 
-pub use super::common::{ComponentBuildConfig, ComponentSource, Variable, WasiFilesMount};
+pub use super::common::{
+    ComponentBuildConfig, ComponentBuildProfile, ComponentSource, Variable, WasiFilesMount,
+};
 
 pub(crate) type Map<K, V> = indexmap::IndexMap<K, V>;
 
+/// The JSON Schema stand-in for an opaque TOML table.
+type JsonTable = serde_json::Map<String, serde_json::Value>;
+
 /// App manifest
-#[derive(Clone, Debug, Serialize, Deserialize)]
+#[derive(Clone, Debug, Serialize, Deserialize, JsonSchema)]
 #[serde(deny_unknown_fields)]
 pub struct AppManifest {
     /// `spin_manifest_version = 2`
     pub spin_manifest_version: FixedVersion<2>,
+    /// `min_spin_version = "2.6"`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub min_spin_version: Option<String>,
     /// `[application]`
     pub application: AppDetails,
     /// `[variables]`
@@ -27,7 +36,7 @@ pub struct AppManifest {
 }
 
 /// App details
-#[derive(Clone, Debug, Serialize, Deserialize)]
+#[derive(Clone, Debug, Serialize, Deserialize, JsonSchema)]
 #[serde(deny_unknown_fields)]
 pub struct AppDetails {
     /// `name = "my-app"`
@@ -43,14 +52,50 @@ pub struct AppDetails {
     pub authors: Vec<String>,
     /// `[application.triggers.<type>]`
     #[serde(rename = "trigger", default, skip_serializing_if = "Map::is_empty")]
+    #[schemars(with = "Map<String, JsonTable>")]
     pub trigger_global_configs: Map<String, toml::Table>,
+    /// `[application.on_start]`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub on_start: Option<LifecycleHook>,
+    /// `[application.on_stop]`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub on_stop: Option<LifecycleHook>,
     /// Settings for custom tools or plugins. Spin ignores this field.
     #[serde(default, skip_serializing_if = "Map::is_empty")]
+    #[schemars(with = "Map<String, JsonTable>")]
     pub tool: Map<String, toml::Table>,
 }
 
+/// A component which is run once at a point in the app's lifecycle, through
+/// its `wasi:cli/run` export: `on_start` before any trigger starts, and
+/// `on_stop` after all triggers have stopped
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct LifecycleHook {
+    /// `component = "migrate"`
+    pub component: KebabId,
+    /// `on_failure = "continue"`
+    #[serde(default, skip_serializing_if = "is_default")]
+    pub on_failure: HookFailurePolicy,
+}
+
+/// What happens when a lifecycle hook component fails
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "kebab-case")]
+pub enum HookFailurePolicy {
+    /// The app fails: an `on_start` failure stops it from starting
+    #[default]
+    Abort,
+    /// The failure is reported, and the app carries on
+    Continue,
+}
+
+fn is_default<T: Default + PartialEq>(value: &T) -> bool {
+    *value == T::default()
+}
+
 /// Trigger configuration
-#[derive(Clone, Debug, Serialize, Deserialize)]
+#[derive(Clone, Debug, Serialize, Deserialize, JsonSchema)]
 pub struct Trigger {
     /// `id = "trigger-id"`
     #[serde(default, skip_serializing_if = "String::is_empty")]
@@ -63,6 +108,7 @@ pub struct Trigger {
     pub components: Map<String, OneOrManyComponentSpecs>,
     /// Opaque trigger-type-specific config
     #[serde(flatten)]
+    #[schemars(with = "JsonTable")]
     pub config: toml::Table,
 }
 
@@ -81,6 +127,43 @@ pub enum ComponentSpec {
     Inline(Box<Component>),
 }
 
+impl JsonSchema for ComponentSpec {
+    fn schema_name() -> String {
+        "ComponentSpec".into()
+    }
+
+    fn json_schema(gen: &mut schemars::gen::SchemaGenerator) -> schemars::schema::Schema {
+        any_of(vec![
+            gen.subschema_for::<KebabId>(),
+            gen.subschema_for::<Component>(),
+        ])
+    }
+}
+
+impl JsonSchema for OneOrManyComponentSpecs {
+    fn schema_name() -> String {
+        "OneOrManyComponentSpecs".into()
+    }
+
+    fn json_schema(gen: &mut schemars::gen::SchemaGenerator) -> schemars::schema::Schema {
+        any_of(vec![
+            gen.subschema_for::<ComponentSpec>(),
+            gen.subschema_for::<Vec<ComponentSpec>>(),
+        ])
+    }
+}
+
+fn any_of(schemas: Vec<schemars::schema::Schema>) -> schemars::schema::Schema {
+    schemars::schema::SchemaObject {
+        subschemas: Some(Box::new(schemars::schema::SubschemaValidation {
+            any_of: Some(schemas),
+            ..Default::default()
+        })),
+        ..Default::default()
+    }
+    .into()
+}
+
 impl TryFrom<toml::Value> for ComponentSpec {
     type Error = toml::de::Error;
 
@@ -96,7 +179,7 @@ impl TryFrom<toml::Value> for ComponentSpec {
 }
 
 /// Component definition
-#[derive(Clone, Debug, Serialize, Deserialize)]
+#[derive(Clone, Debug, Serialize, Deserialize, JsonSchema)]
 #[serde(deny_unknown_fields)]
 pub struct Component {
     /// `source = ...`
@@ -128,6 +211,7 @@ pub struct Component {
         with = "kebab_or_snake_case",
         skip_serializing_if = "Vec::is_empty"
     )]
+    #[schemars(with = "Vec<String>")]
     pub key_value_stores: Vec<String>,
     /// `sqlite_databases = ["default", "my-database"]`
     #[serde(
@@ -135,18 +219,98 @@ pub struct Component {
         with = "kebab_or_snake_case",
         skip_serializing_if = "Vec::is_empty"
     )]
+    #[schemars(with = "Vec<String>")]
     pub sqlite_databases: Vec<String>,
+    /// `blob_containers = ["default", "my-container"]`
+    #[serde(
+        default,
+        with = "kebab_or_snake_case",
+        skip_serializing_if = "Vec::is_empty"
+    )]
+    #[schemars(with = "Vec<String>")]
+    pub blob_containers: Vec<String>,
+    /// `message_brokers = ["events"]`
+    #[serde(
+        default,
+        with = "kebab_or_snake_case",
+        skip_serializing_if = "Vec::is_empty"
+    )]
+    #[schemars(with = "Vec<String>")]
+    pub message_brokers: Vec<String>,
     /// `ai_models = ["llama2-chat"]`
     #[serde(default, skip_serializing_if = "Vec::is_empty")]
     pub ai_models: Vec<KebabId>,
+    /// `[component.x.dependencies]`
+    #[serde(default, skip_serializing_if = "Map::is_empty")]
+    pub dependencies: Map<String, ComponentDependency>,
     /// Build configuration
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub build: Option<ComponentBuildConfig>,
+    /// Test configuration, marking the component as a test run by `spin test`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub test: Option<ComponentTestConfig>,
+    /// `[component.x.resources]`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub resources: Option<ComponentResources>,
+    /// `prepare = "lazy"`
+    #[serde(default, skip_serializing_if = "is_default")]
+    pub prepare: ComponentPreparation,
     /// Settings for custom tools or plugins. Spin ignores this field.
     #[serde(default, skip_serializing_if = "Map::is_empty")]
+    #[schemars(with = "Map<String, JsonTable>")]
     pub tool: Map<String, toml::Table>,
 }
 
+/// An import of a component which is satisfied by an export of another
+/// component of the app, keyed by the import name, e.g.
+/// `"acme:widget/render@1.0.0" = { component = "widget" }`
+#[derive(Clone, Debug, Serialize, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct ComponentDependency {
+    /// `component = "widget"`
+    pub component: KebabId,
+    /// `export = "acme:widget/render@1.0.0"`, if the export's name differs
+    /// from the import's
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub export: Option<String>,
+}
+
+/// The resources a component needs, which Spin enforces as limits
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct ComponentResources {
+    /// `max_memory_mb = 64`: the most linear memory an instance may grow to
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_memory_mb: Option<u64>,
+    /// `timeout_ms = 30000`: how long an execution may run before it is
+    /// interrupted
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timeout_ms: Option<u64>,
+    /// `max_concurrent = 4`: how many instances may execute at once
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_concurrent: Option<usize>,
+}
+
+/// When a component is compiled and pre-instantiated
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "kebab-case")]
+pub enum ComponentPreparation {
+    /// When the app starts, so that it is ready for its first execution
+    #[default]
+    Eager,
+    /// On its first execution, so that the app starts sooner
+    Lazy,
+}
+
+/// Component test configuration
+#[derive(Clone, Debug, Default, Serialize, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct ComponentTestConfig {
+    /// `args = ["--verbose"]`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub args: Vec<String>,
+}
+
 mod kebab_or_snake_case {
     use serde::{Deserialize, Serialize};
     pub use spin_serde::{KebabId, SnakeId};
@@ -189,13 +353,6 @@ impl Component {
     pub fn normalized_allowed_outbound_hosts(&self) -> anyhow::Result<Vec<String>> {
         let normalized =
             crate::compat::convert_allowed_http_to_allowed_hosts(&self.allowed_http_hosts, false)?;
-        if !normalized.is_empty() {
-            terminal::warn!(
-                "Use of the deprecated field `allowed_http_hosts` - to fix, \
-            replace `allowed_http_hosts` with `allowed_outbound_hosts = {normalized:?}`",
-            )
-        }
-
         Ok(self
             .allowed_outbound_hosts
             .iter()
@@ -352,8 +509,14 @@ mod tests {
             allowed_outbound_hosts: vec![],
             key_value_stores: labels.clone(),
             sqlite_databases: labels,
+            blob_containers: vec![],
+            message_brokers: vec![],
             ai_models: vec![],
+            dependencies: Map::new(),
             build: None,
+            test: None,
+            resources: None,
+            prepare: Default::default(),
             tool: Map::new(),
         }
     }
@@ -377,6 +540,15 @@ mod tests {
         assert!(toml::to_string(&component).is_err());
     }
 
+    #[test]
+    fn json_schema_describes_components() {
+        let schema = serde_json::to_value(crate::json_schema()).unwrap();
+        let component = &schema["definitions"]["Component"];
+        assert_eq!(component["additionalProperties"], false);
+        assert!(component["properties"]["allowed_outbound_hosts"].is_object());
+        assert_eq!(schema["properties"]["spin_manifest_version"]["const"], 2);
+    }
+
     #[test]
     fn test_valid_snake_ids() {
         for valid in ["default", "mixed_CASE_words", "letters1_then2_numbers345"] {
@@ -0,0 +1,36 @@
+use spin_app::DynamicHostComponent;
+use spin_core::HostComponent;
+
+use crate::Observe;
+
+pub struct ObserveComponent;
+
+impl HostComponent for ObserveComponent {
+    type Data = Observe;
+    fn add_to_linker<T: Send>(
+        linker: &mut spin_core::Linker<T>,
+        get: impl Fn(&mut spin_core::Data<T>) -> &mut Self::Data + Send + Sync + Copy + 'static,
+    ) -> anyhow::Result<()> {
+        spin_world::v2::observe::add_to_linker(linker, get)
+    }
+
+    fn build_data(&self) -> Self::Data {
+        Default::default()
+    }
+
+    fn reset_data(&self, data: &mut Self::Data) -> bool {
+        data.reset();
+        true
+    }
+}
+
+impl DynamicHostComponent for ObserveComponent {
+    fn update_data(
+        &self,
+        data: &mut Self::Data,
+        component: &spin_app::AppComponent,
+    ) -> anyhow::Result<()> {
+        data.component_id = component.id().to_owned();
+        Ok(())
+    }
+}
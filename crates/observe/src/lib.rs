@@ -0,0 +1,248 @@
+mod host_component;
+
+use std::collections::HashMap;
+use std::sync::{Mutex, OnceLock};
+
+use anyhow::Result;
+use opentelemetry::{
+    global,
+    metrics::{Counter, Histogram},
+    trace::{Status, TraceContextExt, Tracer},
+    Context, KeyValue,
+};
+use spin_core::{async_trait, wasmtime::component::Resource};
+use spin_world::v2::observe::{self as v2, Error, Span};
+use tracing_opentelemetry::OpenTelemetrySpanExt;
+
+pub use host_component::ObserveComponent;
+
+/// The instrumentation scope of guest spans and metrics.
+const SCOPE: &str = "spin.guest";
+const MAX_NAME_LEN: usize = 255;
+
+pub struct Observe {
+    component_id: String,
+    /// The contexts of the guest's spans, each holding its span
+    spans: table::Table<Context>,
+    /// The spans which have not ended, innermost last
+    active: Vec<u32>,
+}
+
+impl Default for Observe {
+    fn default() -> Self {
+        Self {
+            component_id: Default::default(),
+            spans: table::Table::new(1024),
+            active: vec![],
+        }
+    }
+}
+
+impl Observe {
+    /// Ends all open spans and forgets the component, so that the data can
+    /// be reused by another component.
+    pub fn reset(&mut self) {
+        self.component_id.clear();
+        self.spans.clear();
+        self.active.clear();
+    }
+
+    fn context(&self, span: &Resource<Span>) -> Option<&Context> {
+        self.spans.get(span.rep())
+    }
+
+    /// The context new spans are started in: that of the innermost active
+    /// guest span, or else that of the host span for the current request.
+    fn parent_context(&self) -> Context {
+        self.active
+            .last()
+            .and_then(|rep| self.spans.get(*rep))
+            .cloned()
+            .unwrap_or_else(|| tracing::Span::current().context())
+    }
+
+    fn attributes(&self, attributes: Vec<v2::KeyValue>) -> Vec<KeyValue> {
+        std::iter::once(KeyValue::new("spin.component", self.component_id.clone()))
+            .chain(attributes.into_iter().map(to_key_value))
+            .collect()
+    }
+
+    /// Ends a span and any spans started inside it.
+    fn end_span(&mut self, rep: u32) {
+        let Some(index) = self.active.iter().position(|active| *active == rep) else {
+            return;
+        };
+        for rep in self.active.drain(index..).rev() {
+            if let Some(context) = self.spans.get(rep) {
+                context.span().end();
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl v2::Host for Observe {
+    async fn add_to_counter(
+        &mut self,
+        name: String,
+        value: u64,
+        attributes: Vec<v2::KeyValue>,
+    ) -> Result<Result<(), Error>> {
+        Ok(async {
+            validate_metric_name(&name)?;
+            counter(&name).add(value, &self.attributes(attributes));
+            Ok(())
+        }
+        .await)
+    }
+
+    async fn record_histogram(
+        &mut self,
+        name: String,
+        value: f64,
+        attributes: Vec<v2::KeyValue>,
+    ) -> Result<Result<(), Error>> {
+        Ok(async {
+            validate_metric_name(&name)?;
+            histogram(&name).record(value, &self.attributes(attributes));
+            Ok(())
+        }
+        .await)
+    }
+}
+
+#[async_trait]
+impl v2::HostSpan for Observe {
+    async fn start(&mut self, name: String) -> Result<Result<Resource<Span>, Error>> {
+        let parent = self.parent_context();
+        let span = global::tracer(SCOPE).start_with_context(name, &parent);
+        let context = parent.with_span(span);
+        context
+            .span()
+            .set_attribute(KeyValue::new("spin.component", self.component_id.clone()));
+        let Ok(rep) = self.spans.push(context) else {
+            return Ok(Err(Error::TooManySpans));
+        };
+        self.active.push(rep);
+        Ok(Ok(Resource::new_own(rep)))
+    }
+
+    async fn set_attributes(
+        &mut self,
+        span: Resource<Span>,
+        attributes: Vec<v2::KeyValue>,
+    ) -> Result<()> {
+        if let Some(context) = self.context(&span) {
+            let span = context.span();
+            for attribute in attributes {
+                span.set_attribute(to_key_value(attribute));
+            }
+        }
+        Ok(())
+    }
+
+    async fn add_event(
+        &mut self,
+        span: Resource<Span>,
+        name: String,
+        attributes: Vec<v2::KeyValue>,
+    ) -> Result<()> {
+        if let Some(context) = self.context(&span) {
+            let attributes = attributes.into_iter().map(to_key_value).collect();
+            context.span().add_event(name, attributes);
+        }
+        Ok(())
+    }
+
+    async fn set_error(&mut self, span: Resource<Span>, description: String) -> Result<()> {
+        if let Some(context) = self.context(&span) {
+            context.span().set_status(Status::error(description));
+        }
+        Ok(())
+    }
+
+    async fn end(&mut self, span: Resource<Span>) -> Result<()> {
+        self.end_span(span.rep());
+        Ok(())
+    }
+
+    fn drop(&mut self, span: Resource<Span>) -> anyhow::Result<()> {
+        self.end_span(span.rep());
+        self.spans.remove(span.rep());
+        Ok(())
+    }
+}
+
+fn to_key_value(attribute: v2::KeyValue) -> KeyValue {
+    let key = attribute.key;
+    match attribute.value {
+        v2::Value::String(value) => KeyValue::new(key, value),
+        v2::Value::Bool(value) => KeyValue::new(key, value),
+        v2::Value::S64(value) => KeyValue::new(key, value),
+        v2::Value::Float64(value) => KeyValue::new(key, value),
+    }
+}
+
+/// Checks a metric name against the OpenTelemetry instrument name syntax.
+fn validate_metric_name(name: &str) -> Result<(), Error> {
+    let valid = name.len() <= MAX_NAME_LEN
+        && name.starts_with(|c: char| c.is_ascii_alphabetic())
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '.' | '-' | '/'));
+    if valid {
+        Ok(())
+    } else {
+        Err(Error::InvalidName(name.to_owned()))
+    }
+}
+
+// Instruments are created once per name and shared by all instances, so that
+// the metrics of every instance are aggregated together.
+
+fn counter(name: &str) -> Counter<u64> {
+    static COUNTERS: OnceLock<Mutex<HashMap<String, Counter<u64>>>> = OnceLock::new();
+    let mut counters = COUNTERS.get_or_init(Default::default).lock().unwrap();
+    counters
+        .entry(name.to_owned())
+        .or_insert_with(|| global::meter(SCOPE).u64_counter(name.to_owned()).init())
+        .clone()
+}
+
+fn histogram(name: &str) -> Histogram<f64> {
+    static HISTOGRAMS: OnceLock<Mutex<HashMap<String, Histogram<f64>>>> = OnceLock::new();
+    let mut histograms = HISTOGRAMS.get_or_init(Default::default).lock().unwrap();
+    histograms
+        .entry(name.to_owned())
+        .or_insert_with(|| global::meter(SCOPE).f64_histogram(name.to_owned()).init())
+        .clone()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn metric_names_are_validated() {
+        assert!(validate_metric_name("orders.placed").is_ok());
+        assert!(validate_metric_name("cache_hits/total").is_ok());
+        assert!(validate_metric_name("").is_err());
+        assert!(validate_metric_name("2fast").is_err());
+        assert!(validate_metric_name("with space").is_err());
+        assert!(validate_metric_name(&"a".repeat(MAX_NAME_LEN + 1)).is_err());
+    }
+
+    #[test]
+    fn ending_a_span_ends_spans_inside_it() {
+        let mut observe = Observe::default();
+        let outer = observe.spans.push(Context::new()).unwrap();
+        let inner = observe.spans.push(Context::new()).unwrap();
+        observe.active = vec![outer, inner];
+
+        observe.end_span(inner);
+        assert_eq!(observe.active, [outer]);
+        observe.active.push(inner);
+        observe.end_span(outer);
+        assert!(observe.active.is_empty());
+    }
+}
@@ -4,7 +4,6 @@ use std::collections::{BTreeMap, HashMap};
 use std::path::{Path, PathBuf};
 
 use anyhow::{bail, Context, Result};
-use docker_credential::DockerCredential;
 use futures_util::future;
 use futures_util::stream::{self, StreamExt, TryStreamExt};
 use itertools::Itertools;
@@ -17,13 +16,12 @@ use spin_common::sha256;
 use spin_common::ui::quoted_path;
 use spin_common::url::parse_file_url;
 use spin_loader::cache::Cache;
+use spin_loader::registry::auth::{self, AuthConfig};
 use spin_loader::FilesMountStrategy;
 use spin_locked_app::locked::{ContentPath, ContentRef, LockedApp};
 use tokio::fs;
 use walkdir::WalkDir;
 
-use crate::auth::AuthConfig;
-
 // TODO: the media types for application, data and archive layer are not final
 /// Media type for a layer representing a locked Spin application configuration
 pub const SPIN_APPLICATION_MEDIA_TYPE: &str = "application/vnd.fermyon.spin.application.v1+config";
@@ -359,8 +357,9 @@ impl Client {
         Ok(())
     }
 
-    /// Pull a Spin application from an OCI registry.
-    pub async fn pull(&mut self, reference: &str) -> Result<()> {
+    /// Pull a Spin application from an OCI registry, returning the digest of
+    /// its manifest.
+    pub async fn pull(&mut self, reference: &str) -> Result<String> {
         let reference: Reference = reference.parse().context("cannot parse reference")?;
         let auth = Self::auth(&reference).await?;
 
@@ -427,7 +426,7 @@ impl Client {
             .await?;
         tracing::info!("Pulled {}@{}", reference, digest);
 
-        Ok(())
+        Ok(digest)
     }
 
     /// Get the file path to an OCI manifest given a reference.
@@ -623,25 +622,7 @@ impl Client {
             .resolve_registry()
             .strip_suffix('/')
             .unwrap_or_else(|| reference.resolve_registry());
-
-        match AuthConfig::get_auth_from_default(server).await {
-            Ok(c) => Ok(c),
-            Err(_) => match docker_credential::get_credential(server) {
-                Err(e) => {
-                    tracing::trace!("Cannot retrieve credentials from Docker, attempting to use anonymous auth: {}", e);
-                    Ok(RegistryAuth::Anonymous)
-                }
-
-                Ok(DockerCredential::UsernamePassword(username, password)) => {
-                    tracing::trace!("Found Docker credentials");
-                    Ok(RegistryAuth::Basic(username, password))
-                }
-                Ok(DockerCredential::IdentityToken(_)) => {
-                    tracing::trace!("Cannot use contents of Docker config, identity token not supported. Using anonymous auth");
-                    Ok(RegistryAuth::Anonymous)
-                }
-            },
-        }
+        Ok(auth::credentials(server).await)
     }
 
     /// Build the OCI client configuration given the insecure option.
@@ -1,7 +1,6 @@
 //! OCI registries integration.
 #![deny(missing_docs)]
 
-mod auth;
 pub mod client;
 mod loader;
 pub mod utils;
@@ -5,7 +5,10 @@ use oci_distribution::Reference;
 use reqwest::Url;
 use spin_common::ui::quoted_path;
 use spin_loader::cache::Cache;
-use spin_locked_app::locked::{ContentPath, ContentRef, LockedApp, LockedComponent};
+use spin_locked_app::{
+    locked::{ContentPath, ContentRef, LockedApp, LockedComponent},
+    OCI_IMAGE_DIGEST_KEY,
+};
 
 use crate::{Client, ORIGIN_URL_SCHEME};
 
@@ -25,7 +28,7 @@ impl OciLoader {
     /// Pulls and loads an OCI Artifact and returns a LockedApp with the given OCI client and reference
     pub async fn load_app(&self, client: &mut Client, reference: &str) -> Result<LockedApp> {
         // Fetch app
-        client.pull(reference).await.with_context(|| {
+        let digest = client.pull(reference).await.with_context(|| {
             format!("cannot pull Spin application from registry reference {reference:?}")
         })?;
 
@@ -34,8 +37,15 @@ impl OciLoader {
             .lockfile_path(&reference)
             .await
             .context("cannot get path to spin.lock")?;
-        self.load_from_cache(lockfile_path, reference, &client.cache)
-            .await
+        let mut locked_app = self
+            .load_from_cache(lockfile_path, reference, &client.cache)
+            .await?;
+
+        // Record what was pulled, so that it can be verified
+        locked_app
+            .metadata
+            .insert(OCI_IMAGE_DIGEST_KEY.as_ref().to_string(), digest.into());
+        Ok(locked_app)
     }
 
     /// Loads an OCI Artifact from the given cache and returns a LockedApp with the given reference
@@ -24,6 +24,11 @@ impl HostComponent for OutboundHttpComponent {
     fn build_data(&self) -> Self::Data {
         Default::default()
     }
+
+    fn reset_data(&self, data: &mut Self::Data) -> bool {
+        data.reset();
+        true
+    }
 }
 
 impl DynamicHostComponent for OutboundHttpComponent {
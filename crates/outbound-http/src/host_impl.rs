@@ -20,6 +20,14 @@ pub struct OutboundHttp {
 }
 
 impl OutboundHttp {
+    /// Forgets the allowed hosts and origin, so that the data can be reused
+    /// by another component. The client is kept, along with its pooled
+    /// connections.
+    pub fn reset(&mut self) {
+        self.allowed_hosts = Default::default();
+        self.origin.clear();
+    }
+
     /// Check if guest module is allowed to send request to URL, based on the list of
     /// allowed hosts defined by the runtime. If the url passed in is a relative path,
     /// only allow if allowed_hosts contains `self`. If the list of allowed hosts contains
@@ -64,7 +72,7 @@ impl outbound_http::Host for OutboundHttp {
             let req_url = reqwest::Url::parse(&abs_url).map_err(|_| HttpError::InvalidUrl)?;
 
             let headers = request_headers(req.headers).map_err(|_| HttpError::RuntimeError)?;
-            let body = req.body.unwrap_or_default().to_vec();
+            let body = req.body.unwrap_or_default();
 
             if !req.params.is_empty() {
                 tracing::log::warn!("HTTP params field is deprecated");
@@ -127,12 +135,9 @@ async fn response_from_reqwest(res: reqwest::Response) -> Result<Response, HttpE
     let status = res.status().as_u16();
     let headers = response_headers(res.headers()).map_err(|_| HttpError::RuntimeError)?;
 
-    let body = Some(
-        res.bytes()
-            .await
-            .map_err(|_| HttpError::RuntimeError)?
-            .to_vec(),
-    );
+    let body = Some(Vec::from(
+        res.bytes().await.map_err(|_| HttpError::RuntimeError)?,
+    ));
 
     Ok(Response {
         status,
@@ -0,0 +1,77 @@
+use std::sync::Arc;
+
+use anyhow::anyhow;
+use spin_app::{AppComponent, DynamicHostComponent};
+use spin_core::HostComponent;
+
+use crate::{Brokers, OutboundMessaging, MESSAGE_BROKERS_KEY};
+
+pub struct OutboundMessagingComponent {
+    /// The brokers configured in the runtime config, by name.
+    pub brokers: Arc<Brokers>,
+}
+
+impl HostComponent for OutboundMessagingComponent {
+    type Data = OutboundMessaging;
+    fn add_to_linker<T: Send>(
+        linker: &mut spin_core::Linker<T>,
+        get: impl Fn(&mut spin_core::Data<T>) -> &mut Self::Data + Send + Sync + Copy + 'static,
+    ) -> anyhow::Result<()> {
+        spin_world::v2::messaging::add_to_linker(linker, get)
+    }
+
+    fn build_data(&self) -> Self::Data {
+        OutboundMessaging {
+            allowed_brokers: Default::default(),
+            brokers: self.brokers.clone(),
+        }
+    }
+
+    fn reset_data(&self, data: &mut Self::Data) -> bool {
+        data.reset();
+        true
+    }
+}
+
+impl DynamicHostComponent for OutboundMessagingComponent {
+    fn update_data(&self, data: &mut Self::Data, component: &AppComponent) -> anyhow::Result<()> {
+        data.allowed_brokers = component
+            .get_metadata(MESSAGE_BROKERS_KEY)?
+            .unwrap_or_default()
+            .into_iter()
+            .collect();
+        Ok(())
+    }
+
+    fn validate_app(&self, app: &spin_app::App) -> anyhow::Result<()> {
+        let mut errors = vec![];
+
+        for component in app.components() {
+            for allowed in component
+                .get_metadata(MESSAGE_BROKERS_KEY)?
+                .unwrap_or_default()
+            {
+                if !self.brokers.contains_key(&allowed) {
+                    let err = format!("- Component {} uses broker '{allowed}'", component.id());
+                    errors.push(err);
+                }
+            }
+        }
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            let prologue = vec![
+                "One or more components use message brokers which are not defined.",
+                "Check the spelling, or pass a runtime configuration file that defines these brokers.",
+                "Details:",
+            ];
+            let lines: Vec<_> = prologue
+                .into_iter()
+                .map(|s| s.to_owned())
+                .chain(errors)
+                .collect();
+            Err(anyhow!(lines.join("\n")))
+        }
+    }
+}
@@ -0,0 +1,69 @@
+//! Publishing to Kafka topics.
+
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+use rdkafka::message::{Header, OwnedHeaders};
+use rdkafka::producer::{FutureProducer, FutureRecord};
+use rdkafka::ClientConfig;
+use spin_core::async_trait;
+use spin_world::v2::messaging::Error;
+
+use crate::{publish_failed, Publisher};
+
+/// How long to wait for a message to be queued for sending.
+const QUEUE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Produces messages to topics on a Kafka cluster. Metadata is sent as
+/// headers, except for a `key` entry, which is the record's key.
+pub struct KafkaPublisher {
+    producer: FutureProducer,
+}
+
+impl KafkaPublisher {
+    /// Prepares to produce to the cluster at `brokers`, a comma-separated
+    /// list of bootstrap servers, with additional librdkafka producer
+    /// `properties` such as `security.protocol`. No connection is made
+    /// until a message is published.
+    pub fn new(brokers: &str, properties: &BTreeMap<String, String>) -> Result<Self> {
+        let mut config = ClientConfig::new();
+        config.set("bootstrap.servers", brokers);
+        for (name, value) in properties {
+            config.set(name, value);
+        }
+        let producer = config
+            .create()
+            .with_context(|| format!("Failed to create a Kafka producer for {brokers}"))?;
+        Ok(Self { producer })
+    }
+}
+
+#[async_trait]
+impl Publisher for KafkaPublisher {
+    async fn publish(
+        &self,
+        topic: &str,
+        payload: &[u8],
+        metadata: &[(String, String)],
+    ) -> Result<(), Error> {
+        let headers = metadata.iter().filter(|(name, _)| name != "key").fold(
+            OwnedHeaders::new(),
+            |headers, (key, value)| {
+                headers.insert(Header {
+                    key,
+                    value: Some(value),
+                })
+            },
+        );
+        let mut record = FutureRecord::to(topic).payload(payload).headers(headers);
+        if let Some((_, key)) = metadata.iter().find(|(name, _)| name == "key") {
+            record = record.key(key);
+        }
+        self.producer
+            .send(record, QUEUE_TIMEOUT)
+            .await
+            .map_err(|(e, _)| publish_failed(e))?;
+        Ok(())
+    }
+}
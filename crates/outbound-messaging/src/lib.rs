@@ -0,0 +1,152 @@
+#[cfg(feature = "amqp")]
+mod amqp;
+mod host_component;
+#[cfg(feature = "kafka")]
+mod kafka;
+#[cfg(feature = "sqs")]
+mod sqs;
+
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+
+use anyhow::Result;
+use spin_app::MetadataKey;
+use spin_core::async_trait;
+use spin_world::v2::messaging::{self as v2, Error};
+
+#[cfg(feature = "amqp")]
+pub use amqp::AmqpPublisher;
+pub use host_component::OutboundMessagingComponent;
+#[cfg(feature = "kafka")]
+pub use kafka::KafkaPublisher;
+#[cfg(feature = "sqs")]
+pub use sqs::SqsPublisher;
+
+pub const MESSAGE_BROKERS_KEY: MetadataKey<Vec<String>> = MetadataKey::new("message_brokers");
+
+/// Publishes messages to a broker.
+#[async_trait]
+pub trait Publisher: Send + Sync {
+    /// Publishes `payload` to `topic`, returning once the broker has
+    /// accepted it.
+    async fn publish(
+        &self,
+        topic: &str,
+        payload: &[u8],
+        metadata: &[(String, String)],
+    ) -> Result<(), Error>;
+}
+
+/// The brokers configured for an app, by name.
+pub type Brokers = HashMap<String, Arc<dyn Publisher>>;
+
+pub struct OutboundMessaging {
+    allowed_brokers: HashSet<String>,
+    brokers: Arc<Brokers>,
+}
+
+impl OutboundMessaging {
+    /// Forgets the allowed brokers, so that the data can be reused by another
+    /// component.
+    pub fn reset(&mut self) {
+        self.allowed_brokers.clear();
+    }
+
+    fn broker(&self, name: &str) -> Result<&Arc<dyn Publisher>, Error> {
+        let broker = self
+            .brokers
+            .get(name)
+            .ok_or_else(|| Error::NoSuchBroker(name.to_owned()))?;
+        if !self.allowed_brokers.contains(name) {
+            return Err(Error::AccessDenied);
+        }
+        Ok(broker)
+    }
+}
+
+#[async_trait]
+impl v2::Host for OutboundMessaging {
+    async fn publish(
+        &mut self,
+        broker: String,
+        topic: String,
+        payload: Vec<u8>,
+        metadata: v2::Metadata,
+    ) -> Result<Result<(), Error>> {
+        Ok(async {
+            let broker = self.broker(&broker)?;
+            broker.publish(&topic, &payload, &metadata).await
+        }
+        .await)
+    }
+}
+
+#[cfg(any(feature = "amqp", feature = "kafka", feature = "sqs"))]
+fn publish_failed(e: impl std::fmt::Display) -> Error {
+    Error::PublishFailed(e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use v2::Host;
+
+    use super::*;
+
+    #[derive(Default)]
+    struct RecordingPublisher(Mutex<Vec<(String, Vec<u8>)>>);
+
+    #[async_trait]
+    impl Publisher for RecordingPublisher {
+        async fn publish(
+            &self,
+            topic: &str,
+            payload: &[u8],
+            _metadata: &[(String, String)],
+        ) -> Result<(), Error> {
+            self.0
+                .lock()
+                .unwrap()
+                .push((topic.to_owned(), payload.to_owned()));
+            Ok(())
+        }
+    }
+
+    fn messaging(publisher: Arc<RecordingPublisher>, allowed: &[&str]) -> OutboundMessaging {
+        OutboundMessaging {
+            allowed_brokers: allowed.iter().map(|name| name.to_string()).collect(),
+            brokers: Arc::new([("events".to_owned(), publisher as Arc<dyn Publisher>)].into()),
+        }
+    }
+
+    #[tokio::test]
+    async fn publishes_through_allowed_brokers() {
+        let publisher = Arc::new(RecordingPublisher::default());
+        let mut messaging = messaging(publisher.clone(), &["events"]);
+        messaging
+            .publish("events".into(), "orders".into(), b"42".to_vec(), vec![])
+            .await
+            .unwrap()
+            .unwrap();
+        let published = publisher.0.lock().unwrap();
+        assert_eq!(*published, [("orders".to_owned(), b"42".to_vec())]);
+    }
+
+    #[tokio::test]
+    async fn refuses_brokers_not_allowed() {
+        let publisher = Arc::new(RecordingPublisher::default());
+        let mut messaging = messaging(publisher.clone(), &[]);
+        let denied = messaging
+            .publish("events".into(), "orders".into(), vec![], vec![])
+            .await
+            .unwrap();
+        assert!(matches!(denied, Err(Error::AccessDenied)));
+        let missing = messaging
+            .publish("audit".into(), "orders".into(), vec![], vec![])
+            .await
+            .unwrap();
+        assert!(matches!(missing, Err(Error::NoSuchBroker(name)) if name == "audit"));
+        assert!(publisher.0.lock().unwrap().is_empty());
+    }
+}
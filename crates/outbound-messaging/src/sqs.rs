@@ -0,0 +1,102 @@
+//! Publishing to SQS queues.
+
+use std::collections::HashMap;
+
+use anyhow::Result;
+use aws_sdk_sqs::types::MessageAttributeValue;
+use aws_sdk_sqs::Client;
+use spin_core::async_trait;
+use spin_world::v2::messaging::Error;
+use tokio::sync::Mutex;
+
+use crate::{publish_failed, Publisher};
+
+/// SQS accepts at most this many attributes on a message.
+const MAX_ATTRIBUTES: usize = 10;
+
+/// Sends messages to SQS queues. Metadata is sent as string message
+/// attributes.
+pub struct SqsPublisher {
+    client: Client,
+    /// Queue URLs, by the queue names they were looked up by.
+    queue_urls: Mutex<HashMap<String, String>>,
+}
+
+impl SqsPublisher {
+    /// Prepares to send messages with credentials from the standard AWS
+    /// environment and profiles, overriding their region and SQS endpoint if
+    /// given.
+    pub async fn new(region: Option<String>, endpoint_url: Option<String>) -> Self {
+        let mut loader = aws_config::defaults(aws_config::BehaviorVersion::latest());
+        if let Some(region) = region {
+            loader = loader.region(aws_config::Region::new(region));
+        }
+        if let Some(endpoint_url) = endpoint_url {
+            loader = loader.endpoint_url(endpoint_url);
+        }
+        Self {
+            client: Client::new(&loader.load().await),
+            queue_urls: Default::default(),
+        }
+    }
+
+    // Topics may be queue URLs or names, which are looked up once.
+    async fn queue_url(&self, topic: &str) -> Result<String, Error> {
+        if topic.starts_with("https://") || topic.starts_with("http://") {
+            return Ok(topic.to_owned());
+        }
+        let mut queue_urls = self.queue_urls.lock().await;
+        if let Some(url) = queue_urls.get(topic) {
+            return Ok(url.clone());
+        }
+        let url = self
+            .client
+            .get_queue_url()
+            .queue_name(topic)
+            .send()
+            .await
+            .map_err(|e| publish_failed(aws_sdk_sqs::error::DisplayErrorContext(e)))?
+            .queue_url
+            .ok_or_else(|| publish_failed(format!("no URL was returned for queue {topic:?}")))?;
+        queue_urls.insert(topic.to_owned(), url.clone());
+        Ok(url)
+    }
+}
+
+#[async_trait]
+impl Publisher for SqsPublisher {
+    async fn publish(
+        &self,
+        topic: &str,
+        payload: &[u8],
+        metadata: &[(String, String)],
+    ) -> Result<(), Error> {
+        let body = std::str::from_utf8(payload).map_err(|_| {
+            Error::InvalidMessage("SQS message bodies must be UTF-8 text".to_owned())
+        })?;
+        if metadata.len() > MAX_ATTRIBUTES {
+            return Err(Error::InvalidMessage(format!(
+                "SQS messages may have at most {MAX_ATTRIBUTES} metadata entries"
+            )));
+        }
+        let queue_url = self.queue_url(topic).await?;
+        let mut request = self
+            .client
+            .send_message()
+            .queue_url(queue_url)
+            .message_body(body);
+        for (name, value) in metadata {
+            let value = MessageAttributeValue::builder()
+                .data_type("String")
+                .string_value(value)
+                .build()
+                .map_err(|e| Error::InvalidMessage(e.to_string()))?;
+            request = request.message_attributes(name, value);
+        }
+        request
+            .send()
+            .await
+            .map_err(|e| publish_failed(aws_sdk_sqs::error::DisplayErrorContext(e)))?;
+        Ok(())
+    }
+}
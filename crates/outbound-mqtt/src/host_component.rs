@@ -20,6 +20,11 @@ impl HostComponent for OutboundMqttComponent {
     fn build_data(&self) -> Self::Data {
         Default::default()
     }
+
+    fn reset_data(&self, data: &mut Self::Data) -> bool {
+        data.reset();
+        true
+    }
 }
 
 impl DynamicHostComponent for OutboundMqttComponent {
@@ -26,6 +26,13 @@ impl Default for OutboundMqtt {
 const MQTT_CHANNEL_CAP: usize = 1000;
 
 impl OutboundMqtt {
+    /// Closes all open connections and forgets the allowed hosts, so that
+    /// the data can be reused by another component.
+    pub fn reset(&mut self) {
+        self.allowed_hosts = Default::default();
+        self.connections.clear();
+    }
+
     fn is_address_allowed(&self, address: &str) -> bool {
         spin_outbound_networking::check_url(address, "mqtt", &self.allowed_hosts)
     }
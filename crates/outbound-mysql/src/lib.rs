@@ -22,6 +22,13 @@ pub struct OutboundMysql {
 }
 
 impl OutboundMysql {
+    /// Closes all open connections and forgets the allowed hosts, so that
+    /// the data can be reused by another component.
+    pub fn reset(&mut self) {
+        self.allowed_hosts = Default::default();
+        self.connections.clear();
+    }
+
     async fn open_connection(&mut self, address: &str) -> Result<Resource<Connection>, v2::Error> {
         self.connections
             .push(
@@ -61,6 +68,11 @@ impl HostComponent for OutboundMysqlComponent {
     fn build_data(&self) -> Self::Data {
         Default::default()
     }
+
+    fn reset_data(&self, data: &mut Self::Data) -> bool {
+        data.reset();
+        true
+    }
 }
 
 impl DynamicHostComponent for OutboundMysqlComponent {
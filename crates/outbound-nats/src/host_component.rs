@@ -0,0 +1,46 @@
+use anyhow::Context;
+use spin_app::DynamicHostComponent;
+use spin_core::HostComponent;
+
+use crate::OutboundNats;
+
+pub struct OutboundNatsComponent {
+    pub resolver: spin_expressions::SharedPreparedResolver,
+}
+
+impl HostComponent for OutboundNatsComponent {
+    type Data = OutboundNats;
+    fn add_to_linker<T: Send>(
+        linker: &mut spin_core::Linker<T>,
+        get: impl Fn(&mut spin_core::Data<T>) -> &mut Self::Data + Send + Sync + Copy + 'static,
+    ) -> anyhow::Result<()> {
+        spin_world::v2::nats::add_to_linker(linker, get)
+    }
+
+    fn build_data(&self) -> Self::Data {
+        Default::default()
+    }
+
+    fn reset_data(&self, data: &mut Self::Data) -> bool {
+        data.reset();
+        true
+    }
+}
+
+impl DynamicHostComponent for OutboundNatsComponent {
+    fn update_data(
+        &self,
+        data: &mut Self::Data,
+        component: &spin_app::AppComponent,
+    ) -> anyhow::Result<()> {
+        let hosts = component
+            .get_metadata(spin_outbound_networking::ALLOWED_HOSTS_KEY)?
+            .unwrap_or_default();
+        data.allowed_hosts = spin_outbound_networking::AllowedHostsConfig::parse(
+            &hosts,
+            self.resolver.get().unwrap(),
+        )
+        .context("`allowed_outbound_hosts` contained an invalid url")?;
+        Ok(())
+    }
+}
@@ -0,0 +1,151 @@
+mod host_component;
+
+use std::time::Duration;
+
+use anyhow::Result;
+use async_nats::{client::RequestErrorKind, Client, HeaderMap, Request};
+use spin_core::{async_trait, wasmtime::component::Resource};
+use spin_world::v2::nats::{self as v2, Connection as NatsConnection, Error, Message};
+
+pub use host_component::OutboundNatsComponent;
+
+pub struct OutboundNats {
+    allowed_hosts: spin_outbound_networking::AllowedHostsConfig,
+    connections: table::Table<Client>,
+}
+
+impl Default for OutboundNats {
+    fn default() -> Self {
+        Self {
+            allowed_hosts: Default::default(),
+            connections: table::Table::new(1024),
+        }
+    }
+}
+
+impl OutboundNats {
+    /// Closes all open connections and forgets the allowed hosts, so that
+    /// the data can be reused by another component.
+    pub fn reset(&mut self) {
+        self.allowed_hosts = Default::default();
+        self.connections.clear();
+    }
+
+    fn is_address_allowed(&self, address: &str) -> bool {
+        spin_outbound_networking::check_url(address, "nats", &self.allowed_hosts)
+    }
+
+    fn get_conn(&self, connection: Resource<NatsConnection>) -> Result<&Client, Error> {
+        self.connections.get(connection.rep()).ok_or(Error::Other(
+            "could not find connection for resource".into(),
+        ))
+    }
+}
+
+impl v2::Host for OutboundNats {}
+
+#[async_trait]
+impl v2::HostConnection for OutboundNats {
+    async fn open(&mut self, address: String) -> Result<Result<Resource<NatsConnection>, Error>> {
+        if !self.is_address_allowed(&address) {
+            return Ok(Err(v2::Error::ConnectionFailed(format!(
+                "address {address} is not permitted"
+            ))));
+        }
+        Ok(async {
+            let client = async_nats::connect(address.as_str()).await.map_err(|e| {
+                tracing::error!("NATS connection error: {e:?}");
+                Error::ConnectionFailed(e.to_string())
+            })?;
+            self.connections
+                .push(client)
+                .map(Resource::new_own)
+                .map_err(|_| Error::TooManyConnections)
+        }
+        .await)
+    }
+
+    async fn publish(
+        &mut self,
+        connection: Resource<NatsConnection>,
+        subject: String,
+        payload: Vec<u8>,
+        headers: Vec<(String, String)>,
+    ) -> Result<Result<(), Error>> {
+        Ok(async {
+            let client = self.get_conn(connection)?;
+            client
+                .publish_with_headers(subject, to_header_map(&headers), payload.into())
+                .await
+                .map_err(other_error)?;
+            // Wait for the server to process the message, so errors are reported
+            client.flush().await.map_err(other_error)
+        }
+        .await)
+    }
+
+    async fn request(
+        &mut self,
+        connection: Resource<NatsConnection>,
+        subject: String,
+        payload: Vec<u8>,
+        headers: Vec<(String, String)>,
+        timeout_ms: u64,
+    ) -> Result<Result<Message, Error>> {
+        Ok(async {
+            let client = self.get_conn(connection)?;
+            let request = Request::new()
+                .payload(payload.into())
+                .headers(to_header_map(&headers))
+                .timeout(Some(Duration::from_millis(timeout_ms)));
+            let reply =
+                client
+                    .send_request(subject, request)
+                    .await
+                    .map_err(|e| match e.kind() {
+                        RequestErrorKind::TimedOut => Error::Timeout,
+                        _ => other_error(e),
+                    })?;
+            Ok(from_nats_message(reply))
+        }
+        .await)
+    }
+
+    fn drop(&mut self, connection: Resource<NatsConnection>) -> anyhow::Result<()> {
+        self.connections.remove(connection.rep());
+        Ok(())
+    }
+}
+
+/// Converts a message received from NATS to its guest representation.
+pub fn from_nats_message(message: async_nats::Message) -> Message {
+    let headers = message
+        .headers
+        .iter()
+        .flat_map(|headers| headers.iter())
+        .flat_map(|(name, values)| {
+            values
+                .iter()
+                .map(move |value| (name.to_string(), value.to_string()))
+        })
+        .collect();
+    Message {
+        subject: message.subject.to_string(),
+        reply: message.reply.map(|reply| reply.to_string()),
+        payload: message.payload.to_vec(),
+        headers,
+    }
+}
+
+/// Converts guest message headers to a NATS header map.
+pub fn to_header_map(headers: &[(String, String)]) -> HeaderMap {
+    let mut map = HeaderMap::new();
+    for (name, value) in headers {
+        map.append(name.as_str(), value.as_str());
+    }
+    map
+}
+
+fn other_error(e: impl std::fmt::Display) -> Error {
+    Error::Other(e.to_string())
+}
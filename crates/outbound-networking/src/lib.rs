@@ -356,22 +356,35 @@ impl AllowedHostsConfig {
 
     /// Determine if the supplied url is allowed
     pub fn allows(&self, url: &OutboundUrl) -> bool {
-        match self {
+        let allowed = match self {
             AllowedHostsConfig::All => true,
             AllowedHostsConfig::SpecificHosts(hosts) => hosts.iter().any(|h| h.allows(url)),
-        }
+        };
+        record_outbound(&url.origin(), allowed);
+        allowed
     }
 
     pub fn allows_relative_url(&self, schemes: &[&str]) -> bool {
-        match self {
+        let allowed = match self {
             AllowedHostsConfig::All => true,
             AllowedHostsConfig::SpecificHosts(hosts) => {
                 hosts.iter().any(|h| h.allows_relative(schemes))
             }
-        }
+        };
+        record_outbound("self", allowed);
+        allowed
     }
 }
 
+/// Records a decision of the allowed-hosts checker for debug capture and event hooks.
+fn record_outbound(url: &str, allowed: bool) {
+    spin_telemetry::capture::record_outbound(url, allowed);
+    spin_telemetry::events::emit(spin_telemetry::events::RuntimeEvent::OutboundCall {
+        url,
+        allowed,
+    });
+}
+
 impl Default for AllowedHostsConfig {
     fn default() -> Self {
         Self::SpecificHosts(Vec::new())
@@ -428,6 +441,15 @@ impl OutboundUrl {
             original,
         })
     }
+
+    /// The scheme, host and port of the URL, without any credentials it
+    /// contains.
+    fn origin(&self) -> String {
+        match self.port {
+            Some(port) => format!("{}://{}:{port}", self.scheme, self.host),
+            None => format!("{}://{}", self.scheme, self.host),
+        }
+    }
 }
 
 impl std::fmt::Display for OutboundUrl {
@@ -1,7 +1,13 @@
+mod recording;
+
+use std::sync::Arc;
+
 use anyhow::{anyhow, Context, Result};
 use native_tls::TlsConnector;
 use postgres_native_tls::MakeTlsConnector;
+use recording::{Postgres, RecordedRowSet, RecordedValue, Request};
 use spin_app::DynamicHostComponent;
+use spin_cassette::{recorded, Cassette, Recorder};
 use spin_core::{async_trait, wasmtime::component::Resource, HostComponent};
 use spin_world::v1::postgres as v1;
 use spin_world::v1::rdbms_types as v1_types;
@@ -15,30 +21,54 @@ use tokio_postgres::{
 
 pub struct OutboundPgComponent {
     pub resolver: spin_expressions::SharedPreparedResolver,
+    /// Records calls to, or replays them from, this cassette.
+    pub cassette: Option<Arc<Cassette>>,
 }
 
 /// A simple implementation to support outbound pg connection
 #[derive(Default)]
 pub struct OutboundPg {
     allowed_hosts: spin_outbound_networking::AllowedHostsConfig,
-    pub connections: table::Table<Client>,
+    /// Connections replayed from a cassette have no client.
+    pub connections: table::Table<Option<Client>>,
+    recorder: Recorder<Postgres>,
 }
 
 impl OutboundPg {
-    async fn open_connection(&mut self, address: &str) -> Result<Resource<Connection>, v2::Error> {
-        self.connections
-            .push(
-                build_client(address)
-                    .await
-                    .map_err(|e| v2::Error::ConnectionFailed(format!("{e:?}")))?,
-            )
+    /// Closes all open connections and forgets the allowed hosts, so that
+    /// the data can be reused by another component.
+    pub fn reset(&mut self) {
+        self.allowed_hosts = Default::default();
+        self.connections.clear();
+    }
+
+    async fn open_connection(
+        &mut self,
+        address: &str,
+    ) -> Result<Result<Resource<Connection>, v2::Error>> {
+        let request = Request::Open { address };
+        let connect = async {
+            build_client(address)
+                .await
+                .map_err(|e| v2::Error::ConnectionFailed(format!("{e:?}")))
+        };
+        let client = match self.recorder.open(&request, connect).await? {
+            Ok(client) => client,
+            Err(e) => return Ok(Err(e)),
+        };
+        Ok(self
+            .connections
+            .push(client)
             .map_err(|_| v2::Error::ConnectionFailed("too many connections".into()))
-            .map(Resource::new_own)
+            .map(Resource::new_own))
     }
 
     async fn get_client(&mut self, connection: Resource<Connection>) -> Result<&Client, v2::Error> {
+        // Replayed connections are only used for calls which were recorded,
+        // and so are replayed too
         self.connections
             .get(connection.rep())
+            .and_then(Option::as_ref)
             .ok_or_else(|| v2::Error::ConnectionFailed("no connection found".into()))
     }
 
@@ -83,7 +113,15 @@ impl HostComponent for OutboundPgComponent {
     }
 
     fn build_data(&self) -> Self::Data {
-        Default::default()
+        OutboundPg {
+            recorder: Recorder::new(self.cassette.clone()),
+            ..Default::default()
+        }
+    }
+
+    fn reset_data(&self, data: &mut Self::Data) -> bool {
+        data.reset();
+        true
     }
 }
 
@@ -116,7 +154,7 @@ impl v2::HostConnection for OutboundPg {
                 "address {address} is not permitted"
             ))));
         }
-        Ok(self.open_connection(&address).await)
+        self.open_connection(&address).await
     }
 
     async fn execute(
@@ -125,7 +163,11 @@ impl v2::HostConnection for OutboundPg {
         statement: String,
         params: Vec<ParameterValue>,
     ) -> Result<Result<u64, v2::Error>> {
-        Ok(async {
+        let request = Request::Execute {
+            statement: &statement,
+            params: params.iter().map(Into::into).collect(),
+        };
+        recorded!(self.recorder, request, async {
             let params: Vec<&(dyn ToSql + Sync)> = params
                 .iter()
                 .map(to_sql_parameter)
@@ -140,8 +182,7 @@ impl v2::HostConnection for OutboundPg {
                 .map_err(|e| v2::Error::QueryFailed(format!("{:?}", e)))?;
 
             Ok(nrow)
-        }
-        .await)
+        })
     }
 
     async fn query(
@@ -150,7 +191,14 @@ impl v2::HostConnection for OutboundPg {
         statement: String,
         params: Vec<ParameterValue>,
     ) -> Result<Result<RowSet, v2::Error>> {
-        Ok(async {
+        let request = Request::Query {
+            statement: &statement,
+            params: params.iter().map(Into::into).collect(),
+        };
+        if let Some(result) = self.recorder.replay::<RecordedRowSet>(&request)? {
+            return Ok(result.map(Into::into));
+        }
+        let result = async {
             let params: Vec<&(dyn ToSql + Sync)> = params
                 .iter()
                 .map(to_sql_parameter)
@@ -180,7 +228,11 @@ impl v2::HostConnection for OutboundPg {
 
             Ok(RowSet { columns, rows })
         }
-        .await)
+        .await;
+        let recorded = result.as_ref().map(RecordedRowSet::from);
+        self.recorder
+            .record(&request, recorded.as_ref().map_err(|e| *e))?;
+        Ok(result)
     }
 
     fn drop(&mut self, connection: Resource<Connection>) -> anyhow::Result<()> {
@@ -410,7 +462,7 @@ macro_rules! delegate {
                 "address {} is not permitted", $address
             ))));
         }
-        let connection = match $self.open_connection(&$address).await {
+        let connection = match $self.open_connection(&$address).await? {
             Ok(c) => c,
             Err(e) => return Ok(Err(e.into())),
         };
@@ -0,0 +1,263 @@
+//! Recording and replay of Postgres calls with a [`spin_cassette::Cassette`].
+
+use serde::{Deserialize, Serialize};
+use spin_cassette::{Backend, POSTGRES};
+use spin_world::v2::postgres as v2;
+use spin_world::v2::rdbms_types::{Column, DbDataType, DbValue, ParameterValue, RowSet};
+
+/// A call as recorded, by the operation and its arguments. Connections are
+/// identified by their address when opened, and not otherwise.
+#[derive(Serialize)]
+#[serde(tag = "op", rename_all = "lowercase")]
+pub(crate) enum Request<'a> {
+    Open {
+        address: &'a str,
+    },
+    Execute {
+        statement: &'a str,
+        params: Vec<RecordedValue>,
+    },
+    Query {
+        statement: &'a str,
+        params: Vec<RecordedValue>,
+    },
+}
+
+#[derive(Deserialize, Serialize)]
+pub(crate) enum RecordedError {
+    ConnectionFailed(String),
+    BadParameter(String),
+    QueryFailed(String),
+    ValueConversionFailed(String),
+    Other(String),
+}
+
+/// A parameter or a value in a row.
+#[derive(Deserialize, Serialize)]
+pub(crate) enum RecordedValue {
+    Boolean(bool),
+    Int8(i8),
+    Int16(i16),
+    Int32(i32),
+    Int64(i64),
+    Uint8(u8),
+    Uint16(u16),
+    Uint32(u32),
+    Uint64(u64),
+    Floating32(f32),
+    Floating64(f64),
+    Str(String),
+    Binary(Vec<u8>),
+    DbNull,
+    Unsupported,
+}
+
+#[derive(Deserialize, Serialize)]
+enum RecordedDataType {
+    Boolean,
+    Int8,
+    Int16,
+    Int32,
+    Int64,
+    Uint8,
+    Uint16,
+    Uint32,
+    Uint64,
+    Floating32,
+    Floating64,
+    Str,
+    Binary,
+    Other,
+}
+
+#[derive(Deserialize, Serialize)]
+struct RecordedColumn {
+    name: String,
+    data_type: RecordedDataType,
+}
+
+#[derive(Deserialize, Serialize)]
+pub(crate) struct RecordedRowSet {
+    columns: Vec<RecordedColumn>,
+    rows: Vec<Vec<RecordedValue>>,
+}
+
+impl From<&v2::Error> for RecordedError {
+    fn from(error: &v2::Error) -> Self {
+        match error {
+            v2::Error::ConnectionFailed(e) => Self::ConnectionFailed(e.clone()),
+            v2::Error::BadParameter(e) => Self::BadParameter(e.clone()),
+            v2::Error::QueryFailed(e) => Self::QueryFailed(e.clone()),
+            v2::Error::ValueConversionFailed(e) => Self::ValueConversionFailed(e.clone()),
+            v2::Error::Other(e) => Self::Other(e.clone()),
+        }
+    }
+}
+
+impl From<RecordedError> for v2::Error {
+    fn from(error: RecordedError) -> Self {
+        match error {
+            RecordedError::ConnectionFailed(e) => Self::ConnectionFailed(e),
+            RecordedError::BadParameter(e) => Self::BadParameter(e),
+            RecordedError::QueryFailed(e) => Self::QueryFailed(e),
+            RecordedError::ValueConversionFailed(e) => Self::ValueConversionFailed(e),
+            RecordedError::Other(e) => Self::Other(e),
+        }
+    }
+}
+
+impl From<&ParameterValue> for RecordedValue {
+    fn from(value: &ParameterValue) -> Self {
+        match value {
+            ParameterValue::Boolean(v) => Self::Boolean(*v),
+            ParameterValue::Int8(v) => Self::Int8(*v),
+            ParameterValue::Int16(v) => Self::Int16(*v),
+            ParameterValue::Int32(v) => Self::Int32(*v),
+            ParameterValue::Int64(v) => Self::Int64(*v),
+            ParameterValue::Uint8(v) => Self::Uint8(*v),
+            ParameterValue::Uint16(v) => Self::Uint16(*v),
+            ParameterValue::Uint32(v) => Self::Uint32(*v),
+            ParameterValue::Uint64(v) => Self::Uint64(*v),
+            ParameterValue::Floating32(v) => Self::Floating32(*v),
+            ParameterValue::Floating64(v) => Self::Floating64(*v),
+            ParameterValue::Str(v) => Self::Str(v.clone()),
+            ParameterValue::Binary(v) => Self::Binary(v.clone()),
+            ParameterValue::DbNull => Self::DbNull,
+        }
+    }
+}
+
+impl From<&DbValue> for RecordedValue {
+    fn from(value: &DbValue) -> Self {
+        match value {
+            DbValue::Boolean(v) => Self::Boolean(*v),
+            DbValue::Int8(v) => Self::Int8(*v),
+            DbValue::Int16(v) => Self::Int16(*v),
+            DbValue::Int32(v) => Self::Int32(*v),
+            DbValue::Int64(v) => Self::Int64(*v),
+            DbValue::Uint8(v) => Self::Uint8(*v),
+            DbValue::Uint16(v) => Self::Uint16(*v),
+            DbValue::Uint32(v) => Self::Uint32(*v),
+            DbValue::Uint64(v) => Self::Uint64(*v),
+            DbValue::Floating32(v) => Self::Floating32(*v),
+            DbValue::Floating64(v) => Self::Floating64(*v),
+            DbValue::Str(v) => Self::Str(v.clone()),
+            DbValue::Binary(v) => Self::Binary(v.clone()),
+            DbValue::DbNull => Self::DbNull,
+            DbValue::Unsupported => Self::Unsupported,
+        }
+    }
+}
+
+impl From<RecordedValue> for DbValue {
+    fn from(value: RecordedValue) -> Self {
+        match value {
+            RecordedValue::Boolean(v) => Self::Boolean(v),
+            RecordedValue::Int8(v) => Self::Int8(v),
+            RecordedValue::Int16(v) => Self::Int16(v),
+            RecordedValue::Int32(v) => Self::Int32(v),
+            RecordedValue::Int64(v) => Self::Int64(v),
+            RecordedValue::Uint8(v) => Self::Uint8(v),
+            RecordedValue::Uint16(v) => Self::Uint16(v),
+            RecordedValue::Uint32(v) => Self::Uint32(v),
+            RecordedValue::Uint64(v) => Self::Uint64(v),
+            RecordedValue::Floating32(v) => Self::Floating32(v),
+            RecordedValue::Floating64(v) => Self::Floating64(v),
+            RecordedValue::Str(v) => Self::Str(v),
+            RecordedValue::Binary(v) => Self::Binary(v),
+            RecordedValue::DbNull => Self::DbNull,
+            RecordedValue::Unsupported => Self::Unsupported,
+        }
+    }
+}
+
+impl From<DbDataType> for RecordedDataType {
+    fn from(data_type: DbDataType) -> Self {
+        match data_type {
+            DbDataType::Boolean => Self::Boolean,
+            DbDataType::Int8 => Self::Int8,
+            DbDataType::Int16 => Self::Int16,
+            DbDataType::Int32 => Self::Int32,
+            DbDataType::Int64 => Self::Int64,
+            DbDataType::Uint8 => Self::Uint8,
+            DbDataType::Uint16 => Self::Uint16,
+            DbDataType::Uint32 => Self::Uint32,
+            DbDataType::Uint64 => Self::Uint64,
+            DbDataType::Floating32 => Self::Floating32,
+            DbDataType::Floating64 => Self::Floating64,
+            DbDataType::Str => Self::Str,
+            DbDataType::Binary => Self::Binary,
+            DbDataType::Other => Self::Other,
+        }
+    }
+}
+
+impl From<RecordedDataType> for DbDataType {
+    fn from(data_type: RecordedDataType) -> Self {
+        match data_type {
+            RecordedDataType::Boolean => Self::Boolean,
+            RecordedDataType::Int8 => Self::Int8,
+            RecordedDataType::Int16 => Self::Int16,
+            RecordedDataType::Int32 => Self::Int32,
+            RecordedDataType::Int64 => Self::Int64,
+            RecordedDataType::Uint8 => Self::Uint8,
+            RecordedDataType::Uint16 => Self::Uint16,
+            RecordedDataType::Uint32 => Self::Uint32,
+            RecordedDataType::Uint64 => Self::Uint64,
+            RecordedDataType::Floating32 => Self::Floating32,
+            RecordedDataType::Floating64 => Self::Floating64,
+            RecordedDataType::Str => Self::Str,
+            RecordedDataType::Binary => Self::Binary,
+            RecordedDataType::Other => Self::Other,
+        }
+    }
+}
+
+impl From<&RowSet> for RecordedRowSet {
+    fn from(row_set: &RowSet) -> Self {
+        Self {
+            columns: row_set
+                .columns
+                .iter()
+                .map(|column| RecordedColumn {
+                    name: column.name.clone(),
+                    data_type: column.data_type.into(),
+                })
+                .collect(),
+            rows: row_set
+                .rows
+                .iter()
+                .map(|row| row.iter().map(Into::into).collect())
+                .collect(),
+        }
+    }
+}
+
+impl From<RecordedRowSet> for RowSet {
+    fn from(row_set: RecordedRowSet) -> Self {
+        Self {
+            columns: row_set
+                .columns
+                .into_iter()
+                .map(|column| Column {
+                    name: column.name,
+                    data_type: column.data_type.into(),
+                })
+                .collect(),
+            rows: row_set
+                .rows
+                .into_iter()
+                .map(|row| row.into_iter().map(Into::into).collect())
+                .collect(),
+        }
+    }
+}
+
+/// Postgres calls, as recorded.
+pub(crate) struct Postgres;
+
+impl Backend for Postgres {
+    const KIND: &'static str = POSTGRES;
+    type Error = v2::Error;
+    type RecordedError = RecordedError;
+}
@@ -1,11 +1,16 @@
+use std::sync::Arc;
+
 use anyhow::Context;
 use spin_app::DynamicHostComponent;
+use spin_cassette::{Cassette, Recorder};
 use spin_core::HostComponent;
 
 use crate::OutboundRedis;
 
 pub struct OutboundRedisComponent {
     pub resolver: spin_expressions::SharedPreparedResolver,
+    /// Records calls to, or replays them from, this cassette.
+    pub cassette: Option<Arc<Cassette>>,
 }
 
 impl HostComponent for OutboundRedisComponent {
@@ -19,7 +24,15 @@ impl HostComponent for OutboundRedisComponent {
     }
 
     fn build_data(&self) -> Self::Data {
-        Default::default()
+        OutboundRedis {
+            recorder: Recorder::new(self.cassette.clone()),
+            ..Default::default()
+        }
+    }
+
+    fn reset_data(&self, data: &mut Self::Data) -> bool {
+        data.reset();
+        true
     }
 }
 
@@ -1,4 +1,5 @@
 mod host_component;
+mod recording;
 
 use anyhow::Result;
 use redis::{aio::Connection, AsyncCommands, FromRedisValue, Value};
@@ -9,6 +10,8 @@ use spin_world::v2::redis::{
 };
 
 pub use host_component::OutboundRedisComponent;
+use recording::{RecordedValue, Redis, Request};
+use spin_cassette::{recorded, Recorder};
 use tracing::{instrument, Level};
 
 struct RedisResults(Vec<RedisResult>);
@@ -33,7 +36,9 @@ impl FromRedisValue for RedisResults {
 
 pub struct OutboundRedis {
     allowed_hosts: spin_outbound_networking::AllowedHostsConfig,
-    connections: table::Table<Connection>,
+    // Connections replayed from a cassette have no live connection
+    connections: table::Table<Option<Connection>>,
+    recorder: Recorder<Redis>,
 }
 
 impl Default for OutboundRedis {
@@ -41,11 +46,19 @@ impl Default for OutboundRedis {
         Self {
             allowed_hosts: Default::default(),
             connections: table::Table::new(1024),
+            recorder: Recorder::default(),
         }
     }
 }
 
 impl OutboundRedis {
+    /// Closes all open connections and forgets the allowed hosts, so that
+    /// the data can be reused by another component.
+    pub fn reset(&mut self) {
+        self.allowed_hosts = Default::default();
+        self.connections.clear();
+    }
+
     fn is_address_allowed(&self, address: &str) -> bool {
         spin_outbound_networking::check_url(address, "redis", &self.allowed_hosts)
     }
@@ -54,18 +67,23 @@ impl OutboundRedis {
         &mut self,
         address: String,
     ) -> Result<Result<Resource<RedisConnection>, Error>> {
-        Ok(async {
-            let conn = redis::Client::open(address.as_str())
+        let request = Request::Open { address: &address };
+        let connect = async {
+            redis::Client::open(address.as_str())
                 .map_err(|_| Error::InvalidAddress)?
                 .get_async_connection()
                 .await
-                .map_err(other_error)?;
-            self.connections
-                .push(conn)
-                .map(Resource::new_own)
-                .map_err(|_| Error::TooManyConnections)
-        }
-        .await)
+                .map_err(other_error)
+        };
+        let conn = match self.recorder.open(&request, connect).await? {
+            Ok(conn) => conn,
+            Err(e) => return Ok(Err(e)),
+        };
+        Ok(self
+            .connections
+            .push(conn)
+            .map(Resource::new_own)
+            .map_err(|_| Error::TooManyConnections))
     }
 }
 
@@ -92,14 +110,20 @@ impl v2::HostConnection for OutboundRedis {
         channel: String,
         payload: Vec<u8>,
     ) -> Result<Result<(), Error>> {
-        Ok(async {
-            let conn = self.get_conn(connection).await.map_err(other_error)?;
-            conn.publish(&channel, &payload)
-                .await
-                .map_err(other_error)?;
-            Ok(())
-        }
-        .await)
+        recorded!(
+            self.recorder,
+            Request::Publish {
+                channel: &channel,
+                payload: &payload,
+            },
+            async {
+                let conn = self.get_conn(connection).await.map_err(other_error)?;
+                conn.publish(&channel, &payload)
+                    .await
+                    .map_err(other_error)?;
+                Ok(())
+            }
+        )
     }
 
     #[instrument(name = "spin_outbound_redis.get", skip(self, connection), err(level = Level::INFO), fields(otel.kind = "client", db.system = "redis", otel.name = format!("GET {}", key)))]
@@ -108,12 +132,11 @@ impl v2::HostConnection for OutboundRedis {
         connection: Resource<RedisConnection>,
         key: String,
     ) -> Result<Result<Option<Vec<u8>>, Error>> {
-        Ok(async {
+        recorded!(self.recorder, Request::Get { key: &key }, async {
             let conn = self.get_conn(connection).await.map_err(other_error)?;
             let value = conn.get(&key).await.map_err(other_error)?;
             Ok(value)
-        }
-        .await)
+        })
     }
 
     #[instrument(name = "spin_outbound_redis.set", skip(self, connection, value), err(level = Level::INFO), fields(otel.kind = "client", db.system = "redis", otel.name = format!("SET {}", key)))]
@@ -123,12 +146,18 @@ impl v2::HostConnection for OutboundRedis {
         key: String,
         value: Vec<u8>,
     ) -> Result<Result<(), Error>> {
-        Ok(async {
-            let conn = self.get_conn(connection).await.map_err(other_error)?;
-            conn.set(&key, &value).await.map_err(other_error)?;
-            Ok(())
-        }
-        .await)
+        recorded!(
+            self.recorder,
+            Request::Set {
+                key: &key,
+                value: &value,
+            },
+            async {
+                let conn = self.get_conn(connection).await.map_err(other_error)?;
+                conn.set(&key, &value).await.map_err(other_error)?;
+                Ok(())
+            }
+        )
     }
 
     #[instrument(name = "spin_outbound_redis.incr", skip(self, connection), err(level = Level::INFO), fields(otel.kind = "client", db.system = "redis", otel.name = format!("INCRBY {} 1", key)))]
@@ -137,12 +166,11 @@ impl v2::HostConnection for OutboundRedis {
         connection: Resource<RedisConnection>,
         key: String,
     ) -> Result<Result<i64, Error>> {
-        Ok(async {
+        recorded!(self.recorder, Request::Incr { key: &key }, async {
             let conn = self.get_conn(connection).await.map_err(other_error)?;
             let value = conn.incr(&key, 1).await.map_err(other_error)?;
             Ok(value)
-        }
-        .await)
+        })
     }
 
     #[instrument(name = "spin_outbound_redis.del", skip(self, connection), err(level = Level::INFO), fields(otel.kind = "client", db.system = "redis", otel.name = format!("DEL {}", keys.join(" "))))]
@@ -151,12 +179,11 @@ impl v2::HostConnection for OutboundRedis {
         connection: Resource<RedisConnection>,
         keys: Vec<String>,
     ) -> Result<Result<u32, Error>> {
-        Ok(async {
+        recorded!(self.recorder, Request::Del { keys: &keys }, async {
             let conn = self.get_conn(connection).await.map_err(other_error)?;
             let value = conn.del(&keys).await.map_err(other_error)?;
             Ok(value)
-        }
-        .await)
+        })
     }
 
     #[instrument(name = "spin_outbound_redis.sadd", skip(self, connection, values), err(level = Level::INFO), fields(otel.kind = "client", db.system = "redis", otel.name = format!("SADD {} {}", key, values.join(" "))))]
@@ -166,18 +193,24 @@ impl v2::HostConnection for OutboundRedis {
         key: String,
         values: Vec<String>,
     ) -> Result<Result<u32, Error>> {
-        Ok(async {
-            let conn = self.get_conn(connection).await.map_err(other_error)?;
-            let value = conn.sadd(&key, &values).await.map_err(|e| {
-                if e.kind() == redis::ErrorKind::TypeError {
-                    Error::TypeError
-                } else {
-                    Error::Other(e.to_string())
-                }
-            })?;
-            Ok(value)
-        }
-        .await)
+        recorded!(
+            self.recorder,
+            Request::Sadd {
+                key: &key,
+                values: &values,
+            },
+            async {
+                let conn = self.get_conn(connection).await.map_err(other_error)?;
+                let value = conn.sadd(&key, &values).await.map_err(|e| {
+                    if e.kind() == redis::ErrorKind::TypeError {
+                        Error::TypeError
+                    } else {
+                        Error::Other(e.to_string())
+                    }
+                })?;
+                Ok(value)
+            }
+        )
     }
 
     #[instrument(name = "spin_outbound_redis.smembers", skip(self, connection), err(level = Level::INFO), fields(otel.kind = "client", db.system = "redis", otel.name = format!("SMEMBERS {}", key)))]
@@ -186,12 +219,11 @@ impl v2::HostConnection for OutboundRedis {
         connection: Resource<RedisConnection>,
         key: String,
     ) -> Result<Result<Vec<String>, Error>> {
-        Ok(async {
+        recorded!(self.recorder, Request::Smembers { key: &key }, async {
             let conn = self.get_conn(connection).await.map_err(other_error)?;
             let value = conn.smembers(&key).await.map_err(other_error)?;
             Ok(value)
-        }
-        .await)
+        })
     }
 
     #[instrument(name = "spin_outbound_redis.srem", skip(self, connection, values), err(level = Level::INFO), fields(otel.kind = "client", db.system = "redis", otel.name = format!("SREM {} {}", key, values.join(" "))))]
@@ -201,12 +233,18 @@ impl v2::HostConnection for OutboundRedis {
         key: String,
         values: Vec<String>,
     ) -> Result<Result<u32, Error>> {
-        Ok(async {
-            let conn = self.get_conn(connection).await.map_err(other_error)?;
-            let value = conn.srem(&key, &values).await.map_err(other_error)?;
-            Ok(value)
-        }
-        .await)
+        recorded!(
+            self.recorder,
+            Request::Srem {
+                key: &key,
+                values: &values,
+            },
+            async {
+                let conn = self.get_conn(connection).await.map_err(other_error)?;
+                let value = conn.srem(&key, &values).await.map_err(other_error)?;
+                Ok(value)
+            }
+        )
     }
 
     #[instrument(name = "spin_outbound_redis.execute", skip(self, connection), err(level = Level::INFO), fields(otel.kind = "client", db.system = "redis", otel.name = format!("{}", command)))]
@@ -216,7 +254,14 @@ impl v2::HostConnection for OutboundRedis {
         command: String,
         arguments: Vec<RedisParameter>,
     ) -> Result<Result<Vec<RedisResult>, Error>> {
-        Ok(async {
+        let request = Request::Execute {
+            command: &command,
+            arguments: arguments.iter().map(Into::into).collect(),
+        };
+        if let Some(result) = self.recorder.replay::<Vec<RecordedValue>>(&request)? {
+            return Ok(result.map(|values| values.into_iter().map(Into::into).collect()));
+        }
+        let result = async {
             let conn = self.get_conn(connection).await?;
             let mut cmd = redis::cmd(&command);
             arguments.iter().for_each(|value| match value {
@@ -233,7 +278,13 @@ impl v2::HostConnection for OutboundRedis {
                 .map(|values| values.0)
                 .map_err(other_error)
         }
-        .await)
+        .await;
+        let recorded = result
+            .as_ref()
+            .map(|values| values.iter().map(RecordedValue::from).collect::<Vec<_>>());
+        self.recorder
+            .record(&request, recorded.as_ref().map_err(|e| *e))?;
+        Ok(result)
     }
 
     fn drop(&mut self, connection: Resource<RedisConnection>) -> anyhow::Result<()> {
@@ -340,8 +391,11 @@ impl OutboundRedis {
         &mut self,
         connection: Resource<RedisConnection>,
     ) -> Result<&mut Connection, Error> {
+        // Replayed connections are only used for calls which were recorded,
+        // and so are replayed too
         self.connections
             .get_mut(connection.rep())
+            .and_then(Option::as_mut)
             .ok_or(Error::Other(
                 "could not find connection for resource".into(),
             ))
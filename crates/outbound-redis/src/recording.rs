@@ -0,0 +1,126 @@
+//! Recording and replay of Redis calls with a [`spin_cassette::Cassette`].
+
+use serde::{Deserialize, Serialize};
+use spin_cassette::{Backend, REDIS};
+use spin_world::v2::redis::{Error, RedisParameter, RedisResult};
+
+/// A call as recorded, by the operation and its arguments. Connections are
+/// identified by their address when opened, and not otherwise.
+#[derive(Serialize)]
+#[serde(tag = "op", rename_all = "lowercase")]
+pub(crate) enum Request<'a> {
+    Open {
+        address: &'a str,
+    },
+    Publish {
+        channel: &'a str,
+        payload: &'a [u8],
+    },
+    Get {
+        key: &'a str,
+    },
+    Set {
+        key: &'a str,
+        value: &'a [u8],
+    },
+    Incr {
+        key: &'a str,
+    },
+    Del {
+        keys: &'a [String],
+    },
+    Sadd {
+        key: &'a str,
+        values: &'a [String],
+    },
+    Smembers {
+        key: &'a str,
+    },
+    Srem {
+        key: &'a str,
+        values: &'a [String],
+    },
+    Execute {
+        command: &'a str,
+        arguments: Vec<RecordedValue>,
+    },
+}
+
+#[derive(Deserialize, Serialize)]
+pub(crate) enum RecordedError {
+    InvalidAddress,
+    TooManyConnections,
+    TypeError,
+    Other(String),
+}
+
+/// A Redis parameter or result.
+#[derive(Deserialize, Serialize)]
+pub(crate) enum RecordedValue {
+    Nil,
+    Status(String),
+    Int64(i64),
+    Binary(Vec<u8>),
+}
+
+impl From<&Error> for RecordedError {
+    fn from(error: &Error) -> Self {
+        match error {
+            Error::InvalidAddress => Self::InvalidAddress,
+            Error::TooManyConnections => Self::TooManyConnections,
+            Error::TypeError => Self::TypeError,
+            Error::Other(message) => Self::Other(message.clone()),
+        }
+    }
+}
+
+impl From<RecordedError> for Error {
+    fn from(error: RecordedError) -> Self {
+        match error {
+            RecordedError::InvalidAddress => Self::InvalidAddress,
+            RecordedError::TooManyConnections => Self::TooManyConnections,
+            RecordedError::TypeError => Self::TypeError,
+            RecordedError::Other(message) => Self::Other(message),
+        }
+    }
+}
+
+impl From<&RedisParameter> for RecordedValue {
+    fn from(parameter: &RedisParameter) -> Self {
+        match parameter {
+            RedisParameter::Int64(v) => Self::Int64(*v),
+            RedisParameter::Binary(v) => Self::Binary(v.clone()),
+        }
+    }
+}
+
+impl From<&RedisResult> for RecordedValue {
+    fn from(result: &RedisResult) -> Self {
+        match result {
+            RedisResult::Nil => Self::Nil,
+            RedisResult::Status(v) => Self::Status(v.clone()),
+            RedisResult::Int64(v) => Self::Int64(*v),
+            RedisResult::Binary(v) => Self::Binary(v.clone()),
+        }
+    }
+}
+
+impl From<RecordedValue> for RedisResult {
+    fn from(value: RecordedValue) -> Self {
+        match value {
+            RecordedValue::Nil => Self::Nil,
+            RecordedValue::Status(v) => Self::Status(v),
+            RecordedValue::Int64(v) => Self::Int64(v),
+            RecordedValue::Binary(v) => Self::Binary(v),
+        }
+    }
+}
+
+/// Redis calls, as recorded.
+pub(crate) struct Redis;
+
+impl Backend for Redis {
+    const KIND: &'static str = REDIS;
+    type Error = Error;
+    type RecordedError = RecordedError;
+}
@@ -0,0 +1,54 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use anyhow::Context;
+use spin_app::DynamicHostComponent;
+use spin_core::HostComponent;
+
+use crate::{OutboundSmtp, SmtpServer};
+
+pub struct OutboundSmtpComponent {
+    pub resolver: spin_expressions::SharedPreparedResolver,
+    /// The servers configured in the runtime config, by name.
+    pub servers: Arc<HashMap<String, SmtpServer>>,
+}
+
+impl HostComponent for OutboundSmtpComponent {
+    type Data = OutboundSmtp;
+    fn add_to_linker<T: Send>(
+        linker: &mut spin_core::Linker<T>,
+        get: impl Fn(&mut spin_core::Data<T>) -> &mut Self::Data + Send + Sync + Copy + 'static,
+    ) -> anyhow::Result<()> {
+        spin_world::v2::smtp::add_to_linker(linker, get)
+    }
+
+    fn build_data(&self) -> Self::Data {
+        OutboundSmtp {
+            allowed_hosts: Default::default(),
+            servers: self.servers.clone(),
+        }
+    }
+
+    fn reset_data(&self, data: &mut Self::Data) -> bool {
+        data.reset();
+        true
+    }
+}
+
+impl DynamicHostComponent for OutboundSmtpComponent {
+    fn update_data(
+        &self,
+        data: &mut Self::Data,
+        component: &spin_app::AppComponent,
+    ) -> anyhow::Result<()> {
+        let hosts = component
+            .get_metadata(spin_outbound_networking::ALLOWED_HOSTS_KEY)?
+            .unwrap_or_default();
+        data.allowed_hosts = spin_outbound_networking::AllowedHostsConfig::parse(
+            &hosts,
+            self.resolver.get().unwrap(),
+        )
+        .context("`allowed_outbound_hosts` contained an invalid url")?;
+        Ok(())
+    }
+}
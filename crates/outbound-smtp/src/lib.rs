@@ -0,0 +1,229 @@
+mod host_component;
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use anyhow::{Context, Result};
+use lettre::{
+    message::{header::ContentType, Attachment, Mailbox, MultiPart, SinglePart},
+    transport::smtp::authentication::Credentials,
+    AsyncSmtpTransport, AsyncTransport, Message as Email, Tokio1Executor,
+};
+use spin_core::async_trait;
+use spin_world::v2::smtp::{self as v2, Error, Message};
+
+pub use host_component::OutboundSmtpComponent;
+
+/// How a connection to an SMTP server is secured.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum SmtpTls {
+    /// TLS from the start of the connection, usually on port 465.
+    Implicit,
+    /// Plain text upgraded to TLS with STARTTLS, usually on port 587. The
+    /// server must support it.
+    #[default]
+    StartTls,
+    /// Plain text only. Credentials are sent unencrypted.
+    None,
+}
+
+impl SmtpTls {
+    /// The port usually used with this kind of connection.
+    pub fn default_port(self) -> u16 {
+        match self {
+            Self::Implicit => 465,
+            Self::StartTls => 587,
+            Self::None => 25,
+        }
+    }
+}
+
+/// An SMTP server which components may send email through.
+#[derive(Clone)]
+pub struct SmtpServer {
+    host: String,
+    port: u16,
+    transport: AsyncSmtpTransport<Tokio1Executor>,
+}
+
+impl SmtpServer {
+    /// Prepares to send email through the server at `host`, authenticating
+    /// with `credentials` (a username and password) if given. No connection
+    /// is made until a message is sent.
+    pub fn new(
+        host: &str,
+        port: Option<u16>,
+        tls: SmtpTls,
+        credentials: Option<(String, String)>,
+    ) -> Result<Self> {
+        let port = port.unwrap_or(tls.default_port());
+        let builder = match tls {
+            SmtpTls::Implicit => AsyncSmtpTransport::<Tokio1Executor>::relay(host),
+            SmtpTls::StartTls => AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(host),
+            SmtpTls::None => Ok(AsyncSmtpTransport::<Tokio1Executor>::builder_dangerous(
+                host,
+            )),
+        }
+        .with_context(|| format!("Invalid SMTP server {host:?}"))?;
+        let mut builder = builder.port(port);
+        if let Some((username, password)) = credentials {
+            builder = builder.credentials(Credentials::new(username, password));
+        }
+        Ok(Self {
+            host: host.to_owned(),
+            port,
+            transport: builder.build(),
+        })
+    }
+
+    /// The URL by which the server must be allowed in a component's
+    /// `allowed_outbound_hosts`.
+    fn url(&self) -> String {
+        format!("smtp://{}:{}", self.host, self.port)
+    }
+}
+
+pub struct OutboundSmtp {
+    allowed_hosts: spin_outbound_networking::AllowedHostsConfig,
+    servers: Arc<HashMap<String, SmtpServer>>,
+}
+
+impl OutboundSmtp {
+    /// Forgets the allowed hosts, so that the data can be reused by another
+    /// component.
+    pub fn reset(&mut self) {
+        self.allowed_hosts = Default::default();
+    }
+
+    fn server(&self, name: &str) -> Result<&SmtpServer, Error> {
+        let server = self
+            .servers
+            .get(name)
+            .ok_or_else(|| Error::NoSuchServer(name.to_owned()))?;
+        if !spin_outbound_networking::check_url(&server.url(), "smtp", &self.allowed_hosts) {
+            return Err(Error::AccessDenied);
+        }
+        Ok(server)
+    }
+}
+
+#[async_trait]
+impl v2::Host for OutboundSmtp {
+    async fn send(&mut self, server: String, message: Message) -> Result<Result<(), Error>> {
+        Ok(async {
+            let server = self.server(&server)?;
+            let email = to_email(message)?;
+            server.transport.send(email).await.map_err(|e| {
+                tracing::error!("SMTP error: {e:?}");
+                if e.is_permanent() || e.is_transient() {
+                    Error::Rejected(e.to_string())
+                } else {
+                    Error::ConnectionFailed(e.to_string())
+                }
+            })?;
+            Ok(())
+        }
+        .await)
+    }
+}
+
+/// Converts a guest message to an email, checking its addresses.
+fn to_email(message: Message) -> Result<Email, Error> {
+    let mut builder = Email::builder()
+        .from(parse_mailbox(&message.from)?)
+        .subject(message.subject);
+    for to in &message.to {
+        builder = builder.to(parse_mailbox(to)?);
+    }
+    for cc in &message.cc {
+        builder = builder.cc(parse_mailbox(cc)?);
+    }
+    for bcc in &message.bcc {
+        builder = builder.bcc(parse_mailbox(bcc)?);
+    }
+    if let Some(reply_to) = &message.reply_to {
+        builder = builder.reply_to(parse_mailbox(reply_to)?);
+    }
+
+    let email = match (message.html_body, message.attachments.is_empty()) {
+        (None, true) => builder.singlepart(SinglePart::plain(message.body)),
+        (html_body, _) => {
+            let mut content = match html_body {
+                Some(html) => MultiPart::mixed()
+                    .multipart(MultiPart::alternative_plain_html(message.body, html)),
+                None => MultiPart::mixed().singlepart(SinglePart::plain(message.body)),
+            };
+            for attachment in message.attachments {
+                let content_type = ContentType::parse(&attachment.content_type).map_err(|e| {
+                    Error::InvalidMessage(format!(
+                        "invalid content type {:?} of attachment {:?}: {e}",
+                        attachment.content_type, attachment.filename
+                    ))
+                })?;
+                content = content.singlepart(
+                    Attachment::new(attachment.filename).body(attachment.content, content_type),
+                );
+            }
+            builder.multipart(content)
+        }
+    };
+    email.map_err(|e| Error::InvalidMessage(e.to_string()))
+}
+
+fn parse_mailbox(address: &str) -> Result<Mailbox, Error> {
+    address
+        .parse()
+        .map_err(|e| Error::InvalidMessage(format!("invalid address {address:?}: {e}")))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn message() -> Message {
+        Message {
+            from: "Spin <spin@example.com>".into(),
+            to: vec!["ada@example.com".into()],
+            cc: vec![],
+            bcc: vec!["audit@example.com".into()],
+            reply_to: None,
+            subject: "Hello".into(),
+            body: "Hello, world".into(),
+            html_body: None,
+            attachments: vec![],
+        }
+    }
+
+    #[test]
+    fn bcc_is_left_out_of_headers() {
+        let email = to_email(message()).unwrap();
+        let formatted = String::from_utf8(email.formatted()).unwrap();
+        assert!(formatted.contains("To: ada@example.com"), "{formatted}");
+        assert!(!formatted.contains("audit@example.com"), "{formatted}");
+        let recipients = email.envelope().to();
+        assert_eq!(recipients.len(), 2);
+    }
+
+    #[test]
+    fn attachments_need_valid_content_types() {
+        let mut message = message();
+        message.attachments.push(v2::Attachment {
+            filename: "report.pdf".into(),
+            content_type: "application/pdf".into(),
+            content: b"%PDF".to_vec(),
+        });
+        let email = to_email(message.clone()).unwrap();
+        let formatted = String::from_utf8(email.formatted()).unwrap();
+        assert!(formatted.contains("report.pdf"), "{formatted}");
+
+        message.attachments[0].content_type = "not a type".into();
+        assert!(matches!(to_email(message), Err(Error::InvalidMessage(_))));
+    }
+
+    #[test]
+    fn invalid_addresses_are_refused() {
+        let mut message = message();
+        message.to.push("not an address".into());
+        assert!(matches!(to_email(message), Err(Error::InvalidMessage(_))));
+    }
+}
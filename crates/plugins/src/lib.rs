@@ -1,6 +1,7 @@
 pub mod badger;
 pub mod error;
 mod git;
+pub mod lockfile;
 pub mod lookup;
 pub mod manager;
 pub mod manifest;
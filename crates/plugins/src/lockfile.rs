@@ -0,0 +1,163 @@
+//! The record of installed plugins, with the checksums needed to verify that
+//! their binaries haven't changed since they were installed.
+
+use std::{collections::BTreeMap, path::Path};
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use spin_common::{sha256, ui::quoted_path};
+
+/// The name of the lockfile in the plugins directory.
+pub const LOCKFILE_NAME: &str = "spin-plugins.lock";
+const LOCKFILE_VERSION: u32 = 1;
+
+/// The installed plugins, by name.
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct PluginLockfile {
+    version: u32,
+    #[serde(default)]
+    plugins: BTreeMap<String, LockedPlugin>,
+}
+
+/// An installed plugin.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct LockedPlugin {
+    /// The exact version installed.
+    pub version: String,
+    /// The checksum of the package the plugin was installed from.
+    pub package_sha256: String,
+    /// The checksum of the plugin's binary when it was installed.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub binary_sha256: Option<String>,
+}
+
+impl Default for PluginLockfile {
+    fn default() -> Self {
+        Self {
+            version: LOCKFILE_VERSION,
+            plugins: Default::default(),
+        }
+    }
+}
+
+impl PluginLockfile {
+    /// Reads the lockfile at `path`. A missing lockfile has no plugins.
+    pub fn load(path: &Path) -> Result<Self> {
+        let text = match std::fs::read_to_string(path) {
+            Ok(text) => text,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Self::default()),
+            Err(e) => {
+                return Err(e).with_context(|| format!("Failed to read {}", quoted_path(path)))
+            }
+        };
+        let lockfile: Self = serde_json::from_str(&text)
+            .with_context(|| format!("Invalid plugins lockfile {}", quoted_path(path)))?;
+        anyhow::ensure!(
+            lockfile.version == LOCKFILE_VERSION,
+            "Plugins lockfile {} has unsupported version {}",
+            quoted_path(path),
+            lockfile.version
+        );
+        Ok(lockfile)
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        let text = serde_json::to_string_pretty(self)?;
+        std::fs::write(path, text).with_context(|| format!("Failed to write {}", quoted_path(path)))
+    }
+
+    pub fn get(&self, plugin_name: &str) -> Option<&LockedPlugin> {
+        self.plugins.get(plugin_name)
+    }
+
+    pub fn insert(&mut self, plugin_name: impl Into<String>, plugin: LockedPlugin) {
+        self.plugins.insert(plugin_name.into(), plugin);
+    }
+
+    pub fn remove(&mut self, plugin_name: &str) -> Option<LockedPlugin> {
+        self.plugins.remove(plugin_name)
+    }
+
+    pub fn plugins(&self) -> impl Iterator<Item = (&str, &LockedPlugin)> {
+        self.plugins.iter().map(|(name, p)| (name.as_str(), p))
+    }
+}
+
+/// The result of checking an installed plugin's binary against the lockfile.
+#[derive(Debug, PartialEq)]
+pub enum Verification {
+    /// The binary matches the recorded checksum.
+    Verified,
+    /// The binary has changed since the plugin was installed.
+    Modified { expected: String, actual: String },
+    /// The binary is missing.
+    Missing,
+    /// The plugin was installed without recording a checksum, e.g. by an
+    /// earlier version of Spin.
+    NotRecorded,
+}
+
+/// Checks the binary at `binary_path` against the plugin's lockfile entry.
+pub fn verify(binary_path: &Path, locked: Option<&LockedPlugin>) -> Result<Verification> {
+    let Some(expected) = locked.and_then(|p| p.binary_sha256.as_ref()) else {
+        return Ok(Verification::NotRecorded);
+    };
+    if !binary_path.exists() {
+        return Ok(Verification::Missing);
+    }
+    let actual = sha256::hex_digest_from_file(binary_path)
+        .with_context(|| format!("Cannot get digest for {}", quoted_path(binary_path)))?;
+    if &actual == expected {
+        Ok(Verification::Verified)
+    } else {
+        Ok(Verification::Modified {
+            expected: expected.clone(),
+            actual,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lockfile_round_trips_and_verifies_binaries() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let lockfile_path = dir.path().join(LOCKFILE_NAME);
+        let binary_path = dir.path().join("example");
+
+        let mut lockfile = PluginLockfile::load(&lockfile_path)?;
+        assert_eq!(lockfile, PluginLockfile::default());
+        assert_eq!(
+            verify(&binary_path, lockfile.get("example"))?,
+            Verification::NotRecorded
+        );
+
+        std::fs::write(&binary_path, "original")?;
+        lockfile.insert(
+            "example",
+            LockedPlugin {
+                version: "1.2.3".into(),
+                package_sha256: "abc".into(),
+                binary_sha256: Some(sha256::hex_digest_from_bytes("original")),
+            },
+        );
+        lockfile.save(&lockfile_path)?;
+
+        let lockfile = PluginLockfile::load(&lockfile_path)?;
+        let locked = lockfile.get("example");
+        assert_eq!(locked.unwrap().version, "1.2.3");
+        assert_eq!(verify(&binary_path, locked)?, Verification::Verified);
+
+        std::fs::write(&binary_path, "tampered")?;
+        assert!(matches!(
+            verify(&binary_path, locked)?,
+            Verification::Modified { .. }
+        ));
+
+        std::fs::remove_file(&binary_path)?;
+        assert_eq!(verify(&binary_path, locked)?, Verification::Missing);
+        Ok(())
+    }
+}
@@ -1,5 +1,6 @@
 use crate::{
     error::*,
+    lockfile::{self, LockedPlugin, PluginLockfile, Verification},
     lookup::PluginLookup,
     manifest::{warn_unsupported_version, PluginManifest, PluginPackage},
     store::PluginStore,
@@ -108,17 +109,50 @@ impl PluginManager {
             }
             _ => download_plugin(&plugin_manifest.name(), &temp_dir, &target).await?,
         };
-        verify_checksum(&plugin_tarball_path, &plugin_package.sha256)?;
+        self.install_tarball(
+            plugin_manifest,
+            plugin_package,
+            &plugin_tarball_path,
+            source,
+        )
+    }
 
+    /// Installs the Spin plugin with the given manifest from a package which
+    /// has already been downloaded, such as on a machine without network
+    /// access. The package must match the checksum in the manifest.
+    /// Returns name of plugin that was successfully installed.
+    pub fn install_tarball(
+        &self,
+        plugin_manifest: &PluginManifest,
+        plugin_package: &PluginPackage,
+        plugin_tarball_path: &Path,
+        source: &ManifestLocation,
+    ) -> Result<String> {
+        verify_checksum(plugin_tarball_path, &plugin_package.sha256)?;
+
+        let name = plugin_manifest.name();
         self.store
-            .untar_plugin(&plugin_tarball_path, &plugin_manifest.name())
+            .untar_plugin(plugin_tarball_path, &name)
             .with_context(|| format!("Failed to untar {}", plugin_tarball_path.display()))?;
 
         // Save manifest to installed plugins directory
         self.store.add_manifest(plugin_manifest)?;
-        self.write_install_record(&plugin_manifest.name(), source);
+        self.write_install_record(&name, source);
+
+        let binary_path = self.store.installed_binary_path(&name);
+        let binary_sha256 = sha256::hex_digest_from_file(&binary_path).ok();
+        self.update_lockfile(|lockfile| {
+            lockfile.insert(
+                &name,
+                LockedPlugin {
+                    version: plugin_manifest.version().to_owned(),
+                    package_sha256: plugin_package.sha256.clone(),
+                    binary_sha256,
+                },
+            )
+        })?;
 
-        Ok(plugin_manifest.name())
+        Ok(name)
     }
 
     /// Uninstalls a plugin with a given name, removing it and it's manifest from the local plugins
@@ -133,9 +167,35 @@ impl PluginManager {
             fs::remove_file(manifest_file)?;
             fs::remove_dir_all(plugin_store.plugin_subdirectory_path(plugin_name))?;
         }
+        self.update_lockfile(|lockfile| {
+            lockfile.remove(plugin_name);
+        })?;
         Ok(exists)
     }
 
+    /// Returns the plugins recorded in the lockfile.
+    pub fn lockfile(&self) -> Result<PluginLockfile> {
+        PluginLockfile::load(&self.store.lockfile_path())
+    }
+
+    /// Checks the binary of an installed plugin against the checksum
+    /// recorded when it was installed.
+    pub fn verify(&self, plugin_name: &str) -> Result<Verification> {
+        let lockfile = self.lockfile()?;
+        lockfile::verify(
+            &self.store.installed_binary_path(plugin_name),
+            lockfile.get(plugin_name),
+        )
+    }
+
+    fn update_lockfile(&self, f: impl FnOnce(&mut PluginLockfile)) -> Result<()> {
+        let path = self.store.lockfile_path();
+        let mut lockfile = PluginLockfile::load(&path)?;
+        fs::create_dir_all(self.store.get_plugins_directory())?;
+        f(&mut lockfile);
+        lockfile.save(&path)
+    }
+
     /// Checks manifest to see if the plugin is compatible with the running version of Spin, does
     /// not have a conflicting name with Spin internal commands, and is not a downgrade of a
     /// currently installed plugin.
@@ -397,4 +457,56 @@ mod tests {
 
         Ok(())
     }
+
+    #[cfg(not(target_os = "windows"))]
+    #[test]
+    fn offline_install_is_recorded_and_verifiable() -> anyhow::Result<()> {
+        let temp_dir = tempdir()?;
+        let store = PluginStore::new(temp_dir.path().join("plugins"));
+        let manager = PluginManager { store };
+
+        // A package containing just the plugin binary
+        let tarball_path = temp_dir.path().join("example.tar.gz");
+        let mut builder = tar::Builder::new(flate2::write::GzEncoder::new(
+            File::create(&tarball_path)?,
+            flate2::Compression::default(),
+        ));
+        let binary = b"#!/bin/sh\necho example\n";
+        let mut header = tar::Header::new_gnu();
+        header.set_size(binary.len() as u64);
+        header.set_mode(0o755);
+        header.set_cksum();
+        builder.append_data(&mut header, "example", &binary[..])?;
+        builder.into_inner()?.finish()?;
+
+        let manifest: PluginManifest = serde_json::from_value(serde_json::json!({
+            "name": "example",
+            "version": "1.2.3",
+            "spinCompatibility": ">=0.1",
+            "license": "Apache-2.0",
+            "packages": [{
+                "os": "linux",
+                "arch": "amd64",
+                "url": "https://example.com/example.tar.gz",
+                "sha256": sha256::hex_digest_from_file(&tarball_path)?,
+            }]
+        }))?;
+        let source = ManifestLocation::Local(temp_dir.path().join("example.json"));
+        manager.install_tarball(&manifest, &manifest.packages[0], &tarball_path, &source)?;
+
+        let lockfile = manager.lockfile()?;
+        let locked = lockfile.get("example").expect("plugin should be locked");
+        assert_eq!(locked.version, "1.2.3");
+        assert_eq!(manager.verify("example")?, Verification::Verified);
+
+        fs::write(manager.store().installed_binary_path("example"), "tampered")?;
+        assert!(matches!(
+            manager.verify("example")?,
+            Verification::Modified { .. }
+        ));
+
+        manager.uninstall("example")?;
+        assert!(manager.lockfile()?.get("example").is_none());
+        Ok(())
+    }
 }
@@ -29,6 +29,10 @@ pub struct PluginManifest {
     license: String,
     /// Points to source package[s] of the plugin..
     pub(crate) packages: Vec<PluginPackage>,
+    /// Whether the plugin provides diagnoses for `spin doctor`, by
+    /// implementing `<plugin> doctor diagnose` and `<plugin> doctor treat`.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    doctor: bool,
 }
 
 impl PluginManifest {
@@ -54,6 +58,10 @@ impl PluginManifest {
         Url::parse(self.homepage.as_deref()?).ok()
     }
 
+    pub fn provides_diagnoses(&self) -> bool {
+        self.doctor
+    }
+
     pub fn has_compatible_package(&self) -> bool {
         self.packages.iter().any(|p| p.matches_current_os_arch())
     }
@@ -9,7 +9,7 @@ use std::{
 use tar::Archive;
 use tracing::log;
 
-use crate::{error::*, manifest::PluginManifest};
+use crate::{error::*, lockfile::LOCKFILE_NAME, manifest::PluginManifest};
 
 /// Directory where the manifests of installed plugins are stored.
 pub const PLUGIN_MANIFESTS_DIRECTORY_NAME: &str = "manifests";
@@ -63,6 +63,11 @@ impl PluginStore {
         binary
     }
 
+    /// Get the path to the lockfile recording the installed plugins.
+    pub fn lockfile_path(&self) -> PathBuf {
+        self.root.join(LOCKFILE_NAME)
+    }
+
     pub fn installation_record_file(&self, plugin_name: &str) -> PathBuf {
         self.root
             .join(plugin_name)
@@ -160,7 +165,7 @@ impl PluginStore {
         Ok(())
     }
 
-    pub(crate) fn untar_plugin(&self, plugin_file_name: &PathBuf, plugin_name: &str) -> Result<()> {
+    pub(crate) fn untar_plugin(&self, plugin_file_name: &Path, plugin_name: &str) -> Result<()> {
         // Get handle to file
         let tar_gz = File::open(plugin_file_name)?;
         // Unzip file
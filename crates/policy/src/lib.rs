@@ -0,0 +1,278 @@
+//! Policies which an application must satisfy to be run.
+//!
+//! A policy file lets an operator refuse applications whose manifests ask
+//! for more than the host is willing to allow, before any of their
+//! components are loaded. For example:
+//!
+//! ```toml
+//! # Refuse components which may connect to any host (`*`), or to any
+//! # subdomain of one (`*.example.com`)
+//! deny_wildcard_hosts = true
+//! # Refuse components which may use more than 128 MiB of memory, or
+//! # which don't limit their memory
+//! max_memory_mb = 128
+//! # Refuse components which use these capabilities
+//! deny_capabilities = ["ai_models", "sqlite_databases"]
+//! ```
+
+use std::path::Path;
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use spin_common::ui::quoted_path;
+use spin_locked_app::{
+    locked::{LockedApp, LockedComponent},
+    MetadataExt, MetadataKey,
+};
+use spin_outbound_networking::{AllowedHostConfig, HostConfig, ALLOWED_HOSTS_KEY};
+
+const KEY_VALUE_STORES_KEY: MetadataKey<Vec<String>> = MetadataKey::new("key_value_stores");
+const DATABASES_KEY: MetadataKey<Vec<String>> = MetadataKey::new("databases");
+const AI_MODELS_KEY: MetadataKey<Vec<String>> = MetadataKey::new("ai_models");
+const RESOURCES_KEY: MetadataKey<Resources> = MetadataKey::new("resources");
+
+/// The rules of a policy file. Rules which are unset allow anything.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct Policy {
+    /// Refuse components with wildcard outbound hosts.
+    #[serde(default)]
+    pub deny_wildcard_hosts: bool,
+    /// The most memory, in MiB, a component may use. Components which don't
+    /// limit their memory are refused.
+    pub max_memory_mb: Option<u64>,
+    /// Capabilities which components may not use.
+    #[serde(default)]
+    pub deny_capabilities: Vec<Capability>,
+}
+
+/// A capability a component may request in the manifest, named by its
+/// manifest field.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Capability {
+    /// Making outbound network connections
+    AllowedOutboundHosts,
+    /// Using key-value stores
+    KeyValueStores,
+    /// Using SQLite databases
+    SqliteDatabases,
+    /// Using AI models
+    AiModels,
+}
+
+/// The rule a component violated.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Rule {
+    /// `deny_wildcard_hosts`
+    DenyWildcardHosts,
+    /// `max_memory_mb`
+    MaxMemoryMb,
+    /// `deny_capabilities`
+    DenyCapabilities,
+}
+
+/// A component's violation of a policy rule.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct Violation {
+    /// The ID of the component.
+    pub component: String,
+    /// The rule it violated.
+    pub rule: Rule,
+    /// What the component requested.
+    pub message: String,
+}
+
+impl std::fmt::Display for Violation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "component `{}`: {}", self.component, self.message)
+    }
+}
+
+#[derive(Default, Deserialize)]
+struct Resources {
+    max_memory_mb: Option<u64>,
+}
+
+impl Policy {
+    /// Reads a policy file.
+    pub fn from_file(path: &Path) -> Result<Self> {
+        let contents = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read policy file {}", quoted_path(path)))?;
+        toml::from_str(&contents)
+            .with_context(|| format!("Invalid policy file {}", quoted_path(path)))
+    }
+
+    /// Returns every violation of the policy by the app's components.
+    pub fn evaluate(&self, app: &LockedApp) -> Result<Vec<Violation>> {
+        let mut violations = vec![];
+        for component in &app.components {
+            self.evaluate_component(component, &mut violations)
+                .with_context(|| format!("Invalid metadata for component `{}`", component.id))?;
+        }
+        Ok(violations)
+    }
+
+    fn evaluate_component(
+        &self,
+        component: &LockedComponent,
+        violations: &mut Vec<Violation>,
+    ) -> Result<()> {
+        let mut violation = |rule, message: String| {
+            violations.push(Violation {
+                component: component.id.clone(),
+                rule,
+                message,
+            })
+        };
+        let allowed_hosts = component
+            .metadata
+            .get_typed(ALLOWED_HOSTS_KEY)?
+            .unwrap_or_default();
+
+        if self.deny_wildcard_hosts {
+            for host in &allowed_hosts {
+                if is_wildcard_host(host) {
+                    violation(
+                        Rule::DenyWildcardHosts,
+                        format!("allowed outbound host {host:?} is a wildcard"),
+                    );
+                }
+            }
+        }
+
+        if let Some(max_memory_mb) = self.max_memory_mb {
+            let resources = component
+                .metadata
+                .get_typed(RESOURCES_KEY)?
+                .unwrap_or_default();
+            match resources.max_memory_mb {
+                Some(memory_mb) if memory_mb <= max_memory_mb => {}
+                Some(memory_mb) => violation(
+                    Rule::MaxMemoryMb,
+                    format!(
+                        "max_memory_mb = {memory_mb} is more than the {max_memory_mb} MiB allowed"
+                    ),
+                ),
+                None => violation(
+                    Rule::MaxMemoryMb,
+                    format!("no max_memory_mb is set; at most {max_memory_mb} MiB is allowed"),
+                ),
+            }
+        }
+
+        for &capability in &self.deny_capabilities {
+            let requested = match capability {
+                Capability::AllowedOutboundHosts => allowed_hosts.clone(),
+                Capability::KeyValueStores => component.metadata.get_typed(KEY_VALUE_STORES_KEY)?,
+                Capability::SqliteDatabases => component.metadata.get_typed(DATABASES_KEY)?,
+                Capability::AiModels => component.metadata.get_typed(AI_MODELS_KEY)?,
+            }
+            .unwrap_or_default();
+            if !requested.is_empty() {
+                violation(
+                    Rule::DenyCapabilities,
+                    format!("{} is not allowed", capability.manifest_field()),
+                );
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Capability {
+    fn manifest_field(&self) -> &'static str {
+        match self {
+            Self::AllowedOutboundHosts => "allowed_outbound_hosts",
+            Self::KeyValueStores => "key_value_stores",
+            Self::SqliteDatabases => "sqlite_databases",
+            Self::AiModels => "ai_models",
+        }
+    }
+}
+
+// Hosts which use variables can't be known until runtime, and aren't
+// counted as wildcards unless written as one.
+fn is_wildcard_host(host: &str) -> bool {
+    match AllowedHostConfig::parse(host) {
+        Ok(config) => matches!(config.host(), HostConfig::Any | HostConfig::AnySubdomain(_)),
+        Err(_) => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn app(components: serde_json::Value) -> LockedApp {
+        LockedApp::from_json(
+            serde_json::json!({
+                "spin_lock_version": 1,
+                "triggers": [],
+                "components": components,
+            })
+            .to_string()
+            .as_bytes(),
+        )
+        .unwrap()
+    }
+
+    fn component(id: &str, metadata: serde_json::Value) -> serde_json::Value {
+        serde_json::json!({
+            "id": id,
+            "metadata": metadata,
+            "source": { "content_type": "application/wasm", "source": "file:///app.wasm" },
+        })
+    }
+
+    #[test]
+    fn violations_are_found() {
+        let policy: Policy = toml::from_str(
+            r#"
+            deny_wildcard_hosts = true
+            max_memory_mb = 128
+            deny_capabilities = ["ai_models"]
+            "#,
+        )
+        .unwrap();
+        let app = app(serde_json::json!([
+            component(
+                "good",
+                serde_json::json!({
+                    "allowed_outbound_hosts": ["https://example.com"],
+                    "resources": { "max_memory_mb": 64 },
+                })
+            ),
+            component(
+                "bad",
+                serde_json::json!({
+                    "allowed_outbound_hosts": ["*://*:*", "https://*.example.com"],
+                    "ai_models": ["llama2-chat"],
+                })
+            ),
+        ]));
+
+        let violations = policy.evaluate(&app).unwrap();
+        let rules: Vec<_> = violations.iter().map(|v| v.rule).collect();
+        assert_eq!(
+            rules,
+            [
+                Rule::DenyWildcardHosts,
+                Rule::DenyWildcardHosts,
+                Rule::MaxMemoryMb,
+                Rule::DenyCapabilities
+            ]
+        );
+        assert!(violations.iter().all(|v| v.component == "bad"));
+    }
+
+    #[test]
+    fn empty_policy_allows_anything() {
+        let app = app(serde_json::json!([component(
+            "any",
+            serde_json::json!({ "allowed_outbound_hosts": ["*://*:*"] })
+        )]));
+        assert!(Policy::default().evaluate(&app).unwrap().is_empty());
+    }
+}
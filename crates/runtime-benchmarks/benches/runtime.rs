@@ -0,0 +1,229 @@
+use std::collections::HashSet;
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use criterion::{criterion_group, criterion_main, Criterion, Throughput};
+use http::uri::Scheme;
+use http::Request;
+use outbound_http::OutboundHttp;
+use spin_core::{Component, Config, Engine, WasiVersion};
+use spin_key_value::{manager, EmptyStoreManager, KeyValueComponent, StoreManager};
+use spin_key_value_sqlite::{DatabaseLocation, KeyValueSqlite};
+use spin_outbound_networking::AllowedHostsConfig;
+use spin_sqlite::Connection;
+use spin_sqlite_inproc::{InProcConnection, InProcDatabaseLocation};
+use spin_testing::{assert_http_response_success, HttpTestConfig};
+use spin_trigger_http::HttpTrigger;
+use spin_world::v1::{http::Host as _, http_types};
+use spin_world::v2::sqlite;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::runtime::Runtime;
+
+criterion_main!(benches);
+criterion_group!(
+    benches,
+    bench_cold_instantiation,
+    bench_store_build,
+    bench_warm_requests,
+    bench_key_value,
+    bench_sqlite,
+    bench_outbound_http,
+);
+
+// Instantiating a pre-instantiated component in a new store, as for each
+// execution which doesn't reuse an instance
+fn bench_cold_instantiation(c: &mut Criterion) {
+    let async_runtime = Runtime::new().unwrap();
+
+    let mut builder = Engine::<()>::builder(&Config::default()).unwrap();
+    builder
+        .link_import(|l, _| wasmtime_wasi::preview2::command::add_to_linker(l))
+        .unwrap();
+    builder
+        .link_import(|l, _| spin_core::wasi_2023_10_18::add_to_linker(l))
+        .unwrap();
+    let engine = builder.build();
+    let wasm = std::fs::read(test_program("spin-http-benchmark.wasm")).unwrap();
+    let wasm = spin_componentize::componentize_if_necessary(&wasm).unwrap();
+    let component = Component::new(engine.as_ref(), &wasm).unwrap();
+    let instance_pre = engine.instantiate_pre(&component).unwrap();
+
+    c.bench_function("instantiation/cold", |b| {
+        b.to_async(&async_runtime).iter(|| async {
+            let mut store = engine.store_builder(WasiVersion::Preview2).build().unwrap();
+            instance_pre.instantiate_async(&mut store).await.unwrap();
+        });
+    });
+}
+
+// Building and dropping a store with host component data, which is reused
+// from earlier stores once there are some
+fn bench_store_build(c: &mut Criterion) {
+    let mut builder = Engine::<()>::builder(&Config::default()).unwrap();
+    let key_value = builder
+        .add_host_component(KeyValueComponent::new(manager(|_| {
+            Arc::new(EmptyStoreManager)
+        })))
+        .unwrap();
+    let engine = builder.build();
+    let store_manager: Arc<dyn StoreManager> = Arc::new(EmptyStoreManager);
+
+    c.bench_function("store/build", |b| {
+        b.iter(|| {
+            let mut store_builder = engine.store_builder(WasiVersion::Preview2);
+            store_builder
+                .host_components_data()
+                .get_or_insert(key_value)
+                .init(HashSet::from(["default".into()]), store_manager.clone());
+            store_builder.build().unwrap()
+        });
+    });
+}
+
+// Requests to a trigger whose component has been prepared
+fn bench_warm_requests(c: &mut Criterion) {
+    let async_runtime = Runtime::new().unwrap();
+    let trigger: HttpTrigger = async_runtime.block_on(
+        HttpTestConfig::default()
+            .test_program("spin-http-benchmark.wasm")
+            .http_spin_trigger("/")
+            .build_trigger(),
+    );
+
+    let mut group = c.benchmark_group("http");
+    group.throughput(Throughput::Elements(1));
+    group.bench_function("warm-request", |b| {
+        b.to_async(&async_runtime).iter(|| async {
+            let req = Request::get("/?noop").body(Default::default()).unwrap();
+            let resp = trigger
+                .handle(req, Scheme::HTTP, "127.0.0.1:55555".parse().unwrap())
+                .await
+                .unwrap();
+            assert_http_response_success(&resp);
+        });
+    });
+}
+
+fn bench_key_value(c: &mut Criterion) {
+    let async_runtime = Runtime::new().unwrap();
+    let manager = KeyValueSqlite::new(DatabaseLocation::InMemory);
+    let store = async_runtime.block_on(manager.get("default")).unwrap();
+    async_runtime.block_on(store.set("key", b"value")).unwrap();
+
+    let mut group = c.benchmark_group("key-value");
+    group.bench_function("set", |b| {
+        b.to_async(&async_runtime)
+            .iter(|| async { store.set("key", b"value").await.unwrap() });
+    });
+    group.bench_function("get", |b| {
+        b.to_async(&async_runtime)
+            .iter(|| async { store.get("key").await.unwrap().unwrap() });
+    });
+}
+
+fn bench_sqlite(c: &mut Criterion) {
+    let async_runtime = Runtime::new().unwrap();
+    let connection = InProcConnection::new(InProcDatabaseLocation::InMemory).unwrap();
+    async_runtime
+        .block_on(connection.execute_batch(
+            "CREATE TABLE bench (id INTEGER PRIMARY KEY, value TEXT);
+             INSERT INTO bench (id, value) VALUES (1, 'value');",
+        ))
+        .unwrap();
+
+    let mut group = c.benchmark_group("sqlite");
+    group.bench_function("execute", |b| {
+        b.to_async(&async_runtime).iter(|| async {
+            connection
+                .query(
+                    "UPDATE bench SET value = ? WHERE id = 1",
+                    vec![sqlite::Value::Text("value".into())],
+                )
+                .await
+                .unwrap()
+        });
+    });
+    group.bench_function("query", |b| {
+        b.to_async(&async_runtime).iter(|| async {
+            connection
+                .query(
+                    "SELECT value FROM bench WHERE id = ?",
+                    vec![sqlite::Value::Integer(1)],
+                )
+                .await
+                .unwrap()
+        });
+    });
+}
+
+// The overhead of the outbound HTTP host over making the same request
+// directly, to a local server
+fn bench_outbound_http(c: &mut Criterion) {
+    let async_runtime = Runtime::new().unwrap();
+    let addr = async_runtime.block_on(serve_ok());
+    let url = format!("http://{addr}/");
+
+    let mut group = c.benchmark_group("outbound-http");
+    let client = reqwest::Client::new();
+    group.bench_function("direct", |b| {
+        b.to_async(&async_runtime).iter(|| async {
+            let resp = client.get(&url).send().await.unwrap();
+            resp.bytes().await.unwrap()
+        });
+    });
+    let host = tokio::sync::Mutex::new(OutboundHttp {
+        allowed_hosts: AllowedHostsConfig::All,
+        ..Default::default()
+    });
+    group.bench_function("host", |b| {
+        b.to_async(&async_runtime).iter(|| async {
+            let req = http_types::Request {
+                method: http_types::Method::Get,
+                uri: url.clone(),
+                headers: vec![],
+                params: vec![],
+                body: None,
+            };
+            host.lock().await.send_request(req).await.unwrap().unwrap()
+        });
+    });
+}
+
+// Helpers
+
+fn test_program(name: &str) -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("../../target/test-programs")
+        .join(name)
+}
+
+// Serves an empty 200 response to every request, on a local port.
+async fn serve_ok() -> SocketAddr {
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move {
+        loop {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            tokio::spawn(async move {
+                let mut buf = vec![0; 4096];
+                let mut read = 0;
+                loop {
+                    match stream.read(&mut buf[read..]).await {
+                        Ok(0) | Err(_) => return,
+                        Ok(n) => read += n,
+                    }
+                    // Requests have no bodies, so each ends with its headers
+                    if buf[..read].ends_with(b"\r\n\r\n") {
+                        read = 0;
+                        let resp = b"HTTP/1.1 200 OK\r\ncontent-length: 0\r\n\r\n";
+                        if stream.write_all(resp).await.is_err() {
+                            return;
+                        }
+                    }
+                }
+            });
+        }
+    });
+    addr
+}
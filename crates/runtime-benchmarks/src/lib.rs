@@ -0,0 +1,161 @@
+//! Reports on the Spin runtime benchmarks.
+//!
+//! The benchmarks themselves are a criterion suite in `benches/runtime.rs`.
+//! Criterion records each benchmark's measurements under its output
+//! directory, `target/criterion` by default; a [`Report`] collects them into
+//! a single JSON document which can be kept, e.g. as a release artifact, and
+//! compared against the next run to catch regressions.
+
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+
+/// The latest measurements of a set of benchmarks.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Report {
+    /// Measurements by benchmark ID, e.g. `key-value/get`
+    pub benchmarks: BTreeMap<String, Measurement>,
+}
+
+/// The measured time of one iteration of a benchmark.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Measurement {
+    pub mean_ns: f64,
+    pub median_ns: f64,
+    pub std_dev_ns: f64,
+}
+
+/// A benchmark which got slower than its baseline.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct Regression {
+    pub id: String,
+    pub baseline_mean_ns: f64,
+    pub mean_ns: f64,
+    /// How much slower, as a percentage of the baseline
+    pub change_percent: f64,
+}
+
+impl Report {
+    /// Reads the latest measurements of every benchmark in a criterion
+    /// output directory.
+    pub fn from_criterion_dir(dir: &Path) -> Result<Self> {
+        let mut report = Self::default();
+        report.read_dir(dir)?;
+        Ok(report)
+    }
+
+    fn read_dir(&mut self, dir: &Path) -> Result<()> {
+        let entries = std::fs::read_dir(dir)
+            .with_context(|| format!("Failed to read benchmark results in {}", dir.display()))?;
+        for entry in entries {
+            let path = entry?.path();
+            if !path.is_dir() {
+                continue;
+            }
+            // Criterion keeps the latest run of each benchmark in `new`
+            if path.file_name().is_some_and(|name| name == "new") {
+                if path.join("benchmark.json").exists() {
+                    let (id, measurement) = read_measurement(&path)?;
+                    self.benchmarks.insert(id, measurement);
+                }
+            } else {
+                self.read_dir(&path)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the benchmarks whose mean time is more than `max_percent`
+    /// higher than in `baseline`. Benchmarks missing from either report are
+    /// not compared.
+    pub fn regressions(&self, baseline: &Report, max_percent: f64) -> Vec<Regression> {
+        self.benchmarks
+            .iter()
+            .filter_map(|(id, measurement)| {
+                let baseline = baseline.benchmarks.get(id)?;
+                let change_percent =
+                    (measurement.mean_ns - baseline.mean_ns) / baseline.mean_ns * 100.0;
+                (change_percent > max_percent).then(|| Regression {
+                    id: id.clone(),
+                    baseline_mean_ns: baseline.mean_ns,
+                    mean_ns: measurement.mean_ns,
+                    change_percent,
+                })
+            })
+            .collect()
+    }
+}
+
+fn read_measurement(dir: &Path) -> Result<(String, Measurement)> {
+    #[derive(Deserialize)]
+    struct Benchmark {
+        full_id: String,
+    }
+    #[derive(Deserialize)]
+    struct Estimates {
+        mean: Estimate,
+        median: Estimate,
+        std_dev: Estimate,
+    }
+    #[derive(Deserialize)]
+    struct Estimate {
+        point_estimate: f64,
+    }
+
+    let benchmark: Benchmark = read_json(&dir.join("benchmark.json"))?;
+    let estimates: Estimates = read_json(&dir.join("estimates.json"))?;
+    let measurement = Measurement {
+        mean_ns: estimates.mean.point_estimate,
+        median_ns: estimates.median.point_estimate,
+        std_dev_ns: estimates.std_dev.point_estimate,
+    };
+    Ok((benchmark.full_id, measurement))
+}
+
+fn read_json<T: serde::de::DeserializeOwned>(path: &Path) -> Result<T> {
+    let contents =
+        std::fs::read(path).with_context(|| format!("Failed to read {}", path.display()))?;
+    serde_json::from_slice(&contents).with_context(|| format!("Invalid JSON in {}", path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write_run(dir: &Path, id: &str, mean_ns: f64) {
+        let new = dir.join(id).join("new");
+        std::fs::create_dir_all(&new).unwrap();
+        let benchmark = serde_json::json!({ "full_id": id });
+        let estimate = |value: f64| serde_json::json!({ "point_estimate": value });
+        let estimates = serde_json::json!({
+            "mean": estimate(mean_ns),
+            "median": estimate(mean_ns),
+            "std_dev": estimate(1.0),
+        });
+        std::fs::write(new.join("benchmark.json"), benchmark.to_string()).unwrap();
+        std::fs::write(new.join("estimates.json"), estimates.to_string()).unwrap();
+    }
+
+    #[test]
+    fn reports_are_compared_with_baselines() {
+        let baseline_dir = tempfile::tempdir().unwrap();
+        write_run(baseline_dir.path(), "key-value/get", 1000.0);
+        write_run(baseline_dir.path(), "key-value/set", 1000.0);
+        let dir = tempfile::tempdir().unwrap();
+        write_run(dir.path(), "key-value/get", 1050.0);
+        write_run(dir.path(), "key-value/set", 1500.0);
+        write_run(dir.path(), "sqlite/query", 1000.0);
+
+        let baseline = Report::from_criterion_dir(baseline_dir.path()).unwrap();
+        let report = Report::from_criterion_dir(dir.path()).unwrap();
+        assert_eq!(report.benchmarks.len(), 3);
+        assert_eq!(report.benchmarks["key-value/set"].mean_ns, 1500.0);
+
+        let regressions = report.regressions(&baseline, 10.0);
+        assert_eq!(regressions.len(), 1);
+        assert_eq!(regressions[0].id, "key-value/set");
+        assert_eq!(regressions[0].change_percent, 50.0);
+    }
+}
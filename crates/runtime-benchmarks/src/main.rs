@@ -0,0 +1,64 @@
+use std::path::PathBuf;
+
+use anyhow::{Context, Result};
+use clap::Parser;
+use spin_runtime_benchmarks::Report;
+
+/// Collect the results of the runtime benchmarks into a JSON report, and
+/// optionally fail if any has regressed from a previous report.
+#[derive(Parser, Debug)]
+#[clap(name = "spin-bench-report")]
+struct Args {
+    /// The criterion output directory the benchmarks were run with.
+    #[clap(long = "criterion-dir", default_value = "target/criterion")]
+    criterion_dir: PathBuf,
+
+    /// Write the report to this file rather than stdout.
+    #[clap(long = "output", short = 'o')]
+    output: Option<PathBuf>,
+
+    /// A previous report to compare against.
+    #[clap(long = "baseline")]
+    baseline: Option<PathBuf>,
+
+    /// How much slower than the baseline, as a percentage, a benchmark may
+    /// get before it counts as a regression.
+    #[clap(long = "max-regression", default_value = "10", requires = "baseline")]
+    max_regression: f64,
+}
+
+fn main() -> Result<()> {
+    let args = Args::parse();
+
+    let report = Report::from_criterion_dir(&args.criterion_dir)?;
+    let json = serde_json::to_string_pretty(&report)?;
+    match &args.output {
+        Some(path) => std::fs::write(path, json)
+            .with_context(|| format!("Failed to write report to {}", path.display()))?,
+        None => println!("{json}"),
+    }
+
+    if let Some(path) = &args.baseline {
+        let baseline = std::fs::read(path)
+            .with_context(|| format!("Failed to read baseline {}", path.display()))?;
+        let baseline: Report = serde_json::from_slice(&baseline)
+            .with_context(|| format!("Invalid baseline report {}", path.display()))?;
+        let regressions = report.regressions(&baseline, args.max_regression);
+        for regression in &regressions {
+            eprintln!(
+                "{}: {:.0}ns -> {:.0}ns (+{:.1}%)",
+                regression.id,
+                regression.baseline_mean_ns,
+                regression.mean_ns,
+                regression.change_percent
+            );
+        }
+        anyhow::ensure!(
+            regressions.is_empty(),
+            "{} benchmark(s) regressed by more than {}%",
+            regressions.len(),
+            args.max_regression
+        );
+    }
+    Ok(())
+}
@@ -0,0 +1,113 @@
+/*
+ * C bindings for running Spin applications' HTTP handlers, from the
+ * spin-runtime-ffi crate. Link with libspin_runtime_ffi.
+ *
+ * Functions which can fail return a SpinStatus, and set the message returned
+ * by spin_last_error on the calling thread when they do. All functions may
+ * be called from any thread, and an app may handle requests from several
+ * threads at once.
+ *
+ *     SpinEngine *engine;
+ *     SpinHttpApp *app;
+ *     SpinHttpResponse response;
+ *     if (spin_engine_new(&engine) != SPIN_STATUS_OK ||
+ *         spin_http_app_load(engine, "spin.toml", NULL, &app) != SPIN_STATUS_OK) {
+ *         fprintf(stderr, "%s\n", spin_last_error());
+ *         return 1;
+ *     }
+ *     if (spin_http_app_handle(app, "GET", "/hello", NULL, 0, NULL, 0, &response) == SPIN_STATUS_OK) {
+ *         fwrite(response.body, 1, response.body_len, stdout);
+ *         spin_http_response_free(&response);
+ *     }
+ *     spin_http_app_free(app);
+ *     spin_engine_free(engine);
+ */
+
+#ifndef SPIN_RUNTIME_H
+#define SPIN_RUNTIME_H
+
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+/* The result of a call. */
+typedef enum SpinStatus {
+    SPIN_STATUS_OK = 0,
+    /* An argument was null or malformed. */
+    SPIN_STATUS_INVALID_ARGUMENT = 1,
+    /* The call failed, such as when an app can't be loaded. */
+    SPIN_STATUS_ERROR = 2,
+    /* The call panicked. The engine and its apps may be unusable. */
+    SPIN_STATUS_PANIC = 3,
+} SpinStatus;
+
+/* Runs apps' Wasm components. Owns the threads on which they run. */
+typedef struct SpinEngine SpinEngine;
+
+/* An app loaded for its HTTP trigger. */
+typedef struct SpinHttpApp SpinHttpApp;
+
+/* An HTTP header. Names and values are bytes, not null-terminated. */
+typedef struct SpinHeader {
+    const uint8_t *name;
+    size_t name_len;
+    const uint8_t *value;
+    size_t value_len;
+} SpinHeader;
+
+/* An HTTP response, owned by the caller until passed to
+ * spin_http_response_free. */
+typedef struct SpinHttpResponse {
+    uint16_t status;
+    const SpinHeader *headers;
+    size_t headers_len;
+    const uint8_t *body;
+    size_t body_len;
+} SpinHttpResponse;
+
+/* Returns the message of the last error on the calling thread, or NULL if
+ * the last call succeeded. The message is valid until the thread's next
+ * call. */
+const char *spin_last_error(void);
+
+/* Creates an engine, with as many worker threads as there are CPUs. */
+SpinStatus spin_engine_new(SpinEngine **engine);
+
+/* Frees an engine. Its apps must have been freed first. */
+void spin_engine_free(SpinEngine *engine);
+
+/* Loads the app described by the spin.toml at manifest_path, and prepares
+ * its HTTP trigger's components. runtime_config_path may be NULL, or a
+ * runtime config file as given to `spin up`. */
+SpinStatus spin_http_app_load(const SpinEngine *engine,
+                              const char *manifest_path,
+                              const char *runtime_config_path,
+                              SpinHttpApp **app);
+
+/* Frees an app. */
+void spin_http_app_free(SpinHttpApp *app);
+
+/* Handles a request with the app's HTTP trigger, as if it had received it,
+ * blocking until the response is complete. Only the path and query are used
+ * from uri. headers and body may be NULL when their lengths are 0. On
+ * success, response must be freed with spin_http_response_free. */
+SpinStatus spin_http_app_handle(const SpinHttpApp *app,
+                                const char *method,
+                                const char *uri,
+                                const SpinHeader *headers,
+                                size_t headers_len,
+                                const uint8_t *body,
+                                size_t body_len,
+                                SpinHttpResponse *response);
+
+/* Frees the headers and body of a response, and zeroes it. */
+void spin_http_response_free(SpinHttpResponse *response);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* SPIN_RUNTIME_H */
@@ -0,0 +1,394 @@
+//! C bindings for [`spin_runtime`], so that hosts written in other languages
+//! (Go, C++, Python via cffi, ...) can run Spin applications' HTTP handlers.
+//! The API is declared in `include/spin_runtime.h`.
+//!
+//! Functions which can fail return a [`SpinStatus`], and set the message
+//! returned by [`spin_last_error`] on the calling thread when they do. All
+//! functions may be called from any thread, and an app may handle requests
+//! from several threads at once.
+
+use std::{
+    cell::RefCell,
+    ffi::{c_char, CStr, CString},
+    fmt,
+    panic::{catch_unwind, AssertUnwindSafe},
+    ptr, slice,
+};
+
+use anyhow::{Context, Result};
+use spin_runtime::{App, HttpTrigger, RuntimeConfig};
+
+/// The result of a call.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SpinStatus {
+    Ok = 0,
+    /// An argument was null or malformed.
+    InvalidArgument = 1,
+    /// The call failed, such as when an app can't be loaded.
+    Error = 2,
+    /// The call panicked. The engine and its apps may be unusable.
+    Panic = 3,
+}
+
+/// Runs apps' Wasm components. Owns the threads on which they run.
+pub struct SpinEngine {
+    runtime: tokio::runtime::Runtime,
+}
+
+/// An app loaded for its HTTP trigger.
+pub struct SpinHttpApp {
+    trigger: HttpTrigger,
+    runtime: tokio::runtime::Handle,
+}
+
+/// An HTTP header. Names and values are bytes, not null-terminated.
+#[repr(C)]
+#[derive(Debug)]
+pub struct SpinHeader {
+    pub name: *const u8,
+    pub name_len: usize,
+    pub value: *const u8,
+    pub value_len: usize,
+}
+
+/// An HTTP response, owned by the caller until passed to
+/// [`spin_http_response_free`].
+#[repr(C)]
+#[derive(Debug)]
+pub struct SpinHttpResponse {
+    pub status: u16,
+    pub headers: *const SpinHeader,
+    pub headers_len: usize,
+    pub body: *const u8,
+    pub body_len: usize,
+}
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+// Marks errors caused by the caller's arguments.
+#[derive(Debug)]
+struct InvalidArgument(&'static str);
+
+impl fmt::Display for InvalidArgument {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid argument `{}`", self.0)
+    }
+}
+
+impl std::error::Error for InvalidArgument {}
+
+/// Returns the message of the last error on the calling thread, or null if
+/// the last call succeeded. The message is valid until the thread's next
+/// call.
+#[no_mangle]
+pub extern "C" fn spin_last_error() -> *const c_char {
+    LAST_ERROR.with(|last| {
+        last.borrow()
+            .as_ref()
+            .map_or(ptr::null(), |message| message.as_ptr())
+    })
+}
+
+/// Creates an engine, with as many worker threads as there are CPUs.
+///
+/// # Safety
+///
+/// `engine` must be valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn spin_engine_new(engine: *mut *mut SpinEngine) -> SpinStatus {
+    call(|| {
+        let engine = out_ptr(engine, "engine")?;
+        let runtime = tokio::runtime::Builder::new_multi_thread()
+            .enable_all()
+            .build()
+            .context("Failed to start engine")?;
+        *engine = Box::into_raw(Box::new(SpinEngine { runtime }));
+        Ok(())
+    })
+}
+
+/// Frees an engine. Its apps must have been freed first.
+///
+/// # Safety
+///
+/// `engine` must be null or returned by [`spin_engine_new`], and not used
+/// again.
+#[no_mangle]
+pub unsafe extern "C" fn spin_engine_free(engine: *mut SpinEngine) {
+    if !engine.is_null() {
+        drop(Box::from_raw(engine));
+    }
+}
+
+/// Loads the app described by the `spin.toml` at `manifest_path`, and
+/// prepares its HTTP trigger's components. `runtime_config_path` may be
+/// null, or a runtime config file as given to `spin up`.
+///
+/// # Safety
+///
+/// `engine` must be a live engine, the paths null-terminated strings, and
+/// `app` valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn spin_http_app_load(
+    engine: *const SpinEngine,
+    manifest_path: *const c_char,
+    runtime_config_path: *const c_char,
+    app: *mut *mut SpinHttpApp,
+) -> SpinStatus {
+    call(|| {
+        let engine = engine.as_ref().ok_or(InvalidArgument("engine"))?;
+        let manifest_path = string(manifest_path, "manifest_path")?;
+        let mut config = RuntimeConfig::new();
+        if !runtime_config_path.is_null() {
+            config = config.file(string(runtime_config_path, "runtime_config_path")?);
+        }
+        let app = out_ptr(app, "app")?;
+        let trigger = engine.runtime.block_on(async {
+            let loaded = App::load(manifest_path).await?;
+            HttpTrigger::build(&loaded, &config).await
+        })?;
+        *app = Box::into_raw(Box::new(SpinHttpApp {
+            trigger,
+            runtime: engine.runtime.handle().clone(),
+        }));
+        Ok(())
+    })
+}
+
+/// Frees an app.
+///
+/// # Safety
+///
+/// `app` must be null or returned by [`spin_http_app_load`], and not used
+/// again.
+#[no_mangle]
+pub unsafe extern "C" fn spin_http_app_free(app: *mut SpinHttpApp) {
+    if !app.is_null() {
+        let app = Box::from_raw(app);
+        let _guard = app.runtime.enter();
+        drop(app);
+    }
+}
+
+/// Handles a request with the app's HTTP trigger, as if it had received it,
+/// blocking until the response is complete. Only the path and query are
+/// used from `uri`. On success, `response` must be freed with
+/// [`spin_http_response_free`].
+///
+/// # Safety
+///
+/// `app` must be a live app, `method` and `uri` null-terminated strings,
+/// `headers` and `body` null or valid for their lengths, and `response`
+/// valid for writes.
+#[no_mangle]
+#[allow(clippy::too_many_arguments)]
+pub unsafe extern "C" fn spin_http_app_handle(
+    app: *const SpinHttpApp,
+    method: *const c_char,
+    uri: *const c_char,
+    headers: *const SpinHeader,
+    headers_len: usize,
+    body: *const u8,
+    body_len: usize,
+    response: *mut SpinHttpResponse,
+) -> SpinStatus {
+    call(|| {
+        let app = app.as_ref().ok_or(InvalidArgument("app"))?;
+        let response = response.as_mut().ok_or(InvalidArgument("response"))?;
+        let mut request = http::Request::builder()
+            .method(string(method, "method")?)
+            .uri(string(uri, "uri")?);
+        for header in bytes(headers, headers_len, "headers")? {
+            request = request.header(
+                bytes(header.name, header.name_len, "headers")?,
+                bytes(header.value, header.value_len, "headers")?,
+            );
+        }
+        let request = request
+            .body(bytes(body, body_len, "body")?.to_vec())
+            .map_err(|err| {
+                anyhow::Error::new(InvalidArgument("request")).context(format!("{err}"))
+            })?;
+        let handled = app.runtime.block_on(app.trigger.dispatch(request))?;
+        *response = SpinHttpResponse::from(handled);
+        Ok(())
+    })
+}
+
+/// Frees the headers and body of a response, and zeroes it.
+///
+/// # Safety
+///
+/// `response` must be null or filled by [`spin_http_app_handle`], and not
+/// freed already.
+#[no_mangle]
+pub unsafe extern "C" fn spin_http_response_free(response: *mut SpinHttpResponse) {
+    let Some(response) = response.as_mut() else {
+        return;
+    };
+    if !response.headers.is_null() {
+        let headers = Box::from_raw(ptr::slice_from_raw_parts_mut(
+            response.headers as *mut SpinHeader,
+            response.headers_len,
+        ));
+        for header in headers.iter() {
+            free_bytes(header.name, header.name_len);
+            free_bytes(header.value, header.value_len);
+        }
+    }
+    free_bytes(response.body, response.body_len);
+    *response = SpinHttpResponse::empty();
+}
+
+impl SpinHttpResponse {
+    fn empty() -> Self {
+        Self {
+            status: 0,
+            headers: ptr::null(),
+            headers_len: 0,
+            body: ptr::null(),
+            body_len: 0,
+        }
+    }
+}
+
+impl From<http::Response<Vec<u8>>> for SpinHttpResponse {
+    fn from(response: http::Response<Vec<u8>>) -> Self {
+        let (parts, body) = response.into_parts();
+        let headers: Box<[SpinHeader]> = parts
+            .headers
+            .iter()
+            .map(|(name, value)| {
+                let (name, name_len) = leak_bytes(name.as_str().as_bytes().into());
+                let (value, value_len) = leak_bytes(value.as_bytes().into());
+                SpinHeader {
+                    name,
+                    name_len,
+                    value,
+                    value_len,
+                }
+            })
+            .collect();
+        let headers_len = headers.len();
+        let (body, body_len) = leak_bytes(body.into_boxed_slice());
+        Self {
+            status: parts.status.as_u16(),
+            headers: Box::into_raw(headers) as *const SpinHeader,
+            headers_len,
+            body,
+            body_len,
+        }
+    }
+}
+
+// Runs a call, recording its error for `spin_last_error`. Panics must not
+// unwind into the caller.
+fn call(f: impl FnOnce() -> Result<()>) -> SpinStatus {
+    let (status, message) = match catch_unwind(AssertUnwindSafe(f)) {
+        Ok(Ok(())) => (SpinStatus::Ok, None),
+        Ok(Err(err)) if err.is::<InvalidArgument>() => {
+            (SpinStatus::InvalidArgument, Some(format!("{err:#}")))
+        }
+        Ok(Err(err)) => (SpinStatus::Error, Some(format!("{err:#}"))),
+        Err(_) => (SpinStatus::Panic, Some("Spin panicked".to_owned())),
+    };
+    let message = message.map(|message| {
+        // Interior nulls would truncate the message, so replace them
+        CString::new(message.replace('\0', " ")).expect("nulls were replaced")
+    });
+    LAST_ERROR.with(|last| *last.borrow_mut() = message);
+    status
+}
+
+unsafe fn out_ptr<'a, T>(ptr: *mut *mut T, name: &'static str) -> Result<&'a mut *mut T> {
+    Ok(ptr.as_mut().ok_or(InvalidArgument(name))?)
+}
+
+unsafe fn string<'a>(ptr: *const c_char, name: &'static str) -> Result<&'a str> {
+    if ptr.is_null() {
+        return Err(InvalidArgument(name).into());
+    }
+    CStr::from_ptr(ptr)
+        .to_str()
+        .map_err(|_| InvalidArgument(name).into())
+}
+
+// A null pointer is accepted for an empty slice.
+unsafe fn bytes<'a, T>(ptr: *const T, len: usize, name: &'static str) -> Result<&'a [T]> {
+    if ptr.is_null() {
+        if len == 0 {
+            return Ok(&[]);
+        }
+        return Err(InvalidArgument(name).into());
+    }
+    Ok(slice::from_raw_parts(ptr, len))
+}
+
+fn leak_bytes(bytes: Box<[u8]>) -> (*const u8, usize) {
+    let len = bytes.len();
+    (Box::into_raw(bytes) as *const u8, len)
+}
+
+unsafe fn free_bytes(ptr: *const u8, len: usize) {
+    if !ptr.is_null() {
+        drop(Box::from_raw(ptr::slice_from_raw_parts_mut(
+            ptr as *mut u8,
+            len,
+        )));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn last_error() -> String {
+        unsafe { CStr::from_ptr(spin_last_error()) }
+            .to_string_lossy()
+            .into_owned()
+    }
+
+    #[test]
+    fn errors_are_reported() {
+        let mut engine = ptr::null_mut();
+        assert_eq!(unsafe { spin_engine_new(&mut engine) }, SpinStatus::Ok);
+        assert!(spin_last_error().is_null());
+
+        let mut app = ptr::null_mut();
+        let status = unsafe { spin_http_app_load(engine, ptr::null(), ptr::null(), &mut app) };
+        assert_eq!(status, SpinStatus::InvalidArgument);
+        assert!(last_error().contains("manifest_path"));
+
+        let manifest_path = CString::new("/nonexistent/spin.toml").unwrap();
+        let status =
+            unsafe { spin_http_app_load(engine, manifest_path.as_ptr(), ptr::null(), &mut app) };
+        assert_eq!(status, SpinStatus::Error);
+        assert!(last_error().contains("/nonexistent/spin.toml"));
+        assert!(app.is_null());
+
+        unsafe { spin_engine_free(engine) };
+    }
+
+    #[test]
+    fn responses_are_freed() {
+        let handled = http::Response::builder()
+            .status(201)
+            .header("content-type", "text/plain")
+            .body(b"hello".to_vec())
+            .unwrap();
+        let mut response = SpinHttpResponse::from(handled);
+        assert_eq!(response.status, 201);
+        let headers = unsafe { slice::from_raw_parts(response.headers, response.headers_len) };
+        let name = unsafe { slice::from_raw_parts(headers[0].name, headers[0].name_len) };
+        assert_eq!(name, b"content-type");
+        let body = unsafe { slice::from_raw_parts(response.body, response.body_len) };
+        assert_eq!(body, b"hello");
+
+        unsafe { spin_http_response_free(&mut response) };
+        assert!(response.body.is_null());
+        assert_eq!(response.headers_len, 0);
+    }
+}
@@ -0,0 +1,143 @@
+use std::{
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use anyhow::{anyhow, Context, Result};
+use spin_app::locked::LockedApp;
+use spin_common::ui::quoted_path;
+use spin_loader::FilesMountStrategy;
+use spin_oci::OciLoader;
+use url::Url;
+
+/// Where to load an application from.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum AppSource {
+    /// A `spin.toml` manifest file.
+    Manifest(PathBuf),
+    /// A registry reference, such as `ghcr.io/acme/app:v1`.
+    Registry(String),
+}
+
+impl From<&Path> for AppSource {
+    fn from(path: &Path) -> Self {
+        Self::Manifest(path.to_owned())
+    }
+}
+
+impl From<PathBuf> for AppSource {
+    fn from(path: PathBuf) -> Self {
+        Self::Manifest(path)
+    }
+}
+
+impl From<&str> for AppSource {
+    /// A manifest path, as it is the more common source; use
+    /// [`AppSource::Registry`] for registry references.
+    fn from(path: &str) -> Self {
+        Self::Manifest(path.into())
+    }
+}
+
+/// A loaded application, ready for its triggers to be built.
+///
+/// Loading copies the files the application mounts into a temporary
+/// directory, which is removed once the app and all the triggers built from
+/// it are dropped.
+#[derive(Clone)]
+pub struct App {
+    locked: Arc<LockedApp>,
+    working_dir: Arc<tempfile::TempDir>,
+    local_app_dir: Option<PathBuf>,
+}
+
+impl App {
+    /// Loads an application from a manifest or a registry.
+    pub async fn load(source: impl Into<AppSource>) -> Result<Self> {
+        let working_dir = tempfile::tempdir().context("Failed to create working directory")?;
+        let (locked, local_app_dir) = match source.into() {
+            AppSource::Manifest(manifest_path) => {
+                let locked = spin_loader::from_file(
+                    &manifest_path,
+                    FilesMountStrategy::Copy(working_dir.path().join("assets")),
+                    None,
+                )
+                .await
+                .with_context(|| {
+                    format!(
+                        "Failed to load manifest from {}",
+                        quoted_path(&manifest_path)
+                    )
+                })?;
+                let local_app_dir = manifest_path.canonicalize()?.parent().map(Path::to_owned);
+                (locked, local_app_dir)
+            }
+            AppSource::Registry(reference) => {
+                let mut client = spin_oci::Client::new(false, None)
+                    .await
+                    .context("cannot create registry client")?;
+                let locked = OciLoader::new(working_dir.path())
+                    .load_app(&mut client, &reference)
+                    .await?;
+                (locked, None)
+            }
+        };
+        Ok(Self {
+            locked: Arc::new(locked),
+            working_dir: Arc::new(working_dir),
+            local_app_dir,
+        })
+    }
+
+    /// The application's name.
+    pub fn name(&self) -> Option<&str> {
+        self.locked.metadata.get("name")?.as_str()
+    }
+
+    /// The types of the application's triggers, such as `http`.
+    pub fn trigger_types(&self) -> Vec<&str> {
+        let mut types: Vec<&str> = self
+            .locked
+            .triggers
+            .iter()
+            .map(|trigger| trigger.trigger_type.as_str())
+            .collect();
+        types.sort_unstable();
+        types.dedup();
+        types
+    }
+
+    pub(crate) fn working_dir(&self) -> &Path {
+        self.working_dir.path()
+    }
+
+    pub(crate) fn keep_working_dir(&self) -> Arc<tempfile::TempDir> {
+        self.working_dir.clone()
+    }
+
+    pub(crate) fn local_app_dir(&self) -> Option<PathBuf> {
+        self.local_app_dir.clone()
+    }
+
+    /// Writes the locked app for a trigger to load, returning its URL.
+    pub(crate) fn write_locked(&self) -> Result<String> {
+        let path = self.working_dir().join("spin.lock");
+        let contents =
+            serde_json::to_vec_pretty(&*self.locked).context("failed to serialize locked app")?;
+        std::fs::write(&path, contents)
+            .with_context(|| format!("failed to write {}", quoted_path(&path)))?;
+        Url::from_file_path(&path)
+            .map(String::from)
+            .map_err(|_| anyhow!("cannot convert to file URL: {}", quoted_path(&path)))
+    }
+}
+
+impl std::fmt::Debug for App {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("App")
+            .field("name", &self.name())
+            .field("trigger_types", &self.trigger_types())
+            .finish_non_exhaustive()
+    }
+}
@@ -0,0 +1,192 @@
+use std::{collections::BTreeMap, path::PathBuf};
+
+use anyhow::Result;
+use spin_trigger::{
+    RedisKeyValueStoreOpts, RuntimeConfigBuilder, SpinKeyValueStoreOpts, SpinSqliteDatabaseOpts,
+};
+
+use crate::SharedEngine;
+
+/// Runtime configuration for an application: where it keeps state, and the
+/// stores and databases its components use.
+///
+/// Configuration is layered in the order it is given, later layers taking
+/// precedence: runtime config files, then TOML, then the stores, databases
+/// and directories set by the other methods.
+#[derive(Clone, Debug, Default)]
+pub struct RuntimeConfig {
+    files: Vec<PathBuf>,
+    toml: Vec<String>,
+    key_value_stores: BTreeMap<String, KeyValueStore>,
+    sqlite_databases: BTreeMap<String, SqliteDatabase>,
+    state_dir: Option<PathBuf>,
+    log_dir: Option<PathBuf>,
+    engine: Option<SharedEngine>,
+}
+
+/// A key-value store for components to use.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum KeyValueStore {
+    /// A store which is lost when the trigger stops.
+    InMemory,
+    /// A store in a SQLite database file.
+    Sqlite(PathBuf),
+    /// A store in a Redis server.
+    Redis {
+        /// The server's address, e.g. `redis://localhost:6379`.
+        url: String,
+    },
+}
+
+/// A SQLite database for components to use.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum SqliteDatabase {
+    /// A database which is lost when the trigger stops.
+    InMemory,
+    /// A database file.
+    File(PathBuf),
+}
+
+impl RuntimeConfig {
+    /// Configuration with Spin's defaults.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a runtime config file, as given to `spin up --runtime-config-file`.
+    pub fn file(mut self, path: impl Into<PathBuf>) -> Self {
+        self.files.push(path.into());
+        self
+    }
+
+    /// Adds runtime config in the TOML format of runtime config files, for
+    /// options which have no method of their own. Relative paths in it are
+    /// relative to the current directory.
+    pub fn toml(mut self, toml: impl Into<String>) -> Self {
+        self.toml.push(toml.into());
+        self
+    }
+
+    /// Sets the directory in which the application keeps its state, such as
+    /// its default key-value store. Defaults to `.spin` next to the
+    /// manifest for apps loaded from one, and to none otherwise.
+    pub fn state_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.state_dir = Some(dir.into());
+        self
+    }
+
+    /// Sets the directory to which component output is logged.
+    pub fn log_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.log_dir = Some(dir.into());
+        self
+    }
+
+    /// Sets the key-value store components open by `name`.
+    pub fn key_value_store(mut self, name: impl Into<String>, store: KeyValueStore) -> Self {
+        self.key_value_stores.insert(name.into(), store);
+        self
+    }
+
+    /// Sets the SQLite database components open by `name`.
+    pub fn sqlite_database(mut self, name: impl Into<String>, database: SqliteDatabase) -> Self {
+        self.sqlite_databases.insert(name.into(), database);
+        self
+    }
+
+    /// Runs the app's triggers on an engine shared with other apps' triggers,
+    /// rather than on an engine of their own.
+    pub fn engine(mut self, engine: &SharedEngine) -> Self {
+        self.engine = Some(engine.clone());
+        self
+    }
+
+    pub(crate) fn shared_engine(&self) -> Option<&SharedEngine> {
+        self.engine.as_ref()
+    }
+
+    /// Builds the trigger's runtime config for an app.
+    pub(crate) fn build(
+        &self,
+        local_app_dir: Option<PathBuf>,
+    ) -> Result<spin_trigger::RuntimeConfig> {
+        let mut config = spin_trigger::RuntimeConfig::new(local_app_dir);
+        for file in &self.files {
+            config.merge_config_file(file)?;
+        }
+        for toml in &self.toml {
+            config.merge_config_toml(toml)?;
+        }
+        let mut builder = RuntimeConfigBuilder::new();
+        for (name, store) in &self.key_value_stores {
+            builder = match store {
+                KeyValueStore::InMemory => builder.key_value_store(name, spin_store(None)),
+                KeyValueStore::Sqlite(path) => {
+                    builder.key_value_store(name, spin_store(Some(path.clone())))
+                }
+                KeyValueStore::Redis { url } => builder.key_value_store(
+                    name,
+                    RedisKeyValueStoreOpts {
+                        url: url.clone(),
+                        encryption_key: None,
+                    },
+                ),
+            };
+        }
+        for (name, database) in &self.sqlite_databases {
+            let path = match database {
+                SqliteDatabase::InMemory => None,
+                SqliteDatabase::File(path) => Some(path.clone()),
+            };
+            builder = builder.sqlite_database(name, SpinSqliteDatabaseOpts { path });
+        }
+        config.merge_config_builder(builder);
+        if let Some(state_dir) = &self.state_dir {
+            config.set_state_dir(state_dir.to_string_lossy());
+        }
+        if let Some(log_dir) = &self.log_dir {
+            config.set_log_dir(log_dir);
+        }
+        Ok(config)
+    }
+}
+
+fn spin_store(path: Option<PathBuf>) -> SpinKeyValueStoreOpts {
+    SpinKeyValueStoreOpts {
+        path,
+        encryption_key: None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn later_layers_take_precedence() {
+        let config = RuntimeConfig::new()
+            .toml(
+                r#"
+                state_dir = "/from/toml"
+                [key_value_store.default]
+                type = "redis"
+                url = "redis://localhost"
+                "#,
+            )
+            .state_dir("/from/method")
+            .key_value_store("default", KeyValueStore::Sqlite("/data/kv.db".into()))
+            .sqlite_database("default", SqliteDatabase::InMemory)
+            .build(None)
+            .unwrap();
+        assert_eq!(config.state_dir().unwrap(), PathBuf::from("/from/method"));
+    }
+
+    #[test]
+    fn invalid_toml_is_an_error() {
+        RuntimeConfig::new()
+            .toml("not_an_option = true")
+            .build(None)
+            .unwrap_err();
+    }
+}
@@ -0,0 +1,34 @@
+use anyhow::{Context, Result};
+
+/// A Wasm engine which the triggers of several applications share, so that
+/// running them in one process compiles and reserves memory for their
+/// components in one pool rather than in one per trigger.
+///
+/// Triggers run on a shared engine when it is set with
+/// [`RuntimeConfig::engine`](crate::RuntimeConfig::engine). Each app keeps its
+/// own state and configuration.
+#[derive(Clone)]
+pub struct SharedEngine {
+    config: spin_core::Config,
+}
+
+impl SharedEngine {
+    /// Creates an engine with Spin's default configuration.
+    pub fn new() -> Result<Self> {
+        let mut config = spin_core::Config::default();
+        config
+            .share_engine()
+            .context("Failed to create shared engine")?;
+        Ok(Self { config })
+    }
+
+    pub(crate) fn config(&self) -> &spin_core::Config {
+        &self.config
+    }
+}
+
+impl std::fmt::Debug for SharedEngine {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SharedEngine").finish_non_exhaustive()
+    }
+}
@@ -0,0 +1,47 @@
+//! An embeddable Spin runtime, for running Spin applications inside your own
+//! Rust server.
+//!
+//! This crate is a small facade over Spin's triggers, whose API follows
+//! semver independently of the internal crates it is built on: load an
+//! [`App`], describe its [`RuntimeConfig`], then build its triggers to serve
+//! it or to dispatch requests to it directly.
+//!
+//! ```no_run
+//! use spin_runtime::{App, HttpTrigger, KeyValueStore, RuntimeConfig};
+//!
+//! # async fn run() -> anyhow::Result<()> {
+//! let app = App::load("spin.toml").await?;
+//! let config = RuntimeConfig::new()
+//!     .state_dir("/var/lib/my-server/spin")
+//!     .key_value_store("default", KeyValueStore::InMemory);
+//!
+//! // Dispatch a request without listening
+//! let trigger = HttpTrigger::build(&app, &config).await?;
+//! let request = http::Request::get("/hello").body(vec![])?;
+//! let response = trigger.dispatch(request).await?;
+//! assert!(response.status().is_success());
+//!
+//! // Or serve it until stopped
+//! let running = trigger.serve("127.0.0.1:3000".parse()?);
+//! running.stop().await?;
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! Several apps can run in one process on a [`SharedEngine`], each with its
+//! own state directory and listen address. An app served with
+//! [`HttpTrigger::serve_replaceable`] can be upgraded in place, switching
+//! requests to the new version at once while the old one finishes those it
+//! was handling.
+//!
+//! Triggers must be built and run inside a multi-threaded Tokio runtime.
+
+mod app;
+mod config;
+mod engine;
+mod trigger;
+
+pub use app::{App, AppSource};
+pub use config::{KeyValueStore, RuntimeConfig, SqliteDatabase};
+pub use engine::SharedEngine;
+pub use trigger::{HttpServer, HttpTrigger, RedisTrigger, RetiredApp, RunningTrigger};
@@ -0,0 +1,298 @@
+use std::{
+    net::SocketAddr,
+    sync::{Arc, RwLock},
+    time::Duration,
+};
+
+use anyhow::{anyhow, Context, Result};
+use http::{uri::Scheme, Request, Response};
+use http_body_util::BodyExt;
+use hyper::{body::Incoming, server::conn::http1, service::service_fn};
+use hyper_util::rt::TokioIo;
+use serde::de::DeserializeOwned;
+use spin_trigger::{
+    cli::NoArgs, loader::TriggerLoader, HostComponentInitData, TriggerExecutor,
+    TriggerExecutorBuilder,
+};
+use spin_trigger_http::{CliArgs, ListenAddr};
+use tokio::{
+    net::TcpListener,
+    task::{JoinHandle, JoinSet},
+};
+
+use crate::{App, RuntimeConfig};
+
+/// The peer address of dispatched requests, which have no client.
+const DISPATCH_PEER_ADDR: SocketAddr = SocketAddr::V4(std::net::SocketAddrV4::new(
+    std::net::Ipv4Addr::LOCALHOST,
+    0,
+));
+
+/// How often a retired app is checked for requests still being handled.
+const DRAIN_POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// An app's HTTP trigger.
+pub struct HttpTrigger {
+    executor: spin_trigger_http::HttpTrigger,
+    working_dir: Arc<tempfile::TempDir>,
+}
+
+impl HttpTrigger {
+    /// Builds the app's HTTP trigger, preparing its components.
+    pub async fn build(app: &App, config: &RuntimeConfig) -> Result<Self> {
+        Ok(Self {
+            executor: build_executor(app, config).await?,
+            working_dir: app.keep_working_dir(),
+        })
+    }
+
+    /// Handles a request as if it had been received by the trigger, without
+    /// listening. Only the request's path and query are used from its URI.
+    pub async fn dispatch(&self, request: Request<Vec<u8>>) -> Result<Response<Vec<u8>>> {
+        let request = request.map(|body| spin_http::body::full(body.into()));
+        let response = self
+            .executor
+            .handle(request, Scheme::HTTP, DISPATCH_PEER_ADDR)
+            .await?;
+        let (parts, body) = response.into_parts();
+        let body = body
+            .collect()
+            .await
+            .map_err(|err| anyhow!("Failed to read response body: {err:?}"))?
+            .to_bytes();
+        Ok(Response::from_parts(parts, body.to_vec()))
+    }
+
+    /// Serves the app on `addr` until stopped.
+    pub fn serve(self, addr: SocketAddr) -> RunningTrigger {
+        let args = CliArgs {
+            address: ListenAddr::Tcp(addr),
+            tls_cert: None,
+            tls_key: None,
+            h2c: false,
+            debug_capture: false,
+        };
+        RunningTrigger::spawn(self.executor, args, self.working_dir)
+    }
+
+    /// Serves the app on `addr` until stopped, in a way which lets it be
+    /// replaced by another version of the app with [`HttpServer::replace`].
+    ///
+    /// Unlike [`serve`](Self::serve), the server only speaks HTTP/1.1 over
+    /// plain TCP.
+    pub async fn serve_replaceable(self, addr: SocketAddr) -> Result<HttpServer> {
+        let listener = TcpListener::bind(addr)
+            .await
+            .with_context(|| format!("Unable to listen on {addr}"))?;
+        let addr = listener.local_addr()?;
+        let current = Arc::new(RwLock::new(Arc::new(ServedApp {
+            executor: self.executor,
+            _working_dir: self.working_dir,
+        })));
+        let task = tokio::spawn(accept_connections(listener, current.clone()));
+        Ok(HttpServer {
+            current,
+            addr,
+            task,
+        })
+    }
+}
+
+/// An HTTP listener serving an app which can be replaced while it runs,
+/// without refusing or dropping any connection. Each request is handled by
+/// the version of the app current when it arrived.
+pub struct HttpServer {
+    current: Arc<RwLock<Arc<ServedApp>>>,
+    addr: SocketAddr,
+    task: JoinHandle<Result<()>>,
+}
+
+struct ServedApp {
+    executor: spin_trigger_http::HttpTrigger,
+    _working_dir: Arc<tempfile::TempDir>,
+}
+
+impl HttpServer {
+    /// The address the server listens on.
+    pub fn local_addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    /// Routes new requests to `trigger`'s app, returning the app it replaces
+    /// so that the requests it is still handling can be waited for.
+    pub fn replace(&self, trigger: HttpTrigger) -> RetiredApp {
+        let app = Arc::new(ServedApp {
+            executor: trigger.executor,
+            _working_dir: trigger.working_dir,
+        });
+        let previous = std::mem::replace(&mut *self.current.write().unwrap(), app);
+        RetiredApp { app: previous }
+    }
+
+    /// Whether the server has stopped by itself, such as on an error.
+    pub fn is_finished(&self) -> bool {
+        self.task.is_finished()
+    }
+
+    /// Stops the server. Requests being handled are abandoned.
+    pub async fn stop(self) -> Result<()> {
+        self.task.abort();
+        match self.task.await {
+            Ok(result) => result,
+            Err(err) if err.is_cancelled() => Ok(()),
+            Err(err) => Err(err).context("HTTP server panicked"),
+        }
+    }
+}
+
+/// An app which an [`HttpServer`] no longer routes requests to, but which
+/// may still be handling some. Dropping it abandons those requests.
+pub struct RetiredApp {
+    app: Arc<ServedApp>,
+}
+
+impl RetiredApp {
+    /// Whether the app has finished handling its requests, including
+    /// sending their response bodies.
+    pub fn is_idle(&self) -> bool {
+        Arc::strong_count(&self.app) == 1
+    }
+
+    /// Waits at most `timeout` for the app to finish handling its requests,
+    /// then drops it. Returns whether it finished in time.
+    pub async fn drain(self, timeout: Duration) -> bool {
+        let deadline = tokio::time::Instant::now() + timeout;
+        while !self.is_idle() {
+            if tokio::time::Instant::now() >= deadline {
+                return false;
+            }
+            tokio::time::sleep(DRAIN_POLL_INTERVAL).await;
+        }
+        true
+    }
+}
+
+// Serves connections until aborted, which also aborts the connections.
+async fn accept_connections(
+    listener: TcpListener,
+    current: Arc<RwLock<Arc<ServedApp>>>,
+) -> Result<()> {
+    let mut connections = JoinSet::new();
+    loop {
+        let (stream, addr) = tokio::select! {
+            accepted = listener.accept() => accepted?,
+            Some(_) = connections.join_next() => continue,
+        };
+        let current = current.clone();
+        connections.spawn(async move {
+            let service = service_fn(move |request: Request<Incoming>| {
+                // Holding the app until the response body is sent marks it
+                // as busy for RetiredApp::is_idle
+                let app = current.read().unwrap().clone();
+                async move {
+                    let request = request.map(|body| {
+                        body.map_err(wasmtime_wasi_http::hyper_response_error)
+                            .boxed()
+                    });
+                    let response = app.executor.handle(request, Scheme::HTTP, addr).await?;
+                    Ok::<_, anyhow::Error>(response.map(move |body| {
+                        body.map_frame(move |frame| {
+                            let _ = &app;
+                            frame
+                        })
+                        .boxed()
+                    }))
+                }
+            });
+            if let Err(err) = http1::Builder::new()
+                .keep_alive(true)
+                .serve_connection(TokioIo::new(stream), service)
+                .await
+            {
+                tracing::warn!("Error serving HTTP connection: {err:?}");
+            }
+        });
+    }
+}
+
+/// An app's Redis trigger.
+pub struct RedisTrigger {
+    executor: spin_trigger_redis::RedisTrigger,
+    working_dir: Arc<tempfile::TempDir>,
+}
+
+impl RedisTrigger {
+    /// Builds the app's Redis trigger, preparing its components.
+    pub async fn build(app: &App, config: &RuntimeConfig) -> Result<Self> {
+        Ok(Self {
+            executor: build_executor(app, config).await?,
+            working_dir: app.keep_working_dir(),
+        })
+    }
+
+    /// Subscribes to the app's channels until stopped.
+    pub fn start(self) -> RunningTrigger {
+        RunningTrigger::spawn(self.executor, NoArgs, self.working_dir)
+    }
+}
+
+/// A trigger running in the background.
+pub struct RunningTrigger {
+    task: JoinHandle<Result<()>>,
+}
+
+impl RunningTrigger {
+    fn spawn<Executor: TriggerExecutor + 'static>(
+        executor: Executor,
+        run_config: Executor::RunConfig,
+        working_dir: Arc<tempfile::TempDir>,
+    ) -> Self
+    where
+        Executor::RunConfig: Send + 'static,
+    {
+        let task = tokio::spawn(async move {
+            let _working_dir = working_dir;
+            executor.run(run_config).await
+        });
+        Self { task }
+    }
+
+    /// Whether the trigger has stopped by itself, such as on an error.
+    pub fn is_finished(&self) -> bool {
+        self.task.is_finished()
+    }
+
+    /// Waits for the trigger to stop by itself, returning its error if it
+    /// failed.
+    pub async fn wait(self) -> Result<()> {
+        self.task.await.context("Trigger panicked")?
+    }
+
+    /// Stops the trigger. Requests being handled are abandoned.
+    pub async fn stop(self) -> Result<()> {
+        self.task.abort();
+        match self.task.await {
+            Ok(result) => result,
+            Err(err) if err.is_cancelled() => Ok(()),
+            Err(err) => Err(err).context("Trigger panicked"),
+        }
+    }
+}
+
+async fn build_executor<Executor>(app: &App, config: &RuntimeConfig) -> Result<Executor>
+where
+    Executor: TriggerExecutor,
+    Executor::TriggerConfig: DeserializeOwned,
+{
+    let runtime_config = config.build(app.local_app_dir())?;
+    let locked_url = app.write_locked()?;
+    let loader = TriggerLoader::new(app.working_dir(), false);
+    let mut builder = TriggerExecutorBuilder::<Executor>::new(loader);
+    if let Some(engine) = config.shared_engine() {
+        *builder.config_mut() = engine.config().clone();
+    }
+    builder
+        .build(locked_url, runtime_config, HostComponentInitData::default())
+        .await
+        .with_context(|| format!("Failed to build {} trigger", Executor::TRIGGER_TYPE))
+}
@@ -22,6 +22,25 @@ impl<const V: usize> TryFrom<usize> for FixedVersion<V> {
     }
 }
 
+impl<const V: usize> schemars::JsonSchema for FixedVersion<V> {
+    fn is_referenceable() -> bool {
+        false
+    }
+
+    fn schema_name() -> String {
+        format!("FixedVersion{V}")
+    }
+
+    fn json_schema(_gen: &mut schemars::gen::SchemaGenerator) -> schemars::schema::Schema {
+        schemars::schema::SchemaObject {
+            instance_type: Some(schemars::schema::InstanceType::Integer.into()),
+            const_value: Some(V.into()),
+            ..Default::default()
+        }
+        .into()
+    }
+}
+
 /// FixedVersion represents a version integer field with a const value,
 /// but accepts lower versions during deserialisation.
 #[derive(Clone, Debug, Default, Deserialize)]
@@ -57,6 +57,11 @@ impl HostComponent for SqliteComponent {
         }
         SqliteDispatch::new(Arc::new(Noop))
     }
+
+    fn reset_data(&self, data: &mut Self::Data) -> bool {
+        data.reset();
+        true
+    }
 }
 
 impl DynamicHostComponent for SqliteComponent {
@@ -59,6 +59,13 @@ impl SqliteDispatch {
         self.connections_store = connections_store;
     }
 
+    /// Closes all open connections and forgets the allowed databases, so
+    /// that the dispatch can be reused by another component.
+    pub fn reset(&mut self) {
+        self.allowed_databases.clear();
+        self.connections.clear();
+    }
+
     fn get_connection(
         &self,
         connection: Resource<sqlite::Connection>,
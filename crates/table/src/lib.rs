@@ -69,4 +69,16 @@ impl<V> Table<V> {
     pub fn remove(&mut self, key: u32) -> Option<V> {
         self.tuples.remove(&key)
     }
+
+    /// Remove and return all resources, keeping the table's allocated capacity so that it can be reused.
+    pub fn drain(&mut self) -> impl Iterator<Item = V> + '_ {
+        self.next_key = 0;
+        self.tuples.drain().map(|(_, value)| value)
+    }
+
+    /// Remove all resources, keeping the table's allocated capacity so that it can be reused.
+    pub fn clear(&mut self) {
+        self.tuples.clear();
+        self.next_key = 0;
+    }
 }
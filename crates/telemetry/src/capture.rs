@@ -0,0 +1,381 @@
+//! Capture of what happens while a single request is handled, for debugging.
+//!
+//! A [`Capture`] collects the spans started while it is in [scope](Capture::scope), along with
+//! their descendants on other tasks and the events logged in them, the output of components,
+//! the decisions of the allowed-hosts checker, and the instantiation time and memory use of
+//! component instances. [`Capture::bundle`] returns all of this as a JSON document.
+
+use std::future::Future;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use chrono::{DateTime, SecondsFormat, Utc};
+use serde_json::{json, Map, Value};
+use tracing::span::{Attributes, Id, Record};
+use tracing::{Event, Level, Metadata, Subscriber};
+use tracing_subscriber::{layer::Context, registry::LookupSpan, Layer};
+
+use crate::logs::JsonFields;
+
+/// The most spans and events kept for a request. Later ones are dropped, and the bundle is
+/// marked as truncated.
+const MAX_ENTRIES: usize = 10_000;
+/// The most component output kept for a request, in bytes.
+const MAX_OUTPUT: usize = 1024 * 1024;
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+static ACTIVE: AtomicUsize = AtomicUsize::new(0);
+
+tokio::task_local! {
+    static CURRENT: Capture;
+}
+
+/// Declares that requests may be captured. Component output must be piped through Spin
+/// rather than inherited for it to be captured, which is only done once this is called.
+pub fn enable() {
+    ENABLED.store(true, Ordering::Relaxed);
+}
+
+/// Whether requests may be captured.
+pub fn enabled() -> bool {
+    ENABLED.load(Ordering::Relaxed)
+}
+
+/// Whether any capture is in progress, and so spans should be recorded.
+pub(crate) fn active() -> bool {
+    ACTIVE.load(Ordering::Relaxed) > 0
+}
+
+/// The record of a single request.
+#[derive(Clone)]
+pub struct Capture(Arc<Mutex<CaptureData>>);
+
+struct CaptureData {
+    started: Instant,
+    started_at: DateTime<Utc>,
+    spans: Vec<CapturedSpan>,
+    // Events logged outside of any captured span
+    events: Vec<Value>,
+    output: Vec<Value>,
+    output_len: usize,
+    outbound: Vec<Value>,
+    instances: Vec<Value>,
+    truncated: bool,
+}
+
+struct CapturedSpan {
+    name: &'static str,
+    target: &'static str,
+    parent: Option<usize>,
+    fields: Map<String, Value>,
+    start: Duration,
+    end: Option<Duration>,
+    events: Vec<Value>,
+}
+
+impl Capture {
+    #[allow(clippy::new_without_default)]
+    pub fn new() -> Self {
+        ACTIVE.fetch_add(1, Ordering::Relaxed);
+        Self(Arc::new(Mutex::new(CaptureData {
+            started: Instant::now(),
+            started_at: Utc::now(),
+            spans: vec![],
+            events: vec![],
+            output: vec![],
+            output_len: 0,
+            outbound: vec![],
+            instances: vec![],
+            truncated: false,
+        })))
+    }
+
+    /// Runs `fut` with this capture recording what it does.
+    pub async fn scope<F: Future>(&self, fut: F) -> F::Output {
+        CURRENT.scope(self.clone(), fut).await
+    }
+
+    /// The captured spans, output, outbound calls and instance statistics as JSON.
+    pub fn bundle(&self) -> Value {
+        let data = self.0.lock().unwrap();
+        let spans: Vec<Value> = data
+            .spans
+            .iter()
+            .enumerate()
+            .map(|(id, span)| {
+                json!({
+                    "id": id,
+                    "parent": span.parent,
+                    "name": span.name,
+                    "target": span.target,
+                    "start_ms": millis(span.start),
+                    "duration_ms": span.end.map(|end| millis(end - span.start)),
+                    "fields": span.fields,
+                    "events": span.events,
+                })
+            })
+            .collect();
+        json!({
+            "started": data.started_at.to_rfc3339_opts(SecondsFormat::Millis, true),
+            "duration_ms": millis(data.started.elapsed()),
+            "spans": spans,
+            "events": data.events,
+            "output": data.output,
+            "outbound": data.outbound,
+            "instances": data.instances,
+            "truncated": data.truncated,
+        })
+    }
+
+    fn add_span(
+        &self,
+        metadata: &Metadata<'_>,
+        parent: Option<usize>,
+        fields: JsonFields,
+    ) -> usize {
+        let mut data = self.0.lock().unwrap();
+        if data.spans.len() >= MAX_ENTRIES {
+            data.truncated = true;
+            return usize::MAX;
+        }
+        let start = data.started.elapsed();
+        data.spans.push(CapturedSpan {
+            name: metadata.name(),
+            target: metadata.target(),
+            parent,
+            fields: fields.0,
+            start,
+            end: None,
+            events: vec![],
+        });
+        data.spans.len() - 1
+    }
+
+    fn with_span(&self, index: usize, f: impl FnOnce(&mut CapturedSpan, Duration)) {
+        let mut data = self.0.lock().unwrap();
+        let elapsed = data.started.elapsed();
+        if let Some(span) = data.spans.get_mut(index) {
+            f(span, elapsed)
+        }
+    }
+}
+
+impl Drop for CaptureData {
+    fn drop(&mut self) {
+        ACTIVE.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+impl CaptureData {
+    fn offset_ms(&self) -> f64 {
+        millis(self.started.elapsed())
+    }
+}
+
+fn millis(duration: Duration) -> f64 {
+    duration.as_secs_f64() * 1000.0
+}
+
+/// Carries the capture of the current task, if any, into `fut`, which may then be spawned as a
+/// task of its own.
+pub fn in_current_capture<F: Future>(fut: F) -> impl Future<Output = F::Output> {
+    let capture = CURRENT.try_with(Capture::clone).ok();
+    async move {
+        match capture {
+            Some(capture) => CURRENT.scope(capture, fut).await,
+            None => fut.await,
+        }
+    }
+}
+
+/// Runs `f` on the capture of the request being handled by the current task, if any.
+fn with_current(f: impl FnOnce(&mut CaptureData)) {
+    if !active() {
+        return;
+    }
+    _ = CURRENT.try_with(|capture| f(&mut capture.0.lock().unwrap()));
+}
+
+/// Records output written by a component.
+pub fn record_output(component_id: &str, stream: &str, bytes: &[u8]) {
+    with_current(|data| {
+        if data.output_len + bytes.len() > MAX_OUTPUT {
+            data.truncated = true;
+            return;
+        }
+        data.output_len += bytes.len();
+        let entry = json!({
+            "offset_ms": data.offset_ms(),
+            "component": component_id,
+            "stream": stream,
+            "text": String::from_utf8_lossy(bytes),
+        });
+        data.output.push(entry);
+    })
+}
+
+/// Records whether a component was allowed to connect to `url`.
+pub fn record_outbound(url: &str, allowed: bool) {
+    with_current(|data| {
+        let entry = json!({
+            "offset_ms": data.offset_ms(),
+            "url": url,
+            "allowed": allowed,
+        });
+        data.outbound.push(entry);
+    })
+}
+
+pub(crate) fn record_instantiation(component_id: &str, duration: Duration) {
+    with_current(|data| {
+        let entry = json!({
+            "offset_ms": data.offset_ms(),
+            "component": component_id,
+            "instantiation_ms": millis(duration),
+        });
+        data.instances.push(entry);
+    })
+}
+
+pub(crate) fn record_memory(component_id: &str, bytes: u64) {
+    with_current(|data| {
+        let entry = json!({
+            "offset_ms": data.offset_ms(),
+            "component": component_id,
+            "memory_bytes": bytes,
+        });
+        data.instances.push(entry);
+    })
+}
+
+/// The capture a span is recorded in, and its index there.
+#[derive(Clone)]
+struct CapturedSpanRef {
+    capture: Capture,
+    index: usize,
+}
+
+/// A tracing layer recording spans and events in the capture they belong to: that of their
+/// parent span, or else that of the current task. It should be filtered with [`active`].
+pub(crate) struct CaptureLayer;
+
+impl<S: Subscriber + for<'a> LookupSpan<'a>> Layer<S> for CaptureLayer {
+    fn on_new_span(&self, attrs: &Attributes<'_>, id: &Id, ctx: Context<'_, S>) {
+        let Some(span) = ctx.span(id) else {
+            return;
+        };
+        let parent = span
+            .parent()
+            .and_then(|parent| parent.extensions().get::<CapturedSpanRef>().cloned());
+        let (capture, parent) = match parent {
+            Some(parent) => (parent.capture, Some(parent.index)),
+            None => match CURRENT.try_with(Capture::clone) {
+                Ok(capture) => (capture, None),
+                Err(_) => return,
+            },
+        };
+        let mut fields = JsonFields::default();
+        attrs.record(&mut fields);
+        let index = capture.add_span(attrs.metadata(), parent, fields);
+        span.extensions_mut()
+            .insert(CapturedSpanRef { capture, index });
+    }
+
+    fn on_record(&self, id: &Id, values: &Record<'_>, ctx: Context<'_, S>) {
+        let Some(span) = ctx.span(id) else {
+            return;
+        };
+        if let Some(captured) = span.extensions().get::<CapturedSpanRef>() {
+            let mut fields = JsonFields::default();
+            values.record(&mut fields);
+            captured
+                .capture
+                .with_span(captured.index, |span, _| span.fields.extend(fields.0));
+        }
+    }
+
+    fn on_event(&self, event: &Event<'_>, ctx: Context<'_, S>) {
+        let mut fields = JsonFields::default();
+        event.record(&mut fields);
+        let metadata = event.metadata();
+        let entry = |offset: Duration| {
+            json!({
+                "offset_ms": millis(offset),
+                "level": metadata.level().as_str(),
+                "target": metadata.target(),
+                "fields": fields.0,
+            })
+        };
+        let captured = ctx
+            .event_span(event)
+            .and_then(|span| span.extensions().get::<CapturedSpanRef>().cloned());
+        match captured {
+            Some(captured) => captured.capture.with_span(captured.index, |span, elapsed| {
+                span.events.push(entry(elapsed))
+            }),
+            None => with_current(|data| {
+                if data.events.len() >= MAX_ENTRIES {
+                    data.truncated = true;
+                } else {
+                    let elapsed = data.started.elapsed();
+                    data.events.push(entry(elapsed));
+                }
+            }),
+        }
+    }
+
+    fn on_close(&self, id: Id, ctx: Context<'_, S>) {
+        let Some(span) = ctx.span(&id) else {
+            return;
+        };
+        if let Some(captured) = span.extensions().get::<CapturedSpanRef>() {
+            captured
+                .capture
+                .with_span(captured.index, |span, elapsed| span.end = Some(elapsed));
+        }
+    }
+}
+
+/// The filter for [`CaptureLayer`]: spans and events are only recorded while a capture is in
+/// progress, and at most at debug level.
+pub(crate) fn capture_filter(metadata: &Metadata<'_>) -> bool {
+    active() && *metadata.level() <= Level::DEBUG
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tracing_subscriber::prelude::*;
+
+    #[tokio::test]
+    async fn spans_output_and_decisions_are_captured() {
+        let subscriber = tracing_subscriber::registry().with(CaptureLayer);
+        let _guard = tracing::subscriber::set_default(subscriber);
+
+        let capture = Capture::new();
+        capture
+            .scope(async {
+                let outer = tracing::info_span!("outer", component = "hello");
+                let _outer = outer.enter();
+                tracing::info_span!("inner").in_scope(|| tracing::info!("working"));
+                record_output("hello", "stdout", b"hi\n");
+                record_outbound("https://example.com:443", false);
+            })
+            .await;
+        // Nothing outside the scope is captured
+        record_outbound("https://example.org:443", true);
+
+        let bundle = capture.bundle();
+        let spans = bundle["spans"].as_array().unwrap();
+        assert_eq!(spans.len(), 2);
+        assert_eq!(spans[0]["name"], "outer");
+        assert_eq!(spans[0]["fields"]["component"], "hello");
+        assert_eq!(spans[1]["parent"], 0);
+        assert_eq!(spans[1]["events"][0]["fields"]["message"], "working");
+        assert!(spans[1]["duration_ms"].is_number());
+        assert_eq!(bundle["output"][0]["text"], "hi\n");
+        assert_eq!(bundle["outbound"].as_array().unwrap().len(), 1);
+        assert_eq!(bundle["outbound"][0]["allowed"], false);
+    }
+}
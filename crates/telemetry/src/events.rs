@@ -0,0 +1,126 @@
+//! Hooks for embedders into events in the execution of components.
+//!
+//! Embedders and plugins [register](register) a [`RuntimeEventHook`] to be told when instances
+//! are created, when handlers start and complete, when a guest traps or runs past its store
+//! deadline, and when a component makes an outbound connection. This is enough to implement
+//! custom telemetry, metering or policy without patching trigger code.
+//!
+//! Hooks are process-wide, and are called synchronously on the thread and task on which the
+//! event happens, so they can use e.g. [`current_trace_id`](crate::current_trace_id) to relate
+//! events to a request. They must be quick: anything slow should be handed off to another
+//! thread or task.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+
+static REGISTERED: AtomicBool = AtomicBool::new(false);
+static HOOKS: RwLock<Vec<Arc<dyn RuntimeEventHook>>> = RwLock::new(Vec::new());
+
+/// An event in the execution of a component.
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum RuntimeEvent<'a> {
+    /// An instance of a component was created.
+    InstanceCreated {
+        trigger_type: &'a str,
+        component_id: &'a str,
+        /// How long instantiation took.
+        duration: Duration,
+    },
+    /// A trigger started handling an event with a component.
+    HandlerStarted {
+        trigger_type: &'a str,
+        component_id: &'a str,
+    },
+    /// A trigger finished handling an event with a component, successfully or not.
+    HandlerCompleted {
+        trigger_type: &'a str,
+        component_id: &'a str,
+        /// How long the handler ran for.
+        duration: Duration,
+    },
+    /// A guest trapped. The error can be downcast to a `wasmtime::Trap` for its code.
+    Trapped {
+        trigger_type: &'a str,
+        component_id: &'a str,
+        error: &'a anyhow::Error,
+    },
+    /// A guest was interrupted because it ran past the deadline set on its store.
+    DeadlineExceeded {
+        trigger_type: &'a str,
+        component_id: &'a str,
+    },
+    /// A component tried to connect to `url`, given as `scheme://host:port` or as `self` for
+    /// the app itself, and the allowed-hosts checker decided whether it may.
+    OutboundCall { url: &'a str, allowed: bool },
+}
+
+/// A callback for [`RuntimeEvent`]s.
+pub trait RuntimeEventHook: Send + Sync + 'static {
+    fn on_event(&self, event: &RuntimeEvent<'_>);
+}
+
+impl<F> RuntimeEventHook for F
+where
+    F: Fn(&RuntimeEvent<'_>) + Send + Sync + 'static,
+{
+    fn on_event(&self, event: &RuntimeEvent<'_>) {
+        self(event)
+    }
+}
+
+/// Registers a hook to be called with every subsequent runtime event.
+pub fn register(hook: impl RuntimeEventHook) {
+    HOOKS.write().unwrap().push(Arc::new(hook));
+    REGISTERED.store(true, Ordering::Release);
+}
+
+/// Whether any hooks are registered, so that callers can skip work only needed for events.
+pub fn enabled() -> bool {
+    REGISTERED.load(Ordering::Acquire)
+}
+
+/// Calls every registered hook with the event.
+pub fn emit(event: RuntimeEvent<'_>) {
+    if !enabled() {
+        return;
+    }
+    // Hooks are called without the lock held, so that a hook may register another.
+    let hooks = match HOOKS.read() {
+        Ok(hooks) => hooks.clone(),
+        Err(_) => return,
+    };
+    for hook in hooks {
+        hook.on_event(&event);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use super::*;
+
+    #[test]
+    fn registered_hooks_see_events() {
+        let seen = Arc::new(Mutex::new(vec![]));
+        let hook_seen = seen.clone();
+        register(move |event: &RuntimeEvent<'_>| {
+            if let RuntimeEvent::HandlerStarted { component_id, .. } = event {
+                hook_seen.lock().unwrap().push(component_id.to_string());
+            }
+        });
+
+        emit(RuntimeEvent::HandlerStarted {
+            trigger_type: "http",
+            component_id: "hello",
+        });
+        emit(RuntimeEvent::OutboundCall {
+            url: "https://example.com:443",
+            allowed: true,
+        });
+
+        assert_eq!(*seen.lock().unwrap(), ["hello"]);
+    }
+}
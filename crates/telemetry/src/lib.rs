@@ -1,12 +1,21 @@
 use std::io::IsTerminal;
+use std::sync::OnceLock;
 
 use opentelemetry_sdk::propagation::TraceContextPropagator;
-use tracing_subscriber::{fmt, prelude::*, registry, EnvFilter, Layer};
+use tracing_subscriber::{filter, fmt, prelude::*, registry, reload, EnvFilter, Layer};
 
+pub mod capture;
 pub mod detector;
+pub mod events;
+mod log_level;
+pub mod logs;
+pub mod metrics;
 mod propagation;
+pub mod sinks;
 mod traces;
 
+pub use log_level::{log_filter, set_log_filter};
+pub use propagation::current_trace_id;
 pub use propagation::extract_trace_context;
 pub use propagation::inject_trace_context;
 
@@ -14,18 +23,33 @@ pub use propagation::inject_trace_context;
 ///
 /// Under the hood this involves initializing a [tracing::Subscriber] with multiple [Layer]s. One
 /// [Layer] emits [tracing] events to stderr, and another sends spans to an OTEL collector.
+/// Component metrics, recorded with the functions in [metrics], are also sent to the OTEL
+/// collector when a metrics endpoint is configured. Events are also forwarded to any log
+/// [sinks] installed later.
 ///
-/// Configuration is pulled from the environment.
+/// Configuration is pulled from the environment. The filter of the stderr logs, initially
+/// from `RUST_LOG`, can be changed later with [set_log_filter].
 pub fn init(spin_version: String) -> anyhow::Result<ShutdownGuard> {
-    // This layer will print all tracing library log messages to stderr.
+    _ = SPIN_VERSION.set(spin_version.clone());
+
+    // This layer will print all tracing library log messages to stderr, as JSON lines if
+    // requested by the log format. Its filter can be replaced while Spin runs.
+    let (fmt_filter, fmt_filter_handle) = reload::Layer::new(log_level::with_fixed_directives(
+        EnvFilter::from_default_env(),
+    )?);
+    log_level::install(
+        std::env::var("RUST_LOG").unwrap_or_default(),
+        fmt_filter_handle,
+    );
     let fmt_layer = fmt::layer()
         .with_writer(std::io::stderr)
-        .with_ansi(std::io::stderr().is_terminal())
-        .with_filter(
-            EnvFilter::from_default_env()
-                .add_directive("wasmtime_wasi_http=warn".parse()?)
-                .add_directive("watchexec=off".parse()?),
-        );
+        .with_ansi(std::io::stderr().is_terminal() && !logs::log_format().is_json());
+    let fmt_layer = if logs::log_format().is_json() {
+        fmt_layer.event_format(logs::JsonEventFormat).boxed()
+    } else {
+        fmt_layer.boxed()
+    }
+    .with_filter(fmt_filter);
 
     // We only want to build the otel layer if the user passed some endpoint configuration and it wasn't explicitly disabled.
     let build_otel_layer = !otel_sdk_disabled() && otel_enabled();
@@ -33,19 +57,39 @@ pub fn init(spin_version: String) -> anyhow::Result<ShutdownGuard> {
         // In this case we want to set the error handler to log errors to the tracing layer.
         opentelemetry::global::set_error_handler(otel_error_handler)?;
 
-        Some(traces::otel_tracing_layer(spin_version)?)
+        Some(traces::otel_tracing_layer(spin_version.clone())?)
+    } else {
+        None
+    };
+
+    let meter_provider = if build_otel_layer && metrics::otel_metrics_enabled() {
+        Some(metrics::init_otel_metrics(spin_version)?)
     } else {
         None
     };
 
     // Build a registry subscriber with the layers we want to use.
-    registry().with(otel_layer).with(fmt_layer).init();
+    registry()
+        .with(otel_layer)
+        .with(fmt_layer)
+        .with(capture::CaptureLayer.with_filter(filter::filter_fn(capture::capture_filter)))
+        .with(sinks::SinkLayer.with_filter(filter::filter_fn(|metadata| {
+            metadata.is_event() && sinks::enabled(*metadata.level(), false)
+        })))
+        .init();
 
     // Used to propagate trace information in the standard W3C TraceContext format. Even if the otel
     // layer is disabled we still want to propagate trace context.
     opentelemetry::global::set_text_map_propagator(TraceContextPropagator::new());
 
-    Ok(ShutdownGuard)
+    Ok(ShutdownGuard { meter_provider })
+}
+
+static SPIN_VERSION: OnceLock<String> = OnceLock::new();
+
+/// The version of Spin given to [init], which describes the process in exported telemetry.
+fn spin_version() -> String {
+    SPIN_VERSION.get().cloned().unwrap_or_default()
 }
 
 /// Returns a boolean indicating if the OTEL layer should be enabled.
@@ -87,11 +131,21 @@ fn otel_error_handler(err: opentelemetry::global::Error) {
 ///
 /// Shutdown of the open telemetry services will happen on `Drop`.
 #[must_use]
-pub struct ShutdownGuard;
+pub struct ShutdownGuard {
+    meter_provider: Option<opentelemetry_sdk::metrics::SdkMeterProvider>,
+}
 
 impl Drop for ShutdownGuard {
     fn drop(&mut self) {
+        // Write out any logs still queued for the log sinks.
+        sinks::shutdown();
         // Give tracer provider a chance to flush any pending traces.
         opentelemetry::global::shutdown_tracer_provider();
+        // Likewise export any metrics recorded since the last periodic export.
+        if let Some(meter_provider) = self.meter_provider.take() {
+            if let Err(err) = meter_provider.shutdown() {
+                tracing::debug!(?err, "Failed to shut down OpenTelemetry meter provider");
+            }
+        }
     }
 }
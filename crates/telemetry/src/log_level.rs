@@ -0,0 +1,54 @@
+//! Changing which host logs are written to stderr while Spin runs, as if
+//! `RUST_LOG` had been set to new directives, e.g. from a trigger's admin API.
+
+use std::sync::{Mutex, OnceLock};
+
+use anyhow::{Context, Result};
+use tracing::level_filters::LevelFilter;
+use tracing_subscriber::{reload, EnvFilter};
+
+static LOG_FILTER: OnceLock<LogFilter> = OnceLock::new();
+
+/// The filter of the stderr log layer, which can be replaced.
+struct LogFilter {
+    directives: Mutex<String>,
+    reload: Box<dyn Fn(EnvFilter) -> Result<(), reload::Error> + Send + Sync>,
+}
+
+/// Adds the directives which apply whatever `RUST_LOG` says.
+pub(crate) fn with_fixed_directives(filter: EnvFilter) -> Result<EnvFilter> {
+    Ok(filter
+        .add_directive("wasmtime_wasi_http=warn".parse()?)
+        .add_directive("watchexec=off".parse()?))
+}
+
+/// Makes the stderr log layer's filter replaceable through `handle`.
+pub(crate) fn install<S: 'static>(directives: String, handle: reload::Handle<EnvFilter, S>) {
+    _ = LOG_FILTER.set(LogFilter {
+        directives: Mutex::new(directives),
+        reload: Box::new(move |filter| handle.reload(filter)),
+    });
+}
+
+/// Returns the directives filtering the logs written to stderr, in the
+/// `RUST_LOG` format. Empty if only errors are logged.
+pub fn log_filter() -> Option<String> {
+    Some(LOG_FILTER.get()?.directives.lock().unwrap().clone())
+}
+
+/// Filters the logs written to stderr with `directives`, in the `RUST_LOG`
+/// format, e.g. `spin_trigger_http=debug,info`. Empty directives log only
+/// errors.
+pub fn set_log_filter(directives: &str) -> Result<()> {
+    let log_filter = LOG_FILTER
+        .get()
+        .context("Telemetry was not initialized, so the log filter can't be changed")?;
+    let filter = EnvFilter::builder()
+        .with_default_directive(LevelFilter::ERROR.into())
+        .parse(directives)
+        .with_context(|| format!("Invalid log filter {directives:?}"))?;
+    (log_filter.reload)(with_fixed_directives(filter)?)
+        .context("Failed to change the log filter")?;
+    *log_filter.directives.lock().unwrap() = directives.to_owned();
+    Ok(())
+}
@@ -0,0 +1,211 @@
+//! The format of Spin's host logs.
+//!
+//! With the JSON format, every log line Spin writes (tracing events, HTTP access logs and
+//! followed component output) is a single JSON object with the fields `timestamp`, `level`
+//! and `message`, and `component` and `trace_id` where they are known. The format is passed
+//! on to trigger processes through the [`LOG_FORMAT_ENV`] environment variable.
+
+use std::fmt;
+use std::sync::OnceLock;
+
+use chrono::{SecondsFormat, Utc};
+use serde_json::{Map, Value};
+use tracing::field::{Field, Visit};
+use tracing::{Event, Subscriber};
+use tracing_subscriber::fmt::{format::Writer, FmtContext, FormatEvent, FormatFields};
+use tracing_subscriber::registry::LookupSpan;
+
+/// The environment variable through which the log format is passed to subprocesses.
+pub const LOG_FORMAT_ENV: &str = "SPIN_LOG_FORMAT";
+
+static LOG_FORMAT: OnceLock<LogFormat> = OnceLock::new();
+
+/// The format of host log lines.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum LogFormat {
+    /// Human-readable text
+    #[default]
+    Text,
+    /// One JSON object per line
+    Json,
+}
+
+impl LogFormat {
+    pub fn is_json(self) -> bool {
+        self == Self::Json
+    }
+}
+
+impl std::str::FromStr for LogFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "text" => Ok(Self::Text),
+            "json" => Ok(Self::Json),
+            _ => Err(format!(
+                "unknown log format '{s}': expected 'text' or 'json'"
+            )),
+        }
+    }
+}
+
+impl fmt::Display for LogFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Text => "text",
+            Self::Json => "json",
+        })
+    }
+}
+
+/// The log format for the process, as given by the [`LOG_FORMAT_ENV`] environment variable
+/// when it was first asked for.
+pub fn log_format() -> LogFormat {
+    *LOG_FORMAT.get_or_init(|| {
+        std::env::var(LOG_FORMAT_ENV)
+            .ok()
+            .and_then(|format| format.parse().ok())
+            .unwrap_or_default()
+    })
+}
+
+/// A JSON log line with the fields common to all of Spin's logs.
+#[derive(Debug)]
+pub struct JsonLogLine(Map<String, Value>);
+
+impl JsonLogLine {
+    /// Starts a line with the current time, the given level (such as "INFO") and message.
+    pub fn new(level: &str, message: impl Into<String>) -> Self {
+        let mut object = Map::new();
+        object.insert(
+            "timestamp".into(),
+            Utc::now()
+                .to_rfc3339_opts(SecondsFormat::Millis, true)
+                .into(),
+        );
+        object.insert("level".into(), level.into());
+        object.insert("message".into(), message.into().into());
+        Self(object)
+    }
+
+    /// Sets the component the line is about, if known.
+    pub fn component(self, component: Option<&str>) -> Self {
+        match component {
+            Some(component) => self.field("component", component),
+            None => self,
+        }
+    }
+
+    /// Sets the trace the line belongs to, if any.
+    pub fn trace_id(self, trace_id: Option<String>) -> Self {
+        match trace_id {
+            Some(trace_id) => self.field("trace_id", trace_id),
+            None => self,
+        }
+    }
+
+    /// Sets any other field.
+    pub fn field(mut self, name: &str, value: impl Into<Value>) -> Self {
+        self.0.insert(name.into(), value.into());
+        self
+    }
+}
+
+impl fmt::Display for JsonLogLine {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // Strings are escaped, so this is always a single line.
+        write!(f, "{}", Value::Object(self.0.clone()))
+    }
+}
+
+/// Formats tracing events as [`JsonLogLine`]s. The `component` field of an event, if any,
+/// becomes the line's component, and its other fields are kept alongside.
+pub(crate) struct JsonEventFormat;
+
+impl<S, N> FormatEvent<S, N> for JsonEventFormat
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+    N: for<'a> FormatFields<'a> + 'static,
+{
+    fn format_event(
+        &self,
+        _ctx: &FmtContext<'_, S, N>,
+        mut writer: Writer<'_>,
+        event: &Event<'_>,
+    ) -> fmt::Result {
+        let mut fields = JsonFields::default();
+        event.record(&mut fields);
+        let metadata = event.metadata();
+        let message = match fields.0.remove("message") {
+            Some(Value::String(message)) => message,
+            Some(other) => other.to_string(),
+            None => String::new(),
+        };
+        let component = match fields.0.remove("component") {
+            Some(Value::String(component)) => Some(component),
+            _ => None,
+        };
+        let mut line = JsonLogLine::new(metadata.level().as_str(), message)
+            .component(component.as_deref())
+            .trace_id(crate::current_trace_id())
+            .field("target", metadata.target());
+        for (name, value) in fields.0 {
+            line = line.field(&name, value);
+        }
+        writeln!(writer, "{line}")
+    }
+}
+
+/// Collects the fields of a tracing event as JSON values.
+#[derive(Default)]
+pub(crate) struct JsonFields(pub(crate) Map<String, Value>);
+
+impl Visit for JsonFields {
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        self.0
+            .insert(field.name().into(), format!("{value:?}").into());
+    }
+
+    fn record_str(&mut self, field: &Field, value: &str) {
+        self.0.insert(field.name().into(), value.into());
+    }
+
+    fn record_i64(&mut self, field: &Field, value: i64) {
+        self.0.insert(field.name().into(), value.into());
+    }
+
+    fn record_u64(&mut self, field: &Field, value: u64) {
+        self.0.insert(field.name().into(), value.into());
+    }
+
+    fn record_f64(&mut self, field: &Field, value: f64) {
+        self.0.insert(field.name().into(), value.into());
+    }
+
+    fn record_bool(&mut self, field: &Field, value: bool) {
+        self.0.insert(field.name().into(), value.into());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn json_log_lines_have_common_fields() {
+        let line = JsonLogLine::new("INFO", "first\nsecond")
+            .component(Some("hello"))
+            .trace_id(None)
+            .field("stream", "stdout")
+            .to_string();
+        assert!(!line.contains('\n'));
+        let value: Value = serde_json::from_str(&line).unwrap();
+        assert_eq!(value["level"], "INFO");
+        assert_eq!(value["message"], "first\nsecond");
+        assert_eq!(value["component"], "hello");
+        assert_eq!(value["stream"], "stdout");
+        assert!(value.get("trace_id").is_none());
+        assert!(chrono::DateTime::parse_from_rfc3339(value["timestamp"].as_str().unwrap()).is_ok());
+    }
+}
@@ -0,0 +1,429 @@
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::sync::{Mutex, OnceLock};
+use std::time::Duration;
+
+use opentelemetry::metrics::{Counter, Histogram, Unit};
+use opentelemetry::KeyValue;
+use opentelemetry_otlp::WithExportConfig;
+use opentelemetry_otlp::{OTEL_EXPORTER_OTLP_ENDPOINT, OTEL_EXPORTER_OTLP_METRICS_ENDPOINT};
+use opentelemetry_sdk::metrics::SdkMeterProvider;
+
+use crate::traces::{fix_endpoint_bug, spin_resource};
+
+/// Constructs a meter provider which periodically sends metrics to an OTEL collector, and
+/// installs it as the global meter provider.
+///
+/// Like the tracing layer, it pulls OTEL configuration from the environment, including
+/// `OTEL_METRIC_EXPORT_INTERVAL` for how often metrics are exported.
+pub(crate) fn init_otel_metrics(spin_version: String) -> anyhow::Result<SdkMeterProvider> {
+    let mut exporter = opentelemetry_otlp::new_exporter().http();
+    if let Some(endpoint) = fix_endpoint_bug(OTEL_EXPORTER_OTLP_METRICS_ENDPOINT, "v1/metrics") {
+        exporter = exporter.with_endpoint(endpoint);
+    }
+
+    let provider = opentelemetry_otlp::new_pipeline()
+        .metrics(opentelemetry_sdk::runtime::Tokio)
+        .with_exporter(exporter)
+        .with_resource(spin_resource(spin_version))
+        .build()?;
+    opentelemetry::global::set_meter_provider(provider.clone());
+    Ok(provider)
+}
+
+/// Returns a boolean indicating if metrics should be exported, which is the case if either
+/// `OTEL_EXPORTER_OTLP_ENDPOINT` or `OTEL_EXPORTER_OTLP_METRICS_ENDPOINT` is set and not empty.
+pub(crate) fn otel_metrics_enabled() -> bool {
+    [
+        OTEL_EXPORTER_OTLP_ENDPOINT,
+        OTEL_EXPORTER_OTLP_METRICS_ENDPOINT,
+    ]
+    .iter()
+    .any(|key| std::env::var_os(key).is_some_and(|val| !val.is_empty()))
+}
+
+/// The instruments Spin records component metrics with. Until a meter provider is installed
+/// these are no-ops.
+struct Instruments {
+    executions: Counter<u64>,
+    execution_duration: Histogram<f64>,
+    errors: Counter<u64>,
+    memory: Histogram<u64>,
+    instantiation_duration: Histogram<f64>,
+    compilation_duration: Histogram<f64>,
+    link_duration: Histogram<f64>,
+}
+
+fn instruments() -> &'static Instruments {
+    static INSTRUMENTS: OnceLock<Instruments> = OnceLock::new();
+    INSTRUMENTS.get_or_init(|| {
+        let meter = opentelemetry::global::meter("spin");
+        Instruments {
+            executions: meter
+                .u64_counter("spin.component.executions")
+                .with_description("Number of executions of a component, such as HTTP requests")
+                .init(),
+            execution_duration: meter
+                .f64_histogram("spin.component.execution.duration")
+                .with_description("Time taken by executions of a component")
+                .with_unit(Unit::new("s"))
+                .init(),
+            errors: meter
+                .u64_counter("spin.component.errors")
+                .with_description("Number of executions of a component which failed")
+                .init(),
+            memory: meter
+                .u64_histogram("spin.component.memory")
+                .with_description("Linear memory used by an instance of a component")
+                .with_unit(Unit::new("By"))
+                .init(),
+            instantiation_duration: meter
+                .f64_histogram("spin.component.instantiation.duration")
+                .with_description("Time taken to instantiate a component")
+                .with_unit(Unit::new("s"))
+                .init(),
+            compilation_duration: meter
+                .f64_histogram("spin.component.compilation.duration")
+                .with_description("Time taken to compile a component, or load it from the cache")
+                .with_unit(Unit::new("s"))
+                .init(),
+            link_duration: meter
+                .f64_histogram("spin.component.link.duration")
+                .with_description("Time taken to link the imports of a compiled component")
+                .with_unit(Unit::new("s"))
+                .init(),
+        }
+    })
+}
+
+fn component_attributes(trigger_type: &str, component_id: &str) -> [KeyValue; 2] {
+    [
+        KeyValue::new("spin.trigger", trigger_type.to_owned()),
+        KeyValue::new("spin.component", component_id.to_owned()),
+    ]
+}
+
+/// Records a finished execution of a component, such as the handling of an HTTP request.
+pub fn record_execution(trigger_type: &str, component_id: &str, duration: Duration) {
+    let attributes = component_attributes(trigger_type, component_id);
+    let instruments = instruments();
+    instruments.executions.add(1, &attributes);
+    instruments
+        .execution_duration
+        .record(duration.as_secs_f64(), &attributes);
+    update_prometheus(trigger_type, component_id, |metrics| {
+        metrics.executions += 1;
+        metrics.execution_duration.observe(duration.as_secs_f64());
+    });
+}
+
+/// Records a failed execution of a component.
+pub fn record_error(trigger_type: &str, component_id: &str) {
+    instruments()
+        .errors
+        .add(1, &component_attributes(trigger_type, component_id));
+    update_prometheus(trigger_type, component_id, |metrics| metrics.errors += 1);
+}
+
+/// Records the linear memory used by an instance of a component after an execution.
+pub fn record_memory(trigger_type: &str, component_id: &str, bytes: u64) {
+    crate::capture::record_memory(component_id, bytes);
+    instruments()
+        .memory
+        .record(bytes, &component_attributes(trigger_type, component_id));
+    update_prometheus(trigger_type, component_id, |metrics| {
+        metrics.memory.observe(bytes as f64)
+    });
+}
+
+/// Records the time taken to instantiate a component.
+pub fn record_instantiation(trigger_type: &str, component_id: &str, duration: Duration) {
+    crate::capture::record_instantiation(component_id, duration);
+    instruments().instantiation_duration.record(
+        duration.as_secs_f64(),
+        &component_attributes(trigger_type, component_id),
+    );
+    update_prometheus(trigger_type, component_id, |metrics| {
+        metrics
+            .instantiation_duration
+            .observe(duration.as_secs_f64())
+    });
+}
+
+/// Records the time taken to compile a component, or to load it from the cache.
+pub fn record_compilation(trigger_type: &str, component_id: &str, duration: Duration) {
+    instruments().compilation_duration.record(
+        duration.as_secs_f64(),
+        &component_attributes(trigger_type, component_id),
+    );
+    update_prometheus(trigger_type, component_id, |metrics| {
+        metrics.compilation_duration.observe(duration.as_secs_f64())
+    });
+}
+
+/// Records the time taken to link the imports of a compiled component.
+pub fn record_linking(trigger_type: &str, component_id: &str, duration: Duration) {
+    instruments().link_duration.record(
+        duration.as_secs_f64(),
+        &component_attributes(trigger_type, component_id),
+    );
+    update_prometheus(trigger_type, component_id, |metrics| {
+        metrics.link_duration.observe(duration.as_secs_f64())
+    });
+}
+
+/// The metrics kept for Prometheus scrapes, once enabled with [`enable_prometheus`].
+static PROMETHEUS: OnceLock<Mutex<PrometheusRegistry>> = OnceLock::new();
+
+/// Starts keeping the recorded metrics in process, so that they can be served in the
+/// Prometheus text format by [`prometheus_text`]. Metrics recorded before this is called are
+/// not included.
+pub fn enable_prometheus() {
+    PROMETHEUS.get_or_init(Default::default);
+}
+
+/// Returns the recorded metrics in the Prometheus text exposition format, or `None` if
+/// [`enable_prometheus`] has not been called.
+pub fn prometheus_text() -> Option<String> {
+    PROMETHEUS
+        .get()
+        .map(|registry| registry.lock().unwrap().render())
+}
+
+fn update_prometheus(
+    trigger_type: &str,
+    component_id: &str,
+    f: impl FnOnce(&mut ComponentMetrics),
+) {
+    if let Some(registry) = PROMETHEUS.get() {
+        let mut registry = registry.lock().unwrap();
+        f(registry
+            .components
+            .entry((trigger_type.to_owned(), component_id.to_owned()))
+            .or_default())
+    }
+}
+
+/// Bucket bounds for durations, in seconds.
+const DURATION_BUCKETS: &[f64] = &[
+    0.001, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0,
+];
+/// Bucket bounds for memory, in bytes: 1 MiB to 4 GiB.
+const MEMORY_BUCKETS: &[f64] = &[
+    1048576.0,
+    4194304.0,
+    16777216.0,
+    67108864.0,
+    268435456.0,
+    1073741824.0,
+    4294967296.0,
+];
+
+#[derive(Default)]
+struct PrometheusRegistry {
+    /// Metrics keyed by trigger type and component ID
+    components: BTreeMap<(String, String), ComponentMetrics>,
+}
+
+struct ComponentMetrics {
+    executions: u64,
+    errors: u64,
+    execution_duration: HistogramData,
+    memory: HistogramData,
+    instantiation_duration: HistogramData,
+    compilation_duration: HistogramData,
+    link_duration: HistogramData,
+}
+
+impl Default for ComponentMetrics {
+    fn default() -> Self {
+        Self {
+            executions: 0,
+            errors: 0,
+            execution_duration: HistogramData::new(DURATION_BUCKETS),
+            memory: HistogramData::new(MEMORY_BUCKETS),
+            instantiation_duration: HistogramData::new(DURATION_BUCKETS),
+            compilation_duration: HistogramData::new(DURATION_BUCKETS),
+            link_duration: HistogramData::new(DURATION_BUCKETS),
+        }
+    }
+}
+
+struct HistogramData {
+    bounds: &'static [f64],
+    /// The number of observations in each bucket, not cumulative
+    counts: Vec<u64>,
+    sum: f64,
+    count: u64,
+}
+
+impl HistogramData {
+    fn new(bounds: &'static [f64]) -> Self {
+        Self {
+            bounds,
+            counts: vec![0; bounds.len()],
+            sum: 0.0,
+            count: 0,
+        }
+    }
+
+    fn observe(&mut self, value: f64) {
+        if let Some(bucket) = self.bounds.iter().position(|bound| value <= *bound) {
+            self.counts[bucket] += 1;
+        }
+        self.sum += value;
+        self.count += 1;
+    }
+}
+
+impl PrometheusRegistry {
+    fn render(&self) -> String {
+        let mut out = String::new();
+        self.render_counter(
+            &mut out,
+            "spin_component_executions_total",
+            "Number of executions of a component, such as HTTP requests",
+            |metrics| metrics.executions,
+        );
+        self.render_counter(
+            &mut out,
+            "spin_component_errors_total",
+            "Number of executions of a component which failed",
+            |metrics| metrics.errors,
+        );
+        self.render_histogram(
+            &mut out,
+            "spin_component_execution_duration_seconds",
+            "Time taken by executions of a component",
+            |metrics| &metrics.execution_duration,
+        );
+        self.render_histogram(
+            &mut out,
+            "spin_component_memory_bytes",
+            "Linear memory used by an instance of a component",
+            |metrics| &metrics.memory,
+        );
+        self.render_histogram(
+            &mut out,
+            "spin_component_instantiation_duration_seconds",
+            "Time taken to instantiate a component",
+            |metrics| &metrics.instantiation_duration,
+        );
+        self.render_histogram(
+            &mut out,
+            "spin_component_compilation_duration_seconds",
+            "Time taken to compile a component, or load it from the cache",
+            |metrics| &metrics.compilation_duration,
+        );
+        self.render_histogram(
+            &mut out,
+            "spin_component_link_duration_seconds",
+            "Time taken to link the imports of a compiled component",
+            |metrics| &metrics.link_duration,
+        );
+        out
+    }
+
+    fn render_counter(
+        &self,
+        out: &mut String,
+        name: &str,
+        help: &str,
+        value: impl Fn(&ComponentMetrics) -> u64,
+    ) {
+        _ = writeln!(out, "# HELP {name} {help}");
+        _ = writeln!(out, "# TYPE {name} counter");
+        for ((trigger, component), metrics) in &self.components {
+            let labels = labels(trigger, component);
+            _ = writeln!(out, "{name}{{{labels}}} {}", value(metrics));
+        }
+    }
+
+    fn render_histogram(
+        &self,
+        out: &mut String,
+        name: &str,
+        help: &str,
+        histogram: impl Fn(&ComponentMetrics) -> &HistogramData,
+    ) {
+        _ = writeln!(out, "# HELP {name} {help}");
+        _ = writeln!(out, "# TYPE {name} histogram");
+        for ((trigger, component), metrics) in &self.components {
+            let histogram = histogram(metrics);
+            if histogram.count == 0 {
+                continue;
+            }
+            let labels = labels(trigger, component);
+            let mut cumulative = 0;
+            for (bound, count) in histogram.bounds.iter().zip(&histogram.counts) {
+                cumulative += count;
+                _ = writeln!(out, "{name}_bucket{{{labels},le=\"{bound}\"}} {cumulative}");
+            }
+            _ = writeln!(
+                out,
+                "{name}_bucket{{{labels},le=\"+Inf\"}} {}",
+                histogram.count
+            );
+            _ = writeln!(out, "{name}_sum{{{labels}}} {}", histogram.sum);
+            _ = writeln!(out, "{name}_count{{{labels}}} {}", histogram.count);
+        }
+    }
+}
+
+fn labels(trigger: &str, component: &str) -> String {
+    format!(
+        "trigger=\"{}\",component=\"{}\"",
+        escape_label(trigger),
+        escape_label(component)
+    )
+}
+
+fn escape_label(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn prometheus_text_has_component_labels_and_cumulative_buckets() {
+        let mut registry = PrometheusRegistry::default();
+        let metrics = registry
+            .components
+            .entry(("http".to_owned(), "hello".to_owned()))
+            .or_default();
+        metrics.executions = 2;
+        metrics.errors = 1;
+        metrics.execution_duration.observe(0.003);
+        metrics.execution_duration.observe(0.2);
+
+        let text = registry.render();
+        assert!(text.contains("# TYPE spin_component_executions_total counter\n"));
+        assert!(text
+            .contains("spin_component_executions_total{trigger=\"http\",component=\"hello\"} 2\n"));
+        assert!(
+            text.contains("spin_component_errors_total{trigger=\"http\",component=\"hello\"} 1\n")
+        );
+        let duration = "spin_component_execution_duration_seconds";
+        assert!(text.contains(&format!(
+            "{duration}_bucket{{trigger=\"http\",component=\"hello\",le=\"0.005\"}} 1\n"
+        )));
+        assert!(text.contains(&format!(
+            "{duration}_bucket{{trigger=\"http\",component=\"hello\",le=\"0.25\"}} 2\n"
+        )));
+        assert!(text.contains(&format!(
+            "{duration}_count{{trigger=\"http\",component=\"hello\"}} 2\n"
+        )));
+        // Histograms without observations are left out
+        assert!(!text.contains("spin_component_memory_bytes_count"));
+    }
+
+    #[test]
+    fn label_values_are_escaped() {
+        assert_eq!(escape_label("a\"b\\c\nd"), "a\\\"b\\\\c\\nd");
+    }
+}
@@ -0,0 +1,300 @@
+//! Forwarding of logs to the sinks configured in the runtime config.
+//!
+//! Sinks receive host logs (tracing events) and component output, one record per line, and ship
+//! them to syslog, an OTLP collector or a rotating file. Each sink has its own minimum level and
+//! writes from its own thread, so that a slow or unreachable sink never holds up the host or a
+//! component: if a sink falls too far behind, records for it are dropped.
+
+mod file;
+mod otlp;
+mod syslog;
+
+use std::cell::Cell;
+use std::sync::mpsc::{sync_channel, SyncSender};
+use std::sync::RwLock;
+use std::thread::JoinHandle;
+
+use chrono::{DateTime, SecondsFormat, Utc};
+use serde_json::Value;
+use tracing::{Event, Level, Subscriber};
+use tracing_subscriber::{layer::Context, Layer};
+
+use crate::logs::{JsonFields, JsonLogLine};
+
+pub use file::{FileSink, FileSinkOptions, TimeRotation};
+pub use otlp::OtlpSink;
+pub use syslog::{Facility, SyslogSink};
+
+/// The number of records a sink may fall behind by before records are dropped.
+const QUEUE_LEN: usize = 4096;
+
+static SINKS: RwLock<Vec<Sink>> = RwLock::new(Vec::new());
+
+thread_local! {
+    // Set on sink threads, so that anything a sink logs is not fed back to the sinks.
+    static IN_SINK: Cell<bool> = const { Cell::new(false) };
+}
+
+/// A line of host log or component output.
+#[derive(Clone, Debug)]
+pub struct LogRecord {
+    pub time: DateTime<Utc>,
+    pub level: Level,
+    pub message: String,
+    /// The component which wrote the line, for component output.
+    pub component: Option<String>,
+    /// The stream (stdout or stderr) of component output, or the target of a host log.
+    pub source: String,
+    pub trace_id: Option<String>,
+}
+
+impl LogRecord {
+    /// The record as a single-line JSON object, in the format of `--log-format json`.
+    pub fn json_line(&self) -> JsonLogLine {
+        let source = if self.component.is_some() {
+            "stream"
+        } else {
+            "target"
+        };
+        JsonLogLine::new(self.level.as_str(), self.message.clone())
+            .field("timestamp", self.timestamp())
+            .component(self.component.as_deref())
+            .trace_id(self.trace_id.clone())
+            .field(source, self.source.clone())
+    }
+
+    /// The record as a line of text.
+    pub fn text_line(&self) -> String {
+        let origin = self.component.as_deref().unwrap_or(&self.source);
+        format!(
+            "{} {:>5} [{origin}] {}",
+            self.timestamp(),
+            self.level,
+            self.message
+        )
+    }
+
+    fn timestamp(&self) -> String {
+        self.time.to_rfc3339_opts(SecondsFormat::Millis, true)
+    }
+}
+
+/// A destination for log records.
+pub trait LogSink: Send + 'static {
+    fn write(&mut self, record: &LogRecord) -> anyhow::Result<()>;
+
+    /// Called when the sink is shut down, after the last record is written.
+    fn flush(&mut self) -> anyhow::Result<()> {
+        Ok(())
+    }
+}
+
+/// Which logs a sink receives.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum LogSources {
+    /// Both host logs and component output
+    #[default]
+    All,
+    /// Only host logs
+    Host,
+    /// Only component output
+    Components,
+}
+
+impl LogSources {
+    fn includes(self, record: &LogRecord) -> bool {
+        match self {
+            Self::All => true,
+            Self::Host => record.component.is_none(),
+            Self::Components => record.component.is_some(),
+        }
+    }
+}
+
+/// A [`LogSink`] running on its own thread, with the filters that pick its records.
+pub struct Sink {
+    name: String,
+    level: Level,
+    sources: LogSources,
+    sender: SyncSender<LogRecord>,
+    thread: JoinHandle<()>,
+}
+
+impl Sink {
+    /// Starts a thread writing the records with at most the verbosity of `level` from `sources`
+    /// to `sink`. The `name` identifies the sink in Spin's own logs.
+    pub fn spawn(
+        name: impl Into<String>,
+        level: Level,
+        sources: LogSources,
+        mut sink: impl LogSink,
+    ) -> anyhow::Result<Self> {
+        let name = name.into();
+        let (sender, receiver) = sync_channel::<LogRecord>(QUEUE_LEN);
+        let thread_name = name.clone();
+        let thread = std::thread::Builder::new()
+            .name(format!("log-sink-{name}"))
+            .spawn(move || {
+                IN_SINK.with(|in_sink| in_sink.set(true));
+                let mut failing = false;
+                for record in receiver {
+                    match sink.write(&record) {
+                        Ok(()) => failing = false,
+                        // Only report the first of a run of failures
+                        Err(err) if !failing => {
+                            failing = true;
+                            tracing::warn!("Failed to write to log sink {thread_name}: {err:#}");
+                        }
+                        Err(_) => (),
+                    }
+                }
+                if let Err(err) = sink.flush() {
+                    tracing::warn!("Failed to flush log sink {thread_name}: {err:#}");
+                }
+            })?;
+        Ok(Self {
+            name,
+            level,
+            sources,
+            sender,
+            thread,
+        })
+    }
+
+    fn accepts(&self, record: &LogRecord) -> bool {
+        record.level <= self.level && self.sources.includes(record)
+    }
+
+    fn send(&self, record: &LogRecord) {
+        // A record the sink has no room for is dropped. This can't be logged here, as logging
+        // takes the lock on the sinks which the caller holds.
+        _ = self.sender.try_send(record.clone());
+    }
+}
+
+/// Starts forwarding logs to the given sinks, in addition to any already installed.
+pub fn install(sinks: impl IntoIterator<Item = Sink>) {
+    SINKS.write().unwrap().extend(sinks);
+}
+
+/// Stops all sinks, waiting for each to write the records already sent to it.
+pub fn shutdown() {
+    let sinks = std::mem::take(&mut *SINKS.write().unwrap());
+    for sink in sinks {
+        drop(sink.sender);
+        if sink.thread.join().is_err() {
+            tracing::warn!("Log sink {} panicked", sink.name);
+        }
+    }
+}
+
+/// Sends a record to every sink which accepts it.
+pub fn forward(record: LogRecord) {
+    if IN_SINK.with(Cell::get) {
+        return;
+    }
+    let Ok(sinks) = SINKS.read() else {
+        return;
+    };
+    for sink in sinks.iter().filter(|sink| sink.accepts(&record)) {
+        sink.send(&record);
+    }
+}
+
+/// Whether any sink would accept a record of the given level from a component or the host.
+pub fn enabled(level: Level, from_component: bool) -> bool {
+    let Ok(sinks) = SINKS.read() else {
+        return false;
+    };
+    sinks.iter().any(|sink| {
+        level <= sink.level
+            && match sink.sources {
+                LogSources::All => true,
+                LogSources::Host => !from_component,
+                LogSources::Components => from_component,
+            }
+    })
+}
+
+/// Forwards a line of a component's output. Lines written to stderr are logged as warnings,
+/// and to stdout as info.
+pub fn forward_component_line(component_id: &str, stream: &str, line: &str) {
+    let level = if stream == "stderr" {
+        Level::WARN
+    } else {
+        Level::INFO
+    };
+    if !enabled(level, true) {
+        return;
+    }
+    forward(LogRecord {
+        time: Utc::now(),
+        level,
+        message: line.to_owned(),
+        component: Some(component_id.to_owned()),
+        source: stream.to_owned(),
+        trace_id: crate::current_trace_id(),
+    });
+}
+
+/// A tracing layer forwarding host log events to the installed sinks. It should be filtered
+/// with [`enabled`], so that events no sink wants are not recorded.
+pub(crate) struct SinkLayer;
+
+impl<S: Subscriber> Layer<S> for SinkLayer {
+    fn on_event(&self, event: &Event<'_>, _ctx: Context<'_, S>) {
+        let metadata = event.metadata();
+        if IN_SINK.with(Cell::get) {
+            return;
+        }
+        let mut fields = JsonFields::default();
+        event.record(&mut fields);
+        let mut message = match fields.0.remove("message") {
+            Some(Value::String(message)) => message,
+            Some(other) => other.to_string(),
+            None => String::new(),
+        };
+        for (name, value) in fields.0 {
+            message.push_str(&format!(" {name}={value}"));
+        }
+        forward(LogRecord {
+            time: Utc::now(),
+            level: *metadata.level(),
+            message,
+            component: None,
+            source: metadata.target().to_owned(),
+            trace_id: crate::current_trace_id(),
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(level: Level, component: Option<&str>) -> LogRecord {
+        LogRecord {
+            time: Utc::now(),
+            level,
+            message: "hello".into(),
+            component: component.map(Into::into),
+            source: "stdout".into(),
+            trace_id: None,
+        }
+    }
+
+    #[test]
+    fn sinks_filter_by_level_and_source() {
+        struct Discard;
+        impl LogSink for Discard {
+            fn write(&mut self, _record: &LogRecord) -> anyhow::Result<()> {
+                Ok(())
+            }
+        }
+        let sink = Sink::spawn("test", Level::WARN, LogSources::Components, Discard).unwrap();
+        assert!(sink.accepts(&record(Level::ERROR, Some("hello"))));
+        assert!(sink.accepts(&record(Level::WARN, Some("hello"))));
+        assert!(!sink.accepts(&record(Level::INFO, Some("hello"))));
+        assert!(!sink.accepts(&record(Level::ERROR, None)));
+    }
+}
@@ -0,0 +1,160 @@
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+
+use super::{LogRecord, LogSink};
+
+/// How often a log file is rotated regardless of its size.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TimeRotation {
+    Hourly,
+    Daily,
+}
+
+impl TimeRotation {
+    /// The period a time falls in: records in different periods go to different files.
+    fn period(self, time: DateTime<Utc>) -> String {
+        match self {
+            Self::Hourly => time.format("%Y-%m-%dT%H").to_string(),
+            Self::Daily => time.format("%Y-%m-%d").to_string(),
+        }
+    }
+}
+
+/// Options for a [`FileSink`].
+#[derive(Clone, Debug)]
+pub struct FileSinkOptions {
+    pub path: PathBuf,
+    /// The size in bytes at which the file is rotated, if any.
+    pub max_file_size: Option<u64>,
+    /// The number of rotated files to keep.
+    pub max_files: usize,
+    pub rotation: Option<TimeRotation>,
+    /// Whether to write JSON lines rather than text.
+    pub json: bool,
+}
+
+/// Writes records to a file which is renamed to `<name>.1` when it is rotated, shifting older
+/// files to `<name>.2` and so on.
+pub struct FileSink {
+    options: FileSinkOptions,
+    file: File,
+    size: u64,
+    // The rotation period of the records in the current file
+    period: Option<String>,
+}
+
+impl FileSink {
+    pub fn open(options: FileSinkOptions) -> Result<Self> {
+        if let Some(dir) = options.path.parent() {
+            std::fs::create_dir_all(dir)
+                .with_context(|| format!("Unable to create log directory {}", dir.display()))?;
+        }
+        let file = open(&options.path)?;
+        let metadata = file.metadata()?;
+        let period = options.rotation.map(|rotation| {
+            let modified = metadata
+                .modified()
+                .map(DateTime::<Utc>::from)
+                .unwrap_or_else(|_| Utc::now());
+            rotation.period(modified)
+        });
+        Ok(Self {
+            size: metadata.len(),
+            options,
+            file,
+            period,
+        })
+    }
+
+    fn rotate(&mut self) -> Result<()> {
+        let path = &self.options.path;
+        let rotated = |n: usize| {
+            let mut name = path.clone().into_os_string();
+            name.push(format!(".{n}"));
+            PathBuf::from(name)
+        };
+        if self.options.max_files == 0 {
+            std::fs::remove_file(path)?;
+        } else {
+            for n in (1..self.options.max_files).rev() {
+                let from = rotated(n);
+                if from.exists() {
+                    std::fs::rename(from, rotated(n + 1))?;
+                }
+            }
+            std::fs::rename(path, rotated(1))?;
+        }
+        self.file = open(path)?;
+        self.size = 0;
+        Ok(())
+    }
+}
+
+impl LogSink for FileSink {
+    fn write(&mut self, record: &LogRecord) -> Result<()> {
+        let line = if self.options.json {
+            record.json_line().to_string()
+        } else {
+            record.text_line()
+        };
+        let len = line.len() as u64 + 1;
+
+        let period = self
+            .options
+            .rotation
+            .map(|rotation| rotation.period(record.time));
+        let new_period = period.is_some() && period != self.period;
+        let full = self
+            .options
+            .max_file_size
+            .is_some_and(|max| self.size + len > max);
+        if self.size > 0 && (new_period || full) {
+            self.rotate()?;
+        }
+        self.period = period;
+
+        writeln!(self.file, "{line}")?;
+        self.size += len;
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        Ok(self.file.flush()?)
+    }
+}
+
+fn open(path: &Path) -> Result<File> {
+    OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .with_context(|| format!("Unable to open log file {}", path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rotation_periods_change_on_the_hour_or_day() {
+        let time = |s| DateTime::parse_from_rfc3339(s).unwrap().with_timezone(&Utc);
+        let before = time("2024-03-01T12:59:59Z");
+        let after = time("2024-03-01T13:00:00Z");
+        assert_ne!(
+            TimeRotation::Hourly.period(before),
+            TimeRotation::Hourly.period(after)
+        );
+        assert_eq!(
+            TimeRotation::Daily.period(before),
+            TimeRotation::Daily.period(after)
+        );
+        assert_ne!(
+            TimeRotation::Daily.period(after),
+            TimeRotation::Daily.period(time("2024-03-02T00:00:00Z"))
+        );
+    }
+}
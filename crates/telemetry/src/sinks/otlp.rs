@@ -0,0 +1,83 @@
+use std::time::SystemTime;
+
+use anyhow::Result;
+use opentelemetry::logs::{AnyValue, LogRecord as OtelLogRecord, Logger as _, Severity};
+use opentelemetry_otlp::{WithExportConfig, OTEL_EXPORTER_OTLP_LOGS_ENDPOINT};
+use opentelemetry_sdk::logs::Logger;
+use tracing::Level;
+
+use super::{LogRecord, LogSink};
+use crate::traces::{fix_endpoint_bug, spin_resource};
+
+/// Sends records to an OTEL collector as OTLP logs.
+pub struct OtlpSink {
+    logger: Logger,
+}
+
+impl OtlpSink {
+    /// Starts exporting logs to `endpoint`, the base URL of an OTLP/HTTP collector, or else to
+    /// the endpoint given by the `OTEL_EXPORTER_OTLP_*` environment variables.
+    ///
+    /// This must be called within a Tokio runtime, on which logs are exported in batches.
+    pub fn new(endpoint: Option<&str>) -> Result<Self> {
+        let mut exporter = opentelemetry_otlp::new_exporter().http();
+        let endpoint = match endpoint {
+            Some(endpoint) => Some(format!("{}/v1/logs", endpoint.trim_end_matches('/'))),
+            None => fix_endpoint_bug(OTEL_EXPORTER_OTLP_LOGS_ENDPOINT, "v1/logs"),
+        };
+        if let Some(endpoint) = endpoint {
+            exporter = exporter.with_endpoint(endpoint);
+        }
+
+        let logger = opentelemetry_otlp::new_pipeline()
+            .logging()
+            .with_exporter(exporter)
+            .with_log_config(
+                opentelemetry_sdk::logs::config()
+                    .with_resource(spin_resource(crate::spin_version())),
+            )
+            .install_batch(opentelemetry_sdk::runtime::Tokio)?;
+        Ok(Self { logger })
+    }
+}
+
+impl LogSink for OtlpSink {
+    fn write(&mut self, record: &LogRecord) -> Result<()> {
+        let mut attributes = vec![];
+        if let Some(component) = &record.component {
+            attributes.push(("spin.component".into(), AnyValue::from(component.clone())));
+            attributes.push(("spin.stream".into(), AnyValue::from(record.source.clone())));
+        } else {
+            attributes.push(("spin.target".into(), AnyValue::from(record.source.clone())));
+        }
+        if let Some(trace_id) = &record.trace_id {
+            attributes.push(("trace_id".into(), AnyValue::from(trace_id.clone())));
+        }
+        self.logger.emit(
+            OtelLogRecord::builder()
+                .with_timestamp(SystemTime::from(record.time))
+                .with_observed_timestamp(SystemTime::now())
+                .with_severity_number(severity(record.level))
+                .with_severity_text(record.level.as_str())
+                .with_body(AnyValue::from(record.message.clone()))
+                .with_attributes(attributes)
+                .build(),
+        );
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        opentelemetry::global::shutdown_logger_provider();
+        Ok(())
+    }
+}
+
+fn severity(level: Level) -> Severity {
+    match level {
+        Level::ERROR => Severity::Error,
+        Level::WARN => Severity::Warn,
+        Level::INFO => Severity::Info,
+        Level::DEBUG => Severity::Debug,
+        _ => Severity::Trace,
+    }
+}
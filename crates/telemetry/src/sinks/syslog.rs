@@ -0,0 +1,243 @@
+use std::io::Write;
+use std::net::{TcpStream, ToSocketAddrs, UdpSocket};
+
+use anyhow::{bail, Context, Result};
+use chrono::SecondsFormat;
+use tracing::Level;
+
+use super::{LogRecord, LogSink};
+
+/// The syslog facilities a sink can log as.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Facility {
+    #[default]
+    User,
+    Daemon,
+    Local(u8),
+}
+
+impl Facility {
+    fn code(self) -> u8 {
+        match self {
+            Self::User => 1,
+            Self::Daemon => 3,
+            Self::Local(n) => 16 + n,
+        }
+    }
+}
+
+impl std::str::FromStr for Facility {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "user" => Ok(Self::User),
+            "daemon" => Ok(Self::Daemon),
+            _ => match s.strip_prefix("local").and_then(|n| n.parse().ok()) {
+                Some(n @ 0..=7) => Ok(Self::Local(n)),
+                _ => bail!("unknown syslog facility '{s}': expected 'user', 'daemon' or 'local0' to 'local7'"),
+            },
+        }
+    }
+}
+
+/// Sends records to a syslog server as RFC 5424 messages.
+///
+/// The MSGID of each message is the ID of the component which wrote it, or `host` for Spin's
+/// own logs.
+pub struct SyslogSink {
+    transport: Transport,
+    facility: Facility,
+    hostname: String,
+    app_name: String,
+    proc_id: u32,
+}
+
+enum Transport {
+    Udp(UdpSocket),
+    // Messages are framed by octet counting (RFC 6587). The connection is re-established if
+    // a write fails.
+    Tcp {
+        address: String,
+        stream: Option<TcpStream>,
+    },
+    #[cfg(unix)]
+    Unix(std::os::unix::net::UnixDatagram),
+}
+
+impl SyslogSink {
+    /// Connects to the syslog server at `address`, which is one of `udp://<host>:<port>`,
+    /// `tcp://<host>:<port>` or `unix://<path>`.
+    pub fn connect(address: &str, facility: Facility, app_name: &str) -> Result<Self> {
+        let transport = if let Some(addr) = address.strip_prefix("udp://") {
+            let remote = addr
+                .to_socket_addrs()?
+                .next()
+                .with_context(|| format!("Unable to resolve syslog address {addr}"))?;
+            let local = if remote.is_ipv4() {
+                "0.0.0.0:0"
+            } else {
+                "[::]:0"
+            };
+            let socket = UdpSocket::bind(local)?;
+            socket.connect(remote)?;
+            Transport::Udp(socket)
+        } else if let Some(addr) = address.strip_prefix("tcp://") {
+            Transport::Tcp {
+                address: addr.to_owned(),
+                stream: Some(
+                    TcpStream::connect(addr)
+                        .with_context(|| format!("Unable to connect to syslog at {addr}"))?,
+                ),
+            }
+        } else if let Some(path) = address.strip_prefix("unix://") {
+            unix_transport(path)?
+        } else {
+            bail!("Invalid syslog address '{address}': expected udp://, tcp:// or unix://");
+        };
+        Ok(Self {
+            transport,
+            facility,
+            hostname: hostname(),
+            app_name: header_field(app_name, 48),
+            proc_id: std::process::id(),
+        })
+    }
+
+    fn message(&self, record: &LogRecord) -> String {
+        let priority = self.facility.code() * 8 + severity(record.level);
+        let timestamp = record.time.to_rfc3339_opts(SecondsFormat::Micros, true);
+        let msg_id = header_field(record.component.as_deref().unwrap_or("host"), 32);
+        format!(
+            "<{priority}>1 {timestamp} {} {} {} {msg_id} - {}",
+            self.hostname, self.app_name, self.proc_id, record.message
+        )
+    }
+}
+
+impl LogSink for SyslogSink {
+    fn write(&mut self, record: &LogRecord) -> Result<()> {
+        let message = self.message(record);
+        match &mut self.transport {
+            Transport::Udp(socket) => {
+                socket.send(message.as_bytes())?;
+            }
+            Transport::Tcp { address, stream } => {
+                let framed = format!("{} {message}", message.len());
+                if let Some(connected) = stream {
+                    if connected.write_all(framed.as_bytes()).is_ok() {
+                        return Ok(());
+                    }
+                }
+                *stream = None;
+                let mut reconnected = TcpStream::connect(address.as_str())
+                    .with_context(|| format!("Unable to connect to syslog at {address}"))?;
+                reconnected.write_all(framed.as_bytes())?;
+                *stream = Some(reconnected);
+            }
+            #[cfg(unix)]
+            Transport::Unix(socket) => {
+                socket.send(message.as_bytes())?;
+            }
+        }
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        if let Transport::Tcp {
+            stream: Some(stream),
+            ..
+        } = &mut self.transport
+        {
+            stream.flush()?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(unix)]
+fn unix_transport(path: &str) -> Result<Transport> {
+    let socket = std::os::unix::net::UnixDatagram::unbound()?;
+    socket
+        .connect(path)
+        .with_context(|| format!("Unable to connect to syslog at {path}"))?;
+    Ok(Transport::Unix(socket))
+}
+
+#[cfg(not(unix))]
+fn unix_transport(path: &str) -> Result<Transport> {
+    bail!("Syslog address unix://{path} is not supported on this platform")
+}
+
+fn severity(level: Level) -> u8 {
+    match level {
+        Level::ERROR => 3,
+        Level::WARN => 4,
+        Level::INFO => 6,
+        _ => 7,
+    }
+}
+
+fn hostname() -> String {
+    let name = std::env::var("HOSTNAME")
+        .ok()
+        .or_else(|| std::fs::read_to_string("/proc/sys/kernel/hostname").ok())
+        .unwrap_or_default();
+    header_field(name.trim(), 255)
+}
+
+/// Makes a value fit for a header field, which is printable ASCII without spaces, or `-` for
+/// none.
+fn header_field(value: &str, max_len: usize) -> String {
+    let field: String = value
+        .chars()
+        .map(|c| if c.is_ascii_graphic() { c } else { '_' })
+        .take(max_len)
+        .collect();
+    if field.is_empty() {
+        "-".into()
+    } else {
+        field
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{DateTime, Utc};
+
+    #[test]
+    fn messages_follow_rfc5424() {
+        let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let address = format!("udp://{}", socket.local_addr().unwrap());
+        let mut sink = SyslogSink::connect(&address, "local3".parse().unwrap(), "spin").unwrap();
+        sink.hostname = "box".into();
+        let record = LogRecord {
+            time: DateTime::parse_from_rfc3339("2024-03-01T12:00:00Z")
+                .unwrap()
+                .with_timezone(&Utc),
+            level: Level::WARN,
+            message: "disk full".into(),
+            component: Some("my component".into()),
+            source: "stderr".into(),
+            trace_id: None,
+        };
+        sink.write(&record).unwrap();
+
+        let mut buf = [0; 512];
+        let len = socket.recv(&mut buf).unwrap();
+        let expected = format!(
+            "<156>1 2024-03-01T12:00:00.000000Z box spin {} my_component - disk full",
+            std::process::id()
+        );
+        assert_eq!(std::str::from_utf8(&buf[..len]).unwrap(), expected);
+    }
+
+    #[test]
+    fn facilities_are_parsed() {
+        assert_eq!("daemon".parse::<Facility>().unwrap(), Facility::Daemon);
+        assert_eq!("local7".parse::<Facility>().unwrap(), Facility::Local(7));
+        assert!("local8".parse::<Facility>().is_err());
+        assert!("kern".parse::<Facility>().is_err());
+    }
+}
@@ -22,25 +22,14 @@ pub(crate) fn otel_tracing_layer(
 ) -> anyhow::Result<
     tracing_subscriber::filter::Filtered<OpenTelemetryLayer<Registry, Tracer>, EnvFilter, Registry>,
 > {
-    let resource = Resource::from_detectors(
-        Duration::from_secs(5),
-        vec![
-            // Set service.name from env OTEL_SERVICE_NAME > env OTEL_RESOURCE_ATTRIBUTES > spin
-            // Set service.version from Spin metadata
-            Box::new(SpinResourceDetector::new(spin_version)),
-            // Sets fields from env OTEL_RESOURCE_ATTRIBUTES
-            Box::new(EnvResourceDetector::new()),
-            // Sets telemetry.sdk{name, language, version}
-            Box::new(TelemetryResourceDetector),
-        ],
-    );
+    let resource = spin_resource(spin_version);
 
     // This will configure the exporter based on the OTEL_EXPORTER_* environment variables. We
     // currently default to using the HTTP exporter but in the future we could select off of the
     // combination of OTEL_EXPORTER_OTLP_PROTOCOL and OTEL_EXPORTER_OTLP_TRACES_PROTOCOL to
     // determine whether we should use http/protobuf or grpc.
     let mut exporter = opentelemetry_otlp::new_exporter().http();
-    if let Some(endpoint) = fix_endpoint_bug() {
+    if let Some(endpoint) = fix_endpoint_bug(OTEL_EXPORTER_OTLP_TRACES_ENDPOINT, "v1/traces") {
         exporter = exporter.with_endpoint(endpoint);
     }
 
@@ -62,6 +51,22 @@ pub(crate) fn otel_tracing_layer(
         .with_filter(env_filter))
 }
 
+/// The resource describing this Spin process, shared by traces and metrics.
+pub(crate) fn spin_resource(spin_version: String) -> Resource {
+    Resource::from_detectors(
+        Duration::from_secs(5),
+        vec![
+            // Set service.name from env OTEL_SERVICE_NAME > env OTEL_RESOURCE_ATTRIBUTES > spin
+            // Set service.version from Spin metadata
+            Box::new(SpinResourceDetector::new(spin_version)),
+            // Sets fields from env OTEL_RESOURCE_ATTRIBUTES
+            Box::new(EnvResourceDetector::new()),
+            // Sets telemetry.sdk{name, language, version}
+            Box::new(TelemetryResourceDetector),
+        ],
+    )
+}
+
 // This mitigation was taken from https://github.com/neondatabase/neon/blob/main/libs/tracing-utils/src/lib.rs
 //
 // opentelemetry-otlp v0.15.0 has a bug in how it uses the
@@ -82,13 +87,16 @@ pub(crate) fn otel_tracing_layer(
 // remember to remove this, it won't do any harm either, as the crate will
 // just ignore the OTEL_EXPORTER_OTLP_ENDPOINT setting when the endpoint
 // is set directly with `with_endpoint`.
-fn fix_endpoint_bug() -> Option<String> {
-    if std::env::var(OTEL_EXPORTER_OTLP_TRACES_ENDPOINT).is_err() {
+//
+// The same applies to metrics, with "/v1/metrics", so the signal's own
+// endpoint variable and path are passed in.
+pub(crate) fn fix_endpoint_bug(signal_endpoint_var: &str, signal_path: &str) -> Option<String> {
+    if std::env::var(signal_endpoint_var).is_err() {
         if let Ok(mut endpoint) = std::env::var(OTEL_EXPORTER_OTLP_ENDPOINT) {
             if !endpoint.ends_with('/') {
                 endpoint.push('/');
             }
-            endpoint.push_str("v1/traces");
+            endpoint.push_str(signal_path);
             return Some(endpoint);
         }
     }
@@ -24,6 +24,17 @@ impl<T, E> Cancellable<T, E> {
         }
     }
 
+    pub(crate) fn and_then_cancellable<U>(
+        self,
+        f: impl Fn(T) -> Cancellable<U, E>,
+    ) -> Cancellable<U, E> {
+        match self {
+            Self::Ok(value) => f(value),
+            Self::Cancelled => Cancellable::Cancelled,
+            Self::Err(e) => Cancellable::Err(e),
+        }
+    }
+
     pub(crate) async fn and_then_async<U, Fut: std::future::Future<Output = Result<U, E>>>(
         self,
         f: impl Fn(T) -> Fut,
@@ -0,0 +1,157 @@
+//! Detection of clashes between a component being added to an application
+//! and the components already in it.
+
+use toml_edit::{Document, Item, Value};
+
+/// An HTTP route of an added component which is already in use.
+pub(crate) struct RouteConflict {
+    pub route: String,
+    /// The component already serving the route.
+    pub existing_component: String,
+}
+
+/// The manifest snippet of a component being added to an application.
+pub(crate) struct AddedComponent {
+    doc: Document,
+}
+
+impl AddedComponent {
+    /// Parses a rendered snippet, if it is valid TOML.
+    pub fn parse(text: &str) -> Option<Self> {
+        let doc: Document = text.parse().ok()?;
+        Some(Self { doc })
+    }
+
+    /// Returns the first component defined by the snippet which the
+    /// application already has.
+    pub fn existing_component_id(&self, app: &Document) -> Option<String> {
+        let existing = app.get("component").and_then(Item::as_table_like)?;
+        let added = self.doc.get("component").and_then(Item::as_table_like)?;
+        added
+            .iter()
+            .map(|(id, _)| id)
+            .find(|id| existing.contains_key(id))
+            .map(str::to_owned)
+    }
+
+    /// Replaces the routes of all the snippet's HTTP triggers.
+    pub fn set_routes(&mut self, route: &str) {
+        for index in 0..http_triggers(&self.doc).len() {
+            self.set_route(index, route);
+        }
+    }
+
+    /// Replaces the route of the snippet's `index`th HTTP trigger.
+    pub fn set_route(&mut self, index: usize, route: &str) {
+        let trigger = self
+            .doc
+            .get_mut("trigger")
+            .and_then(|t| t.get_mut("http"))
+            .and_then(Item::as_array_of_tables_mut)
+            .and_then(|triggers| triggers.get_mut(index));
+        if let Some(trigger) = trigger {
+            trigger["route"] = toml_edit::value(route);
+        }
+    }
+
+    /// Returns the first of the snippet's HTTP triggers whose route is
+    /// already used by the application, by its index in the snippet.
+    pub fn route_conflict(&self, app: &Document) -> Option<(usize, RouteConflict)> {
+        let existing = http_triggers(app);
+        http_triggers(&self.doc)
+            .into_iter()
+            .enumerate()
+            .find_map(|(index, (route, _))| {
+                let route = route?;
+                let (_, component) = existing.iter().find(|(existing_route, _)| {
+                    existing_route.as_deref() == Some(route.as_str())
+                })?;
+                let conflict = RouteConflict {
+                    route,
+                    existing_component: component.clone(),
+                };
+                Some((index, conflict))
+            })
+    }
+}
+
+impl std::fmt::Display for AddedComponent {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.doc)
+    }
+}
+
+/// The routes and components of a manifest's HTTP triggers. Private routes
+/// are not served, so they have no route.
+fn http_triggers(doc: &Document) -> Vec<(Option<String>, String)> {
+    let Some(triggers) = doc
+        .get("trigger")
+        .and_then(|t| t.get("http"))
+        .and_then(Item::as_array_of_tables)
+    else {
+        return vec![];
+    };
+    triggers
+        .iter()
+        .map(|trigger| {
+            let route = trigger
+                .get("route")
+                .and_then(Item::as_str)
+                .map(str::to_owned);
+            let component = match trigger.get("component").and_then(Item::as_value) {
+                Some(Value::String(id)) => id.value().clone(),
+                _ => "(inline)".to_owned(),
+            };
+            (route, component)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const APP: &str = r#"spin_manifest_version = 2
+
+[[trigger.http]]
+route = "/..."
+component = "web"
+
+[[trigger.http]]
+route = { private = true }
+component = "internal"
+
+[component.web]
+source = "web.wasm"
+"#;
+
+    const ADDED: &str = r#"[[trigger.http]]
+route = "/..."
+component = "api"
+
+[component.api]
+source = "api.wasm"
+"#;
+
+    #[test]
+    fn can_detect_and_resolve_route_conflicts() {
+        let app: Document = APP.parse().unwrap();
+        let mut added = AddedComponent::parse(ADDED).unwrap();
+        assert_eq!(added.existing_component_id(&app), None);
+
+        let (index, conflict) = added.route_conflict(&app).unwrap();
+        assert_eq!(conflict.route, "/...");
+        assert_eq!(conflict.existing_component, "web");
+
+        added.set_route(index, "/api/...");
+        assert!(added.route_conflict(&app).is_none());
+        assert!(added.to_string().contains("route = \"/api/...\""));
+    }
+
+    #[test]
+    fn can_detect_existing_components() {
+        let app: Document = APP.parse().unwrap();
+        let added = AddedComponent::parse(&ADDED.replace("api", "web")).unwrap();
+        assert_eq!(added.existing_component_id(&app).as_deref(), Some("web"));
+    }
+}
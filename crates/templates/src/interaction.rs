@@ -2,6 +2,7 @@ use std::{collections::HashMap, path::Path};
 
 use crate::{
     cancellable::Cancellable,
+    conflicts::RouteConflict,
     template::{TemplateParameter, TemplateParameterDataType},
     Run,
 };
@@ -33,6 +34,10 @@ pub(crate) trait InteractionStrategy {
         run: &Run,
         parameter: &TemplateParameter,
     ) -> Cancellable<String, anyhow::Error>;
+    fn resolve_route_conflict(
+        &self,
+        conflict: &RouteConflict,
+    ) -> Cancellable<String, anyhow::Error>;
 }
 
 pub(crate) struct Interactive;
@@ -71,9 +76,47 @@ impl InteractionStrategy for Interactive {
             },
         }
     }
+
+    fn resolve_route_conflict(
+        &self,
+        conflict: &RouteConflict,
+    ) -> Cancellable<String, anyhow::Error> {
+        let prompt = format!(
+            "Route '{}' is already used by component '{}'. Enter a different route for the new component",
+            conflict.route, conflict.existing_component
+        );
+        match crate::interaction::ask_route(&prompt) {
+            Ok(route) => Cancellable::Ok(route),
+            Err(e) => Cancellable::Err(e),
+        }
+    }
 }
 
 impl InteractionStrategy for Silent {
+    fn populate_parameters(
+        &self,
+        run: &Run,
+    ) -> Cancellable<HashMap<String, String>, anyhow::Error> {
+        // Report every missing value at once, so they can all be supplied
+        // before trying again
+        let mut values = HashMap::new();
+        let mut missing = vec![];
+        for parameter in run.template.parameters(&run.options.variant) {
+            match self.populate_parameter(run, parameter) {
+                Cancellable::Ok(value) => {
+                    values.insert(parameter.id().to_owned(), value);
+                }
+                Cancellable::Cancelled => return Cancellable::Cancelled,
+                Cancellable::Err(_) => missing.push(format!("'{}'", parameter.id())),
+            }
+        }
+        match missing.as_slice() {
+            [] => Cancellable::Ok(values),
+            [parameter] => Cancellable::Err(anyhow!("Parameter {parameter} not provided")),
+            _ => Cancellable::Err(anyhow!("Parameters {} not provided", missing.join(", "))),
+        }
+    }
+
     fn allow_generate_into(&self, target_dir: &Path) -> Cancellable<(), anyhow::Error> {
         if is_directory_empty(target_dir) {
             Cancellable::Ok(())
@@ -99,6 +142,17 @@ impl InteractionStrategy for Silent {
             },
         }
     }
+
+    fn resolve_route_conflict(
+        &self,
+        conflict: &RouteConflict,
+    ) -> Cancellable<String, anyhow::Error> {
+        Cancellable::Err(anyhow!(
+            "Route '{}' is already used by component '{}'",
+            conflict.route,
+            conflict.existing_component
+        ))
+    }
 }
 
 pub(crate) fn confirm(text: &str) -> std::io::Result<bool> {
@@ -150,3 +204,17 @@ fn is_directory_empty(path: &Path) -> bool {
         Ok(mut read_dir) => read_dir.next().is_none(),
     }
 }
+
+fn ask_route(prompt: &str) -> anyhow::Result<String> {
+    let route = Input::<String>::new()
+        .with_prompt(prompt)
+        .validate_with(|route: &String| {
+            if route.starts_with('/') {
+                Ok(())
+            } else {
+                Err("Routes must start with '/'")
+            }
+        })
+        .interact_text()?;
+    Ok(route)
+}
@@ -4,6 +4,7 @@
 
 mod app_info;
 mod cancellable;
+mod conflicts;
 mod constraints;
 mod directory;
 mod environment;
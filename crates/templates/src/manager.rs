@@ -781,6 +781,7 @@ mod tests {
             values,
             accept_defaults: false,
             no_vcs: false,
+            route: None,
         };
 
         template.run(options).silent().await.unwrap();
@@ -791,6 +792,39 @@ mod tests {
         assert!(cargo.contains("name = \"my-project\""));
     }
 
+    #[tokio::test]
+    async fn silent_run_reports_all_missing_parameters() {
+        let temp_dir = tempdir().unwrap();
+        let store = TemplateStore::new(temp_dir.path());
+        let manager = TemplateManager { store };
+        let source = TemplateSource::File(project_root());
+
+        manager
+            .install(&source, &InstallOptions::default(), &DiscardingReporter)
+            .await
+            .unwrap();
+
+        let template = manager.get("http-rust").unwrap().unwrap();
+
+        let dest_temp_dir = tempdir().unwrap();
+        let output_dir = dest_temp_dir.path().join("myproj");
+        let options = RunOptions {
+            variant: crate::template::TemplateVariantInfo::NewApplication,
+            output_path: output_dir.clone(),
+            name: "my project".to_owned(),
+            values: HashMap::new(),
+            accept_defaults: false,
+            no_vcs: false,
+            route: None,
+        };
+
+        let err = template.run(options).silent().await.unwrap_err();
+        let message = err.to_string();
+        assert!(message.contains("'project-description'"), "{message}");
+        assert!(message.contains("'http-path'"), "{message}");
+        assert!(!output_dir.exists());
+    }
+
     #[tokio::test]
     async fn can_run_template_with_accept_defaults() {
         let temp_dir = tempdir().unwrap();
@@ -815,6 +849,7 @@ mod tests {
             values,
             accept_defaults: true,
             no_vcs: false,
+            route: None,
         };
 
         template.run(options).silent().await.unwrap();
@@ -881,6 +916,7 @@ mod tests {
                 values,
                 accept_defaults: false,
                 no_vcs: false,
+                route: None,
             };
 
             template.run(options).silent().await.unwrap();
@@ -909,6 +945,7 @@ mod tests {
                 values,
                 accept_defaults: false,
                 no_vcs: false,
+                route: None,
             };
 
             template.run(options).silent().await.unwrap();
@@ -934,6 +971,7 @@ mod tests {
                 values,
                 accept_defaults: false,
                 no_vcs: false,
+                route: None,
             };
 
             template.run(options).silent().await.unwrap();
@@ -991,6 +1029,7 @@ mod tests {
                 values,
                 accept_defaults: false,
                 no_vcs: false,
+                route: None,
             };
 
             template.run(options).silent().await.unwrap();
@@ -1019,6 +1058,7 @@ mod tests {
                 values,
                 accept_defaults: false,
                 no_vcs: false,
+                route: None,
             };
 
             template.run(options).silent().await.unwrap();
@@ -1071,6 +1111,7 @@ mod tests {
                 values,
                 accept_defaults: false,
                 no_vcs: false,
+                route: None,
             };
 
             template.run(options).silent().await.unwrap();
@@ -1099,6 +1140,7 @@ mod tests {
                 values,
                 accept_defaults: false,
                 no_vcs: false,
+                route: None,
             };
 
             template.run(options).silent().await.unwrap();
@@ -1124,6 +1166,7 @@ mod tests {
                 values,
                 accept_defaults: false,
                 no_vcs: false,
+                route: None,
             };
 
             template.run(options).silent().await.unwrap();
@@ -1134,6 +1177,81 @@ mod tests {
         assert!(!spin_toml.contains("service.example.com"));
     }
 
+    #[tokio::test]
+    async fn added_components_do_not_clash_with_existing_ones() {
+        let temp_dir = tempdir().unwrap();
+        let store = TemplateStore::new(temp_dir.path());
+        let manager = TemplateManager { store };
+        let source = TemplateSource::File(project_root());
+
+        manager
+            .install(&source, &InstallOptions::default(), &DiscardingReporter)
+            .await
+            .unwrap();
+
+        let template = manager.get("http-rust").unwrap().unwrap();
+        let dest_temp_dir = tempdir().unwrap();
+        let application_dir = dest_temp_dir.path().join("clashes");
+        let spin_toml_path = application_dir.join("spin.toml");
+
+        let values = || {
+            [
+                ("project-description".to_owned(), "my desc".to_owned()),
+                ("http-path".to_owned(), "/...".to_owned()),
+            ]
+            .into_iter()
+            .collect()
+        };
+        let add_options = |name: &str, route: Option<&str>| RunOptions {
+            variant: crate::template::TemplateVariantInfo::AddComponent {
+                manifest_path: spin_toml_path.clone(),
+            },
+            output_path: PathBuf::from(name),
+            name: name.to_owned(),
+            values: values(),
+            accept_defaults: false,
+            no_vcs: false,
+            route: route.map(str::to_owned),
+        };
+
+        let options = RunOptions {
+            variant: crate::template::TemplateVariantInfo::NewApplication,
+            output_path: application_dir.clone(),
+            name: "web".to_owned(),
+            values: values(),
+            accept_defaults: false,
+            no_vcs: false,
+            route: None,
+        };
+        template.run(options).silent().await.unwrap();
+
+        let err = template
+            .run(add_options("api", None))
+            .silent()
+            .await
+            .unwrap_err();
+        assert!(err
+            .to_string()
+            .contains("Route '/...' is already used by component 'web'"));
+
+        let err = template
+            .run(add_options("web", Some("/web/...")))
+            .silent()
+            .await
+            .unwrap_err();
+        assert!(err
+            .to_string()
+            .contains("already contains a component named 'web'"));
+
+        template
+            .run(add_options("api", Some("/api/...")))
+            .silent()
+            .await
+            .unwrap();
+        let spin_toml = tokio::fs::read_to_string(&spin_toml_path).await.unwrap();
+        assert!(spin_toml.contains("route = \"/api/...\"\ncomponent = \"api\""));
+    }
+
     #[tokio::test]
     async fn component_new_no_vcs() {
         let temp_dir = tempdir().unwrap();
@@ -1165,6 +1283,7 @@ mod tests {
             values,
             accept_defaults: false,
             no_vcs: true,
+            route: None,
         };
 
         template.run(options).silent().await.unwrap();
@@ -1204,6 +1323,7 @@ mod tests {
                 values,
                 accept_defaults: false,
                 no_vcs: true,
+                route: None,
             };
 
             template.run(options).silent().await.unwrap();
@@ -1237,6 +1357,7 @@ mod tests {
                 values,
                 accept_defaults: false,
                 no_vcs: true,
+                route: None,
             };
             template.run(options).silent().await.unwrap();
         }
@@ -1287,6 +1408,7 @@ mod tests {
                 values,
                 accept_defaults: false,
                 no_vcs: false,
+                route: None,
             };
 
             template.run(options).silent().await.unwrap();
@@ -1315,6 +1437,7 @@ mod tests {
                 values,
                 accept_defaults: false,
                 no_vcs: false,
+                route: None,
             };
 
             template.run(options).silent().await.unwrap();
@@ -1376,6 +1499,7 @@ mod tests {
                 values,
                 accept_defaults: false,
                 no_vcs: false,
+                route: None,
             };
 
             template
@@ -1446,6 +1570,7 @@ mod tests {
             values,
             accept_defaults: false,
             no_vcs: false,
+            route: None,
         };
 
         let err = template
@@ -10,9 +10,11 @@ use walkdir::WalkDir;
 
 use crate::{
     cancellable::Cancellable,
+    conflicts::AddedComponent,
     interaction::{InteractionStrategy, Interactive, Silent},
     renderer::MergeTarget,
     template::{ExtraOutputAction, TemplateVariantInfo},
+    writer::TemplateOutputs,
 };
 use crate::{
     renderer::{RenderOperation, TemplateContent, TemplateRenderer},
@@ -40,6 +42,9 @@ pub struct RunOptions {
     pub accept_defaults: bool,
     /// If true, do not create a .gitignore file
     pub no_vcs: bool,
+    /// When adding a component, the route for its HTTP triggers, in place of
+    /// the template's.
+    pub route: Option<String>,
 }
 
 impl Run {
@@ -63,9 +68,10 @@ impl Run {
     }
 
     async fn run(&self, interaction: impl InteractionStrategy) -> anyhow::Result<()> {
-        self.build_renderer(interaction)
+        self.build_renderer(&interaction)
             .await
             .and_then(|t| t.render())
+            .and_then_cancellable(|o| self.resolve_conflicts(o, &interaction))
             .and_then_async(|o| async move { o.write().await })
             .await
             .err()
@@ -73,7 +79,7 @@ impl Run {
 
     async fn build_renderer(
         &self,
-        interaction: impl InteractionStrategy,
+        interaction: &impl InteractionStrategy,
     ) -> Cancellable<TemplateRenderer, anyhow::Error> {
         self.build_renderer_raw(interaction).await.into()
     }
@@ -85,7 +91,7 @@ impl Run {
     // a better way but I don't see one yet...
     async fn build_renderer_raw(
         &self,
-        interaction: impl InteractionStrategy,
+        interaction: &impl InteractionStrategy,
     ) -> anyhow::Result<Option<TemplateRenderer>> {
         self.validate_version()?;
         self.validate_trigger()?;
@@ -146,6 +152,49 @@ impl Run {
         }
     }
 
+    /// Makes sure that a component being added doesn't clash with the
+    /// application's existing components, choosing other routes for it if
+    /// they are already in use.
+    fn resolve_conflicts(
+        &self,
+        mut outputs: TemplateOutputs,
+        interaction: &impl InteractionStrategy,
+    ) -> Cancellable<TemplateOutputs, anyhow::Error> {
+        let TemplateVariantInfo::AddComponent { manifest_path } = &self.options.variant else {
+            return Cancellable::Ok(outputs);
+        };
+        // Fail forgiving - don't block the user if things are under construction
+        let Some(app) = std::fs::read_to_string(manifest_path)
+            .ok()
+            .and_then(|text| text.parse::<toml_edit::Document>().ok())
+        else {
+            return Cancellable::Ok(outputs);
+        };
+
+        for text in outputs.appended_toml_mut(manifest_path) {
+            let Some(mut component) = AddedComponent::parse(text) else {
+                continue;
+            };
+            if let Some(id) = component.existing_component_id(&app) {
+                return Cancellable::Err(anyhow!(
+                    "The application already contains a component named '{id}'"
+                ));
+            }
+            if let Some(route) = &self.options.route {
+                component.set_routes(route);
+            }
+            while let Some((index, conflict)) = component.route_conflict(&app) {
+                match interaction.resolve_route_conflict(&conflict) {
+                    Cancellable::Ok(route) => component.set_route(index, &route),
+                    Cancellable::Cancelled => return Cancellable::Cancelled,
+                    Cancellable::Err(e) => return Cancellable::Err(e),
+                }
+            }
+            *text = component.to_string();
+        }
+        Cancellable::Ok(outputs)
+    }
+
     fn included_files(&self, from: &Path, to: &Path) -> anyhow::Result<Vec<RenderOperation>> {
         let gitignore = ".gitignore";
         let mut all_content_files = Self::list_content_files(from)?;
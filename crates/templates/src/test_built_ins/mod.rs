@@ -41,6 +41,7 @@ async fn new_fileserver_creates_assets_dir() -> anyhow::Result<()> {
         values: HashMap::new(),
         accept_defaults: true,
         no_vcs: false,
+        route: None,
     };
     manager
         .get("static-fileserver")?
@@ -87,6 +88,7 @@ async fn add_fileserver_creates_assets_dir_next_to_manifest() -> anyhow::Result<
         values: HashMap::new(),
         accept_defaults: true,
         no_vcs: false,
+        route: None,
     };
     manager
         .get("http-empty")?
@@ -106,6 +108,7 @@ async fn add_fileserver_creates_assets_dir_next_to_manifest() -> anyhow::Result<
         values: fs_settings,
         accept_defaults: true,
         no_vcs: false,
+        route: None,
     };
     manager
         .get("static-fileserver")?
@@ -1,4 +1,4 @@
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 use anyhow::Context;
 
@@ -18,6 +18,19 @@ impl TemplateOutputs {
         Self { outputs }
     }
 
+    /// The TOML to be appended to the file at `path`.
+    pub fn appended_toml_mut<'a>(
+        &'a mut self,
+        path: &'a Path,
+    ) -> impl Iterator<Item = &'a mut String> + 'a {
+        self.outputs
+            .iter_mut()
+            .filter_map(move |output| match output {
+                TemplateOutput::AppendToml(p, text) if p == path => Some(text),
+                _ => None,
+            })
+    }
+
     pub async fn write(&self) -> anyhow::Result<()> {
         for output in &self.outputs {
             output.write().await?;
@@ -8,6 +8,68 @@ use termcolor::{ColorSpec, StandardStream, StandardStreamLock, WriteColor};
 
 static COLOR_OUT: OnceCell<StandardStream> = OnceCell::new();
 static COLOR_ERR: OnceCell<StandardStream> = OnceCell::new();
+static OUTPUT_FORMAT: OnceCell<OutputFormat> = OnceCell::new();
+
+/// The environment variable through which the output format is passed to
+/// subprocesses.
+pub const OUTPUT_FORMAT_ENV: &str = "SPIN_OUTPUT";
+
+/// The format in which commands report their results.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum OutputFormat {
+    /// Human-readable text
+    #[default]
+    Text,
+    /// A JSON document on stdout, with human-readable text going to stderr
+    Json,
+}
+
+impl OutputFormat {
+    /// Whether stdout is reserved for machine-readable output.
+    pub fn is_json(self) -> bool {
+        self == Self::Json
+    }
+}
+
+impl std::str::FromStr for OutputFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "text" => Ok(Self::Text),
+            "json" => Ok(Self::Json),
+            _ => Err(format!(
+                "unknown output format '{s}': expected 'text' or 'json'"
+            )),
+        }
+    }
+}
+
+impl std::fmt::Display for OutputFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::Text => "text",
+            Self::Json => "json",
+        })
+    }
+}
+
+/// Sets the output format for the process. This has no effect once the
+/// format has been used.
+pub fn set_output_format(format: OutputFormat) {
+    _ = OUTPUT_FORMAT.set(format);
+}
+
+/// The output format for the process: as set by [`set_output_format`], or
+/// else as given by the [`OUTPUT_FORMAT_ENV`] environment variable.
+pub fn output_format() -> OutputFormat {
+    *OUTPUT_FORMAT.get_or_init(|| {
+        std::env::var(OUTPUT_FORMAT_ENV)
+            .ok()
+            .and_then(|format| format.parse().ok())
+            .unwrap_or_default()
+    })
+}
 
 /// A wrapper around a standard stream lock that resets the color on drop
 pub struct ColorText(StandardStreamLock<'static>);
@@ -75,9 +137,28 @@ fn color_choice(stream: atty::Stream) -> termcolor::ColorChoice {
 #[macro_export]
 macro_rules! step {
     ($step:expr, $($arg:tt)*) => {{
-        $crate::cprint!($crate::colors::bold_green(), $step);
-        print!(" ");
-        println!($($arg)*);
+        if $crate::output_format().is_json() {
+            $crate::ceprint!($crate::colors::bold_green(), $step);
+            eprint!(" ");
+            eprintln!($($arg)*);
+        } else {
+            $crate::cprint!($crate::colors::bold_green(), $step);
+            print!(" ");
+            println!($($arg)*);
+        }
+    }};
+}
+
+/// Prints a line of human-readable text: to stdout, or to stderr if stdout is
+/// reserved for JSON output.
+#[macro_export]
+macro_rules! text {
+    ($($arg:tt)*) => {{
+        if $crate::output_format().is_json() {
+            eprintln!($($arg)*);
+        } else {
+            println!($($arg)*);
+        }
     }};
 }
 
@@ -0,0 +1,312 @@
+use std::{
+    collections::BTreeSet,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use anyhow::{anyhow, Context, Result};
+use http::{uri::Scheme, Request, Response};
+use http_body_util::BodyExt;
+use serde::de::DeserializeOwned;
+use spin_app::{locked::LockedApp, values::ValuesMap};
+use spin_common::ui::quoted_path;
+use spin_core::StoreBuilder;
+use spin_loader::FilesMountStrategy;
+use spin_trigger::{
+    loader::TriggerLoader, BlobContainerOpts, Cassette, HostComponentInitData, RuntimeConfig,
+    RuntimeConfigBuilder, SpinKeyValueStoreOpts, SpinSqliteDatabaseOpts, TriggerExecutor,
+    TriggerExecutorBuilder, TriggerHooks,
+};
+use spin_trigger_http::HttpTrigger;
+use spin_trigger_redis::RedisTrigger;
+use tempfile::TempDir;
+
+use crate::{test_socket_addr, FrozenClock, MockHttp};
+
+/// An app running in-process, for component authors' integration tests.
+///
+/// Its key-value stores and SQLite databases are in memory, so each test
+/// starts empty, and its outbound HTTP requests go to a [`MockHttp`]
+/// rather than the network, unless they are recorded to or replayed from a
+/// [`Cassette`]. Apps must be built and run inside a
+/// multi-threaded Tokio runtime, such as with
+/// `#[tokio::test(flavor = "multi_thread")]`.
+///
+/// ```no_run
+/// # async fn test() -> anyhow::Result<()> {
+/// use spin_testing::{
+///     http::{self, Method},
+///     MockHttp, TestApp,
+/// };
+///
+/// let outbound = MockHttp::new();
+/// outbound.respond(Method::GET, "https://api.example.com/users", 200, "[]");
+/// let app = TestApp::builder("spin.toml")
+///     .outbound_http(outbound.clone())
+///     .build()
+///     .await?;
+///
+/// let response = app
+///     .send_http_request(http::Request::get("/users").body(vec![])?)
+///     .await?;
+/// assert_eq!(response.status(), 200);
+/// outbound.assert_requested(Method::GET, "https://api.example.com/users", 1);
+/// # Ok(())
+/// # }
+/// ```
+pub struct TestApp {
+    http: Option<HttpTrigger>,
+    redis: Option<RedisTrigger>,
+    // Holds the app's copied files until the triggers are dropped
+    _working_dir: TempDir,
+}
+
+/// Configures a [`TestApp`].
+pub struct TestAppBuilder {
+    manifest_path: PathBuf,
+    outbound_http: MockHttp,
+    cassette: Option<Arc<Cassette>>,
+    clock: Option<FrozenClock>,
+    runtime_config: Option<RuntimeConfigBuilder>,
+}
+
+impl TestApp {
+    /// Configures a test of the app described by the `spin.toml` at
+    /// `manifest_path`.
+    pub fn builder(manifest_path: impl Into<PathBuf>) -> TestAppBuilder {
+        TestAppBuilder {
+            manifest_path: manifest_path.into(),
+            outbound_http: MockHttp::new(),
+            cassette: None,
+            clock: None,
+            runtime_config: None,
+        }
+    }
+
+    /// Loads the app with the default test configuration.
+    pub async fn load(manifest_path: impl Into<PathBuf>) -> Result<Self> {
+        Self::builder(manifest_path).build().await
+    }
+
+    /// Handles a request with the app's HTTP trigger. Only the path and
+    /// query are used from the request's URI.
+    pub async fn send_http_request(&self, request: Request<Vec<u8>>) -> Result<Response<Vec<u8>>> {
+        let trigger = self.http.as_ref().context("The app has no HTTP trigger")?;
+        let request = request.map(|body| spin_http::body::full(body.into()));
+        let response = trigger
+            .handle(request, Scheme::HTTP, test_socket_addr())
+            .await?;
+        let (parts, body) = response.into_parts();
+        let body = body
+            .collect()
+            .await
+            .map_err(|err| anyhow!("Failed to read response body: {err:?}"))?
+            .to_bytes();
+        Ok(Response::from_parts(parts, body.to_vec()))
+    }
+
+    /// Handles a message as if it had been published to `channel`, invoking
+    /// the components of the app's Redis trigger which subscribe to it.
+    pub async fn invoke_redis_message(&self, channel: &str, payload: &[u8]) -> Result<()> {
+        let trigger = self
+            .redis
+            .as_ref()
+            .context("The app has no Redis trigger")?;
+        trigger.handle_message(channel, payload).await
+    }
+}
+
+impl TestAppBuilder {
+    /// Sends the app's outbound HTTP requests to `mock`. By default, they
+    /// go to an empty [`MockHttp`], so that all of them fail.
+    pub fn outbound_http(mut self, mock: MockHttp) -> Self {
+        self.outbound_http = mock;
+        self
+    }
+
+    /// Records the app's outbound HTTP, Redis and Postgres interactions to
+    /// `cassette`, or replays them from it, in place of the [`MockHttp`].
+    /// Replaying a cassette recorded once against real services keeps tests
+    /// deterministic without hand-written mocks.
+    pub fn cassette(mut self, cassette: Cassette) -> Self {
+        self.cassette = Some(Arc::new(cassette));
+        self
+    }
+
+    /// Freezes the clocks the app's components read.
+    pub fn clock(mut self, clock: FrozenClock) -> Self {
+        self.clock = Some(clock);
+        self
+    }
+
+    /// Adds runtime config, such as variables providers, on top of the
+    /// in-memory stores and databases.
+    pub fn runtime_config(mut self, runtime_config: RuntimeConfigBuilder) -> Self {
+        self.runtime_config = Some(runtime_config);
+        self
+    }
+
+    /// Loads the app and builds its HTTP and Redis triggers.
+    pub async fn build(self) -> Result<TestApp> {
+        crate::init_tracing();
+        let working_dir = tempfile::tempdir().context("Failed to create working directory")?;
+        let locked = spin_loader::from_file(
+            &self.manifest_path,
+            FilesMountStrategy::Copy(working_dir.path().join("assets")),
+            None,
+        )
+        .await
+        .with_context(|| {
+            format!(
+                "Failed to load manifest from {}",
+                quoted_path(&self.manifest_path)
+            )
+        })?;
+        let locked_path = working_dir.path().join("spin.lock");
+        std::fs::write(&locked_path, serde_json::to_vec(&locked)?)
+            .context("Failed to write locked app")?;
+        let locked_url = url::Url::from_file_path(&locked_path)
+            .map_err(|_| anyhow!("Invalid locked app path {}", quoted_path(&locked_path)))?
+            .to_string();
+
+        let trigger_types: BTreeSet<_> = locked
+            .triggers
+            .iter()
+            .map(|trigger| trigger.trigger_type.as_str())
+            .collect();
+        // A cassette handles outbound HTTP in place of the mock
+        let hooks = TestHooks {
+            outbound_http: self
+                .cassette
+                .is_none()
+                .then(|| Arc::new(self.outbound_http)),
+            clock: self.clock,
+        };
+        let local_app_dir = self
+            .manifest_path
+            .canonicalize()?
+            .parent()
+            .map(Path::to_owned);
+        let runtime_config = || {
+            let mut config = in_memory_config(&locked).build(local_app_dir.clone());
+            if let Some(builder) = &self.runtime_config {
+                config.merge_config_builder(builder.clone());
+            }
+            config
+        };
+
+        let mut app = TestApp {
+            http: None,
+            redis: None,
+            _working_dir: working_dir,
+        };
+        for trigger_type in trigger_types {
+            match trigger_type {
+                "http" => {
+                    app.http = Some(
+                        build_trigger(
+                            app._working_dir.path(),
+                            &locked_url,
+                            runtime_config(),
+                            hooks.clone(),
+                            self.cassette.clone(),
+                        )
+                        .await?,
+                    );
+                }
+                "redis" => {
+                    app.redis = Some(
+                        build_trigger(
+                            app._working_dir.path(),
+                            &locked_url,
+                            runtime_config(),
+                            hooks.clone(),
+                            self.cassette.clone(),
+                        )
+                        .await?,
+                    );
+                }
+                // Other triggers' components can't be invoked in tests
+                _ => {}
+            }
+        }
+        Ok(app)
+    }
+}
+
+// Configures every store and database the app's components use to be in
+// memory, and keeps no state or logs.
+fn in_memory_config(locked: &LockedApp) -> RuntimeConfigBuilder {
+    let mut builder = RuntimeConfigBuilder::new().state_dir("").log_dir("");
+    for component in &locked.components {
+        for store in metadata_names(&component.metadata, "key_value_stores") {
+            builder = builder.key_value_store(store, SpinKeyValueStoreOpts::default());
+        }
+        for database in metadata_names(&component.metadata, "databases") {
+            builder = builder.sqlite_database(database, SpinSqliteDatabaseOpts { path: None });
+        }
+        for container in metadata_names(&component.metadata, "blob_containers") {
+            builder = builder.blob_container(container, BlobContainerOpts::InMemory);
+        }
+    }
+    builder
+}
+
+fn metadata_names<'a>(metadata: &'a ValuesMap, key: &str) -> impl Iterator<Item = &'a str> {
+    metadata
+        .get(key)
+        .and_then(|names| names.as_array())
+        .into_iter()
+        .flatten()
+        .filter_map(|name| name.as_str())
+}
+
+async fn build_trigger<Executor>(
+    working_dir: &Path,
+    locked_url: &str,
+    runtime_config: RuntimeConfig,
+    hooks: TestHooks,
+    cassette: Option<Arc<Cassette>>,
+) -> Result<Executor>
+where
+    Executor: TriggerExecutor,
+    Executor::TriggerConfig: DeserializeOwned,
+{
+    let mut builder =
+        TriggerExecutorBuilder::<Executor>::new(TriggerLoader::new(working_dir, false));
+    builder.hooks(hooks);
+    if let Some(cassette) = cassette {
+        builder.cassette(cassette);
+    }
+    builder
+        .build(
+            locked_url.to_owned(),
+            runtime_config,
+            HostComponentInitData::default(),
+        )
+        .await
+        .with_context(|| format!("Failed to build {} trigger", Executor::TRIGGER_TYPE))
+}
+
+// Sets up each component's store for testing.
+#[derive(Clone)]
+struct TestHooks {
+    outbound_http: Option<Arc<MockHttp>>,
+    clock: Option<FrozenClock>,
+}
+
+impl TriggerHooks for TestHooks {
+    fn component_store_builder(
+        &self,
+        _component: &spin_app::AppComponent,
+        store_builder: &mut StoreBuilder,
+    ) -> Result<()> {
+        if let Some(outbound_http) = &self.outbound_http {
+            store_builder.outbound_http_interceptor(outbound_http.clone());
+        }
+        if let Some(clock) = &self.clock {
+            store_builder.clocks(clock.clone(), clock.clone())?;
+        }
+        Ok(())
+    }
+}
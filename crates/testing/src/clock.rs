@@ -0,0 +1,90 @@
+use std::{
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use spin_core::{HostMonotonicClock, HostWallClock};
+
+/// A clock which components read instead of the system's, standing still
+/// until it is advanced.
+///
+/// Clones share the same time, so a test can advance the clock its app
+/// reads.
+#[derive(Clone, Debug)]
+pub struct FrozenClock {
+    // Nanoseconds since the Unix epoch
+    now: Arc<AtomicU64>,
+    // Nanoseconds since the clock was created, for the monotonic clock
+    elapsed: Arc<AtomicU64>,
+}
+
+impl FrozenClock {
+    /// A clock frozen at `time`, which must be after the Unix epoch.
+    pub fn at(time: SystemTime) -> Self {
+        let since_epoch = time
+            .duration_since(UNIX_EPOCH)
+            .expect("frozen time must be after the Unix epoch");
+        Self {
+            now: Arc::new(AtomicU64::new(nanos(since_epoch))),
+            elapsed: Default::default(),
+        }
+    }
+
+    /// The time the clock reads.
+    pub fn now(&self) -> SystemTime {
+        UNIX_EPOCH + Duration::from_nanos(self.now.load(Ordering::SeqCst))
+    }
+
+    /// Moves the clock forward by `duration`.
+    pub fn advance(&self, duration: Duration) {
+        self.now.fetch_add(nanos(duration), Ordering::SeqCst);
+        self.elapsed.fetch_add(nanos(duration), Ordering::SeqCst);
+    }
+}
+
+fn nanos(duration: Duration) -> u64 {
+    duration.as_nanos().try_into().expect("duration too long")
+}
+
+impl HostWallClock for FrozenClock {
+    fn resolution(&self) -> Duration {
+        Duration::from_nanos(1)
+    }
+
+    fn now(&self) -> Duration {
+        Duration::from_nanos(self.now.load(Ordering::SeqCst))
+    }
+}
+
+impl HostMonotonicClock for FrozenClock {
+    fn resolution(&self) -> u64 {
+        1
+    }
+
+    fn now(&self) -> u64 {
+        self.elapsed.load(Ordering::SeqCst)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn clocks_stand_still_until_advanced() {
+        let clock = FrozenClock::at(UNIX_EPOCH + Duration::from_secs(1_700_000_000));
+        let shared = clock.clone();
+        assert_eq!(
+            HostWallClock::now(&clock),
+            Duration::from_secs(1_700_000_000)
+        );
+        assert_eq!(HostMonotonicClock::now(&clock), 0);
+
+        shared.advance(Duration::from_secs(5));
+        assert_eq!(clock.now(), UNIX_EPOCH + Duration::from_secs(1_700_000_005));
+        assert_eq!(HostMonotonicClock::now(&clock), 5_000_000_000);
+    }
+}
@@ -1,5 +1,13 @@
 //! This crates contains common code for use in tests. Many methods will panic
 //! in the slightest breeze, so DO NOT USE IN NON-TEST CODE.
+//!
+//! Component authors can test their apps in-process with [`TestApp`], which
+//! replaces the network and clocks with [`MockHttp`] and [`FrozenClock`],
+//! or replays outbound interactions recorded in a [`Cassette`].
+
+mod app;
+mod clock;
+mod mock_http;
 
 use std::{
     net::SocketAddr,
@@ -22,8 +30,16 @@ use spin_http::config::{
 use spin_trigger::{HostComponentInitData, RuntimeConfig, TriggerExecutor, TriggerExecutorBuilder};
 use tokio::fs;
 
+pub use http;
+pub use spin_trigger::Cassette;
 pub use tokio;
 
+pub use crate::{
+    app::{TestApp, TestAppBuilder},
+    clock::FrozenClock,
+    mock_http::{MockHttp, RecordedRequest},
+};
+
 // Built by build.rs
 const TEST_PROGRAM_PATH: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/../../target/test-programs");
 
@@ -74,7 +90,7 @@ impl HttpTestConfig {
         self.http_trigger_config = HttpTriggerConfig {
             component: "test-component".to_string(),
             route: route.into(),
-            executor: None,
+            ..Default::default()
         };
         self
     }
@@ -88,6 +104,7 @@ impl HttpTestConfig {
             component: "test-component".to_string(),
             route: route.into(),
             executor: Some(HttpExecutorType::Wagi(wagi_config)),
+            ..Default::default()
         };
         self
     }
@@ -0,0 +1,204 @@
+use std::sync::{Arc, Mutex};
+
+use anyhow::{bail, Result};
+use bytes::Bytes;
+use http::{HeaderMap, Method, Request, Response, StatusCode};
+use spin_core::{async_trait, OutboundHttpInterceptor};
+
+/// Canned responses to components' outbound HTTP requests, in place of the
+/// network, and a record of the requests they made.
+///
+/// A request matches a response by its method and URL, ignoring the query
+/// unless the response's URL has one. Requests which match no response fail
+/// as a network error would. Clones share the same responses and record.
+#[derive(Clone, Debug, Default)]
+pub struct MockHttp {
+    inner: Arc<Mutex<Mocks>>,
+}
+
+#[derive(Debug, Default)]
+struct Mocks {
+    responses: Vec<MockResponse>,
+    requests: Vec<RecordedRequest>,
+}
+
+#[derive(Debug)]
+struct MockResponse {
+    method: Method,
+    url: String,
+    status: StatusCode,
+    headers: HeaderMap,
+    body: Bytes,
+}
+
+/// An outbound request a component made.
+#[derive(Clone, Debug)]
+pub struct RecordedRequest {
+    pub method: Method,
+    pub url: String,
+    pub headers: HeaderMap,
+    pub body: Bytes,
+}
+
+impl RecordedRequest {
+    /// The body as UTF-8 text; panics if it isn't.
+    pub fn text(&self) -> &str {
+        std::str::from_utf8(&self.body).expect("request body should be UTF-8")
+    }
+}
+
+impl MockHttp {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Responds to `method` requests to `url` with `status` and `body`.
+    /// Responses added later take precedence.
+    pub fn respond(
+        &self,
+        method: Method,
+        url: impl Into<String>,
+        status: u16,
+        body: impl Into<Bytes>,
+    ) -> &Self {
+        self.respond_with(
+            method,
+            url,
+            Response::builder()
+                .status(status)
+                .body(body.into())
+                .expect("status should be valid"),
+        )
+    }
+
+    /// Responds to `method` requests to `url` with `response`.
+    pub fn respond_with(
+        &self,
+        method: Method,
+        url: impl Into<String>,
+        response: Response<impl Into<Bytes>>,
+    ) -> &Self {
+        let (parts, body) = response.into_parts();
+        self.inner.lock().unwrap().responses.push(MockResponse {
+            method,
+            url: url.into(),
+            status: parts.status,
+            headers: parts.headers,
+            body: body.into(),
+        });
+        self
+    }
+
+    /// The requests made so far, in order, whether or not they matched a
+    /// response.
+    pub fn requests(&self) -> Vec<RecordedRequest> {
+        self.inner.lock().unwrap().requests.clone()
+    }
+
+    /// The requests made so far to `method` `url`.
+    pub fn requests_to(&self, method: Method, url: &str) -> Vec<RecordedRequest> {
+        self.requests()
+            .into_iter()
+            .filter(|request| request.method == method && url_matches(url, &request.url))
+            .collect()
+    }
+
+    /// Panics unless exactly `times` requests were made to `method` `url`.
+    pub fn assert_requested(&self, method: Method, url: &str, times: usize) {
+        let count = self.requests_to(method.clone(), url).len();
+        if count != times {
+            let made: Vec<_> = self
+                .requests()
+                .iter()
+                .map(|request| format!("{} {}", request.method, request.url))
+                .collect();
+            panic!(
+                "expected {times} requests to {method} {url}, but there were {count}; requests made: {made:#?}"
+            );
+        }
+    }
+}
+
+#[async_trait]
+impl OutboundHttpInterceptor for MockHttp {
+    async fn send(&self, request: Request<Bytes>) -> Result<Response<Bytes>> {
+        let (parts, body) = request.into_parts();
+        let recorded = RecordedRequest {
+            method: parts.method,
+            url: parts.uri.to_string(),
+            headers: parts.headers,
+            body,
+        };
+        let mut mocks = self.inner.lock().unwrap();
+        mocks.requests.push(recorded.clone());
+        let Some(mock) =
+            mocks.responses.iter().rev().find(|mock| {
+                mock.method == recorded.method && url_matches(&mock.url, &recorded.url)
+            })
+        else {
+            bail!(
+                "no mock response for {} {}; add one with MockHttp::respond",
+                recorded.method,
+                recorded.url
+            );
+        };
+        let mut response = Response::new(mock.body.clone());
+        *response.status_mut() = mock.status;
+        *response.headers_mut() = mock.headers.clone();
+        Ok(response)
+    }
+}
+
+// Whether a request URL matches a mock URL, ignoring the request's query
+// unless the mock URL has one.
+fn url_matches(mock_url: &str, request_url: &str) -> bool {
+    if mock_url.contains('?') {
+        return mock_url == request_url;
+    }
+    let without_query = request_url.split('?').next().unwrap_or_default();
+    mock_url == without_query
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(method: Method, url: &str) -> Request<Bytes> {
+        Request::builder()
+            .method(method)
+            .uri(url)
+            .body(Bytes::from_static(b"ping"))
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn requests_get_canned_responses() {
+        let mock = MockHttp::new();
+        mock.respond(Method::GET, "https://example.com/users", 200, "[]")
+            .respond(Method::POST, "https://example.com/users", 201, "");
+
+        let response = mock
+            .send(request(Method::GET, "https://example.com/users?page=2"))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 200);
+        assert_eq!(response.body().as_ref(), b"[]");
+
+        let response = mock
+            .send(request(Method::POST, "https://example.com/users"))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 201);
+
+        let err = mock
+            .send(request(Method::GET, "https://example.com/other"))
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("https://example.com/other"));
+
+        mock.assert_requested(Method::GET, "https://example.com/users", 1);
+        let posted = mock.requests_to(Method::POST, "https://example.com/users");
+        assert_eq!(posted[0].text(), "ping");
+        assert_eq!(mock.requests().len(), 3);
+    }
+}
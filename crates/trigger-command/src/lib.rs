@@ -0,0 +1,318 @@
+//! Implementation for the Spin command trigger.
+//!
+//! The command trigger runs a component's `wasi:cli/run` export, so that a
+//! Spin app can be used as a command-line tool or batch job. It also runs
+//! apps' `on_start` and `on_stop` lifecycle hook components.
+
+use std::io::Cursor;
+use std::path::{Path, PathBuf};
+
+use anyhow::{bail, Context, Result};
+use clap::Args;
+use serde::{de::IgnoredAny, Deserialize, Serialize};
+use spin_app::{
+    locked::{LockedApp, LockedTrigger},
+    MetadataKey,
+};
+use spin_core::{async_trait, I32Exit, InstancePre, StoreBuilder, WasiVersion};
+use spin_manifest::schema::v2::LifecycleHook;
+use spin_trigger::{cli::env, TriggerAppEngine, TriggerExecutor};
+use tokio::io::AsyncBufReadExt;
+
+pub(crate) type RuntimeData = ();
+
+/// The environment variable which names the input file in `--each-file` runs.
+pub const INPUT_FILE_ENV: &str = "SPIN_COMMAND_INPUT_FILE";
+
+/// MetadataKey for the component run before the app's triggers start.
+pub const ON_START_KEY: MetadataKey<LifecycleHook> = MetadataKey::new("on_start");
+/// MetadataKey for the component run after the app's triggers stop.
+pub const ON_STOP_KEY: MetadataKey<LifecycleHook> = MetadataKey::new("on_stop");
+
+/// The Spin command trigger.
+pub struct CommandTrigger {
+    engine: TriggerAppEngine<Self>,
+    component: String,
+    args: Vec<String>,
+}
+
+/// Command trigger configuration.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct CommandTriggerConfig {
+    /// Component ID to invoke
+    pub component: String,
+    /// Arguments passed to the component, before any given on the command line
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub args: Vec<String>,
+    /// Trigger executor (currently unused)
+    #[serde(default, skip_serializing)]
+    pub executor: IgnoredAny,
+}
+
+#[derive(Args)]
+pub struct CliArgs {
+    /// Run the component once for each line of standard input, passing the
+    /// line as the component's standard input.
+    #[clap(
+        long = "each-line",
+        env = env::COMMAND_EACH_LINE,
+        takes_value = false,
+        conflicts_with = "each_file"
+    )]
+    pub each_line: bool,
+
+    /// Run the component once for each file in the directory, passing the
+    /// file as the component's standard input and its path in the
+    /// SPIN_COMMAND_INPUT_FILE environment variable.
+    #[clap(long = "each-file", env = env::COMMAND_EACH_FILE, value_name = "DIR")]
+    pub each_file: Option<PathBuf>,
+
+    /// Keep running after a run fails, instead of stopping at the first
+    /// failure.
+    #[clap(long = "keep-going", env = env::COMMAND_KEEP_GOING, takes_value = false)]
+    pub keep_going: bool,
+
+    /// Arguments passed to the component.
+    #[clap(last = true)]
+    pub args: Vec<String>,
+}
+
+/// The input to a single run of the component.
+enum Input {
+    /// The trigger's own standard input.
+    Inherit,
+    /// A line of the trigger's standard input.
+    Line(String),
+    /// A file.
+    File(PathBuf),
+}
+
+#[async_trait]
+impl TriggerExecutor for CommandTrigger {
+    const TRIGGER_TYPE: &'static str = "command";
+    type RuntimeData = RuntimeData;
+    type TriggerConfig = CommandTriggerConfig;
+    type RunConfig = CliArgs;
+    type InstancePre = InstancePre<RuntimeData>;
+
+    async fn new(engine: TriggerAppEngine<Self>) -> Result<Self> {
+        let mut configs = engine.trigger_configs().map(|(_, config)| config);
+        let Some(config) = configs.next() else {
+            bail!("The command trigger requires a component");
+        };
+        if configs.next().is_some() {
+            bail!("The command trigger supports only one component");
+        }
+        let component = config.component.clone();
+        let args = config.args.clone();
+        Ok(Self {
+            engine,
+            component,
+            args,
+        })
+    }
+
+    /// Run the component and exit with its exit code.
+    async fn run(self, config: Self::RunConfig) -> Result<()> {
+        let code = self.run_all(&config).await?;
+        if code != 0 {
+            std::process::exit(code);
+        }
+        Ok(())
+    }
+}
+
+impl CommandTrigger {
+    /// Runs the component for each input, returning the exit code of the
+    /// first failed run, or 0.
+    async fn run_all(&self, config: &CliArgs) -> Result<i32> {
+        let args: Vec<&str> = std::iter::once(self.component.as_str())
+            .chain(self.args.iter().map(String::as_str))
+            .chain(config.args.iter().map(String::as_str))
+            .collect();
+
+        let mut outcome = Outcome::new(config.keep_going);
+        if let Some(dir) = &config.each_file {
+            for path in input_files(dir)? {
+                if !outcome.record(self.run_once(&args, Input::File(path)).await?) {
+                    break;
+                }
+            }
+        } else if config.each_line {
+            let mut lines = tokio::io::BufReader::new(tokio::io::stdin()).lines();
+            while let Some(line) = lines.next_line().await? {
+                if !outcome.record(self.run_once(&args, Input::Line(line)).await?) {
+                    break;
+                }
+            }
+        } else {
+            outcome.record(self.run_once(&args, Input::Inherit).await?);
+        }
+        Ok(outcome.code)
+    }
+
+    async fn run_once(&self, args: &[&str], input: Input) -> Result<i32> {
+        let _permit = self.engine.acquire_permit(&self.component).await?;
+        let mut store_builder = self
+            .engine
+            .store_builder(&self.component, WasiVersion::Preview2)?;
+        store_builder.args(args.iter().copied())?;
+        // A command's output is its result, so it is never redirected to logs
+        store_builder.inherit_stdout();
+        store_builder.inherit_stderr();
+        set_input(&mut store_builder, input)?;
+
+        let (instance, mut store) = self
+            .engine
+            .prepare_instance_with_store(&self.component, store_builder)
+            .await?;
+        let func = {
+            let mut exports = instance.exports(&mut store);
+            let mut run = exports
+                .instance("wasi:cli/run@0.2.0")
+                .context("component does not export 'wasi:cli/run@0.2.0'")?;
+            run.typed_func::<(), (Result<(), ()>,)>("run")?
+        };
+        match func.call_async(&mut store, ()).await {
+            Ok((Ok(()),)) => Ok(0),
+            Ok((Err(()),)) => Ok(1),
+            Err(err) => match err.root_cause().downcast_ref::<I32Exit>() {
+                Some(exit) => Ok(exit.0),
+                None => Err(err),
+            },
+        }
+    }
+}
+
+/// Returns an app whose only component is the hook's, run by a command
+/// trigger, so that running it with the command trigger runs the hook.
+pub fn hook_app(app: &LockedApp, hook: &LifecycleHook) -> LockedApp {
+    let component = hook.component.as_ref();
+    let mut app = app.clone();
+    app.components.retain(|c| c.id == component);
+    app.triggers = vec![LockedTrigger {
+        id: format!("hook-{component}"),
+        trigger_type: CommandTrigger::TRIGGER_TYPE.to_owned(),
+        trigger_config: serde_json::json!({ "component": component }),
+    }];
+    app
+}
+
+/// The exit code of a series of runs, which is that of the first failure.
+struct Outcome {
+    keep_going: bool,
+    code: i32,
+}
+
+impl Outcome {
+    fn new(keep_going: bool) -> Self {
+        Self {
+            keep_going,
+            code: 0,
+        }
+    }
+
+    /// Records the exit code of a run, returning whether to keep running.
+    fn record(&mut self, code: i32) -> bool {
+        if self.code == 0 {
+            self.code = code;
+        }
+        code == 0 || self.keep_going
+    }
+}
+
+fn set_input(store_builder: &mut StoreBuilder, input: Input) -> Result<()> {
+    match input {
+        Input::Inherit => store_builder.inherit_stdin(),
+        Input::Line(line) => {
+            store_builder.stdin_pipe(Cursor::new(format!("{line}\n").into_bytes()))
+        }
+        Input::File(path) => {
+            let content = std::fs::read(&path)
+                .with_context(|| format!("Failed to read input file {}", path.display()))?;
+            store_builder.stdin_pipe(Cursor::new(content));
+            store_builder.env([(INPUT_FILE_ENV, path.to_string_lossy())])?;
+        }
+    }
+    Ok(())
+}
+
+/// The files directly in a directory, in name order.
+fn input_files(dir: &Path) -> Result<Vec<PathBuf>> {
+    let entries = std::fs::read_dir(dir)
+        .with_context(|| format!("Failed to read input directory {}", dir.display()))?;
+    let mut files = vec![];
+    for entry in entries {
+        let entry = entry?;
+        if entry.file_type()?.is_file() {
+            files.push(entry.path());
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hook_app_runs_only_the_hook_component() {
+        let app: LockedApp = serde_json::from_value(serde_json::json!({
+            "spin_lock_version": 1,
+            "triggers": [{
+                "id": "trigger-web",
+                "trigger_type": "http",
+                "trigger_config": { "component": "web", "route": "/..." },
+            }],
+            "components": [
+                { "id": "web", "source": { "content_type": "application/wasm" } },
+                { "id": "migrate", "source": { "content_type": "application/wasm" } },
+            ],
+        }))
+        .unwrap();
+        let hook = LifecycleHook {
+            component: "migrate".to_owned().try_into().unwrap(),
+            on_failure: Default::default(),
+        };
+
+        let hook_app = hook_app(&app, &hook);
+        let ids: Vec<_> = hook_app.components.iter().map(|c| c.id.as_str()).collect();
+        assert_eq!(ids, ["migrate"]);
+        assert_eq!(hook_app.triggers.len(), 1);
+        assert_eq!(hook_app.triggers[0].trigger_type, "command");
+        assert_eq!(
+            hook_app.triggers[0].trigger_config,
+            serde_json::json!({ "component": "migrate" })
+        );
+    }
+
+    #[test]
+    fn outcome_is_first_failure() {
+        let mut outcome = Outcome::new(true);
+        assert!(outcome.record(0));
+        assert!(outcome.record(3));
+        assert!(outcome.record(4));
+        assert_eq!(outcome.code, 3);
+
+        let mut outcome = Outcome::new(false);
+        assert!(!outcome.record(2));
+        assert_eq!(outcome.code, 2);
+    }
+
+    #[test]
+    fn input_files_are_sorted_and_skip_directories() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("b.csv"), "2").unwrap();
+        std::fs::write(dir.path().join("a.csv"), "1").unwrap();
+        std::fs::create_dir(dir.path().join("nested")).unwrap();
+
+        let files = input_files(dir.path()).unwrap();
+        let names: Vec<_> = files
+            .iter()
+            .map(|f| f.file_name().unwrap().to_str().unwrap())
+            .collect();
+        assert_eq!(names, ["a.csv", "b.csv"]);
+    }
+}
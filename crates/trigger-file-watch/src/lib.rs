@@ -0,0 +1,447 @@
+//! Implementation for the Spin file watch trigger.
+//!
+//! The file watch trigger invokes a component when files matching its globs
+//! are created, modified or deleted. Changes are debounced, so that a burst of
+//! writes (such as an editor saving a file) is delivered as one call.
+
+mod spin;
+
+use std::collections::BTreeMap;
+use std::path::{Component, Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::{bail, Context, Result};
+use globset::{Glob, GlobSet, GlobSetBuilder};
+use notify::event::{ModifyKind, RenameMode};
+use notify::{Event, EventKind, RecursiveMode, Watcher};
+use serde::{de::IgnoredAny, Deserialize, Serialize};
+use spin_core::{async_trait, InstancePre};
+use spin_trigger::{cli::NoArgs, TriggerAppEngine, TriggerExecutor};
+use spin_world::v2::file_watch_types::{Change, ChangeKind};
+use tokio::sync::mpsc;
+
+use crate::spin::SpinFileWatchExecutor;
+
+pub(crate) type RuntimeData = ();
+pub(crate) type Store = spin_core::Store<RuntimeData>;
+
+/// The Spin file watch trigger.
+#[derive(Clone)]
+pub struct FileWatchTrigger {
+    engine: Arc<TriggerAppEngine<Self>>,
+    watches: Vec<Arc<Watch>>,
+}
+
+/// File watch trigger configuration.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct FileWatchTriggerConfig {
+    /// Component ID to invoke
+    pub component: String,
+    /// Globs of the files to watch, relative to the application directory
+    pub paths: Vec<String>,
+    /// The kinds of change to deliver; all kinds if unset
+    #[serde(default = "default_events")]
+    pub events: Vec<ChangeEvent>,
+    /// Whether to deliver the content of created and modified files
+    #[serde(default)]
+    pub include_content: bool,
+    /// The largest file, in bytes, whose content is delivered
+    #[serde(default = "default_max_content_size")]
+    pub max_content_size: u64,
+    /// How long, in milliseconds, to wait for further changes before
+    /// invoking the component
+    #[serde(default = "default_debounce_ms")]
+    pub debounce_ms: u64,
+    /// Trigger executor (currently unused)
+    #[serde(default, skip_serializing)]
+    pub executor: IgnoredAny,
+}
+
+/// A kind of change to a file.
+#[derive(Clone, Copy, Debug, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ChangeEvent {
+    Create,
+    Modify,
+    Delete,
+}
+
+fn default_events() -> Vec<ChangeEvent> {
+    vec![
+        ChangeEvent::Create,
+        ChangeEvent::Modify,
+        ChangeEvent::Delete,
+    ]
+}
+
+fn default_max_content_size() -> u64 {
+    1024 * 1024
+}
+
+fn default_debounce_ms() -> u64 {
+    200
+}
+
+/// A component's watch, with its globs compiled.
+#[derive(Debug)]
+struct Watch {
+    component: String,
+    app_dir: PathBuf,
+    roots: Vec<PathBuf>,
+    globs: GlobSet,
+    events: Vec<ChangeEvent>,
+    include_content: bool,
+    max_content_size: u64,
+    debounce: Duration,
+}
+
+#[async_trait]
+impl TriggerExecutor for FileWatchTrigger {
+    const TRIGGER_TYPE: &'static str = "file-watch";
+    type RuntimeData = RuntimeData;
+    type TriggerConfig = FileWatchTriggerConfig;
+    type RunConfig = NoArgs;
+    type InstancePre = InstancePre<RuntimeData>;
+
+    async fn new(engine: TriggerAppEngine<Self>) -> Result<Self> {
+        let app_dir = app_dir(&engine)?;
+        let watches = engine
+            .trigger_configs()
+            .map(|(_, config)| Watch::new(&app_dir, config).map(Arc::new))
+            .collect::<Result<_>>()?;
+        Ok(Self {
+            engine: Arc::new(engine),
+            watches,
+        })
+    }
+
+    /// Run the file watch trigger indefinitely.
+    async fn run(self, _config: Self::RunConfig) -> Result<()> {
+        let mut tasks = vec![];
+        println!("Watching files:");
+        for watch in &self.watches {
+            for root in &watch.roots {
+                println!("\t{}: [{}]", root.display(), watch.component);
+                self.engine
+                    .status()
+                    .add_subscription(&watch.component, format!("path {}", root.display()));
+            }
+            let trigger = self.clone();
+            let watch = watch.clone();
+            tasks.push(tokio::spawn(async move { trigger.run_watch(&watch).await }));
+        }
+        if tasks.is_empty() {
+            return Ok(());
+        }
+
+        // wait for the first handle to be returned and drop the rest
+        let (result, _, rest) = futures::future::select_all(tasks).await;
+
+        drop(rest);
+
+        result?
+    }
+}
+
+impl FileWatchTrigger {
+    async fn run_watch(&self, watch: &Watch) -> Result<()> {
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        let mut watcher = notify::recommended_watcher(move |event: notify::Result<Event>| {
+            // The receiver is only dropped when the trigger stops
+            _ = tx.send(event);
+        })
+        .context("File watch trigger failed to create a watcher")?;
+        for root in &watch.roots {
+            watcher
+                .watch(root, RecursiveMode::Recursive)
+                .with_context(|| {
+                    format!("File watch trigger failed to watch {}", root.display())
+                })?;
+        }
+
+        while let Some(event) = rx.recv().await {
+            let mut pending = BTreeMap::new();
+            watch.record(&mut pending, event);
+            let deadline = tokio::time::Instant::now() + watch.debounce;
+            while let Ok(Some(event)) = tokio::time::timeout_at(deadline, rx.recv()).await {
+                watch.record(&mut pending, event);
+            }
+
+            let changes = watch.changes(pending);
+            if changes.is_empty() {
+                continue;
+            }
+            tracing::info!("Detected {} file changes", changes.len());
+            tracing::trace!("Executing file watch component {:?}", watch.component);
+            if let Err(err) = SpinFileWatchExecutor
+                .execute(&self.engine, &watch.component, changes)
+                .await
+            {
+                tracing::warn!("Error handling file changes: {err}");
+                self.engine.status().record_error(&watch.component, &err);
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Watch {
+    fn new(app_dir: &Path, config: &FileWatchTriggerConfig) -> Result<Self> {
+        let component = &config.component;
+        if config.paths.is_empty() {
+            bail!("File watch trigger for component '{component}' has no `paths`");
+        }
+        if config.events.is_empty() {
+            bail!("File watch trigger for component '{component}' has no `events`");
+        }
+        let mut globs = GlobSetBuilder::new();
+        let mut roots: Vec<PathBuf> = vec![];
+        for pattern in &config.paths {
+            let glob = Glob::new(pattern).with_context(|| {
+                format!(
+                    "File watch trigger for component '{component}' has invalid glob {pattern:?}"
+                )
+            })?;
+            globs.add(glob);
+            let root = existing_dir(&app_dir.join(glob_root(pattern)));
+            // Watches are recursive, so nested roots are redundant
+            if !roots.iter().any(|r| root.starts_with(r)) {
+                roots.retain(|r| !r.starts_with(&root));
+                roots.push(root);
+            }
+        }
+        Ok(Self {
+            component: component.clone(),
+            app_dir: app_dir.to_owned(),
+            roots,
+            globs: globs.build()?,
+            events: config.events.clone(),
+            include_content: config.include_content,
+            max_content_size: config.max_content_size,
+            debounce: Duration::from_millis(config.debounce_ms),
+        })
+    }
+
+    /// The path as reported to the component: relative to the application
+    /// directory if it is within it.
+    fn relative<'a>(&self, path: &'a Path) -> &'a Path {
+        path.strip_prefix(&self.app_dir).unwrap_or(path)
+    }
+
+    fn matches(&self, path: &Path) -> bool {
+        self.globs.is_match(self.relative(path)) || self.globs.is_match(path)
+    }
+
+    fn record(&self, pending: &mut BTreeMap<PathBuf, ChangeEvent>, event: notify::Result<Event>) {
+        let event = match event {
+            Ok(event) => event,
+            Err(err) => {
+                tracing::warn!("Error watching files: {err}");
+                return;
+            }
+        };
+        for (path, kind) in event_changes(event) {
+            // Directories are watched for the files in them, not for themselves
+            if self.matches(&path) && !path.is_dir() {
+                coalesce(pending, path, kind);
+            }
+        }
+    }
+
+    fn changes(&self, pending: BTreeMap<PathBuf, ChangeEvent>) -> Vec<Change> {
+        pending
+            .into_iter()
+            .filter(|(_, kind)| self.events.contains(kind))
+            .map(|(path, kind)| {
+                let content = match kind {
+                    ChangeEvent::Delete => None,
+                    _ if self.include_content => self.read_content(&path),
+                    _ => None,
+                };
+                Change {
+                    kind: match kind {
+                        ChangeEvent::Create => ChangeKind::Create,
+                        ChangeEvent::Modify => ChangeKind::Modify,
+                        ChangeEvent::Delete => ChangeKind::Delete,
+                    },
+                    path: self.relative(&path).to_string_lossy().into_owned(),
+                    content,
+                }
+            })
+            .collect()
+    }
+
+    fn read_content(&self, path: &Path) -> Option<Vec<u8>> {
+        let size = std::fs::metadata(path).ok()?.len();
+        if size > self.max_content_size {
+            tracing::warn!(
+                "Not delivering content of {}: {size} bytes is over the limit of {}",
+                path.display(),
+                self.max_content_size
+            );
+            return None;
+        }
+        std::fs::read(path)
+            .map_err(|err| tracing::warn!("Error reading {}: {err}", path.display()))
+            .ok()
+    }
+}
+
+/// The directory of the application manifest, or the working directory if
+/// the application was not loaded from a local file.
+fn app_dir(engine: &TriggerAppEngine<FileWatchTrigger>) -> Result<PathBuf> {
+    let dir = match engine.app_dir()? {
+        Some(dir) => dir,
+        None => std::env::current_dir()?,
+    };
+    // Watchers report canonical paths
+    Ok(dir.canonicalize().unwrap_or(dir))
+}
+
+/// The longest leading part of a glob without any wildcards.
+fn glob_root(pattern: &str) -> PathBuf {
+    Path::new(pattern)
+        .components()
+        .take_while(|c| match c {
+            Component::Normal(part) => !part.to_string_lossy().contains(['*', '?', '[', '{']),
+            _ => true,
+        })
+        .collect()
+}
+
+/// The nearest directory containing a path which exists, so that files
+/// created or replaced under it later are seen.
+fn existing_dir(path: &Path) -> PathBuf {
+    path.ancestors()
+        .find(|p| p.is_dir())
+        .unwrap_or(path)
+        .to_owned()
+}
+
+/// Converts a watcher event to the changes it represents.
+fn event_changes(event: Event) -> Vec<(PathBuf, ChangeEvent)> {
+    let kind = match event.kind {
+        EventKind::Create(_) => ChangeEvent::Create,
+        EventKind::Remove(_) => ChangeEvent::Delete,
+        EventKind::Modify(ModifyKind::Name(RenameMode::From)) => ChangeEvent::Delete,
+        EventKind::Modify(ModifyKind::Name(RenameMode::To)) => ChangeEvent::Create,
+        EventKind::Modify(ModifyKind::Name(RenameMode::Both)) => {
+            let mut paths = event.paths.into_iter();
+            return paths
+                .next()
+                .map(|from| (from, ChangeEvent::Delete))
+                .into_iter()
+                .chain(paths.next().map(|to| (to, ChangeEvent::Create)))
+                .collect();
+        }
+        EventKind::Modify(ModifyKind::Name(_)) => {
+            return event
+                .paths
+                .into_iter()
+                .map(|path| {
+                    let kind = if path.exists() {
+                        ChangeEvent::Create
+                    } else {
+                        ChangeEvent::Delete
+                    };
+                    (path, kind)
+                })
+                .collect();
+        }
+        EventKind::Modify(ModifyKind::Metadata(_)) => return vec![],
+        EventKind::Modify(_) => ChangeEvent::Modify,
+        EventKind::Access(_) | EventKind::Any | EventKind::Other => return vec![],
+    };
+    event.paths.into_iter().map(|path| (path, kind)).collect()
+}
+
+/// Adds a change to those pending delivery, combining it with any earlier
+/// change to the same path.
+fn coalesce(pending: &mut BTreeMap<PathBuf, ChangeEvent>, path: PathBuf, kind: ChangeEvent) {
+    use ChangeEvent::*;
+    let combined = match (pending.get(&path), kind) {
+        (Some(Create), Modify) => Some(Create),
+        (Some(Create), Delete) => None,
+        (Some(Delete), Create) => Some(Modify),
+        _ => Some(kind),
+    };
+    match combined {
+        Some(kind) => pending.insert(path, kind),
+        None => pending.remove(&path),
+    };
+}
+
+/// The file watch executor trait.
+/// All file watch executors must implement this trait.
+#[async_trait]
+pub(crate) trait FileWatchExecutor: Clone + Send + Sync + 'static {
+    async fn execute(
+        &self,
+        engine: &TriggerAppEngine<FileWatchTrigger>,
+        component_id: &str,
+        changes: Vec<Change>,
+    ) -> Result<()>;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn changes_are_coalesced_per_path() {
+        let mut pending = BTreeMap::new();
+        let path = |name: &str| PathBuf::from(name);
+        coalesce(&mut pending, path("new.txt"), ChangeEvent::Create);
+        coalesce(&mut pending, path("new.txt"), ChangeEvent::Modify);
+        coalesce(&mut pending, path("temp.txt"), ChangeEvent::Create);
+        coalesce(&mut pending, path("temp.txt"), ChangeEvent::Delete);
+        coalesce(&mut pending, path("saved.txt"), ChangeEvent::Delete);
+        coalesce(&mut pending, path("saved.txt"), ChangeEvent::Create);
+        coalesce(&mut pending, path("gone.txt"), ChangeEvent::Modify);
+        coalesce(&mut pending, path("gone.txt"), ChangeEvent::Delete);
+
+        let pending: Vec<_> = pending.into_iter().collect();
+        assert_eq!(
+            pending,
+            [
+                (path("gone.txt"), ChangeEvent::Delete),
+                (path("new.txt"), ChangeEvent::Create),
+                (path("saved.txt"), ChangeEvent::Modify),
+            ]
+        );
+    }
+
+    #[test]
+    fn glob_root_stops_at_first_wildcard() {
+        assert_eq!(glob_root("data/*.csv"), Path::new("data"));
+        assert_eq!(glob_root("content/**/*.md"), Path::new("content"));
+        assert_eq!(glob_root("config.toml"), Path::new("config.toml"));
+        assert_eq!(glob_root("**/*.json"), Path::new(""));
+    }
+
+    #[test]
+    fn watch_roots_are_not_nested() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir_all(dir.path().join("content/posts")).unwrap();
+        std::fs::create_dir(dir.path().join("static")).unwrap();
+        let config = FileWatchTriggerConfig {
+            component: "site".into(),
+            paths: vec![
+                "content/posts/*.md".into(),
+                "content/**/*.toml".into(),
+                "static/*.css".into(),
+            ],
+            events: default_events(),
+            ..Default::default()
+        };
+        let watch = Watch::new(dir.path(), &config).unwrap();
+        assert_eq!(
+            watch.roots,
+            [dir.path().join("content"), dir.path().join("static")]
+        );
+        assert!(watch.matches(&dir.path().join("content/posts/hello.md")));
+        assert!(!watch.matches(&dir.path().join("content/posts/hello.txt")));
+    }
+}
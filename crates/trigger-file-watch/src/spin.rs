@@ -0,0 +1,56 @@
+use anyhow::{anyhow, Result};
+use async_trait::async_trait;
+use spin_core::Instance;
+use spin_trigger::TriggerAppEngine;
+use spin_world::v2::file_watch_types::{Change, Error};
+
+use crate::{FileWatchExecutor, FileWatchTrigger, Store};
+
+#[derive(Clone)]
+pub struct SpinFileWatchExecutor;
+
+#[async_trait]
+impl FileWatchExecutor for SpinFileWatchExecutor {
+    async fn execute(
+        &self,
+        engine: &TriggerAppEngine<FileWatchTrigger>,
+        component_id: &str,
+        changes: Vec<Change>,
+    ) -> Result<()> {
+        tracing::trace!("Executing request using the Spin executor for component {component_id}");
+
+        let _permit = engine.acquire_permit(component_id).await?;
+
+        let (instance, store) = engine.prepare_instance(component_id).await?;
+
+        match Self::execute_impl(store, instance, changes).await {
+            Ok(()) => {
+                tracing::trace!("Request finished OK");
+                Ok(())
+            }
+            Err(e) => {
+                tracing::trace!("Request finished with error from {component_id}: {e}");
+                Err(anyhow!("Error from {component_id}: {e}"))
+            }
+        }
+    }
+}
+
+impl SpinFileWatchExecutor {
+    pub async fn execute_impl(
+        mut store: Store,
+        instance: Instance,
+        changes: Vec<Change>,
+    ) -> Result<()> {
+        let func = instance
+            .exports(&mut store)
+            .instance("fermyon:spin/inbound-file-watch@2.0.0")
+            .ok_or_else(|| anyhow!("no fermyon:spin/inbound-file-watch@2.0.0 instance found"))?
+            .typed_func::<(Vec<Change>,), (Result<(), Error>,)>("handle-changes")?;
+
+        match func.call_async(store, (changes,)).await? {
+            (Ok(()),) => Ok(()),
+            (Err(e),) => Err(anyhow!("`handle-changes` returned an error: {e:?}")),
+        }
+    }
+}
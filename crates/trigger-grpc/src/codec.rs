@@ -0,0 +1,196 @@
+//! gRPC message framing and status reporting over HTTP/2.
+
+use std::convert::Infallible;
+
+use anyhow::{bail, Result};
+use bytes::{Buf, BufMut, Bytes, BytesMut};
+use futures::Stream;
+use http::{HeaderMap, HeaderName, HeaderValue};
+use http_body_util::{combinators::UnsyncBoxBody, BodyExt, StreamBody};
+use hyper::body::Frame;
+use percent_encoding::{utf8_percent_encode, AsciiSet, CONTROLS};
+use spin_world::v2::grpc_types::{Metadata, Status, StatusCode};
+
+pub(crate) type Body = UnsyncBoxBody<Bytes, Infallible>;
+
+pub(crate) const GRPC_CONTENT_TYPE: &str = "application/grpc";
+
+/// The length of the prefix before each message: a compression flag and a
+/// big-endian length.
+const PREFIX_LEN: usize = 5;
+
+/// Characters which are percent-encoded in `grpc-message`.
+const GRPC_MESSAGE: &AsciiSet = &CONTROLS.add(b'%');
+
+/// Splits a stream of body data into length-prefixed messages.
+#[derive(Default)]
+pub(crate) struct MessageDecoder {
+    buf: BytesMut,
+}
+
+impl MessageDecoder {
+    pub fn push(&mut self, data: &[u8]) {
+        self.buf.extend_from_slice(data);
+    }
+
+    /// Returns the next complete message, if there is one.
+    pub fn next_message(&mut self) -> Result<Option<Bytes>> {
+        if self.buf.len() < PREFIX_LEN {
+            return Ok(None);
+        }
+        let compressed = self.buf[0];
+        let len = u32::from_be_bytes(self.buf[1..PREFIX_LEN].try_into().unwrap()) as usize;
+        if compressed != 0 {
+            bail!("compressed messages are not supported");
+        }
+        if self.buf.len() < PREFIX_LEN + len {
+            return Ok(None);
+        }
+        self.buf.advance(PREFIX_LEN);
+        Ok(Some(self.buf.split_to(len).freeze()))
+    }
+
+    /// Whether a partial message remains.
+    pub fn is_empty(&self) -> bool {
+        self.buf.is_empty()
+    }
+}
+
+/// Prefixes a message for sending.
+pub(crate) fn encode_message(message: &[u8]) -> Bytes {
+    let mut buf = BytesMut::with_capacity(PREFIX_LEN + message.len());
+    buf.put_u8(0);
+    buf.put_u32(message.len() as u32);
+    buf.put_slice(message);
+    buf.freeze()
+}
+
+/// The numeric code of a status sent in `grpc-status`.
+pub(crate) fn status_code_number(code: StatusCode) -> u32 {
+    match code {
+        StatusCode::Cancelled => 1,
+        StatusCode::Unknown => 2,
+        StatusCode::InvalidArgument => 3,
+        StatusCode::DeadlineExceeded => 4,
+        StatusCode::NotFound => 5,
+        StatusCode::AlreadyExists => 6,
+        StatusCode::PermissionDenied => 7,
+        StatusCode::ResourceExhausted => 8,
+        StatusCode::FailedPrecondition => 9,
+        StatusCode::Aborted => 10,
+        StatusCode::OutOfRange => 11,
+        StatusCode::Unimplemented => 12,
+        StatusCode::Internal => 13,
+        StatusCode::Unavailable => 14,
+        StatusCode::DataLoss => 15,
+        StatusCode::Unauthenticated => 16,
+    }
+}
+
+pub(crate) fn status(code: StatusCode, message: impl Into<String>) -> Status {
+    Status {
+        code,
+        message: message.into(),
+    }
+}
+
+/// The trailers ending a call, with `None` for success.
+pub(crate) fn status_trailers(status: Option<&Status>) -> HeaderMap {
+    let mut trailers = HeaderMap::new();
+    let code = status.map_or(0, |status| status_code_number(status.code));
+    trailers.insert("grpc-status", HeaderValue::from(code));
+    if let Some(status) = status.filter(|status| !status.message.is_empty()) {
+        let message = utf8_percent_encode(&status.message, GRPC_MESSAGE).to_string();
+        // Percent-encoding leaves only visible ASCII, which is always valid
+        trailers.insert("grpc-message", HeaderValue::try_from(message).unwrap());
+    }
+    trailers
+}
+
+/// Converts request headers to guest metadata, leaving out those which are
+/// part of the gRPC protocol itself.
+pub(crate) fn to_metadata(headers: &HeaderMap) -> Metadata {
+    headers
+        .iter()
+        .filter(|(name, _)| {
+            let name = name.as_str();
+            !(name.starts_with("grpc-") || matches!(name, "content-type" | "te" | "content-length"))
+        })
+        .filter_map(|(name, value)| Some((name.to_string(), value.to_str().ok()?.to_owned())))
+        .collect()
+}
+
+/// Adds guest metadata to response headers, skipping invalid entries.
+pub(crate) fn add_metadata(headers: &mut HeaderMap, metadata: &Metadata) {
+    for (name, value) in metadata {
+        match (
+            HeaderName::try_from(name.as_str()),
+            HeaderValue::try_from(value.as_str()),
+        ) {
+            (Ok(name), Ok(value)) => {
+                headers.append(name, value);
+            }
+            _ => tracing::warn!("Ignoring invalid gRPC response metadata {name:?}"),
+        }
+    }
+}
+
+/// A complete response body of messages followed by the status.
+pub(crate) fn messages_body(messages: &[Vec<u8>], status: Option<&Status>) -> Body {
+    let frames: Vec<_> = messages
+        .iter()
+        .map(|message| Frame::data(encode_message(message)))
+        .chain([Frame::trailers(status_trailers(status))])
+        .collect();
+    stream_body(futures::stream::iter(frames))
+}
+
+pub(crate) fn stream_body(frames: impl Stream<Item = Frame<Bytes>> + Send + 'static) -> Body {
+    use futures::StreamExt;
+    StreamBody::new(frames.map(Ok)).boxed_unsync()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn messages_are_split_across_pushes() {
+        let mut data = encode_message(b"hello").to_vec();
+        data.extend_from_slice(&encode_message(b""));
+        data.extend_from_slice(&encode_message(b"world"));
+
+        let mut decoder = MessageDecoder::default();
+        decoder.push(&data[..3]);
+        assert_eq!(decoder.next_message().unwrap(), None);
+        decoder.push(&data[3..12]);
+        assert_eq!(decoder.next_message().unwrap().unwrap(), "hello");
+        assert_eq!(decoder.next_message().unwrap().unwrap(), "");
+        assert_eq!(decoder.next_message().unwrap(), None);
+        assert!(!decoder.is_empty());
+        decoder.push(&data[12..]);
+        assert_eq!(decoder.next_message().unwrap().unwrap(), "world");
+        assert!(decoder.is_empty());
+    }
+
+    #[test]
+    fn compressed_messages_are_rejected() {
+        let mut decoder = MessageDecoder::default();
+        decoder.push(&[1, 0, 0, 0, 1, 42]);
+        assert!(decoder.next_message().is_err());
+    }
+
+    #[test]
+    fn status_message_is_percent_encoded() {
+        let trailers = status_trailers(Some(&status(
+            StatusCode::NotFound,
+            "no such user: 100% über",
+        )));
+        assert_eq!(trailers["grpc-status"], "5");
+        assert_eq!(trailers["grpc-message"], "no such user: 100%25 %C3%BCber");
+
+        let trailers = status_trailers(None);
+        assert_eq!(trailers["grpc-status"], "0");
+        assert!(trailers.get("grpc-message").is_none());
+    }
+}
@@ -0,0 +1,262 @@
+//! Protobuf descriptors of the services served by the trigger.
+
+use std::collections::HashMap;
+use std::path::Path;
+
+use anyhow::{Context, Result};
+use prost::Message;
+use prost_types::{DescriptorProto, FileDescriptorProto, FileDescriptorSet};
+use spin_world::v2::grpc_types::MethodKind;
+
+/// A method of a service in a descriptor.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct MethodDescriptor {
+    pub service: String,
+    pub method: String,
+    pub kind: MethodKind,
+}
+
+impl MethodDescriptor {
+    /// The HTTP path at which the method is called.
+    pub fn path(&self) -> String {
+        format!("/{}/{}", self.service, self.method)
+    }
+}
+
+/// The files of all descriptors loaded by the trigger, indexed for
+/// reflection.
+#[derive(Debug, Default)]
+pub(crate) struct DescriptorPool {
+    files: HashMap<String, FileDescriptorProto>,
+    /// Fully-qualified symbol name to the name of the file defining it
+    symbols: HashMap<String, String>,
+}
+
+/// Reads a binary `FileDescriptorSet`, as written by
+/// `protoc --include_imports --descriptor_set_out`.
+pub(crate) fn read_descriptor_set(path: &Path) -> Result<FileDescriptorSet> {
+    let bytes = std::fs::read(path)
+        .with_context(|| format!("Failed to read descriptor set {}", path.display()))?;
+    FileDescriptorSet::decode(bytes.as_slice())
+        .with_context(|| format!("Invalid descriptor set {}", path.display()))
+}
+
+/// The methods of the named service, or `None` if the descriptor set does
+/// not define it. Client-streaming methods are left out with a warning.
+pub(crate) fn service_methods(
+    set: &FileDescriptorSet,
+    service: &str,
+) -> Option<Vec<MethodDescriptor>> {
+    let descriptor = set
+        .file
+        .iter()
+        .flat_map(|file| file.service.iter().map(move |svc| (file, svc)))
+        .find(|(file, svc)| qualified(file.package(), svc.name()) == service)
+        .map(|(_, svc)| svc)?;
+    let methods = descriptor
+        .method
+        .iter()
+        .filter_map(|method| {
+            if method.client_streaming() {
+                tracing::warn!(
+                    "Skipping client-streaming method {service}/{}: only unary and server-streaming methods are supported",
+                    method.name()
+                );
+                return None;
+            }
+            let kind = if method.server_streaming() {
+                MethodKind::ServerStreaming
+            } else {
+                MethodKind::Unary
+            };
+            Some(MethodDescriptor {
+                service: service.to_owned(),
+                method: method.name().to_owned(),
+                kind,
+            })
+        })
+        .collect();
+    Some(methods)
+}
+
+/// The fully-qualified names of all services in a descriptor set.
+pub(crate) fn service_names(set: &FileDescriptorSet) -> Vec<String> {
+    set.file
+        .iter()
+        .flat_map(|file| {
+            file.service
+                .iter()
+                .map(|svc| qualified(file.package(), svc.name()))
+        })
+        .collect()
+}
+
+impl DescriptorPool {
+    pub fn add(&mut self, set: FileDescriptorSet) {
+        for file in set.file {
+            let name = file.name().to_owned();
+            let package = file.package();
+            for service in &file.service {
+                let service_name = qualified(package, service.name());
+                for method in &service.method {
+                    self.add_symbol(qualified(&service_name, method.name()), &name);
+                }
+                self.add_symbol(service_name, &name);
+            }
+            for message in &file.message_type {
+                self.add_message(package, message, &name);
+            }
+            for enum_type in &file.enum_type {
+                self.add_symbol(qualified(package, enum_type.name()), &name);
+            }
+            self.files.insert(name, file);
+        }
+    }
+
+    fn add_message(&mut self, scope: &str, message: &DescriptorProto, file: &str) {
+        let name = qualified(scope, message.name());
+        for nested in &message.nested_type {
+            self.add_message(&name, nested, file);
+        }
+        for enum_type in &message.enum_type {
+            self.add_symbol(qualified(&name, enum_type.name()), file);
+        }
+        self.add_symbol(name, file);
+    }
+
+    fn add_symbol(&mut self, symbol: String, file: &str) {
+        self.symbols.insert(symbol, file.to_owned());
+    }
+
+    /// The encoded file with the given name and all its dependencies, or
+    /// `None` if there is no such file.
+    pub fn file_by_name(&self, name: &str) -> Option<Vec<Vec<u8>>> {
+        if !self.files.contains_key(name) {
+            return None;
+        }
+        let mut encoded = vec![];
+        let mut seen = vec![];
+        let mut pending = vec![name];
+        while let Some(name) = pending.pop() {
+            if seen.contains(&name) {
+                continue;
+            }
+            seen.push(name);
+            // Dependencies missing from the set are the client's problem
+            if let Some(file) = self.files.get(name) {
+                encoded.push(file.encode_to_vec());
+                pending.extend(file.dependency.iter().map(String::as_str));
+            }
+        }
+        Some(encoded)
+    }
+
+    /// The encoded file defining the given symbol and all its dependencies.
+    pub fn file_containing_symbol(&self, symbol: &str) -> Option<Vec<Vec<u8>>> {
+        self.file_by_name(self.symbols.get(symbol)?)
+    }
+}
+
+fn qualified(scope: &str, name: &str) -> String {
+    if scope.is_empty() {
+        name.to_owned()
+    } else {
+        format!("{scope}.{name}")
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use prost_types::{MethodDescriptorProto, ServiceDescriptorProto};
+
+    /// A descriptor set like that of the gRPC `helloworld` example, with a
+    /// separately imported message file.
+    pub fn greeter_descriptor_set() -> FileDescriptorSet {
+        let method = |name: &str, server_streaming, client_streaming| MethodDescriptorProto {
+            name: Some(name.into()),
+            input_type: Some(".helloworld.HelloRequest".into()),
+            output_type: Some(".helloworld.HelloReply".into()),
+            server_streaming: Some(server_streaming),
+            client_streaming: Some(client_streaming),
+            ..Default::default()
+        };
+        let message = |name: &str| DescriptorProto {
+            name: Some(name.into()),
+            ..Default::default()
+        };
+        FileDescriptorSet {
+            file: vec![
+                FileDescriptorProto {
+                    name: Some("messages.proto".into()),
+                    package: Some("helloworld".into()),
+                    message_type: vec![message("HelloRequest"), message("HelloReply")],
+                    ..Default::default()
+                },
+                FileDescriptorProto {
+                    name: Some("greeter.proto".into()),
+                    package: Some("helloworld".into()),
+                    dependency: vec!["messages.proto".into()],
+                    service: vec![ServiceDescriptorProto {
+                        name: Some("Greeter".into()),
+                        method: vec![
+                            method("SayHello", false, false),
+                            method("SayHellos", true, false),
+                            method("Chat", true, true),
+                        ],
+                        ..Default::default()
+                    }],
+                    ..Default::default()
+                },
+            ],
+        }
+    }
+
+    #[test]
+    fn client_streaming_methods_are_skipped() {
+        let set = greeter_descriptor_set();
+        assert_eq!(service_names(&set), ["helloworld.Greeter"]);
+        assert!(service_methods(&set, "helloworld.Missing").is_none());
+
+        let methods = service_methods(&set, "helloworld.Greeter").unwrap();
+        let summary: Vec<_> = methods.iter().map(|m| (m.path(), m.kind)).collect();
+        assert_eq!(
+            summary,
+            [
+                ("/helloworld.Greeter/SayHello".to_owned(), MethodKind::Unary),
+                (
+                    "/helloworld.Greeter/SayHellos".to_owned(),
+                    MethodKind::ServerStreaming
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn symbols_resolve_to_file_with_dependencies() {
+        let mut pool = DescriptorPool::default();
+        pool.add(greeter_descriptor_set());
+
+        let files = pool
+            .file_containing_symbol("helloworld.Greeter.SayHello")
+            .unwrap();
+        let names: Vec<_> = files
+            .iter()
+            .map(|bytes| {
+                FileDescriptorProto::decode(bytes.as_slice())
+                    .unwrap()
+                    .name
+                    .unwrap()
+            })
+            .collect();
+        assert_eq!(names, ["greeter.proto", "messages.proto"]);
+
+        assert_eq!(
+            pool.file_containing_symbol("helloworld.HelloReply")
+                .unwrap()
+                .len(),
+            1
+        );
+        assert!(pool.file_containing_symbol("helloworld.Missing").is_none());
+    }
+}
@@ -0,0 +1,324 @@
+//! Implementation for the Spin gRPC trigger.
+//!
+//! The gRPC trigger serves the services described by each component's
+//! protobuf descriptor set over cleartext HTTP/2. Messages are passed to the
+//! component as encoded bytes, so no code is generated for the services.
+
+mod codec;
+mod descriptor;
+mod reflection;
+mod spin;
+
+use std::collections::{BTreeMap, HashMap};
+use std::convert::Infallible;
+use std::net::SocketAddr;
+use std::sync::Arc;
+
+use anyhow::{bail, Context, Result};
+use bytes::Bytes;
+use clap::Args;
+use http::{header::CONTENT_TYPE, HeaderMap, HeaderValue, Method};
+use http_body_util::{BodyExt, Empty};
+use hyper::{body::Incoming, server::conn::http2, service::service_fn, Request, Response};
+use hyper_util::rt::{TokioExecutor, TokioIo};
+use serde::{de::IgnoredAny, Deserialize, Serialize};
+use spin_core::{async_trait, InstancePre};
+use spin_trigger::{cli::env, Overloaded, TriggerAppEngine, TriggerExecutor};
+use spin_world::v2::grpc_types::{
+    MethodKind, Request as GrpcRequest, Response as GrpcResponse, Status, StatusCode,
+};
+use tokio::net::{TcpListener, TcpStream};
+
+use crate::codec::{status, Body, MessageDecoder, GRPC_CONTENT_TYPE};
+use crate::descriptor::{DescriptorPool, MethodDescriptor};
+use crate::reflection::{Reflection, REFLECTION_PATHS};
+use crate::spin::SpinGrpcExecutor;
+
+pub(crate) type RuntimeData = ();
+pub(crate) type Store = spin_core::Store<RuntimeData>;
+
+/// The Spin gRPC trigger.
+pub struct GrpcTrigger {
+    engine: TriggerAppEngine<Self>,
+    // Mapping of method path to the method and the component serving it
+    routes: HashMap<String, Route>,
+    // Mapping of service name to the component serving it
+    services: BTreeMap<String, String>,
+    reflection: Option<Arc<Reflection>>,
+}
+
+/// gRPC trigger configuration.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct GrpcTriggerConfig {
+    /// Component ID to invoke
+    pub component: String,
+    /// Path to a binary protobuf descriptor set of the component's services,
+    /// relative to the application directory
+    pub descriptor: String,
+    /// Fully-qualified names of the services the component serves; all
+    /// services in the descriptor set if empty
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub services: Vec<String>,
+    /// Trigger executor (currently unused)
+    #[serde(default, skip_serializing)]
+    pub executor: IgnoredAny,
+}
+
+#[derive(Args)]
+pub struct CliArgs {
+    /// IP address and port to listen on
+    #[clap(long = "listen", env = env::GRPC_LISTEN, default_value = "127.0.0.1:50051")]
+    pub address: SocketAddr,
+
+    /// Serve the gRPC reflection service, so that tools such as grpcurl can
+    /// discover the application's services.
+    #[clap(long = "reflection", env = env::GRPC_REFLECTION, takes_value = false)]
+    pub reflection: bool,
+}
+
+/// A method and the component which serves it.
+#[derive(Clone, Debug)]
+struct Route {
+    component: String,
+    method: MethodDescriptor,
+}
+
+#[async_trait]
+impl TriggerExecutor for GrpcTrigger {
+    const TRIGGER_TYPE: &'static str = "grpc";
+    type RuntimeData = RuntimeData;
+    type TriggerConfig = GrpcTriggerConfig;
+    type RunConfig = CliArgs;
+    type InstancePre = InstancePre<RuntimeData>;
+
+    async fn new(engine: TriggerAppEngine<Self>) -> Result<Self> {
+        // Without a manifest directory, descriptors are relative to the working directory
+        let app_dir = engine.app_dir()?.unwrap_or_default();
+
+        let mut routes = HashMap::new();
+        let mut services = BTreeMap::new();
+        let mut pool = DescriptorPool::default();
+        for (_, config) in engine.trigger_configs() {
+            let component = &config.component;
+            let set = descriptor::read_descriptor_set(&app_dir.join(&config.descriptor))
+                .with_context(|| format!("gRPC trigger for component '{component}'"))?;
+            let names = if config.services.is_empty() {
+                descriptor::service_names(&set)
+            } else {
+                config.services.clone()
+            };
+            if names.is_empty() {
+                bail!(
+                    "gRPC trigger for component '{component}': descriptor set defines no services"
+                );
+            }
+            for service in names {
+                let Some(methods) = descriptor::service_methods(&set, &service) else {
+                    bail!("gRPC trigger for component '{component}': descriptor set does not define service {service}");
+                };
+                if let Some(other) = services.insert(service.clone(), component.clone()) {
+                    bail!("gRPC service {service} is served by both component '{other}' and component '{component}'");
+                }
+                for method in methods {
+                    let route = Route {
+                        component: component.clone(),
+                        method,
+                    };
+                    routes.insert(route.method.path(), route);
+                }
+            }
+            pool.add(set);
+        }
+
+        let reflection = Reflection::new(pool, services.keys().cloned().collect());
+        Ok(Self {
+            engine,
+            routes,
+            services,
+            reflection: Some(Arc::new(reflection)),
+        })
+    }
+
+    /// Run the gRPC trigger indefinitely.
+    async fn run(mut self, config: Self::RunConfig) -> Result<()> {
+        if !config.reflection {
+            self.reflection = None;
+        }
+        let listener = TcpListener::bind(config.address)
+            .await
+            .with_context(|| format!("Unable to listen on {}", config.address))?;
+        self.engine.listening()?;
+
+        println!("Serving gRPC on {}", config.address);
+        println!("Available services:");
+        for (service, component) in &self.services {
+            println!("\t{service}: [{component}]");
+            self.engine
+                .status()
+                .add_subscription(component, format!("service {service}"));
+        }
+        if self.reflection.is_some() {
+            println!("Server reflection is enabled");
+        }
+
+        let self_ = Arc::new(self);
+        loop {
+            let (stream, addr) = listener.accept().await?;
+            Self::serve_connection(self_.clone(), stream, addr);
+        }
+    }
+
+    fn listens() -> bool {
+        true
+    }
+}
+
+impl GrpcTrigger {
+    fn serve_connection(self_: Arc<Self>, stream: TcpStream, addr: SocketAddr) {
+        tokio::spawn(async move {
+            let service = service_fn(move |request| {
+                let self_ = self_.clone();
+                async move { Ok::<_, Infallible>(self_.handle(request).await) }
+            });
+            let result = http2::Builder::new(TokioExecutor::new())
+                .serve_connection(TokioIo::new(stream), service)
+                .await;
+            if let Err(e) = result {
+                tracing::warn!("Error serving gRPC connection from {addr}: {e:?}");
+            }
+        });
+    }
+
+    async fn handle(&self, request: Request<Incoming>) -> Response<Body> {
+        let content_type = request
+            .headers()
+            .get(CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok());
+        let is_grpc = matches!(content_type, Some(ct) if ct.starts_with(GRPC_CONTENT_TYPE));
+        if request.method() != Method::POST || !is_grpc {
+            let mut response = Response::new(Empty::new().boxed_unsync());
+            *response.status_mut() = http::StatusCode::UNSUPPORTED_MEDIA_TYPE;
+            return response;
+        }
+
+        let path = request.uri().path();
+        if let Some(reflection) = &self.reflection {
+            if REFLECTION_PATHS.contains(&path) {
+                let body = reflection::serve(reflection.clone(), request.into_body());
+                return grpc_response(HeaderMap::new(), body);
+            }
+        }
+        match self.routes.get(path) {
+            Some(route) => self.call(route, request).await,
+            None => {
+                let status = status(
+                    StatusCode::Unimplemented,
+                    format!("method {path} is not served"),
+                );
+                grpc_error(&status)
+            }
+        }
+    }
+
+    async fn call(&self, route: &Route, request: Request<Incoming>) -> Response<Body> {
+        let MethodDescriptor {
+            service,
+            method,
+            kind,
+        } = &route.method;
+        tracing::info!("Received gRPC call {service}/{method}");
+
+        let metadata = codec::to_metadata(request.headers());
+        let message = match read_single_message(request.into_body()).await {
+            Ok(message) => message,
+            Err(status) => return grpc_error(&status),
+        };
+        let request = GrpcRequest {
+            service: service.clone(),
+            method: method.clone(),
+            kind: *kind,
+            metadata,
+            message: message.to_vec(),
+        };
+
+        tracing::trace!("Executing gRPC component {:?}", route.component);
+        match SpinGrpcExecutor
+            .execute(&self.engine, &route.component, request)
+            .await
+        {
+            Ok(Ok(response)) => {
+                if *kind == MethodKind::Unary && response.messages.len() != 1 {
+                    let count = response.messages.len();
+                    tracing::warn!(
+                        "Component returned {count} messages for unary method {service}/{method}"
+                    );
+                    return grpc_error(&status(
+                        StatusCode::Internal,
+                        format!("expected one response message, got {count}"),
+                    ));
+                }
+                let mut headers = HeaderMap::new();
+                codec::add_metadata(&mut headers, &response.metadata);
+                grpc_response(headers, codec::messages_body(&response.messages, None))
+            }
+            Ok(Err(status)) => grpc_error(&status),
+            Err(err) if err.is::<Overloaded>() => {
+                tracing::warn!("{err}");
+                grpc_error(&status(
+                    StatusCode::ResourceExhausted,
+                    "server is overloaded",
+                ))
+            }
+            Err(err) => {
+                tracing::warn!("Error handling gRPC call: {err}");
+                self.engine.status().record_error(&route.component, &err);
+                grpc_error(&status(StatusCode::Internal, "internal error"))
+            }
+        }
+    }
+}
+
+/// Reads the request message of a unary or server-streaming call.
+async fn read_single_message(body: Incoming) -> Result<Bytes, Status> {
+    let data = body
+        .collect()
+        .await
+        .map_err(|e| status(StatusCode::Cancelled, e.to_string()))?
+        .to_bytes();
+    let mut decoder = MessageDecoder::default();
+    decoder.push(&data);
+    let message = decoder
+        .next_message()
+        .map_err(|e| status(StatusCode::Unimplemented, e.to_string()))?;
+    match message {
+        Some(message) if decoder.is_empty() => Ok(message),
+        _ => Err(status(
+            StatusCode::Internal,
+            "expected exactly one request message",
+        )),
+    }
+}
+
+fn grpc_response(mut headers: HeaderMap, body: Body) -> Response<Body> {
+    headers.insert(CONTENT_TYPE, HeaderValue::from_static(GRPC_CONTENT_TYPE));
+    let mut response = Response::new(body);
+    *response.headers_mut() = headers;
+    response
+}
+
+fn grpc_error(status: &Status) -> Response<Body> {
+    grpc_response(HeaderMap::new(), codec::messages_body(&[], Some(status)))
+}
+
+/// The gRPC executor trait.
+/// All gRPC executors must implement this trait.
+#[async_trait]
+pub(crate) trait GrpcExecutor: Clone + Send + Sync + 'static {
+    async fn execute(
+        &self,
+        engine: &TriggerAppEngine<GrpcTrigger>,
+        component_id: &str,
+        request: GrpcRequest,
+    ) -> Result<Result<GrpcResponse, Status>>;
+}
@@ -0,0 +1,249 @@
+//! The gRPC server reflection service, which lets tools such as `grpcurl`
+//! discover the trigger's services.
+
+use std::sync::Arc;
+
+use futures::channel::mpsc::{self, UnboundedSender};
+use http_body_util::BodyExt;
+use hyper::body::{Bytes, Frame, Incoming};
+use prost::Message;
+use spin_world::v2::grpc_types::{Status, StatusCode};
+
+use crate::codec::{self, status, Body, MessageDecoder};
+use crate::descriptor::DescriptorPool;
+
+/// The paths of the reflection method, in its current and older versions.
+pub(crate) const REFLECTION_PATHS: [&str; 2] = [
+    "/grpc.reflection.v1.ServerReflection/ServerReflectionInfo",
+    "/grpc.reflection.v1alpha.ServerReflection/ServerReflectionInfo",
+];
+
+const REFLECTION_SERVICES: [&str; 2] = [
+    "grpc.reflection.v1.ServerReflection",
+    "grpc.reflection.v1alpha.ServerReflection",
+];
+
+const NOT_FOUND: i32 = 5;
+const UNIMPLEMENTED: i32 = 12;
+
+#[derive(Clone, PartialEq, Message)]
+pub(crate) struct ServerReflectionRequest {
+    #[prost(string, tag = "1")]
+    pub host: String,
+    #[prost(oneof = "MessageRequest", tags = "3, 4, 5, 6, 7")]
+    pub message_request: Option<MessageRequest>,
+}
+
+#[derive(Clone, PartialEq, prost::Oneof)]
+pub(crate) enum MessageRequest {
+    #[prost(string, tag = "3")]
+    FileByFilename(String),
+    #[prost(string, tag = "4")]
+    FileContainingSymbol(String),
+    #[prost(message, tag = "5")]
+    FileContainingExtension(ExtensionRequest),
+    #[prost(string, tag = "6")]
+    AllExtensionNumbersOfType(String),
+    #[prost(string, tag = "7")]
+    ListServices(String),
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub(crate) struct ExtensionRequest {
+    #[prost(string, tag = "1")]
+    pub containing_type: String,
+    #[prost(int32, tag = "2")]
+    pub extension_number: i32,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub(crate) struct ServerReflectionResponse {
+    #[prost(string, tag = "1")]
+    pub valid_host: String,
+    #[prost(message, optional, tag = "2")]
+    pub original_request: Option<ServerReflectionRequest>,
+    #[prost(oneof = "MessageResponse", tags = "4, 6, 7")]
+    pub message_response: Option<MessageResponse>,
+}
+
+#[derive(Clone, PartialEq, prost::Oneof)]
+pub(crate) enum MessageResponse {
+    #[prost(message, tag = "4")]
+    FileDescriptorResponse(FileDescriptorResponse),
+    #[prost(message, tag = "6")]
+    ListServicesResponse(ListServiceResponse),
+    #[prost(message, tag = "7")]
+    ErrorResponse(ErrorResponse),
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub(crate) struct FileDescriptorResponse {
+    #[prost(bytes = "vec", repeated, tag = "1")]
+    pub file_descriptor_proto: Vec<Vec<u8>>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub(crate) struct ListServiceResponse {
+    #[prost(message, repeated, tag = "1")]
+    pub service: Vec<ServiceResponse>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub(crate) struct ServiceResponse {
+    #[prost(string, tag = "1")]
+    pub name: String,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub(crate) struct ErrorResponse {
+    #[prost(int32, tag = "1")]
+    pub error_code: i32,
+    #[prost(string, tag = "2")]
+    pub error_message: String,
+}
+
+/// Answers reflection requests from the descriptors of the served services.
+pub(crate) struct Reflection {
+    pool: DescriptorPool,
+    services: Vec<String>,
+}
+
+impl Reflection {
+    pub fn new(pool: DescriptorPool, mut services: Vec<String>) -> Self {
+        services.extend(REFLECTION_SERVICES.map(String::from));
+        services.sort();
+        Self { pool, services }
+    }
+
+    pub fn respond(&self, request: ServerReflectionRequest) -> ServerReflectionResponse {
+        let not_found = |what: &str, name: &str| {
+            MessageResponse::ErrorResponse(ErrorResponse {
+                error_code: NOT_FOUND,
+                error_message: format!("{what} {name:?} not found"),
+            })
+        };
+        let files = |files: Vec<Vec<u8>>| {
+            MessageResponse::FileDescriptorResponse(FileDescriptorResponse {
+                file_descriptor_proto: files,
+            })
+        };
+        let response = match &request.message_request {
+            Some(MessageRequest::ListServices(_)) => {
+                MessageResponse::ListServicesResponse(ListServiceResponse {
+                    service: self
+                        .services
+                        .iter()
+                        .map(|name| ServiceResponse { name: name.clone() })
+                        .collect(),
+                })
+            }
+            Some(MessageRequest::FileByFilename(name)) => match self.pool.file_by_name(name) {
+                Some(found) => files(found),
+                None => not_found("file", name),
+            },
+            Some(MessageRequest::FileContainingSymbol(symbol)) => {
+                match self.pool.file_containing_symbol(symbol) {
+                    Some(found) => files(found),
+                    None => not_found("symbol", symbol),
+                }
+            }
+            Some(_) | None => MessageResponse::ErrorResponse(ErrorResponse {
+                error_code: UNIMPLEMENTED,
+                error_message: "extension reflection is not supported".into(),
+            }),
+        };
+        ServerReflectionResponse {
+            valid_host: request.host.clone(),
+            original_request: Some(request),
+            message_response: Some(response),
+        }
+    }
+}
+
+/// Serves a reflection call, answering each request as it arrives.
+pub(crate) fn serve(reflection: Arc<Reflection>, mut body: Incoming) -> Body {
+    let (tx, rx) = mpsc::unbounded();
+    tokio::spawn(async move {
+        let status = respond_all(&reflection, &mut body, &tx).await.err();
+        _ = tx.unbounded_send(Frame::trailers(codec::status_trailers(status.as_ref())));
+    });
+    codec::stream_body(rx)
+}
+
+async fn respond_all(
+    reflection: &Reflection,
+    body: &mut Incoming,
+    tx: &UnboundedSender<Frame<Bytes>>,
+) -> Result<(), Status> {
+    let mut decoder = MessageDecoder::default();
+    while let Some(frame) = body.frame().await {
+        let frame = frame.map_err(|e| status(StatusCode::Cancelled, e.to_string()))?;
+        let Ok(data) = frame.into_data() else {
+            continue;
+        };
+        decoder.push(&data);
+        while let Some(message) = decoder
+            .next_message()
+            .map_err(|e| status(StatusCode::Unimplemented, e.to_string()))?
+        {
+            let request = ServerReflectionRequest::decode(message)
+                .map_err(|e| status(StatusCode::InvalidArgument, e.to_string()))?;
+            let response = reflection.respond(request).encode_to_vec();
+            if tx
+                .unbounded_send(Frame::data(codec::encode_message(&response)))
+                .is_err()
+            {
+                // The client has gone away
+                return Ok(());
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::descriptor::tests::greeter_descriptor_set;
+
+    fn reflection() -> Reflection {
+        let mut pool = DescriptorPool::default();
+        pool.add(greeter_descriptor_set());
+        Reflection::new(pool, vec!["helloworld.Greeter".into()])
+    }
+
+    fn request(message_request: MessageRequest) -> ServerReflectionRequest {
+        ServerReflectionRequest {
+            host: String::new(),
+            message_request: Some(message_request),
+        }
+    }
+
+    #[test]
+    fn lists_services_including_reflection() {
+        let response = reflection().respond(request(MessageRequest::ListServices(String::new())));
+        let Some(MessageResponse::ListServicesResponse(list)) = response.message_response else {
+            panic!("expected a service list, got {response:?}");
+        };
+        let names: Vec<_> = list.service.into_iter().map(|s| s.name).collect();
+        assert_eq!(
+            names,
+            [
+                "grpc.reflection.v1.ServerReflection",
+                "grpc.reflection.v1alpha.ServerReflection",
+                "helloworld.Greeter"
+            ]
+        );
+    }
+
+    #[test]
+    fn unknown_symbols_are_not_found() {
+        let response = reflection().respond(request(MessageRequest::FileContainingSymbol(
+            "helloworld.Farewell".into(),
+        )));
+        let Some(MessageResponse::ErrorResponse(error)) = response.message_response else {
+            panic!("expected an error, got {response:?}");
+        };
+        assert_eq!(error.error_code, NOT_FOUND);
+    }
+}
@@ -0,0 +1,54 @@
+use anyhow::{anyhow, Result};
+use async_trait::async_trait;
+use spin_core::Instance;
+use spin_trigger::TriggerAppEngine;
+use spin_world::v2::grpc_types::{Request, Response, Status};
+
+use crate::{GrpcExecutor, GrpcTrigger, Store};
+
+#[derive(Clone)]
+pub struct SpinGrpcExecutor;
+
+#[async_trait]
+impl GrpcExecutor for SpinGrpcExecutor {
+    async fn execute(
+        &self,
+        engine: &TriggerAppEngine<GrpcTrigger>,
+        component_id: &str,
+        request: Request,
+    ) -> Result<Result<Response, Status>> {
+        tracing::trace!("Executing request using the Spin executor for component {component_id}");
+
+        let _permit = engine.acquire_permit(component_id).await?;
+
+        let (instance, store) = engine.prepare_instance(component_id).await?;
+
+        match Self::execute_impl(store, instance, request).await {
+            Ok(result) => {
+                tracing::trace!("Request finished OK");
+                Ok(result)
+            }
+            Err(e) => {
+                tracing::trace!("Request finished with error from {component_id}: {e}");
+                Err(anyhow!("Error from {component_id}: {e}"))
+            }
+        }
+    }
+}
+
+impl SpinGrpcExecutor {
+    pub async fn execute_impl(
+        mut store: Store,
+        instance: Instance,
+        request: Request,
+    ) -> Result<Result<Response, Status>> {
+        let func = instance
+            .exports(&mut store)
+            .instance("fermyon:spin/inbound-grpc@2.0.0")
+            .ok_or_else(|| anyhow!("no fermyon:spin/inbound-grpc@2.0.0 instance found"))?
+            .typed_func::<(Request,), (Result<Response, Status>,)>("handle-request")?;
+
+        let (result,) = func.call_async(store, (request,)).await?;
+        Ok(result)
+    }
+}
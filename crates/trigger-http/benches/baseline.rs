@@ -2,10 +2,12 @@ use std::future::Future;
 use std::sync::atomic::{AtomicBool, Ordering::Relaxed};
 use std::sync::Arc;
 
-use criterion::{criterion_group, criterion_main, Criterion};
+use criterion::{criterion_group, criterion_main, Criterion, Throughput};
 
 use http::uri::Scheme;
 use http::Request;
+use http_body_util::BodyExt;
+use hyper::body::Bytes;
 use spin_testing::{assert_http_response_success, HttpTestConfig};
 use spin_trigger_http::HttpTrigger;
 use tokio::runtime::Runtime;
@@ -16,6 +18,9 @@ criterion_group!(
     bench_startup,
     bench_spin_concurrency_minimal,
     bench_wagi_concurrency_minimal,
+    bench_spin_payloads,
+    bench_wagi_payloads,
+    bench_wasi_payloads,
 );
 
 async fn spin_trigger() -> Arc<HttpTrigger> {
@@ -28,6 +33,16 @@ async fn spin_trigger() -> Arc<HttpTrigger> {
     )
 }
 
+async fn wasi_trigger() -> Arc<HttpTrigger> {
+    Arc::new(
+        HttpTestConfig::default()
+            .test_program("wasi-http-benchmark.wasm")
+            .http_spin_trigger("/")
+            .build_trigger()
+            .await,
+    )
+}
+
 async fn wagi_trigger() -> Arc<HttpTrigger> {
     Arc::new(
         HttpTestConfig::default()
@@ -98,6 +113,36 @@ fn bench_concurrency_minimal<F: Future<Output = Arc<HttpTrigger>>>(
     }
 }
 
+fn bench_spin_payloads(c: &mut Criterion) {
+    bench_payloads(c, "spin-executor", spin_trigger);
+}
+fn bench_wagi_payloads(c: &mut Criterion) {
+    bench_payloads(c, "spin-wagi-executor", wagi_trigger);
+}
+fn bench_wasi_payloads(c: &mut Criterion) {
+    bench_payloads(c, "wasi-http-executor", wasi_trigger);
+}
+
+// Benchmark throughput of request bodies echoed back as response bodies
+fn bench_payloads<F: Future<Output = Arc<HttpTrigger>>>(
+    c: &mut Criterion,
+    name: &str,
+    mk: fn() -> F,
+) {
+    let async_runtime = Runtime::new().unwrap();
+    let trigger = async_runtime.block_on(mk());
+
+    let mut group = c.benchmark_group(format!("{name}/?echo"));
+    for size in [64 << 10, 1 << 20, 16 << 20] {
+        let payload = Bytes::from(vec![b'x'; size]);
+        group.throughput(Throughput::Bytes(size as u64));
+        group.bench_function(format!("payload-{}KiB", size >> 10), |b| {
+            b.to_async(&async_runtime)
+                .iter(|| echo(&trigger, payload.clone()));
+        });
+    }
+}
+
 // Helpers
 
 fn concurrency_steps() -> [u32; 3] {
@@ -119,3 +164,17 @@ async fn run(trigger: &HttpTrigger, path: &str) {
         .unwrap();
     assert_http_response_success(&resp);
 }
+
+async fn echo(trigger: &HttpTrigger, payload: Bytes) {
+    let len = payload.len();
+    let req = Request::post("/?echo")
+        .body(spin_http::body::full(payload))
+        .unwrap();
+    let resp = trigger
+        .handle(req, Scheme::HTTP, "127.0.0.1:55555".parse().unwrap())
+        .await
+        .unwrap();
+    assert_http_response_success(&resp);
+    let body = resp.into_body().collect().await.unwrap().to_bytes();
+    assert_eq!(body.len(), len);
+}
@@ -13,8 +13,8 @@ struct SpinHttp;
 impl inbound_http::Guest for SpinHttp {
     fn handle_request(req: inbound_http::Request) -> inbound_http::Response {
         let params = req.uri.find('?').map(|i| &req.uri[i + 1..]).unwrap_or("");
+        let mut echo = false;
         for (key, value) in url::form_urlencoded::parse(params.as_bytes()) {
-            #[allow(clippy::single_match)]
             match &*key {
                 // sleep=<ms> param simulates processing time
                 "sleep" => {
@@ -28,13 +28,15 @@ impl inbound_http::Guest for SpinHttp {
                         do_some_work();
                     }
                 }
+                // echo param returns the request body
+                "echo" => echo = true,
                 _ => (),
             }
         }
         inbound_http::Response {
             status: 200,
             headers: None,
-            body: None,
+            body: if echo { req.body } else { None },
         }
     }
 }
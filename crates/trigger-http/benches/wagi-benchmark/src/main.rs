@@ -8,4 +8,9 @@ fn main() {
     }
 
     println!("Content-Type: text/plain\n");
+
+    // echo param returns the request body
+    if std::env::args().any(|arg| arg == "echo") {
+        std::io::copy(&mut std::io::stdin(), &mut std::io::stdout()).expect("echo failed");
+    }
 }
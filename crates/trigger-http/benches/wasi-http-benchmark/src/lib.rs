@@ -0,0 +1,28 @@
+use futures::{SinkExt, StreamExt};
+use spin_sdk::{
+    http::{Headers, IncomingRequest, OutgoingResponse, ResponseOutparam},
+    http_component,
+};
+
+#[http_component]
+async fn handle_request(request: IncomingRequest, response_out: ResponseOutparam) {
+    let response = OutgoingResponse::new(200, &Headers::new(&[]));
+    let mut body = response.take_body();
+    response_out.set(response);
+
+    // echo param streams the request body back as the response body
+    let echo = request
+        .path_with_query()
+        .and_then(|p| p.split_once('?').map(|(_, query)| query.to_owned()))
+        .is_some_and(|query| query.split('&').any(|param| param == "echo"));
+    if !echo {
+        return;
+    }
+    let mut stream = request.into_body_stream();
+    while let Some(chunk) = stream.next().await {
+        let Ok(chunk) = chunk else { break };
+        if body.send(chunk).await.is_err() {
+            break;
+        }
+    }
+}
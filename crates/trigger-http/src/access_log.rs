@@ -0,0 +1,534 @@
+//! Access logging for the HTTP trigger.
+
+use std::{
+    fs::{File, OpenOptions},
+    io::{self, Write},
+    net::{IpAddr, SocketAddr},
+    path::{Path, PathBuf},
+    pin::Pin,
+    sync::Arc,
+    task::{Context as TaskContext, Poll},
+    time::{Duration, Instant},
+};
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, Local, SecondsFormat};
+use http::header::{REFERER, USER_AGENT};
+use http_body_util::BodyExt;
+use hyper::{
+    body::{Body as _, Bytes, Frame, SizeHint},
+    Request, Response,
+};
+use serde::Deserialize;
+use tokio::sync::mpsc;
+use tracing::log;
+use wasmtime_wasi_http::bindings::http::types::ErrorCode;
+
+use crate::Body;
+
+/// The name of the access log file in the log directory.
+pub const ACCESS_LOG_FILE: &str = "http_access.log";
+
+/// Access logging, read from the `[http_trigger.access_log]` runtime config
+/// table. Requests are logged only if the table is present.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AccessLogConfig {
+    /// The format of log entries.
+    pub format: AccessLogFormat,
+    /// The fields included in JSON entries. Defaults to all fields.
+    pub fields: Vec<AccessLogField>,
+    /// Where entries are written.
+    pub output: AccessLogOutput,
+    /// The size in bytes at which the log file is rotated, or 0 to never
+    /// rotate.
+    pub max_file_size: u64,
+    /// The number of rotated log files to keep.
+    pub max_files: usize,
+}
+
+impl Default for AccessLogConfig {
+    fn default() -> Self {
+        Self {
+            format: Default::default(),
+            fields: AccessLogField::ALL.to_vec(),
+            output: Default::default(),
+            max_file_size: 10 * 1024 * 1024,
+            max_files: 5,
+        }
+    }
+}
+
+/// The format of access log entries.
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum AccessLogFormat {
+    /// The Common Log Format used by many web servers.
+    #[default]
+    Common,
+    /// One JSON object per line.
+    Json,
+}
+
+/// Where access log entries are written.
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum AccessLogOutput {
+    /// `http_access.log` in the app's log directory, or stdout if the app
+    /// has no log directory.
+    #[default]
+    File,
+    /// Standard output.
+    Stdout,
+}
+
+/// A field of a JSON access log entry.
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum AccessLogField {
+    Time,
+    Client,
+    Method,
+    Path,
+    Protocol,
+    Status,
+    Bytes,
+    LatencyMs,
+    Component,
+    Route,
+    TraceId,
+    UserAgent,
+    Referer,
+}
+
+impl AccessLogField {
+    const ALL: &'static [Self] = &[
+        Self::Time,
+        Self::Client,
+        Self::Method,
+        Self::Path,
+        Self::Protocol,
+        Self::Status,
+        Self::Bytes,
+        Self::LatencyMs,
+        Self::Component,
+        Self::Route,
+        Self::TraceId,
+        Self::UserAgent,
+        Self::Referer,
+    ];
+}
+
+/// The component and route which handled a request, attached to responses as
+/// an extension.
+#[derive(Clone, Debug)]
+pub(crate) struct MatchedRoute {
+    pub component_id: String,
+    pub route: String,
+}
+
+/// Writes an entry for each request once its response has been sent.
+pub(crate) struct AccessLog {
+    format: AccessLogFormat,
+    fields: Vec<AccessLogField>,
+    /// Whether host logs are JSON, in which case entries are JSON log lines
+    /// whatever the configured format
+    log_json: bool,
+    sender: mpsc::UnboundedSender<String>,
+}
+
+impl AccessLog {
+    pub fn new(config: &AccessLogConfig, log_dir: Option<&Path>) -> Result<Self> {
+        let mut writer = match (config.output, log_dir) {
+            (AccessLogOutput::File, Some(dir)) => {
+                std::fs::create_dir_all(dir)
+                    .with_context(|| format!("Unable to create log directory {}", dir.display()))?;
+                LogWriter::File(RotatingFile::open(
+                    dir.join(ACCESS_LOG_FILE),
+                    config.max_file_size,
+                    config.max_files,
+                )?)
+            }
+            _ => LogWriter::Stdout,
+        };
+        // Entries are written on a dedicated thread so that file IO never
+        // blocks request handling
+        let (sender, mut receiver) = mpsc::unbounded_channel::<String>();
+        std::thread::Builder::new()
+            .name("spin-access-log".into())
+            .spawn(move || {
+                while let Some(line) = receiver.blocking_recv() {
+                    if let Err(e) = writer.write_line(&line) {
+                        log::warn!("Failed to write access log: {e}");
+                    }
+                }
+            })
+            .context("Unable to start access log writer")?;
+        Ok(Self {
+            format: config.format,
+            fields: config.fields.clone(),
+            log_json: spin_telemetry::logs::log_format().is_json(),
+            sender,
+        })
+    }
+
+    /// Starts an entry for a request.
+    pub fn start(&self, req: &Request<Body>, addr: SocketAddr) -> AccessLogEntry {
+        let header = |name| {
+            req.headers()
+                .get(name)
+                .map(|v| String::from_utf8_lossy(v.as_bytes()).into_owned())
+        };
+        AccessLogEntry {
+            time: Local::now(),
+            started: Instant::now(),
+            client: addr.ip(),
+            method: req.method().to_string(),
+            path: req
+                .uri()
+                .path_and_query()
+                .map(|p| p.as_str())
+                .unwrap_or("/")
+                .to_owned(),
+            protocol: format!("{:?}", req.version()),
+            user_agent: header(USER_AGENT),
+            referer: header(REFERER),
+            status: 0,
+            bytes: 0,
+            latency: Duration::ZERO,
+            component: None,
+            route: None,
+            trace_id: None,
+        }
+    }
+
+    /// Completes an entry with its response. The entry is written once the
+    /// response body has been sent.
+    pub fn finish(
+        self: &Arc<Self>,
+        mut entry: AccessLogEntry,
+        res: Response<Body>,
+    ) -> Response<Body> {
+        entry.status = res.status().as_u16();
+        entry.trace_id = spin_telemetry::current_trace_id();
+        if let Some(matched) = res.extensions().get::<MatchedRoute>() {
+            entry.component = Some(matched.component_id.clone());
+            entry.route = Some(matched.route.clone());
+        }
+        let log = self.clone();
+        res.map(|inner| {
+            LoggedBody {
+                inner,
+                entry: Some(entry),
+                log,
+            }
+            .boxed()
+        })
+    }
+
+    /// Writes an entry for a request which failed without a response.
+    pub fn failed(&self, mut entry: AccessLogEntry) {
+        entry.status = 500;
+        entry.latency = entry.started.elapsed();
+        entry.trace_id = spin_telemetry::current_trace_id();
+        self.write(&entry);
+    }
+
+    fn write(&self, entry: &AccessLogEntry) {
+        let line = match self.format {
+            _ if self.log_json => entry.log_line(&self.fields),
+            AccessLogFormat::Common => entry.common(),
+            AccessLogFormat::Json => entry.json(&self.fields),
+        };
+        // The writer thread only stops if it panicked, in which case there's
+        // nowhere to log to
+        _ = self.sender.send(line);
+    }
+}
+
+/// A record of a served request.
+#[derive(Debug)]
+pub(crate) struct AccessLogEntry {
+    time: DateTime<Local>,
+    started: Instant,
+    client: IpAddr,
+    method: String,
+    path: String,
+    protocol: String,
+    user_agent: Option<String>,
+    referer: Option<String>,
+    status: u16,
+    bytes: u64,
+    latency: Duration,
+    component: Option<String>,
+    route: Option<String>,
+    trace_id: Option<String>,
+}
+
+impl AccessLogEntry {
+    fn common(&self) -> String {
+        format!(
+            "{} - - [{}] \"{} {} {}\" {} {}",
+            self.client,
+            self.time.format("%d/%b/%Y:%H:%M:%S %z"),
+            self.method,
+            self.path,
+            self.protocol,
+            self.status,
+            self.bytes,
+        )
+    }
+
+    fn json(&self, fields: &[AccessLogField]) -> String {
+        serde_json::Value::Object(self.json_fields(fields)).to_string()
+    }
+
+    /// Formats the entry as a JSON host log line, with the selected fields
+    /// alongside the fields common to all host logs.
+    fn log_line(&self, fields: &[AccessLogField]) -> String {
+        let message = format!("{} {} {}", self.method, self.path, self.status);
+        let mut line = spin_telemetry::logs::JsonLogLine::new("INFO", message)
+            .component(self.component.as_deref())
+            .trace_id(self.trace_id.clone());
+        for (name, value) in self.json_fields(fields) {
+            // The line has its own timestamp, and omits unknown values
+            if name == "time" || value.is_null() {
+                continue;
+            }
+            line = line.field(&name, value);
+        }
+        line.to_string()
+    }
+
+    fn json_fields(&self, fields: &[AccessLogField]) -> serde_json::Map<String, serde_json::Value> {
+        let mut object = serde_json::Map::new();
+        for field in fields {
+            let (name, value) = match field {
+                AccessLogField::Time => (
+                    "time",
+                    self.time
+                        .to_rfc3339_opts(SecondsFormat::Millis, false)
+                        .into(),
+                ),
+                AccessLogField::Client => ("client", self.client.to_string().into()),
+                AccessLogField::Method => ("method", self.method.clone().into()),
+                AccessLogField::Path => ("path", self.path.clone().into()),
+                AccessLogField::Protocol => ("protocol", self.protocol.clone().into()),
+                AccessLogField::Status => ("status", self.status.into()),
+                AccessLogField::Bytes => ("bytes", self.bytes.into()),
+                AccessLogField::LatencyMs => {
+                    ("latency_ms", (self.latency.as_secs_f64() * 1000.0).into())
+                }
+                AccessLogField::Component => ("component", self.component.clone().into()),
+                AccessLogField::Route => ("route", self.route.clone().into()),
+                AccessLogField::TraceId => ("trace_id", self.trace_id.clone().into()),
+                AccessLogField::UserAgent => ("user_agent", self.user_agent.clone().into()),
+                AccessLogField::Referer => ("referer", self.referer.clone().into()),
+            };
+            object.insert(name.into(), value);
+        }
+        object
+    }
+}
+
+/// A response body which writes its access log entry once it has been sent
+/// or dropped.
+struct LoggedBody {
+    inner: Body,
+    entry: Option<AccessLogEntry>,
+    log: Arc<AccessLog>,
+}
+
+impl hyper::body::Body for LoggedBody {
+    type Data = Bytes;
+    type Error = ErrorCode;
+
+    fn poll_frame(
+        mut self: Pin<&mut Self>,
+        cx: &mut TaskContext<'_>,
+    ) -> Poll<Option<Result<Frame<Self::Data>, Self::Error>>> {
+        let poll = Pin::new(&mut self.inner).poll_frame(cx);
+        if let Poll::Ready(Some(Ok(frame))) = &poll {
+            if let (Some(data), Some(entry)) = (frame.data_ref(), self.entry.as_mut()) {
+                entry.bytes += data.len() as u64;
+            }
+        }
+        poll
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.inner.is_end_stream()
+    }
+
+    fn size_hint(&self) -> SizeHint {
+        self.inner.size_hint()
+    }
+}
+
+impl Drop for LoggedBody {
+    fn drop(&mut self) {
+        if let Some(mut entry) = self.entry.take() {
+            entry.latency = entry.started.elapsed();
+            self.log.write(&entry);
+        }
+    }
+}
+
+enum LogWriter {
+    Stdout,
+    File(RotatingFile),
+}
+
+impl LogWriter {
+    fn write_line(&mut self, line: &str) -> io::Result<()> {
+        match self {
+            Self::Stdout => writeln!(io::stdout().lock(), "{line}"),
+            Self::File(file) => file.write_line(line),
+        }
+    }
+}
+
+/// A log file which is renamed to `<name>.1` once it reaches a maximum size,
+/// shifting older files to `<name>.2` and so on.
+struct RotatingFile {
+    path: PathBuf,
+    file: File,
+    size: u64,
+    max_size: u64,
+    max_files: usize,
+}
+
+impl RotatingFile {
+    fn open(path: PathBuf, max_size: u64, max_files: usize) -> Result<Self> {
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&path)
+            .with_context(|| format!("Unable to open access log {}", path.display()))?;
+        let size = file.metadata()?.len();
+        Ok(Self {
+            path,
+            file,
+            size,
+            max_size,
+            max_files,
+        })
+    }
+
+    fn write_line(&mut self, line: &str) -> io::Result<()> {
+        let len = line.len() as u64 + 1;
+        if self.max_size > 0 && self.size > 0 && self.size + len > self.max_size {
+            self.rotate()?;
+        }
+        writeln!(self.file, "{line}")?;
+        self.size += len;
+        Ok(())
+    }
+
+    fn rotate(&mut self) -> io::Result<()> {
+        let rotated = |n: usize| {
+            let mut name = self.path.clone().into_os_string();
+            name.push(format!(".{n}"));
+            PathBuf::from(name)
+        };
+        if self.max_files == 0 {
+            std::fs::remove_file(&self.path)?;
+        } else {
+            for n in (1..self.max_files).rev() {
+                let from = rotated(n);
+                if from.exists() {
+                    std::fs::rename(from, rotated(n + 1))?;
+                }
+            }
+            std::fs::rename(&self.path, rotated(1))?;
+        }
+        self.file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?;
+        self.size = 0;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry() -> AccessLogEntry {
+        AccessLogEntry {
+            time: DateTime::parse_from_rfc3339("2024-03-01T12:00:00+00:00")
+                .unwrap()
+                .with_timezone(&Local),
+            started: Instant::now(),
+            client: "127.0.0.1".parse().unwrap(),
+            method: "GET".into(),
+            path: "/hello?name=spin".into(),
+            protocol: "HTTP/1.1".into(),
+            user_agent: Some("curl/8.0".into()),
+            referer: None,
+            status: 200,
+            bytes: 12,
+            latency: Duration::from_millis(5),
+            component: Some("hello".into()),
+            route: Some("/hello".into()),
+            trace_id: None,
+        }
+    }
+
+    #[test]
+    fn formats_common_log_entries() {
+        let line = entry().common();
+        assert!(line.starts_with("127.0.0.1 - - ["), "{line}");
+        assert!(
+            line.ends_with("] \"GET /hello?name=spin HTTP/1.1\" 200 12"),
+            "{line}"
+        );
+    }
+
+    #[test]
+    fn formats_selected_json_fields() {
+        let line = entry().json(&[
+            AccessLogField::Status,
+            AccessLogField::Component,
+            AccessLogField::TraceId,
+        ]);
+        assert_eq!(
+            line,
+            r#"{"component":"hello","status":200,"trace_id":null}"#
+        );
+    }
+
+    #[test]
+    fn formats_json_log_lines() {
+        let line = entry().log_line(&[
+            AccessLogField::Time,
+            AccessLogField::Status,
+            AccessLogField::Component,
+            AccessLogField::TraceId,
+        ]);
+        let value: serde_json::Value = serde_json::from_str(&line).unwrap();
+        assert_eq!(value["level"], "INFO");
+        assert_eq!(value["message"], "GET /hello?name=spin 200");
+        assert_eq!(value["component"], "hello");
+        assert_eq!(value["status"], 200);
+        assert!(value.get("time").is_none());
+        assert!(value.get("trace_id").is_none());
+        assert!(value.get("timestamp").is_some());
+    }
+
+    #[test]
+    fn rotates_log_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(ACCESS_LOG_FILE);
+        let mut file = RotatingFile::open(path.clone(), 10, 2).unwrap();
+        for line in ["first", "second", "third", "fourth"] {
+            file.write_line(line).unwrap();
+        }
+        let read = |name: &str| std::fs::read_to_string(dir.path().join(name)).unwrap();
+        assert_eq!(read(ACCESS_LOG_FILE), "fourth\n");
+        assert_eq!(read("http_access.log.1"), "third\n");
+        assert_eq!(read("http_access.log.2"), "second\n");
+        assert!(!dir.path().join("http_access.log.3").exists());
+    }
+}
@@ -0,0 +1,618 @@
+//! Automatic certificate provisioning and renewal via ACME (RFC 8555), e.g.
+//! from Let's Encrypt.
+
+use std::{
+    collections::HashMap,
+    fs,
+    net::SocketAddr,
+    path::{Path, PathBuf},
+    sync::{Arc, RwLock},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::{anyhow, bail, Context, Result};
+use http::StatusCode;
+use hyper::{body::Incoming, server::conn::http1, service::service_fn, Request, Response};
+use hyper_util::rt::tokio::TokioIo;
+use instant_acme::{
+    Account, AccountCredentials, AuthorizationStatus, ChallengeType, Identifier, NewAccount,
+    NewOrder, OrderStatus,
+};
+use rcgen::{CertificateParams, CustomExtension, DistinguishedName};
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use spin_common::ui::quoted_path;
+use spin_http::body;
+use tokio::{net::TcpListener, task};
+use tokio_rustls::{
+    rustls::{
+        self,
+        server::{ClientHello, ResolvesServerCert},
+        sign::{any_supported_type, CertifiedKey},
+    },
+    TlsAcceptor,
+};
+
+use crate::{
+    tls::{load_certs, load_keys, ALPN_PROTOCOLS},
+    Body,
+};
+
+/// The ALPN protocol used by the TLS-ALPN-01 challenge (RFC 8737).
+pub(crate) const ACME_TLS_ALPN_NAME: &[u8] = b"acme-tls/1";
+
+const HTTP_CHALLENGE_PREFIX: &str = "/.well-known/acme-challenge/";
+const LETS_ENCRYPT_DIRECTORY_URL: &str = "https://acme-v02.api.letsencrypt.org/directory";
+
+// How often to check whether the certificate is due for renewal, and how long
+// to wait before trying again after a failed order.
+const RENEWAL_CHECK_INTERVAL: Duration = Duration::from_secs(12 * 60 * 60);
+const RENEWAL_RETRY_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+// How many times to poll a pending order, or for an issued certificate, before
+// giving up.
+const ORDER_POLL_ATTEMPTS: u32 = 10;
+
+/// ACME configuration, read from the `[http_trigger.acme]` runtime config table.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct AcmeConfig {
+    /// Domain names to request a certificate for.
+    pub domains: Vec<String>,
+    /// Contact URLs for the ACME account, e.g. `mailto:admin@example.com`.
+    #[serde(default)]
+    pub contact: Vec<String>,
+    /// Must be set to agree to the certificate authority's terms of service.
+    #[serde(default)]
+    pub accept_terms_of_service: bool,
+    /// ACME directory URL. Defaults to the Let's Encrypt production directory.
+    #[serde(default = "default_directory_url")]
+    pub directory_url: String,
+    /// The challenge used to prove control of the domains.
+    #[serde(default)]
+    pub challenge: AcmeChallenge,
+    /// Address on which to answer HTTP-01 challenges.
+    #[serde(default = "default_http_challenge_listen")]
+    pub http_challenge_listen: SocketAddr,
+    /// Renew the certificate once it expires in fewer than this many days.
+    #[serde(default = "default_renew_before_days")]
+    pub renew_before_days: u64,
+    /// Where to store the ACME account and certificates. Defaults to `acme`
+    /// under the application state directory.
+    #[serde(default)]
+    pub storage_dir: Option<PathBuf>,
+}
+
+fn default_directory_url() -> String {
+    LETS_ENCRYPT_DIRECTORY_URL.to_owned()
+}
+
+fn default_http_challenge_listen() -> SocketAddr {
+    SocketAddr::from(([0, 0, 0, 0], 80))
+}
+
+fn default_renew_before_days() -> u64 {
+    30
+}
+
+/// The ACME challenge type.
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Eq)]
+pub enum AcmeChallenge {
+    #[default]
+    #[serde(rename = "http-01")]
+    Http01,
+    #[serde(rename = "tls-alpn-01")]
+    TlsAlpn01,
+}
+
+impl From<AcmeChallenge> for ChallengeType {
+    fn from(challenge: AcmeChallenge) -> Self {
+        match challenge {
+            AcmeChallenge::Http01 => ChallengeType::Http01,
+            AcmeChallenge::TlsAlpn01 => ChallengeType::TlsAlpn01,
+        }
+    }
+}
+
+/// State shared between the certificate manager, the TLS acceptor and the
+/// HTTP-01 challenge responder.
+#[derive(Default)]
+pub(crate) struct AcmeState {
+    // The currently installed certificate, if any.
+    certificate: RwLock<Option<Arc<CertifiedKey>>>,
+    // TLS-ALPN-01 challenge certificates by domain.
+    alpn_challenges: RwLock<HashMap<String, Arc<CertifiedKey>>>,
+    // HTTP-01 key authorizations by token.
+    http_challenges: RwLock<HashMap<String, String>>,
+}
+
+impl AcmeState {
+    /// Returns the key authorization for an HTTP-01 challenge request path,
+    /// if the path is for a pending challenge.
+    pub(crate) fn http_challenge_response(&self, path: &str) -> Option<String> {
+        let token = path.strip_prefix(HTTP_CHALLENGE_PREFIX)?;
+        self.http_challenges.read().unwrap().get(token).cloned()
+    }
+
+    /// Returns the chain of the installed certificate, if any.
+    pub(crate) fn certificate_chain(&self) -> Vec<rustls::Certificate> {
+        self.certificate
+            .read()
+            .unwrap()
+            .as_ref()
+            .map(|key| key.cert.clone())
+            .unwrap_or_default()
+    }
+
+    fn clear_challenges(&self) {
+        self.alpn_challenges.write().unwrap().clear();
+        self.http_challenges.write().unwrap().clear();
+    }
+}
+
+impl ResolvesServerCert for AcmeState {
+    fn resolve(&self, client_hello: ClientHello) -> Option<Arc<CertifiedKey>> {
+        let is_challenge = client_hello
+            .alpn()
+            .into_iter()
+            .flatten()
+            .any(|protocol| protocol == ACME_TLS_ALPN_NAME);
+        if is_challenge {
+            let domain = client_hello.server_name()?;
+            return self.alpn_challenges.read().unwrap().get(domain).cloned();
+        }
+        self.certificate.read().unwrap().clone()
+    }
+}
+
+/// Obtains and renews a certificate for the configured domains, installing it
+/// into the shared [`AcmeState`].
+pub(crate) struct AcmeManager {
+    config: AcmeConfig,
+    storage_dir: PathBuf,
+    state: Arc<AcmeState>,
+}
+
+impl AcmeManager {
+    /// Creates a manager. Certificates are stored under the configured
+    /// `storage_dir`, falling back to `default_storage_dir`.
+    pub(crate) fn new(config: AcmeConfig, default_storage_dir: Option<PathBuf>) -> Result<Self> {
+        if config.domains.is_empty() {
+            bail!("ACME configuration must list at least one domain");
+        }
+        if !config.accept_terms_of_service {
+            bail!("ACME configuration must set `accept_terms_of_service = true` to agree to the certificate authority's terms of service");
+        }
+        let storage_dir = config
+            .storage_dir
+            .clone()
+            .or(default_storage_dir)
+            .context("ACME requires somewhere to store certificates: set `storage_dir` or run with a state directory")?;
+        Ok(Self {
+            config,
+            storage_dir,
+            state: Default::default(),
+        })
+    }
+
+    /// Returns the shared state, e.g. for answering HTTP-01 challenges.
+    pub(crate) fn state(&self) -> Arc<AcmeState> {
+        self.state.clone()
+    }
+
+    /// Returns a TLS acceptor which serves the managed certificate and answers
+    /// TLS-ALPN-01 challenges.
+    pub(crate) fn acceptor(&self) -> TlsAcceptor {
+        let mut cfg = rustls::ServerConfig::builder()
+            .with_safe_defaults()
+            .with_no_client_auth()
+            .with_cert_resolver(self.state.clone());
+        cfg.alpn_protocols = ALPN_PROTOCOLS
+            .iter()
+            .chain([&ACME_TLS_ALPN_NAME])
+            .map(|p| p.to_vec())
+            .collect();
+        Arc::new(cfg).into()
+    }
+
+    /// Installs any previously stored certificate and starts the background
+    /// tasks which answer HTTP-01 challenges and keep the certificate renewed.
+    pub(crate) async fn start(self) -> Result<()> {
+        fs::create_dir_all(&self.storage_dir).with_context(|| {
+            format!(
+                "Failed to create ACME storage directory {}",
+                quoted_path(&self.storage_dir)
+            )
+        })?;
+
+        let expiry = match self.load_stored_certificate() {
+            Ok(expiry) => expiry,
+            Err(err) => {
+                tracing::warn!("Ignoring stored ACME certificate: {err:?}");
+                None
+            }
+        };
+
+        if self.config.challenge == AcmeChallenge::Http01 {
+            let addr = self.config.http_challenge_listen;
+            let listener = TcpListener::bind(addr)
+                .await
+                .with_context(|| format!("Unable to listen for ACME challenges on {addr}"))?;
+            task::spawn(serve_http_challenges(listener, self.state()));
+        }
+
+        task::spawn(self.renewal_loop(expiry));
+        Ok(())
+    }
+
+    async fn renewal_loop(self, mut expiry: Option<SystemTime>) {
+        let renew_before = Duration::from_secs(self.config.renew_before_days * 24 * 60 * 60);
+        loop {
+            let due = match expiry {
+                Some(expiry) => {
+                    let renew_at = expiry.checked_sub(renew_before).unwrap_or(UNIX_EPOCH);
+                    SystemTime::now() >= renew_at
+                }
+                None => true,
+            };
+            let wait = if due {
+                match self.obtain_certificate().await {
+                    Ok(new_expiry) => {
+                        tracing::info!("Installed ACME certificate for {:?}", self.config.domains);
+                        expiry = Some(new_expiry);
+                        RENEWAL_CHECK_INTERVAL
+                    }
+                    Err(err) => {
+                        tracing::error!("Failed to obtain ACME certificate: {err:?}");
+                        RENEWAL_RETRY_INTERVAL
+                    }
+                }
+            } else {
+                RENEWAL_CHECK_INTERVAL
+            };
+            tokio::time::sleep(wait).await;
+        }
+    }
+
+    async fn obtain_certificate(&self) -> Result<SystemTime> {
+        let account = self.account().await?;
+        let identifiers = self
+            .config
+            .domains
+            .iter()
+            .map(|domain| Identifier::Dns(domain.clone()))
+            .collect::<Vec<_>>();
+        let mut order = account
+            .new_order(&NewOrder {
+                identifiers: &identifiers,
+            })
+            .await
+            .context("Failed to create ACME order")?;
+
+        let mut ready = vec![];
+        for authz in order.authorizations().await? {
+            match authz.status {
+                AuthorizationStatus::Pending => {}
+                AuthorizationStatus::Valid => continue,
+                status => bail!("unexpected ACME authorization status {status:?}"),
+            }
+            let challenge = authz
+                .challenges
+                .iter()
+                .find(|c| c.r#type == ChallengeType::from(self.config.challenge))
+                .with_context(|| {
+                    format!(
+                        "ACME server did not offer a {:?} challenge",
+                        self.config.challenge
+                    )
+                })?;
+            let Identifier::Dns(domain) = &authz.identifier;
+            let key_authorization = order.key_authorization(challenge);
+            match self.config.challenge {
+                AcmeChallenge::Http01 => {
+                    self.state.http_challenges.write().unwrap().insert(
+                        challenge.token.clone(),
+                        key_authorization.as_str().to_owned(),
+                    );
+                }
+                AcmeChallenge::TlsAlpn01 => {
+                    let key = alpn_challenge_key(domain, key_authorization.digest().as_ref())?;
+                    self.state
+                        .alpn_challenges
+                        .write()
+                        .unwrap()
+                        .insert(domain.clone(), key);
+                }
+            }
+            ready.push(challenge.url.clone());
+        }
+
+        let status = self.complete_challenges(&mut order, &ready).await;
+        self.state.clear_challenges();
+        match status? {
+            OrderStatus::Ready => {}
+            status => bail!("ACME order failed with status {status:?}"),
+        }
+
+        let mut params = CertificateParams::new(self.config.domains.clone());
+        params.distinguished_name = DistinguishedName::new();
+        let cert = rcgen::Certificate::from_params(params)?;
+        order
+            .finalize(&cert.serialize_request_der()?)
+            .await
+            .context("Failed to finalize ACME order")?;
+        let chain = download_certificate(&mut order).await?;
+
+        let domain_dir = self.domain_dir();
+        fs::create_dir_all(&domain_dir)?;
+        fs::write(domain_dir.join("cert.pem"), chain)?;
+        write_private(
+            &domain_dir.join("key.pem"),
+            cert.serialize_private_key_pem().as_bytes(),
+        )?;
+
+        self.load_stored_certificate()?
+            .context("newly stored ACME certificate could not be loaded")
+    }
+
+    async fn complete_challenges(
+        &self,
+        order: &mut instant_acme::Order,
+        ready: &[String],
+    ) -> Result<OrderStatus> {
+        for url in ready {
+            order.set_challenge_ready(url).await?;
+        }
+        let mut delay = Duration::from_millis(250);
+        for _ in 0..ORDER_POLL_ATTEMPTS {
+            tokio::time::sleep(delay).await;
+            let state = order.refresh().await?;
+            if let OrderStatus::Ready | OrderStatus::Invalid | OrderStatus::Valid = state.status {
+                return Ok(state.status);
+            }
+            delay *= 2;
+        }
+        bail!("timed out waiting for ACME challenges to be validated")
+    }
+
+    async fn account(&self) -> Result<Account> {
+        let path = self.storage_dir.join("account.json");
+        if path.exists() {
+            let credentials: AccountCredentials = serde_json::from_slice(&fs::read(&path)?)
+                .with_context(|| format!("Invalid ACME account file {}", quoted_path(&path)))?;
+            return Ok(Account::from_credentials(credentials).await?);
+        }
+
+        let contact = self
+            .config
+            .contact
+            .iter()
+            .map(String::as_str)
+            .collect::<Vec<_>>();
+        let (account, credentials) = Account::create(
+            &NewAccount {
+                contact: &contact,
+                terms_of_service_agreed: true,
+                only_return_existing: false,
+            },
+            &self.config.directory_url,
+            None,
+        )
+        .await
+        .context("Failed to create ACME account")?;
+        write_private(&path, &serde_json::to_vec_pretty(&credentials)?)?;
+        Ok(account)
+    }
+
+    // Installs the stored certificate, if any, returning its expiry time.
+    fn load_stored_certificate(&self) -> Result<Option<SystemTime>> {
+        let domain_dir = self.domain_dir();
+        let cert_path = domain_dir.join("cert.pem");
+        let key_path = domain_dir.join("key.pem");
+        if !cert_path.exists() || !key_path.exists() {
+            return Ok(None);
+        }
+
+        let certs = load_certs(&cert_path)?;
+        let key = load_keys(&key_path)?
+            .into_iter()
+            .next()
+            .with_context(|| format!("No private key found in {}", quoted_path(&key_path)))?;
+        let expiry = certificate_expiry(certs.first().context("certificate chain is empty")?)?;
+        let key = any_supported_type(&key).map_err(|_| anyhow!("unsupported private key type"))?;
+        *self.state.certificate.write().unwrap() = Some(Arc::new(CertifiedKey::new(certs, key)));
+        Ok(Some(expiry))
+    }
+
+    fn domain_dir(&self) -> PathBuf {
+        self.storage_dir.join(domain_dir_name(&self.config.domains))
+    }
+}
+
+// Names the directory holding the certificate for a set of domains. The name
+// covers the whole set, so that changing the domains obtains a new
+// certificate rather than serving one which doesn't match them.
+fn domain_dir_name(domains: &[String]) -> String {
+    let mut domains = domains.iter().map(String::as_str).collect::<Vec<_>>();
+    domains.sort_unstable();
+    domains.dedup();
+    let digest = Sha256::digest(domains.join(",").as_bytes());
+    // Wildcard domains are valid identifiers but awkward file names
+    format!(
+        "{}-{}",
+        domains[0].replace('*', "_"),
+        &hex::encode(digest)[..16]
+    )
+}
+
+async fn download_certificate(order: &mut instant_acme::Order) -> Result<String> {
+    let mut delay = Duration::from_millis(250);
+    for _ in 0..ORDER_POLL_ATTEMPTS {
+        if let Some(chain) = order.certificate().await? {
+            return Ok(chain);
+        }
+        tokio::time::sleep(delay).await;
+        delay *= 2;
+    }
+    bail!("timed out waiting for the ACME certificate to be issued")
+}
+
+/// Writes a file which only the user can read, such as a private key.
+fn write_private(path: &Path, contents: &[u8]) -> Result<()> {
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::{OpenOptionsExt, PermissionsExt};
+        options.mode(0o600);
+        // The mode only applies to new files, so restrict any existing file
+        // before writing to it
+        if path.exists() {
+            fs::set_permissions(path, fs::Permissions::from_mode(0o600))
+                .with_context(|| format!("Failed to restrict {}", quoted_path(path)))?;
+        }
+    }
+    let mut file = options
+        .open(path)
+        .with_context(|| format!("Failed to create {}", quoted_path(path)))?;
+    std::io::Write::write_all(&mut file, contents)
+        .with_context(|| format!("Failed to write {}", quoted_path(path)))
+}
+
+// Builds the self-signed certificate used to answer a TLS-ALPN-01 challenge.
+fn alpn_challenge_key(domain: &str, digest: &[u8]) -> Result<Arc<CertifiedKey>> {
+    let mut params = CertificateParams::new(vec![domain.to_owned()]);
+    params.custom_extensions = vec![CustomExtension::new_acme_identifier(digest)];
+    let cert = rcgen::Certificate::from_params(params)?;
+    let key = rustls::PrivateKey(cert.serialize_private_key_der());
+    let key = any_supported_type(&key).map_err(|_| anyhow!("unsupported private key type"))?;
+    Ok(Arc::new(CertifiedKey::new(
+        vec![rustls::Certificate(cert.serialize_der()?)],
+        key,
+    )))
+}
+
+fn certificate_expiry(cert: &rustls::Certificate) -> Result<SystemTime> {
+    let (_, cert) = x509_parser::parse_x509_certificate(&cert.0)
+        .map_err(|e| anyhow!("invalid certificate: {e}"))?;
+    let not_after = cert.validity().not_after.timestamp();
+    Ok(UNIX_EPOCH + Duration::from_secs(not_after.max(0) as u64))
+}
+
+async fn serve_http_challenges(listener: TcpListener, state: Arc<AcmeState>) {
+    loop {
+        let stream = match listener.accept().await {
+            Ok((stream, _)) => stream,
+            Err(err) => {
+                tracing::warn!("Failed to accept ACME challenge connection: {err}");
+                continue;
+            }
+        };
+        let state = state.clone();
+        task::spawn(async move {
+            let service = service_fn(move |req: Request<Incoming>| {
+                let key_authorization = state.http_challenge_response(req.uri().path());
+                async move { http_challenge_response(key_authorization) }
+            });
+            if let Err(err) = http1::Builder::new()
+                .serve_connection(TokioIo::new(stream), service)
+                .await
+            {
+                tracing::warn!("{err:?}");
+            }
+        });
+    }
+}
+
+/// Creates the response to an HTTP-01 challenge request.
+pub(crate) fn http_challenge_response(key_authorization: Option<String>) -> Result<Response<Body>> {
+    match key_authorization {
+        Some(key_authorization) => Ok(Response::builder()
+            .header("content-type", "application/octet-stream")
+            .body(body::full(key_authorization.into()))?),
+        None => Ok(Response::builder()
+            .status(StatusCode::NOT_FOUND)
+            .body(body::empty())?),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn config_defaults() {
+        let config: AcmeConfig = serde_json::from_value(serde_json::json!({
+            "domains": ["example.com"],
+            "accept_terms_of_service": true,
+        }))
+        .unwrap();
+        assert_eq!(config.directory_url, LETS_ENCRYPT_DIRECTORY_URL);
+        assert_eq!(config.challenge, AcmeChallenge::Http01);
+        assert_eq!(config.http_challenge_listen.port(), 80);
+        assert_eq!(config.renew_before_days, 30);
+    }
+
+    #[test]
+    fn config_requires_terms_of_service() {
+        let config: AcmeConfig = serde_json::from_value(serde_json::json!({
+            "domains": ["example.com"],
+            "challenge": "tls-alpn-01",
+        }))
+        .unwrap();
+        assert_eq!(config.challenge, AcmeChallenge::TlsAlpn01);
+        assert!(AcmeManager::new(config, Some("/tmp".into())).is_err());
+    }
+
+    #[test]
+    fn domain_dirs_cover_the_whole_domain_set() {
+        let domains = |names: &[&str]| {
+            names
+                .iter()
+                .map(|name| name.to_string())
+                .collect::<Vec<_>>()
+        };
+        let dir = domain_dir_name(&domains(&["www.example.com", "example.com"]));
+        assert!(dir.starts_with("example.com-"), "{dir}");
+        assert_eq!(
+            dir,
+            domain_dir_name(&domains(&["example.com", "www.example.com"]))
+        );
+        assert_ne!(dir, domain_dir_name(&domains(&["example.com"])));
+        assert!(domain_dir_name(&domains(&["*.example.com"])).starts_with("_.example.com-"));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn private_files_are_only_readable_by_the_user() {
+        use std::os::unix::fs::PermissionsExt;
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("key.pem");
+        fs::write(&path, "old").unwrap();
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o644)).unwrap();
+        write_private(&path, b"new").unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "new");
+        let mode = fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+    }
+
+    #[test]
+    fn http_challenges_are_answered_by_token() {
+        let state = AcmeState::default();
+        state
+            .http_challenges
+            .write()
+            .unwrap()
+            .insert("token".into(), "token.thumbprint".into());
+
+        assert_eq!(
+            state.http_challenge_response("/.well-known/acme-challenge/token"),
+            Some("token.thumbprint".into())
+        );
+        assert_eq!(
+            state.http_challenge_response("/.well-known/acme-challenge/other"),
+            None
+        );
+        assert_eq!(state.http_challenge_response("/token"), None);
+    }
+}
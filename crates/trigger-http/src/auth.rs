@@ -0,0 +1,433 @@
+//! Authentication enforced by the HTTP trigger before a component is invoked.
+
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
+
+use anyhow::{bail, Context, Result};
+use base64::{engine::general_purpose::STANDARD, engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use http::{
+    header::{AUTHORIZATION, WWW_AUTHENTICATE},
+    HeaderMap, HeaderName, HeaderValue, StatusCode,
+};
+use hyper::{Request, Response};
+use jsonwebtoken::{jwk::JwkSet, Algorithm, DecodingKey, Validation};
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use spin_http::body;
+use tokio::sync::RwLock;
+
+use crate::{runtime_config::HttpTriggerRuntimeConfig, Body};
+
+/// Request headers starting with this prefix are reserved for identities
+/// established by the trigger, and are removed from incoming requests.
+pub(crate) const AUTH_HEADER_PREFIX: &str = "spin-auth-";
+
+const AUTH_USER_HEADER: &str = "spin-auth-user";
+const AUTH_SUBJECT_HEADER: &str = "spin-auth-subject";
+const AUTH_CLAIMS_HEADER: &str = "spin-auth-claims";
+
+/// Authentication for a component, read from the
+/// `[http_trigger.component.<id>.auth]` runtime config table.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase", deny_unknown_fields)]
+pub enum AuthConfig {
+    /// Requests must present one of the given bearer tokens.
+    Bearer {
+        /// The accepted tokens.
+        tokens: Vec<String>,
+    },
+    /// Requests must present HTTP basic credentials listed in a file.
+    Basic {
+        /// A file of `user:password` lines. Passwords may instead be given as
+        /// `sha256:<hex digest>`.
+        credentials_file: PathBuf,
+        /// The realm reported to clients.
+        #[serde(default)]
+        realm: Option<String>,
+    },
+    /// Requests must present a valid JWT bearer token.
+    Jwt(JwtConfig),
+}
+
+/// JWT validation options.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct JwtConfig {
+    /// URL of the JSON Web Key Set used to verify token signatures.
+    pub jwks_url: String,
+    /// Required `iss` claim, if any.
+    #[serde(default)]
+    pub issuer: Option<String>,
+    /// Required `aud` claim, if any.
+    #[serde(default)]
+    pub audience: Option<String>,
+    /// Accepted signing algorithms.
+    #[serde(default = "default_algorithms")]
+    pub algorithms: Vec<Algorithm>,
+    /// How long to cache the key set, in seconds.
+    #[serde(default = "default_jwks_cache_secs")]
+    pub jwks_cache_secs: u64,
+}
+
+fn default_algorithms() -> Vec<Algorithm> {
+    vec![Algorithm::RS256]
+}
+
+fn default_jwks_cache_secs() -> u64 {
+    300
+}
+
+/// The authenticators for each component which requires authentication.
+pub(crate) struct AppAuth {
+    components: HashMap<String, Authenticator>,
+}
+
+impl AppAuth {
+    pub fn new(runtime_config: &HttpTriggerRuntimeConfig) -> Result<Self> {
+        let components = runtime_config
+            .component
+            .iter()
+            .filter_map(|(id, config)| {
+                let auth = config.auth.as_ref()?;
+                Some(
+                    Authenticator::new(auth)
+                        .map(|authenticator| (id.clone(), authenticator))
+                        .with_context(|| format!("Invalid auth config for component '{id}'")),
+                )
+            })
+            .collect::<Result<_>>()?;
+        Ok(Self { components })
+    }
+
+    /// Authenticates a request to the given component. On success, the
+    /// established identity is added to the request headers; on failure, the
+    /// response to return is given instead.
+    pub async fn authenticate(
+        &self,
+        component_id: &str,
+        req: &mut Request<Body>,
+    ) -> Result<(), Response<Body>> {
+        match self.components.get(component_id) {
+            Some(authenticator) => authenticator.authenticate(req.headers_mut()).await,
+            None => Ok(()),
+        }
+    }
+}
+
+enum Authenticator {
+    Bearer(Vec<String>),
+    Basic {
+        credentials: HashMap<String, Password>,
+        challenge: HeaderValue,
+    },
+    Jwt(Box<JwtAuthenticator>),
+}
+
+impl Authenticator {
+    fn new(config: &AuthConfig) -> Result<Self> {
+        Ok(match config {
+            AuthConfig::Bearer { tokens } => {
+                if tokens.is_empty() {
+                    bail!("bearer auth requires at least one token");
+                }
+                Self::Bearer(tokens.clone())
+            }
+            AuthConfig::Basic {
+                credentials_file,
+                realm,
+            } => {
+                let realm = realm.as_deref().unwrap_or("spin");
+                Self::Basic {
+                    credentials: read_credentials(credentials_file)?,
+                    challenge: HeaderValue::from_str(&format!("Basic realm=\"{realm}\""))
+                        .context("invalid basic auth realm")?,
+                }
+            }
+            AuthConfig::Jwt(config) => Self::Jwt(Box::new(JwtAuthenticator::new(config)?)),
+        })
+    }
+
+    async fn authenticate(&self, headers: &mut HeaderMap) -> Result<(), Response<Body>> {
+        let authorization = headers
+            .get(AUTHORIZATION)
+            .and_then(|v| v.to_str().ok())
+            .unwrap_or_default();
+        match self {
+            Self::Bearer(tokens) => {
+                let token = bearer_token(authorization).ok_or_else(bearer_challenge)?;
+                if !tokens
+                    .iter()
+                    .any(|t| constant_time_eq(t.as_bytes(), token.as_bytes()))
+                {
+                    return Err(unauthorized(r#"Bearer error="invalid_token""#));
+                }
+            }
+            Self::Basic {
+                credentials,
+                challenge,
+            } => {
+                let reject = || unauthorized(challenge.clone());
+                let (user, password) = basic_credentials(authorization).ok_or_else(reject)?;
+                let valid = credentials
+                    .get(&user)
+                    .is_some_and(|expected| expected.matches(&password));
+                if !valid {
+                    return Err(reject());
+                }
+                if let Ok(user) = HeaderValue::from_str(&user) {
+                    headers.insert(HeaderName::from_static(AUTH_USER_HEADER), user);
+                }
+            }
+            Self::Jwt(jwt) => {
+                let token = bearer_token(authorization).ok_or_else(bearer_challenge)?;
+                let claims = match jwt.validate(token).await {
+                    Ok(claims) => claims,
+                    Err(e) => {
+                        tracing::debug!("Rejected JWT: {e:#}");
+                        return Err(unauthorized(r#"Bearer error="invalid_token""#));
+                    }
+                };
+                if let Some(subject) = claims
+                    .get("sub")
+                    .and_then(|s| s.as_str())
+                    .and_then(|s| HeaderValue::from_str(s).ok())
+                {
+                    headers.insert(HeaderName::from_static(AUTH_SUBJECT_HEADER), subject);
+                }
+                let encoded =
+                    URL_SAFE_NO_PAD.encode(serde_json::to_vec(&claims).unwrap_or_default());
+                if let Ok(encoded) = HeaderValue::from_str(&encoded) {
+                    headers.insert(HeaderName::from_static(AUTH_CLAIMS_HEADER), encoded);
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+struct JwtAuthenticator {
+    jwks_url: String,
+    validation: Validation,
+    cache_ttl: Duration,
+    client: reqwest::Client,
+    jwks: RwLock<Option<(Instant, JwkSet)>>,
+}
+
+// Minimum time between key set refreshes triggered by unknown key IDs
+const JWKS_MIN_REFRESH: Duration = Duration::from_secs(10);
+
+impl JwtAuthenticator {
+    fn new(config: &JwtConfig) -> Result<Self> {
+        let Some(first_algorithm) = config.algorithms.first() else {
+            bail!("jwt auth requires at least one algorithm");
+        };
+        let mut validation = Validation::new(*first_algorithm);
+        validation.algorithms = config.algorithms.clone();
+        if let Some(issuer) = &config.issuer {
+            validation.set_issuer(&[issuer]);
+        }
+        match &config.audience {
+            Some(audience) => validation.set_audience(&[audience]),
+            None => validation.validate_aud = false,
+        }
+        Ok(Self {
+            jwks_url: config.jwks_url.clone(),
+            validation,
+            cache_ttl: Duration::from_secs(config.jwks_cache_secs),
+            client: reqwest::Client::new(),
+            jwks: RwLock::new(None),
+        })
+    }
+
+    async fn validate(&self, token: &str) -> Result<serde_json::Map<String, serde_json::Value>> {
+        let header = jsonwebtoken::decode_header(token)?;
+        let key = self.decoding_key(header.kid.as_deref()).await?;
+        let data = jsonwebtoken::decode(token, &key, &self.validation)?;
+        Ok(data.claims)
+    }
+
+    async fn decoding_key(&self, kid: Option<&str>) -> Result<DecodingKey> {
+        if let Some((fetched, jwks)) = &*self.jwks.read().await {
+            let fresh = fetched.elapsed() < self.cache_ttl;
+            match find_key(jwks, kid) {
+                Some(key) if fresh => return key,
+                // An unknown key ID may mean the keys were rotated
+                None if fetched.elapsed() < JWKS_MIN_REFRESH => bail!("unknown key ID {kid:?}"),
+                _ => {}
+            }
+        }
+
+        let jwks: JwkSet = self
+            .client
+            .get(&self.jwks_url)
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await
+            .with_context(|| format!("Failed to fetch JWKS from {}", self.jwks_url))?;
+        let key = find_key(&jwks, kid).with_context(|| format!("unknown key ID {kid:?}"))?;
+        *self.jwks.write().await = Some((Instant::now(), jwks));
+        key
+    }
+}
+
+fn find_key(jwks: &JwkSet, kid: Option<&str>) -> Option<Result<DecodingKey>> {
+    let jwk = match kid {
+        Some(kid) => jwks.find(kid)?,
+        None => jwks.keys.first()?,
+    };
+    Some(DecodingKey::from_jwk(jwk).map_err(Into::into))
+}
+
+enum Password {
+    Plain(String),
+    Sha256(Vec<u8>),
+}
+
+impl Password {
+    fn matches(&self, password: &str) -> bool {
+        match self {
+            Self::Plain(expected) => constant_time_eq(expected.as_bytes(), password.as_bytes()),
+            Self::Sha256(expected) => {
+                constant_time_eq(expected, Sha256::digest(password.as_bytes()).as_slice())
+            }
+        }
+    }
+}
+
+fn read_credentials(path: &Path) -> Result<HashMap<String, Password>> {
+    let contents = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read credentials file {path:?}"))?;
+    parse_credentials(&contents).with_context(|| format!("Invalid credentials file {path:?}"))
+}
+
+fn parse_credentials(contents: &str) -> Result<HashMap<String, Password>> {
+    let mut credentials = HashMap::new();
+    for (index, line) in contents.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let Some((user, password)) = line.split_once(':') else {
+            bail!("line {}: expected `user:password`", index + 1);
+        };
+        let password = match password.strip_prefix("sha256:") {
+            Some(digest) => Password::Sha256(
+                decode_hex(digest)
+                    .with_context(|| format!("line {}: invalid sha256 digest", index + 1))?,
+            ),
+            None => Password::Plain(password.to_owned()),
+        };
+        credentials.insert(user.to_owned(), password);
+    }
+    Ok(credentials)
+}
+
+fn decode_hex(digest: &str) -> Result<Vec<u8>> {
+    if digest.len() != 64 {
+        bail!("expected 64 hex digits");
+    }
+    Ok(hex::decode(digest)?)
+}
+
+fn bearer_token(authorization: &str) -> Option<&str> {
+    let (scheme, token) = authorization.split_once(' ')?;
+    scheme
+        .eq_ignore_ascii_case("bearer")
+        .then(|| token.trim())
+        .filter(|t| !t.is_empty())
+}
+
+fn basic_credentials(authorization: &str) -> Option<(String, String)> {
+    let (scheme, encoded) = authorization.split_once(' ')?;
+    if !scheme.eq_ignore_ascii_case("basic") {
+        return None;
+    }
+    let decoded = String::from_utf8(STANDARD.decode(encoded.trim()).ok()?).ok()?;
+    let (user, password) = decoded.split_once(':')?;
+    Some((user.to_owned(), password.to_owned()))
+}
+
+pub(crate) fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+fn bearer_challenge() -> Response<Body> {
+    unauthorized("Bearer")
+}
+
+fn unauthorized(challenge: impl TryInto<HeaderValue>) -> Response<Body> {
+    let mut resp = Response::new(body::empty());
+    *resp.status_mut() = StatusCode::UNAUTHORIZED;
+    if let Ok(challenge) = challenge.try_into() {
+        resp.headers_mut().insert(WWW_AUTHENTICATE, challenge);
+    }
+    resp
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn headers(authorization: &str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(AUTHORIZATION, HeaderValue::from_str(authorization).unwrap());
+        headers
+    }
+
+    #[tokio::test]
+    async fn bearer_tokens_are_checked() {
+        let auth = Authenticator::new(&AuthConfig::Bearer {
+            tokens: vec!["s3cret".into()],
+        })
+        .unwrap();
+        assert!(auth
+            .authenticate(&mut headers("Bearer s3cret"))
+            .await
+            .is_ok());
+
+        let resp = auth
+            .authenticate(&mut headers("Bearer wrong"))
+            .await
+            .unwrap_err();
+        assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+        assert!(auth.authenticate(&mut HeaderMap::new()).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn basic_credentials_are_checked() {
+        // sha256("hunter2")
+        let credentials = parse_credentials(
+            "# users\nalice:wonderland\nbob:sha256:f52fbd32b2b3b86ff88ef6c490628285f482af15ddcb29541f94bcf526a3f6c7\n",
+        )
+        .unwrap();
+        let auth = Authenticator::Basic {
+            credentials,
+            challenge: HeaderValue::from_static("Basic realm=\"spin\""),
+        };
+
+        let mut alice = headers(&format!("Basic {}", STANDARD.encode("alice:wonderland")));
+        assert!(auth.authenticate(&mut alice).await.is_ok());
+        assert_eq!(alice[AUTH_USER_HEADER], "alice");
+
+        let mut bob = headers(&format!("Basic {}", STANDARD.encode("bob:hunter2")));
+        assert!(auth.authenticate(&mut bob).await.is_ok());
+
+        let mut wrong = headers(&format!("Basic {}", STANDARD.encode("bob:wrong")));
+        let resp = auth.authenticate(&mut wrong).await.unwrap_err();
+        assert_eq!(resp.headers()[WWW_AUTHENTICATE], "Basic realm=\"spin\"");
+    }
+
+    #[test]
+    fn malformed_credentials_file_is_rejected() {
+        assert!(parse_credentials("alice").is_err());
+        assert!(parse_credentials("alice:sha256:abc").is_err());
+        // 64 bytes, but not 64 hex digits
+        let digest = format!("é{}", "0".repeat(62));
+        assert!(parse_credentials(&format!("alice:sha256:{digest}")).is_err());
+    }
+}
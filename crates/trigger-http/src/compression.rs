@@ -0,0 +1,355 @@
+//! Response compression and request decompression for the HTTP trigger.
+
+use std::sync::{Arc, Mutex};
+
+use async_compression::tokio::bufread::{BrotliDecoder, BrotliEncoder, GzipDecoder, GzipEncoder};
+use futures::{future, stream, StreamExt, TryStreamExt};
+use http::{
+    header::{ACCEPT_ENCODING, CONTENT_ENCODING, CONTENT_LENGTH, CONTENT_TYPE, VARY},
+    HeaderMap, HeaderValue, Method, StatusCode,
+};
+use http_body_util::{combinators::BoxBody, BodyStream, StreamBody};
+use hyper::{
+    body::{Bytes, Frame},
+    Request, Response,
+};
+use serde::Deserialize;
+use tokio::io::{AsyncBufRead, AsyncRead};
+use tokio_util::io::{ReaderStream, StreamReader};
+use wasmtime_wasi_http::bindings::http::types::ErrorCode;
+
+use crate::Body;
+
+/// Compression options, read from the `[http_trigger.compression]` runtime
+/// config table. Compression is disabled unless the table is present.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CompressionConfig {
+    /// Whether to compress responses for clients which accept it.
+    #[serde(default = "default_true")]
+    pub compress_responses: bool,
+    /// Whether to decompress compressed request bodies before passing them to
+    /// components.
+    #[serde(default = "default_true")]
+    pub decompress_requests: bool,
+    /// Response encodings to use, in order of preference.
+    #[serde(default = "default_encodings")]
+    pub encodings: Vec<Encoding>,
+    /// Responses with a known length smaller than this many bytes are not
+    /// compressed.
+    #[serde(default = "default_min_size")]
+    pub min_size: u64,
+    /// Content types to compress. A pattern ending in `/*` matches any
+    /// subtype.
+    #[serde(default = "default_content_types")]
+    pub content_types: Vec<String>,
+}
+
+fn default_true() -> bool {
+    true
+}
+
+fn default_encodings() -> Vec<Encoding> {
+    vec![Encoding::Brotli, Encoding::Gzip]
+}
+
+fn default_min_size() -> u64 {
+    1024
+}
+
+fn default_content_types() -> Vec<String> {
+    [
+        "text/*",
+        "application/javascript",
+        "application/json",
+        "application/wasm",
+        "application/xml",
+        "image/svg+xml",
+    ]
+    .into_iter()
+    .map(String::from)
+    .collect()
+}
+
+/// A content encoding supported by the trigger.
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Eq)]
+pub enum Encoding {
+    #[serde(rename = "br")]
+    Brotli,
+    #[serde(rename = "gzip")]
+    Gzip,
+}
+
+impl Encoding {
+    fn name(self) -> &'static str {
+        match self {
+            Self::Brotli => "br",
+            Self::Gzip => "gzip",
+        }
+    }
+
+    fn from_name(name: &str) -> Option<Self> {
+        match name.trim().to_ascii_lowercase().as_str() {
+            "br" => Some(Self::Brotli),
+            "gzip" | "x-gzip" => Some(Self::Gzip),
+            _ => None,
+        }
+    }
+}
+
+impl CompressionConfig {
+    /// Replaces a compressed request body with its decompressed contents.
+    /// Requests with an unsupported encoding are left unchanged.
+    pub(crate) fn decompress_request(&self, req: Request<Body>) -> Request<Body> {
+        if !self.decompress_requests {
+            return req;
+        }
+        let Some(encoding) = req
+            .headers()
+            .get(CONTENT_ENCODING)
+            .and_then(|v| v.to_str().ok())
+            .and_then(Encoding::from_name)
+        else {
+            return req;
+        };
+
+        let (mut parts, body) = req.into_parts();
+        parts.headers.remove(CONTENT_ENCODING);
+        parts.headers.remove(CONTENT_LENGTH);
+        let (reader, trailers) = body_reader(body);
+        let body = match encoding {
+            Encoding::Brotli => reader_body(BrotliDecoder::new(reader)),
+            Encoding::Gzip => reader_body(GzipDecoder::new(reader)),
+        };
+        Request::from_parts(parts, with_trailers(body, trailers))
+    }
+
+    /// Chooses the encoding for the response to a request, based on the
+    /// request's method and `Accept-Encoding` header.
+    pub(crate) fn response_encoding(&self, req: &Request<Body>) -> Option<Encoding> {
+        if !self.compress_responses || req.method() == Method::HEAD {
+            return None;
+        }
+        let accept_encoding = req.headers().get(ACCEPT_ENCODING)?.to_str().ok()?;
+        self.negotiate(accept_encoding)
+    }
+
+    /// Compresses a response with the given encoding, if it is eligible.
+    pub(crate) fn compress_response(
+        &self,
+        resp: Response<Body>,
+        encoding: Encoding,
+    ) -> Response<Body> {
+        if !self.should_compress(resp.status(), resp.headers()) {
+            return resp;
+        }
+
+        let (mut parts, body) = resp.into_parts();
+        parts.headers.remove(CONTENT_LENGTH);
+        parts
+            .headers
+            .insert(CONTENT_ENCODING, HeaderValue::from_static(encoding.name()));
+        parts
+            .headers
+            .append(VARY, HeaderValue::from_static("accept-encoding"));
+        let (reader, trailers) = body_reader(body);
+        let body = match encoding {
+            Encoding::Brotli => reader_body(BrotliEncoder::new(reader)),
+            Encoding::Gzip => reader_body(GzipEncoder::new(reader)),
+        };
+        Response::from_parts(parts, with_trailers(body, trailers))
+    }
+
+    fn negotiate(&self, accept_encoding: &str) -> Option<Encoding> {
+        let accepted = accept_encoding
+            .split(',')
+            .filter_map(|item| {
+                let mut params = item.split(';');
+                let name = params.next()?.trim();
+                let quality = params
+                    .find_map(|p| p.trim().strip_prefix("q="))
+                    .map(|q| q.trim().parse::<f32>().unwrap_or(0.0))
+                    .unwrap_or(1.0);
+                Some((name, quality))
+            })
+            .collect::<Vec<_>>();
+        let quality = |name: &str| {
+            accepted
+                .iter()
+                .find(|(n, _)| n.eq_ignore_ascii_case(name))
+                .or_else(|| accepted.iter().find(|(n, _)| *n == "*"))
+                .map(|(_, q)| *q)
+        };
+        self.encodings
+            .iter()
+            .copied()
+            .find(|encoding| quality(encoding.name()).is_some_and(|q| q > 0.0))
+    }
+
+    fn should_compress(&self, status: StatusCode, headers: &HeaderMap) -> bool {
+        if status.is_informational()
+            || status == StatusCode::NO_CONTENT
+            || status == StatusCode::NOT_MODIFIED
+            || status == StatusCode::PARTIAL_CONTENT
+            || headers.contains_key(CONTENT_ENCODING)
+        {
+            return false;
+        }
+        let content_length = headers
+            .get(CONTENT_LENGTH)
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.parse::<u64>().ok());
+        if content_length.is_some_and(|len| len < self.min_size) {
+            return false;
+        }
+        let Some(content_type) = headers.get(CONTENT_TYPE).and_then(|v| v.to_str().ok()) else {
+            return false;
+        };
+        let mime = content_type
+            .split(';')
+            .next()
+            .unwrap_or_default()
+            .trim()
+            .to_ascii_lowercase();
+        self.content_types
+            .iter()
+            .any(|pattern| match pattern.strip_suffix("/*") {
+                Some(prefix) => mime
+                    .strip_prefix(prefix)
+                    .is_some_and(|rest| rest.starts_with('/')),
+                None => mime == pattern.to_ascii_lowercase(),
+            })
+    }
+}
+
+// Trailers set aside while a body's data is transformed.
+type TrailerSlot = Arc<Mutex<Option<HeaderMap>>>;
+
+// Adapts a body's data frames to an `AsyncBufRead`. Trailers are set aside,
+// to be restored with `with_trailers` once the data has been transformed.
+fn body_reader(body: Body) -> (impl AsyncBufRead + Send + Sync + Unpin, TrailerSlot) {
+    let trailers = TrailerSlot::default();
+    let slot = trailers.clone();
+    let stream = BodyStream::new(body)
+        .try_filter_map(move |frame| {
+            let data = match frame.into_data() {
+                Ok(data) => Some(data),
+                Err(frame) => {
+                    if let Ok(trailers) = frame.into_trailers() {
+                        *slot.lock().unwrap() = Some(trailers);
+                    }
+                    None
+                }
+            };
+            future::ready(Ok(data))
+        })
+        .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, format!("{e:?}")));
+    (StreamReader::new(stream), trailers)
+}
+
+// Appends any trailers set aside by `body_reader` to a transformed body, e.g.
+// so that gRPC status trailers survive compression.
+fn with_trailers(body: Body, trailers: TrailerSlot) -> Body {
+    let trailers = stream::once(future::lazy(move |_| trailers.lock().unwrap().take()))
+        .filter_map(|trailers| future::ready(trailers.map(|t| Ok(Frame::trailers(t)))));
+    BoxBody::new(StreamBody::new(BodyStream::new(body).chain(trailers)))
+}
+
+// Adapts an `AsyncRead` back into a body.
+pub(crate) fn reader_body(reader: impl AsyncRead + Send + Sync + 'static) -> Body {
+    let stream = ReaderStream::new(reader).map(|chunk| {
+        chunk
+            .map(Frame::data)
+            .map_err(|e| ErrorCode::InternalError(Some(e.to_string())))
+    });
+    BoxBody::new(StreamBody::new(stream))
+}
+
+#[cfg(test)]
+mod tests {
+    use http_body_util::BodyExt;
+    use spin_http::body;
+
+    use super::*;
+
+    fn config() -> CompressionConfig {
+        serde_json::from_value(serde_json::json!({})).unwrap()
+    }
+
+    fn response(content_type: &str, data: impl Into<Bytes>) -> Response<Body> {
+        let data = data.into();
+        Response::builder()
+            .header(CONTENT_TYPE, content_type)
+            .header(CONTENT_LENGTH, data.len())
+            .body(body::full(data))
+            .unwrap()
+    }
+
+    #[test]
+    fn negotiates_preferred_encoding() {
+        let config = config();
+        assert_eq!(config.negotiate("gzip, br"), Some(Encoding::Brotli));
+        assert_eq!(config.negotiate("gzip"), Some(Encoding::Gzip));
+        assert_eq!(config.negotiate("br;q=0, gzip;q=0.5"), Some(Encoding::Gzip));
+        assert_eq!(config.negotiate("*"), Some(Encoding::Brotli));
+        assert_eq!(config.negotiate("identity"), None);
+        assert_eq!(config.negotiate("*, br;q=0, gzip;q=0"), None);
+    }
+
+    #[test]
+    fn only_eligible_responses_are_compressed() {
+        let config = config();
+        let large = Bytes::from(vec![b'a'; 2048]);
+
+        let compressed = config.compress_response(
+            response("text/html; charset=utf-8", large.clone()),
+            Encoding::Gzip,
+        );
+        assert_eq!(compressed.headers()[CONTENT_ENCODING], "gzip");
+        assert!(compressed.headers().get(CONTENT_LENGTH).is_none());
+
+        let small = config.compress_response(response("text/html", &b"tiny"[..]), Encoding::Gzip);
+        assert!(small.headers().get(CONTENT_ENCODING).is_none());
+
+        let image = config.compress_response(response("image/png", large), Encoding::Gzip);
+        assert!(image.headers().get(CONTENT_ENCODING).is_none());
+    }
+
+    #[tokio::test]
+    async fn compressed_request_round_trips() {
+        let config = config();
+        let data = Bytes::from(b"hello hello hello hello hello hello".repeat(64));
+
+        let compressed =
+            config.compress_response(response("text/plain", data.clone()), Encoding::Brotli);
+        let (parts, body) = compressed.into_parts();
+        let mut req = Request::post("http://localhost/").body(body).unwrap();
+        req.headers_mut()
+            .insert(CONTENT_ENCODING, parts.headers[CONTENT_ENCODING].clone());
+
+        let req = config.decompress_request(req);
+        assert!(req.headers().get(CONTENT_ENCODING).is_none());
+        let body = req.into_body().collect().await.unwrap().to_bytes();
+        assert_eq!(body, data);
+    }
+
+    #[tokio::test]
+    async fn trailers_survive_compression() {
+        let config = config();
+        let data = Bytes::from(b"data ".repeat(512));
+        let mut trailers = HeaderMap::new();
+        trailers.insert("grpc-status", HeaderValue::from_static("0"));
+        let frames = vec![
+            Ok::<_, ErrorCode>(Frame::data(data)),
+            Ok(Frame::trailers(trailers)),
+        ];
+        let resp = Response::builder()
+            .header(CONTENT_TYPE, "text/plain")
+            .body(BoxBody::new(StreamBody::new(stream::iter(frames))))
+            .unwrap();
+
+        let compressed = config.compress_response(resp, Encoding::Gzip);
+        let collected = compressed.into_body().collect().await.unwrap();
+        assert_eq!(collected.trailers().unwrap()["grpc-status"], "0");
+    }
+}
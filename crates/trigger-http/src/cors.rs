@@ -0,0 +1,222 @@
+//! CORS enforcement for the HTTP trigger.
+
+use anyhow::Result;
+use http::{
+    header::{
+        ACCESS_CONTROL_ALLOW_CREDENTIALS, ACCESS_CONTROL_ALLOW_HEADERS,
+        ACCESS_CONTROL_ALLOW_METHODS, ACCESS_CONTROL_ALLOW_ORIGIN, ACCESS_CONTROL_EXPOSE_HEADERS,
+        ACCESS_CONTROL_MAX_AGE, ACCESS_CONTROL_REQUEST_HEADERS, ACCESS_CONTROL_REQUEST_METHOD,
+        ORIGIN, VARY,
+    },
+    HeaderValue, Method, StatusCode,
+};
+use hyper::{Request, Response};
+use spin_http::{body, config::CorsConfig};
+
+use crate::Body;
+
+/// Answers a CORS preflight request without invoking the component. Returns
+/// `None` if the request is not a preflight.
+pub(crate) fn preflight(
+    config: &CorsConfig,
+    req: &Request<Body>,
+) -> Result<Option<Response<Body>>> {
+    let headers = req.headers();
+    let (Some(origin), Some(method)) = (
+        headers.get(ORIGIN),
+        headers.get(ACCESS_CONTROL_REQUEST_METHOD),
+    ) else {
+        return Ok(None);
+    };
+    if req.method() != Method::OPTIONS {
+        return Ok(None);
+    }
+
+    let mut builder = Response::builder().status(StatusCode::NO_CONTENT).header(
+        VARY,
+        "origin, access-control-request-method, access-control-request-headers",
+    );
+
+    let request_headers = headers
+        .get(ACCESS_CONTROL_REQUEST_HEADERS)
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or_default();
+    let method_allowed = method
+        .to_str()
+        .is_ok_and(|method| contains(&config.allowed_methods, method));
+    let headers_allowed = split_list(request_headers).all(|header| {
+        contains(&config.allowed_headers, header) || wildcard(&config.allowed_headers)
+    });
+
+    let Some(allow_origin) = allowed_origin(config, origin) else {
+        return Ok(Some(builder.body(body::empty())?));
+    };
+    if !method_allowed || !headers_allowed {
+        return Ok(Some(builder.body(body::empty())?));
+    }
+
+    builder = builder
+        .header(ACCESS_CONTROL_ALLOW_ORIGIN, allow_origin)
+        .header(
+            ACCESS_CONTROL_ALLOW_METHODS,
+            config.allowed_methods.join(", "),
+        );
+    if wildcard(&config.allowed_headers) {
+        if !request_headers.is_empty() {
+            builder = builder.header(ACCESS_CONTROL_ALLOW_HEADERS, request_headers);
+        }
+    } else if !config.allowed_headers.is_empty() {
+        builder = builder.header(
+            ACCESS_CONTROL_ALLOW_HEADERS,
+            config.allowed_headers.join(", "),
+        );
+    }
+    if let Some(max_age) = config.max_age {
+        builder = builder.header(ACCESS_CONTROL_MAX_AGE, max_age);
+    }
+    if config.allow_credentials {
+        builder = builder.header(ACCESS_CONTROL_ALLOW_CREDENTIALS, "true");
+    }
+    Ok(Some(builder.body(body::empty())?))
+}
+
+/// Adds CORS headers to the response to a cross-origin request. Responses
+/// which already carry CORS headers are left unchanged.
+pub(crate) fn apply(config: &CorsConfig, origin: Option<&HeaderValue>, resp: &mut Response<Body>) {
+    let headers = resp.headers_mut();
+    if headers.contains_key(ACCESS_CONTROL_ALLOW_ORIGIN) {
+        return;
+    }
+    if !wildcard(&config.allowed_origins) {
+        headers.append(VARY, HeaderValue::from_static("origin"));
+    }
+    let Some(allow_origin) = origin.and_then(|origin| allowed_origin(config, origin)) else {
+        return;
+    };
+    headers.insert(ACCESS_CONTROL_ALLOW_ORIGIN, allow_origin);
+    if config.allow_credentials {
+        headers.insert(
+            ACCESS_CONTROL_ALLOW_CREDENTIALS,
+            HeaderValue::from_static("true"),
+        );
+    }
+    if !config.exposed_headers.is_empty() {
+        if let Ok(exposed) = HeaderValue::from_str(&config.exposed_headers.join(", ")) {
+            headers.insert(ACCESS_CONTROL_EXPOSE_HEADERS, exposed);
+        }
+    }
+}
+
+/// Returns the method a CORS preflight request asks about, if the request is
+/// a preflight.
+pub(crate) fn preflight_method(req: &Request<Body>) -> Option<&str> {
+    if req.method() != Method::OPTIONS || !req.headers().contains_key(ORIGIN) {
+        return None;
+    }
+    req.headers()
+        .get(ACCESS_CONTROL_REQUEST_METHOD)?
+        .to_str()
+        .ok()
+}
+
+// Returns the `Access-Control-Allow-Origin` value for an allowed origin.
+fn allowed_origin(config: &CorsConfig, origin: &HeaderValue) -> Option<HeaderValue> {
+    if wildcard(&config.allowed_origins) {
+        return Some(HeaderValue::from_static("*"));
+    }
+    let origin_str = origin.to_str().ok()?;
+    contains(&config.allowed_origins, origin_str).then(|| origin.clone())
+}
+
+fn wildcard(list: &[String]) -> bool {
+    list.iter().any(|item| item == "*")
+}
+
+fn contains(list: &[String], value: &str) -> bool {
+    list.iter().any(|item| item.eq_ignore_ascii_case(value))
+}
+
+fn split_list(value: &str) -> impl Iterator<Item = &str> {
+    value.split(',').map(str::trim).filter(|s| !s.is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config() -> CorsConfig {
+        CorsConfig {
+            allowed_origins: vec!["https://example.com".into()],
+            allowed_methods: vec!["GET".into(), "PUT".into()],
+            allowed_headers: vec!["content-type".into()],
+            exposed_headers: vec!["x-request-id".into()],
+            max_age: Some(600),
+            allow_credentials: true,
+        }
+    }
+
+    fn preflight_request(origin: &str, method: &str, headers: &str) -> Request<Body> {
+        Request::options("http://localhost/")
+            .header(ORIGIN, origin)
+            .header(ACCESS_CONTROL_REQUEST_METHOD, method)
+            .header(ACCESS_CONTROL_REQUEST_HEADERS, headers)
+            .body(body::empty())
+            .unwrap()
+    }
+
+    #[test]
+    fn allowed_preflight_is_answered() {
+        let req = preflight_request("https://example.com", "PUT", "Content-Type");
+        let resp = preflight(&config(), &req).unwrap().unwrap();
+        assert_eq!(resp.status(), StatusCode::NO_CONTENT);
+        let headers = resp.headers();
+        assert_eq!(headers[ACCESS_CONTROL_ALLOW_ORIGIN], "https://example.com");
+        assert_eq!(headers[ACCESS_CONTROL_ALLOW_METHODS], "GET, PUT");
+        assert_eq!(headers[ACCESS_CONTROL_ALLOW_HEADERS], "content-type");
+        assert_eq!(headers[ACCESS_CONTROL_MAX_AGE], "600");
+        assert_eq!(headers[ACCESS_CONTROL_ALLOW_CREDENTIALS], "true");
+    }
+
+    #[test]
+    fn disallowed_preflight_gets_no_cors_headers() {
+        for req in [
+            preflight_request("https://evil.example", "PUT", ""),
+            preflight_request("https://example.com", "DELETE", ""),
+            preflight_request("https://example.com", "PUT", "x-custom"),
+        ] {
+            let resp = preflight(&config(), &req).unwrap().unwrap();
+            assert!(!resp.headers().contains_key(ACCESS_CONTROL_ALLOW_ORIGIN));
+        }
+    }
+
+    #[test]
+    fn non_preflight_options_is_passed_through() {
+        let req = Request::options("http://localhost/")
+            .body(body::empty())
+            .unwrap();
+        assert!(preflight(&config(), &req).unwrap().is_none());
+        assert_eq!(preflight_method(&req), None);
+        let req = preflight_request("https://example.com", "PUT", "");
+        assert_eq!(preflight_method(&req), Some("PUT"));
+    }
+
+    #[test]
+    fn actual_response_gets_cors_headers() {
+        let origin = HeaderValue::from_static("https://example.com");
+        let mut resp = Response::new(body::empty());
+        apply(&config(), Some(&origin), &mut resp);
+        let headers = resp.headers();
+        assert_eq!(headers[ACCESS_CONTROL_ALLOW_ORIGIN], "https://example.com");
+        assert_eq!(headers[ACCESS_CONTROL_EXPOSE_HEADERS], "x-request-id");
+        assert_eq!(headers[VARY], "origin");
+
+        let wildcard = CorsConfig {
+            allowed_origins: vec!["*".into()],
+            ..Default::default()
+        };
+        let mut resp = Response::new(body::empty());
+        apply(&wildcard, Some(&origin), &mut resp);
+        assert_eq!(resp.headers()[ACCESS_CONTROL_ALLOW_ORIGIN], "*");
+        assert!(!resp.headers().contains_key(VARY));
+    }
+}
@@ -0,0 +1,404 @@
+//! Per-request debug capture.
+//!
+//! A request is captured if it carries the token from the
+//! `[http_trigger.debug_capture]` runtime config table in its `X-Spin-Debug`
+//! header, or if the trigger was started with `--debug-capture`. The bundle
+//! for a captured request - the tree of spans of its handling, the output of
+//! components, the decisions of the allowed-hosts checker on outbound
+//! connections, and instance memory use - is written as JSON to `debug/` in
+//! the state directory once the response has been sent, and its path is
+//! given in the `X-Spin-Debug-Capture` response header.
+//!
+//! If the `[observability]` runtime config table asks for slow or failed
+//! requests to be retained, every request is captured, and the bundles of
+//! those which turn out to be slow or to fail are written to `samples/` in the
+//! state directory. Only the most recent of these are kept.
+
+use std::{
+    path::{Path, PathBuf},
+    pin::Pin,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    task::{Context as TaskContext, Poll},
+    time::{Duration, Instant},
+};
+
+use anyhow::{Context, Result};
+use chrono::Utc;
+use http::{HeaderMap, HeaderValue};
+use http_body_util::BodyExt;
+use hyper::{
+    body::{Body as _, Bytes, Frame, SizeHint},
+    Request, Response,
+};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use spin_telemetry::capture::Capture;
+use spin_trigger::ObservabilityOpts;
+use wasmtime_wasi_http::bindings::http::types::ErrorCode;
+
+use crate::{auth::constant_time_eq, Body};
+
+/// The request header which asks for a request to be captured.
+pub const DEBUG_HEADER: &str = "x-spin-debug";
+/// The response header giving the path of the bundle of a captured request.
+pub const CAPTURE_HEADER: &str = "x-spin-debug-capture";
+
+/// The directory in the state directory to which bundles are written.
+const CAPTURE_DIR: &str = "debug";
+/// The directory in the state directory to which the bundles of retained
+/// slow and failed requests are written.
+const SAMPLE_DIR: &str = "samples";
+
+/// Headers whose values are left out of bundles.
+const REDACTED_HEADERS: &[&str] = &["authorization", "cookie", "proxy-authorization"];
+
+/// Options for debug capture, read from the `[http_trigger.debug_capture]`
+/// runtime config table.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct DebugCaptureConfig {
+    /// The token a request must send in the `X-Spin-Debug` header to be
+    /// captured. May reference application variables.
+    pub token: String,
+}
+
+/// Decides which requests to capture, and writes their bundles.
+pub(crate) struct DebugCapture {
+    token: Option<String>,
+    capture_all: bool,
+    dir: PathBuf,
+    sampling: Option<Arc<Sampling>>,
+}
+
+/// Which requests are retained when not asked to be captured.
+struct Sampling {
+    slow_request_threshold: Option<Duration>,
+    errors: bool,
+    max_samples: usize,
+    dir: PathBuf,
+}
+
+impl DebugCapture {
+    /// Returns the capture settings, or `None` if no requests are to be
+    /// captured.
+    pub fn new(
+        token: Option<String>,
+        capture_all: bool,
+        observability: &ObservabilityOpts,
+        state_dir: Option<&Path>,
+    ) -> Result<Option<Self>> {
+        if token.is_none() && !capture_all && !observability.is_enabled() {
+            return Ok(None);
+        }
+        if let Some(token) = &token {
+            anyhow::ensure!(!token.is_empty(), "debug capture token must not be empty");
+        }
+        let state_dir =
+            state_dir.context("Debug capture needs a state directory to write bundles to")?;
+        let sampling = observability.is_enabled().then(|| {
+            Arc::new(Sampling {
+                slow_request_threshold: observability.slow_request_threshold(),
+                errors: observability.sample_errors,
+                max_samples: observability.max_samples(),
+                dir: state_dir.join(SAMPLE_DIR),
+            })
+        });
+        spin_telemetry::capture::enable();
+        Ok(Some(Self {
+            token,
+            capture_all,
+            dir: state_dir.join(CAPTURE_DIR),
+            sampling,
+        }))
+    }
+
+    /// Captures every request, not only those which ask to be.
+    pub fn capture_all(&mut self) {
+        self.capture_all = true;
+    }
+
+    /// Starts capturing the request if it should be. The debug header is
+    /// removed, so that the token is not passed on to the component.
+    pub fn start(&self, req: &mut Request<Body>) -> Option<RequestCapture> {
+        let header = req.headers_mut().remove(DEBUG_HEADER);
+        let requested = match (&self.token, &header) {
+            (Some(token), Some(value)) => constant_time_eq(token.as_bytes(), value.as_bytes()),
+            _ => false,
+        };
+        if !(self.capture_all || requested || self.sampling.is_some()) {
+            return None;
+        }
+        Some(RequestCapture {
+            capture: Capture::new(),
+            started: Instant::now(),
+            requested: self.capture_all || requested,
+            dir: self.dir.clone(),
+            sampling: self.sampling.clone(),
+            name: bundle_name(),
+            request: json!({
+                "method": req.method().as_str(),
+                "uri": req.uri().to_string(),
+                "version": format!("{:?}", req.version()),
+                "headers": headers_json(req.headers()),
+            }),
+        })
+    }
+}
+
+/// The capture of a single request.
+pub(crate) struct RequestCapture {
+    capture: Capture,
+    started: Instant,
+    // Whether the request asked to be captured, rather than being sampled
+    requested: bool,
+    dir: PathBuf,
+    sampling: Option<Arc<Sampling>>,
+    name: String,
+    request: Value,
+}
+
+impl RequestCapture {
+    pub fn capture(&self) -> &Capture {
+        &self.capture
+    }
+
+    /// Decides whether to keep the capture. If the request asked to be
+    /// captured, names the bundle in the response. The bundle is written once
+    /// the response body has been sent.
+    pub fn finish(self, res: Result<Response<Body>>) -> Result<Response<Body>> {
+        let failed = match &res {
+            Ok(res) => res.status().is_server_error(),
+            Err(_) => true,
+        };
+        if !self.requested {
+            let retain = self.sampling.as_ref().is_some_and(|sampling| {
+                (failed && sampling.errors)
+                    || sampling
+                        .slow_request_threshold
+                        .is_some_and(|threshold| self.started.elapsed() >= threshold)
+            });
+            if !retain {
+                return res;
+            }
+        }
+        let mut res = match res {
+            Ok(res) => res,
+            Err(e) => {
+                self.write(json!({ "error": format!("{e:?}") }));
+                return Err(e);
+            }
+        };
+        if self.requested {
+            let path = self.dir.join(&self.name);
+            if let Ok(value) = HeaderValue::from_str(&path.to_string_lossy()) {
+                res.headers_mut().insert(CAPTURE_HEADER, value);
+            }
+        }
+        let response = json!({
+            "status": res.status().as_u16(),
+            "headers": headers_json(res.headers()),
+        });
+        Ok(res.map(|inner| {
+            CapturedBody {
+                inner,
+                bytes: 0,
+                capture: Some((self, response)),
+            }
+            .boxed()
+        }))
+    }
+
+    fn write(self, response: Value) {
+        let Self {
+            capture,
+            requested,
+            dir,
+            sampling,
+            name,
+            request,
+            ..
+        } = self;
+        let mut bundle = capture.bundle();
+        bundle["request"] = request;
+        bundle["response"] = response;
+        let dir = match &sampling {
+            Some(sampling) if !requested => &sampling.dir,
+            _ => &dir,
+        };
+        let path = dir.join(name);
+        let result =
+            std::fs::create_dir_all(dir).and_then(|_| std::fs::write(&path, bundle.to_string()));
+        match result {
+            Ok(()) => tracing::info!("Wrote debug capture to {}", path.display()),
+            Err(e) => tracing::warn!("Failed to write debug capture to {}: {e}", path.display()),
+        }
+        if let Some(sampling) = sampling.filter(|_| !requested) {
+            if let Err(e) = sampling.prune() {
+                tracing::warn!("Failed to remove old samples: {e}");
+            }
+        }
+    }
+}
+
+impl Sampling {
+    /// Removes the oldest samples beyond the number to keep.
+    fn prune(&self) -> std::io::Result<()> {
+        let mut names = std::fs::read_dir(&self.dir)?
+            .filter_map(|entry| entry.ok()?.file_name().into_string().ok())
+            .filter(|name| name.ends_with(".json"))
+            .collect::<Vec<_>>();
+        if names.len() <= self.max_samples {
+            return Ok(());
+        }
+        // Bundle names sort by the time they were captured.
+        names.sort();
+        let excess = names.len() - self.max_samples;
+        for name in &names[..excess] {
+            std::fs::remove_file(self.dir.join(name))?;
+        }
+        Ok(())
+    }
+}
+
+/// A response body which writes the bundle of its request once it has been
+/// sent or dropped.
+struct CapturedBody {
+    inner: Body,
+    bytes: u64,
+    capture: Option<(RequestCapture, Value)>,
+}
+
+impl hyper::body::Body for CapturedBody {
+    type Data = Bytes;
+    type Error = ErrorCode;
+
+    fn poll_frame(
+        mut self: Pin<&mut Self>,
+        cx: &mut TaskContext<'_>,
+    ) -> Poll<Option<Result<Frame<Self::Data>, Self::Error>>> {
+        let poll = Pin::new(&mut self.inner).poll_frame(cx);
+        if let Poll::Ready(Some(Ok(frame))) = &poll {
+            if let Some(data) = frame.data_ref() {
+                self.bytes += data.len() as u64;
+            }
+        }
+        poll
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.inner.is_end_stream()
+    }
+
+    fn size_hint(&self) -> SizeHint {
+        self.inner.size_hint()
+    }
+}
+
+impl Drop for CapturedBody {
+    fn drop(&mut self) {
+        if let Some((capture, mut response)) = self.capture.take() {
+            response["body_bytes"] = self.bytes.into();
+            capture.write(response);
+        }
+    }
+}
+
+/// A unique name for a bundle, which sorts by the time it was captured.
+fn bundle_name() -> String {
+    static SEQUENCE: AtomicU64 = AtomicU64::new(0);
+    let sequence = SEQUENCE.fetch_add(1, Ordering::Relaxed);
+    format!(
+        "{}-{}-{sequence:06}.json",
+        Utc::now().format("%Y%m%dT%H%M%S%.3fZ"),
+        std::process::id()
+    )
+}
+
+fn headers_json(headers: &HeaderMap) -> Value {
+    headers
+        .iter()
+        .map(|(name, value)| {
+            let value = if REDACTED_HEADERS.contains(&name.as_str()) {
+                "<redacted>".into()
+            } else {
+                String::from_utf8_lossy(value.as_bytes()).into_owned()
+            };
+            json!([name.as_str(), value])
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(debug: Option<&str>) -> Request<Body> {
+        let mut builder = Request::get("/hello").header("authorization", "Bearer secret");
+        if let Some(debug) = debug {
+            builder = builder.header(DEBUG_HEADER, debug);
+        }
+        builder.body(spin_http::body::empty()).unwrap()
+    }
+
+    #[test]
+    fn only_requests_with_the_token_are_captured() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let capture = DebugCapture::new(
+            Some("t0ken".into()),
+            false,
+            &Default::default(),
+            Some(dir.path()),
+        )?
+        .unwrap();
+
+        assert!(capture.start(&mut request(None)).is_none());
+        assert!(capture.start(&mut request(Some("wrong"))).is_none());
+
+        let mut req = request(Some("t0ken"));
+        let captured = capture.start(&mut req).unwrap();
+        assert!(req.headers().get(DEBUG_HEADER).is_none());
+        assert!(captured.requested);
+        assert_eq!(captured.dir, dir.path().join(CAPTURE_DIR));
+        assert_eq!(captured.request["headers"][0][1], "<redacted>");
+        Ok(())
+    }
+
+    #[test]
+    fn capture_all_needs_no_token() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let opts = ObservabilityOpts::default();
+        assert!(DebugCapture::new(None, false, &opts, Some(dir.path()))?.is_none());
+        assert!(DebugCapture::new(None, true, &opts, None).is_err());
+
+        let capture = DebugCapture::new(None, true, &opts, Some(dir.path()))?.unwrap();
+        assert!(capture.start(&mut request(None)).is_some());
+        Ok(())
+    }
+
+    #[test]
+    fn only_failed_requests_are_sampled() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let opts = ObservabilityOpts {
+            sample_errors: true,
+            max_samples: Some(1),
+            ..Default::default()
+        };
+        let capture = DebugCapture::new(None, false, &opts, Some(dir.path()))?.unwrap();
+        let samples = dir.path().join(SAMPLE_DIR);
+
+        let ok = Response::new(spin_http::body::empty());
+        let captured = capture.start(&mut request(None)).unwrap();
+        drop(captured.finish(Ok(ok))?);
+        assert!(!samples.exists());
+
+        for _ in 0..2 {
+            let captured = capture.start(&mut request(None)).unwrap();
+            assert!(captured.finish(Err(anyhow::anyhow!("boom"))).is_err());
+        }
+        assert_eq!(std::fs::read_dir(&samples)?.count(), 1);
+        Ok(())
+    }
+}
@@ -1,6 +1,9 @@
 use std::{net::SocketAddr, str, str::FromStr};
 
-use crate::{Body, ChainedRequestHandler, HttpExecutor, HttpInstance, HttpTrigger, Store};
+use crate::{
+    keep_warm::{WarmInstance, WarmPools},
+    Body, ChainedRequestHandler, HttpExecutor, HttpInstance, HttpTrigger, Store,
+};
 use anyhow::bail;
 use anyhow::{anyhow, Context, Result};
 use futures::TryFutureExt;
@@ -13,15 +16,17 @@ use spin_core::wasi_2023_10_18::exports::wasi::http::incoming_handler::Guest as
 use spin_core::wasi_2023_11_10::exports::wasi::http::incoming_handler::Guest as IncomingHandler2023_11_10;
 use spin_core::Instance;
 use spin_http::body;
-use spin_trigger::TriggerAppEngine;
+use spin_trigger::{TriggerAppEngine, TriggerExecutor};
 use spin_world::v1::http_types;
 use std::sync::Arc;
 use tokio::{sync::oneshot, task};
 use tracing::{instrument, Instrument};
 use wasmtime_wasi_http::{proxy::Proxy, WasiHttpView};
 
-#[derive(Clone)]
-pub struct HttpHandlerExecutor;
+#[derive(Clone, Default)]
+pub struct HttpHandlerExecutor {
+    warm_pools: Arc<WarmPools>,
+}
 
 #[async_trait]
 impl HttpExecutor for HttpHandlerExecutor {
@@ -40,21 +45,55 @@ impl HttpExecutor for HttpHandlerExecutor {
             component_id
         );
 
-        let (instance, mut store) = engine.prepare_instance(component_id).await?;
-        let HttpInstance::Component(instance) = instance else {
-            unreachable!()
+        let permit = engine.acquire_permit(component_id).await?;
+        let mut warm = match self.warm_pools.take(component_id) {
+            Some(warm) => warm,
+            None => {
+                let (instance, store) = engine.prepare_instance(component_id).await?;
+                let HttpInstance::Component(instance) = instance else {
+                    unreachable!()
+                };
+                WarmInstance::new(store, instance)
+            }
         };
 
-        set_http_origin_from_request(&mut store, engine.clone(), self, &req);
+        set_http_origin_from_request(&mut warm.store, engine.clone(), self, &req);
 
-        let resp = match HandlerType::from_exports(instance.exports(&mut store)) {
+        let resp = match HandlerType::from_exports(warm.instance.exports(&mut warm.store)) {
             Some(HandlerType::Wasi) => {
-                Self::execute_wasi(store, instance, base, raw_route, req, client_addr).await?
+                let warm_pools = self.warm_pools.clone();
+                let component_id = component_id.to_owned();
+                // The response body is produced after this returns, so the
+                // permit is held until the instance is recycled
+                let recycle = move |warm: WarmInstance| {
+                    spin_telemetry::metrics::record_memory(
+                        HttpTrigger::TRIGGER_TYPE,
+                        &component_id,
+                        warm.store.as_ref().data().memory_consumed(),
+                    );
+                    warm_pools.put(&component_id, warm);
+                    drop(permit);
+                };
+                Self::execute_wasi(warm, base, raw_route, req, client_addr, recycle).await?
             }
             Some(HandlerType::Spin) => {
-                Self::execute_spin(store, instance, base, raw_route, req, client_addr)
-                    .await
-                    .map_err(contextualise_err)?
+                let resp = Self::execute_spin(
+                    &mut warm.store,
+                    warm.instance,
+                    base,
+                    raw_route,
+                    req,
+                    client_addr,
+                )
+                .await
+                .map_err(contextualise_err)?;
+                spin_telemetry::metrics::record_memory(
+                    HttpTrigger::TRIGGER_TYPE,
+                    component_id,
+                    warm.store.as_ref().data().memory_consumed(),
+                );
+                self.warm_pools.put(component_id, warm);
+                resp
             }
             None => bail!(
                 "Expected component to either export `{WASI_HTTP_EXPORT_2023_10_18}`, \
@@ -72,8 +111,12 @@ impl HttpExecutor for HttpHandlerExecutor {
 }
 
 impl HttpHandlerExecutor {
+    pub(crate) fn new(warm_pools: Arc<WarmPools>) -> Self {
+        Self { warm_pools }
+    }
+
     pub async fn execute_spin(
-        mut store: Store,
+        store: &mut Store,
         instance: Instance,
         base: &str,
         raw_route: &str,
@@ -82,14 +125,16 @@ impl HttpHandlerExecutor {
     ) -> Result<Response<Body>> {
         let headers = Self::headers(&req, raw_route, base, client_addr)?;
         let func = instance
-            .exports(&mut store)
+            .exports(&mut *store)
             .instance("fermyon:spin/inbound-http")
             // Safe since we have already checked that this instance exists
             .expect("no fermyon:spin/inbound-http found")
             .typed_func::<(http_types::Request,), (http_types::Response,)>("handle-request")?;
 
         let (parts, body) = req.into_parts();
-        let bytes = body.collect().await?.to_bytes().to_vec();
+        // The guest takes the body as a list, so it is collected, but the
+        // collected buffer is reused rather than copied where possible
+        let bytes = Vec::from(body.collect().await?.to_bytes());
 
         let method = if let Some(method) = Self::method(&parts.method) {
             method
@@ -117,7 +162,7 @@ impl HttpHandlerExecutor {
             body: Some(bytes),
         };
 
-        let (resp,) = func.call_async(&mut store, (req,)).await?;
+        let (resp,) = func.call_async(&mut *store, (req,)).await?;
 
         if resp.status < 100 || resp.status > 600 {
             tracing::error!("malformed HTTP status code");
@@ -153,12 +198,12 @@ impl HttpHandlerExecutor {
     }
 
     async fn execute_wasi(
-        mut store: Store,
-        instance: Instance,
+        mut warm: WarmInstance,
         base: &str,
         raw_route: &str,
         mut req: Request<Body>,
         client_addr: SocketAddr,
+        recycle: impl FnOnce(WarmInstance) + Send + 'static,
     ) -> anyhow::Result<Response<Body>> {
         let headers = Self::headers(&req, raw_route, base, client_addr)?;
         req.headers_mut().clear();
@@ -172,10 +217,11 @@ impl HttpHandlerExecutor {
                 };
                 Some((name, value))
             }));
-        let request = store.as_mut().data_mut().new_incoming_request(req)?;
+        let request = warm.store.as_mut().data_mut().new_incoming_request(req)?;
 
         let (response_tx, response_rx) = oneshot::channel();
-        let response = store
+        let response = warm
+            .store
             .as_mut()
             .data_mut()
             .new_response_outparam(response_tx)?;
@@ -186,8 +232,9 @@ impl HttpHandlerExecutor {
             Handler2023_10_18(IncomingHandler2023_10_18),
         }
 
-        let handler = match instance
-            .exports(&mut store)
+        let handler = match warm
+            .instance
+            .exports(&mut warm.store)
             .instance("wasi:http/incoming-handler@0.2.0-rc-2023-10-18")
         {
             Some(mut instance) => Some(Handler::Handler2023_10_18(IncomingHandler2023_10_18::new(
@@ -197,8 +244,9 @@ impl HttpHandlerExecutor {
         };
         let handler = match handler {
             Some(handler) => Some(handler),
-            None => match instance
-                .exports(&mut store)
+            None => match warm
+                .instance
+                .exports(&mut warm.store)
                 .instance("wasi:http/incoming-handler@0.2.0-rc-2023-11-10")
             {
                 Some(mut instance) => Some(Handler::Handler2023_11_10(
@@ -209,29 +257,29 @@ impl HttpHandlerExecutor {
         };
         let handler = match handler {
             Some(handler) => handler,
-            None => Handler::Latest(Proxy::new(&mut store, &instance)?),
+            None => Handler::Latest(Proxy::new(&mut warm.store, &warm.instance)?),
         };
 
         let span = tracing::debug_span!("execute_wasi");
-        let handle = task::spawn(
+        let handle = task::spawn(spin_telemetry::capture::in_current_capture(
             async move {
                 let result = match handler {
                     Handler::Latest(proxy) => {
                         proxy
                             .wasi_http_incoming_handler()
-                            .call_handle(&mut store, request, response)
+                            .call_handle(&mut warm.store, request, response)
                             .instrument(span)
                             .await
                     }
                     Handler::Handler2023_10_18(proxy) => {
                         proxy
-                            .call_handle(&mut store, request, response)
+                            .call_handle(&mut warm.store, request, response)
                             .instrument(span)
                             .await
                     }
                     Handler::Handler2023_11_10(proxy) => {
                         proxy
-                            .call_handle(&mut store, request, response)
+                            .call_handle(&mut warm.store, request, response)
                             .instrument(span)
                             .await
                     }
@@ -239,16 +287,24 @@ impl HttpHandlerExecutor {
 
                 tracing::trace!(
                     "wasi-http memory consumed: {}",
-                    store.as_ref().data().memory_consumed()
+                    warm.store.as_ref().data().memory_consumed()
                 );
 
+                if result.is_ok() {
+                    recycle(warm);
+                }
                 result
             }
             .in_current_span(),
-        );
+        ));
+
+        // Stop the guest if we stop waiting for its response, e.g. because the
+        // execution timeout elapsed.
+        let mut abort_guard = AbortOnDrop(Some(handle.abort_handle()));
 
         match response_rx.await {
             Ok(response) => {
+                abort_guard.0 = None;
                 task::spawn(
                     async move {
                         handle
@@ -332,6 +388,17 @@ impl HttpHandlerExecutor {
     }
 }
 
+// Aborts a task when dropped, unless disarmed by taking the handle.
+struct AbortOnDrop(Option<task::AbortHandle>);
+
+impl Drop for AbortOnDrop {
+    fn drop(&mut self) {
+        if let Some(handle) = self.0.take() {
+            handle.abort();
+        }
+    }
+}
+
 /// Whether this handler uses the custom Spin http handler interface for wasi-http
 enum HandlerType {
     Spin,
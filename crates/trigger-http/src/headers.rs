@@ -0,0 +1,137 @@
+//! Header rewrite rules for the HTTP trigger.
+
+use anyhow::{bail, Context, Result};
+use http::{HeaderMap, HeaderName, HeaderValue};
+use spin_http::config::{HeaderRewriteConfig, HeaderRulesConfig};
+
+use crate::{auth::AUTH_HEADER_PREFIX, PATH_PARAM_HEADER_PREFIX};
+
+/// The header rewrites for a component's requests and responses.
+pub(crate) struct HeaderRewrites {
+    request: HeaderRules,
+    response: HeaderRules,
+}
+
+impl HeaderRewrites {
+    pub fn new(config: &HeaderRulesConfig) -> Result<Self> {
+        let request = HeaderRules::new(&config.request).context("Invalid request header rules")?;
+        if let Some(name) = request.names().find(|name| {
+            name.as_str().starts_with(AUTH_HEADER_PREFIX)
+                || name.as_str().starts_with(PATH_PARAM_HEADER_PREFIX)
+        }) {
+            bail!("Request header rules cannot modify '{name}', which is set by Spin");
+        }
+        let response =
+            HeaderRules::new(&config.response).context("Invalid response header rules")?;
+        Ok(Self { request, response })
+    }
+
+    /// Rewrites the headers of a request to the component.
+    pub fn rewrite_request(&self, headers: &mut HeaderMap) {
+        self.request.apply(headers)
+    }
+
+    /// Rewrites the headers of a response to the client.
+    pub fn rewrite_response(&self, headers: &mut HeaderMap) {
+        self.response.apply(headers)
+    }
+}
+
+struct HeaderRules {
+    remove: Vec<HeaderName>,
+    set: Vec<(HeaderName, HeaderValue)>,
+}
+
+impl HeaderRules {
+    fn new(config: &HeaderRewriteConfig) -> Result<Self> {
+        let remove = config
+            .remove
+            .iter()
+            .map(|name| parse_name(name))
+            .collect::<Result<_>>()?;
+        let set = config
+            .set
+            .iter()
+            .map(|(name, value)| {
+                let value = HeaderValue::from_str(value)
+                    .with_context(|| format!("invalid value for header '{name}'"))?;
+                Ok((parse_name(name)?, value))
+            })
+            .collect::<Result<_>>()?;
+        Ok(Self { remove, set })
+    }
+
+    fn names(&self) -> impl Iterator<Item = &HeaderName> {
+        self.remove
+            .iter()
+            .chain(self.set.iter().map(|(name, _)| name))
+    }
+
+    fn apply(&self, headers: &mut HeaderMap) {
+        for name in &self.remove {
+            headers.remove(name);
+        }
+        for (name, value) in &self.set {
+            headers.insert(name.clone(), value.clone());
+        }
+    }
+}
+
+fn parse_name(name: &str) -> Result<HeaderName> {
+    HeaderName::from_bytes(name.as_bytes()).with_context(|| format!("invalid header name {name:?}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rules(set: &[(&str, &str)], remove: &[&str]) -> HeaderRewriteConfig {
+        HeaderRewriteConfig {
+            set: set
+                .iter()
+                .map(|(name, value)| (name.to_string(), value.to_string()))
+                .collect(),
+            remove: remove.iter().map(|name| name.to_string()).collect(),
+        }
+    }
+
+    #[test]
+    fn rules_remove_then_set() {
+        let rewrites = HeaderRewrites::new(&HeaderRulesConfig {
+            request: rules(&[("X-Forwarded-Proto", "https")], &["x-forwarded-proto"]),
+            response: rules(
+                &[("strict-transport-security", "max-age=63072000")],
+                &["server", "x-powered-by"],
+            ),
+        })
+        .unwrap();
+
+        let mut headers = HeaderMap::new();
+        headers.insert("x-forwarded-proto", HeaderValue::from_static("http"));
+        rewrites.rewrite_request(&mut headers);
+        assert_eq!(headers["x-forwarded-proto"], "https");
+
+        let mut headers = HeaderMap::new();
+        headers.insert("server", HeaderValue::from_static("spin"));
+        headers.insert("content-type", HeaderValue::from_static("text/plain"));
+        rewrites.rewrite_response(&mut headers);
+        assert!(!headers.contains_key("server"));
+        assert_eq!(headers["strict-transport-security"], "max-age=63072000");
+        assert_eq!(headers["content-type"], "text/plain");
+    }
+
+    #[test]
+    fn invalid_rules_are_rejected() {
+        for request in [
+            rules(&[("bad header", "x")], &[]),
+            rules(&[("x-ok", "bad\nvalue")], &[]),
+            rules(&[], &["spin-auth-user"]),
+        ] {
+            let config = HeaderRulesConfig {
+                request,
+                ..Default::default()
+            };
+            assert!(HeaderRewrites::new(&config).is_err());
+        }
+    }
+}
@@ -0,0 +1,224 @@
+//! Liveness and readiness endpoints for the HTTP trigger.
+
+use std::{collections::BTreeMap, sync::Arc};
+
+use anyhow::{bail, Context, Result};
+use http::{header::CONTENT_TYPE, StatusCode};
+use hyper::Response;
+use serde::{Deserialize, Serialize};
+use spin_http::body;
+use spin_key_value::Store;
+use spin_sqlite::Connection;
+use spin_trigger::TriggerAppEngine;
+use tokio::sync::OnceCell;
+
+use crate::{Body, HttpTrigger};
+
+/// Health endpoints, read from the `[http_trigger.health]` runtime config
+/// table. Served only if the table is present.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct HealthConfig {
+    /// Path of the liveness endpoint, which succeeds while the trigger is
+    /// serving requests.
+    pub liveness_path: String,
+    /// Path of the readiness endpoint, which succeeds once every component
+    /// can be instantiated. Lazily-prepared components are compiled by the
+    /// readiness check itself.
+    pub readiness_path: String,
+    /// Whether readiness also requires the default key-value store and
+    /// SQLite database to be reachable.
+    pub check_backends: bool,
+}
+
+impl Default for HealthConfig {
+    fn default() -> Self {
+        Self {
+            liveness_path: "/healthz".into(),
+            readiness_path: "/readyz".into(),
+            check_backends: false,
+        }
+    }
+}
+
+/// The result of a health check, serialized as the endpoint's response body.
+#[derive(Debug, Default, Serialize)]
+struct HealthReport {
+    status: CheckStatus,
+    checks: BTreeMap<String, CheckResult>,
+}
+
+#[derive(Debug, Serialize)]
+struct CheckResult {
+    status: CheckStatus,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+enum CheckStatus {
+    #[default]
+    Ok,
+    Unavailable,
+}
+
+impl HealthReport {
+    fn record(&mut self, name: impl Into<String>, result: Result<()>) {
+        let result = match result {
+            Ok(()) => CheckResult {
+                status: CheckStatus::Ok,
+                error: None,
+            },
+            Err(e) => {
+                self.status = CheckStatus::Unavailable;
+                CheckResult {
+                    status: CheckStatus::Unavailable,
+                    error: Some(format!("{e:#}")),
+                }
+            }
+        };
+        self.checks.insert(name.into(), result);
+    }
+
+    fn into_response(self) -> Result<Response<Body>> {
+        let status = match self.status {
+            CheckStatus::Ok => StatusCode::OK,
+            CheckStatus::Unavailable => StatusCode::SERVICE_UNAVAILABLE,
+        };
+        Ok(Response::builder()
+            .status(status)
+            .header(CONTENT_TYPE, "application/json")
+            .body(body::full(serde_json::to_vec(&self)?.into()))?)
+    }
+}
+
+/// Serves the health endpoints.
+pub(crate) struct HealthChecks {
+    config: HealthConfig,
+    // Backends are opened on first use, and retried until they open
+    key_value: OnceCell<Arc<dyn Store>>,
+    sqlite: OnceCell<Arc<dyn Connection>>,
+}
+
+impl HealthChecks {
+    pub fn new(config: HealthConfig) -> Result<Self> {
+        for path in [&config.liveness_path, &config.readiness_path] {
+            if !path.starts_with('/') {
+                bail!("health endpoint path {path:?} must start with '/'");
+            }
+        }
+        Ok(Self {
+            config,
+            key_value: OnceCell::new(),
+            sqlite: OnceCell::new(),
+        })
+    }
+
+    /// Answers a request to a health endpoint. Returns `None` if the path is
+    /// not a health endpoint.
+    pub async fn respond(
+        &self,
+        path: &str,
+        engine: &TriggerAppEngine<HttpTrigger>,
+    ) -> Option<Result<Response<Body>>> {
+        if path == self.config.liveness_path {
+            Some(HealthReport::default().into_response())
+        } else if path == self.config.readiness_path {
+            Some(self.readiness(engine).await.into_response())
+        } else {
+            None
+        }
+    }
+
+    async fn readiness(&self, engine: &TriggerAppEngine<HttpTrigger>) -> HealthReport {
+        let mut report = HealthReport::default();
+        for (_, config) in engine.trigger_configs() {
+            let id = &config.component;
+            // Lazy components are compiled here rather than on their first
+            // request, so that a ready trigger never stalls on compilation
+            let result = if engine.is_prepared(id) {
+                Ok(())
+            } else if engine.is_lazy(id) {
+                engine
+                    .prepare_component(id)
+                    .await
+                    .context("lazy component could not be prepared")
+            } else {
+                Err(anyhow::anyhow!("component is not prepared"))
+            };
+            report.record(format!("component:{id}"), result);
+        }
+        if self.config.check_backends {
+            report.record("key_value:default", self.check_key_value(engine).await);
+            report.record("sqlite:default", self.check_sqlite(engine).await);
+        }
+        report
+    }
+
+    async fn check_key_value(&self, engine: &TriggerAppEngine<HttpTrigger>) -> Result<()> {
+        let store = self
+            .key_value
+            .get_or_try_init(|| async {
+                let (_, manager) = engine
+                    .runtime_config()
+                    .key_value_stores()?
+                    .into_iter()
+                    .find(|(name, _)| name == "default")
+                    .context("no default key-value store")?;
+                manager
+                    .get("default")
+                    .await
+                    .map_err(|e| anyhow::anyhow!("{e:?}"))
+            })
+            .await?;
+        store
+            .exists("spin-readiness-probe")
+            .await
+            .map_err(|e| anyhow::anyhow!("{e:?}"))?;
+        Ok(())
+    }
+
+    async fn check_sqlite(&self, engine: &TriggerAppEngine<HttpTrigger>) -> Result<()> {
+        let connection = self
+            .sqlite
+            .get_or_try_init(|| async {
+                engine
+                    .runtime_config()
+                    .sqlite_databases()
+                    .await?
+                    .into_iter()
+                    .find(|(name, _)| name == "default")
+                    .map(|(_, connection)| connection)
+                    .context("no default SQLite database")
+            })
+            .await?;
+        connection
+            .query("SELECT 1", vec![])
+            .await
+            .map_err(|e| anyhow::anyhow!("{e:?}"))?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn failed_checks_make_report_unavailable() {
+        let mut report = HealthReport::default();
+        report.record("component:a", Ok(()));
+        assert_eq!(report.status, CheckStatus::Ok);
+        report.record("sqlite:default", Err(anyhow::anyhow!("locked")));
+        assert_eq!(report.status, CheckStatus::Unavailable);
+
+        let json = serde_json::to_value(&report).unwrap();
+        assert_eq!(json["status"], "unavailable");
+        assert_eq!(json["checks"]["component:a"]["status"], "ok");
+        assert_eq!(json["checks"]["sqlite:default"]["error"], "locked");
+
+        let resp = report.into_response().unwrap();
+        assert_eq!(resp.status(), StatusCode::SERVICE_UNAVAILABLE);
+    }
+}
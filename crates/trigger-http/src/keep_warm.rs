@@ -0,0 +1,164 @@
+//! Reuse of component instances across requests.
+
+use std::{
+    collections::HashMap,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use anyhow::{bail, Result};
+use serde::Deserialize;
+use spin_core::Instance;
+
+use crate::{runtime_config::HttpTriggerRuntimeConfig, Store};
+
+/// Keeps instances of a component warm between requests, read from the
+/// `[http_trigger.component.<id>.keep_warm]` runtime config table. Instances
+/// which trap are discarded.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct KeepWarmConfig {
+    /// The number of requests an instance serves before it is discarded.
+    pub max_requests: u32,
+    /// How long, in seconds, an instance is kept after it is created.
+    pub max_age_secs: u64,
+    /// The number of idle instances kept for reuse.
+    pub max_idle: usize,
+}
+
+impl Default for KeepWarmConfig {
+    fn default() -> Self {
+        Self {
+            max_requests: 100,
+            max_age_secs: 300,
+            max_idle: 1,
+        }
+    }
+}
+
+/// An instance of a component, with the store it lives in.
+pub(crate) struct WarmInstance {
+    pub store: Store,
+    pub instance: Instance,
+    created: Instant,
+    requests: u32,
+}
+
+impl WarmInstance {
+    pub fn new(store: Store, instance: Instance) -> Self {
+        Self {
+            store,
+            instance,
+            created: Instant::now(),
+            requests: 0,
+        }
+    }
+}
+
+/// Idle instances of each component which is kept warm.
+#[derive(Default)]
+pub(crate) struct WarmPools {
+    pools: HashMap<String, WarmPool>,
+}
+
+impl WarmPools {
+    pub fn new(runtime_config: &HttpTriggerRuntimeConfig) -> Result<Self> {
+        let mut pools = HashMap::new();
+        for (component_id, config) in &runtime_config.component {
+            let Some(keep_warm) = &config.keep_warm else {
+                continue;
+            };
+            if keep_warm.max_requests == 0 {
+                bail!("Invalid keep_warm for component '{component_id}': `max_requests` must be at least 1");
+            }
+            pools.insert(
+                component_id.clone(),
+                WarmPool {
+                    config: keep_warm.clone(),
+                    idle: Default::default(),
+                },
+            );
+        }
+        Ok(Self { pools })
+    }
+
+    /// Takes an idle instance of the component, if there is one.
+    pub fn take(&self, component_id: &str) -> Option<WarmInstance> {
+        self.pools.get(component_id)?.take()
+    }
+
+    /// Returns an instance which has successfully served a request, keeping
+    /// it for reuse if the component is kept warm and the instance is within
+    /// its limits.
+    pub fn put(&self, component_id: &str, instance: WarmInstance) {
+        if let Some(pool) = self.pools.get(component_id) {
+            pool.put(instance);
+        }
+    }
+}
+
+struct WarmPool {
+    config: KeepWarmConfig,
+    idle: Mutex<Vec<WarmInstance>>,
+}
+
+impl WarmPool {
+    fn take(&self) -> Option<WarmInstance> {
+        loop {
+            let instance = self.idle.lock().unwrap().pop()?;
+            if !self.expired(&instance) {
+                return Some(instance);
+            }
+        }
+    }
+
+    fn put(&self, mut instance: WarmInstance) {
+        instance.requests += 1;
+        if instance.requests >= self.config.max_requests || self.expired(&instance) {
+            return;
+        }
+        let mut idle = self.idle.lock().unwrap();
+        if idle.len() < self.config.max_idle {
+            idle.push(instance);
+        }
+    }
+
+    fn expired(&self, instance: &WarmInstance) -> bool {
+        instance.created.elapsed() >= Duration::from_secs(self.config.max_age_secs)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::runtime_config::ComponentRuntimeConfig;
+
+    #[test]
+    fn only_configured_components_are_kept_warm() {
+        let mut runtime_config = HttpTriggerRuntimeConfig::default();
+        runtime_config.component.insert(
+            "warm".into(),
+            ComponentRuntimeConfig {
+                keep_warm: Some(Default::default()),
+                ..Default::default()
+            },
+        );
+        runtime_config
+            .component
+            .insert("cold".into(), Default::default());
+        let pools = WarmPools::new(&runtime_config).unwrap();
+        assert!(pools.pools.contains_key("warm"));
+        assert!(!pools.pools.contains_key("cold"));
+        assert!(pools.take("warm").is_none());
+
+        runtime_config
+            .component
+            .get_mut("warm")
+            .unwrap()
+            .keep_warm
+            .as_mut()
+            .unwrap()
+            .max_requests = 0;
+        assert!(WarmPools::new(&runtime_config).is_err());
+    }
+}
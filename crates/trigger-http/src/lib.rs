@@ -1,13 +1,29 @@
 //! Implementation for the Spin HTTP engine.
 
+mod access_log;
+mod acme;
+mod auth;
+mod compression;
+mod cors;
+mod debug_capture;
 mod handler;
+mod headers;
+mod health;
+mod keep_warm;
+mod limits;
+mod listener;
+mod rate_limit;
+mod runtime_config;
+mod static_files;
 mod tls;
+mod tls_status;
 mod wagi;
+mod webhook;
 
 use std::{
-    collections::HashMap,
+    collections::{BTreeMap, HashMap},
     io::IsTerminal,
-    net::{Ipv4Addr, SocketAddr, ToSocketAddrs},
+    net::SocketAddr,
     path::PathBuf,
     str::FromStr,
     sync::Arc,
@@ -16,37 +32,74 @@ use std::{
 use anyhow::{Context, Result};
 use async_trait::async_trait;
 use clap::Args;
-use http::{header::HOST, uri::Scheme, HeaderValue, StatusCode, Uri};
+use http::{
+    header::{HOST, ORIGIN},
+    uri::Scheme,
+    HeaderName, HeaderValue, StatusCode, Uri,
+};
 use http_body_util::BodyExt;
 use hyper::{
     body::{Bytes, Incoming},
-    server::conn::http1,
+    server::conn::{http1, http2},
     service::service_fn,
     Request, Response,
 };
-use hyper_util::rt::tokio::TokioIo;
+use hyper_util::rt::{tokio::TokioIo, TokioExecutor, TokioTimer};
 use spin_app::{AppComponent, APP_DESCRIPTION_KEY};
 use spin_core::{Engine, OutboundWasiHttpHandler};
 use spin_http::{
-    app_info::AppInfo,
+    app_info::{AppInfo, SignatureInfo},
     body,
-    config::{HttpExecutorType, HttpTriggerConfig, HttpTriggerRouteConfig},
-    routes::{RoutePattern, Router},
+    config::{CorsConfig, HttpExecutorType, HttpTriggerConfig, HttpTriggerRouteConfig},
+    routes::{RouteError, RouteMatch, RoutePattern, Router},
 };
 use spin_outbound_networking::{
     is_service_chaining_host, parse_service_chaining_target, AllowedHostsConfig, OutboundUrl,
 };
-use spin_trigger::{TriggerAppEngine, TriggerExecutor, TriggerInstancePre};
+use spin_trigger::{
+    cli::env, status::ComponentTiming, Overloaded, SignatureVerification, TriggerAppEngine,
+    TriggerExecutor, TriggerInstancePre,
+};
 use tokio::{
     io::{AsyncRead, AsyncWrite},
-    net::TcpListener,
+    net::TcpStream,
     task,
 };
+use tokio_rustls::TlsAcceptor;
 use tracing::{log, Instrument};
 use wasmtime_wasi_http::{body::HyperIncomingBody as Body, WasiHttpView};
 
-use crate::{handler::HttpHandlerExecutor, wagi::WagiHttpExecutor};
+use crate::{
+    access_log::{AccessLog, MatchedRoute},
+    acme::{AcmeManager, AcmeState, ACME_TLS_ALPN_NAME},
+    auth::{AppAuth, AUTH_HEADER_PREFIX},
+    debug_capture::DebugCapture,
+    handler::HttpHandlerExecutor,
+    headers::HeaderRewrites,
+    health::HealthChecks,
+    keep_warm::WarmPools,
+    limits::{AppLimits, LimitExceeded},
+    listener::{parse_listen_addr, Listener, UNKNOWN_PEER_ADDR},
+    rate_limit::AppRateLimits,
+    static_files::StaticFiles,
+    tls_status::TlsStatus,
+    wagi::WagiHttpExecutor,
+    webhook::WebhookVerifier,
+};
 
+pub use access_log::{
+    AccessLogConfig, AccessLogField, AccessLogFormat, AccessLogOutput, ACCESS_LOG_FILE,
+};
+pub use acme::{AcmeChallenge, AcmeConfig};
+pub use auth::{AuthConfig, JwtConfig};
+pub use compression::{CompressionConfig, Encoding};
+pub use debug_capture::{DebugCaptureConfig, CAPTURE_HEADER, DEBUG_HEADER};
+pub use health::HealthConfig;
+pub use keep_warm::KeepWarmConfig;
+pub use limits::RequestLimits;
+pub use listener::ListenAddr;
+pub use rate_limit::{RateLimitConfig, RateLimitKey};
+pub use runtime_config::{ComponentRuntimeConfig, HttpTriggerRuntimeConfig};
 pub use tls::TlsConfig;
 
 pub(crate) type RuntimeData = HttpRuntimeData;
@@ -60,21 +113,72 @@ pub struct HttpTrigger {
     base: String,
     // Component ID -> component trigger config
     component_trigger_configs: HashMap<String, HttpTriggerConfig>,
+    // Options from the `[http_trigger]` runtime config table
+    runtime_config: HttpTriggerRuntimeConfig,
+    // Request limits for each component
+    limits: AppLimits,
+    // Authentication for each component
+    auth: AppAuth,
+    // Rate limiting for each component
+    rate_limits: AppRateLimits,
+    // ACME state, if certificates are provisioned automatically
+    acme: Option<Arc<AcmeState>>,
+    // Certificates and handshake failures, if serving TLS
+    tls_status: Option<Arc<TlsStatus>>,
+    // Component ID -> static files, for routes served without invoking the component
+    static_files: HashMap<String, StaticFiles>,
+    // Component ID -> header rewrite rules
+    header_rewrites: HashMap<String, HeaderRewrites>,
+    // Component ID -> webhook signature verification
+    webhooks: HashMap<String, WebhookVerifier>,
+    // Liveness and readiness endpoints, if enabled
+    health: Option<HealthChecks>,
+    // Access log, if enabled
+    access_log: Option<Arc<AccessLog>>,
+    // Per-request debug capture, if enabled
+    debug_capture: Option<DebugCapture>,
+    // Idle instances of components which are kept warm
+    warm_pools: Arc<WarmPools>,
 }
 
 #[derive(Args)]
 pub struct CliArgs {
-    /// IP address and port to listen on
-    #[clap(long = "listen", default_value = "127.0.0.1:3000", value_parser = parse_listen_addr)]
-    pub address: SocketAddr,
+    /// IP address and port to listen on, `unix:<path>` for a Unix domain socket, or `systemd`
+    /// for a socket passed by systemd socket activation
+    #[clap(
+        long = "listen",
+        env = env::HTTP_LISTEN,
+        default_value = "127.0.0.1:3000",
+        value_parser = parse_listen_addr
+    )]
+    pub address: ListenAddr,
 
     /// The path to the certificate to use for https, if this is not set, normal http will be used. The cert should be in PEM format
-    #[clap(long, env = "SPIN_TLS_CERT", requires = "tls-key")]
+    #[clap(long, env = env::HTTP_TLS_CERT, requires = "tls-key")]
     pub tls_cert: Option<PathBuf>,
 
     /// The path to the certificate key to use for https, if this is not set, normal http will be used. The key should be in PKCS#8 format
-    #[clap(long, env = "SPIN_TLS_KEY", requires = "tls-cert")]
+    #[clap(long, env = env::HTTP_TLS_KEY, requires = "tls-cert")]
     pub tls_key: Option<PathBuf>,
+
+    /// Accept cleartext HTTP/2 (h2c) from clients with prior knowledge, alongside HTTP/1.1.
+    /// When TLS is enabled, HTTP/2 is always negotiated via ALPN instead.
+    #[clap(
+        long = "h2c",
+        env = env::HTTP_H2C,
+        takes_value = false,
+        conflicts_with = "tls-cert"
+    )]
+    pub h2c: bool,
+
+    /// Capture a debug bundle for every request, as if it had sent the debug capture token.
+    /// Bundles are written to the `debug` directory in the state directory.
+    #[clap(
+        long = "debug-capture",
+        env = env::HTTP_DEBUG_CAPTURE,
+        takes_value = false
+    )]
+    pub debug_capture: bool,
 }
 
 impl CliArgs {
@@ -118,11 +222,8 @@ impl TriggerExecutor for HttpTrigger {
             base = format!("/{base}");
         }
 
-        let component_routes = engine
-            .trigger_configs()
-            .map(|(_, config)| (config.component.as_str(), &config.route));
-
-        let (router, duplicate_routes) = Router::build(&base, component_routes)?;
+        let (router, duplicate_routes) =
+            Router::build_from_triggers(&base, engine.trigger_configs().map(|(_, config)| config))?;
 
         if !duplicate_routes.is_empty() {
             log::error!("The following component routes are duplicates and will never be used:");
@@ -142,43 +243,283 @@ impl TriggerExecutor for HttpTrigger {
             router.routes().collect::<Vec<_>>()
         );
 
-        let component_trigger_configs = engine
+        let component_trigger_configs: HashMap<_, _> = engine
             .trigger_configs()
             .map(|(_, config)| (config.component.clone(), config.clone()))
             .collect();
 
+        let runtime_config = engine.trigger_runtime_opts::<HttpTriggerRuntimeConfig>()?;
+        for component_id in runtime_config.component.keys() {
+            if !component_trigger_configs.contains_key(component_id) {
+                log::warn!("Runtime config for component '{component_id}' does not match any HTTP component");
+            }
+        }
+        let routes: Vec<(&str, &str)> = component_trigger_configs
+            .iter()
+            .filter_map(|(component_id, config)| match &config.route {
+                HttpTriggerRouteConfig::Route(route) => {
+                    Some((component_id.as_str(), route.as_str()))
+                }
+                HttpTriggerRouteConfig::IsRoutable(_) => None,
+            })
+            .collect();
+        for route in runtime_config.route.keys() {
+            if !routes.iter().any(|(_, r)| r == route) {
+                log::warn!("Runtime config for route '{route}' does not match any HTTP route");
+            }
+        }
+        let limits = AppLimits::new(&runtime_config, routes.iter().copied())
+            .context("Invalid [http_trigger] limits")?;
+        let auth = AppAuth::new(&runtime_config)?;
+        let rate_limits = AppRateLimits::new(&runtime_config, engine.runtime_config()).await?;
+        let mut warm_pools = WarmPools::new(&runtime_config)?;
+        if engine.is_hot_reloading() {
+            // Warm instances would keep serving the previous version
+            warm_pools = WarmPools::default();
+        }
+        let warm_pools = Arc::new(warm_pools);
+
+        if let Some(cors) = &runtime_config.cors {
+            cors.validate().context("Invalid [http_trigger.cors]")?;
+        }
+        for (component_id, config) in &component_trigger_configs {
+            if let Some(cors) = &config.cors {
+                cors.validate().with_context(|| {
+                    format!("Invalid CORS policy for component '{component_id}'")
+                })?;
+            }
+        }
+
+        let static_files = component_trigger_configs
+            .iter()
+            .filter_map(|(component_id, config)| {
+                let static_config = config.static_files.as_ref()?;
+                Some(
+                    engine
+                        .app()
+                        .get_component(component_id)
+                        .context("Static route references an unknown component")
+                        .and_then(|component| StaticFiles::new(&component, static_config))
+                        .map(|static_files| (component_id.clone(), static_files))
+                        .with_context(|| {
+                            format!("Invalid static route for component '{component_id}'")
+                        }),
+                )
+            })
+            .collect::<Result<_>>()?;
+
+        let access_log = runtime_config
+            .access_log
+            .as_ref()
+            .map(|config| AccessLog::new(config, engine.runtime_config().log_dir().as_deref()))
+            .transpose()
+            .context("Invalid [http_trigger.access_log]")?
+            .map(Arc::new);
+
+        let health = runtime_config
+            .health
+            .clone()
+            .map(HealthChecks::new)
+            .transpose()
+            .context("Invalid [http_trigger.health]")?;
+
+        let header_rewrites = component_trigger_configs
+            .iter()
+            .filter_map(|(component_id, config)| {
+                let rules = config.headers.as_ref()?;
+                Some(
+                    HeaderRewrites::new(rules)
+                        .map(|rewrites| (component_id.clone(), rewrites))
+                        .with_context(|| {
+                            format!("Invalid header rules for component '{component_id}'")
+                        }),
+                )
+            })
+            .collect::<Result<_>>()?;
+
+        let webhooks = component_trigger_configs
+            .iter()
+            .filter_map(|(component_id, config)| {
+                let webhook = config.webhook.as_ref()?;
+                Some(
+                    WebhookVerifier::new(webhook, |secret| {
+                        let template = spin_expressions::Template::new(secret)?;
+                        Ok(engine.resolve_template(&template)?)
+                    })
+                    .map(|verifier| (component_id.clone(), verifier))
+                    .with_context(|| {
+                        format!("Invalid webhook config for component '{component_id}'")
+                    }),
+                )
+            })
+            .collect::<Result<_>>()?;
+
+        let debug_capture_token = runtime_config
+            .debug_capture
+            .as_ref()
+            .map(|config| {
+                let template = spin_expressions::Template::new(config.token.as_str())?;
+                Ok(engine.resolve_template(&template)?)
+            })
+            .transpose()
+            .context("Invalid [http_trigger.debug_capture]")?;
+        let debug_capture = DebugCapture::new(
+            debug_capture_token,
+            false,
+            &engine.runtime_config().observability(),
+            engine.runtime_config().state_dir().as_deref(),
+        )
+        .context("Invalid [http_trigger.debug_capture] or [observability] runtime config")?;
+
         Ok(Self {
             engine: Arc::new(engine),
             router,
             base,
             component_trigger_configs,
+            runtime_config,
+            limits,
+            auth,
+            rate_limits,
+            acme: None,
+            tls_status: None,
+            static_files,
+            header_rewrites,
+            webhooks,
+            health,
+            access_log,
+            debug_capture,
+            warm_pools,
         })
     }
 
-    async fn run(self, config: Self::RunConfig) -> Result<()> {
+    async fn run(mut self, config: Self::RunConfig) -> Result<()> {
         let listen_addr = config.address;
+        let h2c = config.h2c;
+        if config.debug_capture {
+            match &mut self.debug_capture {
+                Some(debug_capture) => debug_capture.capture_all(),
+                None => {
+                    self.debug_capture = DebugCapture::new(
+                        None,
+                        true,
+                        &Default::default(),
+                        self.engine.runtime_config().state_dir().as_deref(),
+                    )?
+                }
+            }
+        }
         let tls = config.into_tls_config();
 
+        let acme = match self.runtime_config.acme.clone() {
+            Some(_) if tls.is_some() => {
+                anyhow::bail!(
+                    "TLS certificate options cannot be used together with ACME runtime config"
+                )
+            }
+            Some(acme_config) => {
+                let default_storage_dir = self
+                    .engine
+                    .runtime_config()
+                    .state_dir()
+                    .map(|dir| dir.join("acme"));
+                Some(AcmeManager::new(acme_config, default_storage_dir)?)
+            }
+            None => None,
+        };
+        let tls_status = match (&acme, &tls) {
+            (Some(acme), _) => Some(TlsStatus::from_acme(acme.state())),
+            (None, Some(tls)) => Some(TlsStatus::from_file(tls)?),
+            (None, None) => None,
+        };
+        if let Some(tls_status) = tls_status {
+            let tls_status = Arc::new(tls_status);
+            for warning in tls_status.report().warnings {
+                terminal::warn!("{warning}");
+            }
+            let report = tls_status.clone();
+            self.engine.status().add_section("tls", move || {
+                serde_json::to_value(report.report()).unwrap_or_default()
+            });
+            self.tls_status = Some(tls_status);
+        }
+
         // Print startup messages
-        let scheme = if tls.is_some() { "https" } else { "http" };
-        let base_url = format!("{}://{:?}", scheme, listen_addr);
-        terminal::step!("\nServing", "{}", base_url);
-        log::info!("Serving {}", base_url);
+        let scheme = if tls.is_some() || acme.is_some() {
+            "https"
+        } else {
+            "http"
+        };
+        let base_url = match &listen_addr {
+            ListenAddr::Tcp(addr) => format!("{}://{:?}", scheme, addr),
+            _ => format!("{}://localhost", scheme),
+        };
+        let json = terminal::output_format().is_json();
+        match &listen_addr {
+            ListenAddr::Tcp(_) => terminal::step!("\nServing", "{}", base_url),
+            _ => terminal::step!("\nServing", "{} (listening on {})", base_url, listen_addr),
+        }
+        log::info!("Serving {} on {}", base_url, listen_addr);
+        if let Some(signature) = self.engine.signature() {
+            terminal::step!(
+                "Verified",
+                "{} is signed by {}",
+                signature.reference,
+                signature.identity
+            );
+        }
 
-        println!("Available Routes:");
+        let mut summary = StartupSummary {
+            trigger: Self::TRIGGER_TYPE,
+            url: &base_url,
+            listen: listen_addr.to_string(),
+            routes: vec![],
+            timing: self.engine.status().timings(),
+            signature: self.engine.signature(),
+        };
+        if !json {
+            println!("Available Routes:");
+        }
         for (route, component_id) in self.router.routes() {
-            println!("  {}: {}{}", component_id, base_url, route);
-            if let Some(component) = self.engine.app().get_component(component_id) {
-                if let Some(description) = component.get_metadata(APP_DESCRIPTION_KEY)? {
+            self.engine
+                .status()
+                .add_subscription(component_id, format!("route {route}"));
+            let description = match self.engine.app().get_component(component_id) {
+                Some(component) => component.get_metadata(APP_DESCRIPTION_KEY)?,
+                None => None,
+            };
+            if !json {
+                println!("  {}: {}{}", component_id, base_url, route);
+                if let Some(description) = &description {
                     println!("    {}", description);
                 }
             }
+            summary.routes.push(StartupRoute {
+                component: component_id.to_owned(),
+                route: route.to_string(),
+                url: format!("{base_url}{route}"),
+                description,
+            });
+        }
+        if json {
+            println!("{}", serde_json::to_string(&summary)?);
         }
 
-        if let Some(tls) = tls {
-            self.serve_tls(listen_addr, tls).await?
+        // Routes whose components are ready are served while the rest load
+        if self.engine.is_serving_while_loading() {
+            let engine = self.engine.clone();
+            tokio::spawn(async move { engine.prepare_pending().await });
+        }
+
+        if let Some(acme) = acme {
+            let acceptor = acme.acceptor();
+            self.acme = Some(acme.state());
+            acme.start().await?;
+            self.serve_tls(&listen_addr, acceptor).await?
+        } else if let Some(tls) = tls {
+            let acceptor = tls.server_config()?;
+            self.serve_tls(&listen_addr, acceptor).await?
         } else {
-            self.serve(listen_addr).await?
+            self.serve(&listen_addr, h2c).await?
         };
         Ok(())
     }
@@ -186,6 +527,10 @@ impl TriggerExecutor for HttpTrigger {
     fn supported_host_requirements() -> Vec<&'static str> {
         vec![spin_app::locked::SERVICE_CHAINING_KEY]
     }
+
+    fn listens() -> bool {
+        true
+    }
 }
 
 #[async_trait]
@@ -228,6 +573,45 @@ impl HttpTrigger {
         mut req: Request<Body>,
         scheme: Scheme,
         addr: SocketAddr,
+    ) -> Result<Response<Body>> {
+        let capture = self
+            .debug_capture
+            .as_ref()
+            .and_then(|debug_capture| debug_capture.start(&mut req));
+        let Some(capture) = capture else {
+            return self.handle_logged(req, scheme, addr).await;
+        };
+        let res = capture
+            .capture()
+            .scope(self.handle_logged(req, scheme, addr))
+            .await;
+        capture.finish(res)
+    }
+
+    async fn handle_logged(
+        &self,
+        req: Request<Body>,
+        scheme: Scheme,
+        addr: SocketAddr,
+    ) -> Result<Response<Body>> {
+        let Some(access_log) = &self.access_log else {
+            return self.handle_request(req, scheme, addr).await;
+        };
+        let entry = access_log.start(&req, addr);
+        match self.handle_request(req, scheme, addr).await {
+            Ok(res) => Ok(access_log.finish(entry, res)),
+            Err(e) => {
+                access_log.failed(entry);
+                Err(e)
+            }
+        }
+    }
+
+    async fn handle_request(
+        &self,
+        mut req: Request<Body>,
+        scheme: Scheme,
+        addr: SocketAddr,
     ) -> Result<Response<Body>> {
         set_req_uri(&mut req, scheme)?;
         strip_forbidden_headers(&mut req);
@@ -242,6 +626,14 @@ impl HttpTrigger {
 
         let path = req.uri().path();
 
+        // Answer ACME HTTP-01 challenges, e.g. when the challenge listener is
+        // behind a proxy that forwards to the app
+        if let Some(acme) = &self.acme {
+            if let Some(key_authorization) = acme.http_challenge_response(path) {
+                return acme::http_challenge_response(Some(key_authorization));
+            }
+        }
+
         // Handle well-known spin paths
         if let Some(well_known) = path.strip_prefix(spin_http::WELL_KNOWN_PREFIX) {
             return match well_known {
@@ -251,9 +643,43 @@ impl HttpTrigger {
             };
         }
 
-        // Route to app component
-        match self.router.route(path) {
-            Ok(component_id) => {
+        // Handle health endpoints
+        if let Some(health) = &self.health {
+            if let Some(res) = health.respond(path, &self.engine).await {
+                return res;
+            }
+        }
+
+        // Route to app component. CORS preflights are routed by the method
+        // they ask about if the route for that method has a CORS policy, and
+        // otherwise as the OPTIONS requests they are.
+        let host = req
+            .headers()
+            .get(HOST)
+            .and_then(|host| host.to_str().ok())
+            .unwrap_or_default();
+        let route = |method: &str| self.router.route_request(Some(method), Some(host), path);
+        let routed = match cors::preflight_method(&req).map(route) {
+            Some(Ok(route_match)) if self.cors_config(route_match.component_id).is_some() => {
+                Ok(route_match)
+            }
+            _ => route(req.method().as_str()),
+        };
+        match routed {
+            Ok(RouteMatch {
+                component_id,
+                pattern: route_pattern,
+                params,
+            }) => {
+                for (name, value) in params {
+                    let name = HeaderName::from_bytes(
+                        format!("{PATH_PARAM_HEADER_PREFIX}{name}").as_bytes(),
+                    );
+                    if let (Ok(name), Ok(value)) = (name, HeaderValue::from_str(&value)) {
+                        req.headers_mut().insert(name, value);
+                    }
+                }
+
                 let trigger = self.component_trigger_configs.get(component_id).unwrap();
 
                 let executor = trigger.executor.as_ref().unwrap_or(&HttpExecutorType::Http);
@@ -263,50 +689,161 @@ impl HttpTrigger {
                     HttpTriggerRouteConfig::IsRoutable(_) => "/...",
                 };
 
-                let res = match executor {
-                    HttpExecutorType::Http => {
-                        HttpHandlerExecutor
-                            .execute(
-                                self.engine.clone(),
-                                component_id,
-                                &self.base,
-                                raw_route,
-                                req,
-                                addr,
-                            )
-                            .await
+                let compression = self.runtime_config.compression.as_ref();
+                let encoding = compression.and_then(|c| c.response_encoding(&req));
+
+                let cors = self.cors_config(component_id);
+                if let Some(cors) = cors {
+                    if let Some(res) = cors::preflight(cors, &req)? {
+                        return Ok(res);
                     }
-                    HttpExecutorType::Wagi(wagi_config) => {
-                        let executor = WagiHttpExecutor {
-                            wagi_config: wagi_config.clone(),
-                        };
-                        executor
-                            .execute(
-                                self.engine.clone(),
-                                component_id,
-                                &self.base,
-                                raw_route,
-                                req,
-                                addr,
-                            )
-                            .await
+                }
+                let origin = req.headers().get(ORIGIN).cloned();
+
+                let (rate_limit_headers, rate_limited) =
+                    match self.rate_limits.check(component_id, &req, addr).await {
+                        Ok(headers) => (headers, None),
+                        Err(res) => (None, Some(res)),
+                    };
+
+                let header_rewrites = self.header_rewrites.get(component_id);
+                let finish = |res: Response<Body>| {
+                    let mut res = match (compression, encoding) {
+                        (Some(compression), Some(encoding)) => {
+                            compression.compress_response(res, encoding)
+                        }
+                        _ => res,
+                    };
+                    if let Some(cors) = cors {
+                        cors::apply(cors, origin.as_ref(), &mut res);
+                    }
+                    if let Some(headers) = &rate_limit_headers {
+                        res.headers_mut().extend(headers.clone());
+                    }
+                    if let Some(rewrites) = header_rewrites {
+                        rewrites.rewrite_response(res.headers_mut());
                     }
+                    res.extensions_mut().insert(MatchedRoute {
+                        component_id: component_id.to_owned(),
+                        route: route_pattern.full_pattern_non_empty().into_owned(),
+                    });
+                    res
+                };
+
+                if let Some(res) = rate_limited {
+                    return Ok(finish(res));
+                }
+
+                if let Err(res) = self.auth.authenticate(component_id, &mut req).await {
+                    return Ok(finish(res));
+                }
+
+                if let Some(rewrites) = header_rewrites {
+                    rewrites.rewrite_request(req.headers_mut());
+                }
+
+                if let Some(static_files) = self.static_files.get(component_id) {
+                    let path_info = route_pattern.relative(req.uri().path())?;
+                    return Ok(finish(static_files.serve(&req, &path_info).await?));
+                }
+
+                let req = match compression {
+                    Some(compression) => compression.decompress_request(req),
+                    None => req,
+                };
+
+                let admitted = match self.limits.component(component_id).admit(req) {
+                    Ok(admitted) => admitted,
+                    Err(LimitExceeded(status)) => return Ok(finish(Self::limit_exceeded(status)?)),
                 };
-                match res {
-                    Ok(res) => Ok(res),
+
+                let webhook = self.webhooks.get(component_id);
+                let res = admitted
+                    .execute(|req| async move {
+                        let req = match webhook {
+                            Some(webhook) => match webhook.verify(req).await? {
+                                Ok(req) => req,
+                                Err(res) => return Ok(res),
+                            },
+                            None => req,
+                        };
+                        match executor {
+                            HttpExecutorType::Http => {
+                                HttpHandlerExecutor::new(self.warm_pools.clone())
+                                    .execute(
+                                        self.engine.clone(),
+                                        component_id,
+                                        &self.base,
+                                        raw_route,
+                                        req,
+                                        addr,
+                                    )
+                                    .await
+                            }
+                            HttpExecutorType::Wagi(wagi_config) => {
+                                let executor = WagiHttpExecutor {
+                                    wagi_config: wagi_config.clone(),
+                                };
+                                executor
+                                    .execute(
+                                        self.engine.clone(),
+                                        component_id,
+                                        &self.base,
+                                        raw_route,
+                                        req,
+                                        addr,
+                                    )
+                                    .await
+                            }
+                        }
+                    })
+                    .await;
+                // Errors are finished like any other response, so that
+                // browsers can read them through CORS
+                let res = match res {
+                    Ok(res) => res,
                     Err(e) => {
-                        log::error!("Error processing request: {:?}", e);
-                        Self::internal_error(None)
+                        match e.downcast_ref::<LimitExceeded>() {
+                            Some(LimitExceeded(status)) => {
+                                log::warn!("Request to component {component_id} exceeded a limit: {status}");
+                                Self::limit_exceeded(*status)?
+                            }
+                            None if e.is::<Overloaded>() => {
+                                log::warn!("{e}");
+                                Self::limit_exceeded(StatusCode::SERVICE_UNAVAILABLE)?
+                            }
+                            None => {
+                                log::error!("Error processing request: {:?}", e);
+                                self.engine.status().record_error(component_id, &e);
+                                Self::internal_error(None)?
+                            }
+                        }
                     }
-                }
+                };
+                Ok(finish(res))
+            }
+            Err(RouteError::MethodNotAllowed(allowed)) => Self::method_not_allowed(&allowed),
+            Err(RouteError::NotFound) => {
+                Self::not_found(NotFoundRouteKind::Normal(path.to_string()))
             }
-            Err(_) => Self::not_found(NotFoundRouteKind::Normal(path.to_string())),
         }
     }
 
+    /// Returns the CORS policy for a component: its trigger's own, or else
+    /// the app's.
+    fn cors_config(&self, component_id: &str) -> Option<&CorsConfig> {
+        let trigger = self.component_trigger_configs.get(component_id)?;
+        trigger.cors.as_ref().or(self.runtime_config.cors.as_ref())
+    }
+
     /// Returns spin status information.
     fn app_info(&self) -> Result<Response<Body>> {
-        let info = AppInfo::new(self.engine.app());
+        let mut info = AppInfo::new(self.engine.app());
+        info.signature = self.engine.signature().map(|signature| SignatureInfo {
+            reference: signature.reference.clone(),
+            identity: signature.identity.clone(),
+            issuer: signature.issuer.clone(),
+        });
         let body = serde_json::to_vec_pretty(&info)?;
         Ok(Response::builder()
             .header("content-type", "application/json")
@@ -325,6 +862,19 @@ impl HttpTrigger {
             .body(body)?)
     }
 
+    /// Creates a response for a request which exceeded one of its limits.
+    fn limit_exceeded(status: StatusCode) -> Result<Response<Body>> {
+        Ok(Response::builder().status(status).body(body::empty())?)
+    }
+
+    /// Creates an HTTP 405 response.
+    fn method_not_allowed(allowed: &[String]) -> Result<Response<Body>> {
+        Ok(Response::builder()
+            .status(StatusCode::METHOD_NOT_ALLOWED)
+            .header(http::header::ALLOW, allowed.join(", "))
+            .body(body::empty())?)
+    }
+
     /// Creates an HTTP 404 response.
     fn not_found(kind: NotFoundRouteKind) -> Result<Response<Body>> {
         use std::sync::atomic::{AtomicBool, Ordering};
@@ -345,100 +895,200 @@ impl HttpTrigger {
         self_: Arc<Self>,
         stream: S,
         addr: SocketAddr,
+        protocol: ConnectionProtocol,
     ) {
         task::spawn(async move {
-            if let Err(e) = http1::Builder::new()
-                .keep_alive(true)
-                .serve_connection(
-                    TokioIo::new(stream),
-                    service_fn(move |request| {
-                        let self_ = self_.clone();
-                        let span = tracing::info_span!(
-                            "handle_http_request",
-                            "otel.kind" = "server",
-                            "http.request.method" = %request.method(),
-                            "network.peer.address" = %addr.ip(),
-                            "network.peer.port" = %addr.port(),
-                            "network.protocol.name" = "http",
-                            "url.path" = request.uri().path(),
-                            "url.query" = request.uri().query().unwrap_or(""),
-                            "url.scheme" = request.uri().scheme_str().unwrap_or(""),
-                            "client.address" = request.headers().get("x-forwarded-for").and_then(|val| val.to_str().ok()),
-                            // TODO(Caleb): Recorded later
-                            // "error.type" = Empty,
-                            // "http.response.status_code" = Empty,
-                            // "http.route" = Empty,
-                        );
-                        async move {
-                            self_
-                                .handle(
-                                    request.map(|body: Incoming| {
-                                        body.map_err(wasmtime_wasi_http::hyper_response_error)
-                                            .boxed()
-                                    }),
-                                    Scheme::HTTP,
-                                    addr,
-                                )
-                                .instrument(span)
-                                .await
-                        }
-                    }),
-                )
-                .await
-            {
+            let limits = self_.runtime_config.limits.clone();
+            let service = service_fn(move |request| {
+                let self_ = self_.clone();
+                let span = tracing::info_span!(
+                    "handle_http_request",
+                    "otel.kind" = "server",
+                    "http.request.method" = %request.method(),
+                    "network.peer.address" = %addr.ip(),
+                    "network.peer.port" = %addr.port(),
+                    "network.protocol.name" = "http",
+                    "network.protocol.version" = ?request.version(),
+                    "url.path" = request.uri().path(),
+                    "url.query" = request.uri().query().unwrap_or(""),
+                    "url.scheme" = request.uri().scheme_str().unwrap_or(""),
+                    "client.address" = request.headers().get("x-forwarded-for").and_then(|val| val.to_str().ok()),
+                    // TODO(Caleb): Recorded later
+                    // "error.type" = Empty,
+                    // "http.response.status_code" = Empty,
+                    // "http.route" = Empty,
+                );
+                async move {
+                    self_
+                        .handle(
+                            request.map(|body: Incoming| {
+                                body.map_err(wasmtime_wasi_http::hyper_response_error)
+                                    .boxed()
+                            }),
+                            Scheme::HTTP,
+                            addr,
+                        )
+                        .instrument(span)
+                        .await
+                }
+            });
+            let io = TokioIo::new(stream);
+            let result = match protocol {
+                ConnectionProtocol::Http1 => {
+                    let mut builder = http1::Builder::new();
+                    builder.keep_alive(true);
+                    if let Some(max_header_size) = limits.max_header_size {
+                        builder.max_buf_size(max_header_size);
+                    }
+                    if let Some(timeout) = limits.request_read_timeout() {
+                        builder
+                            .timer(TokioTimer::new())
+                            .header_read_timeout(timeout);
+                    }
+                    builder.serve_connection(io, service).await
+                }
+                ConnectionProtocol::Http2 => {
+                    let mut builder = http2::Builder::new(TokioExecutor::new());
+                    if let Some(max_header_size) = limits.max_header_size {
+                        builder
+                            .max_header_list_size(max_header_size.try_into().unwrap_or(u32::MAX));
+                    }
+                    builder.serve_connection(io, service).await
+                }
+            };
+            if let Err(e) = result {
                 log::warn!("{e:?}");
             }
         });
     }
 
-    async fn serve(self, listen_addr: SocketAddr) -> Result<()> {
+    async fn serve(self, listen_addr: &ListenAddr, h2c: bool) -> Result<()> {
         let self_ = Arc::new(self);
 
-        let listener = TcpListener::bind(listen_addr)
-            .await
-            .with_context(|| format!("Unable to listen on {}", listen_addr))?;
-
-        loop {
-            let (stream, addr) = listener.accept().await?;
-            Self::serve_connection(self_.clone(), stream, addr);
+        let listener = Listener::bind(listen_addr).await?;
+        self_.engine.listening()?;
+
+        match listener {
+            Listener::Tcp(listener) => loop {
+                let (stream, addr) = listener.accept().await?;
+                if h2c {
+                    let self_ = self_.clone();
+                    task::spawn(async move {
+                        let protocol = ConnectionProtocol::sniff(&stream).await;
+                        Self::serve_connection(self_, stream, addr, protocol);
+                    });
+                } else {
+                    Self::serve_connection(self_.clone(), stream, addr, ConnectionProtocol::Http1);
+                }
+            },
+            #[cfg(unix)]
+            Listener::Unix(listener) => {
+                if h2c {
+                    anyhow::bail!("--h2c is not supported on Unix domain sockets");
+                }
+                loop {
+                    let (stream, _) = listener.accept().await?;
+                    Self::serve_connection(
+                        self_.clone(),
+                        stream,
+                        UNKNOWN_PEER_ADDR,
+                        ConnectionProtocol::Http1,
+                    );
+                }
+            }
         }
     }
 
-    async fn serve_tls(self, listen_addr: SocketAddr, tls: TlsConfig) -> Result<()> {
+    async fn serve_tls(self, listen_addr: &ListenAddr, acceptor: TlsAcceptor) -> Result<()> {
         let self_ = Arc::new(self);
 
-        let listener = TcpListener::bind(listen_addr)
-            .await
-            .with_context(|| format!("Unable to listen on {}", listen_addr))?;
-
-        let acceptor = tls.server_config()?;
+        let listener = Listener::bind(listen_addr).await?;
+        self_.engine.listening()?;
 
         loop {
-            let (stream, addr) = listener.accept().await?;
-            match acceptor.accept(stream).await {
-                Ok(stream) => Self::serve_connection(self_.clone(), stream, addr),
-                Err(err) => tracing::error!(?err, "Failed to start TLS session"),
+            match &listener {
+                Listener::Tcp(listener) => {
+                    let (stream, addr) = listener.accept().await?;
+                    Self::accept_tls(&self_, &acceptor, stream, addr).await;
+                }
+                #[cfg(unix)]
+                Listener::Unix(listener) => {
+                    let (stream, _) = listener.accept().await?;
+                    Self::accept_tls(&self_, &acceptor, stream, UNKNOWN_PEER_ADDR).await;
+                }
+            }
+        }
+    }
+
+    async fn accept_tls<S: AsyncRead + AsyncWrite + Unpin + Send + 'static>(
+        self_: &Arc<Self>,
+        acceptor: &TlsAcceptor,
+        stream: S,
+        addr: SocketAddr,
+    ) {
+        match acceptor.accept(stream).await {
+            // A TLS-ALPN-01 validation is complete once the handshake is done
+            Ok(stream) if stream.get_ref().1.alpn_protocol() == Some(ACME_TLS_ALPN_NAME) => {}
+            Ok(stream) => {
+                let protocol = ConnectionProtocol::from_alpn(stream.get_ref().1.alpn_protocol());
+                Self::serve_connection(self_.clone(), stream, addr, protocol)
+            }
+            Err(err) => {
+                tracing::error!(?err, "Failed to start TLS session");
+                if let Some(tls_status) = &self_.tls_status {
+                    tls_status.record_handshake_failure(addr);
+                }
             }
         }
     }
 }
 
-fn parse_listen_addr(addr: &str) -> anyhow::Result<SocketAddr> {
-    let addrs: Vec<SocketAddr> = addr.to_socket_addrs()?.collect();
-    // Prefer 127.0.0.1 over e.g. [::1] because CHANGE IS HARD
-    if let Some(addr) = addrs
-        .iter()
-        .find(|addr| addr.is_ipv4() && addr.ip() == Ipv4Addr::LOCALHOST)
-    {
-        return Ok(*addr);
+/// The HTTP version spoken on an accepted connection.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum ConnectionProtocol {
+    Http1,
+    Http2,
+}
+
+impl ConnectionProtocol {
+    /// The client connection preface which starts every HTTP/2 connection.
+    const HTTP2_PREFACE: &'static [u8] = b"PRI * HTTP/2.0\r\n\r\nSM\r\n\r\n";
+
+    fn from_alpn(protocol: Option<&[u8]>) -> Self {
+        match protocol {
+            Some(b"h2") => Self::Http2,
+            _ => Self::Http1,
+        }
+    }
+
+    /// Detects a prior-knowledge (h2c) HTTP/2 client by peeking at the
+    /// start of the connection, falling back to HTTP/1.1.
+    async fn sniff(stream: &TcpStream) -> Self {
+        let mut buf = [0; Self::HTTP2_PREFACE.len()];
+        match stream.peek(&mut buf).await {
+            Ok(n) if Self::is_http2_preface(&buf[..n]) => Self::Http2,
+            _ => Self::Http1,
+        }
+    }
+
+    // The `PRI` method is reserved for the HTTP/2 preface, so a partial
+    // match is enough to tell the protocols apart.
+    fn is_http2_preface(start: &[u8]) -> bool {
+        !start.is_empty() && Self::HTTP2_PREFACE.starts_with(start)
     }
-    // Otherwise, take the first addr (OS preference)
-    addrs.into_iter().next().context("couldn't resolve address")
 }
 
 fn set_req_uri(req: &mut Request<Body>, scheme: Scheme) -> Result<()> {
     const DEFAULT_HOST: &str = "localhost";
 
+    // HTTP/2 requests carry the authority in the URI rather than a Host header
+    if !req.headers().contains_key(HOST) {
+        if let Some(authority) = req.uri().authority() {
+            let host = HeaderValue::from_str(authority.as_str())?;
+            req.headers_mut().insert(HOST, host);
+        }
+    }
+
     let authority_hdr = req
         .headers()
         .get(http::header::HOST)
@@ -464,14 +1114,31 @@ fn strip_forbidden_headers(req: &mut Request<Body>) {
             }
         }
     }
+    // Identity and path parameter headers may only be set by the trigger
+    let trigger_headers = headers
+        .keys()
+        .filter(|name| {
+            name.as_str().starts_with(AUTH_HEADER_PREFIX)
+                || name.as_str().starts_with(PATH_PARAM_HEADER_PREFIX)
+        })
+        .cloned()
+        .collect::<Vec<_>>();
+    for name in trigger_headers {
+        headers.remove(name);
+    }
 }
 
+/// Prefix of the request headers carrying the values of named route
+/// parameters, e.g. `spin-path-match-id` for the route `/users/:id`.
+pub const PATH_PARAM_HEADER_PREFIX: &str = "spin-path-match-";
+
 // We need to make the following pieces of information available to both executors.
 // While the values we set are identical, the way they are passed to the
 // modules is going to be different, so each executor must must use the info
 // in its standardized way (environment variables for the Wagi executor, and custom headers
 // for the Spin HTTP executor).
 const FULL_URL: &[&str] = &["SPIN_FULL_URL", "X_FULL_URL"];
+
 const PATH_INFO: &[&str] = &["SPIN_PATH_INFO", "PATH_INFO"];
 const MATCHED_ROUTE: &[&str] = &["SPIN_MATCHED_ROUTE", "X_MATCHED_ROUTE"];
 const COMPONENT_ROUTE: &[&str] = &["SPIN_COMPONENT_ROUTE", "X_COMPONENT_ROUTE"];
@@ -691,10 +1358,34 @@ impl OutboundWasiHttpHandler for HttpRuntimeData {
             return Self::chain_request(data, request, component_id);
         }
 
-        wasmtime_wasi_http::types::default_send_request(data, request)
+        spin_core::send_outbound_request(data, request)
     }
 }
 
+/// The startup summary printed with `--output json`.
+#[derive(serde::Serialize)]
+struct StartupSummary<'a> {
+    trigger: &'a str,
+    url: &'a str,
+    listen: String,
+    routes: Vec<StartupRoute>,
+    /// Component ID -> where the time to start the component went
+    timing: BTreeMap<String, ComponentTiming>,
+    /// The app's verified signature, if the runtime config requires signed
+    /// apps
+    #[serde(skip_serializing_if = "Option::is_none")]
+    signature: Option<&'a SignatureVerification>,
+}
+
+#[derive(serde::Serialize)]
+struct StartupRoute {
+    component: String,
+    route: String,
+    url: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    description: Option<String>,
+}
+
 #[derive(Debug, PartialEq)]
 enum NotFoundRouteKind {
     Normal(String),
@@ -830,10 +1521,42 @@ mod tests {
     }
 
     #[test]
-    fn parse_listen_addr_prefers_ipv4() {
-        let addr = parse_listen_addr("localhost:12345").unwrap();
-        assert_eq!(addr.ip(), Ipv4Addr::LOCALHOST);
-        assert_eq!(addr.port(), 12345);
+    fn http2_preface_is_detected() {
+        assert!(ConnectionProtocol::is_http2_preface(
+            ConnectionProtocol::HTTP2_PREFACE
+        ));
+        assert!(ConnectionProtocol::is_http2_preface(b"PRI * HTTP/2"));
+        assert!(!ConnectionProtocol::is_http2_preface(b"GET / HTTP/1.1\r\n"));
+        assert!(!ConnectionProtocol::is_http2_preface(b""));
+    }
+
+    #[test]
+    fn alpn_selects_protocol() {
+        assert_eq!(
+            ConnectionProtocol::from_alpn(Some(b"h2")),
+            ConnectionProtocol::Http2
+        );
+        assert_eq!(
+            ConnectionProtocol::from_alpn(Some(b"http/1.1")),
+            ConnectionProtocol::Http1
+        );
+        assert_eq!(
+            ConnectionProtocol::from_alpn(None),
+            ConnectionProtocol::Http1
+        );
+    }
+
+    #[test]
+    fn http2_authority_is_used_without_host_header() -> Result<()> {
+        let mut req = Request::get("http://example.com:8080/foo")
+            .version(http::Version::HTTP_2)
+            .body(Default::default())?;
+
+        set_req_uri(&mut req, Scheme::HTTPS)?;
+
+        assert_eq!(req.uri().to_string(), "https://example.com:8080/foo");
+        assert_eq!(req.headers()[HOST], "example.com:8080");
+        Ok(())
     }
 
     #[test]
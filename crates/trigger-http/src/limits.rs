@@ -0,0 +1,474 @@
+//! Request size, timeout and concurrency limits for the HTTP trigger.
+
+use std::{
+    collections::HashMap,
+    fmt,
+    future::Future,
+    pin::Pin,
+    sync::{Arc, OnceLock},
+    task::{ready, Context, Poll},
+    time::Duration,
+};
+
+use anyhow::{bail, Result};
+use http::{header::CONTENT_LENGTH, StatusCode};
+use hyper::{
+    body::{Bytes, Frame, SizeHint},
+    Request,
+};
+use serde::Deserialize;
+use tokio::{
+    sync::{OwnedSemaphorePermit, Semaphore},
+    time::Sleep,
+};
+use wasmtime_wasi_http::bindings::http::types::ErrorCode;
+
+use crate::{runtime_config::HttpTriggerRuntimeConfig, Body};
+
+// hyper refuses read buffers smaller than this.
+const MIN_MAX_HEADER_SIZE: usize = 8192;
+
+/// Request limits, read from the `[http_trigger.limits]` runtime config table
+/// and overridden per component by `[http_trigger.component.<id>.limits]`
+/// and per route by `[http_trigger.route."<route>".limits]`.
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RequestLimits {
+    /// Maximum size of the request head, in bytes. This is applied per
+    /// connection, so can only be set for the whole app.
+    pub max_header_size: Option<usize>,
+    /// Maximum size of the request body, in bytes. Larger requests receive
+    /// 413 Payload Too Large.
+    pub max_body_size: Option<u64>,
+    /// Time allowed for receiving the request, in milliseconds. Requests
+    /// whose body takes longer receive 408 Request Timeout.
+    pub request_read_timeout_ms: Option<u64>,
+    /// Time allowed for a component to produce a response, in milliseconds.
+    /// Slower components receive 503 Service Unavailable.
+    pub execution_timeout_ms: Option<u64>,
+    /// Maximum number of requests a component may handle concurrently. Each
+    /// component has its own count, so an app-wide value is a default for
+    /// every component rather than a total. Requests over the limit receive
+    /// 429 Too Many Requests.
+    pub max_concurrent_requests: Option<usize>,
+}
+
+impl RequestLimits {
+    /// Returns the request read timeout, if set.
+    pub fn request_read_timeout(&self) -> Option<Duration> {
+        self.request_read_timeout_ms.map(Duration::from_millis)
+    }
+
+    /// Returns the execution timeout, if set.
+    pub fn execution_timeout(&self) -> Option<Duration> {
+        self.execution_timeout_ms.map(Duration::from_millis)
+    }
+
+    /// Returns these limits, taking any unset values from `defaults`.
+    fn or(&self, defaults: &Self) -> Self {
+        Self {
+            max_header_size: self.max_header_size.or(defaults.max_header_size),
+            max_body_size: self.max_body_size.or(defaults.max_body_size),
+            request_read_timeout_ms: self
+                .request_read_timeout_ms
+                .or(defaults.request_read_timeout_ms),
+            execution_timeout_ms: self.execution_timeout_ms.or(defaults.execution_timeout_ms),
+            max_concurrent_requests: self
+                .max_concurrent_requests
+                .or(defaults.max_concurrent_requests),
+        }
+    }
+
+    pub(crate) fn validate(&self) -> Result<()> {
+        if let Some(size) = self.max_header_size {
+            if size < MIN_MAX_HEADER_SIZE {
+                bail!("max_header_size must be at least {MIN_MAX_HEADER_SIZE} bytes");
+            }
+        }
+        if self.max_concurrent_requests == Some(0) {
+            bail!("max_concurrent_requests must be greater than zero");
+        }
+        Ok(())
+    }
+}
+
+/// The effective limits for each component of an app.
+#[derive(Default)]
+pub(crate) struct AppLimits {
+    // For components which no route invokes
+    app: ComponentLimits,
+    components: HashMap<String, ComponentLimits>,
+}
+
+impl AppLimits {
+    /// Resolves the limits of each component, given the route which invokes
+    /// it, from the route's, the component's and the app's limits.
+    pub(crate) fn new<'a>(
+        config: &HttpTriggerRuntimeConfig,
+        routes: impl IntoIterator<Item = (&'a str, &'a str)>,
+    ) -> Result<Self> {
+        config.limits.validate()?;
+        for (id, component) in &config.component {
+            if component.limits.max_header_size.is_some() {
+                bail!("component '{id}': max_header_size can only be set in [http_trigger.limits]");
+            }
+            component.limits.validate()?;
+        }
+        for (route, route_config) in &config.route {
+            if route_config.limits.max_header_size.is_some() {
+                bail!("route '{route}': max_header_size can only be set in [http_trigger.limits]");
+            }
+            route_config.limits.validate()?;
+        }
+
+        let mut components = HashMap::new();
+        for (component_id, route) in routes {
+            let mut limits = config.limits.clone();
+            if let Some(component) = config.component.get(component_id) {
+                limits = component.limits.or(&limits);
+            }
+            if let Some(route) = config.route.get(route) {
+                limits = route.limits.or(&limits);
+            }
+            components.insert(component_id.to_owned(), ComponentLimits::new(limits));
+        }
+        Ok(Self {
+            app: ComponentLimits::new(config.limits.clone()),
+            components,
+        })
+    }
+
+    /// Returns the limits applying to requests for the given component.
+    pub(crate) fn component(&self, component_id: &str) -> &ComponentLimits {
+        self.components.get(component_id).unwrap_or(&self.app)
+    }
+}
+
+/// The limits applying to requests for a single component.
+#[derive(Default)]
+pub(crate) struct ComponentLimits {
+    limits: RequestLimits,
+    // Shared between all requests to the component when concurrency is limited.
+    concurrency: Option<Arc<Semaphore>>,
+}
+
+impl ComponentLimits {
+    fn new(limits: RequestLimits) -> Self {
+        let concurrency = limits
+            .max_concurrent_requests
+            .map(|max| Arc::new(Semaphore::new(max)));
+        Self {
+            limits,
+            concurrency,
+        }
+    }
+
+    /// Admits a request, failing with a [`LimitExceeded`] error if it is
+    /// already known to exceed a limit. The returned request's body enforces
+    /// the size and read timeout limits as it is read.
+    pub(crate) fn admit(&self, req: Request<Body>) -> Result<AdmittedRequest, LimitExceeded> {
+        let permit = match &self.concurrency {
+            Some(semaphore) => Some(
+                semaphore
+                    .clone()
+                    .try_acquire_owned()
+                    .map_err(|_| LimitExceeded(StatusCode::TOO_MANY_REQUESTS))?,
+            ),
+            None => None,
+        };
+
+        if let Some(max_body_size) = self.limits.max_body_size {
+            let content_length = req
+                .headers()
+                .get(CONTENT_LENGTH)
+                .and_then(|v| v.to_str().ok())
+                .and_then(|v| v.parse::<u64>().ok());
+            if content_length.is_some_and(|len| len > max_body_size) {
+                return Err(LimitExceeded(StatusCode::PAYLOAD_TOO_LARGE));
+            }
+        }
+
+        let violation = Arc::new(OnceLock::new());
+        let req = if self.limits.max_body_size.is_some()
+            || self.limits.request_read_timeout_ms.is_some()
+        {
+            let violation = violation.clone();
+            let max_body_size = self.limits.max_body_size;
+            let deadline = self
+                .limits
+                .request_read_timeout()
+                .map(|timeout| Box::pin(tokio::time::sleep(timeout)));
+            req.map(|body| {
+                Body::new(LimitedBody {
+                    inner: body,
+                    remaining: max_body_size,
+                    max_body_size,
+                    deadline,
+                    violation,
+                })
+            })
+        } else {
+            req
+        };
+
+        Ok(AdmittedRequest {
+            req,
+            violation,
+            execution_timeout: self.limits.execution_timeout(),
+            _permit: permit,
+        })
+    }
+}
+
+/// A request which has been admitted under its component's limits.
+pub(crate) struct AdmittedRequest {
+    req: Request<Body>,
+    violation: Arc<OnceLock<StatusCode>>,
+    execution_timeout: Option<Duration>,
+    // Held until the response has been produced.
+    _permit: Option<OwnedSemaphorePermit>,
+}
+
+impl AdmittedRequest {
+    /// Runs the handler for the request under the execution timeout. If the
+    /// handler fails because a limit was exceeded while reading the request,
+    /// the error is a [`LimitExceeded`].
+    pub(crate) async fn execute<F, Fut, T>(self, handler: F) -> Result<T>
+    where
+        F: FnOnce(Request<Body>) -> Fut,
+        Fut: Future<Output = Result<T>>,
+    {
+        let fut = handler(self.req);
+        let result = match self.execution_timeout {
+            Some(timeout) => match tokio::time::timeout(timeout, fut).await {
+                Ok(result) => result,
+                Err(_) => Err(LimitExceeded(StatusCode::SERVICE_UNAVAILABLE).into()),
+            },
+            None => fut.await,
+        };
+        result.map_err(|e| match self.violation.get() {
+            Some(status) => LimitExceeded(*status).into(),
+            None => e,
+        })
+    }
+}
+
+/// An error indicating that a request exceeded one of its limits, carrying
+/// the status code to respond with.
+#[derive(Debug)]
+pub(crate) struct LimitExceeded(pub StatusCode);
+
+impl fmt::Display for LimitExceeded {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "request limit exceeded ({})", self.0)
+    }
+}
+
+impl std::error::Error for LimitExceeded {}
+
+// A request body which enforces the body size and read timeout limits,
+// recording which was violated so that the trigger can respond accordingly.
+struct LimitedBody {
+    inner: Body,
+    remaining: Option<u64>,
+    max_body_size: Option<u64>,
+    deadline: Option<Pin<Box<Sleep>>>,
+    violation: Arc<OnceLock<StatusCode>>,
+}
+
+impl LimitedBody {
+    fn fail(
+        &mut self,
+        status: StatusCode,
+        error: ErrorCode,
+    ) -> Poll<Option<Result<Frame<Bytes>, ErrorCode>>> {
+        _ = self.violation.set(status);
+        Poll::Ready(Some(Err(error)))
+    }
+}
+
+impl hyper::body::Body for LimitedBody {
+    type Data = Bytes;
+    type Error = ErrorCode;
+
+    fn poll_frame(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Frame<Self::Data>, Self::Error>>> {
+        let this = &mut *self;
+        let frame = match Pin::new(&mut this.inner).poll_frame(cx) {
+            Poll::Ready(frame) => frame,
+            Poll::Pending => {
+                if let Some(deadline) = &mut this.deadline {
+                    ready!(deadline.as_mut().poll(cx));
+                    return this.fail(
+                        StatusCode::REQUEST_TIMEOUT,
+                        ErrorCode::ConnectionReadTimeout,
+                    );
+                }
+                return Poll::Pending;
+            }
+        };
+
+        if let Some(Ok(frame)) = &frame {
+            if let (Some(data), Some(remaining)) = (frame.data_ref(), this.remaining) {
+                let len = data.len() as u64;
+                if len > remaining {
+                    let max_body_size = this.max_body_size;
+                    return this.fail(
+                        StatusCode::PAYLOAD_TOO_LARGE,
+                        ErrorCode::HttpRequestBodySize(max_body_size),
+                    );
+                }
+                this.remaining = Some(remaining - len);
+            }
+        }
+        Poll::Ready(frame)
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.inner.is_end_stream()
+    }
+
+    fn size_hint(&self) -> SizeHint {
+        self.inner.size_hint()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use http_body_util::BodyExt;
+    use spin_http::body;
+
+    use super::*;
+
+    fn limits(limits: RequestLimits) -> ComponentLimits {
+        ComponentLimits::new(limits)
+    }
+
+    fn request(body: &'static [u8]) -> Request<Body> {
+        Request::post("http://localhost/")
+            .body(body::full(Bytes::from_static(body)))
+            .unwrap()
+    }
+
+    #[test]
+    fn limits_fall_back_to_component_then_app_limits() {
+        let config: HttpTriggerRuntimeConfig = serde_json::from_value(serde_json::json!({
+            "limits": { "max_body_size": 10, "execution_timeout_ms": 100 },
+            "component": { "hello": { "limits": { "max_body_size": 20 } } },
+            "route": { "/hello/...": { "limits": { "execution_timeout_ms": 200 } } },
+        }))
+        .unwrap();
+        let app_limits =
+            AppLimits::new(&config, [("hello", "/hello/..."), ("other", "/other")]).unwrap();
+
+        let hello = &app_limits.component("hello").limits;
+        assert_eq!(hello.max_body_size, Some(20));
+        assert_eq!(hello.execution_timeout_ms, Some(200));
+
+        let other = &app_limits.component("other").limits;
+        assert_eq!(other.max_body_size, Some(10));
+        assert_eq!(other.execution_timeout_ms, Some(100));
+    }
+
+    #[test]
+    fn components_do_not_share_concurrency() {
+        let config: HttpTriggerRuntimeConfig = serde_json::from_value(serde_json::json!({
+            "limits": { "max_concurrent_requests": 1 },
+            "component": { "a": { "auth": null } },
+        }))
+        .unwrap();
+        let app_limits = AppLimits::new(&config, [("a", "/a"), ("b", "/b"), ("c", "/c")]).unwrap();
+
+        let _a = app_limits.component("a").admit(request(b"")).unwrap();
+        let _b = app_limits.component("b").admit(request(b"")).unwrap();
+        let _c = app_limits.component("c").admit(request(b"")).unwrap();
+        let err = app_limits.component("b").admit(request(b"")).err().unwrap();
+        assert_eq!(err.0, StatusCode::TOO_MANY_REQUESTS);
+    }
+
+    #[test]
+    fn component_cannot_set_header_size() {
+        let config: HttpTriggerRuntimeConfig = serde_json::from_value(serde_json::json!({
+            "component": { "hello": { "limits": { "max_header_size": 16384 } } },
+        }))
+        .unwrap();
+        assert!(AppLimits::new(&config, [("hello", "/")]).is_err());
+
+        let config: HttpTriggerRuntimeConfig = serde_json::from_value(serde_json::json!({
+            "route": { "/": { "limits": { "max_header_size": 16384 } } },
+        }))
+        .unwrap();
+        assert!(AppLimits::new(&config, [("hello", "/")]).is_err());
+    }
+
+    #[test]
+    fn too_many_concurrent_requests_are_rejected() {
+        let limits = limits(RequestLimits {
+            max_concurrent_requests: Some(1),
+            ..Default::default()
+        });
+
+        let first = limits.admit(request(b"")).unwrap();
+        let err = limits.admit(request(b"")).err().unwrap();
+        assert_eq!(err.0, StatusCode::TOO_MANY_REQUESTS);
+
+        drop(first);
+        assert!(limits.admit(request(b"")).is_ok());
+    }
+
+    #[test]
+    fn declared_oversized_body_is_rejected() {
+        let limits = limits(RequestLimits {
+            max_body_size: Some(4),
+            ..Default::default()
+        });
+        let mut req = request(b"hello");
+        req.headers_mut()
+            .insert(CONTENT_LENGTH, "5".parse().unwrap());
+
+        let err = limits.admit(req).err().unwrap();
+        assert_eq!(err.0, StatusCode::PAYLOAD_TOO_LARGE);
+    }
+
+    #[tokio::test]
+    async fn streamed_oversized_body_is_rejected() {
+        let limits = limits(RequestLimits {
+            max_body_size: Some(4),
+            ..Default::default()
+        });
+
+        let admitted = limits.admit(request(b"hello")).unwrap();
+        let err = admitted
+            .execute(|req| async move {
+                req.into_body()
+                    .collect()
+                    .await
+                    .map_err(|e| anyhow::anyhow!("{e:?}"))
+            })
+            .await
+            .unwrap_err();
+        let err = err.downcast::<LimitExceeded>().unwrap();
+        assert_eq!(err.0, StatusCode::PAYLOAD_TOO_LARGE);
+    }
+
+    #[tokio::test]
+    async fn slow_handler_times_out() {
+        let limits = limits(RequestLimits {
+            execution_timeout_ms: Some(10),
+            ..Default::default()
+        });
+
+        let admitted = limits.admit(request(b"")).unwrap();
+        let err = admitted
+            .execute(|_| async {
+                tokio::time::sleep(Duration::from_secs(10)).await;
+                Ok(())
+            })
+            .await
+            .unwrap_err();
+        let err = err.downcast::<LimitExceeded>().unwrap();
+        assert_eq!(err.0, StatusCode::SERVICE_UNAVAILABLE);
+    }
+}
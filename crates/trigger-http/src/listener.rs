@@ -0,0 +1,181 @@
+//! Listening sockets for the HTTP trigger.
+
+use std::{
+    fmt,
+    net::{Ipv4Addr, SocketAddr, SocketAddrV4, ToSocketAddrs},
+    path::PathBuf,
+};
+
+use anyhow::{bail, Context, Result};
+use tokio::net::TcpListener;
+
+/// The peer address reported for connections which have no IP address, such
+/// as those accepted on a Unix domain socket.
+pub(crate) const UNKNOWN_PEER_ADDR: SocketAddr =
+    SocketAddr::V4(SocketAddrV4::new(Ipv4Addr::UNSPECIFIED, 0));
+
+/// An address for the HTTP trigger to listen on.
+#[derive(Clone, Debug, PartialEq)]
+pub enum ListenAddr {
+    /// A TCP address, e.g. `127.0.0.1:3000`.
+    Tcp(SocketAddr),
+    /// A Unix domain socket path, given as `unix:<path>`.
+    Unix(PathBuf),
+    /// The first socket passed by systemd socket activation, given as
+    /// `systemd`.
+    Systemd,
+}
+
+impl fmt::Display for ListenAddr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Tcp(addr) => addr.fmt(f),
+            Self::Unix(path) => write!(f, "unix:{}", path.display()),
+            Self::Systemd => f.write_str("systemd"),
+        }
+    }
+}
+
+pub(crate) fn parse_listen_addr(addr: &str) -> Result<ListenAddr> {
+    if let Some(path) = addr.strip_prefix("unix:") {
+        if path.is_empty() {
+            bail!("missing Unix socket path");
+        }
+        return Ok(ListenAddr::Unix(path.into()));
+    }
+    if addr == "systemd" {
+        return Ok(ListenAddr::Systemd);
+    }
+    let addrs: Vec<SocketAddr> = addr.to_socket_addrs()?.collect();
+    // Prefer 127.0.0.1 over e.g. [::1] because CHANGE IS HARD
+    if let Some(addr) = addrs
+        .iter()
+        .find(|addr| addr.is_ipv4() && addr.ip() == Ipv4Addr::LOCALHOST)
+    {
+        return Ok(ListenAddr::Tcp(*addr));
+    }
+    // Otherwise, take the first addr (OS preference)
+    addrs
+        .into_iter()
+        .next()
+        .map(ListenAddr::Tcp)
+        .context("couldn't resolve address")
+}
+
+/// A bound listening socket.
+pub(crate) enum Listener {
+    Tcp(TcpListener),
+    #[cfg(unix)]
+    Unix(tokio::net::UnixListener),
+}
+
+impl Listener {
+    pub async fn bind(addr: &ListenAddr) -> Result<Self> {
+        match addr {
+            ListenAddr::Tcp(addr) => Ok(Self::Tcp(
+                TcpListener::bind(addr)
+                    .await
+                    .with_context(|| format!("Unable to listen on {addr}"))?,
+            )),
+            #[cfg(unix)]
+            ListenAddr::Unix(path) => {
+                use std::os::unix::fs::FileTypeExt;
+                // Remove a socket left behind by a previous run
+                if let Ok(metadata) = std::fs::symlink_metadata(path) {
+                    if metadata.file_type().is_socket() {
+                        std::fs::remove_file(path).with_context(|| {
+                            format!("Unable to remove stale socket {}", path.display())
+                        })?;
+                    }
+                }
+                Ok(Self::Unix(
+                    tokio::net::UnixListener::bind(path)
+                        .with_context(|| format!("Unable to listen on unix:{}", path.display()))?,
+                ))
+            }
+            #[cfg(unix)]
+            ListenAddr::Systemd => systemd_listener(),
+            #[cfg(not(unix))]
+            _ => bail!("Listening on {addr} is only supported on Unix platforms"),
+        }
+    }
+}
+
+/// Takes the first socket passed via the systemd socket activation protocol.
+///
+/// `LISTEN_PID` is not checked, as the trigger usually runs as a child of the
+/// `spin up` process which systemd started.
+#[cfg(unix)]
+fn systemd_listener() -> Result<Listener> {
+    use std::os::unix::io::FromRawFd;
+
+    // Passed file descriptors start at 3
+    const SD_LISTEN_FDS_START: i32 = 3;
+
+    let fds: u32 = std::env::var("LISTEN_FDS")
+        .context("LISTEN_FDS is not set; was Spin started by systemd socket activation?")?
+        .parse()
+        .context("Invalid LISTEN_FDS")?;
+    if fds == 0 {
+        bail!("No sockets were passed by systemd");
+    }
+    if fds > 1 {
+        tracing::warn!("systemd passed {fds} sockets; only the first will be used");
+    }
+
+    // Safety: the socket activation protocol passes ownership of this file
+    // descriptor to the process, and nothing else takes it.
+    let socket = unsafe { socket2::Socket::from_raw_fd(SD_LISTEN_FDS_START) };
+    socket
+        .set_nonblocking(true)
+        .context("Invalid socket passed by systemd")?;
+    let local_addr = socket
+        .local_addr()
+        .context("Invalid socket passed by systemd")?;
+    if local_addr.as_socket().is_some() {
+        let listener = std::net::TcpListener::from(socket);
+        Ok(Listener::Tcp(TcpListener::from_std(listener)?))
+    } else {
+        let listener = std::os::unix::net::UnixListener::from(socket);
+        Ok(Listener::Unix(tokio::net::UnixListener::from_std(
+            listener,
+        )?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_listen_addr_prefers_ipv4() {
+        let ListenAddr::Tcp(addr) = parse_listen_addr("localhost:12345").unwrap() else {
+            panic!("expected a TCP address");
+        };
+        assert_eq!(addr.ip(), Ipv4Addr::LOCALHOST);
+        assert_eq!(addr.port(), 12345);
+    }
+
+    #[test]
+    fn parse_listen_addr_accepts_sockets() {
+        assert_eq!(
+            parse_listen_addr("unix:/run/spin.sock").unwrap(),
+            ListenAddr::Unix("/run/spin.sock".into())
+        );
+        assert_eq!(parse_listen_addr("systemd").unwrap(), ListenAddr::Systemd);
+        assert!(parse_listen_addr("unix:").is_err());
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn binds_unix_socket_replacing_stale_one() {
+        let dir = tempfile::tempdir().unwrap();
+        let addr = ListenAddr::Unix(dir.path().join("spin.sock"));
+        let first = Listener::bind(&addr).await.unwrap();
+        drop(first);
+        assert!(matches!(
+            Listener::bind(&addr).await.unwrap(),
+            Listener::Unix(_)
+        ));
+    }
+}
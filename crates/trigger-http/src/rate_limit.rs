@@ -0,0 +1,345 @@
+//! Per-client rate limiting for the HTTP trigger.
+
+use std::{
+    collections::HashMap,
+    net::SocketAddr,
+    sync::{Arc, Mutex},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::{bail, Context, Result};
+use http::{HeaderMap, HeaderName, HeaderValue, StatusCode};
+use hyper::{Request, Response};
+use serde::{Deserialize, Serialize};
+use spin_http::body;
+use spin_key_value::Store;
+use spin_trigger::RuntimeConfig;
+use tracing::log;
+
+use crate::{runtime_config::HttpTriggerRuntimeConfig, Body};
+
+const RATE_LIMIT_LIMIT: &str = "ratelimit-limit";
+const RATE_LIMIT_REMAINING: &str = "ratelimit-remaining";
+const RATE_LIMIT_RESET: &str = "ratelimit-reset";
+
+// Local buckets are pruned once there are this many clients
+const MAX_LOCAL_BUCKETS: usize = 100_000;
+
+/// Rate limiting for a component, read from the
+/// `[http_trigger.component.<id>.rate_limit]` runtime config table.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RateLimitConfig {
+    /// Sustained number of requests per second allowed for each client.
+    pub requests_per_second: f64,
+    /// Number of requests a client may make in a burst. Defaults to one
+    /// second's worth of requests.
+    #[serde(default)]
+    pub burst: Option<u32>,
+    /// How clients are identified: `"client_ip"` (the default) or
+    /// `"header:<name>"`, e.g. for API keys.
+    #[serde(default)]
+    pub key: RateLimitKey,
+    /// A key-value store label in which to keep limit state, so that
+    /// instances sharing the store share limits. Sharing is best-effort:
+    /// concurrent requests on different instances may race.
+    #[serde(default)]
+    pub key_value_store: Option<String>,
+}
+
+/// How clients are identified for rate limiting.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Eq)]
+#[serde(try_from = "String")]
+pub enum RateLimitKey {
+    /// The client's IP address.
+    #[default]
+    ClientIp,
+    /// The value of a request header. Requests without the header are
+    /// identified by client IP.
+    Header(HeaderName),
+}
+
+impl TryFrom<String> for RateLimitKey {
+    type Error = anyhow::Error;
+
+    fn try_from(value: String) -> Result<Self> {
+        if value == "client_ip" {
+            return Ok(Self::ClientIp);
+        }
+        match value.strip_prefix("header:") {
+            Some(name) => Ok(Self::Header(
+                HeaderName::try_from(name.trim())
+                    .with_context(|| format!("invalid header name {name:?}"))?,
+            )),
+            None => {
+                bail!("invalid rate limit key {value:?}; expected `client_ip` or `header:<name>`")
+            }
+        }
+    }
+}
+
+/// The rate limiters for each component which has one.
+pub(crate) struct AppRateLimits {
+    components: HashMap<String, RateLimiter>,
+}
+
+impl AppRateLimits {
+    pub async fn new(
+        runtime_config: &HttpTriggerRuntimeConfig,
+        spin_runtime_config: &RuntimeConfig,
+    ) -> Result<Self> {
+        let mut components = HashMap::new();
+        for (component_id, config) in &runtime_config.component {
+            let Some(rate_limit) = &config.rate_limit else {
+                continue;
+            };
+            let limiter = RateLimiter::new(component_id, rate_limit, spin_runtime_config)
+                .await
+                .with_context(|| format!("Invalid rate limit for component '{component_id}'"))?;
+            components.insert(component_id.clone(), limiter);
+        }
+        Ok(Self { components })
+    }
+
+    /// Counts a request against its client's limit. Returns the rate limit
+    /// headers for the response, or the response to return if the client is
+    /// over its limit.
+    pub async fn check(
+        &self,
+        component_id: &str,
+        req: &Request<Body>,
+        addr: SocketAddr,
+    ) -> Result<Option<HeaderMap>, Response<Body>> {
+        match self.components.get(component_id) {
+            Some(limiter) => limiter.check(req, addr).await.map(Some),
+            None => Ok(None),
+        }
+    }
+}
+
+struct RateLimiter {
+    component_id: String,
+    rate: f64,
+    burst: f64,
+    key: RateLimitKey,
+    state: BucketState,
+}
+
+enum BucketState {
+    Local(Mutex<HashMap<String, Bucket>>),
+    Shared(Arc<dyn Store>),
+}
+
+impl RateLimiter {
+    async fn new(
+        component_id: &str,
+        config: &RateLimitConfig,
+        spin_runtime_config: &RuntimeConfig,
+    ) -> Result<Self> {
+        if !config.requests_per_second.is_finite() || config.requests_per_second <= 0.0 {
+            bail!("`requests_per_second` must be positive");
+        }
+        let burst = match config.burst {
+            Some(0) => bail!("`burst` must be at least 1"),
+            Some(burst) => f64::from(burst),
+            None => config.requests_per_second.ceil(),
+        };
+        let state = match &config.key_value_store {
+            Some(label) => {
+                let (_, manager) = spin_runtime_config
+                    .key_value_stores()?
+                    .into_iter()
+                    .find(|(name, _)| name == label)
+                    .with_context(|| format!("no key-value store labelled {label:?}"))?;
+                let store = manager
+                    .get(label)
+                    .await
+                    .map_err(|e| anyhow::anyhow!("{e:?}"))
+                    .with_context(|| format!("failed to open key-value store {label:?}"))?;
+                BucketState::Shared(store)
+            }
+            None => BucketState::Local(Default::default()),
+        };
+        Ok(Self {
+            component_id: component_id.to_owned(),
+            rate: config.requests_per_second,
+            burst,
+            key: config.key.clone(),
+            state,
+        })
+    }
+
+    async fn check(
+        &self,
+        req: &Request<Body>,
+        addr: SocketAddr,
+    ) -> Result<HeaderMap, Response<Body>> {
+        let client = match &self.key {
+            RateLimitKey::Header(name) => match req.headers().get(name) {
+                Some(value) => format!("header:{}", String::from_utf8_lossy(value.as_bytes())),
+                None => format!("ip:{}", addr.ip()),
+            },
+            RateLimitKey::ClientIp => format!("ip:{}", addr.ip()),
+        };
+        let now = now_secs();
+        let outcome = match &self.state {
+            BucketState::Local(buckets) => {
+                let mut buckets = buckets.lock().unwrap();
+                if buckets.len() >= MAX_LOCAL_BUCKETS {
+                    buckets.retain(|_, bucket| {
+                        bucket.refilled(now, self.rate, self.burst) < self.burst
+                    });
+                }
+                buckets
+                    .entry(client)
+                    .or_insert_with(|| Bucket::full(self.burst, now))
+                    .take(now, self.rate, self.burst)
+            }
+            BucketState::Shared(store) => self.take_shared(store.as_ref(), &client, now).await,
+        };
+
+        let mut headers = HeaderMap::new();
+        headers.insert(RATE_LIMIT_LIMIT, HeaderValue::from(self.burst as u64));
+        match outcome {
+            Ok(remaining) => {
+                let reset = ((self.burst - remaining) / self.rate).ceil() as u64;
+                headers.insert(
+                    RATE_LIMIT_REMAINING,
+                    HeaderValue::from(remaining.floor() as u64),
+                );
+                headers.insert(RATE_LIMIT_RESET, HeaderValue::from(reset));
+                Ok(headers)
+            }
+            Err(wait) => {
+                let wait = wait.ceil().max(1.0) as u64;
+                headers.insert(RATE_LIMIT_REMAINING, HeaderValue::from(0));
+                headers.insert(RATE_LIMIT_RESET, HeaderValue::from(wait));
+                headers.insert(http::header::RETRY_AFTER, HeaderValue::from(wait));
+                let mut resp = Response::new(body::empty());
+                *resp.status_mut() = StatusCode::TOO_MANY_REQUESTS;
+                *resp.headers_mut() = headers;
+                Err(resp)
+            }
+        }
+    }
+
+    // Takes a token from a bucket kept in a key-value store. Store errors
+    // allow the request rather than failing it.
+    async fn take_shared(&self, store: &dyn Store, client: &str, now: f64) -> Result<f64, f64> {
+        let key = format!("spin-rate-limit/{}/{client}", self.component_id);
+        let mut bucket = match store.get(&key).await {
+            Ok(Some(bytes)) => {
+                serde_json::from_slice(&bytes).unwrap_or_else(|_| Bucket::full(self.burst, now))
+            }
+            Ok(None) => Bucket::full(self.burst, now),
+            Err(e) => {
+                log::warn!("Failed to read rate limit state: {e:?}");
+                return Ok(self.burst);
+            }
+        };
+        let outcome = bucket.take(now, self.rate, self.burst);
+        match serde_json::to_vec(&bucket) {
+            Ok(bytes) => {
+                if let Err(e) = store.set(&key, &bytes).await {
+                    log::warn!("Failed to write rate limit state: {e:?}");
+                }
+            }
+            Err(e) => log::warn!("Failed to serialize rate limit state: {e}"),
+        }
+        outcome
+    }
+}
+
+/// A token bucket. Times are seconds since the Unix epoch.
+#[derive(Clone, Copy, Debug, Deserialize, Serialize)]
+struct Bucket {
+    tokens: f64,
+    updated: f64,
+}
+
+impl Bucket {
+    fn full(burst: f64, now: f64) -> Self {
+        Self {
+            tokens: burst,
+            updated: now,
+        }
+    }
+
+    fn refilled(&self, now: f64, rate: f64, burst: f64) -> f64 {
+        (self.tokens + (now - self.updated).max(0.0) * rate).min(burst)
+    }
+
+    /// Takes a token, returning the tokens remaining, or the number of
+    /// seconds until a token will be available.
+    fn take(&mut self, now: f64, rate: f64, burst: f64) -> Result<f64, f64> {
+        self.tokens = self.refilled(now, rate, burst);
+        self.updated = now;
+        if self.tokens >= 1.0 {
+            self.tokens -= 1.0;
+            Ok(self.tokens)
+        } else {
+            Err((1.0 - self.tokens) / rate)
+        }
+    }
+}
+
+fn now_secs() -> f64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs_f64()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bucket_refills_over_time() {
+        let mut bucket = Bucket::full(2.0, 0.0);
+        assert_eq!(bucket.take(0.0, 1.0, 2.0), Ok(1.0));
+        assert_eq!(bucket.take(0.0, 1.0, 2.0), Ok(0.0));
+        assert_eq!(bucket.take(0.5, 1.0, 2.0), Err(0.5));
+        assert_eq!(bucket.take(1.0, 1.0, 2.0), Ok(0.0));
+        // Refills never exceed the burst size
+        assert_eq!(bucket.take(100.0, 1.0, 2.0), Ok(1.0));
+    }
+
+    #[test]
+    fn parses_keys() {
+        assert_eq!(
+            RateLimitKey::try_from("client_ip".to_owned()).unwrap(),
+            RateLimitKey::ClientIp
+        );
+        assert_eq!(
+            RateLimitKey::try_from("header:X-API-Key".to_owned()).unwrap(),
+            RateLimitKey::Header(HeaderName::from_static("x-api-key"))
+        );
+        assert!(RateLimitKey::try_from("cookie".to_owned()).is_err());
+    }
+
+    #[tokio::test]
+    async fn clients_are_limited_separately() {
+        let limiter = RateLimiter {
+            component_id: "test".into(),
+            rate: 0.001,
+            burst: 1.0,
+            key: RateLimitKey::Header(HeaderName::from_static("x-api-key")),
+            state: BucketState::Local(Default::default()),
+        };
+        let addr: SocketAddr = "127.0.0.1:1234".parse().unwrap();
+        let req = |key: &str| {
+            Request::get("http://localhost/")
+                .header("x-api-key", key)
+                .body(body::empty())
+                .unwrap()
+        };
+
+        let headers = limiter.check(&req("a"), addr).await.unwrap();
+        assert_eq!(headers[RATE_LIMIT_REMAINING], "0");
+        let resp = limiter.check(&req("a"), addr).await.unwrap_err();
+        assert_eq!(resp.status(), StatusCode::TOO_MANY_REQUESTS);
+        assert!(resp.headers().contains_key(http::header::RETRY_AFTER));
+        assert!(limiter.check(&req("b"), addr).await.is_ok());
+    }
+}
@@ -0,0 +1,75 @@
+use std::collections::HashMap;
+
+use serde::Deserialize;
+use spin_http::config::CorsConfig;
+
+use crate::{
+    access_log::AccessLogConfig, acme::AcmeConfig, auth::AuthConfig,
+    compression::CompressionConfig, debug_capture::DebugCaptureConfig, health::HealthConfig,
+    keep_warm::KeepWarmConfig, limits::RequestLimits, rate_limit::RateLimitConfig,
+};
+
+/// Options for the HTTP trigger, read from the `[http_trigger]` table of the
+/// runtime config file.
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct HttpTriggerRuntimeConfig {
+    /// Access logging. Disabled if unset.
+    #[serde(default)]
+    pub access_log: Option<AccessLogConfig>,
+    /// Automatic certificate provisioning via ACME.
+    #[serde(default)]
+    pub acme: Option<AcmeConfig>,
+    /// Response compression and request decompression. Disabled if unset.
+    #[serde(default)]
+    pub compression: Option<CompressionConfig>,
+    /// CORS policy for routes which don't set their own.
+    #[serde(default)]
+    pub cors: Option<CorsConfig>,
+    /// Per-request debug capture. Disabled if unset.
+    #[serde(default)]
+    pub debug_capture: Option<DebugCaptureConfig>,
+    /// Liveness and readiness endpoints. Disabled if unset.
+    #[serde(default)]
+    pub health: Option<HealthConfig>,
+    /// Request limits applying to every component.
+    #[serde(default)]
+    pub limits: RequestLimits,
+    /// Per-component options, keyed by component ID.
+    #[serde(default)]
+    pub component: HashMap<String, ComponentRuntimeConfig>,
+    /// Per-route options, keyed by the route as written in the manifest.
+    #[serde(default)]
+    pub route: HashMap<String, RouteRuntimeConfig>,
+}
+
+/// Options for a single component, read from the
+/// `[http_trigger.component.<id>]` table of the runtime config file.
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ComponentRuntimeConfig {
+    /// Request limits for this component, overriding the app-wide limits.
+    #[serde(default)]
+    pub limits: RequestLimits,
+    /// Authentication required before the component is invoked.
+    #[serde(default)]
+    pub auth: Option<AuthConfig>,
+    /// Per-client rate limiting for the component.
+    #[serde(default)]
+    pub rate_limit: Option<RateLimitConfig>,
+    /// Reuse of instances across requests, for components whose
+    /// initialization dominates request latency.
+    #[serde(default)]
+    pub keep_warm: Option<KeepWarmConfig>,
+}
+
+/// Options for a single route, read from the `[http_trigger.route."<route>"]`
+/// table of the runtime config file.
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RouteRuntimeConfig {
+    /// Request limits for this route, overriding the component's and the
+    /// app-wide limits.
+    #[serde(default)]
+    pub limits: RequestLimits,
+}
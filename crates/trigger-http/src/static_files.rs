@@ -0,0 +1,402 @@
+//! Serving routes directly from a component's files mounts.
+
+use std::{
+    io::{ErrorKind, SeekFrom},
+    path::{Component, Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::{bail, Context, Result};
+use http::{
+    header::{
+        ACCEPT_RANGES, ALLOW, CACHE_CONTROL, CONTENT_LENGTH, CONTENT_RANGE, CONTENT_TYPE, ETAG,
+        IF_MODIFIED_SINCE, IF_NONE_MATCH, IF_RANGE, LAST_MODIFIED, RANGE,
+    },
+    HeaderMap, HeaderValue, Method, StatusCode,
+};
+use hyper::{Request, Response};
+use percent_encoding::percent_decode_str;
+use spin_app::AppComponent;
+use spin_common::url::parse_file_url;
+use spin_http::{body, config::StaticFilesConfig};
+use tokio::{
+    fs::File,
+    io::{AsyncReadExt, AsyncSeekExt},
+};
+
+use crate::{compression::reader_body, Body};
+
+/// Serves files for a route configured with `static = { ... }`.
+pub(crate) struct StaticFiles {
+    root: PathBuf,
+    index: Option<String>,
+    cache_control: Option<HeaderValue>,
+}
+
+impl StaticFiles {
+    /// Resolves the configured directory against the component's files mounts.
+    pub(crate) fn new<L>(component: &AppComponent<L>, config: &StaticFilesConfig) -> Result<Self> {
+        let dir = Path::new("/").join(&config.dir);
+        let mut best: Option<(usize, PathBuf)> = None;
+        for mount in component.files() {
+            let Ok(rest) = dir.strip_prefix(&mount.path) else {
+                continue;
+            };
+            let depth = mount.path.components().count();
+            if best.as_ref().is_some_and(|(d, _)| *d >= depth) {
+                continue;
+            }
+            let source = mount
+                .content
+                .source
+                .as_deref()
+                .with_context(|| format!("Missing 'source' on files mount {mount:?}"))?;
+            best = Some((depth, parse_file_url(source)?.join(rest)));
+        }
+        let Some((_, root)) = best else {
+            bail!(
+                "Component '{}' has no files mount containing static directory {:?}",
+                component.id(),
+                config.dir
+            );
+        };
+        if !root.is_dir() {
+            bail!(
+                "Static directory {:?} of component '{}' is not a directory",
+                config.dir,
+                component.id()
+            );
+        }
+        // Served paths are checked against the canonical root
+        let root = root.canonicalize().with_context(|| {
+            format!(
+                "Failed to resolve static directory {:?} of component '{}'",
+                config.dir,
+                component.id()
+            )
+        })?;
+
+        let cache_control = config
+            .cache_control
+            .as_deref()
+            .map(HeaderValue::from_str)
+            .transpose()
+            .context("Invalid static `cache_control`")?;
+
+        Ok(Self {
+            root,
+            index: config.index.clone(),
+            cache_control,
+        })
+    }
+
+    /// Serves the file at `path_info`, relative to the static directory.
+    pub(crate) async fn serve(
+        &self,
+        req: &Request<Body>,
+        path_info: &str,
+    ) -> Result<Response<Body>> {
+        if req.method() != Method::GET && req.method() != Method::HEAD {
+            return Ok(Response::builder()
+                .status(StatusCode::METHOD_NOT_ALLOWED)
+                .header(ALLOW, "GET, HEAD")
+                .body(body::empty())?);
+        }
+
+        let Some((path, mut file, len, modified)) = self.open(path_info).await? else {
+            return status(StatusCode::NOT_FOUND);
+        };
+
+        let etag = format!("\"{len:x}-{:x}\"", unix_nanos(modified));
+        let last_modified = httpdate::fmt_http_date(modified);
+
+        let mut builder = Response::builder()
+            .header(ETAG, &etag)
+            .header(LAST_MODIFIED, &last_modified)
+            .header(ACCEPT_RANGES, "bytes");
+        if let Some(cache_control) = &self.cache_control {
+            builder = builder.header(CACHE_CONTROL, cache_control);
+        }
+
+        if is_not_modified(req.headers(), &etag, modified) {
+            return Ok(builder
+                .status(StatusCode::NOT_MODIFIED)
+                .body(body::empty())?);
+        }
+
+        let content_type = mime_guess::from_path(&path).first_or_octet_stream();
+        builder = builder.header(CONTENT_TYPE, content_type.as_ref());
+
+        let range = if if_range_matches(req.headers(), &etag, &last_modified) {
+            req.headers().get(RANGE).map(|r| parse_range(r, len))
+        } else {
+            None
+        };
+        let (builder, len) = match range {
+            None | Some(Ok(None)) => (builder.status(StatusCode::OK), len),
+            Some(Ok(Some((start, end)))) => {
+                file.seek(SeekFrom::Start(start)).await?;
+                let builder = builder
+                    .status(StatusCode::PARTIAL_CONTENT)
+                    .header(CONTENT_RANGE, format!("bytes {start}-{end}/{len}"));
+                (builder, end - start + 1)
+            }
+            Some(Err(())) => {
+                return Ok(Response::builder()
+                    .status(StatusCode::RANGE_NOT_SATISFIABLE)
+                    .header(CONTENT_RANGE, format!("bytes */{len}"))
+                    .body(body::empty())?);
+            }
+        };
+
+        let body = if req.method() == Method::HEAD {
+            body::empty()
+        } else {
+            reader_body(file.take(len))
+        };
+        Ok(builder.header(CONTENT_LENGTH, len).body(body)?)
+    }
+
+    // Opens the file for a request path, returning `None` if there is no such
+    // file or the path tries to escape the static directory, including through
+    // symlinks.
+    async fn open(&self, path_info: &str) -> Result<Option<(PathBuf, File, u64, SystemTime)>> {
+        let Ok(decoded) = percent_decode_str(path_info).decode_utf8() else {
+            return Ok(None);
+        };
+        let mut path = self.root.clone();
+        for component in Path::new(decoded.as_ref()).components() {
+            match component {
+                Component::Normal(segment) => path.push(segment),
+                Component::RootDir | Component::CurDir => {}
+                Component::ParentDir | Component::Prefix(_) => return Ok(None),
+            }
+        }
+
+        let mut metadata = match tokio::fs::metadata(&path).await {
+            Ok(metadata) => metadata,
+            Err(e) if e.kind() == ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e).with_context(|| format!("Failed to read {path:?}")),
+        };
+        if metadata.is_dir() {
+            let Some(index) = &self.index else {
+                return Ok(None);
+            };
+            path.push(index);
+            metadata = match tokio::fs::metadata(&path).await {
+                Ok(metadata) => metadata,
+                Err(e) if e.kind() == ErrorKind::NotFound => return Ok(None),
+                Err(e) => return Err(e).with_context(|| format!("Failed to read {path:?}")),
+            };
+        }
+        if !metadata.is_file() {
+            return Ok(None);
+        }
+        let resolved = match tokio::fs::canonicalize(&path).await {
+            Ok(resolved) => resolved,
+            Err(e) if e.kind() == ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e).with_context(|| format!("Failed to resolve {path:?}")),
+        };
+        if !resolved.starts_with(&self.root) {
+            return Ok(None);
+        }
+
+        let file = File::open(&resolved)
+            .await
+            .with_context(|| format!("Failed to open {path:?}"))?;
+        let modified = metadata.modified().unwrap_or(UNIX_EPOCH);
+        Ok(Some((path, file, metadata.len(), modified)))
+    }
+}
+
+fn status(status: StatusCode) -> Result<Response<Body>> {
+    Ok(Response::builder().status(status).body(body::empty())?)
+}
+
+fn unix_nanos(time: SystemTime) -> u128 {
+    time.duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_nanos()
+}
+
+// Evaluates `If-None-Match`, falling back to `If-Modified-Since` as RFC 9110
+// requires.
+fn is_not_modified(headers: &HeaderMap, etag: &str, modified: SystemTime) -> bool {
+    if let Some(if_none_match) = headers.get(IF_NONE_MATCH) {
+        let Ok(if_none_match) = if_none_match.to_str() else {
+            return false;
+        };
+        return if_none_match.split(',').any(|tag| {
+            let tag = tag.trim();
+            tag == "*" || tag.trim_start_matches("W/") == etag
+        });
+    }
+    let Some(since) = headers
+        .get(IF_MODIFIED_SINCE)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| httpdate::parse_http_date(v).ok())
+    else {
+        return false;
+    };
+    // HTTP dates only have second resolution
+    let modified_secs = modified
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+    let since_secs = since
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+    modified_secs <= since_secs
+}
+
+// A `Range` is only honoured if any `If-Range` matches the current file.
+fn if_range_matches(headers: &HeaderMap, etag: &str, last_modified: &str) -> bool {
+    match headers.get(IF_RANGE) {
+        Some(if_range) => if_range == etag || if_range == last_modified,
+        None => true,
+    }
+}
+
+// Parses a single `bytes` range into inclusive offsets. Returns `Ok(None)` for
+// ranges which should be ignored (multiple ranges or invalid syntax) and `Err`
+// for ranges which can't be satisfied.
+fn parse_range(value: &HeaderValue, len: u64) -> Result<Option<(u64, u64)>, ()> {
+    let Some(spec) = value.to_str().ok().and_then(|v| v.strip_prefix("bytes=")) else {
+        return Ok(None);
+    };
+    if spec.contains(',') {
+        return Ok(None);
+    }
+    let Some((start, end)) = spec.trim().split_once('-') else {
+        return Ok(None);
+    };
+    let (start, end) = match (start.parse::<u64>(), end.parse::<u64>()) {
+        // `bytes=-N`: the last N bytes
+        (Err(_), Ok(suffix)) if start.is_empty() => {
+            if suffix == 0 {
+                return Err(());
+            }
+            (len.saturating_sub(suffix), len.saturating_sub(1))
+        }
+        // `bytes=N-`: from N to the end
+        (Ok(start), Err(_)) if end.is_empty() => (start, len.saturating_sub(1)),
+        (Ok(start), Ok(end)) if start <= end => (start, end.min(len.saturating_sub(1))),
+        _ => return Ok(None),
+    };
+    if start >= len {
+        return Err(());
+    }
+    Ok(Some((start, end)))
+}
+
+#[cfg(test)]
+mod tests {
+    use http_body_util::BodyExt;
+
+    use super::*;
+
+    fn static_files(root: &Path) -> StaticFiles {
+        StaticFiles {
+            root: root.canonicalize().unwrap(),
+            index: Some("index.html".into()),
+            cache_control: Some(HeaderValue::from_static("max-age=60")),
+        }
+    }
+
+    fn get(headers: &[(&str, &str)]) -> Request<Body> {
+        let mut req = Request::get("http://localhost/")
+            .body(body::empty())
+            .unwrap();
+        for (name, value) in headers {
+            req.headers_mut().insert(
+                http::HeaderName::from_bytes(name.as_bytes()).unwrap(),
+                HeaderValue::from_str(value).unwrap(),
+            );
+        }
+        req
+    }
+
+    async fn body_text(resp: Response<Body>) -> String {
+        let bytes = resp.into_body().collect().await.unwrap().to_bytes();
+        String::from_utf8(bytes.to_vec()).unwrap()
+    }
+
+    #[test]
+    fn parses_ranges() {
+        let range = |v| parse_range(&HeaderValue::from_static(v), 10);
+        assert_eq!(range("bytes=0-4"), Ok(Some((0, 4))));
+        assert_eq!(range("bytes=5-"), Ok(Some((5, 9))));
+        assert_eq!(range("bytes=-3"), Ok(Some((7, 9))));
+        assert_eq!(range("bytes=8-100"), Ok(Some((8, 9))));
+        assert_eq!(range("bytes=10-"), Err(()));
+        assert_eq!(range("bytes=0-1,3-4"), Ok(None));
+        assert_eq!(range("items=0-1"), Ok(None));
+    }
+
+    #[tokio::test]
+    async fn serves_files_with_validators() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("index.html"), "<h1>hello</h1>").unwrap();
+        std::fs::write(dir.path().join("app.js"), "console.log(1)").unwrap();
+        let files = static_files(dir.path());
+
+        let resp = files.serve(&get(&[]), "/").await.unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert_eq!(resp.headers()[CONTENT_TYPE], "text/html");
+        assert_eq!(resp.headers()[CACHE_CONTROL], "max-age=60");
+        let etag = resp.headers()[ETAG].to_str().unwrap().to_owned();
+        assert_eq!(body_text(resp).await, "<h1>hello</h1>");
+
+        let resp = files
+            .serve(&get(&[("if-none-match", &etag)]), "/")
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), StatusCode::NOT_MODIFIED);
+
+        let resp = files
+            .serve(&get(&[("range", "bytes=8-")]), "/app.js")
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), StatusCode::PARTIAL_CONTENT);
+        assert_eq!(resp.headers()[CONTENT_RANGE], "bytes 8-13/14");
+        assert_eq!(body_text(resp).await, "log(1)");
+    }
+
+    #[tokio::test]
+    async fn does_not_escape_root() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().join("public");
+        std::fs::create_dir(&root).unwrap();
+        std::fs::write(dir.path().join("secret.txt"), "secret").unwrap();
+        let files = static_files(&root);
+
+        for path in ["/../secret.txt", "/%2e%2e/secret.txt", "/missing.txt"] {
+            let resp = files.serve(&get(&[]), path).await.unwrap();
+            assert_eq!(resp.status(), StatusCode::NOT_FOUND, "{path}");
+        }
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn does_not_follow_symlinks_out_of_root() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().join("public");
+        std::fs::create_dir_all(root.join("assets")).unwrap();
+        std::fs::write(dir.path().join("secret.txt"), "secret").unwrap();
+        std::fs::write(root.join("assets/app.js"), "console.log(1)").unwrap();
+        std::os::unix::fs::symlink(dir.path().join("secret.txt"), root.join("leak.txt")).unwrap();
+        std::os::unix::fs::symlink(dir.path(), root.join("parent")).unwrap();
+        std::os::unix::fs::symlink(root.join("assets/app.js"), root.join("app.js")).unwrap();
+        let files = static_files(&root);
+
+        for path in ["/leak.txt", "/parent/secret.txt"] {
+            let resp = files.serve(&get(&[]), path).await.unwrap();
+            assert_eq!(resp.status(), StatusCode::NOT_FOUND, "{path}");
+        }
+        // Symlinks within the root are still served
+        let resp = files.serve(&get(&[]), "/app.js").await.unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert_eq!(body_text(resp).await, "console.log(1)");
+    }
+}
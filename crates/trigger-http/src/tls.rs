@@ -6,6 +6,9 @@ use std::{
 };
 use tokio_rustls::{rustls, TlsAcceptor};
 
+/// The application protocols offered via ALPN, in order of preference.
+pub(crate) const ALPN_PROTOCOLS: &[&[u8]] = &[b"h2", b"http/1.1"];
+
 /// TLS configuration for the server.
 #[derive(Clone)]
 pub struct TlsConfig {
@@ -21,25 +24,26 @@ impl TlsConfig {
         let certs = load_certs(&self.cert_path)?;
         let mut keys = load_keys(&self.key_path)?;
 
-        let cfg = rustls::ServerConfig::builder()
+        let mut cfg = rustls::ServerConfig::builder()
             .with_safe_defaults()
             .with_no_client_auth()
             .with_single_cert(certs, keys.remove(0))
             .map_err(|e| anyhow::anyhow!("{}", e))?;
+        cfg.alpn_protocols = ALPN_PROTOCOLS.iter().map(|p| p.to_vec()).collect();
 
         Ok(Arc::new(cfg).into())
     }
 }
 
 // Loads public certificate from file.
-fn load_certs(path: impl AsRef<Path>) -> io::Result<Vec<rustls::Certificate>> {
+pub(crate) fn load_certs(path: impl AsRef<Path>) -> io::Result<Vec<rustls::Certificate>> {
     certs(&mut io::BufReader::new(fs::File::open(path)?))
         .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "invalid cert"))
         .map(|mut certs| certs.drain(..).map(rustls::Certificate).collect())
 }
 
 // Loads private key from file.
-fn load_keys(path: impl AsRef<Path>) -> io::Result<Vec<rustls::PrivateKey>> {
+pub(crate) fn load_keys(path: impl AsRef<Path>) -> io::Result<Vec<rustls::PrivateKey>> {
     pkcs8_private_keys(&mut io::BufReader::new(fs::File::open(path)?))
         .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "invalid key"))
         .map(|mut keys| keys.drain(..).map(rustls::PrivateKey).collect())
@@ -0,0 +1,286 @@
+//! The status of the HTTP trigger's TLS: the certificates it serves, when
+//! they expire, and how many handshakes have failed from each client host.
+//! It is reported as the `tls` section of the trigger status served with
+//! `--status-listen`, and at `/status/tls`, so that certificate rotation
+//! problems are visible before they cause outages.
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    net::SocketAddr,
+    path::PathBuf,
+    sync::{Arc, Mutex},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::{anyhow, Context, Result};
+use chrono::{DateTime, SecondsFormat, Utc};
+use serde::Serialize;
+use spin_common::ui::quoted_path;
+use tokio_rustls::rustls;
+
+use crate::{
+    acme::AcmeState,
+    listener::UNKNOWN_PEER_ADDR,
+    tls::{load_certs, TlsConfig},
+};
+
+/// Certificates which expire within this many days are warned about.
+const EXPIRY_WARNING_DAYS: i64 = 30;
+
+/// The most client hosts whose handshake failures are counted separately.
+/// Failures from any more are counted together, so that a scan of many
+/// hosts can't grow the counts without bound.
+const MAX_FAILURE_HOSTS: usize = 1024;
+
+/// Failures from hosts beyond [`MAX_FAILURE_HOSTS`] are counted under this.
+const OTHER_HOSTS: &str = "other";
+
+/// The TLS status of a trigger, shared between its listener and the status
+/// report.
+pub(crate) struct TlsStatus {
+    certificates: Certificates,
+    handshake_failures: Mutex<HashMap<String, u64>>,
+}
+
+// Where the served certificates come from.
+enum Certificates {
+    // A certificate chain loaded from a file at startup.
+    File {
+        path: PathBuf,
+        chain: Vec<rustls::Certificate>,
+    },
+    // Certificates obtained with ACME, which change when they are renewed.
+    Acme(Arc<AcmeState>),
+}
+
+/// A report of the TLS status.
+#[derive(Debug, Serialize)]
+pub(crate) struct TlsReport {
+    pub certificates: Vec<CertificateInfo>,
+    /// Problems to fix before they cause outages, such as certificates
+    /// which expire soon
+    pub warnings: Vec<String>,
+    /// Failed handshakes by client host
+    pub handshake_failures: BTreeMap<String, u64>,
+}
+
+/// A certificate in a served chain.
+#[derive(Debug, Serialize)]
+pub(crate) struct CertificateInfo {
+    /// The file the certificate was loaded from, or `acme`
+    pub source: String,
+    pub subject: String,
+    pub issuer: String,
+    pub dns_names: Vec<String>,
+    pub not_before: String,
+    pub not_after: String,
+    /// Negative once the certificate has expired
+    pub expires_in_days: i64,
+}
+
+impl TlsStatus {
+    /// The status of a certificate chain loaded from a file.
+    pub fn from_file(config: &TlsConfig) -> Result<Self> {
+        let chain = load_certs(&config.cert_path).with_context(|| {
+            format!(
+                "Failed to read TLS certificate {}",
+                quoted_path(&config.cert_path)
+            )
+        })?;
+        Ok(Self::new(Certificates::File {
+            path: config.cert_path.clone(),
+            chain,
+        }))
+    }
+
+    /// The status of certificates obtained with ACME.
+    pub fn from_acme(state: Arc<AcmeState>) -> Self {
+        Self::new(Certificates::Acme(state))
+    }
+
+    fn new(certificates: Certificates) -> Self {
+        Self {
+            certificates,
+            handshake_failures: Default::default(),
+        }
+    }
+
+    /// Counts a failed handshake from a client.
+    pub fn record_handshake_failure(&self, addr: SocketAddr) {
+        let host = if addr == UNKNOWN_PEER_ADDR {
+            // Unix domain socket connections have no peer address
+            "local".to_owned()
+        } else {
+            addr.ip().to_string()
+        };
+        let mut failures = self.handshake_failures.lock().unwrap();
+        let key = if failures.contains_key(&host) || failures.len() < MAX_FAILURE_HOSTS {
+            host
+        } else {
+            OTHER_HOSTS.to_owned()
+        };
+        *failures.entry(key).or_default() += 1;
+    }
+
+    /// Reports the status.
+    pub fn report(&self) -> TlsReport {
+        self.report_at(SystemTime::now())
+    }
+
+    fn report_at(&self, now: SystemTime) -> TlsReport {
+        let mut certificates = vec![];
+        let mut warnings = vec![];
+        let (source, chain) = match &self.certificates {
+            Certificates::File { path, chain } => {
+                // Certificates are loaded once, so a rotated file isn't
+                // served until the trigger restarts
+                if load_certs(path).is_ok_and(|current| current != *chain) {
+                    warnings.push(format!(
+                        "{} has changed since it was loaded: restart to serve the new certificate",
+                        quoted_path(path)
+                    ));
+                }
+                (path.display().to_string(), chain.clone())
+            }
+            Certificates::Acme(state) => {
+                let chain = state.certificate_chain();
+                if chain.is_empty() {
+                    warnings.push("No ACME certificate has been obtained yet".to_owned());
+                }
+                ("acme".to_owned(), chain)
+            }
+        };
+        for cert in &chain {
+            match certificate_info(&source, cert, now) {
+                Ok(info) => {
+                    if info.expires_in_days < 0 {
+                        warnings.push(format!(
+                            "The certificate for {} from {source} expired on {}",
+                            info.subject, info.not_after
+                        ));
+                    } else if info.expires_in_days < EXPIRY_WARNING_DAYS {
+                        warnings.push(format!(
+                            "The certificate for {} from {source} expires in {} days, on {}",
+                            info.subject, info.expires_in_days, info.not_after
+                        ));
+                    }
+                    certificates.push(info);
+                }
+                Err(err) => warnings.push(format!("Invalid certificate from {source}: {err}")),
+            }
+        }
+        let handshake_failures = self
+            .handshake_failures
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(host, count)| (host.clone(), *count))
+            .collect();
+        TlsReport {
+            certificates,
+            warnings,
+            handshake_failures,
+        }
+    }
+}
+
+fn certificate_info(
+    source: &str,
+    cert: &rustls::Certificate,
+    now: SystemTime,
+) -> Result<CertificateInfo> {
+    let (_, cert) = x509_parser::parse_x509_certificate(&cert.0)
+        .map_err(|e| anyhow!("invalid certificate: {e}"))?;
+    let validity = cert.validity();
+    let not_after = validity.not_after.timestamp();
+    let now = now.duration_since(UNIX_EPOCH)?.as_secs() as i64;
+    let dns_names = match cert.subject_alternative_name() {
+        Ok(Some(san)) => san
+            .value
+            .general_names
+            .iter()
+            .filter_map(|name| match name {
+                x509_parser::extensions::GeneralName::DNSName(name) => Some(name.to_string()),
+                _ => None,
+            })
+            .collect(),
+        _ => vec![],
+    };
+    Ok(CertificateInfo {
+        source: source.to_owned(),
+        subject: cert.subject().to_string(),
+        issuer: cert.issuer().to_string(),
+        dns_names,
+        not_before: rfc3339(validity.not_before.timestamp()),
+        not_after: rfc3339(not_after),
+        expires_in_days: (not_after - now).div_euclid(24 * 60 * 60),
+    })
+}
+
+fn rfc3339(timestamp: i64) -> String {
+    DateTime::<Utc>::from_timestamp(timestamp, 0)
+        .map(|time| time.to_rfc3339_opts(SecondsFormat::Secs, true))
+        .unwrap_or_else(|| timestamp.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    fn certificate(expires: (i32, u8, u8)) -> rustls::Certificate {
+        let mut params = rcgen::CertificateParams::new(vec!["example.com".to_owned()]);
+        params.not_before = rcgen::date_time_ymd(2024, 1, 1);
+        params.not_after = rcgen::date_time_ymd(expires.0, expires.1, expires.2);
+        let cert = rcgen::Certificate::from_params(params).unwrap();
+        rustls::Certificate(cert.serialize_der().unwrap())
+    }
+
+    fn status(chain: Vec<rustls::Certificate>) -> TlsStatus {
+        TlsStatus::new(Certificates::File {
+            path: "/nonexistent/cert.pem".into(),
+            chain,
+        })
+    }
+
+    #[test]
+    fn expiring_certificates_are_warned_about() {
+        // 2024-01-11
+        let now = UNIX_EPOCH + Duration::from_secs(1_704_931_200);
+
+        let report = status(vec![certificate((2024, 12, 31))]).report_at(now);
+        let cert = &report.certificates[0];
+        assert_eq!(cert.dns_names, ["example.com"]);
+        assert_eq!(cert.not_before, "2024-01-01T00:00:00Z");
+        assert_eq!(cert.expires_in_days, 355);
+        assert_eq!(cert.not_after, "2024-12-31T00:00:00Z");
+        assert!(report.warnings.is_empty(), "{:?}", report.warnings);
+
+        let report = status(vec![certificate((2024, 1, 21))]).report_at(now);
+        assert_eq!(report.certificates[0].expires_in_days, 10);
+        assert!(report.warnings[0].contains("expires in 10 days"));
+
+        let report = status(vec![certificate((2024, 1, 6))]).report_at(now);
+        assert!(report.warnings[0].contains("expired"));
+    }
+
+    #[test]
+    fn handshake_failures_are_counted_by_host() {
+        let status = status(vec![]);
+        status.record_handshake_failure("10.0.0.1:1234".parse().unwrap());
+        status.record_handshake_failure("10.0.0.1:5678".parse().unwrap());
+        status.record_handshake_failure("10.0.0.2:1234".parse().unwrap());
+        let failures = status.report().handshake_failures;
+        assert_eq!(failures["10.0.0.1"], 2);
+        assert_eq!(failures["10.0.0.2"], 1);
+
+        for i in 0..MAX_FAILURE_HOSTS {
+            let addr = SocketAddr::from(([10, 1, (i / 256) as u8, (i % 256) as u8], 1));
+            status.record_handshake_failure(addr);
+        }
+        let failures = status.report().handshake_failures;
+        assert_eq!(failures.len(), MAX_FAILURE_HOSTS + 1);
+        assert_eq!(failures[OTHER_HOSTS], 2);
+    }
+}
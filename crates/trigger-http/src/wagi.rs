@@ -48,7 +48,7 @@ impl HttpExecutor for WagiHttpExecutor {
 
         let (parts, body) = req.into_parts();
 
-        let body = body.collect().await?.to_bytes().to_vec();
+        let body = Vec::from(body.collect().await?.to_bytes());
         let len = body.len();
 
         // TODO
@@ -83,6 +83,7 @@ impl HttpExecutor for WagiHttpExecutor {
 
         let stdout = WritePipe::new_in_memory();
 
+        let _permit = engine.acquire_permit(component).await?;
         let mut store_builder = engine.store_builder(component, WasiVersion::Preview1)?;
         // Set up Wagi environment
         store_builder.args(argv.split(' '))?;
@@ -130,7 +131,7 @@ impl HttpExecutor for WagiHttpExecutor {
              but did not write to stdout. Check the `executor` in spin.toml."
         );
 
-        wagi::compose_response(&stdout)
+        wagi::compose_response(stdout.into())
     }
 }
 
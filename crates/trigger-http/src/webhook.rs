@@ -0,0 +1,319 @@
+//! Webhook signature verification enforced by the HTTP trigger before a
+//! component is invoked.
+
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use anyhow::{Context, Result};
+use hmac::{Hmac, Mac};
+use http::{header::CONTENT_LENGTH, HeaderMap, HeaderValue, StatusCode};
+use http_body_util::{combinators::BoxBody, BodyExt, LengthLimitError, Limited, StreamBody};
+use hyper::{
+    body::{Bytes, Frame},
+    Request, Response,
+};
+use sha2::Sha256;
+use spin_http::{
+    body,
+    config::{WebhookConfig, WebhookScheme},
+};
+use wasmtime_wasi_http::bindings::http::types::ErrorCode;
+
+use crate::Body;
+
+/// Set on verified requests to the scheme whose signature was checked.
+const WEBHOOK_HEADER: &str = "spin-auth-webhook";
+
+/// Verifies the signatures of webhook deliveries to a component.
+pub(crate) struct WebhookVerifier {
+    scheme: WebhookScheme,
+    secret: Vec<u8>,
+    tolerance_secs: u64,
+    max_body_size: usize,
+}
+
+impl WebhookVerifier {
+    /// Creates a verifier, resolving the secret with `resolve`.
+    pub fn new(
+        config: &WebhookConfig,
+        resolve: impl FnOnce(&str) -> Result<String>,
+    ) -> Result<Self> {
+        let secret = resolve(&config.secret).context("Unable to resolve webhook secret")?;
+        anyhow::ensure!(!secret.is_empty(), "webhook secret must not be empty");
+        Ok(Self {
+            scheme: config.scheme,
+            secret: secret.into_bytes(),
+            tolerance_secs: config.tolerance_secs,
+            max_body_size: config.max_body_size,
+        })
+    }
+
+    /// Reads the request body and checks its signature, returning the
+    /// request to pass on to the component, or the response to send if the
+    /// signature is missing or invalid, or the body is too large to check.
+    pub async fn verify(
+        &self,
+        req: Request<Body>,
+    ) -> Result<std::result::Result<Request<Body>, Response<Body>>> {
+        let (mut parts, body) = req.into_parts();
+        let declared_size = parts
+            .headers
+            .get(CONTENT_LENGTH)
+            .and_then(|value| value.to_str().ok()?.parse::<u64>().ok());
+        if declared_size.is_some_and(|size| size > self.max_body_size as u64) {
+            return Ok(Err(self.too_large()));
+        }
+        let collected = match Limited::new(body, self.max_body_size).collect().await {
+            Ok(collected) => collected,
+            Err(e) if e.is::<LengthLimitError>() => return Ok(Err(self.too_large())),
+            Err(e) => return Err(anyhow::anyhow!(e)),
+        };
+        let trailers = collected.trailers().cloned();
+        let payload = collected.to_bytes();
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        if let Err(reason) = self.check(&parts.headers, &payload, now) {
+            tracing::info!(
+                "Rejecting {} webhook delivery: {reason}",
+                self.scheme.as_str()
+            );
+            let mut resp = Response::new(body::empty());
+            *resp.status_mut() = StatusCode::UNAUTHORIZED;
+            return Ok(Err(resp));
+        }
+        parts.headers.insert(
+            WEBHOOK_HEADER,
+            HeaderValue::from_static(self.scheme.as_str()),
+        );
+        Ok(Ok(Request::from_parts(
+            parts,
+            full_with_trailers(payload, trailers),
+        )))
+    }
+
+    fn too_large(&self) -> Response<Body> {
+        tracing::info!(
+            "Rejecting {} webhook delivery larger than {} bytes",
+            self.scheme.as_str(),
+            self.max_body_size
+        );
+        let mut resp = Response::new(body::empty());
+        *resp.status_mut() = StatusCode::PAYLOAD_TOO_LARGE;
+        resp
+    }
+
+    /// Checks the signature of a delivery received at `now`, in seconds since
+    /// the Unix epoch.
+    fn check(&self, headers: &HeaderMap, payload: &Bytes, now: u64) -> Result<(), &'static str> {
+        match self.scheme {
+            WebhookScheme::Github => {
+                let signature = header(headers, "x-hub-signature-256")?
+                    .strip_prefix("sha256=")
+                    .ok_or("malformed signature")?;
+                self.verify_hex(&[payload], signature)
+            }
+            WebhookScheme::Stripe => {
+                let header = header(headers, "stripe-signature")?;
+                let mut timestamp = None;
+                let mut signatures = vec![];
+                for (key, value) in header.split(',').filter_map(|kv| kv.split_once('=')) {
+                    match key.trim() {
+                        "t" => timestamp = Some(value.trim()),
+                        "v1" => signatures.push(value.trim()),
+                        _ => {}
+                    }
+                }
+                let timestamp = timestamp.ok_or("missing timestamp")?;
+                self.check_timestamp(timestamp, now)?;
+                let signed: [&[u8]; 3] = [timestamp.as_bytes(), b".", payload];
+                signatures
+                    .into_iter()
+                    .find(|signature| self.verify_hex(&signed, signature).is_ok())
+                    .map(|_| ())
+                    .ok_or("signature mismatch")
+            }
+            WebhookScheme::Slack => {
+                let timestamp = header(headers, "x-slack-request-timestamp")?;
+                self.check_timestamp(timestamp, now)?;
+                let signature = header(headers, "x-slack-signature")?
+                    .strip_prefix("v0=")
+                    .ok_or("malformed signature")?;
+                self.verify_hex(&[b"v0:", timestamp.as_bytes(), b":", payload], signature)
+            }
+        }
+    }
+
+    fn check_timestamp(&self, timestamp: &str, now: u64) -> Result<(), &'static str> {
+        let timestamp: u64 = timestamp.parse().map_err(|_| "malformed timestamp")?;
+        if now.abs_diff(timestamp) > self.tolerance_secs {
+            return Err("timestamp outside tolerance");
+        }
+        Ok(())
+    }
+
+    /// Checks a hex HMAC-SHA256 signature of the concatenated `parts`, in
+    /// constant time.
+    fn verify_hex(&self, parts: &[&[u8]], signature: &str) -> Result<(), &'static str> {
+        let signature = hex::decode(signature).map_err(|_| "malformed signature")?;
+        let mut mac = Hmac::<Sha256>::new_from_slice(&self.secret).expect("HMAC takes any key");
+        for part in parts {
+            mac.update(part);
+        }
+        mac.verify_slice(&signature)
+            .map_err(|_| "signature mismatch")
+    }
+}
+
+fn header<'a>(headers: &'a HeaderMap, name: &str) -> Result<&'a str, &'static str> {
+    headers
+        .get(name)
+        .and_then(|value| value.to_str().ok())
+        .ok_or("missing signature header")
+}
+
+// Rebuilds a collected body, keeping any trailers, e.g. for gRPC.
+fn full_with_trailers(data: Bytes, trailers: Option<HeaderMap>) -> Body {
+    let Some(trailers) = trailers else {
+        return body::full(data);
+    };
+    let frames = [
+        Ok::<_, ErrorCode>(Frame::data(data)),
+        Ok(Frame::trailers(trailers)),
+    ];
+    BoxBody::new(StreamBody::new(futures::stream::iter(frames)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NOW: u64 = 1_700_000_000;
+
+    fn verifier(scheme: WebhookScheme) -> WebhookVerifier {
+        WebhookVerifier {
+            scheme,
+            secret: b"s3cret".to_vec(),
+            tolerance_secs: 300,
+            max_body_size: 64,
+        }
+    }
+
+    fn sign(parts: &[&[u8]]) -> String {
+        let mut mac = Hmac::<Sha256>::new_from_slice(b"s3cret").unwrap();
+        for part in parts {
+            mac.update(part);
+        }
+        hex::encode(mac.finalize().into_bytes())
+    }
+
+    fn headers(pairs: &[(&'static str, String)]) -> HeaderMap {
+        pairs
+            .iter()
+            .map(|(name, value)| {
+                (
+                    http::HeaderName::from_static(name),
+                    HeaderValue::from_str(value).unwrap(),
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn github_signatures_are_checked() {
+        let verifier = verifier(WebhookScheme::Github);
+        let payload = Bytes::from_static(b"{\"action\":\"opened\"}");
+        let valid = headers(&[(
+            "x-hub-signature-256",
+            format!("sha256={}", sign(&[&payload])),
+        )]);
+        assert_eq!(verifier.check(&valid, &payload, NOW), Ok(()));
+
+        let tampered = Bytes::from_static(b"{\"action\":\"closed\"}");
+        assert!(verifier.check(&valid, &tampered, NOW).is_err());
+        assert!(verifier.check(&HeaderMap::new(), &payload, NOW).is_err());
+    }
+
+    #[tokio::test]
+    async fn verified_requests_keep_their_trailers() {
+        let verifier = verifier(WebhookScheme::Github);
+        let payload = Bytes::from_static(b"{\"action\":\"opened\"}");
+        let mut trailers = HeaderMap::new();
+        trailers.insert("x-checksum", HeaderValue::from_static("abc"));
+        let req = Request::post("/")
+            .header(
+                "x-hub-signature-256",
+                format!("sha256={}", sign(&[&payload])),
+            )
+            .body(full_with_trailers(payload.clone(), Some(trailers.clone())))
+            .unwrap();
+
+        let req = verifier.verify(req).await.unwrap().unwrap();
+        assert_eq!(req.headers()[WEBHOOK_HEADER], "github");
+        let collected = req.into_body().collect().await.unwrap();
+        assert_eq!(collected.trailers(), Some(&trailers));
+        assert_eq!(collected.to_bytes(), payload);
+    }
+
+    #[tokio::test]
+    async fn oversize_bodies_are_rejected_before_verification() {
+        let verifier = verifier(WebhookScheme::Github);
+        let payload = Bytes::from(vec![b'a'; 65]);
+        let signature = format!("sha256={}", sign(&[&payload]));
+
+        let declared = Request::post("/")
+            .header("x-hub-signature-256", &signature)
+            .header(CONTENT_LENGTH, payload.len())
+            .body(body::full(payload.clone()))
+            .unwrap();
+        let resp = verifier.verify(declared).await.unwrap().unwrap_err();
+        assert_eq!(resp.status(), StatusCode::PAYLOAD_TOO_LARGE);
+
+        // Without a Content-Length, the body is cut off at the limit
+        let undeclared = Request::post("/")
+            .header("x-hub-signature-256", &signature)
+            .body(full_with_trailers(payload, None))
+            .unwrap();
+        let resp = verifier.verify(undeclared).await.unwrap().unwrap_err();
+        assert_eq!(resp.status(), StatusCode::PAYLOAD_TOO_LARGE);
+    }
+
+    #[test]
+    fn stripe_signatures_and_timestamps_are_checked() {
+        let verifier = verifier(WebhookScheme::Stripe);
+        let payload = Bytes::from_static(b"{\"type\":\"charge.succeeded\"}");
+        let t = NOW.to_string();
+        let signature = sign(&[t.as_bytes(), b".", &payload]);
+        let valid = headers(&[(
+            "stripe-signature",
+            format!("t={t},v1=00,v1={signature},v0=ignored"),
+        )]);
+        assert_eq!(verifier.check(&valid, &payload, NOW), Ok(()));
+        assert_eq!(
+            verifier.check(&valid, &payload, NOW + 301),
+            Err("timestamp outside tolerance")
+        );
+
+        let unsigned = headers(&[("stripe-signature", format!("t={t},v1=00"))]);
+        assert!(verifier.check(&unsigned, &payload, NOW).is_err());
+    }
+
+    #[test]
+    fn slack_signatures_and_timestamps_are_checked() {
+        let verifier = verifier(WebhookScheme::Slack);
+        let payload = Bytes::from_static(b"token=abc&command=%2Fweather");
+        let ts = (NOW - 10).to_string();
+        let signature = sign(&[b"v0:", ts.as_bytes(), b":", &payload]);
+        let valid = headers(&[
+            ("x-slack-request-timestamp", ts.clone()),
+            ("x-slack-signature", format!("v0={signature}")),
+        ]);
+        assert_eq!(verifier.check(&valid, &payload, NOW), Ok(()));
+
+        let replayed = headers(&[
+            ("x-slack-request-timestamp", (NOW - 1000).to_string()),
+            ("x-slack-signature", format!("v0={signature}")),
+        ]);
+        assert!(verifier.check(&replayed, &payload, NOW).is_err());
+    }
+}
@@ -0,0 +1,71 @@
+//! Dead-lettering records to a Kafka topic.
+
+use std::time::Duration;
+
+use anyhow::{anyhow, Result};
+use rdkafka::message::{Header, OwnedHeaders};
+use rdkafka::producer::{FutureProducer, FutureRecord};
+use rdkafka::ClientConfig;
+use spin_core::async_trait;
+use spin_trigger::retry::{DeadLetter, DeadLetterQueue};
+
+/// How long to wait for a dead-lettered record to be queued for sending.
+const QUEUE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Produces dead-lettered records to a topic on the trigger's brokers.
+///
+/// Records keep their key and headers, with `dead-letter-*` headers
+/// describing the failure.
+pub(crate) struct TopicDeadLetters {
+    producer: FutureProducer,
+    topic: String,
+}
+
+impl TopicDeadLetters {
+    pub fn new(config: &ClientConfig, topic: &str) -> Result<Self> {
+        Ok(Self {
+            producer: config.create()?,
+            topic: topic.to_owned(),
+        })
+    }
+}
+
+#[async_trait]
+impl DeadLetterQueue for TopicDeadLetters {
+    async fn send(&self, letter: &DeadLetter) -> Result<()> {
+        let attempts = letter.attempts.to_string();
+        let headers = letter
+            .metadata
+            .iter()
+            .filter(|(name, _)| name.as_str() != "key")
+            .map(|(name, value)| (name.as_str(), value.as_str()))
+            .chain([
+                ("dead-letter-component", letter.component.as_str()),
+                ("dead-letter-source", letter.source.as_str()),
+                ("dead-letter-error", letter.error.as_str()),
+                ("dead-letter-attempts", attempts.as_str()),
+            ])
+            .fold(OwnedHeaders::new(), |headers, (key, value)| {
+                headers.insert(Header {
+                    key,
+                    value: Some(value),
+                })
+            });
+        let mut record = FutureRecord::to(&self.topic)
+            .payload(&letter.payload)
+            .headers(headers);
+        if let Some(key) = letter.metadata.get("key") {
+            record = record.key(key);
+        }
+        self.producer
+            .send(record, QUEUE_TIMEOUT)
+            .await
+            .map(|_| ())
+            .map_err(|(err, _)| {
+                anyhow!(
+                    "failed to produce to dead-letter topic {}: {err}",
+                    self.topic
+                )
+            })
+    }
+}
@@ -0,0 +1,489 @@
+//! Implementation for the Spin Kafka trigger.
+
+mod dead_letter;
+mod runtime_config;
+mod spin;
+
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::{anyhow, bail, Context, Result};
+use rdkafka::consumer::{CommitMode, Consumer, StreamConsumer};
+use rdkafka::message::{Headers, Message as _, OwnedMessage};
+use rdkafka::{ClientConfig, Offset, TopicPartitionList};
+use serde::{de::IgnoredAny, Deserialize, Serialize};
+use spin_core::{async_trait, InstancePre};
+use spin_trigger::retry::{DeadLetter, DeadLetterQueue, MessageRetry, RetryConfig};
+use spin_trigger::{cli::NoArgs, TriggerAppEngine, TriggerExecutor};
+use spin_world::v2::kafka_types::{Header, Message};
+
+use crate::dead_letter::TopicDeadLetters;
+use crate::spin::SpinKafkaExecutor;
+
+pub use runtime_config::{
+    KafkaTriggerRuntimeConfig, SaslConfig, SaslMechanism, SecurityProtocol, TlsConfig,
+};
+
+pub(crate) type RuntimeData = ();
+pub(crate) type Store = spin_core::Store<RuntimeData>;
+
+/// How long to wait before records are delivered again after a handler fails.
+const REDELIVERY_DELAY: Duration = Duration::from_secs(1);
+/// How long to wait for a consumer to seek back to failed records.
+const SEEK_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// The Spin Kafka trigger.
+#[derive(Clone)]
+pub struct KafkaTrigger {
+    engine: Arc<TriggerAppEngine<Self>>,
+    runtime_config: KafkaTriggerRuntimeConfig,
+    subscriptions: Vec<Subscription>,
+}
+
+/// Kafka trigger configuration.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct KafkaTriggerConfig {
+    /// Component ID to invoke
+    pub component: String,
+    /// Topics to subscribe to
+    pub topics: Vec<String>,
+    /// Optional override of the trigger's brokers
+    pub brokers: Option<String>,
+    /// Optional override of the consumer group
+    pub group_id: Option<String>,
+    /// The maximum number of records delivered in one call to the handler
+    #[serde(default = "default_batch_size")]
+    pub batch_size: usize,
+    /// How long, in milliseconds, to wait for a batch to fill
+    #[serde(default = "default_batch_timeout_ms")]
+    pub batch_timeout_ms: u64,
+    /// Retry policy for records the component fails to handle, instead of
+    /// delivering them again until they succeed; a `queue` dead-letter
+    /// destination is a topic on the same brokers
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub retry: Option<RetryConfig>,
+    /// Trigger executor (currently unused)
+    #[serde(default, skip_serializing)]
+    pub executor: IgnoredAny,
+}
+
+fn default_batch_size() -> usize {
+    1
+}
+
+fn default_batch_timeout_ms() -> u64 {
+    100
+}
+
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+struct TriggerMetadata {
+    brokers: String,
+    group_id: Option<String>,
+}
+
+/// A component's subscription, with its templates resolved.
+#[derive(Clone, Debug)]
+struct Subscription {
+    component: String,
+    brokers: String,
+    group_id: String,
+    topics: Vec<String>,
+    batch_size: usize,
+    batch_timeout: Duration,
+    retry: Option<MessageRetry>,
+}
+
+#[async_trait]
+impl TriggerExecutor for KafkaTrigger {
+    const TRIGGER_TYPE: &'static str = "kafka";
+    type RuntimeData = RuntimeData;
+    type TriggerConfig = KafkaTriggerConfig;
+    type RunConfig = NoArgs;
+    type InstancePre = InstancePre<RuntimeData>;
+
+    async fn new(engine: TriggerAppEngine<Self>) -> Result<Self> {
+        let metadata = engine
+            .trigger_metadata::<TriggerMetadata>()?
+            .unwrap_or_default();
+        let runtime_config = engine.trigger_runtime_opts::<KafkaTriggerRuntimeConfig>()?;
+        runtime_config.validate()?;
+
+        let resolve = |value: &str| -> Result<String> {
+            let expr = spin_expressions::Template::new(value)?;
+            Ok(engine.resolve_template(&expr)?)
+        };
+
+        let mut subscriptions = vec![];
+        for (_, config) in engine.trigger_configs() {
+            let component = &config.component;
+            if config.topics.is_empty() {
+                bail!("Kafka trigger for component '{component}' must subscribe to at least one topic");
+            }
+            if config.batch_size == 0 {
+                bail!("Kafka trigger for component '{component}': `batch_size` must be at least 1");
+            }
+            let brokers = resolve(config.brokers.as_deref().unwrap_or(&metadata.brokers))?;
+            if brokers.is_empty() {
+                bail!("Kafka trigger for component '{component}' has no brokers");
+            }
+            let group_id = match config.group_id.as_ref().or(metadata.group_id.as_ref()) {
+                Some(group_id) => resolve(group_id)?,
+                None => default_group_id(&engine.app_name, component),
+            };
+            let topics = config
+                .topics
+                .iter()
+                .map(|topic| resolve(topic))
+                .collect::<Result<_>>()?;
+            let retry = match &config.retry {
+                Some(retry) => Some(
+                    MessageRetry::new(retry, engine.runtime_config(), |topic| {
+                        let mut producer_config = ClientConfig::new();
+                        producer_config.set("bootstrap.servers", &brokers);
+                        runtime_config.apply(&mut producer_config);
+                        Ok(Arc::new(TopicDeadLetters::new(&producer_config, topic)?)
+                            as Arc<dyn DeadLetterQueue>)
+                    })
+                    .await
+                    .with_context(|| {
+                        format!(
+                            "Invalid retry policy for Kafka trigger for component '{component}'"
+                        )
+                    })?,
+                ),
+                None => None,
+            };
+            subscriptions.push(Subscription {
+                component: component.clone(),
+                brokers,
+                group_id,
+                topics,
+                batch_size: config.batch_size,
+                batch_timeout: Duration::from_millis(config.batch_timeout_ms),
+                retry,
+            });
+        }
+
+        Ok(Self {
+            engine: Arc::new(engine),
+            runtime_config,
+            subscriptions,
+        })
+    }
+
+    /// Run the Kafka trigger indefinitely.
+    async fn run(self, _config: Self::RunConfig) -> Result<()> {
+        if self.subscriptions.is_empty() {
+            return Ok(());
+        }
+        let tasks: Vec<_> = self
+            .subscriptions
+            .clone()
+            .into_iter()
+            .map(|subscription| {
+                let trigger = self.clone();
+                tokio::spawn(async move { trigger.run_consumer(subscription).await })
+            })
+            .collect();
+
+        // wait for the first handle to be returned and drop the rest
+        let (result, _, rest) = futures::future::select_all(tasks).await;
+
+        drop(rest);
+
+        result?
+    }
+}
+
+impl KafkaTrigger {
+    // Handle a batch of records.
+    async fn handle(&self, component_id: &str, batch: &[OwnedMessage]) -> Result<()> {
+        tracing::info!(
+            "Received {} Kafka record(s) for component {component_id}",
+            batch.len()
+        );
+        let messages = batch.iter().map(to_message).collect();
+        let result = SpinKafkaExecutor
+            .execute(&self.engine, component_id, messages)
+            .await;
+        self.engine.status().record(component_id, result)
+    }
+
+    async fn run_consumer(&self, subscription: Subscription) -> Result<()> {
+        let Subscription {
+            component,
+            brokers,
+            group_id,
+            topics,
+            ..
+        } = &subscription;
+
+        tracing::info!("Connecting to Kafka brokers at {brokers}");
+        let mut config = ClientConfig::new();
+        config
+            .set("bootstrap.servers", brokers)
+            .set("group.id", group_id)
+            .set("enable.auto.commit", "false")
+            .set("auto.offset.reset", "earliest");
+        self.runtime_config.apply(&mut config);
+        let consumer: StreamConsumer = config
+            .create()
+            .with_context(|| anyhow!("Kafka trigger failed to create a consumer for {brokers}"))?;
+
+        let topic_refs: Vec<&str> = topics.iter().map(String::as_str).collect();
+        consumer
+            .subscribe(&topic_refs)
+            .with_context(|| anyhow!("Kafka trigger failed to subscribe to {topics:?}"))?;
+        println!(
+            "Active Kafka topics on {brokers} for group {group_id}: [{}]: {component}",
+            topics.join(",")
+        );
+        for topic in topics {
+            let subscription = format!("topic {brokers}/{topic} (group {group_id})");
+            self.engine
+                .status()
+                .add_subscription(component, subscription);
+        }
+
+        loop {
+            let batch = match next_batch(&consumer, &subscription).await {
+                Ok(batch) => batch,
+                Err(err) => {
+                    tracing::warn!("Error receiving Kafka records: {err}");
+                    continue;
+                }
+            };
+            let offsets = BatchOffsets::new(&batch);
+            let result = match &subscription.retry {
+                Some(retry) => {
+                    let letters = batch
+                        .iter()
+                        .map(|record| dead_letter(component, record))
+                        .collect();
+                    retry
+                        .deliver(letters, || self.handle(component, &batch))
+                        .await
+                        .map(|_| ())
+                }
+                None => self.handle(component, &batch).await,
+            };
+            match result {
+                Ok(()) => {
+                    if let Err(err) = consumer.commit(&offsets.commit_list()?, CommitMode::Async) {
+                        tracing::warn!("Error committing Kafka offsets: {err}");
+                    }
+                }
+                Err(err) => {
+                    tracing::warn!("Error handling Kafka records: {err}");
+                    // Rewind so the records are delivered again
+                    for ((topic, partition), (first, _)) in &offsets.0 {
+                        consumer
+                            .seek(topic, *partition, Offset::Offset(*first), SEEK_TIMEOUT)
+                            .with_context(|| {
+                                anyhow!("Kafka trigger failed to rewind {topic}/{partition}")
+                            })?;
+                    }
+                    tokio::time::sleep(REDELIVERY_DELAY).await;
+                }
+            }
+        }
+    }
+}
+
+/// Waits for at least one record, then collects more until the batch is full
+/// or its timeout expires.
+async fn next_batch(
+    consumer: &StreamConsumer,
+    subscription: &Subscription,
+) -> Result<Vec<OwnedMessage>> {
+    let mut batch = vec![consumer.recv().await?.detach()];
+    let deadline = tokio::time::Instant::now() + subscription.batch_timeout;
+    while batch.len() < subscription.batch_size {
+        match tokio::time::timeout_at(deadline, consumer.recv()).await {
+            Ok(Ok(msg)) => batch.push(msg.detach()),
+            Ok(Err(err)) => {
+                // Deliver what has already been received
+                tracing::warn!("Error receiving Kafka records: {err}");
+                break;
+            }
+            Err(_) => break,
+        }
+    }
+    Ok(batch)
+}
+
+fn default_group_id(app_name: &str, component_id: &str) -> String {
+    format!("spin-{app_name}-{component_id}")
+}
+
+/// The first and last offsets of a batch in each partition.
+struct BatchOffsets(BTreeMap<(String, i32), (i64, i64)>);
+
+impl BatchOffsets {
+    fn new(batch: &[OwnedMessage]) -> Self {
+        let mut offsets = BTreeMap::new();
+        for msg in batch {
+            let offset = msg.offset();
+            offsets
+                .entry((msg.topic().to_owned(), msg.partition()))
+                .and_modify(|(first, last): &mut (i64, i64)| {
+                    *first = offset.min(*first);
+                    *last = offset.max(*last);
+                })
+                .or_insert((offset, offset));
+        }
+        Self(offsets)
+    }
+
+    /// The offsets to commit once the batch has been handled, which are the
+    /// offsets of the next records to consume.
+    fn commit_list(&self) -> Result<TopicPartitionList> {
+        let mut list = TopicPartitionList::new();
+        for ((topic, partition), (_, last)) in &self.0 {
+            list.add_partition_offset(topic, *partition, Offset::Offset(last + 1))?;
+        }
+        Ok(list)
+    }
+}
+
+/// The dead letter for a record, which keeps its key and headers as metadata.
+fn dead_letter(component: &str, msg: &OwnedMessage) -> DeadLetter {
+    let mut letter = DeadLetter::new(component, msg.topic(), msg.payload().unwrap_or_default())
+        .with_metadata("partition", msg.partition().to_string())
+        .with_metadata("offset", msg.offset().to_string());
+    if let Some(key) = msg.key() {
+        letter = letter.with_metadata("key", String::from_utf8_lossy(key));
+    }
+    for header in msg.headers().iter().flat_map(|headers| headers.iter()) {
+        let value = header
+            .value
+            .map(String::from_utf8_lossy)
+            .unwrap_or_default();
+        letter = letter.with_metadata(header.key, value);
+    }
+    letter
+}
+
+fn to_message(msg: &OwnedMessage) -> Message {
+    let headers = msg
+        .headers()
+        .map(|headers| {
+            headers
+                .iter()
+                .map(|header| Header {
+                    key: header.key.to_owned(),
+                    value: header.value.map(<[u8]>::to_vec),
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+    Message {
+        topic: msg.topic().to_owned(),
+        partition: msg.partition(),
+        offset: msg.offset(),
+        key: msg.key().map(<[u8]>::to_vec),
+        payload: msg.payload().map(<[u8]>::to_vec),
+        headers,
+        timestamp: msg.timestamp().to_millis(),
+    }
+}
+
+/// The Kafka executor trait.
+/// All Kafka executors must implement this trait.
+#[async_trait]
+pub(crate) trait KafkaExecutor: Clone + Send + Sync + 'static {
+    async fn execute(
+        &self,
+        engine: &TriggerAppEngine<KafkaTrigger>,
+        component_id: &str,
+        messages: Vec<Message>,
+    ) -> Result<()>;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rdkafka::message::{Header as KafkaHeader, OwnedHeaders};
+    use rdkafka::Timestamp;
+
+    fn record(topic: &str, partition: i32, offset: i64) -> OwnedMessage {
+        OwnedMessage::new(
+            Some(b"payload".to_vec()),
+            Some(b"key".to_vec()),
+            topic.into(),
+            Timestamp::CreateTime(1700000000000),
+            partition,
+            offset,
+            Some(OwnedHeaders::new().insert(KafkaHeader {
+                key: "trace",
+                value: Some("abc"),
+            })),
+        )
+    }
+
+    #[test]
+    fn batch_commits_next_offset_per_partition() {
+        let batch = [
+            record("orders", 0, 7),
+            record("orders", 1, 3),
+            record("orders", 0, 8),
+            record("refunds", 0, 1),
+        ];
+        let offsets = BatchOffsets::new(&batch);
+        assert_eq!(offsets.0[&("orders".into(), 0)], (7, 8));
+
+        let list = offsets.commit_list().unwrap();
+        let committed: Vec<_> = list
+            .elements()
+            .iter()
+            .map(|e| (e.topic().to_owned(), e.partition(), e.offset()))
+            .collect();
+        assert_eq!(
+            committed,
+            [
+                ("orders".to_owned(), 0, Offset::Offset(9)),
+                ("orders".to_owned(), 1, Offset::Offset(4)),
+                ("refunds".to_owned(), 0, Offset::Offset(2)),
+            ]
+        );
+    }
+
+    #[test]
+    fn records_are_converted_to_messages() {
+        let message = to_message(&record("orders", 2, 42));
+        assert_eq!(message.topic, "orders");
+        assert_eq!(message.partition, 2);
+        assert_eq!(message.offset, 42);
+        assert_eq!(message.key.as_deref(), Some(&b"key"[..]));
+        assert_eq!(message.payload.as_deref(), Some(&b"payload"[..]));
+        assert_eq!(message.headers[0].key, "trace");
+        assert_eq!(message.headers[0].value.as_deref(), Some(&b"abc"[..]));
+        assert_eq!(message.timestamp, Some(1700000000000));
+    }
+
+    #[test]
+    fn records_are_dead_lettered_with_key_and_headers() {
+        let letter = dead_letter("orders", &record("orders", 2, 42));
+        assert_eq!(letter.source, "orders");
+        assert_eq!(letter.payload, b"payload");
+        assert_eq!(letter.metadata["key"], "key");
+        assert_eq!(letter.metadata["trace"], "abc");
+        assert_eq!(letter.metadata["offset"], "42");
+    }
+
+    #[test]
+    fn trigger_config_defaults() {
+        let config: KafkaTriggerConfig = toml::toml! {
+            component = "orders"
+            topics = ["orders"]
+        }
+        .try_into()
+        .unwrap();
+        assert_eq!(config.batch_size, 1);
+        assert_eq!(config.batch_timeout_ms, 100);
+        assert_eq!(default_group_id("shop", "orders"), "spin-shop-orders");
+    }
+}
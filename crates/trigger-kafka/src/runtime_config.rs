@@ -0,0 +1,202 @@
+use std::{collections::BTreeMap, path::PathBuf};
+
+use anyhow::{bail, Result};
+use rdkafka::ClientConfig;
+use serde::Deserialize;
+
+/// Kafka client options, read from the `[kafka_trigger]` runtime config
+/// table. These apply to every consumer the trigger creates.
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct KafkaTriggerRuntimeConfig {
+    /// The protocol used to communicate with brokers.
+    pub security_protocol: SecurityProtocol,
+    /// SASL authentication, required by the `sasl_*` protocols.
+    pub sasl: Option<SaslConfig>,
+    /// TLS options, used by the `ssl` and `sasl_ssl` protocols.
+    pub tls: Option<TlsConfig>,
+    /// Additional librdkafka consumer properties, applied last.
+    pub properties: BTreeMap<String, String>,
+}
+
+/// The protocol used to communicate with brokers.
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum SecurityProtocol {
+    #[default]
+    Plaintext,
+    Ssl,
+    SaslPlaintext,
+    SaslSsl,
+}
+
+impl SecurityProtocol {
+    fn as_str(self) -> &'static str {
+        match self {
+            Self::Plaintext => "plaintext",
+            Self::Ssl => "ssl",
+            Self::SaslPlaintext => "sasl_plaintext",
+            Self::SaslSsl => "sasl_ssl",
+        }
+    }
+
+    fn uses_sasl(self) -> bool {
+        matches!(self, Self::SaslPlaintext | Self::SaslSsl)
+    }
+
+    fn uses_tls(self) -> bool {
+        matches!(self, Self::Ssl | Self::SaslSsl)
+    }
+}
+
+/// SASL credentials.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SaslConfig {
+    /// The SASL mechanism.
+    pub mechanism: SaslMechanism,
+    pub username: String,
+    pub password: String,
+}
+
+/// A SASL mechanism.
+#[derive(Clone, Copy, Debug, Deserialize)]
+pub enum SaslMechanism {
+    #[serde(rename = "PLAIN")]
+    Plain,
+    #[serde(rename = "SCRAM-SHA-256")]
+    ScramSha256,
+    #[serde(rename = "SCRAM-SHA-512")]
+    ScramSha512,
+}
+
+impl SaslMechanism {
+    fn as_str(self) -> &'static str {
+        match self {
+            Self::Plain => "PLAIN",
+            Self::ScramSha256 => "SCRAM-SHA-256",
+            Self::ScramSha512 => "SCRAM-SHA-512",
+        }
+    }
+}
+
+/// TLS options for connections to brokers.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct TlsConfig {
+    /// PEM file of CA certificates used to verify brokers, instead of the
+    /// system roots.
+    pub ca_cert: Option<PathBuf>,
+    /// PEM file of the client certificate, for mutual TLS.
+    pub client_cert: Option<PathBuf>,
+    /// PEM file of the client private key, for mutual TLS.
+    pub client_key: Option<PathBuf>,
+    /// Whether the broker hostname is checked against its certificate.
+    pub verify_hostname: bool,
+}
+
+impl Default for TlsConfig {
+    fn default() -> Self {
+        Self {
+            ca_cert: None,
+            client_cert: None,
+            client_key: None,
+            verify_hostname: true,
+        }
+    }
+}
+
+impl KafkaTriggerRuntimeConfig {
+    /// Checks that the options are consistent with the security protocol.
+    pub fn validate(&self) -> Result<()> {
+        let protocol = self.security_protocol;
+        if protocol.uses_sasl() != self.sasl.is_some() {
+            bail!(
+                "Kafka trigger `sasl` options must be given if and only if `security_protocol` is `sasl_plaintext` or `sasl_ssl`"
+            );
+        }
+        if let Some(tls) = &self.tls {
+            if !protocol.uses_tls() {
+                bail!("Kafka trigger `tls` options require `security_protocol` to be `ssl` or `sasl_ssl`");
+            }
+            if tls.client_cert.is_some() != tls.client_key.is_some() {
+                bail!(
+                    "Kafka trigger `tls.client_cert` and `tls.client_key` must be given together"
+                );
+            }
+        }
+        Ok(())
+    }
+
+    /// Applies the options to a client configuration.
+    pub fn apply(&self, config: &mut ClientConfig) {
+        config.set("security.protocol", self.security_protocol.as_str());
+        if let Some(sasl) = &self.sasl {
+            config
+                .set("sasl.mechanism", sasl.mechanism.as_str())
+                .set("sasl.username", &sasl.username)
+                .set("sasl.password", &sasl.password);
+        }
+        if let Some(tls) = &self.tls {
+            let paths = [
+                ("ssl.ca.location", &tls.ca_cert),
+                ("ssl.certificate.location", &tls.client_cert),
+                ("ssl.key.location", &tls.client_key),
+            ];
+            for (key, path) in paths
+                .into_iter()
+                .filter_map(|(key, path)| Some((key, path.as_ref()?)))
+            {
+                config.set(key, path.to_string_lossy());
+            }
+            if !tls.verify_hostname {
+                config.set("ssl.endpoint.identification.algorithm", "none");
+            }
+        }
+        for (key, value) in &self.properties {
+            config.set(key, value);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sasl_ssl_options_are_applied() {
+        let runtime_config: KafkaTriggerRuntimeConfig = toml::toml! {
+            security_protocol = "sasl_ssl"
+            sasl = { mechanism = "SCRAM-SHA-512", username = "spin", password = "secret" }
+            tls = { ca_cert = "ca.pem" }
+            properties = { "auto.offset.reset" = "latest" }
+        }
+        .try_into()
+        .unwrap();
+        runtime_config.validate().unwrap();
+
+        let mut config = ClientConfig::new();
+        runtime_config.apply(&mut config);
+        assert_eq!(config.get("security.protocol"), Some("sasl_ssl"));
+        assert_eq!(config.get("sasl.mechanism"), Some("SCRAM-SHA-512"));
+        assert_eq!(config.get("sasl.username"), Some("spin"));
+        assert_eq!(config.get("ssl.ca.location"), Some("ca.pem"));
+        assert_eq!(config.get("ssl.certificate.location"), None);
+        assert_eq!(config.get("auto.offset.reset"), Some("latest"));
+    }
+
+    #[test]
+    fn inconsistent_options_are_rejected() {
+        let no_credentials = KafkaTriggerRuntimeConfig {
+            security_protocol: SecurityProtocol::SaslPlaintext,
+            ..Default::default()
+        };
+        assert!(no_credentials.validate().is_err());
+
+        let tls_without_ssl = KafkaTriggerRuntimeConfig {
+            tls: Some(Default::default()),
+            ..Default::default()
+        };
+        assert!(tls_without_ssl.validate().is_err());
+    }
+}
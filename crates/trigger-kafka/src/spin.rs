@@ -0,0 +1,56 @@
+use anyhow::{anyhow, Result};
+use async_trait::async_trait;
+use spin_core::Instance;
+use spin_trigger::TriggerAppEngine;
+use spin_world::v2::kafka_types::{Error, Message};
+
+use crate::{KafkaExecutor, KafkaTrigger, Store};
+
+#[derive(Clone)]
+pub struct SpinKafkaExecutor;
+
+#[async_trait]
+impl KafkaExecutor for SpinKafkaExecutor {
+    async fn execute(
+        &self,
+        engine: &TriggerAppEngine<KafkaTrigger>,
+        component_id: &str,
+        messages: Vec<Message>,
+    ) -> Result<()> {
+        tracing::trace!("Executing request using the Spin executor for component {component_id}");
+
+        let _permit = engine.acquire_permit(component_id).await?;
+
+        let (instance, store) = engine.prepare_instance(component_id).await?;
+
+        match Self::execute_impl(store, instance, messages).await {
+            Ok(()) => {
+                tracing::trace!("Request finished OK");
+                Ok(())
+            }
+            Err(e) => {
+                tracing::trace!("Request finished with error from {component_id}: {e}");
+                Err(anyhow!("Error from {component_id}: {e}"))
+            }
+        }
+    }
+}
+
+impl SpinKafkaExecutor {
+    pub async fn execute_impl(
+        mut store: Store,
+        instance: Instance,
+        messages: Vec<Message>,
+    ) -> Result<()> {
+        let func = instance
+            .exports(&mut store)
+            .instance("fermyon:spin/inbound-kafka@2.0.0")
+            .ok_or_else(|| anyhow!("no fermyon:spin/inbound-kafka@2.0.0 instance found"))?
+            .typed_func::<(Vec<Message>,), (Result<(), Error>,)>("handle-messages")?;
+
+        match func.call_async(store, (messages,)).await? {
+            (Ok(()),) => Ok(()),
+            (Err(Error::Other(e)),) => Err(anyhow!("`handle-messages` returned an error: {e}")),
+        }
+    }
+}
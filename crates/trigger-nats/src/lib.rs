@@ -0,0 +1,357 @@
+//! Implementation for the Spin NATS trigger.
+
+mod runtime_config;
+mod spin;
+
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::{anyhow, bail, Context, Result};
+use async_nats::jetstream::{self, consumer::pull, AckKind};
+use async_nats::Client;
+use futures::StreamExt;
+use serde::{de::IgnoredAny, Deserialize, Serialize};
+use spin_common::url::remove_credentials;
+use spin_core::{async_trait, InstancePre};
+use spin_trigger::{cli::NoArgs, TriggerAppEngine, TriggerExecutor};
+use spin_world::v2::nats::{Message, Payload};
+
+use crate::spin::SpinNatsExecutor;
+
+pub use runtime_config::{NatsTriggerRuntimeConfig, TlsConfig};
+
+pub(crate) type RuntimeData = ();
+pub(crate) type Store = spin_core::Store<RuntimeData>;
+
+/// The Spin NATS trigger.
+#[derive(Clone)]
+pub struct NatsTrigger {
+    engine: Arc<TriggerAppEngine<Self>>,
+    runtime_config: NatsTriggerRuntimeConfig,
+    // Mapping of server address to the subscriptions on it
+    server_subscriptions: HashMap<String, Vec<Subscription>>,
+}
+
+/// NATS trigger configuration.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct NatsTriggerConfig {
+    /// Component ID to invoke
+    pub component: String,
+    /// Subject to subscribe to, which may contain wildcards
+    pub subject: String,
+    /// Queue group to join, so that each message is handled by one member
+    pub queue_group: Option<String>,
+    /// Optional override of the trigger's address
+    pub address: Option<String>,
+    /// Consume from a JetStream stream instead of a core subscription
+    pub jetstream: Option<JetStreamConfig>,
+    /// Trigger executor (currently unused)
+    #[serde(default, skip_serializing)]
+    pub executor: IgnoredAny,
+}
+
+/// JetStream consumer options.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct JetStreamConfig {
+    /// The stream to consume from
+    pub stream: String,
+    /// Name of a durable consumer, which survives restarts; ephemeral if unset
+    pub durable: Option<String>,
+    /// Which messages in the stream a new consumer starts from
+    #[serde(default)]
+    pub deliver_policy: DeliverPolicy,
+    /// How long, in seconds, the server waits for a message to be handled
+    /// before redelivering it
+    #[serde(default = "default_ack_wait_secs")]
+    pub ack_wait_secs: u64,
+    /// The maximum number of deliveries of a message; unlimited if unset
+    pub max_deliver: Option<i64>,
+}
+
+fn default_ack_wait_secs() -> u64 {
+    30
+}
+
+/// Which messages in the stream a new consumer starts from.
+#[derive(Clone, Copy, Debug, Default, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum DeliverPolicy {
+    #[default]
+    All,
+    New,
+    Last,
+}
+
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+struct TriggerMetadata {
+    address: String,
+}
+
+/// A component's subscription, with its templates resolved.
+#[derive(Clone, Debug)]
+struct Subscription {
+    component: String,
+    subject: String,
+    queue_group: Option<String>,
+    jetstream: Option<JetStreamConfig>,
+}
+
+#[async_trait]
+impl TriggerExecutor for NatsTrigger {
+    const TRIGGER_TYPE: &'static str = "nats";
+    type RuntimeData = RuntimeData;
+    type TriggerConfig = NatsTriggerConfig;
+    type RunConfig = NoArgs;
+    type InstancePre = InstancePre<RuntimeData>;
+
+    async fn new(engine: TriggerAppEngine<Self>) -> Result<Self> {
+        let default_address = engine
+            .trigger_metadata::<TriggerMetadata>()?
+            .unwrap_or_default()
+            .address;
+        let runtime_config = engine.trigger_runtime_opts::<NatsTriggerRuntimeConfig>()?;
+        runtime_config.validate()?;
+
+        let resolve = |value: &str| -> Result<String> {
+            let expr = spin_expressions::Template::new(value)?;
+            Ok(engine.resolve_template(&expr)?)
+        };
+
+        let mut server_subscriptions: HashMap<String, Vec<Subscription>> = HashMap::new();
+        for (_, config) in engine.trigger_configs() {
+            let component = &config.component;
+            let address = resolve(config.address.as_deref().unwrap_or(&default_address))?;
+            if address.is_empty() {
+                bail!("NATS trigger for component '{component}' has no address");
+            }
+            if config.jetstream.is_some() && config.queue_group.is_some() {
+                bail!("NATS trigger for component '{component}' cannot use both `queue_group` and `jetstream`; JetStream consumers are already shared");
+            }
+            let queue_group = config.queue_group.as_deref().map(resolve).transpose()?;
+            server_subscriptions
+                .entry(address)
+                .or_default()
+                .push(Subscription {
+                    component: component.clone(),
+                    subject: resolve(&config.subject)?,
+                    queue_group,
+                    jetstream: config.jetstream.clone(),
+                });
+        }
+
+        Ok(Self {
+            engine: Arc::new(engine),
+            runtime_config,
+            server_subscriptions,
+        })
+    }
+
+    /// Run the NATS trigger indefinitely.
+    async fn run(self, _config: Self::RunConfig) -> Result<()> {
+        let mut tasks = vec![];
+        for (address, subscriptions) in &self.server_subscriptions {
+            tracing::info!("Connecting to NATS server at {address}");
+            let client = self
+                .runtime_config
+                .connect_options()
+                .await?
+                .connect(address.as_str())
+                .await
+                .with_context(|| anyhow!("NATS trigger failed to connect to {address}"))?;
+
+            let sanitised_addr = remove_credentials(address)?;
+            println!("Active subjects on {sanitised_addr}:");
+            for subscription in subscriptions {
+                println!(
+                    "\t{sanitised_addr}:{}: [{}]",
+                    subscription.subject, subscription.component
+                );
+                self.engine.status().add_subscription(
+                    &subscription.component,
+                    format!("subject {sanitised_addr}:{}", subscription.subject),
+                );
+                let trigger = self.clone();
+                let client = client.clone();
+                let subscription = subscription.clone();
+                tasks.push(tokio::spawn(async move {
+                    match &subscription.jetstream {
+                        Some(jetstream) => {
+                            trigger
+                                .run_jetstream_consumer(client, &subscription, jetstream)
+                                .await
+                        }
+                        None => trigger.run_subscriber(client, &subscription).await,
+                    }
+                }));
+            }
+        }
+        if tasks.is_empty() {
+            return Ok(());
+        }
+
+        // wait for the first handle to be returned and drop the rest
+        let (result, _, rest) = futures::future::select_all(tasks).await;
+
+        drop(rest);
+
+        result?
+    }
+}
+
+impl NatsTrigger {
+    // Handle the message, returning the component's reply.
+    async fn handle(&self, component_id: &str, message: Message) -> Result<Option<Payload>> {
+        tracing::info!("Received message on subject {:?}", message.subject);
+        tracing::trace!("Executing NATS component {component_id:?}");
+        let result = SpinNatsExecutor
+            .execute(&self.engine, component_id, message)
+            .await;
+        self.engine.status().record(component_id, result)
+    }
+
+    async fn run_subscriber(&self, client: Client, subscription: &Subscription) -> Result<()> {
+        let subject = subscription.subject.clone();
+        let mut subscriber = match &subscription.queue_group {
+            Some(group) => client.queue_subscribe(subject, group.clone()).await,
+            None => client.subscribe(subject).await,
+        }
+        .with_context(|| {
+            anyhow!(
+                "NATS trigger failed to subscribe to {}",
+                subscription.subject
+            )
+        })?;
+
+        while let Some(message) = subscriber.next().await {
+            let message = outbound_nats::from_nats_message(message);
+            let reply_subject = message.reply.clone();
+            match self.handle(&subscription.component, message).await {
+                Ok(Some(reply)) => match reply_subject {
+                    Some(reply_subject) => {
+                        if let Err(err) = client.publish(reply_subject, reply.into()).await {
+                            tracing::warn!("Error publishing reply: {err}");
+                        }
+                    }
+                    None => tracing::debug!("Discarding reply to a message which expects none"),
+                },
+                Ok(None) => {}
+                Err(err) => tracing::warn!("Error handling message: {err}"),
+            }
+        }
+        println!("Subscription to {} closed", subscription.subject);
+        Ok(())
+    }
+
+    async fn run_jetstream_consumer(
+        &self,
+        client: Client,
+        subscription: &Subscription,
+        config: &JetStreamConfig,
+    ) -> Result<()> {
+        let context = jetstream::new(client);
+        let stream = context
+            .get_stream(&config.stream)
+            .await
+            .map_err(|e| anyhow!("NATS trigger failed to find stream {}: {e}", config.stream))?;
+        let consumer_config = consumer_config(&subscription.subject, config);
+        let consumer = match &config.durable {
+            Some(name) => stream.get_or_create_consumer(name, consumer_config).await,
+            None => stream.create_consumer(consumer_config).await,
+        }
+        .map_err(|e| {
+            anyhow!(
+                "NATS trigger failed to create a consumer on {}: {e}",
+                config.stream
+            )
+        })?;
+
+        let mut messages = consumer
+            .messages()
+            .await
+            .map_err(|e| anyhow!("NATS trigger failed to consume from {}: {e}", config.stream))?;
+        while let Some(message) = messages.next().await {
+            let message = match message {
+                Ok(message) => message,
+                Err(err) => {
+                    tracing::warn!("Error receiving JetStream message: {err}");
+                    continue;
+                }
+            };
+            let mut guest_message = outbound_nats::from_nats_message(message.message.clone());
+            // The reply subject is used to acknowledge the message
+            guest_message.reply = None;
+            let ack = match self.handle(&subscription.component, guest_message).await {
+                Ok(_) => AckKind::Ack,
+                Err(err) => {
+                    tracing::warn!("Error handling message: {err}");
+                    AckKind::Nak(None)
+                }
+            };
+            if let Err(err) = message.ack_with(ack).await {
+                tracing::warn!("Error acknowledging JetStream message: {err}");
+            }
+        }
+        println!("JetStream consumer on {} closed", config.stream);
+        Ok(())
+    }
+}
+
+fn consumer_config(subject: &str, config: &JetStreamConfig) -> pull::Config {
+    use async_nats::jetstream::consumer::DeliverPolicy as Policy;
+    pull::Config {
+        durable_name: config.durable.clone(),
+        filter_subject: subject.to_owned(),
+        deliver_policy: match config.deliver_policy {
+            DeliverPolicy::All => Policy::All,
+            DeliverPolicy::New => Policy::New,
+            DeliverPolicy::Last => Policy::Last,
+        },
+        ack_wait: Duration::from_secs(config.ack_wait_secs),
+        max_deliver: config.max_deliver.unwrap_or(-1),
+        ..Default::default()
+    }
+}
+
+/// The NATS executor trait.
+/// All NATS executors must implement this trait.
+#[async_trait]
+pub(crate) trait NatsExecutor: Clone + Send + Sync + 'static {
+    async fn execute(
+        &self,
+        engine: &TriggerAppEngine<NatsTrigger>,
+        component_id: &str,
+        message: Message,
+    ) -> Result<Option<Payload>>;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn jetstream_options_map_to_consumer_config() {
+        let config: NatsTriggerConfig = toml::toml! {
+            component = "orders"
+            subject = "orders.>"
+            jetstream = { stream = "ORDERS", durable = "spin-orders", deliver_policy = "new" }
+        }
+        .try_into()
+        .unwrap();
+        let jetstream = config.jetstream.unwrap();
+        assert_eq!(jetstream.ack_wait_secs, 30);
+
+        let consumer = consumer_config(&config.subject, &jetstream);
+        assert_eq!(consumer.durable_name.as_deref(), Some("spin-orders"));
+        assert_eq!(consumer.filter_subject, "orders.>");
+        assert_eq!(
+            consumer.deliver_policy,
+            async_nats::jetstream::consumer::DeliverPolicy::New
+        );
+        assert_eq!(consumer.ack_wait, Duration::from_secs(30));
+        assert_eq!(consumer.max_deliver, -1);
+    }
+}
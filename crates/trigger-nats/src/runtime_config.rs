@@ -0,0 +1,119 @@
+use std::path::PathBuf;
+
+use anyhow::{bail, Context, Result};
+use async_nats::ConnectOptions;
+use serde::Deserialize;
+
+/// NATS connection options, read from the `[nats_trigger]` runtime config
+/// table. At most one authentication method may be given.
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct NatsTriggerRuntimeConfig {
+    /// Authenticate with a token.
+    pub token: Option<String>,
+    /// Authenticate with a username and `password`.
+    pub username: Option<String>,
+    pub password: Option<String>,
+    /// Authenticate with an nkey seed.
+    pub nkey_seed: Option<String>,
+    /// Authenticate with a user JWT and nkey from a `.creds` file.
+    pub credentials_file: Option<PathBuf>,
+    /// TLS options for connections to the server.
+    pub tls: Option<TlsConfig>,
+}
+
+/// TLS options for connections to the server.
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct TlsConfig {
+    /// Refuse to connect to servers which do not offer TLS.
+    pub required: bool,
+    /// PEM file of CA certificates used to verify the server, in addition to
+    /// the system roots.
+    pub ca_cert: Option<PathBuf>,
+    /// PEM file of the client certificate, for mutual TLS.
+    pub client_cert: Option<PathBuf>,
+    /// PEM file of the client private key, for mutual TLS.
+    pub client_key: Option<PathBuf>,
+}
+
+impl NatsTriggerRuntimeConfig {
+    /// Checks that the options are consistent.
+    pub fn validate(&self) -> Result<()> {
+        let methods = [
+            self.token.is_some(),
+            self.username.is_some(),
+            self.nkey_seed.is_some(),
+            self.credentials_file.is_some(),
+        ];
+        if methods.into_iter().filter(|given| *given).count() > 1 {
+            bail!("NATS trigger runtime config may give only one of `token`, `username`, `nkey_seed` and `credentials_file`");
+        }
+        if self.username.is_some() != self.password.is_some() {
+            bail!("NATS trigger `username` and `password` must be given together");
+        }
+        if let Some(tls) = &self.tls {
+            if tls.client_cert.is_some() != tls.client_key.is_some() {
+                bail!("NATS trigger `tls.client_cert` and `tls.client_key` must be given together");
+            }
+        }
+        Ok(())
+    }
+
+    /// Builds the options for connecting to a server.
+    pub async fn connect_options(&self) -> Result<ConnectOptions> {
+        let mut options = ConnectOptions::new();
+        if let Some(token) = &self.token {
+            options = options.token(token.clone());
+        }
+        if let (Some(username), Some(password)) = (&self.username, &self.password) {
+            options = options.user_and_password(username.clone(), password.clone());
+        }
+        if let Some(seed) = &self.nkey_seed {
+            options = options.nkey(seed.clone());
+        }
+        if let Some(path) = &self.credentials_file {
+            options = options.credentials_file(path).await.with_context(|| {
+                format!("failed to read NATS credentials file {}", path.display())
+            })?;
+        }
+        if let Some(tls) = &self.tls {
+            options = options.require_tls(tls.required);
+            if let Some(ca_cert) = &tls.ca_cert {
+                options = options.add_root_certificates(ca_cert.clone());
+            }
+            if let (Some(cert), Some(key)) = (&tls.client_cert, &tls.client_key) {
+                options = options.add_client_certificate(cert.clone(), key.clone());
+            }
+        }
+        Ok(options)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_one_auth_method_is_allowed() {
+        let config: NatsTriggerRuntimeConfig = toml::toml! {
+            nkey_seed = "SUACSSL3UAHUDXKFSNVUZRF5UHPMWZ6BFDTJ7M6USDXIEDNPPQYYYCU3VY"
+            tls = { required = true, ca_cert = "ca.pem" }
+        }
+        .try_into()
+        .unwrap();
+        config.validate().unwrap();
+
+        let config = NatsTriggerRuntimeConfig {
+            token: Some("s3cret".into()),
+            ..config
+        };
+        assert!(config.validate().is_err());
+
+        let no_password = NatsTriggerRuntimeConfig {
+            username: Some("spin".into()),
+            ..Default::default()
+        };
+        assert!(no_password.validate().is_err());
+    }
+}
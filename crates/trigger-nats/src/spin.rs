@@ -0,0 +1,56 @@
+use anyhow::{anyhow, Result};
+use async_trait::async_trait;
+use spin_core::Instance;
+use spin_trigger::TriggerAppEngine;
+use spin_world::v2::nats::{Error, Message, Payload};
+
+use crate::{NatsExecutor, NatsTrigger, Store};
+
+#[derive(Clone)]
+pub struct SpinNatsExecutor;
+
+#[async_trait]
+impl NatsExecutor for SpinNatsExecutor {
+    async fn execute(
+        &self,
+        engine: &TriggerAppEngine<NatsTrigger>,
+        component_id: &str,
+        message: Message,
+    ) -> Result<Option<Payload>> {
+        tracing::trace!("Executing request using the Spin executor for component {component_id}");
+
+        let _permit = engine.acquire_permit(component_id).await?;
+
+        let (instance, store) = engine.prepare_instance(component_id).await?;
+
+        match Self::execute_impl(store, instance, message).await {
+            Ok(reply) => {
+                tracing::trace!("Request finished OK");
+                Ok(reply)
+            }
+            Err(e) => {
+                tracing::trace!("Request finished with error from {component_id}: {e}");
+                Err(anyhow!("Error from {component_id}: {e}"))
+            }
+        }
+    }
+}
+
+impl SpinNatsExecutor {
+    pub async fn execute_impl(
+        mut store: Store,
+        instance: Instance,
+        message: Message,
+    ) -> Result<Option<Payload>> {
+        let func = instance
+            .exports(&mut store)
+            .instance("fermyon:spin/inbound-nats@2.0.0")
+            .ok_or_else(|| anyhow!("no fermyon:spin/inbound-nats@2.0.0 instance found"))?
+            .typed_func::<(Message,), (Result<Option<Payload>, Error>,)>("handle-message")?;
+
+        match func.call_async(store, (message,)).await? {
+            (Ok(reply),) => Ok(reply),
+            (Err(e),) => Err(anyhow!("`handle-message` returned an error: {e:?}")),
+        }
+    }
+}
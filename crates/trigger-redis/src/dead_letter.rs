@@ -0,0 +1,64 @@
+//! Dead-lettering messages to a Redis stream.
+
+use anyhow::{Context, Result};
+use redis::{AsyncCommands, Client};
+use spin_core::async_trait;
+use spin_trigger::retry::{DeadLetter, DeadLetterQueue};
+
+/// Adds dead-lettered messages to a stream on the trigger's server.
+///
+/// Entries keep the message's metadata as fields, alongside `payload` and
+/// `dead-letter-*` fields describing the failure.
+pub(crate) struct StreamDeadLetters {
+    client: Client,
+    stream: String,
+}
+
+impl StreamDeadLetters {
+    pub fn new(address: &str, stream: &str) -> Result<Self> {
+        Ok(Self {
+            client: Client::open(address)?,
+            stream: stream.to_owned(),
+        })
+    }
+}
+
+#[async_trait]
+impl DeadLetterQueue for StreamDeadLetters {
+    async fn send(&self, letter: &DeadLetter) -> Result<()> {
+        let mut conn = self.client.get_async_connection().await?;
+        conn.xadd::<_, _, _, _, ()>(&self.stream, "*", &entry_fields(letter))
+            .await
+            .with_context(|| format!("failed to add to dead-letter stream {}", self.stream))
+    }
+}
+
+fn entry_fields(letter: &DeadLetter) -> Vec<(String, Vec<u8>)> {
+    let mut fields: Vec<(String, Vec<u8>)> = letter
+        .metadata
+        .iter()
+        .map(|(key, value)| (key.clone(), value.clone().into_bytes()))
+        .collect();
+    if !letter.payload.is_empty() {
+        fields.push(("payload".into(), letter.payload.clone()));
+    }
+    fields.extend([
+        (
+            "dead-letter-component".into(),
+            letter.component.clone().into_bytes(),
+        ),
+        (
+            "dead-letter-source".into(),
+            letter.source.clone().into_bytes(),
+        ),
+        (
+            "dead-letter-error".into(),
+            letter.error.clone().into_bytes(),
+        ),
+        (
+            "dead-letter-attempts".into(),
+            letter.attempts.to_string().into_bytes(),
+        ),
+    ]);
+    fields
+}
@@ -3,6 +3,7 @@ use async_trait::async_trait;
 use spin_core::Instance;
 use spin_trigger::TriggerAppEngine;
 use spin_world::v1::redis_types::{Error, Payload};
+use spin_world::v2::redis_stream_types::{Error as StreamError, StreamEntry};
 
 use crate::{RedisExecutor, RedisTrigger, Store};
 
@@ -20,6 +21,8 @@ impl RedisExecutor for SpinRedisExecutor {
     ) -> Result<()> {
         tracing::trace!("Executing request using the Spin executor for component {component_id}");
 
+        let _permit = engine.acquire_permit(component_id).await?;
+
         let (instance, store) = engine.prepare_instance(component_id).await?;
 
         match Self::execute_impl(store, instance, channel, payload.to_vec()).await {
@@ -33,6 +36,25 @@ impl RedisExecutor for SpinRedisExecutor {
             }
         }
     }
+
+    async fn execute_stream(
+        &self,
+        engine: &TriggerAppEngine<RedisTrigger>,
+        component_id: &str,
+        entries: Vec<StreamEntry>,
+    ) -> Result<()> {
+        tracing::trace!(
+            "Executing stream entries using the Spin executor for component {component_id}"
+        );
+
+        let _permit = engine.acquire_permit(component_id).await?;
+
+        let (instance, store) = engine.prepare_instance(component_id).await?;
+
+        Self::execute_stream_impl(store, instance, entries)
+            .await
+            .map_err(|e| anyhow!("Error from {component_id}: {e}"))
+    }
 }
 
 impl SpinRedisExecutor {
@@ -53,4 +75,23 @@ impl SpinRedisExecutor {
             _ => Err(anyhow!("`handle-message` returned an error")),
         }
     }
+
+    pub async fn execute_stream_impl(
+        mut store: Store,
+        instance: Instance,
+        entries: Vec<StreamEntry>,
+    ) -> Result<()> {
+        let func = instance
+            .exports(&mut store)
+            .instance("fermyon:spin/inbound-redis-stream@2.0.0")
+            .ok_or_else(|| anyhow!("no fermyon:spin/inbound-redis-stream@2.0.0 instance found"))?
+            .typed_func::<(Vec<StreamEntry>,), (Result<(), StreamError>,)>("handle-entries")?;
+
+        match func.call_async(store, (entries,)).await? {
+            (Ok(()),) => Ok(()),
+            (Err(StreamError::Other(e)),) => {
+                Err(anyhow!("`handle-entries` returned an error: {e}"))
+            }
+        }
+    }
 }
@@ -0,0 +1,321 @@
+//! Consuming Redis streams with consumer groups.
+
+use std::time::{Duration, Instant};
+
+use anyhow::{anyhow, Context, Result};
+use redis::aio::Connection;
+use redis::streams::{StreamId, StreamRangeReply, StreamReadOptions, StreamReadReply};
+use redis::{AsyncCommands, Client};
+use serde::{Deserialize, Serialize};
+use spin_common::url::remove_credentials;
+use spin_trigger::retry::{DeadLetter, MessageRetry};
+use spin_trigger::TriggerAppEngine;
+use spin_world::v2::redis_stream_types::StreamEntry;
+
+use crate::{spin::SpinRedisExecutor, RedisExecutor, RedisTrigger};
+
+/// How long to wait before reconnecting after the connection fails.
+const RECONNECT_DELAY: Duration = Duration::from_secs(5);
+
+/// Configuration for consuming a Redis stream.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct RedisStreamConfig {
+    /// Key of the stream, which is created if it does not exist
+    pub key: String,
+    /// Consumer group to read as, which is created if it does not exist
+    pub group: String,
+    /// Name of this consumer within the group; unique to the process if unset
+    pub consumer: Option<String>,
+    /// The maximum number of entries delivered in one call to the handler
+    #[serde(default = "default_batch_size")]
+    pub batch_size: usize,
+    /// How long, in milliseconds, a read waits for new entries
+    #[serde(default = "default_block_ms")]
+    pub block_ms: usize,
+    /// How long, in milliseconds, an entry may stay pending before another
+    /// consumer claims it and delivers it again
+    #[serde(default = "default_claim_idle_ms")]
+    pub claim_idle_ms: u64,
+}
+
+fn default_batch_size() -> usize {
+    1
+}
+
+fn default_block_ms() -> usize {
+    5000
+}
+
+fn default_claim_idle_ms() -> u64 {
+    60000
+}
+
+/// A component's stream, with its templates resolved.
+#[derive(Clone, Debug)]
+pub(crate) struct StreamSubscription {
+    component: String,
+    key: String,
+    group: String,
+    consumer: String,
+    batch_size: usize,
+    block_ms: usize,
+    claim_idle: Duration,
+    retry: Option<MessageRetry>,
+}
+
+impl StreamSubscription {
+    pub fn new(
+        engine: &TriggerAppEngine<RedisTrigger>,
+        component: &str,
+        config: &RedisStreamConfig,
+        retry: Option<MessageRetry>,
+    ) -> Result<Self> {
+        if config.batch_size == 0 {
+            anyhow::bail!(
+                "Redis stream trigger for component '{component}': `batch_size` must be at least 1"
+            );
+        }
+        let resolve = |value: &str| -> Result<String> {
+            let expr = spin_expressions::Template::new(value)?;
+            Ok(engine.resolve_template(&expr)?)
+        };
+        let consumer = match &config.consumer {
+            Some(consumer) => resolve(consumer)?,
+            None => format!("spin-{component}-{}", std::process::id()),
+        };
+        Ok(Self {
+            component: component.to_owned(),
+            key: resolve(&config.key)?,
+            group: resolve(&config.group)?,
+            consumer,
+            batch_size: config.batch_size,
+            block_ms: config.block_ms,
+            claim_idle: Duration::from_millis(config.claim_idle_ms),
+            retry,
+        })
+    }
+}
+
+impl RedisTrigger {
+    pub(crate) async fn run_stream_consumer(
+        &self,
+        address: String,
+        subscription: StreamSubscription,
+    ) -> Result<()> {
+        let StreamSubscription {
+            component,
+            key,
+            group,
+            ..
+        } = &subscription;
+        tracing::info!("Connecting to Redis server at {}", address);
+        let client = Client::open(address.as_str())?;
+        let mut conn = connect(&client, &subscription)
+            .await
+            .with_context(|| anyhow!("Redis trigger failed to connect to {}", address))?;
+
+        let sanitised_addr = remove_credentials(&address)?;
+        println!("\t{sanitised_addr}:{key} (group {group}): [{component}]");
+        self.engine.status().add_subscription(
+            component,
+            format!("stream {sanitised_addr}:{key} (group {group})"),
+        );
+
+        let mut last_claim: Option<Instant> = None;
+        loop {
+            let claim_due =
+                !matches!(last_claim, Some(at) if at.elapsed() < subscription.claim_idle);
+            let entries = if claim_due {
+                last_claim = Some(Instant::now());
+                claim_idle_entries(&mut conn, &subscription).await
+            } else {
+                Ok(vec![])
+            };
+            let entries = match entries {
+                Ok(entries) if !entries.is_empty() => Ok(entries),
+                Ok(_) => read_new_entries(&mut conn, &subscription).await,
+                Err(err) => Err(err),
+            };
+            let entries = match entries {
+                Ok(entries) => entries,
+                Err(err) => {
+                    tracing::warn!("Error reading Redis stream {key}: {err}");
+                    tokio::time::sleep(RECONNECT_DELAY).await;
+                    if let Ok(new_conn) = connect(&client, &subscription).await {
+                        conn = new_conn;
+                    }
+                    continue;
+                }
+            };
+            if entries.is_empty() {
+                continue;
+            }
+
+            let ids: Vec<String> = entries.iter().map(|entry| entry.id.clone()).collect();
+            tracing::info!("Received {} entries on stream {address}:{key}", ids.len());
+            let entries: Vec<_> = entries
+                .into_iter()
+                .map(|entry| to_stream_entry(key, entry))
+                .collect();
+            let execute = || async {
+                let result = SpinRedisExecutor
+                    .execute_stream(&self.engine, component, entries.clone())
+                    .await;
+                self.engine.status().record(component, result)
+            };
+            let result = match &subscription.retry {
+                Some(retry) => {
+                    let letters = entries
+                        .iter()
+                        .map(|entry| dead_letter(component, entry))
+                        .collect();
+                    retry.deliver(letters, execute).await.map(|_| ())
+                }
+                None => execute().await,
+            };
+            match result {
+                Ok(()) => {
+                    if let Err(err) = conn.xack::<_, _, _, ()>(key, group, &ids).await {
+                        tracing::warn!("Error acknowledging Redis stream entries: {err}");
+                    }
+                }
+                // The entries stay pending until they are claimed again
+                Err(err) => tracing::warn!("Error handling Redis stream entries: {err}"),
+            }
+        }
+    }
+}
+
+/// Connects to the server, creating the consumer group if needed.
+async fn connect(client: &Client, subscription: &StreamSubscription) -> Result<Connection> {
+    let mut conn = client.get_async_connection().await?;
+    let created: redis::RedisResult<()> = conn
+        .xgroup_create_mkstream(&subscription.key, &subscription.group, "0")
+        .await;
+    match created {
+        Ok(()) => {}
+        Err(err) if err.code() == Some("BUSYGROUP") => {}
+        Err(err) => {
+            return Err(err).with_context(|| {
+                format!(
+                    "failed to create consumer group {} on {}",
+                    subscription.group, subscription.key
+                )
+            })
+        }
+    }
+    Ok(conn)
+}
+
+async fn read_new_entries(
+    conn: &mut Connection,
+    subscription: &StreamSubscription,
+) -> Result<Vec<StreamId>> {
+    let options = StreamReadOptions::default()
+        .group(&subscription.group, &subscription.consumer)
+        .count(subscription.batch_size)
+        .block(subscription.block_ms);
+    let reply: Option<StreamReadReply> = conn
+        .xread_options(&[&subscription.key], &[">"], &options)
+        .await?;
+    Ok(reply
+        .into_iter()
+        .flat_map(|reply| reply.keys)
+        .flat_map(|key| key.ids)
+        .collect())
+}
+
+/// Claims entries which other consumers (or this one) failed to acknowledge
+/// within the claim timeout.
+async fn claim_idle_entries(
+    conn: &mut Connection,
+    subscription: &StreamSubscription,
+) -> Result<Vec<StreamId>> {
+    let reply: redis::Value = redis::cmd("XAUTOCLAIM")
+        .arg(&subscription.key)
+        .arg(&subscription.group)
+        .arg(&subscription.consumer)
+        .arg(subscription.claim_idle.as_millis() as u64)
+        .arg("0-0")
+        .arg("COUNT")
+        .arg(subscription.batch_size)
+        .query_async(conn)
+        .await?;
+    parse_autoclaim(reply)
+}
+
+fn parse_autoclaim(reply: redis::Value) -> Result<Vec<StreamId>> {
+    let redis::Value::Bulk(items) = reply else {
+        anyhow::bail!("unexpected XAUTOCLAIM reply");
+    };
+    let claimed = items.get(1).context("unexpected XAUTOCLAIM reply")?;
+    let range: StreamRangeReply = redis::from_redis_value(claimed)?;
+    Ok(range.ids)
+}
+
+/// The dead letter for an entry, which keeps its fields as metadata.
+fn dead_letter(component: &str, entry: &StreamEntry) -> DeadLetter {
+    entry.fields.iter().fold(
+        DeadLetter::new(component, &entry.key, vec![]).with_metadata("entry-id", &entry.id),
+        |letter, (field, value)| letter.with_metadata(field, String::from_utf8_lossy(value)),
+    )
+}
+
+fn to_stream_entry(key: &str, entry: StreamId) -> StreamEntry {
+    let mut fields: Vec<(String, Vec<u8>)> = entry
+        .map
+        .into_iter()
+        .filter_map(|(field, value)| Some((field, redis::from_redis_value(&value).ok()?)))
+        .collect();
+    fields.sort();
+    StreamEntry {
+        key: key.to_owned(),
+        id: entry.id,
+        fields,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use redis::Value;
+
+    fn entry(id: &str, fields: &[(&str, &str)]) -> Value {
+        let fields = fields
+            .iter()
+            .flat_map(|(f, v)| {
+                [
+                    Value::Data(f.as_bytes().into()),
+                    Value::Data(v.as_bytes().into()),
+                ]
+            })
+            .collect();
+        Value::Bulk(vec![Value::Data(id.as_bytes().into()), Value::Bulk(fields)])
+    }
+
+    #[test]
+    fn autoclaim_reply_is_parsed_into_entries() {
+        let reply = Value::Bulk(vec![
+            Value::Data(b"0-0".to_vec()),
+            Value::Bulk(vec![
+                entry("1-0", &[("job", "resize"), ("image", "a.png")]),
+                entry("2-0", &[("job", "crop")]),
+            ]),
+            Value::Bulk(vec![]),
+        ]);
+        let ids = parse_autoclaim(reply).unwrap();
+        assert_eq!(ids.len(), 2);
+
+        let first = to_stream_entry("jobs", ids.into_iter().next().unwrap());
+        assert_eq!(first.key, "jobs");
+        assert_eq!(first.id, "1-0");
+        assert_eq!(
+            first.fields,
+            [
+                ("image".to_owned(), b"a.png".to_vec()),
+                ("job".to_owned(), b"resize".to_vec())
+            ]
+        );
+    }
+}
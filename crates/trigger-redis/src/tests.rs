@@ -18,9 +18,43 @@ async fn test_pubsub() -> Result<()> {
         .test_program("redis-rust.wasm")
         .build_trigger("messages")
         .await;
-    let test = HashMap::new();
+    let test = ServerSubscriptions::default();
     let msg = create_trigger_event("messages", "hello");
     trigger.handle("", &test, msg).await?;
 
     Ok(())
 }
+
+#[tokio::test]
+async fn test_handle_message() -> Result<()> {
+    let trigger: RedisTrigger = RedisTestConfig::default()
+        .test_program("redis-rust.wasm")
+        .build_trigger("messages")
+        .await;
+    trigger.handle_message("messages", b"hello").await?;
+    trigger.handle_message("other", b"hello").await.unwrap_err();
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_psubscribe() -> Result<()> {
+    let trigger: RedisTrigger = RedisTestConfig::default()
+        .test_program("redis-rust.wasm")
+        .build_trigger("messages")
+        .await;
+    let subscriptions = ServerSubscriptions {
+        patterns: HashMap::from([("mess*".to_owned(), vec!["test-component".to_owned()])]),
+        ..Default::default()
+    };
+    let msg = Msg::from_value(&Value::Bulk(vec![
+        Value::Data("pmessage".into()),
+        Value::Data("mess*".into()),
+        Value::Data("messages".into()),
+        Value::Data("hello".into()),
+    ]))
+    .unwrap();
+    trigger.handle("", &subscriptions, msg).await?;
+
+    Ok(())
+}
@@ -0,0 +1,252 @@
+//! A stable interface for building Spin triggers outside the Spin repository.
+//!
+//! External triggers are plugins named `trigger-<type>`, which `spin up` runs
+//! for applications with triggers of that type. Building one directly on
+//! `spin-trigger` means tracking an internal API which changes from release
+//! to release; this crate wraps it in a smaller [`Trigger`] trait and
+//! [`TriggerContext`], which follow semver.
+//!
+//! A trigger implements [`Trigger`] and calls [`run`] from its `main`:
+//!
+//! ```ignore
+//! #[tokio::main]
+//! async fn main() -> anyhow::Result<()> {
+//!     spin_trigger_sdk::run::<TimerTrigger>().await
+//! }
+//! ```
+//!
+//! The protocol between `spin up` and the trigger executable is versioned
+//! separately by [`INTERFACE_VERSION`]. A trigger built against a newer
+//! interface than the running Spin provides is refused with an error asking
+//! the user to upgrade Spin, rather than failing in some obscure way.
+//!
+//! Component exports are called through the [`wasmtime`] types re-exported
+//! here, which follow Spin's Wasmtime version. Those types are outside this
+//! crate's semver guarantees.
+
+use std::path::PathBuf;
+
+use anyhow::{anyhow, Context, Result};
+use clap::Parser;
+use serde::de::DeserializeOwned;
+use spin_core::{
+    wasmtime::component::{ComponentNamedList, Lift, Lower, TypedFunc},
+    InstancePre,
+};
+use spin_trigger::{
+    cli::TriggerExecutorCommand, ExecutionPermit, TriggerAppEngine, TriggerExecutor,
+};
+
+pub use async_trait::async_trait;
+pub use spin_core::{wasmtime, Instance};
+pub use spin_trigger::{cli::NoArgs, Overloaded};
+
+/// The version of the protocol between `spin up` and trigger executables
+/// which triggers built with this crate speak.
+pub const INTERFACE_VERSION: u32 = spin_trigger::cli::TRIGGER_INTERFACE_VERSION;
+
+/// The store in which a component instance runs.
+pub type Store = spin_core::Store<()>;
+
+/// A trigger type.
+#[async_trait]
+pub trait Trigger: Sized + Send + Sync + 'static {
+    /// The trigger type, as used in application manifests (`[[trigger.<type>]]`).
+    const TYPE: &'static str;
+
+    /// The configuration of each trigger of this type in the manifest.
+    type Config: DeserializeOwned + Send + Sync;
+
+    /// Command-line options for the trigger, or [`NoArgs`].
+    type RunArgs: clap::Args + Send;
+
+    /// Creates the trigger for the application in `context`.
+    async fn new(context: TriggerContext<Self>) -> Result<Self>;
+
+    /// Runs the trigger until it is stopped.
+    async fn run(self, args: Self::RunArgs) -> Result<()>;
+}
+
+/// Runs a trigger executable: parses the command line and environment set by
+/// `spin up`, loads the application, and runs the trigger.
+pub async fn run<T: Trigger>() -> Result<()> {
+    TriggerExecutorCommand::<Executor<T>>::parse().run().await
+}
+
+/// The application and host services available to a trigger.
+pub struct TriggerContext<T: Trigger> {
+    engine: TriggerAppEngine<Executor<T>>,
+}
+
+impl<T: Trigger> TriggerContext<T> {
+    /// Returns the name of the application.
+    pub fn app_name(&self) -> Result<String> {
+        Ok(self.engine.app().require_metadata(spin_app::APP_NAME_KEY)?)
+    }
+
+    /// Returns the directory containing the application manifest, or `None`
+    /// if the application was not loaded from a local file.
+    pub fn app_dir(&self) -> Result<Option<PathBuf>> {
+        self.engine.app_dir()
+    }
+
+    /// Returns the application-wide `[application.trigger.<type>]` settings.
+    pub fn trigger_metadata<M: DeserializeOwned + Default>(&self) -> Result<Option<M>> {
+        Ok(self.engine.trigger_metadata()?)
+    }
+
+    /// Returns the `[<type>_trigger]` table of the runtime config file.
+    pub fn runtime_options<O: DeserializeOwned + Default>(&self) -> Result<O> {
+        self.engine.trigger_runtime_opts()
+    }
+
+    /// Returns the ID and configuration of each trigger of this type.
+    pub fn triggers(&self) -> impl Iterator<Item = (String, &T::Config)> {
+        self.engine
+            .trigger_configs()
+            .map(|(trigger, config)| (trigger.id().to_owned(), config))
+    }
+
+    /// Returns the component which a trigger invokes.
+    pub fn trigger_component(&self, trigger_id: &str) -> Result<String> {
+        let trigger = self
+            .engine
+            .app()
+            .triggers_with_type(T::TYPE)
+            .find(|trigger| trigger.id() == trigger_id)
+            .with_context(|| format!("no {} trigger with ID '{trigger_id}'", T::TYPE))?;
+        Ok(trigger.component()?.id().to_owned())
+    }
+
+    /// Resolves a template such as `"{{ redis_address }}"` against the
+    /// application's variables.
+    pub fn resolve(&self, template: &str) -> Result<String> {
+        let template = spin_expressions::Template::new(template)?;
+        Ok(self.engine.resolve_template(&template)?)
+    }
+
+    /// Records something a component is invoked for, such as a queue or
+    /// schedule, in the trigger's status.
+    pub fn add_subscription(&self, component_id: &str, subscription: impl Into<String>) {
+        self.engine
+            .status()
+            .add_subscription(component_id, subscription);
+    }
+
+    /// Records an error handling an event for a component in the trigger's
+    /// status.
+    pub fn record_error(&self, component_id: &str, error: &anyhow::Error) {
+        self.engine.status().record_error(component_id, error);
+    }
+
+    /// Instantiates a component, ready to call its exports. This waits within
+    /// the configured concurrency limits, and fails with [`Overloaded`] if too
+    /// many executions are waiting.
+    pub async fn instantiate(&self, component_id: &str) -> Result<ComponentInstance> {
+        let permit = self.engine.acquire_permit(component_id).await?;
+        let (instance, store) = self.engine.prepare_instance(component_id).await?;
+        Ok(ComponentInstance {
+            instance,
+            store,
+            _permit: permit,
+        })
+    }
+}
+
+/// An instance of a component and its store. The instance counts towards the
+/// concurrency limits until it is dropped.
+pub struct ComponentInstance {
+    instance: Instance,
+    store: Store,
+    _permit: ExecutionPermit,
+}
+
+impl ComponentInstance {
+    /// Looks up a function exported from an interface, such as
+    /// `("my:trigger/inbound@1.0.0", "handle")`.
+    pub fn typed_func<Params, Results>(
+        &mut self,
+        interface: &str,
+        name: &str,
+    ) -> Result<TypedFunc<Params, Results>>
+    where
+        Params: ComponentNamedList + Lower,
+        Results: ComponentNamedList + Lift,
+    {
+        let mut exports = self.instance.exports(&mut self.store);
+        let mut interface_exports = exports
+            .instance(interface)
+            .ok_or_else(|| anyhow!("component does not export {interface}"))?;
+        interface_exports
+            .typed_func(name)
+            .with_context(|| format!("{interface} export {name:?}"))
+    }
+
+    /// Returns the store, to call functions with.
+    pub fn store(&mut self) -> &mut Store {
+        &mut self.store
+    }
+}
+
+/// Adapts a [`Trigger`] to Spin's internal trigger executor interface.
+#[doc(hidden)]
+pub struct Executor<T>(T);
+
+#[async_trait]
+impl<T: Trigger> TriggerExecutor for Executor<T> {
+    const TRIGGER_TYPE: &'static str = T::TYPE;
+    type RuntimeData = ();
+    type TriggerConfig = T::Config;
+    type RunConfig = T::RunArgs;
+    type InstancePre = InstancePre<()>;
+
+    async fn new(engine: TriggerAppEngine<Self>) -> Result<Self> {
+        T::new(TriggerContext { engine }).await.map(Self)
+    }
+
+    async fn run(self, config: Self::RunConfig) -> Result<()> {
+        self.0.run(config).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct TimerTrigger;
+
+    #[derive(clap::Args)]
+    struct TimerArgs {
+        /// Seconds between ticks
+        #[clap(long = "interval", default_value = "60")]
+        interval: u64,
+    }
+
+    #[async_trait]
+    impl Trigger for TimerTrigger {
+        const TYPE: &'static str = "timer";
+        type Config = ();
+        type RunArgs = TimerArgs;
+
+        async fn new(_context: TriggerContext<Self>) -> Result<Self> {
+            Ok(Self)
+        }
+
+        async fn run(self, _args: Self::RunArgs) -> Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn trigger_args_are_parsed_alongside_spin_options() {
+        let command = TriggerExecutorCommand::<Executor<TimerTrigger>>::try_parse_from([
+            "trigger-timer",
+            "--interval",
+            "5",
+            "--disable-cache",
+        ])
+        .unwrap();
+        assert_eq!(command.run_config.interval, 5);
+        assert!(command.disable_cache);
+    }
+}
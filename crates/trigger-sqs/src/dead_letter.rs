@@ -0,0 +1,66 @@
+//! Dead-lettering messages to an SQS queue.
+
+use anyhow::{Context, Result};
+use aws_sdk_sqs::types::MessageAttributeValue;
+use aws_sdk_sqs::Client;
+use spin_core::async_trait;
+use spin_trigger::retry::{DeadLetter, DeadLetterQueue};
+
+/// SQS accepts at most this many attributes on a message.
+const MAX_ATTRIBUTES: usize = 10;
+
+/// Sends dead-lettered messages to a queue.
+///
+/// Messages keep their body, with `dead-letter-*` attributes describing the
+/// failure. The original message ID and as many of its string attributes
+/// as SQS allows alongside those are kept as attributes too.
+pub(crate) struct QueueDeadLetters {
+    client: Client,
+    queue_url: String,
+}
+
+impl QueueDeadLetters {
+    pub fn new(client: Client, queue_url: &str) -> Self {
+        Self {
+            client,
+            queue_url: queue_url.to_owned(),
+        }
+    }
+}
+
+#[async_trait]
+impl DeadLetterQueue for QueueDeadLetters {
+    async fn send(&self, letter: &DeadLetter) -> Result<()> {
+        let attempts = letter.attempts.to_string();
+        let failure = [
+            ("dead-letter-component", letter.component.as_str()),
+            ("dead-letter-source", letter.source.as_str()),
+            ("dead-letter-error", letter.error.as_str()),
+            ("dead-letter-attempts", attempts.as_str()),
+        ];
+        let original = letter
+            .metadata
+            .iter()
+            .filter(|(_, value)| !value.is_empty())
+            .map(|(name, value)| (name.as_str(), value.as_str()))
+            .take(MAX_ATTRIBUTES - failure.len());
+
+        let mut request = self
+            .client
+            .send_message()
+            .queue_url(&self.queue_url)
+            .message_body(String::from_utf8_lossy(&letter.payload));
+        for (name, value) in original.chain(failure) {
+            let value = MessageAttributeValue::builder()
+                .data_type("String")
+                .string_value(value)
+                .build()?;
+            request = request.message_attributes(name, value);
+        }
+        request
+            .send()
+            .await
+            .with_context(|| format!("failed to send to dead-letter queue {}", self.queue_url))?;
+        Ok(())
+    }
+}
@@ -0,0 +1,523 @@
+//! Implementation for the Spin SQS trigger.
+
+mod dead_letter;
+mod spin;
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::{anyhow, bail, Context, Result};
+use aws_sdk_sqs::types::{
+    Message as SqsMessage, MessageAttributeValue as SqsAttributeValue, MessageSystemAttributeName,
+    QueueAttributeName,
+};
+use aws_sdk_sqs::Client;
+use serde::{de::IgnoredAny, Deserialize, Serialize};
+use spin_core::{async_trait, InstancePre};
+use spin_trigger::retry::{DeadLetter, DeadLetterQueue, MessageRetry, RetryConfig};
+use spin_trigger::{cli::NoArgs, TriggerAppEngine, TriggerExecutor};
+use spin_world::v2::sqs_types::{Message, MessageAttribute, MessageAttributeValue};
+
+use crate::dead_letter::QueueDeadLetters;
+use crate::spin::SpinSqsExecutor;
+
+pub(crate) type RuntimeData = ();
+pub(crate) type Store = spin_core::Store<RuntimeData>;
+
+/// How long to wait before polling again after a receive fails.
+const RECEIVE_RETRY_DELAY: Duration = Duration::from_secs(5);
+
+/// The Spin SQS trigger.
+#[derive(Clone)]
+pub struct SqsTrigger {
+    engine: Arc<TriggerAppEngine<Self>>,
+    client: Client,
+    queues: Vec<QueueSubscription>,
+}
+
+/// SQS trigger configuration.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct SqsTriggerConfig {
+    /// Component ID to invoke
+    pub component: String,
+    /// URL of the queue to receive from
+    pub queue_url: String,
+    /// The maximum number of messages received in one request, from 1 to 10
+    #[serde(default = "default_max_messages")]
+    pub max_messages: u32,
+    /// How long, in seconds, a receive request waits for messages, from 0 to 20
+    #[serde(default = "default_wait_time_secs")]
+    pub wait_time_secs: u32,
+    /// How long, in seconds, a received message is hidden from other
+    /// receivers. The trigger extends this while the handler runs.
+    #[serde(default = "default_visibility_timeout_secs")]
+    pub visibility_timeout_secs: u32,
+    /// Retry policy for messages the component fails to handle, applied
+    /// before SQS redelivers them; a `queue` dead-letter destination is a
+    /// queue URL
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub retry: Option<RetryConfig>,
+    /// Trigger executor (currently unused)
+    #[serde(default, skip_serializing)]
+    pub executor: IgnoredAny,
+}
+
+fn default_max_messages() -> u32 {
+    10
+}
+
+fn default_wait_time_secs() -> u32 {
+    20
+}
+
+fn default_visibility_timeout_secs() -> u32 {
+    30
+}
+
+/// AWS client options, read from the `[sqs_trigger]` runtime config table.
+/// Credentials are taken from the standard AWS environment and profiles.
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SqsTriggerRuntimeConfig {
+    /// The AWS region, overriding the region from the environment.
+    pub region: Option<String>,
+    /// A custom SQS endpoint, e.g. for a local emulator.
+    pub endpoint_url: Option<String>,
+}
+
+/// A component's queue, with its templates resolved.
+#[derive(Clone, Debug)]
+struct QueueSubscription {
+    component: String,
+    queue_url: String,
+    max_messages: u32,
+    wait_time_secs: u32,
+    visibility_timeout_secs: u32,
+    retry: Option<MessageRetry>,
+}
+
+#[async_trait]
+impl TriggerExecutor for SqsTrigger {
+    const TRIGGER_TYPE: &'static str = "sqs";
+    type RuntimeData = RuntimeData;
+    type TriggerConfig = SqsTriggerConfig;
+    type RunConfig = NoArgs;
+    type InstancePre = InstancePre<RuntimeData>;
+
+    async fn new(engine: TriggerAppEngine<Self>) -> Result<Self> {
+        let runtime_config = engine.trigger_runtime_opts::<SqsTriggerRuntimeConfig>()?;
+
+        let mut loader = aws_config::defaults(aws_config::BehaviorVersion::latest());
+        if let Some(region) = runtime_config.region {
+            loader = loader.region(aws_config::Region::new(region));
+        }
+        if let Some(endpoint_url) = runtime_config.endpoint_url {
+            loader = loader.endpoint_url(endpoint_url);
+        }
+        let client = Client::new(&loader.load().await);
+
+        let mut queues = vec![];
+        for (_, config) in engine.trigger_configs() {
+            config.validate()?;
+            let component = &config.component;
+            let resolve = |template: &str| -> Result<String> {
+                let expr = spin_expressions::Template::new(template)?;
+                Ok(engine.resolve_template(&expr)?)
+            };
+            let retry = match &config.retry {
+                Some(retry) => Some(
+                    MessageRetry::new(retry, engine.runtime_config(), |queue_url| {
+                        let queue_url = resolve(queue_url)?;
+                        Ok(Arc::new(QueueDeadLetters::new(client.clone(), &queue_url))
+                            as Arc<dyn DeadLetterQueue>)
+                    })
+                    .await
+                    .with_context(|| {
+                        format!("Invalid retry policy for SQS trigger for component '{component}'")
+                    })?,
+                ),
+                None => None,
+            };
+            queues.push(QueueSubscription {
+                component: component.clone(),
+                queue_url: resolve(&config.queue_url)?,
+                max_messages: config.max_messages,
+                wait_time_secs: config.wait_time_secs,
+                visibility_timeout_secs: config.visibility_timeout_secs,
+                retry,
+            });
+        }
+
+        Ok(Self {
+            engine: Arc::new(engine),
+            client,
+            queues,
+        })
+    }
+
+    /// Run the SQS trigger indefinitely.
+    async fn run(self, _config: Self::RunConfig) -> Result<()> {
+        if self.queues.is_empty() {
+            return Ok(());
+        }
+        let tasks: Vec<_> = self
+            .queues
+            .clone()
+            .into_iter()
+            .map(|queue| {
+                let trigger = self.clone();
+                tokio::spawn(async move { trigger.run_poller(queue).await })
+            })
+            .collect();
+
+        // wait for the first handle to be returned and drop the rest
+        let (result, _, rest) = futures::future::select_all(tasks).await;
+
+        drop(rest);
+
+        result?
+    }
+}
+
+impl SqsTriggerConfig {
+    fn validate(&self) -> Result<()> {
+        let component = &self.component;
+        if !(1..=10).contains(&self.max_messages) {
+            bail!("SQS trigger for component '{component}': `max_messages` must be from 1 to 10");
+        }
+        if self.wait_time_secs > 20 {
+            bail!("SQS trigger for component '{component}': `wait_time_secs` must be at most 20");
+        }
+        if self.visibility_timeout_secs < 2 {
+            bail!("SQS trigger for component '{component}': `visibility_timeout_secs` must be at least 2");
+        }
+        Ok(())
+    }
+}
+
+impl SqsTrigger {
+    async fn run_poller(&self, queue: QueueSubscription) -> Result<()> {
+        let QueueSubscription {
+            component,
+            queue_url,
+            ..
+        } = &queue;
+
+        let dead_letter = self
+            .redrive_policy(queue_url)
+            .await
+            .with_context(|| anyhow!("SQS trigger failed to read attributes of {queue_url}"))?;
+        match &dead_letter {
+            Some(policy) => println!(
+                "Active SQS queue {queue_url}: [{component}] (dead-letter queue {} after {} receives)",
+                policy.dead_letter_target_arn, policy.max_receive_count
+            ),
+            None => println!("Active SQS queue {queue_url}: [{component}]"),
+        }
+        self.engine
+            .status()
+            .add_subscription(component, format!("queue {queue_url}"));
+        let max_receive_count = dead_letter.map(|policy| policy.max_receive_count);
+
+        loop {
+            let output = match self
+                .client
+                .receive_message()
+                .queue_url(queue_url)
+                .max_number_of_messages(queue.max_messages as i32)
+                .wait_time_seconds(queue.wait_time_secs as i32)
+                .visibility_timeout(queue.visibility_timeout_secs as i32)
+                .attribute_names(QueueAttributeName::All)
+                .message_attribute_names("All")
+                .send()
+                .await
+            {
+                Ok(output) => output,
+                Err(err) => {
+                    tracing::warn!("Error receiving SQS messages from {queue_url}: {err}");
+                    tokio::time::sleep(RECEIVE_RETRY_DELAY).await;
+                    continue;
+                }
+            };
+
+            let messages = output.messages.unwrap_or_default();
+            tracing::info!(
+                "Received {} SQS message(s) from {queue_url}",
+                messages.len()
+            );
+            let handlers = messages
+                .into_iter()
+                .map(|message| self.handle(&queue, message, max_receive_count));
+            futures::future::join_all(handlers).await;
+        }
+    }
+
+    // Handle a message, deleting it if the handler succeeds.
+    async fn handle(
+        &self,
+        queue: &QueueSubscription,
+        message: SqsMessage,
+        max_receive_count: Option<u32>,
+    ) {
+        let queue_url = &queue.queue_url;
+        let Some(receipt_handle) = message.receipt_handle.clone() else {
+            tracing::warn!("SQS message from {queue_url} has no receipt handle");
+            return;
+        };
+        let message = to_message(message, max_receive_count);
+        let id = message.id.clone().unwrap_or_default();
+        let final_attempt = message.max_receive_count == Some(message.receive_count);
+
+        let result = {
+            let execute = || async {
+                let result = SpinSqsExecutor
+                    .execute(&self.engine, &queue.component, message.clone())
+                    .await;
+                self.engine.status().record(&queue.component, result)
+            };
+            let handler = async {
+                match &queue.retry {
+                    Some(retry) => {
+                        let letter = dead_letter(&queue.component, queue_url, &message);
+                        retry.deliver(vec![letter], execute).await.map(|_| ())
+                    }
+                    None => execute().await,
+                }
+            };
+            let extender = self.extend_visibility(queue, &receipt_handle);
+            tokio::pin!(handler);
+            tokio::select! {
+                result = &mut handler => result,
+                _ = extender => handler.await,
+            }
+        };
+
+        match result {
+            Ok(()) => {
+                if let Err(err) = self
+                    .client
+                    .delete_message()
+                    .queue_url(queue_url)
+                    .receipt_handle(receipt_handle)
+                    .send()
+                    .await
+                {
+                    tracing::warn!("Error deleting SQS message {id} from {queue_url}: {err}");
+                }
+            }
+            Err(err) if final_attempt => tracing::warn!(
+                "Error handling SQS message {id} on its final attempt; it will move to the dead-letter queue: {err}"
+            ),
+            Err(err) => tracing::warn!("Error handling SQS message {id}: {err}"),
+        }
+    }
+
+    /// Keeps a message hidden from other receivers, extending its visibility
+    /// timeout at half the timeout. Runs until an extension fails.
+    async fn extend_visibility(&self, queue: &QueueSubscription, receipt_handle: &str) {
+        let timeout = queue.visibility_timeout_secs;
+        let mut interval = tokio::time::interval(Duration::from_secs(timeout as u64 / 2));
+        interval.tick().await;
+        loop {
+            interval.tick().await;
+            if let Err(err) = self
+                .client
+                .change_message_visibility()
+                .queue_url(&queue.queue_url)
+                .receipt_handle(receipt_handle)
+                .visibility_timeout(timeout as i32)
+                .send()
+                .await
+            {
+                tracing::warn!("Error extending SQS message visibility: {err}");
+                return;
+            }
+        }
+    }
+
+    async fn redrive_policy(&self, queue_url: &str) -> Result<Option<RedrivePolicy>> {
+        let output = self
+            .client
+            .get_queue_attributes()
+            .queue_url(queue_url)
+            .attribute_names(QueueAttributeName::RedrivePolicy)
+            .send()
+            .await?;
+        output
+            .attributes
+            .unwrap_or_default()
+            .get(&QueueAttributeName::RedrivePolicy)
+            .map(|policy| RedrivePolicy::parse(policy))
+            .transpose()
+    }
+}
+
+/// Where SQS sends messages which are received too many times.
+#[derive(Debug, PartialEq)]
+struct RedrivePolicy {
+    dead_letter_target_arn: String,
+    max_receive_count: u32,
+}
+
+impl RedrivePolicy {
+    fn parse(policy: &str) -> Result<Self> {
+        #[derive(Deserialize)]
+        #[serde(rename_all = "camelCase")]
+        struct Raw {
+            dead_letter_target_arn: String,
+            // SQS returns the count as a string or a number
+            max_receive_count: serde_json::Value,
+        }
+        let raw: Raw = serde_json::from_str(policy).context("invalid RedrivePolicy")?;
+        let max_receive_count = match &raw.max_receive_count {
+            serde_json::Value::Number(n) => n.as_u64().and_then(|n| n.try_into().ok()),
+            serde_json::Value::String(s) => s.parse().ok(),
+            _ => None,
+        }
+        .context("invalid RedrivePolicy maxReceiveCount")?;
+        Ok(Self {
+            dead_letter_target_arn: raw.dead_letter_target_arn,
+            max_receive_count,
+        })
+    }
+}
+
+/// The dead letter for a message, which keeps its ID and string attributes as
+/// metadata.
+fn dead_letter(component: &str, queue_url: &str, message: &Message) -> DeadLetter {
+    let body = message.body.as_deref().unwrap_or_default();
+    let mut letter = DeadLetter::new(component, queue_url, body);
+    if let Some(id) = &message.id {
+        letter = letter.with_metadata("message-id", id);
+    }
+    for attribute in &message.attributes {
+        if let MessageAttributeValue::Str(value) = &attribute.value {
+            letter = letter.with_metadata(&attribute.name, value);
+        }
+    }
+    letter
+}
+
+fn to_message(message: SqsMessage, max_receive_count: Option<u32>) -> Message {
+    let receive_count = message
+        .attributes
+        .as_ref()
+        .and_then(|attrs| attrs.get(&MessageSystemAttributeName::ApproximateReceiveCount))
+        .and_then(|count| count.parse().ok())
+        .unwrap_or(1);
+    let mut attributes: Vec<_> = message
+        .message_attributes
+        .unwrap_or_default()
+        .into_iter()
+        .filter_map(|(name, value)| to_attribute(name, value))
+        .collect();
+    attributes.sort_by(|a, b| a.name.cmp(&b.name));
+    Message {
+        id: message.message_id,
+        body: message.body,
+        attributes,
+        receive_count,
+        max_receive_count,
+    }
+}
+
+fn to_attribute(name: String, value: SqsAttributeValue) -> Option<MessageAttribute> {
+    let attribute_value = match (value.string_value, value.binary_value) {
+        (Some(s), _) => MessageAttributeValue::Str(s),
+        (None, Some(b)) => MessageAttributeValue::Binary(b.into_inner()),
+        (None, None) => return None,
+    };
+    Some(MessageAttribute {
+        name,
+        data_type: value.data_type,
+        value: attribute_value,
+    })
+}
+
+/// The SQS executor trait.
+/// All SQS executors must implement this trait.
+#[async_trait]
+pub(crate) trait SqsExecutor: Clone + Send + Sync + 'static {
+    async fn execute(
+        &self,
+        engine: &TriggerAppEngine<SqsTrigger>,
+        component_id: &str,
+        message: Message,
+    ) -> Result<()>;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn redrive_policy_count_may_be_string_or_number() {
+        let arn = "arn:aws:sqs:us-east-1:123456789012:orders-dlq";
+        for policy in [
+            format!(r#"{{"deadLetterTargetArn":"{arn}","maxReceiveCount":5}}"#),
+            format!(r#"{{"deadLetterTargetArn":"{arn}","maxReceiveCount":"5"}}"#),
+        ] {
+            assert_eq!(
+                RedrivePolicy::parse(&policy).unwrap(),
+                RedrivePolicy {
+                    dead_letter_target_arn: arn.into(),
+                    max_receive_count: 5,
+                }
+            );
+        }
+        assert!(RedrivePolicy::parse(r#"{"deadLetterTargetArn":"x"}"#).is_err());
+    }
+
+    #[test]
+    fn messages_carry_receive_counts_and_attributes() {
+        let message = SqsMessage::builder()
+            .message_id("m-1")
+            .body("hello")
+            .attributes(MessageSystemAttributeName::ApproximateReceiveCount, "3")
+            .message_attributes(
+                "tenant",
+                SqsAttributeValue::builder()
+                    .data_type("String")
+                    .string_value("acme")
+                    .build()
+                    .unwrap(),
+            )
+            .build();
+        let message = to_message(message, Some(3));
+        assert_eq!(message.id.as_deref(), Some("m-1"));
+        assert_eq!(message.body.as_deref(), Some("hello"));
+        assert_eq!(message.receive_count, 3);
+        assert_eq!(message.max_receive_count, Some(3));
+        assert_eq!(message.attributes[0].name, "tenant");
+        assert_eq!(message.attributes[0].data_type, "String");
+        assert!(matches!(
+            &message.attributes[0].value,
+            MessageAttributeValue::Str(s) if s == "acme"
+        ));
+
+        let letter = dead_letter("orders", "https://sqs.example/orders", &message);
+        assert_eq!(letter.payload, b"hello");
+        assert_eq!(letter.metadata["message-id"], "m-1");
+        assert_eq!(letter.metadata["tenant"], "acme");
+    }
+
+    #[test]
+    fn trigger_config_limits() {
+        let config: SqsTriggerConfig = toml::toml! {
+            component = "orders"
+            queue_url = "https://sqs.us-east-1.amazonaws.com/123456789012/orders"
+        }
+        .try_into()
+        .unwrap();
+        config.validate().unwrap();
+        assert_eq!(config.max_messages, 10);
+        assert_eq!(config.wait_time_secs, 20);
+
+        let too_many = SqsTriggerConfig {
+            max_messages: 11,
+            ..config
+        };
+        assert!(too_many.validate().is_err());
+    }
+}
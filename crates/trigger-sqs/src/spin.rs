@@ -0,0 +1,56 @@
+use anyhow::{anyhow, Result};
+use async_trait::async_trait;
+use spin_core::Instance;
+use spin_trigger::TriggerAppEngine;
+use spin_world::v2::sqs_types::{Error, Message};
+
+use crate::{SqsExecutor, SqsTrigger, Store};
+
+#[derive(Clone)]
+pub struct SpinSqsExecutor;
+
+#[async_trait]
+impl SqsExecutor for SpinSqsExecutor {
+    async fn execute(
+        &self,
+        engine: &TriggerAppEngine<SqsTrigger>,
+        component_id: &str,
+        message: Message,
+    ) -> Result<()> {
+        tracing::trace!("Executing request using the Spin executor for component {component_id}");
+
+        let _permit = engine.acquire_permit(component_id).await?;
+
+        let (instance, store) = engine.prepare_instance(component_id).await?;
+
+        match Self::execute_impl(store, instance, message).await {
+            Ok(()) => {
+                tracing::trace!("Request finished OK");
+                Ok(())
+            }
+            Err(e) => {
+                tracing::trace!("Request finished with error from {component_id}: {e}");
+                Err(anyhow!("Error from {component_id}: {e}"))
+            }
+        }
+    }
+}
+
+impl SpinSqsExecutor {
+    pub async fn execute_impl(
+        mut store: Store,
+        instance: Instance,
+        message: Message,
+    ) -> Result<()> {
+        let func = instance
+            .exports(&mut store)
+            .instance("fermyon:spin/inbound-sqs@2.0.0")
+            .ok_or_else(|| anyhow!("no fermyon:spin/inbound-sqs@2.0.0 instance found"))?
+            .typed_func::<(Message,), (Result<(), Error>,)>("handle-message")?;
+
+        match func.call_async(store, (message,)).await? {
+            (Ok(()),) => Ok(()),
+            (Err(Error::Other(e)),) => Err(anyhow!("`handle-message` returned an error: {e}")),
+        }
+    }
+}
@@ -0,0 +1,447 @@
+//! The admin API of a running trigger, served with `--admin-listen`, for
+//! orchestration tools to inspect and control the trigger without
+//! restarting it.
+//!
+//! - `GET /status` reports the trigger's status, as `--status-listen` does
+//! - `GET /components` lists the trigger's components and what they are
+//!   invoked for
+//! - `GET /config` summarizes the runtime config in effect
+//! - `GET /log-level` and `PUT /log-level` read and replace the filter of
+//!   the logs written to stderr, with a body in the `RUST_LOG` format
+//! - `POST /drain` refuses new executions and stops the trigger once those
+//!   running have finished
+//! - `POST /shutdown` stops the trigger at once
+//!
+//! Admin APIs only listen on loopback addresses, and only answer requests
+//! addressed to a loopback host which carry the admin token as a bearer
+//! token. The token is read from `SPIN_ADMIN_TOKEN`, or else generated when
+//! the API starts and written to `admin-token` in the state directory,
+//! readable only by its owner. It is never printed, as the output of a
+//! detached `spin up` goes to a log file.
+
+use std::convert::Infallible;
+use std::io::Write;
+use std::net::{IpAddr, SocketAddr};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::{bail, Context, Result};
+use http_body_util::{BodyExt, Full, Limited};
+use hyper::{
+    body::{Bytes, Incoming},
+    header::{HeaderValue, AUTHORIZATION, CONTENT_LENGTH, HOST, WWW_AUTHENTICATE},
+    http::uri::Authority,
+    server::conn::http1,
+    service::service_fn,
+    Method, Request, Response, StatusCode,
+};
+use hyper_util::rt::TokioIo;
+use serde::Serialize;
+use spin_common::ui::quoted_path;
+use tokio::net::TcpListener;
+use tokio::sync::Notify;
+
+use crate::cli::env;
+use crate::{RuntimeConfigSummary, TriggerStatus};
+
+/// How often a draining trigger checks whether its executions have finished.
+const DRAIN_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// The largest request body an admin API reads.
+pub const MAX_BODY_SIZE: usize = 64 * 1024;
+
+/// The length of generated admin tokens.
+const TOKEN_LENGTH: usize = 32;
+
+/// The file in the state directory to which a generated token is written.
+pub const TOKEN_FILE: &str = "admin-token";
+
+/// Checks that admin requests come from a client which holds the admin
+/// token, and are addressed to a loopback host, which a web page in a
+/// browser can't make them through DNS rebinding.
+#[derive(Clone)]
+pub struct AdminAuth {
+    token: Arc<str>,
+}
+
+impl AdminAuth {
+    /// Uses the token in `SPIN_ADMIN_TOKEN`, or generates one and writes it
+    /// to the state directory if it is unset or empty. Without a state
+    /// directory, the token must be set.
+    pub fn from_env(state_dir: Option<&Path>) -> Result<Self> {
+        match std::env::var(env::ADMIN_TOKEN) {
+            Ok(token) if !token.is_empty() => Ok(Self::new(token)),
+            _ => {
+                let Some(state_dir) = state_dir else {
+                    bail!(
+                        "The admin API needs a token; set one in {} for an app without a state directory",
+                        env::ADMIN_TOKEN
+                    );
+                };
+                let token: String =
+                    rand::Rng::sample_iter(rand::thread_rng(), rand::distributions::Alphanumeric)
+                        .take(TOKEN_LENGTH)
+                        .map(char::from)
+                        .collect();
+                let path = write_token(state_dir, &token)?;
+                terminal::text!(
+                    "Admin requests must be sent with the token in {} as a bearer token; set {} to choose the token",
+                    quoted_path(&path),
+                    env::ADMIN_TOKEN
+                );
+                Ok(Self::new(token))
+            }
+        }
+    }
+
+    pub fn new(token: impl Into<Arc<str>>) -> Self {
+        Self {
+            token: token.into(),
+        }
+    }
+
+    /// Returns the response refusing `req`, if it has no valid token, isn't
+    /// addressed to a loopback host, or declares too large a body.
+    #[allow(clippy::result_large_err)]
+    pub fn check<B>(&self, req: &Request<B>) -> Result<(), Response<Full<Bytes>>> {
+        if !req
+            .headers()
+            .get(HOST)
+            .and_then(|host| host.to_str().ok())
+            .is_some_and(is_loopback_host)
+        {
+            return Err(empty_response(StatusCode::FORBIDDEN));
+        }
+        let token = req
+            .headers()
+            .get(AUTHORIZATION)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.strip_prefix("Bearer "));
+        if !token.is_some_and(|token| tokens_match(token.trim(), &self.token)) {
+            let mut response = empty_response(StatusCode::UNAUTHORIZED);
+            response
+                .headers_mut()
+                .insert(WWW_AUTHENTICATE, HeaderValue::from_static("Bearer"));
+            return Err(response);
+        }
+        let declared_size = req
+            .headers()
+            .get(CONTENT_LENGTH)
+            .and_then(|value| value.to_str().ok()?.parse::<u64>().ok());
+        if declared_size.is_some_and(|size| size > MAX_BODY_SIZE as u64) {
+            return Err(empty_response(StatusCode::PAYLOAD_TOO_LARGE));
+        }
+        Ok(())
+    }
+}
+
+/// Listens for admin requests on `addr`, which must be a loopback address.
+pub async fn bind(addr: SocketAddr) -> Result<TcpListener> {
+    if !addr.ip().is_loopback() {
+        anyhow::bail!(
+            "The admin API can only listen on a loopback address, such as 127.0.0.1:{}, not {addr}",
+            addr.port()
+        );
+    }
+    TcpListener::bind(addr)
+        .await
+        .with_context(|| format!("Unable to listen for admin requests on {addr}"))
+}
+
+/// Reads the body of an admin request, up to `MAX_BODY_SIZE` bytes.
+pub async fn read_body(req: Request<Incoming>) -> Result<Bytes> {
+    let body = Limited::new(req.into_body(), MAX_BODY_SIZE)
+        .collect()
+        .await
+        .map_err(|err| anyhow::anyhow!(err))
+        .context("Failed to read request body")?;
+    Ok(body.to_bytes())
+}
+
+fn is_loopback_host(host: &str) -> bool {
+    let Ok(authority) = host.parse::<Authority>() else {
+        return false;
+    };
+    let host = authority.host();
+    let host = host
+        .strip_prefix('[')
+        .and_then(|host| host.strip_suffix(']'))
+        .unwrap_or(host);
+    host.eq_ignore_ascii_case("localhost")
+        || host.parse::<IpAddr>().is_ok_and(|ip| ip.is_loopback())
+}
+
+/// Writes a generated token to the state directory, readable only by its
+/// owner. Any existing file is replaced rather than written through, so
+/// that neither its permissions nor a link in its place are kept.
+fn write_token(state_dir: &Path, token: &str) -> Result<PathBuf> {
+    std::fs::create_dir_all(state_dir)
+        .with_context(|| format!("Failed to create {}", quoted_path(state_dir)))?;
+    let path = state_dir.join(TOKEN_FILE);
+    match std::fs::remove_file(&path) {
+        Err(e) if e.kind() != std::io::ErrorKind::NotFound => {
+            return Err(e).with_context(|| format!("Failed to replace {}", quoted_path(&path)));
+        }
+        _ => {}
+    }
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+    options
+        .open(&path)
+        .and_then(|mut file| file.write_all(token.as_bytes()))
+        .with_context(|| format!("Failed to write the admin token to {}", quoted_path(&path)))?;
+    Ok(path)
+}
+
+// Compares every byte, so that the time taken doesn't reveal how much of
+// the token was guessed.
+fn tokens_match(given: &str, expected: &str) -> bool {
+    given.len() == expected.len()
+        && given
+            .bytes()
+            .zip(expected.bytes())
+            .fold(0, |diff, (a, b)| diff | (a ^ b))
+            == 0
+}
+
+pub(crate) struct AdminApi {
+    status: TriggerStatus,
+    config: RuntimeConfigSummary,
+    auth: AdminAuth,
+    // Notified when the trigger should stop
+    shutdown: Notify,
+}
+
+#[derive(Serialize)]
+struct ComponentSummary<'a> {
+    id: &'a str,
+    subscriptions: &'a [String],
+}
+
+impl AdminApi {
+    pub fn new(status: TriggerStatus, config: RuntimeConfigSummary, auth: AdminAuth) -> Self {
+        Self {
+            status,
+            config,
+            auth,
+            shutdown: Notify::new(),
+        }
+    }
+
+    /// Serves the admin API until the trigger is drained or shut down
+    /// through it.
+    pub async fn serve(self, addr: SocketAddr) -> Result<()> {
+        let listener = bind(addr).await?;
+        let addr = listener.local_addr()?;
+        terminal::text!("Serving the trigger admin API on http://{addr}");
+        let auth = self.auth.clone();
+        let api = Arc::new(self);
+        loop {
+            let (stream, _) = tokio::select! {
+                accepted = listener.accept() => accepted?,
+                _ = api.shutdown.notified() => {
+                    tracing::info!("Shutdown requested through the admin API");
+                    return Ok(());
+                }
+            };
+            let api = api.clone();
+            let auth = auth.clone();
+            tokio::spawn(async move {
+                let service = service_fn(move |req| {
+                    let api = api.clone();
+                    let auth = auth.clone();
+                    async move {
+                        Ok::<_, Infallible>(match auth.check(&req) {
+                            Ok(()) => api.respond(req).await,
+                            Err(refusal) => refusal,
+                        })
+                    }
+                });
+                if let Err(err) = http1::Builder::new()
+                    .serve_connection(TokioIo::new(stream), service)
+                    .await
+                {
+                    tracing::debug!("Error serving admin request: {err}");
+                }
+            });
+        }
+    }
+
+    async fn respond(self: Arc<Self>, req: Request<Incoming>) -> Response<Full<Bytes>> {
+        let method = req.method().clone();
+        let path = req.uri().path().to_owned();
+        match (&method, path.as_str()) {
+            (&Method::GET, "/status") => json_response(StatusCode::OK, &self.status.report()),
+            (&Method::GET, "/components") => {
+                let report = self.status.report();
+                let components: Vec<_> = report
+                    .components
+                    .iter()
+                    .map(|(id, status)| ComponentSummary {
+                        id,
+                        subscriptions: &status.subscriptions,
+                    })
+                    .collect();
+                json_response(StatusCode::OK, &components)
+            }
+            (&Method::GET, "/config") => json_response(StatusCode::OK, &self.config),
+            (&Method::GET, "/log-level") => json_response(
+                StatusCode::OK,
+                &serde_json::json!({ "filter": spin_telemetry::log_filter() }),
+            ),
+            (&Method::PUT, "/log-level") => {
+                let result = read_body(req).await.and_then(|body| {
+                    let filter =
+                        String::from_utf8(body.to_vec()).context("Log filter is not UTF-8")?;
+                    spin_telemetry::set_log_filter(filter.trim())
+                });
+                match result {
+                    Ok(()) => json_response(
+                        StatusCode::OK,
+                        &serde_json::json!({ "filter": spin_telemetry::log_filter() }),
+                    ),
+                    Err(err) => error_response(StatusCode::BAD_REQUEST, &err),
+                }
+            }
+            (&Method::POST, "/drain") => {
+                if !self.status.is_draining() {
+                    tracing::info!("Draining requested through the admin API");
+                    self.status.drain();
+                    tokio::spawn(self.clone().shut_down_when_drained());
+                }
+                json_response(
+                    StatusCode::ACCEPTED,
+                    &serde_json::json!({ "pending_executions": self.status.pending_executions() }),
+                )
+            }
+            (&Method::POST, "/shutdown") => {
+                self.shutdown.notify_one();
+                json_response(StatusCode::ACCEPTED, &serde_json::json!({}))
+            }
+            (_, "/status" | "/components" | "/config" | "/log-level" | "/drain" | "/shutdown") => {
+                empty_response(StatusCode::METHOD_NOT_ALLOWED)
+            }
+            _ => empty_response(StatusCode::NOT_FOUND),
+        }
+    }
+
+    async fn shut_down_when_drained(self: Arc<Self>) {
+        while self.status.pending_executions() > 0 {
+            tokio::time::sleep(DRAIN_POLL_INTERVAL).await;
+        }
+        self.shutdown.notify_one();
+    }
+}
+
+fn json_response(status: StatusCode, value: &impl Serialize) -> Response<Full<Bytes>> {
+    let Ok(body) = serde_json::to_vec_pretty(value) else {
+        return empty_response(StatusCode::INTERNAL_SERVER_ERROR);
+    };
+    let mut response = Response::new(Full::new(body.into()));
+    *response.status_mut() = status;
+    response.headers_mut().insert(
+        hyper::header::CONTENT_TYPE,
+        hyper::header::HeaderValue::from_static("application/json"),
+    );
+    response
+}
+
+fn error_response(status: StatusCode, err: &anyhow::Error) -> Response<Full<Bytes>> {
+    json_response(status, &serde_json::json!({ "error": format!("{err:#}") }))
+}
+
+fn empty_response(status: StatusCode) -> Response<Full<Bytes>> {
+    let mut response = Response::new(Full::new(Bytes::new()));
+    *response.status_mut() = status;
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(host: &str, authorization: Option<&str>) -> Request<()> {
+        let mut builder = Request::get("/status").header(HOST, host);
+        if let Some(authorization) = authorization {
+            builder = builder.header(AUTHORIZATION, authorization);
+        }
+        builder.body(()).unwrap()
+    }
+
+    fn refusal(auth: &AdminAuth, req: &Request<()>) -> Option<StatusCode> {
+        auth.check(req).err().map(|response| response.status())
+    }
+
+    #[test]
+    fn requests_need_the_token() {
+        let auth = AdminAuth::new("secret");
+        let host = "127.0.0.1:3001";
+        assert_eq!(refusal(&auth, &request(host, Some("Bearer secret"))), None);
+        for authorization in [None, Some("Bearer secreT"), Some("secret"), Some("Bearer ")] {
+            assert_eq!(
+                refusal(&auth, &request(host, authorization)),
+                Some(StatusCode::UNAUTHORIZED),
+                "{authorization:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn requests_must_be_addressed_to_a_loopback_host() {
+        let auth = AdminAuth::new("secret");
+        for host in ["localhost:3001", "127.0.0.1", "[::1]:3001", "LOCALHOST"] {
+            assert_eq!(
+                refusal(&auth, &request(host, Some("Bearer secret"))),
+                None,
+                "{host}"
+            );
+        }
+        for host in [
+            "attacker.example:3001",
+            "10.0.0.1:3001",
+            "localhost.example",
+            "",
+        ] {
+            assert_eq!(
+                refusal(&auth, &request(host, Some("Bearer secret"))),
+                Some(StatusCode::FORBIDDEN),
+                "{host}"
+            );
+        }
+    }
+
+    #[test]
+    fn large_bodies_are_refused() {
+        let auth = AdminAuth::new("secret");
+        let mut req = request("localhost", Some("Bearer secret"));
+        req.headers_mut()
+            .insert(CONTENT_LENGTH, HeaderValue::from(MAX_BODY_SIZE + 1));
+        assert_eq!(refusal(&auth, &req), Some(StatusCode::PAYLOAD_TOO_LARGE));
+    }
+
+    #[tokio::test]
+    async fn only_loopback_addresses_are_bound() {
+        let err = bind("0.0.0.0:0".parse().unwrap()).await.unwrap_err();
+        assert!(err.to_string().contains("loopback"), "{err}");
+        bind("127.0.0.1:0".parse().unwrap()).await.unwrap();
+    }
+
+    #[test]
+    fn generated_tokens_are_private_to_their_owner() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(TOKEN_FILE);
+        std::fs::write(&path, "stale").unwrap();
+
+        write_token(dir.path(), "secret").unwrap();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "secret");
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o600);
+        }
+    }
+}
@@ -1,4 +1,7 @@
+use std::net::SocketAddr;
+use std::num::NonZeroUsize;
 use std::path::PathBuf;
+use std::sync::Arc;
 
 use anyhow::{Context, Result};
 use clap::{Args, IntoApp, Parser};
@@ -6,17 +9,25 @@ use serde::de::DeserializeOwned;
 use spin_app::Loader;
 use spin_common::{arg_parser::parse_kv, sloth};
 
+use crate::admin::{AdminApi, AdminAuth};
 use crate::network::Network;
+use crate::privileges::{Credentials, PrivilegeDrop};
+use crate::runtime_config::concurrency::{ConcurrencyOpts, QueueOverflow};
 use crate::runtime_config::llm::LLmOptions;
 use crate::runtime_config::sqlite::SqlitePersistenceMessageHook;
+use crate::sandbox::{self, SandboxPaths, SyscallPolicy};
 use crate::stdio::StdioLoggingTriggerHooks;
 use crate::{
     loader::TriggerLoader,
     runtime_config::{key_value::KeyValuePersistenceMessageHook, RuntimeConfig},
     stdio::FollowComponents,
 };
-use crate::{TriggerExecutor, TriggerExecutorBuilder};
+use crate::{
+    Cassette, SignatureVerification, StartupOptions, TriggerExecutor, TriggerExecutorBuilder,
+    TriggerStatus,
+};
 
+pub mod env;
 mod launch_metadata;
 pub use launch_metadata::LaunchMetadata;
 
@@ -31,6 +42,12 @@ pub const SPIN_LOCKED_URL: &str = "SPIN_LOCKED_URL";
 pub const SPIN_LOCAL_APP_DIR: &str = "SPIN_LOCAL_APP_DIR";
 pub const SPIN_WORKING_DIR: &str = "SPIN_WORKING_DIR";
 
+/// The version of the interface between `spin up` and trigger executables:
+/// the environment variables and flags above, and the locked app format.
+/// This is bumped whenever a change would break triggers built against an
+/// earlier version.
+pub const TRIGGER_INTERFACE_VERSION: u32 = 1;
+
 /// A command that runs a TriggerExecutor.
 #[derive(Parser, Debug)]
 #[clap(
@@ -47,7 +64,7 @@ where
         name = APP_LOG_DIR,
         short = 'L',
         long = "log-dir",
-        env = "SPIN_LOG_DIR",
+        env = env::LOG_DIR,
     )]
     pub log: Option<PathBuf>,
 
@@ -55,7 +72,7 @@ where
     #[clap(
         name = DISABLE_WASMTIME_CACHE,
         long = "disable-cache",
-        env = DISABLE_WASMTIME_CACHE,
+        env = env::DISABLE_CACHE,
         conflicts_with = WASMTIME_CACHE_FILE,
         takes_value = false,
     )]
@@ -65,20 +82,22 @@ where
     #[clap(
         name = WASMTIME_CACHE_FILE,
         long = "cache",
-        env = WASMTIME_CACHE_FILE,
+        env = env::CACHE,
         conflicts_with = DISABLE_WASMTIME_CACHE,
     )]
     pub cache: Option<PathBuf>,
 
     /// Disable Wasmtime's pooling instance allocator.
-    #[clap(long = "disable-pooling")]
+    #[clap(long = "disable-pooling", env = env::DISABLE_POOLING, takes_value = false)]
     pub disable_pooling: bool,
 
     /// Print output to stdout/stderr only for given component(s)
     #[clap(
         name = FOLLOW_LOG_OPT,
         long = "follow",
+        env = env::FOLLOW,
         multiple_occurrences = true,
+        use_value_delimiter = true,
     )]
     pub follow_components: Vec<String>,
 
@@ -87,19 +106,25 @@ where
         long = "quiet",
         short = 'q',
         aliases = &["sh", "shush"],
+        env = env::QUIET,
+        takes_value = false,
         conflicts_with = FOLLOW_LOG_OPT,
         )]
     pub silence_component_logs: bool,
 
     /// Set the static assets of the components in the temporary directory as writable.
-    #[clap(long = "allow-transient-write")]
+    #[clap(
+        long = "allow-transient-write",
+        env = env::ALLOW_TRANSIENT_WRITE,
+        takes_value = false
+    )]
     pub allow_transient_write: bool,
 
     /// Configuration file for config providers and wasmtime config.
     #[clap(
         name = RUNTIME_CONFIG_FILE,
         long = "runtime-config-file",
-        env = RUNTIME_CONFIG_FILE,
+        env = env::RUNTIME_CONFIG_FILE,
     )]
     pub runtime_config_file: Option<PathBuf>,
 
@@ -109,9 +134,113 @@ where
     /// For local apps, this defaults to `.spin/` relative to the `spin.toml` file.
     /// For remote apps, this has no default (unset).
     /// Passing an empty value forces the value to be unset.
-    #[clap(long)]
+    #[clap(long, env = env::STATE_DIR)]
     pub state_dir: Option<String>,
 
+    /// The maximum number of component executions at once, across all
+    /// components. Overrides the runtime config.
+    #[clap(long = "max-concurrency", env = env::MAX_CONCURRENCY)]
+    pub max_concurrency: Option<usize>,
+
+    /// The maximum number of executions of each component at once. Overrides
+    /// the runtime config.
+    #[clap(
+        long = "max-component-concurrency",
+        env = env::MAX_COMPONENT_CONCURRENCY
+    )]
+    pub max_component_concurrency: Option<usize>,
+
+    /// The maximum number of executions waiting for a concurrency slot.
+    /// Unbounded by default.
+    #[clap(long = "max-queued", env = env::MAX_QUEUED)]
+    pub max_queued: Option<usize>,
+
+    /// What happens to an execution which arrives when the queue is full.
+    #[clap(long = "queue-overflow", env = env::QUEUE_OVERFLOW, arg_enum)]
+    pub queue_overflow: Option<QueueOverflow>,
+
+    /// Reload components when their Wasm files change, without restarting
+    /// the trigger. Intended for development, e.g. with `spin watch`.
+    #[clap(long = "hot-reload", env = env::HOT_RELOAD, takes_value = false)]
+    pub hot_reload: bool,
+
+    /// Serve the status of the trigger's components as JSON at /status on
+    /// this address. Use port 0 to pick a free port, e.g. when the
+    /// application has several trigger types.
+    #[clap(long = "status-listen", env = env::STATUS_LISTEN)]
+    pub status_listen: Option<SocketAddr>,
+
+    /// Serve an admin API on this address, to inspect the trigger's
+    /// components and runtime config, change its log filter, and drain or
+    /// shut it down without signals. The address must be a loopback address,
+    /// and requests must carry the token in SPIN_ADMIN_TOKEN, or else the one
+    /// generated at startup and written to admin-token in the state
+    /// directory.
+    #[clap(long = "admin-listen", env = env::ADMIN_LISTEN)]
+    pub admin_listen: Option<SocketAddr>,
+
+    /// Print how long each component took to compile, link and prepare at
+    /// startup, and to instantiate for its first execution.
+    #[clap(long = "timing", env = env::TIMING, takes_value = false)]
+    pub timing: bool,
+
+    /// The maximum number of components to load, compile and pre-instantiate
+    /// at once at startup. Defaults to the number of available CPUs.
+    #[clap(long = "startup-parallelism", env = env::STARTUP_PARALLELISM)]
+    pub startup_parallelism: Option<NonZeroUsize>,
+
+    /// Start serving before all components are prepared. Components are
+    /// prepared in the background, or when first executed, and are not
+    /// reported ready until then.
+    #[clap(
+        long = "serve-while-loading",
+        env = env::SERVE_WHILE_LOADING,
+        takes_value = false
+    )]
+    pub serve_while_loading: bool,
+
+    /// Restrict the trigger, on Linux, to the files the application uses and
+    /// to the system calls it needs once started, to limit the reach of any
+    /// escape from a component into the host.
+    #[clap(long = "sandbox", env = env::SANDBOX, takes_value = false)]
+    pub sandbox: bool,
+
+    /// Once the trigger has bound its ports, switch to this user, given by
+    /// name or ID, so that it can listen on privileged ports without serving
+    /// as root. Requires running as root, on Linux.
+    #[clap(long = "user", env = env::USER)]
+    pub user: Option<String>,
+
+    /// The group to switch to with --user. Defaults to the user's primary
+    /// group.
+    #[clap(long = "group", env = env::GROUP, requires = "user")]
+    pub group: Option<String>,
+
+    /// Once the trigger has bound its ports, change its root directory to
+    /// this one. Paths the trigger uses once started, such as the state and
+    /// log directories, must exist at the same paths inside it. Requires
+    /// running as root, on Linux.
+    #[clap(long = "chroot", env = env::CHROOT)]
+    pub chroot: Option<PathBuf>,
+
+    /// Record the application's outbound HTTP, Redis and Postgres
+    /// interactions to this cassette file, replacing its contents, for
+    /// replaying with --replay.
+    #[clap(long = "record", env = env::RECORD, value_name = "FILE")]
+    pub record: Option<PathBuf>,
+
+    /// Answer the application's outbound HTTP, Redis and Postgres
+    /// interactions from this cassette file, recorded with --record,
+    /// without touching the network. Interactions which weren't recorded
+    /// fail.
+    #[clap(
+        long = "replay",
+        env = env::REPLAY,
+        value_name = "FILE",
+        conflicts_with = "record"
+    )]
+    pub replay: Option<PathBuf>,
+
     #[clap(flatten)]
     pub run_config: Executor::RunConfig,
 
@@ -144,7 +273,7 @@ where
     Executor::TriggerConfig: DeserializeOwned,
 {
     /// Create a new TriggerExecutorBuilder from this TriggerExecutorCommand.
-    pub async fn run(self) -> Result<()> {
+    pub async fn run(mut self) -> Result<()> {
         if self.help_args_only {
             Self::command()
                 .disable_help_flag(true)
@@ -160,6 +289,64 @@ where
             return Ok(());
         }
 
+        self.apply_legacy_env_vars();
+
+        let runtime_config = self.build_runtime_config()?;
+        let verified_signature = if self.sandbox {
+            match sandbox::entered() {
+                Some(entry) => entry.verified_signature.clone(),
+                None => {
+                    // cosign can't run in the sandbox, so the app's signature
+                    // is verified before entering it
+                    let verified_signature = self.verify_signature(&runtime_config).await?;
+                    let paths = SandboxPaths::new(
+                        Executor::TRIGGER_TYPE,
+                        &std::env::var(SPIN_WORKING_DIR).context(SPIN_WORKING_DIR)?,
+                        &std::env::var(SPIN_LOCKED_URL).context(SPIN_LOCKED_URL)?,
+                        std::env::var_os(SPIN_LOCAL_APP_DIR).map(Into::into),
+                        &runtime_config,
+                    )?;
+                    // This process already has the async runtime's threads,
+                    // which Landlock can't restrict, so the trigger runs in
+                    // a new process which restricts itself before it has any
+                    match sandbox::reexec(paths, verified_signature)? {}
+                }
+            }
+        } else {
+            None
+        };
+        match runtime_config.performance().build_runtime()? {
+            // Blocking this thread hands its other tasks to other workers
+            // while the trigger runs on the runtime built for it.
+            Some(runtime) => tokio::task::block_in_place(move || {
+                runtime.block_on(self.run_trigger(runtime_config, verified_signature))
+            }),
+            None => self.run_trigger(runtime_config, verified_signature).await,
+        }
+    }
+
+    // Verifies the signature of the app, if the runtime config requires it.
+    async fn verify_signature(
+        &self,
+        runtime_config: &RuntimeConfig,
+    ) -> Result<Option<SignatureVerification>> {
+        let security = runtime_config.security();
+        if !security.require_signed_apps {
+            return Ok(None);
+        }
+        let working_dir = std::env::var(SPIN_WORKING_DIR).context(SPIN_WORKING_DIR)?;
+        let locked_url = std::env::var(SPIN_LOCKED_URL).context(SPIN_LOCKED_URL)?;
+        let locked_app = TriggerLoader::new(working_dir, self.allow_transient_write)
+            .load_app(&locked_url)
+            .await?;
+        security.verify_app(&spin_app::App::inert(locked_app))
+    }
+
+    async fn run_trigger(
+        self,
+        runtime_config: RuntimeConfig,
+        verified_signature: Option<SignatureVerification>,
+    ) -> Result<()> {
         // Required env vars
         let working_dir = std::env::var(SPIN_WORKING_DIR).context(SPIN_WORKING_DIR)?;
         let locked_url = std::env::var(SPIN_LOCKED_URL).context(SPIN_LOCKED_URL)?;
@@ -171,9 +358,103 @@ where
         );
 
         let loader = TriggerLoader::new(working_dir, self.allow_transient_write);
-        let executor = self.build_executor(loader, locked_url, init_data).await?;
+        spin_telemetry::sinks::install(runtime_config.log_sinks()?);
+        let prometheus_listen = runtime_config
+            .metrics()
+            .prometheus_listen(Executor::TRIGGER_TYPE);
+        let profiler = crate::profiling::Profiler::new(Executor::TRIGGER_TYPE, &runtime_config)?;
+        // Users are looked up before any change of root hides their entries
+        let credentials = self
+            .user
+            .as_deref()
+            .map(|user| Credentials::lookup(user, self.group.as_deref()))
+            .transpose()?;
+        let privilege_drop = PrivilegeDrop::new(
+            credentials,
+            self.chroot.clone(),
+            [runtime_config.state_dir(), runtime_config.log_dir()]
+                .into_iter()
+                .flatten()
+                .collect(),
+        );
+        let syscall_policy = SyscallPolicy {
+            profiling: profiler.is_some(),
+            chroot: privilege_drop.as_ref().is_some_and(|drop| drop.chroots()),
+            switch_user: privilege_drop
+                .as_ref()
+                .is_some_and(|drop| drop.switches_user()),
+            listen: Executor::listens()
+                || self.status_listen.is_some()
+                || self.admin_listen.is_some()
+                || prometheus_listen.is_some(),
+        };
+        let config_summary = runtime_config.summary();
+        // The token file is written before any change of user or root
+        let admin_auth = self
+            .admin_listen
+            .map(|_| AdminAuth::from_env(runtime_config.state_dir().as_deref()))
+            .transpose()?;
+        let (executor, status) = self
+            .build_executor(
+                loader,
+                locked_url,
+                runtime_config,
+                init_data,
+                privilege_drop.clone(),
+                verified_signature,
+            )
+            .await?;
+        if self.sandbox {
+            sandbox::restrict_syscalls(&syscall_policy)?;
+        }
+        if let Some(privilege_drop) = privilege_drop.filter(|_| !Executor::listens()) {
+            // Triggers which don't listen have no ports to bind first
+            privilege_drop.apply()?;
+        }
 
+        let status_listen = self.status_listen;
+        let admin = self
+            .admin_listen
+            .zip(admin_auth)
+            .map(|(addr, auth)| (addr, AdminApi::new(status.clone(), config_summary, auth)));
         let run_fut = executor.run(self.run_config);
+        let run_fut = async move {
+            match status_listen {
+                Some(addr) => tokio::select! {
+                    res = run_fut => res,
+                    res = status.serve(addr) => res,
+                },
+                None => run_fut.await,
+            }
+        };
+        let run_fut = async move {
+            match admin {
+                // The admin API returns once a drain or shutdown is requested
+                Some((addr, admin)) => tokio::select! {
+                    res = run_fut => res,
+                    res = admin.serve(addr) => res,
+                },
+                None => run_fut.await,
+            }
+        };
+        let run_fut = async move {
+            match prometheus_listen {
+                Some(addr) => tokio::select! {
+                    res = run_fut => res,
+                    res = crate::prometheus::serve(Executor::TRIGGER_TYPE, addr) => res,
+                },
+                None => run_fut.await,
+            }
+        };
+        let run_fut = async move {
+            match profiler {
+                Some(profiler) => tokio::select! {
+                    res = run_fut => res,
+                    res = profiler.run() => res,
+                },
+                None => run_fut.await,
+            }
+        };
 
         let (abortable, abort_handle) = futures::future::abortable(run_fut);
         ctrlc::set_handler(move || abort_handle.abort())?;
@@ -197,21 +478,73 @@ where
         &self,
         loader: impl Loader + Send + Sync + 'static,
         locked_url: String,
+        runtime_config: RuntimeConfig,
         init_data: crate::HostComponentInitData,
-    ) -> Result<Executor> {
-        let runtime_config = self.build_runtime_config()?;
-
+        privilege_drop: Option<std::sync::Arc<PrivilegeDrop>>,
+        verified_signature: Option<SignatureVerification>,
+    ) -> Result<(Executor, TriggerStatus)> {
         let _sloth_guard = warn_if_wasm_build_slothful();
 
         let mut builder = TriggerExecutorBuilder::new(loader);
         self.update_config(builder.config_mut())?;
+        runtime_config.wasm_memory().apply(builder.config_mut());
+        runtime_config.performance().apply(builder.config_mut());
+        if runtime_config
+            .profiling()
+            .is_enabled(Executor::TRIGGER_TYPE)
+        {
+            builder.config_mut().enable_perf_map();
+        }
 
         builder.hooks(StdioLoggingTriggerHooks::new(self.follow_components()));
         builder.hooks(Network::default());
         builder.hooks(KeyValuePersistenceMessageHook);
         builder.hooks(SqlitePersistenceMessageHook);
+        if let Some(privilege_drop) = privilege_drop {
+            builder.hooks(privilege_drop);
+        }
+        if let Some(path) = &self.record {
+            builder.cassette(Arc::new(Cassette::recording(path)?));
+        }
+        if let Some(path) = &self.replay {
+            builder.cassette(Arc::new(Cassette::replaying(path)?));
+        }
+        if self.hot_reload {
+            builder.hot_reload();
+        }
+        if self.timing {
+            builder.print_timing();
+        }
+        if let Some(signature) = verified_signature {
+            builder.verified_signature(signature);
+        }
+        let mut startup = StartupOptions {
+            serve_while_loading: self.serve_while_loading,
+            ..Default::default()
+        };
+        if let Some(parallelism) = self.startup_parallelism {
+            startup.parallelism = parallelism.get();
+        }
+        builder.startup_options(startup);
+
+        let status = builder.status();
+        let executor = builder.build(locked_url, runtime_config, init_data).await?;
+        Ok((executor, status))
+    }
 
-        builder.build(locked_url, runtime_config, init_data).await
+    /// Applies options set by environment variables from before they had
+    /// `SPIN_` names, if neither the option nor its new variable is set.
+    fn apply_legacy_env_vars(&mut self) {
+        if !self.disable_cache && self.cache.is_none() {
+            self.disable_cache = env::legacy_flag(env::DISABLE_CACHE);
+            if !self.disable_cache {
+                self.cache = env::legacy_value(env::CACHE).map(PathBuf::from);
+            }
+        }
+        if self.runtime_config_file.is_none() {
+            self.runtime_config_file =
+                env::legacy_value(env::RUNTIME_CONFIG_FILE).map(PathBuf::from);
+        }
     }
 
     fn build_runtime_config(&self) -> Result<RuntimeConfig> {
@@ -226,6 +559,13 @@ where
         if let Some(config_file) = &self.runtime_config_file {
             config.merge_config_file(config_file)?;
         }
+        config.set_concurrency(ConcurrencyOpts {
+            max_concurrent: self.max_concurrency,
+            max_concurrent_per_component: self.max_component_concurrency,
+            components: Default::default(),
+            max_queued: self.max_queued,
+            overflow: self.queue_overflow,
+        });
         Ok(config)
     }
 
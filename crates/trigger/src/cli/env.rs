@@ -0,0 +1,136 @@
+//! Environment variables which set trigger options, so that applications can
+//! be configured without changing the `spin up` command line, e.g. in
+//! containers.
+//!
+//! Each option takes its value from, in order of precedence:
+//!
+//! 1. its flag on the command line
+//! 2. its environment variable
+//! 3. its default
+//!
+//! Boolean flags are set by any value other than `0`, `false`, `f`, `no`,
+//! `n`, `off` or the empty string. Options which may be given more than
+//! once take a comma-separated list. Options which run one-off actions,
+//! such as `--key-value` and `--sqlite`, have no environment variable.
+//!
+//! Triggers should name the variables for their own options
+//! `SPIN_<TRIGGER TYPE>_<OPTION>`, and declare them here.
+
+use std::ffi::OsString;
+
+pub const LOG_DIR: &str = "SPIN_LOG_DIR";
+pub const DISABLE_CACHE: &str = "SPIN_DISABLE_CACHE";
+pub const CACHE: &str = "SPIN_CACHE";
+pub const DISABLE_POOLING: &str = "SPIN_DISABLE_POOLING";
+pub const FOLLOW: &str = "SPIN_FOLLOW";
+pub const QUIET: &str = "SPIN_QUIET";
+pub const ALLOW_TRANSIENT_WRITE: &str = "SPIN_ALLOW_TRANSIENT_WRITE";
+pub const RUNTIME_CONFIG_FILE: &str = "SPIN_RUNTIME_CONFIG_FILE";
+pub const STATE_DIR: &str = "SPIN_STATE_DIR";
+pub const MAX_CONCURRENCY: &str = "SPIN_MAX_CONCURRENCY";
+pub const MAX_COMPONENT_CONCURRENCY: &str = "SPIN_MAX_COMPONENT_CONCURRENCY";
+pub const MAX_QUEUED: &str = "SPIN_MAX_QUEUED";
+pub const QUEUE_OVERFLOW: &str = "SPIN_QUEUE_OVERFLOW";
+pub const HOT_RELOAD: &str = "SPIN_HOT_RELOAD";
+pub const STATUS_LISTEN: &str = "SPIN_STATUS_LISTEN";
+pub const ADMIN_LISTEN: &str = "SPIN_ADMIN_LISTEN";
+pub const ADMIN_TOKEN: &str = "SPIN_ADMIN_TOKEN";
+pub const TIMING: &str = "SPIN_TIMING";
+pub const STARTUP_PARALLELISM: &str = "SPIN_STARTUP_PARALLELISM";
+pub const SERVE_WHILE_LOADING: &str = "SPIN_SERVE_WHILE_LOADING";
+pub const SANDBOX: &str = "SPIN_SANDBOX";
+pub const USER: &str = "SPIN_USER";
+pub const GROUP: &str = "SPIN_GROUP";
+pub const CHROOT: &str = "SPIN_CHROOT";
+pub const RECORD: &str = "SPIN_RECORD";
+pub const REPLAY: &str = "SPIN_REPLAY";
+
+pub const HTTP_LISTEN: &str = "SPIN_HTTP_LISTEN";
+pub const HTTP_TLS_CERT: &str = "SPIN_TLS_CERT";
+pub const HTTP_TLS_KEY: &str = "SPIN_TLS_KEY";
+pub const HTTP_H2C: &str = "SPIN_HTTP_H2C";
+pub const HTTP_DEBUG_CAPTURE: &str = "SPIN_HTTP_DEBUG_CAPTURE";
+
+pub const GRPC_LISTEN: &str = "SPIN_GRPC_LISTEN";
+pub const GRPC_REFLECTION: &str = "SPIN_GRPC_REFLECTION";
+
+pub const COMMAND_EACH_LINE: &str = "SPIN_COMMAND_EACH_LINE";
+pub const COMMAND_EACH_FILE: &str = "SPIN_COMMAND_EACH_FILE";
+pub const COMMAND_KEEP_GOING: &str = "SPIN_COMMAND_KEEP_GOING";
+
+/// Variables which set options before they had `SPIN_` names, by the name
+/// which replaced them. They are used only if the new variable isn't set.
+const LEGACY_NAMES: &[(&str, &str)] = &[
+    (DISABLE_CACHE, "DISABLE_WASMTIME_CACHE"),
+    (CACHE, "WASMTIME_CACHE_FILE"),
+    (RUNTIME_CONFIG_FILE, "RUNTIME_CONFIG_FILE"),
+];
+
+/// Returns the value of the variable which an option had before `name`, if
+/// `name` isn't set.
+pub(crate) fn legacy_value(name: &str) -> Option<OsString> {
+    if std::env::var_os(name).is_some() {
+        return None;
+    }
+    let (_, legacy) = LEGACY_NAMES.iter().find(|(new, _)| *new == name)?;
+    std::env::var_os(legacy)
+}
+
+/// Returns whether a boolean flag's legacy variable is set, by the same
+/// rules as flags' `SPIN_` variables.
+pub(crate) fn legacy_flag(name: &str) -> bool {
+    legacy_value(name).is_some_and(|value| is_truthy(&value.to_string_lossy()))
+}
+
+fn is_truthy(value: &str) -> bool {
+    !matches!(
+        value.to_ascii_lowercase().as_str(),
+        "" | "0" | "false" | "f" | "no" | "n" | "off"
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use clap::CommandFactory;
+
+    use crate::cli::{help::HelpArgsOnlyTrigger, TriggerExecutorCommand};
+
+    use super::*;
+
+    /// Options which run one-off actions rather than configure the trigger,
+    /// and hidden options used by `spin up` itself.
+    const WITHOUT_ENV: &[&str] = &[
+        "key-value",
+        "sqlite",
+        "help-args-only",
+        "launch-metadata-only",
+    ];
+
+    #[test]
+    fn all_trigger_options_have_spin_env_vars() {
+        let command = TriggerExecutorCommand::<HelpArgsOnlyTrigger>::command();
+        for arg in command.get_arguments() {
+            let Some(long) = arg.get_long() else {
+                continue;
+            };
+            if WITHOUT_ENV.contains(&long) || long == "help" || long == "version" {
+                continue;
+            }
+            let env = arg.get_env().map(|env| env.to_string_lossy().into_owned());
+            assert!(
+                env.as_deref().is_some_and(|env| env.starts_with("SPIN_")),
+                "--{long} has no SPIN_ environment variable"
+            );
+        }
+    }
+
+    #[test]
+    fn falsey_values_do_not_set_flags() {
+        for value in ["", "0", "false", "FALSE", "no", "off"] {
+            assert!(!is_truthy(value), "{value:?}");
+        }
+        for value in ["1", "true", "yes", "on"] {
+            assert!(is_truthy(value), "{value:?}");
+        }
+    }
+}
@@ -1,8 +1,12 @@
+use anyhow::{bail, Result};
 use clap::{Args, CommandFactory};
 use serde::{Deserialize, Serialize};
 use std::ffi::OsString;
 
-use crate::{cli::TriggerExecutorCommand, TriggerExecutor};
+use crate::{
+    cli::{TriggerExecutorCommand, TRIGGER_INTERFACE_VERSION},
+    TriggerExecutor,
+};
 
 /// Contains information about the trigger flags (and potentially
 /// in future configuration) that a consumer (such as `spin up`)
@@ -10,6 +14,9 @@ use crate::{cli::TriggerExecutorCommand, TriggerExecutor};
 #[derive(Clone, Debug, Deserialize, Serialize)]
 pub struct LaunchMetadata {
     all_flags: Vec<LaunchFlag>,
+    // Absent for triggers built before the interface was versioned
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    interface_version: Option<u32>,
 }
 
 // This assumes no triggers that want to participate in multi-trigger
@@ -36,7 +43,21 @@ impl LaunchMetadata {
             .map(LaunchFlag::infer)
             .collect();
 
-        LaunchMetadata { all_flags }
+        LaunchMetadata {
+            all_flags,
+            interface_version: Some(TRIGGER_INTERFACE_VERSION),
+        }
+    }
+
+    /// Fails if the trigger was built against a newer interface than this
+    /// version of Spin provides.
+    pub fn ensure_compatible(&self) -> Result<()> {
+        match self.interface_version {
+            Some(version) if version > TRIGGER_INTERFACE_VERSION => bail!(
+                "trigger requires interface version {version}, but this version of Spin provides version {TRIGGER_INTERFACE_VERSION}; please upgrade Spin"
+            ),
+            _ => Ok(()),
+        }
     }
 
     pub fn matches<'a>(&self, groups: &[Vec<&'a OsString>]) -> Vec<&'a OsString> {
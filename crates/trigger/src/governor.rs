@@ -0,0 +1,345 @@
+//! Limits on concurrent component executions.
+//!
+//! Every trigger asks the [`ExecutionGovernor`] for a permit before executing
+//! a component. Executions over the limits wait in a single queue, so a storm
+//! of events for one component is not served ahead of earlier events for
+//! another; when the queue is full, or the trigger is draining before it
+//! stops, an execution is refused with an [`Overloaded`] error, which
+//! triggers report in their own way (e.g. HTTP 503).
+
+use std::collections::{HashMap, VecDeque};
+use std::fmt;
+use std::sync::{Arc, Mutex};
+
+use anyhow::Result;
+use tokio::sync::oneshot;
+
+use crate::runtime_config::concurrency::{ConcurrencyOpts, QueueOverflow};
+use crate::status::InFlight;
+
+/// Hands out permits to execute components, within the configured limits.
+#[derive(Clone, Default)]
+pub struct ExecutionGovernor {
+    // None if there are no limits
+    inner: Option<Arc<Inner>>,
+}
+
+/// Permission to execute a component, which is given back when dropped.
+#[must_use]
+pub struct ExecutionPermit {
+    inner: Option<Arc<Inner>>,
+    component: String,
+    // Counts the execution in the trigger status while the permit is held
+    in_flight: Option<InFlight>,
+}
+
+/// The error when an execution is refused because too many executions are
+/// waiting.
+#[derive(Debug)]
+pub struct Overloaded {
+    /// The component whose execution was refused.
+    pub component: String,
+    /// Whether the execution was shed from the queue to make room for a newer
+    /// one, rather than refused on arrival.
+    pub shed: bool,
+    /// Whether the execution was refused because the trigger is draining
+    /// before it stops, rather than because the queue is full.
+    pub draining: bool,
+}
+
+impl fmt::Display for Overloaded {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let how = if self.draining {
+            "was refused because the trigger is shutting down"
+        } else if self.shed {
+            "was shed from the full execution queue"
+        } else {
+            "was refused because the execution queue is full"
+        };
+        write!(f, "execution of component '{}' {how}", self.component)
+    }
+}
+
+impl std::error::Error for Overloaded {}
+
+struct Inner {
+    opts: ConcurrencyOpts,
+    state: Mutex<State>,
+}
+
+#[derive(Default)]
+struct State {
+    running: usize,
+    running_by_component: HashMap<String, usize>,
+    queue: VecDeque<Waiter>,
+}
+
+struct Waiter {
+    component: String,
+    granted: oneshot::Sender<Result<(), Overloaded>>,
+}
+
+impl ExecutionGovernor {
+    pub fn new(opts: ConcurrencyOpts) -> Result<Self> {
+        opts.validate()?;
+        if !opts.is_limited() {
+            return Ok(Self::default());
+        }
+        Ok(Self {
+            inner: Some(Arc::new(Inner {
+                opts,
+                state: Default::default(),
+            })),
+        })
+    }
+
+    /// Waits for permission to execute the given component.
+    pub async fn acquire(&self, component: &str) -> Result<ExecutionPermit, Overloaded> {
+        let permit = || ExecutionPermit {
+            inner: self.inner.clone(),
+            component: component.to_owned(),
+            in_flight: None,
+        };
+        let Some(inner) = &self.inner else {
+            return Ok(permit());
+        };
+
+        let granted = {
+            let mut state = inner.state.lock().unwrap();
+            if inner.has_capacity(&state, component) {
+                state.start(component);
+                return Ok(permit());
+            }
+            inner.make_room(&mut state, component)?;
+            let (tx, rx) = oneshot::channel();
+            state.queue.push_back(Waiter {
+                component: component.to_owned(),
+                granted: tx,
+            });
+            rx
+        };
+
+        let mut waiting = Waiting {
+            inner: inner.clone(),
+            component,
+            granted,
+        };
+        match (&mut waiting.granted).await {
+            Ok(Ok(())) => Ok(permit()),
+            Ok(Err(overloaded)) => Err(overloaded),
+            // Waiters leave the queue only by being granted or refused
+            Err(_) => unreachable!("execution queue dropped a waiter"),
+        }
+    }
+}
+
+impl Inner {
+    fn limit(&self, component: &str) -> Option<usize> {
+        self.opts
+            .components
+            .get(component)
+            .copied()
+            .or(self.opts.max_concurrent_per_component)
+    }
+
+    fn has_capacity(&self, state: &State, component: &str) -> bool {
+        let running = state.running_by_component.get(component).copied();
+        !matches!(self.opts.max_concurrent, Some(max) if state.running >= max)
+            && !matches!(self.limit(component), Some(max) if running.unwrap_or(0) >= max)
+    }
+
+    /// Ensures there is room in the queue for another execution, by refusing
+    /// either the new one or the oldest.
+    fn make_room(&self, state: &mut State, component: &str) -> Result<(), Overloaded> {
+        // Waiters which gave up are no longer queued
+        state.queue.retain(|waiter| !waiter.granted.is_closed());
+        if !matches!(self.opts.max_queued, Some(max) if state.queue.len() >= max) {
+            return Ok(());
+        }
+        let overflow = self.opts.overflow.unwrap_or_default();
+        match (overflow, state.queue.pop_front()) {
+            (QueueOverflow::ShedOldest, Some(oldest)) => {
+                tracing::warn!(
+                    "Shedding queued execution of component '{}'",
+                    oldest.component
+                );
+                let shed = Overloaded {
+                    component: oldest.component,
+                    shed: true,
+                    draining: false,
+                };
+                _ = oldest.granted.send(Err(shed));
+                Ok(())
+            }
+            (_, oldest) => {
+                if let Some(oldest) = oldest {
+                    state.queue.push_front(oldest);
+                }
+                tracing::warn!("Refusing execution of component '{component}': queue is full");
+                Err(Overloaded {
+                    component: component.to_owned(),
+                    shed: false,
+                    draining: false,
+                })
+            }
+        }
+    }
+
+    /// Records the end of an execution and starts any waiters which now fit.
+    fn finish(&self, component: &str) {
+        let mut state = self.state.lock().unwrap();
+        state.stop(component);
+        let mut index = 0;
+        while index < state.queue.len() {
+            if !self.has_capacity(&state, &state.queue[index].component) {
+                index += 1;
+                continue;
+            }
+            let waiter = state.queue.remove(index).unwrap();
+            state.start(&waiter.component);
+            if waiter.granted.send(Ok(())).is_err() {
+                // The waiter gave up
+                state.stop(&waiter.component);
+            }
+        }
+    }
+}
+
+impl State {
+    fn start(&mut self, component: &str) {
+        self.running += 1;
+        *self
+            .running_by_component
+            .entry(component.to_owned())
+            .or_default() += 1;
+    }
+
+    fn stop(&mut self, component: &str) {
+        self.running -= 1;
+        if let Some(running) = self.running_by_component.get_mut(component) {
+            *running -= 1;
+            if *running == 0 {
+                self.running_by_component.remove(component);
+            }
+        }
+    }
+}
+
+impl ExecutionPermit {
+    pub(crate) fn track(mut self, in_flight: InFlight) -> Self {
+        self.in_flight = Some(in_flight);
+        self
+    }
+}
+
+impl Drop for ExecutionPermit {
+    fn drop(&mut self) {
+        self.in_flight.take();
+        if let Some(inner) = &self.inner {
+            inner.finish(&self.component);
+        }
+    }
+}
+
+/// A queued execution, which gives back its slot if it is granted one after
+/// the waiting future is dropped.
+struct Waiting<'a> {
+    inner: Arc<Inner>,
+    component: &'a str,
+    granted: oneshot::Receiver<Result<(), Overloaded>>,
+}
+
+impl Drop for Waiting<'_> {
+    fn drop(&mut self) {
+        self.granted.close();
+        if let Ok(Ok(())) = self.granted.try_recv() {
+            self.inner.finish(self.component);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::FutureExt;
+
+    use super::*;
+
+    fn governor(opts: ConcurrencyOpts) -> ExecutionGovernor {
+        ExecutionGovernor::new(opts).unwrap()
+    }
+
+    #[tokio::test]
+    async fn queued_executions_start_when_permits_are_released() {
+        let governor = governor(ConcurrencyOpts {
+            max_concurrent: Some(2),
+            components: [("slow".to_owned(), 1)].into(),
+            ..Default::default()
+        });
+        let slow = governor.acquire("slow").await.unwrap();
+        let mut slow_queued = Box::pin(governor.acquire("slow"));
+        assert!((&mut slow_queued).now_or_never().is_none());
+
+        // Other components may still run up to the app limit
+        let fast = governor.acquire("fast").await.unwrap();
+        let mut fast_queued = Box::pin(governor.acquire("fast"));
+        assert!((&mut fast_queued).now_or_never().is_none());
+
+        drop(slow);
+        let _slow = slow_queued.await.unwrap();
+        assert!((&mut fast_queued).now_or_never().is_none());
+        drop(fast);
+        fast_queued.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn full_queue_rejects_new_executions() {
+        let governor = governor(ConcurrencyOpts {
+            max_concurrent: Some(1),
+            max_queued: Some(1),
+            ..Default::default()
+        });
+        let _running = governor.acquire("a").await.unwrap();
+        let mut queued = Box::pin(governor.acquire("a"));
+        assert!((&mut queued).now_or_never().is_none());
+
+        let err = governor.acquire("b").await.err().unwrap();
+        assert_eq!(err.component, "b");
+        assert!(!err.shed);
+    }
+
+    #[tokio::test]
+    async fn full_queue_sheds_oldest_execution() {
+        let governor = governor(ConcurrencyOpts {
+            max_concurrent: Some(1),
+            max_queued: Some(1),
+            overflow: Some(QueueOverflow::ShedOldest),
+            ..Default::default()
+        });
+        let running = governor.acquire("a").await.unwrap();
+        let mut oldest = Box::pin(governor.acquire("a"));
+        assert!((&mut oldest).now_or_never().is_none());
+        let mut newest = Box::pin(governor.acquire("b"));
+        assert!((&mut newest).now_or_never().is_none());
+
+        let err = oldest.await.err().unwrap();
+        assert_eq!(err.component, "a");
+        assert!(err.shed);
+
+        drop(running);
+        newest.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn abandoned_waiters_do_not_hold_permits() {
+        let governor = governor(ConcurrencyOpts {
+            max_concurrent: Some(1),
+            ..Default::default()
+        });
+        let running = governor.acquire("a").await.unwrap();
+        let mut abandoned = Box::pin(governor.acquire("a"));
+        assert!((&mut abandoned).now_or_never().is_none());
+        drop(abandoned);
+        drop(running);
+        assert!(governor.acquire("a").now_or_never().is_some());
+    }
+}
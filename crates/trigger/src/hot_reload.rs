@@ -0,0 +1,117 @@
+//! Replacement of a running trigger's components when they are rebuilt.
+//!
+//! With hot reloading enabled, each instantiation first checks whether the
+//! component's local Wasm file has changed since it was loaded, and if so
+//! pre-instantiates the new file in place of the old. Listeners and
+//! subscriptions are untouched, so e.g. `spin watch` can pick up a rebuilt
+//! component without restarting `spin up`. If the new file fails to load,
+//! the previous version keeps serving until the file changes again.
+//!
+//! A trigger which starts serving while its components are still loading
+//! holds them as pending, to be prepared in the background or when first
+//! executed, whichever comes first.
+
+use std::path::PathBuf;
+use std::sync::{Arc, RwLock};
+use std::time::SystemTime;
+
+use spin_app::AppComponent;
+use spin_common::url::parse_file_url;
+
+/// A pre-instantiated component, which may be replaced when hot reloading,
+/// or which may not have been prepared yet.
+pub(crate) struct PreparedComponent<P> {
+    pre: RwLock<Option<Arc<P>>>,
+    // The local file the component was loaded from, if any
+    source: Option<PathBuf>,
+    // When the source was last modified as of the last (attempted) load.
+    // Held while reloading, so a change is only reloaded once.
+    loaded: tokio::sync::Mutex<Option<SystemTime>>,
+}
+
+impl<P> PreparedComponent<P> {
+    pub fn new(pre: P, component: &AppComponent) -> Self {
+        let prepared = Self::pending(component);
+        *prepared.pre.write().unwrap() = Some(Arc::new(pre));
+        prepared
+    }
+
+    /// Returns a component which has yet to be prepared.
+    pub fn pending(component: &AppComponent) -> Self {
+        let source = component
+            .source()
+            .content
+            .source
+            .as_deref()
+            .filter(|url| url.starts_with("file:"))
+            .and_then(|url| parse_file_url(url).ok());
+        let loaded = source.as_deref().and_then(modified);
+        Self {
+            pre: RwLock::new(None),
+            source,
+            loaded: tokio::sync::Mutex::new(loaded),
+        }
+    }
+
+    /// Returns the current pre-instantiated component, if it has been
+    /// prepared.
+    pub fn current(&self) -> Option<Arc<P>> {
+        self.pre.read().unwrap().clone()
+    }
+
+    /// Returns the current pre-instantiated component, first preparing it
+    /// with `load` if it hasn't been yet. Concurrent callers wait for the
+    /// first to prepare it; if that fails, the next tries again.
+    pub async fn get_or_prepare<F>(&self, load: F) -> anyhow::Result<Arc<P>>
+    where
+        F: std::future::Future<Output = anyhow::Result<P>>,
+    {
+        if let Some(pre) = self.current() {
+            return Ok(pre);
+        }
+        let mut loaded = self.loaded.lock().await;
+        if let Some(pre) = self.current() {
+            return Ok(pre);
+        }
+        let modified = self.source.as_deref().and_then(modified);
+        let pre = Arc::new(load.await?);
+        *loaded = modified;
+        *self.pre.write().unwrap() = Some(pre.clone());
+        Ok(pre)
+    }
+
+    /// Replaces the component with the result of `load` if its source has
+    /// changed since it was last loaded.
+    pub async fn reload_if_changed<F>(&self, component_id: &str, load: F)
+    where
+        F: std::future::Future<Output = anyhow::Result<P>>,
+    {
+        let Some(source) = &self.source else {
+            return;
+        };
+        let Some(modified) = modified(source) else {
+            return;
+        };
+        let mut loaded = self.loaded.lock().await;
+        if *loaded == Some(modified) {
+            return;
+        }
+        // Failed loads are not retried until the file changes again
+        *loaded = Some(modified);
+        match load.await {
+            Ok(pre) => {
+                *self.pre.write().unwrap() = Some(Arc::new(pre));
+                terminal::step!("Reloaded", "component '{component_id}'");
+            }
+            Err(err) => {
+                terminal::warn!(
+                    "Failed to reload component '{component_id}'; the previous version is still in use: {err:#}"
+                );
+            }
+        }
+    }
+}
+
+fn modified(path: &std::path::Path) -> Option<SystemTime> {
+    std::fs::metadata(path).and_then(|m| m.modified()).ok()
+}
@@ -1,23 +1,77 @@
+pub mod admin;
 pub mod cli;
+mod governor;
+mod hot_reload;
 pub mod loader;
 pub mod network;
+mod privileges;
+mod profiling;
+mod prometheus;
+pub mod retry;
 mod runtime_config;
+pub mod sandbox;
+pub mod status;
 mod stdio;
 
-use std::{collections::HashMap, marker::PhantomData};
+use std::{
+    collections::{HashMap, HashSet},
+    marker::PhantomData,
+    num::NonZeroUsize,
+    path::PathBuf,
+    sync::Arc,
+    time::{Duration, Instant},
+};
 
 use anyhow::{Context, Result};
 pub use async_trait::async_trait;
+use futures::{StreamExt, TryStreamExt};
 use runtime_config::llm::LLmOptions;
 use serde::de::DeserializeOwned;
 
-use spin_app::{App, AppComponent, AppLoader, AppTrigger, Loader, OwnedApp, APP_NAME_KEY};
+use spin_app::{
+    App, AppComponent, AppLoader, AppTrigger, Loader, MetadataKey, OwnedApp, APP_NAME_KEY,
+};
+pub use spin_cassette::Cassette;
 use spin_core::{
     Config, Engine, EngineBuilder, Instance, InstancePre, OutboundWasiHttpHandler, Store,
     StoreBuilder, WasiVersion,
 };
+use spin_manifest::schema::v2::{ComponentPreparation, ComponentResources};
+pub use spin_telemetry::events::{RuntimeEvent, RuntimeEventHook};
+
+pub use crate::governor::{ExecutionGovernor, ExecutionPermit, Overloaded};
+use crate::hot_reload::PreparedComponent;
+pub use crate::runtime_config::{
+    blob_container::{
+        AzureBlobContainerOpts, BlobContainerOpts, GcsBlobContainerOpts, LocalBlobContainerOpts,
+        S3BlobContainerOpts,
+    },
+    concurrency::{ConcurrencyOpts, QueueOverflow},
+    key_value::{
+        AzureCosmosConfig, KeyValueStoreOpts, RedisKeyValueStoreOpts, SpinKeyValueStoreOpts,
+    },
+    keys::{
+        AwsKmsKeyProviderOpts, AzureKeyVaultKeyProviderOpts, EnvKeyProviderOpts,
+        FileKeyProviderOpts, KeyProviderOpts,
+    },
+    observability::ObservabilityOpts,
+    security::SignatureVerification,
+    sqlite::{LibsqlOpts, SpinSqliteDatabaseOpts, SqliteDatabaseOpts},
+    variables_provider::{
+        EnvVariablesProviderOpts, VariablesProviderOpts, VaultVariablesProviderOpts,
+    },
+    RuntimeConfig, RuntimeConfigBuilder, RuntimeConfigSummary,
+};
+pub use crate::status::TriggerStatus;
+
+/// MetadataKey for the URL the application was loaded from.
+const ORIGIN_KEY: MetadataKey = MetadataKey::new("origin");
 
-pub use crate::runtime_config::RuntimeConfig;
+/// MetadataKey for the resources a component declares in its manifest.
+pub const RESOURCES_KEY: MetadataKey<ComponentResources> = MetadataKey::new("resources");
+
+/// MetadataKey for when a component is prepared, if not at startup.
+pub const PREPARE_KEY: MetadataKey<ComponentPreparation> = MetadataKey::new("prepare");
 
 #[async_trait]
 pub trait TriggerExecutor: Sized + Send + Sync {
@@ -41,6 +95,12 @@ pub trait TriggerExecutor: Sized + Send + Sync {
     fn supported_host_requirements() -> Vec<&'static str> {
         Vec::new()
     }
+
+    /// Whether the trigger binds ports to listen on when it runs. Such
+    /// triggers call [`TriggerAppEngine::listening`] once they have.
+    fn listens() -> bool {
+        false
+    }
 }
 
 /// Helper type alias to project the `Instance` of a given `TriggerExecutor`.
@@ -88,11 +148,37 @@ where
     }
 }
 
+/// How a trigger prepares its app's components when it starts.
+#[derive(Clone, Debug)]
+pub struct StartupOptions {
+    /// The most components to load, compile and pre-instantiate at once.
+    pub parallelism: usize,
+    /// Start running the trigger before its components are prepared. They
+    /// are then prepared in the background, or when first executed, and are
+    /// not ready until then.
+    pub serve_while_loading: bool,
+}
+
+impl Default for StartupOptions {
+    fn default() -> Self {
+        Self {
+            parallelism: std::thread::available_parallelism().map_or(1, NonZeroUsize::get),
+            serve_while_loading: false,
+        }
+    }
+}
+
 pub struct TriggerExecutorBuilder<Executor: TriggerExecutor> {
     loader: AppLoader,
     config: Config,
     hooks: Vec<Box<dyn TriggerHooks>>,
     disable_default_host_components: bool,
+    cassette: Option<Arc<Cassette>>,
+    hot_reload: bool,
+    print_timing: bool,
+    startup: StartupOptions,
+    status: TriggerStatus,
+    verified_signature: Option<SignatureVerification>,
     _phantom: PhantomData<Executor>,
 }
 
@@ -104,6 +190,12 @@ impl<Executor: TriggerExecutor> TriggerExecutorBuilder<Executor> {
             config: Default::default(),
             hooks: Default::default(),
             disable_default_host_components: false,
+            cassette: None,
+            hot_reload: false,
+            print_timing: false,
+            startup: Default::default(),
+            status: TriggerStatus::new(Executor::TRIGGER_TYPE),
+            verified_signature: None,
             _phantom: PhantomData,
         }
     }
@@ -125,6 +217,53 @@ impl<Executor: TriggerExecutor> TriggerExecutorBuilder<Executor> {
         self
     }
 
+    /// Calls `hook` with every [`RuntimeEvent`] of this process. Unlike
+    /// [`TriggerHooks`], event hooks are process-wide and see events from
+    /// host components such as outbound connections.
+    pub fn event_hook(&mut self, hook: impl RuntimeEventHook) -> &mut Self {
+        spin_telemetry::events::register(hook);
+        self
+    }
+
+    /// Records components' outbound HTTP, Redis and Postgres interactions
+    /// to `cassette`, or replays them from it without touching the network.
+    pub fn cassette(&mut self, cassette: Arc<Cassette>) -> &mut Self {
+        self.cassette = Some(cassette);
+        self
+    }
+
+    /// Trust the app's signature as already verified, e.g. before the
+    /// trigger entered its sandbox, rather than verifying it while building.
+    pub fn verified_signature(&mut self, signature: SignatureVerification) -> &mut Self {
+        self.verified_signature = Some(signature);
+        self
+    }
+
+    /// Reload components whose local Wasm files change while the trigger is
+    /// running.
+    pub fn hot_reload(&mut self) -> &mut Self {
+        self.hot_reload = true;
+        self
+    }
+
+    /// Print where the time to start each component went: when it is
+    /// prepared at startup, and when it is first instantiated.
+    pub fn print_timing(&mut self) -> &mut Self {
+        self.print_timing = true;
+        self
+    }
+
+    /// Sets how the app's components are prepared when the trigger starts.
+    pub fn startup_options(&mut self, options: StartupOptions) -> &mut Self {
+        self.startup = options;
+        self
+    }
+
+    /// Returns the status of the trigger which will be built.
+    pub fn status(&self) -> TriggerStatus {
+        self.status.clone()
+    }
+
     pub async fn build(
         mut self,
         app_uri: String,
@@ -138,6 +277,9 @@ impl<Executor: TriggerExecutor> TriggerExecutorBuilder<Executor> {
 
         let engine = {
             let mut builder = Engine::builder(&self.config)?;
+            // Triggers only set deadlines with `Store::set_deadline`, so
+            // the engine need only tick often while one is set
+            builder.idle_epoch_tick_interval(Some(spin_core::DEFAULT_IDLE_EPOCH_TICK_INTERVAL));
 
             if !self.disable_default_host_components {
                 // Wasmtime 17: WASI@0.2.0
@@ -156,6 +298,7 @@ impl<Executor: TriggerExecutor> TriggerExecutorBuilder<Executor> {
                     &mut builder,
                     outbound_redis::OutboundRedisComponent {
                         resolver: resolver_cell.clone(),
+                        cassette: self.cassette.clone(),
                     },
                 )?;
                 self.loader.add_dynamic_host_component(
@@ -164,6 +307,20 @@ impl<Executor: TriggerExecutor> TriggerExecutorBuilder<Executor> {
                         resolver: resolver_cell.clone(),
                     },
                 )?;
+                self.loader.add_dynamic_host_component(
+                    &mut builder,
+                    outbound_nats::OutboundNatsComponent {
+                        resolver: resolver_cell.clone(),
+                    },
+                )?;
+                self.loader.add_dynamic_host_component(
+                    &mut builder,
+                    runtime_config::smtp::build_component(&runtime_config, resolver_cell.clone())?,
+                )?;
+                self.loader.add_dynamic_host_component(
+                    &mut builder,
+                    runtime_config::message_broker::build_component(&runtime_config).await?,
+                )?;
                 self.loader.add_dynamic_host_component(
                     &mut builder,
                     outbound_mysql::OutboundMysqlComponent {
@@ -174,8 +331,11 @@ impl<Executor: TriggerExecutor> TriggerExecutorBuilder<Executor> {
                     &mut builder,
                     outbound_pg::OutboundPgComponent {
                         resolver: resolver_cell.clone(),
+                        cassette: self.cassette.clone(),
                     },
                 )?;
+                self.loader
+                    .add_dynamic_host_component(&mut builder, spin_observe::ObserveComponent)?;
                 self.loader.add_dynamic_host_component(
                     &mut builder,
                     runtime_config::llm::build_component(&runtime_config, init_data.llm.use_gpu)
@@ -194,6 +354,10 @@ impl<Executor: TriggerExecutor> TriggerExecutorBuilder<Executor> {
                     runtime_config::sqlite::build_component(&runtime_config, &init_data.sqlite)
                         .await?,
                 )?;
+                self.loader.add_dynamic_host_component(
+                    &mut builder,
+                    runtime_config::blob_container::build_component(&runtime_config)?,
+                )?;
                 self.loader.add_dynamic_host_component(
                     &mut builder,
                     outbound_http::OutboundHttpComponent {
@@ -223,6 +387,13 @@ impl<Executor: TriggerExecutor> TriggerExecutorBuilder<Executor> {
 
         let app_name = app.borrowed().require_metadata(APP_NAME_KEY)?;
 
+        // Refuse untrusted apps before any of their components are prepared
+        let security = runtime_config.security();
+        let signature = match self.verified_signature.take() {
+            Some(signature) => Some(security.check_verified(app.borrowed(), signature)?),
+            None => security.verify_app(app.borrowed())?,
+        };
+
         let resolver =
             spin_variables::make_resolver(app.borrowed(), runtime_config.variables_providers())?;
         let prepared_resolver = std::sync::Arc::new(resolver.prepare().await?);
@@ -230,15 +401,35 @@ impl<Executor: TriggerExecutor> TriggerExecutorBuilder<Executor> {
             .set(prepared_resolver.clone())
             .map_err(|_| anyhow::anyhow!("resolver cell was already set!"))?;
 
+        if let Some(cassette) = self.cassette.take() {
+            self.hooks.push(Box::new(CassetteHooks(cassette)));
+        }
         self.hooks
             .iter_mut()
             .try_for_each(|h| h.app_loaded(app.borrowed(), &runtime_config, &prepared_resolver))?;
 
         // Run trigger executor
-        Executor::new(
-            TriggerAppEngine::new(engine, app_name, app, self.hooks, &prepared_resolver).await?,
+        let mut app_engine = TriggerAppEngine::new(
+            engine,
+            app_name,
+            app,
+            self.hooks,
+            &prepared_resolver,
+            runtime_config,
+            self.status,
+            self.startup,
         )
-        .await
+        .await?;
+        app_engine.hot_reload = self.hot_reload;
+        app_engine.signature = signature;
+        if self.print_timing {
+            app_engine.print_timing = true;
+            // Components left pending report their timing in the trigger status
+            if !app_engine.is_serving_while_loading() {
+                print_preparation_timing(&app_engine.status, self.config.is_memory_init_cow());
+            }
+        }
+        Executor::new(app_engine).await
     }
 }
 
@@ -280,20 +471,44 @@ pub struct TriggerAppEngine<Executor: TriggerExecutor> {
     // Trigger configs for this trigger type, with order matching `app.triggers_with_type(Executor::TRIGGER_TYPE)`
     trigger_configs: Vec<Executor::TriggerConfig>,
     // Map of {Component ID -> InstancePre} for each component.
-    component_instance_pres: HashMap<String, Executor::InstancePre>,
+    component_instance_pres: HashMap<String, PreparedComponent<Executor::InstancePre>>,
+    // Components which are prepared on their first execution
+    lazy_components: HashSet<String>,
     // Resolver for value template expressions
     resolver: std::sync::Arc<spin_expressions::PreparedResolver>,
+    // Runtime config the app was loaded with
+    runtime_config: RuntimeConfig,
+    // Limits on concurrent executions of the app's components
+    governor: ExecutionGovernor,
+    // Status of the app's components, for operators
+    status: TriggerStatus,
+    // Whether components are reloaded when their sources change
+    hot_reload: bool,
+    // Whether the first instantiation of each component is printed
+    print_timing: bool,
+    // How components are prepared at startup
+    startup: StartupOptions,
+    // The app's verified signature, if signed apps are required
+    signature: Option<SignatureVerification>,
 }
 
 impl<Executor: TriggerExecutor> TriggerAppEngine<Executor> {
     /// Returns a new TriggerAppEngine. May return an error if trigger config validation or
     /// component pre-instantiation fails.
+    ///
+    /// Components are prepared concurrently, up to `startup.parallelism` at a
+    /// time, unless `startup.serve_while_loading` leaves them pending; see
+    /// [`Self::prepare_pending`]. Components with `prepare = "lazy"` are left
+    /// until they are first executed.
     pub async fn new(
         engine: Engine<Executor::RuntimeData>,
         app_name: String,
         app: OwnedApp,
         hooks: Vec<Box<dyn TriggerHooks>>,
         resolver: &std::sync::Arc<spin_expressions::PreparedResolver>,
+        runtime_config: RuntimeConfig,
+        status: TriggerStatus,
+        startup: StartupOptions,
     ) -> Result<Self>
     where
         <Executor as TriggerExecutor>::TriggerConfig: DeserializeOwned,
@@ -311,7 +526,8 @@ impl<Executor: TriggerExecutor> TriggerAppEngine<Executor> {
             })
             .collect::<Result<Vec<_>>>()?;
 
-        let mut component_instance_pres = HashMap::default();
+        let mut used_components = vec![];
+        let mut lazy_components = HashSet::new();
         for component in app.borrowed().components() {
             let id = component.id();
             // There is an issue here for triggers that consider the trigger config during
@@ -322,12 +538,13 @@ impl<Executor: TriggerExecutor> TriggerAppEngine<Executor> {
                 .find(|(c, _)| c == id)
                 .map(|(_, cfg)| cfg);
             if let Some(config) = trigger_config {
-                component_instance_pres.insert(
-                    id.to_owned(),
-                    Executor::InstancePre::instantiate_pre(&engine, &component, config)
-                        .await
-                        .with_context(|| format!("Failed to instantiate component '{id}'"))?,
-                );
+                status.add_component(id);
+                if component.get_metadata(PREPARE_KEY)?.unwrap_or_default()
+                    == ComponentPreparation::Lazy
+                {
+                    lazy_components.insert(id.to_owned());
+                }
+                used_components.push((component, config));
             } else {
                 tracing::warn!(
                     "component '{id}' is not used by any triggers in app '{app_name}'",
@@ -337,6 +554,42 @@ impl<Executor: TriggerExecutor> TriggerAppEngine<Executor> {
             }
         }
 
+        let (eager, pending): (Vec<_>, Vec<_>) = used_components
+            .iter()
+            .partition(|(c, _)| !startup.serve_while_loading && !lazy_components.contains(c.id()));
+        let (engine_ref, status_ref) = (&engine, &status);
+        let prepare = eager.into_iter().map(|(component, config)| async move {
+            let id = component.id();
+            let pre = instantiate_pre::<Executor>(engine_ref, component, config, status_ref)
+                .await
+                .with_context(|| format!("Failed to instantiate component '{id}'"))?;
+            anyhow::Ok((id.to_owned(), PreparedComponent::new(pre, component)))
+        });
+        let mut component_instance_pres: HashMap<_, _> = futures::stream::iter(prepare)
+            .buffer_unordered(startup.parallelism.max(1))
+            .try_collect()
+            .await?;
+        component_instance_pres.extend(pending.into_iter().map(|(component, _)| {
+            let id = component.id().to_owned();
+            (id, PreparedComponent::pending(component))
+        }));
+        // Release the borrows of `app` and `trigger_configs`
+        drop(used_components);
+
+        // Components' own concurrency limits apply unless the runtime config
+        // sets limits for them
+        let mut concurrency = runtime_config.concurrency();
+        for component in app.borrowed().components() {
+            let resources = component.get_metadata(RESOURCES_KEY)?.unwrap_or_default();
+            if let Some(max) = resources.max_concurrent {
+                concurrency
+                    .components
+                    .entry(component.id().to_owned())
+                    .or_insert(max);
+            }
+        }
+        let governor = ExecutionGovernor::new(concurrency).context("Invalid concurrency limits")?;
+
         Ok(Self {
             engine,
             app_name,
@@ -344,7 +597,15 @@ impl<Executor: TriggerExecutor> TriggerAppEngine<Executor> {
             hooks,
             trigger_configs: trigger_configs.into_iter().map(|(_, v)| v).collect(),
             component_instance_pres,
+            lazy_components,
             resolver: resolver.clone(),
+            runtime_config,
+            governor,
+            status,
+            hot_reload: false,
+            print_timing: false,
+            startup,
+            signature: None,
         })
     }
 
@@ -353,10 +614,33 @@ impl<Executor: TriggerExecutor> TriggerAppEngine<Executor> {
         self.app.borrowed()
     }
 
+    /// Returns the directory containing the application manifest, or `None`
+    /// if the application was not loaded from a local file.
+    pub fn app_dir(&self) -> Result<Option<PathBuf>> {
+        match self.app().get_metadata(ORIGIN_KEY)? {
+            Some(origin) if origin.starts_with("file:") => {
+                let manifest = spin_common::url::parse_file_url(&origin)?;
+                Ok(manifest.parent().map(|dir| dir.to_owned()))
+            }
+            _ => Ok(None),
+        }
+    }
+
     pub fn trigger_metadata<T: DeserializeOwned + Default>(&self) -> spin_app::Result<Option<T>> {
         self.app().get_trigger_metadata(Executor::TRIGGER_TYPE)
     }
 
+    /// Returns the RuntimeConfig the app was loaded with.
+    pub fn runtime_config(&self) -> &RuntimeConfig {
+        &self.runtime_config
+    }
+
+    /// Returns the typed `[<trigger type>_trigger]` runtime config options
+    /// for this executor type.
+    pub fn trigger_runtime_opts<T: DeserializeOwned + Default>(&self) -> Result<T> {
+        self.runtime_config.trigger_opts(Executor::TRIGGER_TYPE)
+    }
+
     /// Returns AppTriggers and typed TriggerConfigs for this executor type.
     pub fn trigger_configs(&self) -> impl Iterator<Item = (AppTrigger, &Executor::TriggerConfig)> {
         self.app()
@@ -364,6 +648,43 @@ impl<Executor: TriggerExecutor> TriggerAppEngine<Executor> {
             .zip(&self.trigger_configs)
     }
 
+    /// Waits for permission to execute the given component within the
+    /// configured concurrency limits. The permit must be held until the
+    /// execution finishes. Executions are refused once the trigger is
+    /// [draining](TriggerStatus::drain).
+    pub async fn acquire_permit(&self, component_id: &str) -> Result<ExecutionPermit, Overloaded> {
+        if self.status.is_draining() {
+            return Err(Overloaded {
+                component: component_id.to_owned(),
+                shed: false,
+                draining: true,
+            });
+        }
+        let queued = self.status.queue(component_id);
+        let permit = self.governor.acquire(component_id).await;
+        drop(queued);
+        Ok(permit?.track(self.status.start(component_id)))
+    }
+
+    /// Returns the app's verified signature, if the runtime config requires
+    /// signed apps.
+    pub fn signature(&self) -> Option<&SignatureVerification> {
+        self.signature.as_ref()
+    }
+
+    /// Notifies the trigger's hooks that it has bound the ports it listens
+    /// on, and is about to serve. Triggers which [listen](TriggerExecutor::listens)
+    /// must call this once, and fail if it does.
+    pub fn listening(&self) -> Result<()> {
+        self.hooks.iter().try_for_each(|hooks| hooks.listening())
+    }
+
+    /// Returns the status of the trigger's components, to which triggers
+    /// report subscriptions and errors.
+    pub fn status(&self) -> &TriggerStatus {
+        &self.status
+    }
+
     /// Returns a new StoreBuilder for the given component ID.
     pub fn store_builder(
         &self,
@@ -372,6 +693,11 @@ impl<Executor: TriggerExecutor> TriggerAppEngine<Executor> {
     ) -> Result<StoreBuilder> {
         let mut builder = self.engine.store_builder(wasi_version);
         let component = self.get_component(component_id)?;
+        let resources = component.get_metadata(RESOURCES_KEY)?.unwrap_or_default();
+        if let Some(max_memory_mb) = resources.max_memory_mb {
+            let max_memory_size = max_memory_mb.saturating_mul(1024 * 1024);
+            builder.max_memory_size(max_memory_size.try_into().unwrap_or(usize::MAX));
+        }
         self.hooks
             .iter()
             .try_for_each(|h| h.component_store_builder(&component, &mut builder))?;
@@ -399,23 +725,126 @@ impl<Executor: TriggerExecutor> TriggerAppEngine<Executor> {
         // Build Store
         component.apply_store_config(&mut store_builder).await?;
         let mut store = store_builder.build()?;
+        let resources = component.get_metadata(RESOURCES_KEY)?.unwrap_or_default();
+        if let Some(timeout_ms) = resources.timeout_ms {
+            store.set_deadline(Instant::now() + Duration::from_millis(timeout_ms));
+        }
 
         // Instantiate
-        let pre = self
+        let prepared = self
             .component_instance_pres
             .get(component_id)
             .expect("component_instance_pres missing valid component_id");
+        let config = self.component_trigger_config(component_id);
+        let load = || instantiate_pre::<Executor>(&self.engine, &component, config, &self.status);
+        let mut pre = prepared
+            .get_or_prepare(load())
+            .await
+            .with_context(|| format!("Failed to instantiate component '{component_id}'"))?;
+        if self.hot_reload {
+            prepared.reload_if_changed(component_id, load()).await;
+            pre = prepared.current().unwrap_or(pre);
+        }
 
+        let started = Instant::now();
         let instance = pre.instantiate(&mut store).await.with_context(|| {
             format!(
                 "app {:?} component {:?} instantiation failed",
                 self.app_name, component_id
             )
         })?;
+        let duration = started.elapsed();
+        if self.status.record_instantiation(component_id, duration) && self.print_timing {
+            terminal::text!(
+                "Component {component_id} first instantiated in {:.1}ms",
+                duration.as_secs_f64() * 1000.0
+            );
+        }
+        spin_telemetry::metrics::record_instantiation(
+            Executor::TRIGGER_TYPE,
+            component_id,
+            duration,
+        );
+        spin_telemetry::events::emit(RuntimeEvent::InstanceCreated {
+            trigger_type: Executor::TRIGGER_TYPE,
+            component_id,
+            duration,
+        });
 
         Ok((instance, store))
     }
 
+    /// Returns true if components are reloaded when their sources change, in
+    /// which case triggers should not reuse instances across events.
+    pub fn is_hot_reloading(&self) -> bool {
+        self.hot_reload
+    }
+
+    /// Returns true if the given component has been pre-instantiated.
+    pub fn is_prepared(&self, component_id: &str) -> bool {
+        self.component_instance_pres
+            .get(component_id)
+            .is_some_and(|prepared| prepared.current().is_some())
+    }
+
+    /// Returns true if the trigger should start serving before its
+    /// components are prepared, calling [`Self::prepare_pending`] meanwhile.
+    pub fn is_serving_while_loading(&self) -> bool {
+        self.startup.serve_while_loading
+    }
+
+    /// Returns true if the given component is prepared on its first
+    /// execution rather than at startup.
+    pub fn is_lazy(&self, component_id: &str) -> bool {
+        self.lazy_components.contains(component_id)
+    }
+
+    /// Prepares any components which have yet to be, other than lazy ones,
+    /// up to the startup parallelism at a time. Failures are reported in the
+    /// trigger status, and the component is tried again when it is next
+    /// executed.
+    pub async fn prepare_pending(&self) {
+        let parallelism = self.startup.parallelism.max(1);
+        futures::stream::iter(&self.component_instance_pres)
+            .for_each_concurrent(parallelism, |(component_id, prepared)| async move {
+                if prepared.current().is_some() || self.is_lazy(component_id) {
+                    return;
+                }
+                if let Err(err) = self.prepare_component(component_id).await {
+                    terminal::error!("Failed to prepare component '{component_id}': {err:#}");
+                    self.status.record_error(component_id, &err);
+                }
+            })
+            .await;
+    }
+
+    /// Prepares the given component if it has yet to be, lazy or not.
+    /// Concurrent calls for the same component share one preparation.
+    pub async fn prepare_component(&self, component_id: &str) -> Result<()> {
+        let prepared = self
+            .component_instance_pres
+            .get(component_id)
+            .with_context(|| {
+                format!("app {:?} has no component {component_id:?}", self.app_name)
+            })?;
+        if prepared.current().is_some() {
+            return Ok(());
+        }
+        let component = self.get_component(component_id)?;
+        let config = self.component_trigger_config(component_id);
+        let load = instantiate_pre::<Executor>(&self.engine, &component, config, &self.status);
+        prepared.get_or_prepare(load).await?;
+        Ok(())
+    }
+
+    // Returns the trigger config of a prepared component.
+    fn component_trigger_config(&self, component_id: &str) -> &Executor::TriggerConfig {
+        self.trigger_configs()
+            .find(|(trigger, _)| matches!(trigger.component(), Ok(c) if c.id() == component_id))
+            .map(|(_, config)| config)
+            .expect("prepared component has no trigger config")
+    }
+
     pub fn get_component(&self, component_id: &str) -> Result<AppComponent> {
         self.app().get_component(component_id).with_context(|| {
             format!(
@@ -433,6 +862,47 @@ impl<Executor: TriggerExecutor> TriggerAppEngine<Executor> {
     }
 }
 
+/// Prepares a component for instantiation, recording how long it took to
+/// compile and link.
+async fn instantiate_pre<Executor: TriggerExecutor>(
+    engine: &Engine<Executor::RuntimeData>,
+    component: &AppComponent<'_>,
+    config: &Executor::TriggerConfig,
+    status: &TriggerStatus,
+) -> Result<Executor::InstancePre> {
+    let started = Instant::now();
+    let (pre, compilation) = loader::measure_compilation(Executor::InstancePre::instantiate_pre(
+        engine, component, config,
+    ))
+    .await;
+    let pre = pre?;
+    status.record_preparation(component.id(), compilation, started.elapsed());
+    Ok(pre)
+}
+
+// Prints where the time to prepare each component went, for `--timing`.
+fn print_preparation_timing(status: &TriggerStatus, memory_init_cow: bool) {
+    let ms = |time: Option<f64>| format!("{:.1}ms", time.unwrap_or_default());
+    let memory_init = if memory_init_cow {
+        "enabled"
+    } else {
+        "disabled"
+    };
+    terminal::text!("Component timing (copy-on-write memory initialization {memory_init}):");
+    for (component_id, timing) in status.timings() {
+        if timing.pre_instantiation_ms.is_none() {
+            terminal::text!("  {component_id}: prepared on first execution");
+            continue;
+        }
+        terminal::text!(
+            "  {component_id}: compiled in {}, linked in {} (prepared in {})",
+            ms(timing.compile_ms),
+            ms(timing.link_ms),
+            ms(timing.pre_instantiation_ms),
+        );
+    }
+}
+
 /// TriggerHooks allows a Spin environment to hook into a TriggerAppEngine's
 /// configuration and execution processes.
 pub trait TriggerHooks: Send + Sync {
@@ -458,6 +928,26 @@ pub trait TriggerHooks: Send + Sync {
     ) -> Result<()> {
         Ok(())
     }
+
+    /// Called once, after a listening trigger has bound its ports and
+    /// before it serves anything.
+    fn listening(&self) -> Result<()> {
+        Ok(())
+    }
 }
 
 impl TriggerHooks for () {}
+
+// Sends components' outbound HTTP requests through a cassette.
+struct CassetteHooks(Arc<Cassette>);
+
+impl TriggerHooks for CassetteHooks {
+    fn component_store_builder(
+        &self,
+        _component: &AppComponent,
+        store_builder: &mut StoreBuilder,
+    ) -> Result<()> {
+        store_builder.outbound_http_interceptor(self.0.clone());
+        Ok(())
+    }
+}
@@ -1,4 +1,9 @@
-use std::path::PathBuf;
+use std::{
+    cell::Cell,
+    future::Future,
+    path::PathBuf,
+    time::{Duration, Instant},
+};
 
 use anyhow::{ensure, Context, Result};
 use async_trait::async_trait;
@@ -11,6 +16,38 @@ use tokio::fs;
 
 use spin_common::{ui::quoted_path, url::parse_file_url};
 
+tokio::task_local! {
+    // The time spent compiling components in a call to `measure_compilation`.
+    static COMPILATION_TIME: Cell<Duration>;
+}
+
+/// Runs `fut`, returning its output along with the time the [`TriggerLoader`]
+/// spent compiling components, or loading them from the cache, while it ran.
+pub(crate) async fn measure_compilation<F: Future>(fut: F) -> (F::Output, Duration) {
+    COMPILATION_TIME
+        .scope(Cell::new(Duration::ZERO), async {
+            let output = fut.await;
+            (output, COMPILATION_TIME.with(Cell::get))
+        })
+        .await
+}
+
+// Adds the time until it is dropped to the compilation time being measured,
+// if any.
+struct CompilationTimer(Instant);
+
+impl CompilationTimer {
+    fn start() -> Self {
+        Self(Instant::now())
+    }
+}
+
+impl Drop for CompilationTimer {
+    fn drop(&mut self) {
+        _ = COMPILATION_TIME.try_with(|time| time.set(time.get() + self.0.elapsed()));
+    }
+}
+
 /// Compilation status of all components of a Spin application
 pub enum CompilationStatus {
     #[cfg(feature = "unsafe-aot-compilation")]
@@ -88,6 +125,7 @@ impl Loader for TriggerLoader {
         engine: &spin_core::wasmtime::Engine,
         source: &LockedComponentSource,
     ) -> Result<spin_core::Component> {
+        let _timer = CompilationTimer::start();
         let source = source
             .content
             .source
@@ -117,9 +155,16 @@ impl Loader for TriggerLoader {
                     quoted_path(&path)
                 )
             })?;
-            let component = spin_componentize::componentize_if_necessary(&bytes)?;
-            spin_core::Component::new(engine, component.as_ref())
-                .with_context(|| format!("loading module {}", quoted_path(&path)))
+            // Compile on a blocking thread, so that components can be
+            // compiled in parallel, and so as not to hold up a trigger
+            // serving while its components load
+            let engine = engine.clone();
+            tokio::task::spawn_blocking(move || {
+                let component = spin_componentize::componentize_if_necessary(&bytes)?;
+                spin_core::Component::new(&engine, component.as_ref())
+                    .with_context(|| format!("loading module {}", quoted_path(&path)))
+            })
+            .await?
             }
         }
     }
@@ -129,6 +174,7 @@ impl Loader for TriggerLoader {
         engine: &spin_core::wasmtime::Engine,
         source: &LockedComponentSource,
     ) -> Result<spin_core::Module> {
+        let _timer = CompilationTimer::start();
         let source = source
             .content
             .source
@@ -0,0 +1,302 @@
+//! Dropping privileges once a trigger is listening, for running `spin up`
+//! as root under a service manager: the trigger binds privileged ports as
+//! root, then optionally confines itself to a directory with `chroot`, and
+//! switches to an unprivileged user and group before serving anything.
+//!
+//! Triggers which listen report when they have bound their ports by calling
+//! [`TriggerAppEngine::listening`](crate::TriggerAppEngine::listening);
+//! other triggers drop privileges as soon as they are built. Once dropped,
+//! the state and log directories are checked to be writable, so that a
+//! misconfigured service fails at startup rather than on its first write.
+//!
+//! Paths the trigger opens after dropping privileges, such as key-value and
+//! SQLite databases in the state directory, are resolved inside the new root,
+//! and so must exist at the same paths there.
+
+use std::path::PathBuf;
+use std::sync::atomic::AtomicBool;
+use std::sync::Arc;
+
+use anyhow::Result;
+#[cfg(target_os = "linux")]
+use anyhow::{bail, Context};
+#[cfg(target_os = "linux")]
+use spin_common::ui::quoted_path;
+
+use crate::TriggerHooks;
+
+/// A user and group to switch to.
+#[derive(Debug)]
+#[cfg_attr(not(target_os = "linux"), allow(dead_code))]
+pub(crate) struct Credentials {
+    uid: u32,
+    gid: u32,
+    // The user's name, for their supplementary groups; unset for numeric
+    // users with no passwd entry, who get none.
+    name: Option<std::ffi::CString>,
+}
+
+impl Credentials {
+    /// Looks up a user, and optionally a group, given by name or ID. The
+    /// group defaults to the user's primary group.
+    #[cfg(target_os = "linux")]
+    pub fn lookup(user: &str, group: Option<&str>) -> Result<Self> {
+        let (uid, primary_gid, name) = match lookup_user(user)? {
+            Some((uid, gid, name)) => (uid, Some(gid), Some(name)),
+            None => match user.parse() {
+                Ok(uid) => (uid, None, None),
+                Err(_) => bail!("There is no user named {user:?}"),
+            },
+        };
+        let gid = match group {
+            Some(group) => match lookup_group(group)? {
+                Some(gid) => gid,
+                None => group
+                    .parse()
+                    .with_context(|| format!("There is no group named {group:?}"))?,
+            },
+            None => primary_gid.with_context(|| {
+                format!("User {user} has no passwd entry, so --group is required")
+            })?,
+        };
+        Ok(Self { uid, gid, name })
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    pub fn lookup(_user: &str, _group: Option<&str>) -> Result<Self> {
+        anyhow::bail!("--user is only supported on Linux")
+    }
+}
+
+/// How a trigger drops privileges once it is listening. Privileges are
+/// dropped at most once, however many times [`Self::apply`] is called.
+#[derive(Debug)]
+#[cfg_attr(not(target_os = "linux"), allow(dead_code))]
+pub(crate) struct PrivilegeDrop {
+    credentials: Option<Credentials>,
+    chroot: Option<PathBuf>,
+    writable_dirs: Vec<PathBuf>,
+    applied: AtomicBool,
+}
+
+impl PrivilegeDrop {
+    /// Returns `None` if there are no privileges to drop.
+    pub fn new(
+        credentials: Option<Credentials>,
+        chroot: Option<PathBuf>,
+        writable_dirs: Vec<PathBuf>,
+    ) -> Option<Arc<Self>> {
+        if credentials.is_none() && chroot.is_none() {
+            return None;
+        }
+        Some(Arc::new(Self {
+            credentials,
+            chroot,
+            writable_dirs,
+            applied: AtomicBool::new(false),
+        }))
+    }
+
+    /// Whether the trigger confines itself to a directory.
+    pub fn chroots(&self) -> bool {
+        self.chroot.is_some()
+    }
+
+    /// Whether the trigger switches to another user and group.
+    pub fn switches_user(&self) -> bool {
+        self.credentials.is_some()
+    }
+
+    /// Drops privileges, for every thread of the process.
+    #[cfg(target_os = "linux")]
+    pub fn apply(&self) -> Result<()> {
+        if self.applied.swap(true, std::sync::atomic::Ordering::SeqCst) {
+            return Ok(());
+        }
+        if let Some(root) = &self.chroot {
+            let path = path_to_cstring(root)?;
+            // Safety: path is a valid C string
+            check(unsafe { libc::chroot(path.as_ptr()) })
+                .with_context(|| format!("Failed to change root to {}", quoted_path(root)))?;
+            std::env::set_current_dir("/").context("Failed to change to the new root")?;
+        }
+        if let Some(credentials) = &self.credentials {
+            // Supplementary groups go first: setting them needs privileges
+            // which switching user gives up. glibc applies each of these
+            // to every thread.
+            match &credentials.name {
+                // Safety: name is a valid C string
+                Some(name) => check(unsafe { libc::initgroups(name.as_ptr(), credentials.gid) }),
+                // Safety: the list is one valid gid
+                None => check(unsafe { libc::setgroups(1, &credentials.gid) }),
+            }
+            .context("Failed to set supplementary groups")?;
+            // Safety: these take plain IDs
+            check(unsafe { libc::setgid(credentials.gid) })
+                .with_context(|| format!("Failed to switch to group {}", credentials.gid))?;
+            check(unsafe { libc::setuid(credentials.uid) })
+                .with_context(|| format!("Failed to switch to user {}", credentials.uid))?;
+            // Safety: as above
+            if credentials.uid != 0 && unsafe { libc::setuid(0) } == 0 {
+                bail!("Privileges were not dropped: the trigger could switch back to root");
+            }
+            tracing::info!(
+                "Dropped privileges to user {} and group {}",
+                credentials.uid,
+                credentials.gid
+            );
+        }
+        for dir in &self.writable_dirs {
+            check_writable(dir)?;
+        }
+        Ok(())
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    pub fn apply(&self) -> Result<()> {
+        anyhow::bail!("Dropping privileges is only supported on Linux")
+    }
+}
+
+impl TriggerHooks for Arc<PrivilegeDrop> {
+    fn listening(&self) -> Result<()> {
+        self.apply()
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn check(ret: libc::c_int) -> std::io::Result<()> {
+    if ret == 0 {
+        Ok(())
+    } else {
+        Err(std::io::Error::last_os_error())
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn path_to_cstring(path: &std::path::Path) -> Result<std::ffi::CString> {
+    use std::os::unix::ffi::OsStrExt;
+    Ok(std::ffi::CString::new(path.as_os_str().as_bytes())?)
+}
+
+/// Checks that a directory can be written to, creating it if need be.
+#[cfg(target_os = "linux")]
+fn check_writable(dir: &std::path::Path) -> Result<()> {
+    let not_writable = || {
+        format!(
+            "{} is not writable after dropping privileges",
+            quoted_path(dir)
+        )
+    };
+    std::fs::create_dir_all(dir).with_context(not_writable)?;
+    let probe = dir.join(format!(".spin-write-check-{}", std::process::id()));
+    std::fs::write(&probe, b"").with_context(not_writable)?;
+    std::fs::remove_file(&probe).with_context(not_writable)?;
+    Ok(())
+}
+
+// The getpw*_r and getgr*_r functions need a buffer for the entry's strings.
+#[cfg(target_os = "linux")]
+const ENTRY_BUFFER_LEN: usize = 16 * 1024;
+
+/// Looks up a user by name, or by ID if there is no user with that name.
+#[cfg(target_os = "linux")]
+fn lookup_user(user: &str) -> Result<Option<(u32, u32, std::ffi::CString)>> {
+    let mut passwd: libc::passwd = unsafe { std::mem::zeroed() };
+    let mut buffer = vec![0 as libc::c_char; ENTRY_BUFFER_LEN];
+    let mut result = std::ptr::null_mut();
+    let name = std::ffi::CString::new(user)?;
+    // Safety: the pointers are valid for the lengths given
+    let mut ret = unsafe {
+        libc::getpwnam_r(
+            name.as_ptr(),
+            &mut passwd,
+            buffer.as_mut_ptr(),
+            buffer.len(),
+            &mut result,
+        )
+    };
+    if ret == 0 && result.is_null() {
+        if let Ok(uid) = user.parse() {
+            // Safety: as above
+            ret = unsafe {
+                libc::getpwuid_r(
+                    uid,
+                    &mut passwd,
+                    buffer.as_mut_ptr(),
+                    buffer.len(),
+                    &mut result,
+                )
+            };
+        }
+    }
+    if ret != 0 {
+        return Err(std::io::Error::from_raw_os_error(ret))
+            .with_context(|| format!("Failed to look up user {user:?}"));
+    }
+    if result.is_null() {
+        return Ok(None);
+    }
+    // Safety: a found entry's name is a valid C string in the buffer
+    let name = unsafe { std::ffi::CStr::from_ptr(passwd.pw_name) }.to_owned();
+    Ok(Some((passwd.pw_uid, passwd.pw_gid, name)))
+}
+
+/// Looks up a group by name, or by ID if there is no group with that name.
+#[cfg(target_os = "linux")]
+fn lookup_group(group: &str) -> Result<Option<u32>> {
+    let mut entry: libc::group = unsafe { std::mem::zeroed() };
+    let mut buffer = vec![0 as libc::c_char; ENTRY_BUFFER_LEN];
+    let mut result = std::ptr::null_mut();
+    let name = std::ffi::CString::new(group)?;
+    // Safety: the pointers are valid for the lengths given
+    let mut ret = unsafe {
+        libc::getgrnam_r(
+            name.as_ptr(),
+            &mut entry,
+            buffer.as_mut_ptr(),
+            buffer.len(),
+            &mut result,
+        )
+    };
+    if ret == 0 && result.is_null() {
+        if let Ok(gid) = group.parse() {
+            // Safety: as above
+            ret = unsafe {
+                libc::getgrgid_r(
+                    gid,
+                    &mut entry,
+                    buffer.as_mut_ptr(),
+                    buffer.len(),
+                    &mut result,
+                )
+            };
+        }
+    }
+    if ret != 0 {
+        return Err(std::io::Error::from_raw_os_error(ret))
+            .with_context(|| format!("Failed to look up group {group:?}"));
+    }
+    Ok((!result.is_null()).then_some(entry.gr_gid))
+}
+
+#[cfg(all(test, target_os = "linux"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn users_are_looked_up_by_name_or_id() {
+        let root = Credentials::lookup("root", None).unwrap();
+        assert_eq!((root.uid, root.gid), (0, 0));
+        assert_eq!(root.name.unwrap().to_str().unwrap(), "root");
+
+        let by_id = Credentials::lookup("0", Some("0")).unwrap();
+        assert_eq!((by_id.uid, by_id.gid), (0, 0));
+
+        Credentials::lookup("no-such-user-for-spin", None).unwrap_err();
+        Credentials::lookup("4000000", None).unwrap_err();
+        let unnamed = Credentials::lookup("4000000", Some("4000000")).unwrap();
+        assert_eq!((unnamed.uid, unnamed.gid), (4000000, 4000000));
+        assert!(unnamed.name.is_none());
+    }
+}
@@ -0,0 +1,383 @@
+//! Collects CPU profiles of the trigger process in the pprof format, for
+//! finding where time goes in production without attaching a profiler.
+//! Profiles are served on demand at [`PROFILE_PATH`] and, if an interval is
+//! set, written continuously to `profiles/` in the state directory. Enabled
+//! by the `[profiling]` runtime config table.
+//!
+//! Components are JIT-compiled, so their frames have no symbols in the
+//! binary. While profiling is enabled Wasmtime writes a perf map naming the
+//! functions it compiles, and frames in Wasm code are named from that.
+
+use std::convert::Infallible;
+use std::future::pending;
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+use http_body_util::Full;
+use hyper::{
+    body::Bytes, server::conn::http1, service::service_fn, Method, Request, Response, StatusCode,
+};
+use hyper_util::rt::TokioIo;
+use tokio::net::TcpListener;
+
+use crate::runtime_config::RuntimeConfig;
+
+/// The path at which profiles are served.
+pub const PROFILE_PATH: &str = "/debug/pprof/profile";
+
+const PROFILES_DIR: &str = "profiles";
+const DEFAULT_PROFILE_SECONDS: u64 = 30;
+const MAX_PROFILE_SECONDS: u64 = 300;
+
+/// Only one profile is collected at a time: the sampling signal handler is
+/// process-wide.
+static COLLECTING: tokio::sync::Mutex<()> = tokio::sync::Mutex::const_new(());
+
+pub(crate) struct Profiler {
+    trigger_type: String,
+    listen: Option<SocketAddr>,
+    interval: Option<Duration>,
+    dir: Option<PathBuf>,
+    max_files: usize,
+    frequency: i32,
+}
+
+impl Profiler {
+    /// Returns the profiler configured for the trigger type, if profiling
+    /// is enabled.
+    pub fn new(trigger_type: &str, runtime_config: &RuntimeConfig) -> Result<Option<Self>> {
+        let opts = runtime_config.profiling();
+        if !opts.is_enabled(trigger_type) {
+            return Ok(None);
+        }
+        let interval = opts.interval();
+        let dir = match interval {
+            Some(_) => Some(
+                runtime_config
+                    .state_dir()
+                    .context("Continuous profiling requires a state directory")?
+                    .join(PROFILES_DIR),
+            ),
+            None => None,
+        };
+        Ok(Some(Self {
+            trigger_type: trigger_type.to_owned(),
+            listen: opts.listen(trigger_type),
+            interval,
+            dir,
+            max_files: opts.max_files(),
+            frequency: opts.frequency(),
+        }))
+    }
+
+    /// Serves and writes profiles until the trigger stops.
+    pub async fn run(self) -> Result<()> {
+        let serve = async {
+            match self.listen {
+                Some(addr) => self.serve(addr).await,
+                None => pending().await,
+            }
+        };
+        let write = async {
+            match (self.interval, &self.dir) {
+                (Some(interval), Some(dir)) => self.write_continuously(interval, dir).await,
+                _ => pending().await,
+            }
+        };
+        tokio::try_join!(serve, write)?;
+        Ok(())
+    }
+
+    async fn serve(&self, addr: SocketAddr) -> Result<()> {
+        let listener = TcpListener::bind(addr)
+            .await
+            .with_context(|| format!("Unable to listen for profiling requests on {addr}"))?;
+        let addr = listener.local_addr()?;
+        terminal::text!(
+            "Serving {} trigger CPU profiles on http://{addr}{PROFILE_PATH}",
+            self.trigger_type
+        );
+        let frequency = self.frequency;
+        loop {
+            let (stream, _) = listener.accept().await?;
+            tokio::spawn(async move {
+                let service = service_fn(move |req| async move {
+                    Ok::<_, Infallible>(respond(&req, frequency).await)
+                });
+                if let Err(err) = http1::Builder::new()
+                    .serve_connection(TokioIo::new(stream), service)
+                    .await
+                {
+                    tracing::debug!("Error serving profiling request: {err}");
+                }
+            });
+        }
+    }
+
+    async fn write_continuously(&self, interval: Duration, dir: &Path) -> Result<()> {
+        tokio::fs::create_dir_all(dir)
+            .await
+            .with_context(|| format!("Unable to create profile directory {}", dir.display()))?;
+        loop {
+            // Waits for any profile being served, rather than failing.
+            let collecting = COLLECTING.lock().await;
+            let profile = collect(interval, self.frequency).await;
+            drop(collecting);
+            match profile {
+                Ok(profile) => {
+                    let name = format!(
+                        "{}-{}.pb",
+                        self.trigger_type,
+                        chrono::Utc::now().format("%Y%m%dT%H%M%SZ")
+                    );
+                    if let Err(err) = tokio::fs::write(dir.join(name), profile).await {
+                        tracing::warn!("Unable to write CPU profile: {err:#}");
+                    }
+                    self.prune(dir).await;
+                }
+                Err(err) => {
+                    tracing::warn!("Unable to collect CPU profile: {err:#}");
+                    tokio::time::sleep(interval).await;
+                }
+            }
+        }
+    }
+
+    /// Removes the oldest of this trigger type's profiles beyond `max_files`.
+    /// Names sort by time, so the oldest sort first.
+    async fn prune(&self, dir: &Path) {
+        let prefix = format!("{}-", self.trigger_type);
+        let Ok(mut entries) = tokio::fs::read_dir(dir).await else {
+            return;
+        };
+        let mut profiles = vec![];
+        while let Ok(Some(entry)) = entries.next_entry().await {
+            let name = entry.file_name().to_string_lossy().into_owned();
+            if name.starts_with(&prefix) && name.ends_with(".pb") {
+                profiles.push(entry.path());
+            }
+        }
+        profiles.sort();
+        let excess = profiles.len().saturating_sub(self.max_files);
+        for path in &profiles[..excess] {
+            if let Err(err) = tokio::fs::remove_file(path).await {
+                tracing::debug!("Unable to remove profile {}: {err}", path.display());
+            }
+        }
+    }
+}
+
+async fn respond<B>(req: &Request<B>, frequency: i32) -> Response<Full<Bytes>> {
+    let (status, body) = match (req.method(), req.uri().path()) {
+        (&Method::GET, PROFILE_PATH) => match profile_seconds(req.uri().query()) {
+            Some(seconds) => match COLLECTING.try_lock() {
+                Ok(_collecting) => match collect(Duration::from_secs(seconds), frequency).await {
+                    Ok(profile) => (StatusCode::OK, Bytes::from(profile)),
+                    Err(err) => (
+                        StatusCode::INTERNAL_SERVER_ERROR,
+                        Bytes::from(format!("{err:#}")),
+                    ),
+                },
+                Err(_) => (
+                    StatusCode::CONFLICT,
+                    Bytes::from_static(b"A profile is already being collected"),
+                ),
+            },
+            None => (
+                StatusCode::BAD_REQUEST,
+                Bytes::from(format!(
+                    "seconds must be between 1 and {MAX_PROFILE_SECONDS}"
+                )),
+            ),
+        },
+        (_, PROFILE_PATH) => (StatusCode::METHOD_NOT_ALLOWED, Bytes::new()),
+        _ => (StatusCode::NOT_FOUND, Bytes::new()),
+    };
+    let mut response = Response::new(Full::new(body));
+    *response.status_mut() = status;
+    if status == StatusCode::OK {
+        response.headers_mut().insert(
+            hyper::header::CONTENT_TYPE,
+            hyper::header::HeaderValue::from_static("application/octet-stream"),
+        );
+    }
+    response
+}
+
+/// Parses the `seconds` query parameter, as used by `go tool pprof`.
+fn profile_seconds(query: Option<&str>) -> Option<u64> {
+    let seconds = query
+        .unwrap_or_default()
+        .split('&')
+        .find_map(|param| param.strip_prefix("seconds="));
+    match seconds {
+        Some(seconds) => seconds
+            .parse()
+            .ok()
+            .filter(|seconds| (1..=MAX_PROFILE_SECONDS).contains(seconds)),
+        None => Some(DEFAULT_PROFILE_SECONDS),
+    }
+}
+
+/// Profiles the process for `duration`, returning the encoded profile.
+#[cfg(unix)]
+async fn collect(duration: Duration, frequency: i32) -> Result<Vec<u8>> {
+    tokio::task::spawn_blocking(move || unix::collect(duration, frequency)).await?
+}
+
+#[cfg(not(unix))]
+async fn collect(_duration: Duration, _frequency: i32) -> Result<Vec<u8>> {
+    anyhow::bail!("CPU profiling is not supported on this platform")
+}
+
+#[cfg(unix)]
+mod unix {
+    use std::time::Duration;
+
+    use anyhow::{Context, Result};
+    use pprof::{protos::Message, Frames, Report, Symbol};
+
+    pub fn collect(duration: Duration, frequency: i32) -> Result<Vec<u8>> {
+        let guard = pprof::ProfilerGuardBuilder::default()
+            .frequency(frequency)
+            .blocklist(&["libc", "libgcc", "pthread", "vdso"])
+            .build()
+            .context("Unable to start CPU profiler")?;
+        std::thread::sleep(duration);
+        let unresolved = guard
+            .report()
+            .build_unresolved()
+            .context("Unable to collect CPU profile")?;
+        drop(guard);
+
+        let perf_map = PerfMap::read();
+        let report = Report {
+            data: unresolved
+                .data
+                .into_iter()
+                .map(|(frames, count)| {
+                    let frames = Frames {
+                        frames: resolve(&frames.frames, &perf_map),
+                        thread_name: String::from_utf8_lossy(
+                            &frames.thread_name[..frames.thread_name_length],
+                        )
+                        .into_owned(),
+                        thread_id: frames.thread_id,
+                        sample_timestamp: frames.sample_timestamp,
+                    };
+                    (frames, count)
+                })
+                .collect(),
+            timing: unresolved.timing,
+        };
+        let profile = report.pprof().context("Unable to encode CPU profile")?;
+        let mut bytes = vec![];
+        profile.encode(&mut bytes)?;
+        Ok(bytes)
+    }
+
+    /// Symbolizes sampled frames, naming frames in Wasm code from the perf
+    /// map. Unlike pprof's own symbolization, frames without symbols are
+    /// kept, so that time in unnamed code is still counted.
+    fn resolve(frames: &[backtrace::Frame], perf_map: &PerfMap) -> Vec<Vec<Symbol>> {
+        let mut resolved = vec![];
+        let mut iter = frames.iter();
+        while let Some(frame) = iter.next() {
+            let mut symbols = vec![];
+            backtrace::resolve_frame(frame, |symbol| {
+                symbols.push(Symbol {
+                    name: symbol.name().map(|name| name.as_bytes().to_vec()),
+                    addr: symbol.addr(),
+                    lineno: symbol.lineno(),
+                    filename: symbol.filename().map(|path| path.to_owned()),
+                })
+            });
+            // Skips the signal handler and the frame it interrupted, which
+            // is repeated below it.
+            if symbols
+                .iter()
+                .any(|symbol| symbol.name().ends_with("perf_signal_handler"))
+            {
+                iter.next();
+                continue;
+            }
+            if symbols.is_empty() {
+                let ip = frame.ip() as usize;
+                let name = perf_map
+                    .lookup(ip)
+                    .map(str::to_owned)
+                    .unwrap_or_else(|| format!("{ip:#x}"));
+                symbols.push(Symbol {
+                    name: Some(name.into_bytes()),
+                    addr: Some(frame.ip()),
+                    lineno: None,
+                    filename: None,
+                });
+            }
+            resolved.push(symbols);
+        }
+        resolved
+    }
+
+    /// The functions Wasmtime has compiled, from `/tmp/perf-<pid>.map`.
+    #[derive(Default)]
+    pub(super) struct PerfMap(Vec<(usize, usize, String)>);
+
+    impl PerfMap {
+        fn read() -> Self {
+            let path = format!("/tmp/perf-{}.map", std::process::id());
+            match std::fs::read_to_string(path) {
+                Ok(contents) => Self::parse(&contents),
+                Err(_) => Self::default(),
+            }
+        }
+
+        /// Parses lines of `<start> <size> <name>`, with addresses in hex.
+        pub(super) fn parse(contents: &str) -> Self {
+            let mut entries: Vec<_> = contents
+                .lines()
+                .filter_map(|line| {
+                    let mut parts = line.splitn(3, ' ');
+                    let start = usize::from_str_radix(parts.next()?, 16).ok()?;
+                    let size = usize::from_str_radix(parts.next()?, 16).ok()?;
+                    Some((start, start + size, parts.next()?.to_owned()))
+                })
+                .collect();
+            entries.sort_by_key(|(start, ..)| *start);
+            Self(entries)
+        }
+
+        pub(super) fn lookup(&self, ip: usize) -> Option<&str> {
+            let index = self.0.partition_point(|(start, ..)| *start <= ip);
+            let (_, end, name) = self.0.get(index.checked_sub(1)?)?;
+            (ip < *end).then_some(name.as_str())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn profile_seconds_are_bounded() {
+        assert_eq!(profile_seconds(None), Some(DEFAULT_PROFILE_SECONDS));
+        assert_eq!(profile_seconds(Some("debug=1&seconds=5")), Some(5));
+        assert_eq!(profile_seconds(Some("seconds=0")), None);
+        assert_eq!(profile_seconds(Some("seconds=3600")), None);
+        assert_eq!(profile_seconds(Some("seconds=soon")), None);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn wasm_frames_are_named_from_perf_map() {
+        let perf_map =
+            unix::PerfMap::parse("2000 10 wasm[1]::function[3]\n1000 20 wasm[0]::function[7]\n");
+        assert_eq!(perf_map.lookup(0x1010), Some("wasm[0]::function[7]"));
+        assert_eq!(perf_map.lookup(0x2000), Some("wasm[1]::function[3]"));
+        assert_eq!(perf_map.lookup(0x1020), None);
+        assert_eq!(perf_map.lookup(0xfff), None);
+    }
+}
@@ -0,0 +1,63 @@
+//! Serves the runtime metrics of a trigger's components in the Prometheus
+//! text format, for operators who scrape metrics rather than run an OTLP
+//! collector. Enabled by `prometheus_listen` in the `[metrics]` runtime
+//! config table.
+
+use std::convert::Infallible;
+use std::net::SocketAddr;
+
+use anyhow::{Context, Result};
+use http_body_util::Full;
+use hyper::{body::Bytes, server::conn::http1, service::service_fn, Method, Response, StatusCode};
+use hyper_util::rt::TokioIo;
+use tokio::net::TcpListener;
+
+/// The path at which metrics are served.
+pub const METRICS_PATH: &str = "/metrics";
+
+const CONTENT_TYPE: &str = "text/plain; version=0.0.4";
+
+/// Serves the recorded metrics at [`METRICS_PATH`] until the trigger stops.
+pub(crate) async fn serve(trigger_type: &str, addr: SocketAddr) -> Result<()> {
+    spin_telemetry::metrics::enable_prometheus();
+    let listener = TcpListener::bind(addr)
+        .await
+        .with_context(|| format!("Unable to listen for metrics requests on {addr}"))?;
+    let addr = listener.local_addr()?;
+    terminal::text!("Serving {trigger_type} trigger metrics on http://{addr}{METRICS_PATH}");
+    loop {
+        let (stream, _) = listener.accept().await?;
+        tokio::spawn(async move {
+            let service = service_fn(move |req| {
+                let response = respond(req.method(), req.uri().path());
+                async move { Ok::<_, Infallible>(response) }
+            });
+            if let Err(err) = http1::Builder::new()
+                .serve_connection(TokioIo::new(stream), service)
+                .await
+            {
+                tracing::debug!("Error serving metrics request: {err}");
+            }
+        });
+    }
+}
+
+fn respond(method: &Method, path: &str) -> Response<Full<Bytes>> {
+    let (status, body) = match (method, path) {
+        (&Method::GET, METRICS_PATH) => (
+            StatusCode::OK,
+            spin_telemetry::metrics::prometheus_text().unwrap_or_default(),
+        ),
+        (_, METRICS_PATH) => (StatusCode::METHOD_NOT_ALLOWED, String::new()),
+        _ => (StatusCode::NOT_FOUND, String::new()),
+    };
+    let mut response = Response::new(Full::new(body.into()));
+    *response.status_mut() = status;
+    if status == StatusCode::OK {
+        response.headers_mut().insert(
+            hyper::header::CONTENT_TYPE,
+            hyper::header::HeaderValue::from_static(CONTENT_TYPE),
+        );
+    }
+    response
+}
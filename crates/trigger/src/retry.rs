@@ -0,0 +1,408 @@
+//! Retry and dead-letter policies for message triggers.
+//!
+//! A message trigger whose configuration has a [`RetryConfig`] handles each
+//! message through a [`MessageRetry`], which retries failed executions with
+//! exponential backoff. When the attempts run out, the message is sent to a
+//! dead-letter destination: a queue of the trigger's own broker, which the
+//! trigger provides as a [`DeadLetterQueue`], or a key-value store. Without a
+//! destination, the message is dropped with an error, rather than being
+//! redelivered forever.
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::future::Future;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use anyhow::{bail, Context, Result};
+use async_trait::async_trait;
+use base64::{engine::general_purpose::STANDARD, Engine};
+use serde::{Deserialize, Serialize, Serializer};
+
+use crate::RuntimeConfig;
+
+/// A message trigger's retry policy, read from a trigger's `retry` table.
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct RetryConfig {
+    /// The number of times a message is handled, including the first, before
+    /// it is dead-lettered.
+    #[serde(default = "default_max_attempts")]
+    pub max_attempts: u32,
+    /// How long, in milliseconds, to wait before the first retry.
+    #[serde(default = "default_initial_backoff_ms")]
+    pub initial_backoff_ms: u64,
+    /// The longest wait, in milliseconds, between retries.
+    #[serde(default = "default_max_backoff_ms")]
+    pub max_backoff_ms: u64,
+    /// How much the wait grows after each retry.
+    #[serde(default = "default_backoff_multiplier")]
+    pub backoff_multiplier: f64,
+    /// Where messages go when their attempts run out.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub dead_letter: Option<DeadLetterConfig>,
+}
+
+fn default_max_attempts() -> u32 {
+    3
+}
+
+fn default_initial_backoff_ms() -> u64 {
+    1000
+}
+
+fn default_max_backoff_ms() -> u64 {
+    60000
+}
+
+fn default_backoff_multiplier() -> f64 {
+    2.0
+}
+
+/// A dead-letter destination.
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(tag = "type", rename_all = "kebab-case", deny_unknown_fields)]
+pub enum DeadLetterConfig {
+    /// A queue, topic or stream of the trigger's broker.
+    Queue {
+        /// The name of the queue, topic or stream.
+        name: String,
+    },
+    /// A key-value store of the application. Each message is stored as a
+    /// JSON record under `<key_prefix><component>/<id>`.
+    KeyValue {
+        /// The name of the store.
+        store: String,
+        /// The prefix of the keys of dead-lettered messages.
+        #[serde(default = "default_key_prefix")]
+        key_prefix: String,
+    },
+}
+
+fn default_key_prefix() -> String {
+    "dead-letter/".into()
+}
+
+/// A message whose attempts ran out.
+#[derive(Clone, Debug, Serialize, PartialEq, Eq)]
+pub struct DeadLetter {
+    /// The component which failed to handle the message.
+    pub component: String,
+    /// Where the message was received from, e.g. a channel, topic or queue.
+    pub source: String,
+    /// The message payload.
+    #[serde(serialize_with = "serialize_base64")]
+    pub payload: Vec<u8>,
+    /// Message attributes, such as headers or a key, to keep with the message.
+    pub metadata: BTreeMap<String, String>,
+    /// The error from the last attempt.
+    pub error: String,
+    /// How many times the message was handled.
+    pub attempts: u32,
+}
+
+impl DeadLetter {
+    pub fn new(component: &str, source: &str, payload: impl Into<Vec<u8>>) -> Self {
+        Self {
+            component: component.to_owned(),
+            source: source.to_owned(),
+            payload: payload.into(),
+            metadata: BTreeMap::new(),
+            error: String::new(),
+            attempts: 0,
+        }
+    }
+
+    pub fn with_metadata(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        self.metadata.insert(key.into(), value.into());
+        self
+    }
+}
+
+fn serialize_base64<S: Serializer>(payload: &[u8], serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(&STANDARD.encode(payload))
+}
+
+/// A dead-letter destination provided by a trigger, such as a queue of its
+/// broker.
+#[async_trait]
+pub trait DeadLetterQueue: Send + Sync {
+    async fn send(&self, letter: &DeadLetter) -> Result<()>;
+}
+
+/// What became of a message.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Delivery {
+    /// The message was handled, possibly after retries.
+    Handled,
+    /// The message's attempts ran out and it was dead-lettered.
+    DeadLettered,
+    /// The message's attempts ran out and there is no dead-letter
+    /// destination.
+    Dropped,
+}
+
+/// Retries the handling of messages and dead-letters those which fail.
+#[derive(Clone)]
+pub struct MessageRetry {
+    max_attempts: u32,
+    initial_backoff: Duration,
+    max_backoff: Duration,
+    multiplier: f64,
+    dead_letters: Option<Arc<dyn DeadLetterQueue>>,
+}
+
+impl MessageRetry {
+    /// Creates the retry policy for `config`. A `queue` dead-letter
+    /// destination is created with `queue`, which triggers without a notion
+    /// of queues should fail.
+    pub async fn new(
+        config: &RetryConfig,
+        runtime_config: &RuntimeConfig,
+        queue: impl FnOnce(&str) -> Result<Arc<dyn DeadLetterQueue>>,
+    ) -> Result<Self> {
+        if config.max_attempts == 0 {
+            bail!("`max_attempts` must be at least 1");
+        }
+        if config.backoff_multiplier.is_nan() || config.backoff_multiplier < 1.0 {
+            bail!("`backoff_multiplier` must be at least 1");
+        }
+        let dead_letters = match &config.dead_letter {
+            Some(DeadLetterConfig::Queue { name }) => Some(queue(name)?),
+            Some(DeadLetterConfig::KeyValue { store, key_prefix }) => Some(Arc::new(
+                KeyValueDeadLetters::open(runtime_config, store, key_prefix).await?,
+            )
+                as Arc<dyn DeadLetterQueue>),
+            None => None,
+        };
+        Ok(Self {
+            max_attempts: config.max_attempts,
+            initial_backoff: Duration::from_millis(config.initial_backoff_ms),
+            max_backoff: Duration::from_millis(config.max_backoff_ms),
+            multiplier: config.backoff_multiplier,
+            dead_letters,
+        })
+    }
+
+    /// How long to wait before the given retry, counting from 1.
+    pub fn backoff(&self, retry: u32) -> Duration {
+        let factor = self.multiplier.powi(retry.saturating_sub(1) as i32);
+        self.initial_backoff
+            .mul_f64(factor.min(u32::MAX as f64))
+            .min(self.max_backoff)
+    }
+
+    /// Handles a message with `handler`, retrying failures. When the
+    /// attempts run out, `letters` are dead-lettered. Fails only if the
+    /// messages could not be dead-lettered, in which case the trigger should
+    /// leave them to be redelivered.
+    pub async fn deliver<F, Fut>(
+        &self,
+        letters: Vec<DeadLetter>,
+        mut handler: F,
+    ) -> Result<Delivery>
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = Result<()>>,
+    {
+        let mut attempt = 1;
+        let error = loop {
+            match handler().await {
+                Ok(()) => return Ok(Delivery::Handled),
+                Err(err) if attempt >= self.max_attempts => break err,
+                Err(err) => {
+                    let backoff = self.backoff(attempt);
+                    tracing::warn!(
+                        "Attempt {attempt} of {} failed; retrying in {backoff:?}: {err}",
+                        self.max_attempts
+                    );
+                    tokio::time::sleep(backoff).await;
+                    attempt += 1;
+                }
+            }
+        };
+
+        let Some(dead_letters) = &self.dead_letters else {
+            tracing::error!(
+                "Dropping {} message(s) after {attempt} failed attempt(s): {error}",
+                letters.len()
+            );
+            return Ok(Delivery::Dropped);
+        };
+        tracing::warn!(
+            "Dead-lettering {} message(s) after {attempt} failed attempt(s): {error}",
+            letters.len()
+        );
+        for mut letter in letters {
+            letter.error = format!("{error:#}");
+            letter.attempts = attempt;
+            dead_letters
+                .send(&letter)
+                .await
+                .with_context(|| format!("failed to dead-letter message from {}", letter.source))?;
+        }
+        Ok(Delivery::DeadLettered)
+    }
+}
+
+impl fmt::Debug for MessageRetry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MessageRetry")
+            .field("max_attempts", &self.max_attempts)
+            .field("initial_backoff", &self.initial_backoff)
+            .field("max_backoff", &self.max_backoff)
+            .field("multiplier", &self.multiplier)
+            .field("dead_letters", &self.dead_letters.is_some())
+            .finish()
+    }
+}
+
+/// Dead-letters messages to a key-value store.
+struct KeyValueDeadLetters {
+    store: Arc<dyn spin_key_value::Store>,
+    key_prefix: String,
+    sequence: AtomicU64,
+}
+
+impl KeyValueDeadLetters {
+    async fn open(runtime_config: &RuntimeConfig, name: &str, key_prefix: &str) -> Result<Self> {
+        let manager = runtime_config
+            .key_value_stores()?
+            .into_iter()
+            .find(|(store, _)| store == name)
+            .map(|(_, manager)| manager)
+            .with_context(|| format!("dead-letter key-value store '{name}' is not defined"))?;
+        let store = manager
+            .get(name)
+            .await
+            .with_context(|| format!("failed to open dead-letter key-value store '{name}'"))?;
+        Ok(Self {
+            store,
+            key_prefix: key_prefix.to_owned(),
+            sequence: AtomicU64::new(0),
+        })
+    }
+
+    fn key(&self, letter: &DeadLetter) -> String {
+        let millis = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_millis();
+        let sequence = self.sequence.fetch_add(1, Ordering::Relaxed);
+        format!(
+            "{}{}/{millis}-{}-{sequence}",
+            self.key_prefix,
+            letter.component,
+            std::process::id()
+        )
+    }
+}
+
+#[async_trait]
+impl DeadLetterQueue for KeyValueDeadLetters {
+    async fn send(&self, letter: &DeadLetter) -> Result<()> {
+        let record = serde_json::to_vec(letter)?;
+        self.store
+            .set(&self.key(letter), &record)
+            .await
+            .context("failed to store dead-lettered message")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use super::*;
+
+    #[derive(Default)]
+    struct Recorder(Mutex<Vec<DeadLetter>>);
+
+    #[async_trait]
+    impl DeadLetterQueue for Recorder {
+        async fn send(&self, letter: &DeadLetter) -> Result<()> {
+            self.0.lock().unwrap().push(letter.clone());
+            Ok(())
+        }
+    }
+
+    fn config(dead_letter: Option<DeadLetterConfig>) -> RetryConfig {
+        RetryConfig {
+            max_attempts: 3,
+            initial_backoff_ms: 1,
+            max_backoff_ms: 4,
+            backoff_multiplier: 2.0,
+            dead_letter,
+        }
+    }
+
+    async fn retry(config: &RetryConfig, recorder: Arc<Recorder>) -> MessageRetry {
+        MessageRetry::new(config, &RuntimeConfig::new(None), |_| Ok(recorder))
+            .await
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn backoff_grows_to_the_maximum() {
+        let retry = retry(&config(None), Default::default()).await;
+        let backoffs: Vec<_> = (1..=4).map(|n| retry.backoff(n).as_millis()).collect();
+        assert_eq!(backoffs, [1, 2, 4, 4]);
+    }
+
+    #[tokio::test]
+    async fn failed_messages_are_dead_lettered_after_max_attempts() {
+        let recorder = Arc::new(Recorder::default());
+        let queue = DeadLetterConfig::Queue {
+            name: "orders-dlq".into(),
+        };
+        let retry = retry(&config(Some(queue)), recorder.clone()).await;
+
+        let mut calls = 0;
+        let letter =
+            DeadLetter::new("orders", "orders", b"order 1".to_vec()).with_metadata("key", "1");
+        let delivery = retry
+            .deliver(vec![letter], || {
+                calls += 1;
+                async { bail!("out of stock") }
+            })
+            .await
+            .unwrap();
+        assert_eq!(delivery, Delivery::DeadLettered);
+        assert_eq!(calls, 3);
+
+        let letters = recorder.0.lock().unwrap();
+        assert_eq!(letters[0].attempts, 3);
+        assert_eq!(letters[0].error, "out of stock");
+        assert_eq!(letters[0].metadata["key"], "1");
+    }
+
+    #[tokio::test]
+    async fn retried_messages_may_succeed() {
+        let recorder = Arc::new(Recorder::default());
+        let retry = retry(&config(None), recorder.clone()).await;
+        let mut calls = 0;
+        let delivery = retry
+            .deliver(vec![], || {
+                calls += 1;
+                let result = if calls < 2 {
+                    Err(anyhow::anyhow!("busy"))
+                } else {
+                    Ok(())
+                };
+                async move { result }
+            })
+            .await
+            .unwrap();
+        assert_eq!(delivery, Delivery::Handled);
+        assert_eq!(calls, 2);
+        assert!(recorder.0.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn dead_letter_payload_is_base64_encoded() {
+        let letter = DeadLetter::new("orders", "orders", b"hi".to_vec());
+        let json = serde_json::to_value(&letter).unwrap();
+        assert_eq!(json["payload"], "aGk=");
+    }
+}
@@ -0,0 +1,168 @@
+use std::{collections::HashMap, path::PathBuf, sync::Arc};
+
+use anyhow::{Context, Result};
+use serde::Deserialize;
+use spin_blobstore::{
+    object_store::{
+        aws::AmazonS3Builder, azure::MicrosoftAzureBuilder, gcp::GoogleCloudStorageBuilder,
+        local::LocalFileSystem, memory::InMemory, ObjectStore,
+    },
+    BlobstoreComponent,
+};
+
+use super::{resolve_config_path, RuntimeConfig, RuntimeConfigOpts};
+
+const DEFAULT_BLOB_CONTAINER_DIR: &str = "blobs/default";
+
+// Holds deserialized options from a `[blob_container.<name>]` runtime config section.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "snake_case", tag = "type")]
+pub enum BlobContainerOpts {
+    Local(LocalBlobContainerOpts),
+    InMemory,
+    S3(S3BlobContainerOpts),
+    Azure(AzureBlobContainerOpts),
+    Gcs(GcsBlobContainerOpts),
+}
+
+impl BlobContainerOpts {
+    /// The default container: a directory in the state dir if there is one,
+    /// otherwise memory.
+    pub fn default_container_opts(runtime_config: &RuntimeConfig) -> Self {
+        match runtime_config.state_dir() {
+            Some(dir) => Self::Local(LocalBlobContainerOpts {
+                path: dir.join(DEFAULT_BLOB_CONTAINER_DIR),
+            }),
+            None => Self::InMemory,
+        }
+    }
+
+    pub fn build(&self, config_opts: &RuntimeConfigOpts) -> Result<Arc<dyn ObjectStore>> {
+        match self {
+            Self::Local(opts) => opts.build(config_opts),
+            Self::InMemory => Ok(Arc::new(InMemory::new())),
+            Self::S3(opts) => opts.build(),
+            Self::Azure(opts) => opts.build(),
+            Self::Gcs(opts) => opts.build(),
+        }
+    }
+
+    /// The kind of container, for summaries which leave out its settings.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Self::Local(_) => "local",
+            Self::InMemory => "in_memory",
+            Self::S3(_) => "s3",
+            Self::Azure(_) => "azure",
+            Self::Gcs(_) => "gcs",
+        }
+    }
+}
+
+/// A container kept in a local directory. Object names are paths in it.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct LocalBlobContainerOpts {
+    pub path: PathBuf,
+}
+
+impl LocalBlobContainerOpts {
+    fn build(&self, config_opts: &RuntimeConfigOpts) -> Result<Arc<dyn ObjectStore>> {
+        let path = resolve_config_path(&self.path, config_opts)?;
+        std::fs::create_dir_all(&path).context("Failed to create blob container directory")?;
+        Ok(Arc::new(LocalFileSystem::new_with_prefix(path)?))
+    }
+}
+
+/// An S3 bucket, or a bucket of an S3-compatible service at `endpoint`.
+/// Credentials not given here are taken from the usual `AWS_*` environment
+/// variables.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct S3BlobContainerOpts {
+    pub bucket: String,
+    pub region: Option<String>,
+    pub endpoint: Option<String>,
+    pub access_key_id: Option<String>,
+    pub secret_access_key: Option<String>,
+    /// Allows a plain HTTP `endpoint`, for local services such as MinIO.
+    #[serde(default)]
+    pub allow_http: bool,
+}
+
+impl S3BlobContainerOpts {
+    fn build(&self) -> Result<Arc<dyn ObjectStore>> {
+        let mut builder = AmazonS3Builder::from_env()
+            .with_bucket_name(&self.bucket)
+            .with_allow_http(self.allow_http);
+        if let Some(region) = &self.region {
+            builder = builder.with_region(region);
+        }
+        if let Some(endpoint) = &self.endpoint {
+            builder = builder.with_endpoint(endpoint);
+        }
+        if let Some(access_key_id) = &self.access_key_id {
+            builder = builder.with_access_key_id(access_key_id);
+        }
+        if let Some(secret_access_key) = &self.secret_access_key {
+            builder = builder.with_secret_access_key(secret_access_key);
+        }
+        Ok(Arc::new(builder.build().with_context(|| {
+            format!("Invalid S3 bucket {:?}", self.bucket)
+        })?))
+    }
+}
+
+/// An Azure Blob Storage container. Credentials not given here are taken
+/// from the usual `AZURE_*` environment variables.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct AzureBlobContainerOpts {
+    pub account: String,
+    pub container: String,
+    pub access_key: Option<String>,
+}
+
+impl AzureBlobContainerOpts {
+    fn build(&self) -> Result<Arc<dyn ObjectStore>> {
+        let mut builder = MicrosoftAzureBuilder::from_env()
+            .with_account(&self.account)
+            .with_container_name(&self.container);
+        if let Some(access_key) = &self.access_key {
+            builder = builder.with_access_key(access_key);
+        }
+        Ok(Arc::new(builder.build().with_context(|| {
+            format!("Invalid Azure Blob Storage container {:?}", self.container)
+        })?))
+    }
+}
+
+/// A Google Cloud Storage bucket. Credentials not given here are taken from
+/// the usual `GOOGLE_*` environment variables.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct GcsBlobContainerOpts {
+    pub bucket: String,
+    pub service_account_path: Option<PathBuf>,
+}
+
+impl GcsBlobContainerOpts {
+    fn build(&self) -> Result<Arc<dyn ObjectStore>> {
+        let mut builder = GoogleCloudStorageBuilder::from_env().with_bucket_name(&self.bucket);
+        if let Some(path) = &self.service_account_path {
+            builder = builder.with_service_account_path(path.to_string_lossy());
+        }
+        Ok(Arc::new(builder.build().with_context(|| {
+            format!("Invalid Google Cloud Storage bucket {:?}", self.bucket)
+        })?))
+    }
+}
+
+pub(crate) fn build_component(runtime_config: &RuntimeConfig) -> Result<BlobstoreComponent> {
+    let containers: HashMap<_, _> = runtime_config
+        .blob_containers()
+        .context("Failed to build blobstore component")?
+        .into_iter()
+        .collect();
+    Ok(BlobstoreComponent::new(containers))
+}
@@ -0,0 +1,176 @@
+use std::path::PathBuf;
+
+use super::{
+    blob_container::BlobContainerOpts, concurrency::ConcurrencyOpts, key_value::KeyValueStoreOpts,
+    keys::KeyProviderOpts, sqlite::SqliteDatabaseOpts, variables_provider::VariablesProviderOpts,
+    RuntimeConfig, RuntimeConfigOpts,
+};
+
+/// Builds runtime config in code, for embedders and tests which would
+/// otherwise have to write a runtime config file.
+///
+/// Each method sets the option of the same name in the runtime config file
+/// format. Relative paths are relative to the current directory.
+///
+/// ```
+/// # use spin_trigger::{RedisKeyValueStoreOpts, RuntimeConfigBuilder};
+/// let config = RuntimeConfigBuilder::new()
+///     .key_value_store(
+///         "default",
+///         RedisKeyValueStoreOpts {
+///             url: "redis://localhost:6379".into(),
+///             encryption_key: None,
+///         },
+///     )
+///     .state_dir("/var/lib/spin")
+///     .build(None);
+/// assert_eq!(config.state_dir().unwrap(), std::path::Path::new("/var/lib/spin"));
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct RuntimeConfigBuilder {
+    opts: RuntimeConfigOpts,
+}
+
+impl RuntimeConfigBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the state dir. An empty string unsets it.
+    pub fn state_dir(mut self, state_dir: impl Into<String>) -> Self {
+        self.opts.state_dir = Some(state_dir.into());
+        self
+    }
+
+    /// Set the log dir. An empty path disables logging.
+    pub fn log_dir(mut self, log_dir: impl Into<PathBuf>) -> Self {
+        self.opts.log_dir = Some(log_dir.into());
+        self
+    }
+
+    /// Set the key value store opened by `name`, as a
+    /// `[key_value_store.<name>]` table does.
+    pub fn key_value_store(
+        mut self,
+        name: impl Into<String>,
+        store: impl Into<KeyValueStoreOpts>,
+    ) -> Self {
+        self.opts.key_value_stores.insert(name.into(), store.into());
+        self
+    }
+
+    /// Set the SQLite database opened by `name`, as a
+    /// `[sqlite_database.<name>]` table does.
+    pub fn sqlite_database(
+        mut self,
+        name: impl Into<String>,
+        database: impl Into<SqliteDatabaseOpts>,
+    ) -> Self {
+        self.opts
+            .sqlite_databases
+            .insert(name.into(), database.into());
+        self
+    }
+
+    /// Set the blob container opened by `name`, as a
+    /// `[blob_container.<name>]` table does.
+    pub fn blob_container(mut self, name: impl Into<String>, container: BlobContainerOpts) -> Self {
+        self.opts.blob_containers.insert(name.into(), container);
+        self
+    }
+
+    /// Add a variables provider, as a `[[variables_provider]]` table does.
+    pub fn variables_provider(mut self, provider: impl Into<VariablesProviderOpts>) -> Self {
+        self.opts.variables_providers.push(provider.into());
+        self
+    }
+
+    /// Set the key named `name`, as a `[keys.<name>]` table does.
+    pub fn key(mut self, name: impl Into<String>, key: impl Into<KeyProviderOpts>) -> Self {
+        self.opts.keys.insert(name.into(), key.into());
+        self
+    }
+
+    /// Set execution concurrency limits.
+    pub fn concurrency(mut self, concurrency: ConcurrencyOpts) -> Self {
+        self.opts.concurrency = Some(concurrency);
+        self
+    }
+
+    /// Build the runtime config for an app, as [`RuntimeConfig::new`] would
+    /// with the built options merged into it.
+    pub fn build(self, local_app_dir: Option<PathBuf>) -> RuntimeConfig {
+        let mut config = RuntimeConfig::new(local_app_dir);
+        config.merge_config_builder(self);
+        config
+    }
+
+    pub(super) fn into_opts(self) -> RuntimeConfigOpts {
+        self.opts
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::{
+        key_value::SpinKeyValueStoreOpts, sqlite::SpinSqliteDatabaseOpts,
+        variables_provider::EnvVariablesProviderOpts,
+    };
+    use super::*;
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn built_config_resolves_like_a_file() -> anyhow::Result<()> {
+        let dir = tempfile::tempdir()?;
+        let config = RuntimeConfigBuilder::new()
+            .state_dir(dir.path().to_string_lossy())
+            .key_value_store(
+                "cache",
+                SpinKeyValueStoreOpts {
+                    path: Some(dir.path().join("cache.db")),
+                    encryption_key: None,
+                },
+            )
+            .sqlite_database(
+                "default",
+                SpinSqliteDatabaseOpts {
+                    path: Some(dir.path().join("sqlite.db")),
+                },
+            )
+            .variables_provider(EnvVariablesProviderOpts {
+                prefix: Some("TEST".into()),
+                dotenv_path: None,
+            })
+            .build(None);
+
+        assert_eq!(config.state_dir().unwrap(), dir.path());
+        assert_eq!(config.log_dir().unwrap(), dir.path().join("logs"));
+
+        let mut stores: Vec<_> = config.key_value_stores()?.into_iter().collect();
+        stores.sort_by(|a, b| a.0.cmp(&b.0));
+        assert_eq!(stores[0].0, "cache");
+        assert_eq!(stores[1].0, "default");
+
+        let databases: Vec<_> = config.sqlite_databases().await?.into_iter().collect();
+        assert_eq!(databases.len(), 1);
+        assert_eq!(databases[0].0, "default");
+
+        // The default env provider, then the one added
+        assert_eq!(config.variables_providers().len(), 2);
+        Ok(())
+    }
+
+    #[test]
+    fn later_merged_files_take_precedence() -> anyhow::Result<()> {
+        let mut config = RuntimeConfigBuilder::new()
+            .state_dir("/from/builder")
+            .log_dir("/from/builder/logs")
+            .build(None);
+        config.merge_config_toml(r#"state_dir = "/from/toml""#)?;
+        assert_eq!(config.state_dir().unwrap(), PathBuf::from("/from/toml"));
+        assert_eq!(
+            config.log_dir().unwrap(),
+            PathBuf::from("/from/builder/logs")
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,71 @@
+use std::collections::HashMap;
+
+use anyhow::{bail, Result};
+use serde::Deserialize;
+
+/// Limits on concurrent component executions, read from the `[concurrency]`
+/// runtime config table.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct ConcurrencyOpts {
+    /// The maximum number of executions at once, across all components.
+    pub max_concurrent: Option<usize>,
+    /// The maximum number of executions of each component at once.
+    pub max_concurrent_per_component: Option<usize>,
+    /// Per-component overrides of `max_concurrent_per_component`.
+    #[serde(default)]
+    pub components: HashMap<String, usize>,
+    /// The maximum number of executions waiting for a slot; unbounded if
+    /// unset.
+    pub max_queued: Option<usize>,
+    /// What happens to an execution which arrives when the queue is full.
+    pub overflow: Option<QueueOverflow>,
+}
+
+/// What happens to an execution which arrives when the queue is full.
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Eq, clap::ArgEnum)]
+#[serde(rename_all = "kebab-case")]
+pub enum QueueOverflow {
+    /// Refuse the new execution.
+    #[default]
+    Reject,
+    /// Refuse the execution which has waited longest, and queue the new one.
+    ShedOldest,
+}
+
+impl ConcurrencyOpts {
+    /// Fills the options which are unset from `other`, which has lower
+    /// precedence.
+    pub(crate) fn or(mut self, other: &Self) -> Self {
+        self.max_concurrent = self.max_concurrent.or(other.max_concurrent);
+        self.max_concurrent_per_component = self
+            .max_concurrent_per_component
+            .or(other.max_concurrent_per_component);
+        for (component, max) in &other.components {
+            self.components.entry(component.clone()).or_insert(*max);
+        }
+        self.max_queued = self.max_queued.or(other.max_queued);
+        self.overflow = self.overflow.or(other.overflow);
+        self
+    }
+
+    /// Whether any execution limit is set.
+    pub fn is_limited(&self) -> bool {
+        self.max_concurrent.is_some()
+            || self.max_concurrent_per_component.is_some()
+            || !self.components.is_empty()
+    }
+
+    pub(crate) fn validate(&self) -> Result<()> {
+        let limits = [self.max_concurrent, self.max_concurrent_per_component]
+            .into_iter()
+            .flatten()
+            .chain(self.components.values().copied());
+        for limit in limits {
+            if limit == 0 {
+                bail!("concurrency limits must be greater than zero");
+            }
+        }
+        Ok(())
+    }
+}
@@ -76,12 +76,52 @@ impl KeyValueStoreOpts {
             Self::AzureCosmos(opts) => opts.build_store(),
         }
     }
+
+    /// The kind of store, for summaries which leave out its settings.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Self::Spin(SpinKeyValueStoreOpts { path: Some(_), .. }) => "sqlite_file",
+            Self::Spin(SpinKeyValueStoreOpts { path: None, .. }) => "in_memory",
+            Self::Redis(_) => "redis",
+            Self::AzureCosmos(_) => "azure_cosmos",
+        }
+    }
+
+    /// The name of the `[keys]` entry which encrypts the store's values, if
+    /// they are encrypted.
+    pub fn encryption_key(&self) -> Option<&str> {
+        match self {
+            Self::Spin(opts) => opts.encryption_key.as_deref(),
+            Self::Redis(opts) => opts.encryption_key.as_deref(),
+            Self::AzureCosmos(opts) => opts.encryption_key.as_deref(),
+        }
+    }
+}
+
+impl From<SpinKeyValueStoreOpts> for KeyValueStoreOpts {
+    fn from(opts: SpinKeyValueStoreOpts) -> Self {
+        Self::Spin(opts)
+    }
+}
+
+impl From<RedisKeyValueStoreOpts> for KeyValueStoreOpts {
+    fn from(opts: RedisKeyValueStoreOpts) -> Self {
+        Self::Redis(opts)
+    }
+}
+
+impl From<AzureCosmosConfig> for KeyValueStoreOpts {
+    fn from(opts: AzureCosmosConfig) -> Self {
+        Self::AzureCosmos(opts)
+    }
 }
 
 #[derive(Clone, Debug, Default, Deserialize)]
 #[serde(deny_unknown_fields)]
 pub struct SpinKeyValueStoreOpts {
     pub path: Option<PathBuf>,
+    #[serde(default)]
+    pub encryption_key: Option<String>,
 }
 
 impl SpinKeyValueStoreOpts {
@@ -90,7 +130,10 @@ impl SpinKeyValueStoreOpts {
         let path = runtime_config
             .state_dir()
             .map(|dir| dir.join(DEFAULT_SPIN_STORE_FILENAME));
-        Self { path }
+        Self {
+            path,
+            encryption_key: None,
+        }
     }
 
     fn build_store(&self, config_opts: &RuntimeConfigOpts) -> Result<KeyValueStore> {
@@ -111,6 +154,8 @@ impl SpinKeyValueStoreOpts {
 #[derive(Clone, Debug, Deserialize)]
 pub struct RedisKeyValueStoreOpts {
     pub url: String,
+    #[serde(default)]
+    pub encryption_key: Option<String>,
 }
 
 impl RedisKeyValueStoreOpts {
@@ -122,10 +167,12 @@ impl RedisKeyValueStoreOpts {
 
 #[derive(Clone, Debug, Deserialize)]
 pub struct AzureCosmosConfig {
-    key: String,
-    account: String,
-    database: String,
-    container: String,
+    pub key: String,
+    pub account: String,
+    pub database: String,
+    pub container: String,
+    #[serde(default)]
+    pub encryption_key: Option<String>,
 }
 
 impl AzureCosmosConfig {
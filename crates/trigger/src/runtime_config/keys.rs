@@ -0,0 +1,128 @@
+use std::{path::PathBuf, sync::Arc};
+
+use anyhow::Result;
+use serde::Deserialize;
+use spin_keys::provider::{
+    aws_kms::AwsKmsKeyProvider, azure_key_vault::AzureKeyVaultKeyProvider, env::EnvKeyProvider,
+    file::FileKeyProvider,
+};
+
+use super::{resolve_config_path, RuntimeConfigOpts};
+
+pub type KeyProvider = Arc<dyn spin_keys::KeyProvider>;
+
+// Holds deserialized options from a `[keys.<name>]` runtime config section.
+// Features which encrypt data refer to keys by name.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "snake_case", tag = "type")]
+pub enum KeyProviderOpts {
+    File(FileKeyProviderOpts),
+    Env(EnvKeyProviderOpts),
+    AwsKms(AwsKmsKeyProviderOpts),
+    AzureKeyVault(AzureKeyVaultKeyProviderOpts),
+}
+
+impl KeyProviderOpts {
+    pub fn build_provider(&self, config_opts: &RuntimeConfigOpts) -> Result<KeyProvider> {
+        match self {
+            Self::File(opts) => opts.build_provider(config_opts),
+            Self::Env(opts) => Ok(opts.build_provider()),
+            Self::AwsKms(opts) => Ok(opts.build_provider()),
+            Self::AzureKeyVault(opts) => Ok(opts.build_provider()),
+        }
+    }
+}
+
+impl From<FileKeyProviderOpts> for KeyProviderOpts {
+    fn from(opts: FileKeyProviderOpts) -> Self {
+        Self::File(opts)
+    }
+}
+
+impl From<EnvKeyProviderOpts> for KeyProviderOpts {
+    fn from(opts: EnvKeyProviderOpts) -> Self {
+        Self::Env(opts)
+    }
+}
+
+impl From<AwsKmsKeyProviderOpts> for KeyProviderOpts {
+    fn from(opts: AwsKmsKeyProviderOpts) -> Self {
+        Self::AwsKms(opts)
+    }
+}
+
+impl From<AzureKeyVaultKeyProviderOpts> for KeyProviderOpts {
+    fn from(opts: AzureKeyVaultKeyProviderOpts) -> Self {
+        Self::AzureKeyVault(opts)
+    }
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct FileKeyProviderOpts {
+    /// The file holding the key. Relative paths are relative to the runtime
+    /// config file.
+    pub path: PathBuf,
+    /// Generate the key if the file doesn't exist.
+    #[serde(default)]
+    pub create: bool,
+}
+
+impl FileKeyProviderOpts {
+    fn build_provider(&self, config_opts: &RuntimeConfigOpts) -> Result<KeyProvider> {
+        let provider = FileKeyProvider::new(resolve_config_path(&self.path, config_opts)?);
+        if self.create {
+            Ok(Arc::new(provider.create_if_missing()))
+        } else {
+            Ok(Arc::new(provider))
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct EnvKeyProviderOpts {
+    /// The environment variable holding the base64-encoded key.
+    pub variable: String,
+}
+
+impl EnvKeyProviderOpts {
+    fn build_provider(&self) -> KeyProvider {
+        Arc::new(EnvKeyProvider::new(&self.variable))
+    }
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct AwsKmsKeyProviderOpts {
+    /// The KMS key which encrypted the data key.
+    pub key_id: String,
+    /// The base64-encoded data key, as encrypted by KMS.
+    pub ciphertext: String,
+    #[serde(default)]
+    pub region: Option<String>,
+}
+
+impl AwsKmsKeyProviderOpts {
+    fn build_provider(&self) -> KeyProvider {
+        Arc::new(AwsKmsKeyProvider::new(
+            &self.key_id,
+            &self.ciphertext,
+            self.region.as_deref(),
+        ))
+    }
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct AzureKeyVaultKeyProviderOpts {
+    pub vault_url: String,
+    /// The name of the secret holding the base64-encoded key.
+    pub secret: String,
+}
+
+impl AzureKeyVaultKeyProviderOpts {
+    fn build_provider(&self) -> KeyProvider {
+        Arc::new(AzureKeyVaultKeyProvider::new(&self.vault_url, &self.secret))
+    }
+}
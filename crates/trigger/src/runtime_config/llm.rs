@@ -37,14 +37,14 @@ pub(crate) async fn build_component(
     }
 }
 
-#[derive(Debug, serde::Deserialize)]
+#[derive(Clone, Debug, serde::Deserialize)]
 #[serde(rename_all = "snake_case", tag = "type")]
 pub enum LlmComputeOpts {
     Spin,
     RemoteHttp(RemoteHttpComputeOpts),
 }
 
-#[derive(Debug, serde::Deserialize)]
+#[derive(Clone, Debug, serde::Deserialize)]
 pub struct RemoteHttpComputeOpts {
     url: Url,
     auth_token: String,
@@ -0,0 +1,149 @@
+use std::path::PathBuf;
+
+use anyhow::{anyhow, Context, Result};
+use serde::Deserialize;
+use spin_telemetry::sinks::{
+    FileSink, FileSinkOptions, LogSources, OtlpSink, Sink, SyslogSink, TimeRotation,
+};
+use tracing::Level;
+
+use super::{resolve_config_path, RuntimeConfigOpts};
+
+const DEFAULT_LEVEL: &str = "info";
+const DEFAULT_APP_NAME: &str = "spin";
+const DEFAULT_MAX_FILES: usize = 5;
+
+// Holds deserialized options from a `[[log_sink]]` runtime config section.
+//
+// Each trigger type of an application runs in its own process with its own
+// sinks, so a file sink shared by several trigger types should be given a
+// different path in a runtime config file for each.
+#[derive(Clone, Debug, Deserialize)]
+pub struct LogSinkOpts {
+    /// The most verbose level of records to send: one of `error`, `warn`,
+    /// `info`, `debug` or `trace`. Component output on stdout is at `info`
+    /// and on stderr at `warn`.
+    #[serde(default = "default_level")]
+    pub level: String,
+    #[serde(default)]
+    pub sources: LogSinkSources,
+    #[serde(flatten)]
+    pub sink: LogSinkTypeOpts,
+}
+
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum LogSinkSources {
+    #[default]
+    All,
+    Host,
+    Components,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "snake_case", tag = "type")]
+pub enum LogSinkTypeOpts {
+    Syslog(SyslogSinkOpts),
+    Otlp(OtlpSinkOpts),
+    File(FileSinkOpts),
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SyslogSinkOpts {
+    /// One of `udp://<host>:<port>`, `tcp://<host>:<port>` or `unix://<path>`.
+    pub address: String,
+    #[serde(default)]
+    pub facility: Option<String>,
+    #[serde(default)]
+    pub app_name: Option<String>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct OtlpSinkOpts {
+    /// The base URL of an OTLP/HTTP collector. Defaults to the endpoint set
+    /// by the `OTEL_EXPORTER_OTLP_*` environment variables.
+    #[serde(default)]
+    pub endpoint: Option<String>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct FileSinkOpts {
+    pub path: PathBuf,
+    /// The size in bytes at which the file is rotated.
+    #[serde(default)]
+    pub max_file_size: Option<u64>,
+    /// The number of rotated files to keep.
+    #[serde(default)]
+    pub max_files: Option<usize>,
+    #[serde(default)]
+    pub rotate: Option<FileRotation>,
+    #[serde(default)]
+    pub format: FileFormat,
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum FileRotation {
+    Hourly,
+    Daily,
+}
+
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum FileFormat {
+    #[default]
+    Text,
+    Json,
+}
+
+impl LogSinkOpts {
+    /// Starts the sink.
+    pub fn build(&self, config_opts: &RuntimeConfigOpts) -> Result<Sink> {
+        let level: Level = self
+            .level
+            .parse()
+            .map_err(|_| anyhow!("invalid log sink level '{}'", self.level))?;
+        let sources = match self.sources {
+            LogSinkSources::All => LogSources::All,
+            LogSinkSources::Host => LogSources::Host,
+            LogSinkSources::Components => LogSources::Components,
+        };
+        let sink = match &self.sink {
+            LogSinkTypeOpts::Syslog(opts) => {
+                let facility = match &opts.facility {
+                    Some(facility) => facility.parse()?,
+                    None => Default::default(),
+                };
+                let app_name = opts.app_name.as_deref().unwrap_or(DEFAULT_APP_NAME);
+                let sink = SyslogSink::connect(&opts.address, facility, app_name)?;
+                Sink::spawn(format!("syslog {}", opts.address), level, sources, sink)
+            }
+            LogSinkTypeOpts::Otlp(opts) => {
+                let sink = OtlpSink::new(opts.endpoint.as_deref())?;
+                Sink::spawn("otlp", level, sources, sink)
+            }
+            LogSinkTypeOpts::File(opts) => {
+                let path = resolve_config_path(&opts.path, config_opts)?;
+                let sink = FileSink::open(FileSinkOptions {
+                    path: path.clone(),
+                    max_file_size: opts.max_file_size,
+                    max_files: opts.max_files.unwrap_or(DEFAULT_MAX_FILES),
+                    rotation: opts.rotate.map(|rotate| match rotate {
+                        FileRotation::Hourly => TimeRotation::Hourly,
+                        FileRotation::Daily => TimeRotation::Daily,
+                    }),
+                    json: opts.format == FileFormat::Json,
+                })?;
+                Sink::spawn(format!("file {}", path.display()), level, sources, sink)
+            }
+        };
+        sink.context("Failed to start log sink")
+    }
+}
+
+fn default_level() -> String {
+    DEFAULT_LEVEL.into()
+}
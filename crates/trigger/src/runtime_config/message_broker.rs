@@ -0,0 +1,107 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    sync::Arc,
+};
+
+use anyhow::{Context, Result};
+use outbound_messaging::{OutboundMessagingComponent, Publisher};
+use serde::Deserialize;
+
+use crate::RuntimeConfig;
+
+/// Options for a broker components may publish messages through, read from
+/// a `[message_broker.<name>]` runtime config table.
+#[derive(Clone, Debug, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case", tag = "type")]
+pub enum MessageBrokerOpts {
+    Kafka(KafkaBrokerOpts),
+    Sqs(SqsBrokerOpts),
+    Amqp(AmqpBrokerOpts),
+}
+
+/// A Kafka cluster.
+#[derive(Clone, Debug, Deserialize, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct KafkaBrokerOpts {
+    /// A comma-separated list of bootstrap servers.
+    pub brokers: String,
+    /// Additional librdkafka producer properties, such as
+    /// `security.protocol` and `sasl.*`.
+    #[serde(default)]
+    pub properties: BTreeMap<String, String>,
+}
+
+/// SQS, with credentials from the standard AWS environment and profiles.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct SqsBrokerOpts {
+    /// The AWS region, overriding the region from the environment.
+    pub region: Option<String>,
+    /// A custom SQS endpoint, e.g. for a local emulator.
+    pub endpoint_url: Option<String>,
+}
+
+/// An AMQP 0-9-1 broker, such as RabbitMQ.
+#[derive(Clone, Debug, Deserialize, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct AmqpBrokerOpts {
+    pub url: String,
+    /// The exchange messages are published to. Defaults to the default
+    /// exchange, which routes to the queue named by the topic.
+    #[serde(default)]
+    pub exchange: String,
+}
+
+impl MessageBrokerOpts {
+    async fn build(&self) -> Result<Arc<dyn Publisher>> {
+        match self {
+            #[cfg(feature = "messaging-kafka")]
+            Self::Kafka(opts) => Ok(Arc::new(outbound_messaging::KafkaPublisher::new(
+                &opts.brokers,
+                &opts.properties,
+            )?)),
+            #[cfg(not(feature = "messaging-kafka"))]
+            Self::Kafka(_) => Err(not_built("Kafka", "kafka")),
+            #[cfg(feature = "messaging")]
+            Self::Sqs(opts) => Ok(Arc::new(
+                outbound_messaging::SqsPublisher::new(
+                    opts.region.clone(),
+                    opts.endpoint_url.clone(),
+                )
+                .await,
+            )),
+            #[cfg(feature = "messaging")]
+            Self::Amqp(opts) => Ok(Arc::new(outbound_messaging::AmqpPublisher::new(
+                &opts.url,
+                &opts.exchange,
+            ))),
+            #[cfg(not(feature = "messaging"))]
+            Self::Sqs(_) => Err(not_built("SQS", "messaging")),
+            #[cfg(not(feature = "messaging"))]
+            Self::Amqp(_) => Err(not_built("AMQP", "messaging")),
+        }
+    }
+}
+
+#[cfg(not(all(feature = "messaging", feature = "messaging-kafka")))]
+fn not_built(kind: &str, feature: &str) -> anyhow::Error {
+    anyhow::anyhow!(
+        "{kind} message brokers are not supported by this build of Spin; rebuild it with the `{feature}` feature"
+    )
+}
+
+pub(crate) async fn build_component(
+    runtime_config: &RuntimeConfig,
+) -> Result<OutboundMessagingComponent> {
+    let mut brokers = HashMap::new();
+    for (name, opts) in runtime_config.message_brokers() {
+        let broker = opts
+            .build()
+            .await
+            .with_context(|| format!("Invalid runtime config for message broker {name:?}"))?;
+        brokers.insert(name, broker);
+    }
+    Ok(OutboundMessagingComponent {
+        brokers: Arc::new(brokers),
+    })
+}
@@ -0,0 +1,28 @@
+use std::{collections::HashMap, net::SocketAddr};
+
+use serde::Deserialize;
+
+/// Options for serving runtime metrics, read from the `[metrics]` runtime
+/// config table.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct MetricsOpts {
+    /// The address on which to serve metrics in the Prometheus text format,
+    /// at /metrics.
+    pub prometheus_listen: Option<SocketAddr>,
+    /// Per-trigger-type overrides of `prometheus_listen`. Each trigger type
+    /// of an application runs in its own process, so an application with
+    /// several trigger types needs an address for each.
+    #[serde(default)]
+    pub triggers: HashMap<String, SocketAddr>,
+}
+
+impl MetricsOpts {
+    /// The address on which the given trigger type serves Prometheus metrics.
+    pub fn prometheus_listen(&self, trigger_type: &str) -> Option<SocketAddr> {
+        self.triggers
+            .get(trigger_type)
+            .copied()
+            .or(self.prometheus_listen)
+    }
+}
@@ -0,0 +1,43 @@
+use std::time::Duration;
+
+use serde::Deserialize;
+
+const DEFAULT_MAX_SAMPLES: usize = 100;
+
+/// Options for retaining the full detail of slow and failed requests, read
+/// from the `[observability]` runtime config table.
+///
+/// Retained requests are kept whatever the trace sampler decides, so that
+/// the requests most worth looking at are never lost to head-based sampling.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct ObservabilityOpts {
+    /// Requests which take at least this long to respond are retained.
+    #[serde(default)]
+    pub slow_request_threshold_ms: Option<u64>,
+    /// Whether requests which fail, or respond with a server error, are
+    /// retained.
+    #[serde(default)]
+    pub sample_errors: bool,
+    /// The number of retained requests to keep. Once it is reached, the
+    /// oldest are removed as new ones are retained.
+    #[serde(default)]
+    pub max_samples: Option<usize>,
+}
+
+impl ObservabilityOpts {
+    /// Whether any requests are to be retained.
+    pub fn is_enabled(&self) -> bool {
+        self.slow_request_threshold_ms.is_some() || self.sample_errors
+    }
+
+    /// Returns the slow request threshold, if set.
+    pub fn slow_request_threshold(&self) -> Option<Duration> {
+        self.slow_request_threshold_ms.map(Duration::from_millis)
+    }
+
+    /// Returns the number of retained requests to keep.
+    pub fn max_samples(&self) -> usize {
+        self.max_samples.unwrap_or(DEFAULT_MAX_SAMPLES)
+    }
+}
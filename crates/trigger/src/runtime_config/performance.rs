@@ -0,0 +1,97 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use anyhow::{bail, Context, Result};
+use serde::Deserialize;
+
+const MB: usize = 1 << 20;
+
+/// Tuning for large hosts, read from the `[performance]` runtime config
+/// table.
+///
+/// On hosts with several NUMA nodes, throughput suffers when threads move
+/// between CPUs or touch memory last used from another node. These options
+/// size and pin the threads triggers run on, and control how the pooling
+/// allocator reuses instances' memory.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct PerformanceOpts {
+    /// The number of async runtime worker threads; the number of CPUs if
+    /// unset.
+    pub worker_threads: Option<usize>,
+    /// Pin each runtime thread to one CPU, in turn, from `cpus`.
+    #[serde(default)]
+    pub pin_threads: bool,
+    /// The CPUs to run on when `pin_threads` is set; all of them if unset.
+    pub cpus: Option<Vec<usize>>,
+    /// How much of each linear memory the pooling allocator keeps resident
+    /// for the next instance in its slot, in MiB.
+    pub pooling_keep_resident_mb: Option<usize>,
+    /// The most unused pooling allocator slots kept for the components
+    /// which last used them.
+    pub pooling_max_unused_warm_slots: Option<u32>,
+}
+
+impl PerformanceOpts {
+    /// Applies the options to the configuration of the engine components
+    /// are run in.
+    pub fn apply(&self, config: &mut spin_core::Config) {
+        if let Some(keep_resident_mb) = self.pooling_keep_resident_mb {
+            config.pooling_memory_keep_resident(keep_resident_mb * MB);
+        }
+        if let Some(slots) = self.pooling_max_unused_warm_slots {
+            config.pooling_max_unused_warm_slots(slots);
+        }
+    }
+
+    /// Builds the async runtime a trigger should run on, or returns `None`
+    /// if no option calls for other than the default runtime.
+    pub fn build_runtime(&self) -> Result<Option<tokio::runtime::Runtime>> {
+        if self.worker_threads.is_none() && !self.pin_threads {
+            return Ok(None);
+        }
+        let mut builder = tokio::runtime::Builder::new_multi_thread();
+        builder.enable_all();
+        if let Some(worker_threads) = self.worker_threads {
+            if worker_threads == 0 {
+                bail!("[performance] worker_threads must be greater than zero");
+            }
+            builder.worker_threads(worker_threads);
+        }
+        if self.pin_threads {
+            let cpus = self.pinned_cpus()?;
+            let next = AtomicUsize::new(0);
+            builder.on_thread_start(move || {
+                let cpu = cpus[next.fetch_add(1, Ordering::Relaxed) % cpus.len()];
+                if !core_affinity::set_for_current(cpu) {
+                    tracing::warn!("Failed to pin runtime thread to CPU {}", cpu.id);
+                }
+            });
+        }
+        builder
+            .build()
+            .context("Failed to build the trigger's async runtime")
+            .map(Some)
+    }
+
+    fn pinned_cpus(&self) -> Result<Vec<core_affinity::CoreId>> {
+        let available = core_affinity::get_core_ids()
+            .context("Failed to list CPUs to pin runtime threads to")?;
+        let cpus: Vec<_> = match &self.cpus {
+            Some(ids) => ids
+                .iter()
+                .map(|&id| {
+                    available
+                        .iter()
+                        .find(|cpu| cpu.id == id)
+                        .copied()
+                        .with_context(|| format!("[performance] CPU {id} is not available"))
+                })
+                .collect::<Result<_>>()?,
+            None => available,
+        };
+        if cpus.is_empty() {
+            bail!("[performance] cpus must not be empty");
+        }
+        Ok(cpus)
+    }
+}
@@ -0,0 +1,63 @@
+use std::{collections::HashMap, net::SocketAddr, time::Duration};
+
+use serde::Deserialize;
+
+const DEFAULT_FREQUENCY: i32 = 99;
+const DEFAULT_MAX_FILES: usize = 24;
+
+/// Options for continuous CPU profiling of the host, read from the
+/// `[profiling]` runtime config table.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct ProfilingOpts {
+    /// The address on which to serve CPU profiles on demand, at
+    /// /debug/pprof/profile. Only one profile is collected at a time, so
+    /// while continuous profiling is enabled requests are refused with a
+    /// conflict between periods.
+    #[serde(default)]
+    pub listen: Option<SocketAddr>,
+    /// Per-trigger-type overrides of `listen`. Each trigger type of an
+    /// application runs in its own process, so an application with several
+    /// trigger types needs an address for each.
+    #[serde(default)]
+    pub triggers: HashMap<String, SocketAddr>,
+    /// Continuously profile, writing a profile of each period of this many
+    /// seconds to `profiles/` in the state directory.
+    #[serde(default)]
+    pub interval_secs: Option<u64>,
+    /// The number of profiles to keep in the state directory for each
+    /// trigger type.
+    #[serde(default)]
+    pub max_files: Option<usize>,
+    /// How many samples to take per second.
+    #[serde(default)]
+    pub frequency: Option<i32>,
+}
+
+impl ProfilingOpts {
+    /// Whether any profiles are to be collected by the given trigger type.
+    pub fn is_enabled(&self, trigger_type: &str) -> bool {
+        self.listen(trigger_type).is_some() || self.interval_secs.is_some()
+    }
+
+    /// The address on which the given trigger type serves profiles.
+    pub fn listen(&self, trigger_type: &str) -> Option<SocketAddr> {
+        self.triggers.get(trigger_type).copied().or(self.listen)
+    }
+
+    /// Returns the period covered by each profile written to the state
+    /// directory, if continuous profiling is enabled.
+    pub fn interval(&self) -> Option<Duration> {
+        self.interval_secs.map(Duration::from_secs)
+    }
+
+    /// Returns the number of profiles to keep.
+    pub fn max_files(&self) -> usize {
+        self.max_files.unwrap_or(DEFAULT_MAX_FILES)
+    }
+
+    /// Returns the number of samples to take per second.
+    pub fn frequency(&self) -> i32 {
+        self.frequency.unwrap_or(DEFAULT_FREQUENCY)
+    }
+}
@@ -0,0 +1,162 @@
+use anyhow::{bail, Context, Result};
+use serde::{Deserialize, Serialize};
+use spin_app::{App, OCI_IMAGE_DIGEST_KEY};
+
+/// The origin scheme of apps pulled from a registry.
+const ORIGIN_OCI_PREFIX: &str = "vnd.fermyon.origin-oci:";
+
+/// Requirements on the apps a trigger will run, read from the `[security]`
+/// runtime config table.
+///
+/// ```toml
+/// [security]
+/// require_signed_apps = true
+///
+/// [[security.trusted_signer]]
+/// identity = "https://github.com/acme/app/.github/workflows/release.yml@refs/heads/main"
+/// issuer = "https://token.actions.githubusercontent.com"
+/// ```
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct SecurityOpts {
+    /// Refuse to run apps which aren't signed by a trusted signer.
+    #[serde(default)]
+    pub require_signed_apps: bool,
+    /// The signers whose signatures are trusted.
+    #[serde(rename = "trusted_signer", default)]
+    pub trusted_signers: Vec<TrustedSigner>,
+}
+
+/// A signer, as identified by the certificate of a keyless (Sigstore)
+/// signature.
+#[derive(Clone, Debug, Deserialize, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct TrustedSigner {
+    /// The identity in the certificate, such as an email address or the URL
+    /// of a CI workflow.
+    pub identity: String,
+    /// The OIDC issuer which vouched for the identity.
+    pub issuer: String,
+}
+
+/// The verified signature of an app.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct SignatureVerification {
+    /// The registry reference, pinned to its digest, whose signature was
+    /// verified.
+    pub reference: String,
+    /// The identity of the trusted signer which signed it.
+    pub identity: String,
+    /// The OIDC issuer of the signer's identity.
+    pub issuer: String,
+}
+
+impl SecurityOpts {
+    /// Verifies that the app is signed by a trusted signer, if signed apps
+    /// are required. Returns `None` if they aren't.
+    ///
+    /// Signatures are verified with cosign, which must be installed.
+    pub fn verify_app<L>(&self, app: &App<'_, L>) -> Result<Option<SignatureVerification>> {
+        if !self.require_signed_apps {
+            return Ok(None);
+        }
+        if self.trusted_signers.is_empty() {
+            bail!("[security] require_signed_apps is set, but there is no [[security.trusted_signer]]");
+        }
+        let reference = app_reference(app)?;
+        for signer in &self.trusted_signers {
+            if cosign_verify(&reference, signer)? {
+                return Ok(Some(SignatureVerification {
+                    reference,
+                    identity: signer.identity.clone(),
+                    issuer: signer.issuer.clone(),
+                }));
+            }
+        }
+        bail!("{reference} is not signed by a trusted signer; not running it")
+    }
+
+    /// Checks that the app is the one whose signature was verified earlier,
+    /// such as before the trigger entered its sandbox, where cosign can't
+    /// run.
+    pub fn check_verified<L>(
+        &self,
+        app: &App<'_, L>,
+        verification: SignatureVerification,
+    ) -> Result<SignatureVerification> {
+        let reference = app_reference(app)?;
+        if reference != verification.reference {
+            bail!("{reference} is not the application whose signature was verified ({}); not running it", verification.reference);
+        }
+        Ok(verification)
+    }
+}
+
+fn app_reference<L>(app: &App<'_, L>) -> Result<String> {
+    signed_reference(
+        app.get_metadata(crate::ORIGIN_KEY)?.as_deref(),
+        app.get_metadata(OCI_IMAGE_DIGEST_KEY)?.as_deref(),
+    )
+}
+
+// The registry reference an app was pulled from, pinned to the digest which
+// was pulled so that a tag moved since can't substitute another app's
+// signature.
+fn signed_reference(origin: Option<&str>, digest: Option<&str>) -> Result<String> {
+    let Some(reference) = origin.and_then(|origin| origin.strip_prefix(ORIGIN_OCI_PREFIX)) else {
+        bail!("[security] require_signed_apps only allows applications from a registry. Run the application with `spin up --from <REFERENCE>`.");
+    };
+    if reference.contains('@') {
+        return Ok(reference.to_owned());
+    }
+    match digest {
+        Some(digest) => Ok(format!("{reference}@{digest}")),
+        None => bail!("The digest of {reference} is unknown, so its signature can't be verified"),
+    }
+}
+
+// Returns whether the reference is signed by the signer.
+fn cosign_verify(reference: &str, signer: &TrustedSigner) -> Result<bool> {
+    let output = std::process::Command::new("cosign")
+        .arg("verify")
+        .args(["--certificate-identity", &signer.identity])
+        .args(["--certificate-oidc-issuer", &signer.issuer])
+        .arg(reference)
+        .output();
+    let output = match output {
+        Ok(output) => output,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => bail!(
+            "Verifying application signatures requires cosign (https://docs.sigstore.dev/system_config/installation/)"
+        ),
+        Err(err) => return Err(err).context("Failed to run cosign"),
+    };
+    if !output.status.success() {
+        tracing::debug!(
+            "{reference} is not signed by {}: {}",
+            signer.identity,
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    Ok(output.status.success())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn references_are_pinned_to_digest() {
+        let origin = "vnd.fermyon.origin-oci:ghcr.io/acme/app:v1";
+        assert_eq!(
+            signed_reference(Some(origin), Some("sha256:abc")).unwrap(),
+            "ghcr.io/acme/app:v1@sha256:abc"
+        );
+        let pinned = "vnd.fermyon.origin-oci:ghcr.io/acme/app@sha256:abc";
+        assert_eq!(
+            signed_reference(Some(pinned), None).unwrap(),
+            "ghcr.io/acme/app@sha256:abc"
+        );
+        signed_reference(Some(origin), None).unwrap_err();
+        signed_reference(Some("file:///app/spin.toml"), None).unwrap_err();
+    }
+}
@@ -0,0 +1,67 @@
+use std::{collections::HashMap, sync::Arc};
+
+use anyhow::{Context, Result};
+use outbound_smtp::{OutboundSmtpComponent, SmtpServer, SmtpTls};
+use serde::Deserialize;
+
+use crate::RuntimeConfig;
+
+/// Options for a server components may send email through, read from an
+/// `[smtp_server.<name>]` runtime config table.
+#[derive(Clone, Debug, Deserialize, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct SmtpServerOpts {
+    pub host: String,
+    /// Defaults to 465 for `tls = "tls"`, 587 for `"starttls"` and 25 for
+    /// `"none"`.
+    pub port: Option<u16>,
+    #[serde(default)]
+    pub tls: SmtpTlsOpts,
+    pub username: Option<String>,
+    pub password: Option<String>,
+}
+
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum SmtpTlsOpts {
+    /// TLS from the start of the connection
+    Tls,
+    /// A plain connection upgraded with STARTTLS
+    #[default]
+    Starttls,
+    /// No TLS, for local relays and test servers
+    None,
+}
+
+impl SmtpServerOpts {
+    fn build(&self) -> Result<SmtpServer> {
+        let tls = match self.tls {
+            SmtpTlsOpts::Tls => SmtpTls::Implicit,
+            SmtpTlsOpts::Starttls => SmtpTls::StartTls,
+            SmtpTlsOpts::None => SmtpTls::None,
+        };
+        let credentials = match (&self.username, &self.password) {
+            (Some(username), Some(password)) => Some((username.clone(), password.clone())),
+            (None, None) => None,
+            _ => anyhow::bail!("`username` and `password` must be set together"),
+        };
+        SmtpServer::new(&self.host, self.port, tls, credentials)
+    }
+}
+
+pub(crate) fn build_component(
+    runtime_config: &RuntimeConfig,
+    resolver: spin_expressions::SharedPreparedResolver,
+) -> Result<OutboundSmtpComponent> {
+    let mut servers = HashMap::new();
+    for (name, opts) in runtime_config.smtp_servers() {
+        let server = opts
+            .build()
+            .with_context(|| format!("Invalid runtime config for SMTP server {name:?}"))?;
+        servers.insert(name, server);
+    }
+    Ok(OutboundSmtpComponent {
+        resolver,
+        servers: Arc::new(servers),
+    })
+}
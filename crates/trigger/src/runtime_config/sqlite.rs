@@ -98,6 +98,27 @@ impl SqliteDatabaseOpts {
             Self::Libsql(opts) => opts.build().await,
         }
     }
+
+    /// The kind of database, for summaries which leave out its settings.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Self::Spin(SpinSqliteDatabaseOpts { path: Some(_) }) => "file",
+            Self::Spin(SpinSqliteDatabaseOpts { path: None }) => "in_memory",
+            Self::Libsql(_) => "libsql",
+        }
+    }
+}
+
+impl From<SpinSqliteDatabaseOpts> for SqliteDatabaseOpts {
+    fn from(opts: SpinSqliteDatabaseOpts) -> Self {
+        Self::Spin(opts)
+    }
+}
+
+impl From<LibsqlOpts> for SqliteDatabaseOpts {
+    fn from(opts: LibsqlOpts) -> Self {
+        Self::Libsql(opts)
+    }
 }
 
 #[derive(Clone, Debug, serde::Deserialize)]
@@ -134,8 +155,8 @@ impl SpinSqliteDatabaseOpts {
 #[derive(Clone, Debug, serde::Deserialize)]
 #[serde(deny_unknown_fields)]
 pub struct LibsqlOpts {
-    url: String,
-    token: String,
+    pub url: String,
+    pub token: String,
 }
 
 impl LibsqlOpts {
@@ -8,7 +8,7 @@ use super::RuntimeConfig;
 pub type VariablesProvider = Box<dyn spin_expressions::Provider>;
 
 // Holds deserialized options from a `[[config_provider]]` runtime config section.
-#[derive(Debug, Deserialize)]
+#[derive(Clone, Debug, Deserialize)]
 #[serde(rename_all = "snake_case", tag = "type")]
 pub enum VariablesProviderOpts {
     Env(EnvVariablesProviderOpts),
@@ -28,9 +28,29 @@ impl VariablesProviderOpts {
             Self::Vault(opts) => opts.build_provider(),
         }
     }
+
+    /// The kind of provider, for summaries which leave out its settings.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Self::Env(_) => "env",
+            Self::Vault(_) => "vault",
+        }
+    }
+}
+
+impl From<EnvVariablesProviderOpts> for VariablesProviderOpts {
+    fn from(opts: EnvVariablesProviderOpts) -> Self {
+        Self::Env(opts)
+    }
+}
+
+impl From<VaultVariablesProviderOpts> for VariablesProviderOpts {
+    fn from(opts: VaultVariablesProviderOpts) -> Self {
+        Self::Vault(opts)
+    }
 }
 
-#[derive(Debug, Default, Deserialize)]
+#[derive(Clone, Debug, Default, Deserialize)]
 #[serde(deny_unknown_fields)]
 pub struct EnvVariablesProviderOpts {
     /// A prefix to add to variable names when resolving from the environment.
@@ -62,7 +82,7 @@ impl EnvVariablesProviderOpts {
     }
 }
 
-#[derive(Debug, Default, Deserialize)]
+#[derive(Clone, Debug, Default, Deserialize)]
 #[serde(deny_unknown_fields)]
 pub struct VaultVariablesProviderOpts {
     pub url: String,
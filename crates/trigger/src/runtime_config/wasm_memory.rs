@@ -0,0 +1,36 @@
+use serde::Deserialize;
+
+const MB: u64 = 1 << 20;
+
+/// Options for how components' linear memories are set up, read from the
+/// `[wasm_memory]` runtime config table.
+///
+/// Instantiating a component with a large initialized memory is dominated by
+/// initializing that memory. With `copy_on_write`, the default, it is mapped
+/// from an image rather than copied. The effect shows in the instantiation
+/// times of the `--timing` report and the trigger status.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct WasmMemoryOpts {
+    /// Initialize memories copy-on-write from images made when components
+    /// are compiled.
+    #[serde(default)]
+    pub copy_on_write: Option<bool>,
+    /// The size of the guard region after each statically-allocated memory,
+    /// in MiB.
+    #[serde(default)]
+    pub static_guard_mb: Option<u64>,
+}
+
+impl WasmMemoryOpts {
+    /// Applies the options to the configuration of the engine components
+    /// are run in.
+    pub fn apply(&self, config: &mut spin_core::Config) {
+        if let Some(enable) = self.copy_on_write {
+            config.memory_init_cow(enable);
+        }
+        if let Some(guard_mb) = self.static_guard_mb {
+            config.static_memory_guard_size(guard_mb * MB);
+        }
+    }
+}
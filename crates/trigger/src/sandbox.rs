@@ -0,0 +1,403 @@
+//! The opt-in `--sandbox` hardening mode, in which the trigger restricts
+//! itself so that an escape from a component into the host has as little
+//! reach as possible.
+//!
+//! Two restrictions are applied, on Linux only:
+//!
+//! - Landlock limits the filesystem to the app's files, the state and log
+//!   directories, the compilation cache and the system files needed to
+//!   resolve and verify hosts. Landlock only restricts the thread which
+//!   applies it and the threads it goes on to create, and a trigger is run
+//!   on an async runtime whose threads already exist. So the trigger works
+//!   out what it may access, then executes itself again, and the new
+//!   process restricts itself at the start of `main`, before it has any
+//!   other thread.
+//! - A seccomp filter, applied to every thread once the trigger has been
+//!   initialized, refuses the system calls a running trigger never needs,
+//!   such as running programs, creating processes, tracing processes or
+//!   loading kernel modules, and those of features it hasn't enabled.
+//!
+//! An app signature required by the `[security]` runtime config is verified
+//! with cosign before either restriction is applied, as neither would let
+//! cosign run.
+//!
+//! Files the runtime config places elsewhere, such as SQLite databases or
+//! log sink files outside the state and log directories, are inaccessible
+//! in the sandbox.
+
+use std::convert::Infallible;
+use std::path::PathBuf;
+use std::sync::OnceLock;
+
+#[cfg(target_os = "linux")]
+use anyhow::bail;
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use spin_app::locked::LockedApp;
+use spin_common::url::parse_file_url;
+
+use crate::runtime_config::{security::SignatureVerification, RuntimeConfig};
+
+/// Passes the sandbox to a trigger which executes itself again to enter it.
+const SANDBOX_ENTRY: &str = "SPIN_SANDBOX_ENTRY";
+
+/// Set once the process has entered the sandbox.
+static ENTERED: OnceLock<Entry> = OnceLock::new();
+
+/// System files which may be read in the sandbox: name resolution and TLS
+/// configuration, time zones, and the process's own information.
+#[cfg(target_os = "linux")]
+const SYSTEM_READ_ONLY: &[&str] = &[
+    "/etc",
+    "/usr/share/ca-certificates",
+    "/usr/share/zoneinfo",
+    "/usr/lib/ssl",
+    "/proc/self",
+    "/sys/devices/system/cpu",
+    "/dev/null",
+    "/dev/urandom",
+];
+
+/// The paths a sandboxed trigger may access.
+#[derive(Debug, Default, Deserialize, Serialize)]
+#[cfg_attr(not(target_os = "linux"), allow(dead_code))]
+pub(crate) struct SandboxPaths {
+    read_only: Vec<PathBuf>,
+    read_write: Vec<PathBuf>,
+}
+
+impl SandboxPaths {
+    /// The paths used by the app in `locked_url` when run by a trigger of
+    /// type `trigger_type`, with the given working directory and runtime
+    /// config.
+    pub fn new(
+        trigger_type: &str,
+        working_dir: &str,
+        locked_url: &str,
+        local_app_dir: Option<PathBuf>,
+        runtime_config: &RuntimeConfig,
+    ) -> Result<Self> {
+        let working_dir = PathBuf::from(working_dir);
+        let mut paths = Self::default();
+
+        let locked_path = parse_file_url(locked_url)?;
+        let locked = LockedApp::from_json(&std::fs::read(&locked_path)?)?;
+        paths.read_only.push(locked_path);
+        // Component sources, and the files mounted into them
+        for component in &locked.components {
+            let contents = std::iter::once(&component.source.content)
+                .chain(component.files.iter().map(|file| &file.content));
+            for source in contents.filter_map(|content| content.source.as_deref()) {
+                paths
+                    .read_only
+                    .push(working_dir.join(parse_file_url(source)?));
+            }
+        }
+        paths.read_only.extend(local_app_dir);
+        if let Some(cache_dir) = dirs::cache_dir() {
+            // Apps pulled from registries
+            paths.read_only.push(cache_dir.join("spin"));
+            paths.read_write.push(cache_dir.join("wasmtime"));
+        }
+
+        // Transient copies of mounted files are made in the working directory
+        paths.read_write.push(working_dir);
+        paths.read_write.extend(runtime_config.state_dir());
+        paths.read_write.extend(runtime_config.log_dir());
+        if runtime_config.profiling().is_enabled(trigger_type) {
+            // Perf maps are written to the temporary directory
+            paths.read_write.push(std::env::temp_dir());
+        }
+        Ok(paths)
+    }
+}
+
+/// What a trigger passes to itself when it executes itself again to enter
+/// the sandbox.
+#[derive(Debug, Deserialize, Serialize)]
+pub(crate) struct Entry {
+    paths: SandboxPaths,
+    /// The app's signature, verified before entering the sandbox.
+    pub verified_signature: Option<SignatureVerification>,
+}
+
+/// Enters the sandbox prepared by a trigger which executed this process to
+/// enter it, if there is one. It must be called at the start of `main`,
+/// while the process has only one thread.
+pub fn enter_from_env() -> Result<()> {
+    let Some(entry) = std::env::var_os(SANDBOX_ENTRY) else {
+        return Ok(());
+    };
+    std::env::remove_var(SANDBOX_ENTRY);
+    let entry: Entry = serde_json::from_str(entry.to_str().context(SANDBOX_ENTRY)?)
+        .with_context(|| format!("Invalid {SANDBOX_ENTRY}"))?;
+    restrict_filesystem(&entry.paths)?;
+    ENTERED
+        .set(entry)
+        .map_err(|_| anyhow::anyhow!("The sandbox has already been entered"))
+}
+
+/// The sandbox this process entered, if it did.
+pub(crate) fn entered() -> Option<&'static Entry> {
+    ENTERED.get()
+}
+
+/// Executes this trigger again with the same arguments, in a process which
+/// enters the sandbox before it creates any threads. Returns only if that
+/// fails.
+#[cfg(target_os = "linux")]
+pub(crate) fn reexec(
+    paths: SandboxPaths,
+    verified_signature: Option<SignatureVerification>,
+) -> Result<Infallible> {
+    use std::os::unix::process::CommandExt;
+
+    if std::env::var_os(SANDBOX_ENTRY).is_some() {
+        // The new process's `main` didn't call `enter_from_env`
+        bail!("This trigger doesn't support --sandbox");
+    }
+    let entry = serde_json::to_string(&Entry {
+        paths,
+        verified_signature,
+    })?;
+    let exe = std::env::current_exe().context("Failed to find the Spin executable")?;
+    let mut args = std::env::args_os();
+    let mut command = std::process::Command::new(&exe);
+    if let Some(arg0) = args.next() {
+        command.arg0(arg0);
+    }
+    let err = command.args(args).env(SANDBOX_ENTRY, entry).exec();
+    Err(err).with_context(|| format!("Failed to run {} in the sandbox", exe.display()))
+}
+
+#[cfg(not(target_os = "linux"))]
+pub(crate) fn reexec(
+    _paths: SandboxPaths,
+    _verified_signature: Option<SignatureVerification>,
+) -> Result<Infallible> {
+    anyhow::bail!("--sandbox is only supported on Linux")
+}
+
+/// Restricts the current thread, and the threads it goes on to create, to
+/// the given paths.
+#[cfg(target_os = "linux")]
+fn restrict_filesystem(paths: &SandboxPaths) -> Result<()> {
+    use landlock::{
+        path_beneath_rules, Access, AccessFs, Ruleset, RulesetAttr, RulesetCreatedAttr,
+        RulesetStatus, ABI,
+    };
+
+    // Paths which don't exist yet can't be allowed once restricted
+    for dir in &paths.read_write {
+        std::fs::create_dir_all(dir)
+            .with_context(|| format!("Failed to create sandbox directory {}", dir.display()))?;
+    }
+
+    let abi = ABI::V2;
+    let status = Ruleset::default()
+        .handle_access(AccessFs::from_all(abi))?
+        .create()?
+        .add_rules(path_beneath_rules(
+            SYSTEM_READ_ONLY,
+            AccessFs::from_read(abi),
+        ))?
+        .add_rules(path_beneath_rules(
+            &paths.read_only,
+            AccessFs::from_read(abi),
+        ))?
+        .add_rules(path_beneath_rules(
+            &paths.read_write,
+            AccessFs::from_all(abi),
+        ))?
+        .restrict_self()
+        .context("Failed to restrict filesystem access")?;
+    match status.ruleset {
+        RulesetStatus::FullyEnforced => {}
+        RulesetStatus::PartiallyEnforced => {
+            terminal::warn!(
+                "This kernel only partly supports Landlock: the filesystem sandbox is incomplete."
+            )
+        }
+        RulesetStatus::NotEnforced => {
+            bail!("This kernel does not support Landlock, which --sandbox requires")
+        }
+    }
+    Ok(())
+}
+
+#[cfg(not(target_os = "linux"))]
+fn restrict_filesystem(_paths: &SandboxPaths) -> Result<()> {
+    anyhow::bail!("--sandbox is only supported on Linux")
+}
+
+/// Which system calls a sandboxed trigger may make beyond those every
+/// trigger needs, according to the features it has enabled.
+#[derive(Debug, Default)]
+pub(crate) struct SyscallPolicy {
+    /// Profiling is enabled, and so may use perf events.
+    pub profiling: bool,
+    /// The trigger changes its root directory once it is listening.
+    pub chroot: bool,
+    /// The trigger switches to another user and group once it is listening.
+    pub switch_user: bool,
+    /// The trigger, or its status, admin or metrics API, accepts
+    /// connections.
+    pub listen: bool,
+}
+
+/// Refuses, in every thread of the process, the system calls which the
+/// policy doesn't allow.
+#[cfg(target_os = "linux")]
+pub(crate) fn restrict_syscalls(policy: &SyscallPolicy) -> Result<()> {
+    use std::collections::BTreeMap;
+
+    use seccompiler::{
+        BpfProgram, SeccompAction, SeccompCmpArgLen, SeccompCmpOp, SeccompCondition, SeccompFilter,
+        SeccompRule,
+    };
+
+    let mut denied = vec![
+        libc::SYS_execve,
+        libc::SYS_execveat,
+        libc::SYS_ptrace,
+        libc::SYS_process_vm_readv,
+        libc::SYS_process_vm_writev,
+        libc::SYS_mount,
+        libc::SYS_umount2,
+        libc::SYS_pivot_root,
+        libc::SYS_unshare,
+        libc::SYS_setns,
+        libc::SYS_swapon,
+        libc::SYS_swapoff,
+        libc::SYS_reboot,
+        libc::SYS_kexec_load,
+        libc::SYS_kexec_file_load,
+        libc::SYS_init_module,
+        libc::SYS_finit_module,
+        libc::SYS_delete_module,
+        libc::SYS_bpf,
+        libc::SYS_userfaultfd,
+        libc::SYS_keyctl,
+        libc::SYS_add_key,
+        libc::SYS_request_key,
+        libc::SYS_personality,
+        libc::SYS_acct,
+        libc::SYS_settimeofday,
+        libc::SYS_clock_settime,
+        libc::SYS_sethostname,
+        libc::SYS_setdomainname,
+    ];
+    #[cfg(target_arch = "x86_64")]
+    denied.extend([
+        libc::SYS_fork,
+        libc::SYS_vfork,
+        libc::SYS_iopl,
+        libc::SYS_ioperm,
+    ]);
+    if !policy.profiling {
+        denied.push(libc::SYS_perf_event_open);
+    }
+    if !policy.chroot {
+        denied.push(libc::SYS_chroot);
+    }
+    if !policy.switch_user {
+        denied.extend([
+            libc::SYS_setuid,
+            libc::SYS_setgid,
+            libc::SYS_setreuid,
+            libc::SYS_setregid,
+            libc::SYS_setresuid,
+            libc::SYS_setresgid,
+            libc::SYS_setfsuid,
+            libc::SYS_setfsgid,
+            libc::SYS_setgroups,
+            libc::SYS_capset,
+        ]);
+    }
+    if !policy.listen {
+        denied.extend([libc::SYS_listen, libc::SYS_accept, libc::SYS_accept4]);
+    }
+
+    let mut rules: BTreeMap<_, _> = denied
+        .into_iter()
+        .map(|syscall| (syscall, Vec::<SeccompRule>::new()))
+        .collect();
+    // New threads may be created, but not new processes: clone is refused
+    // without CLONE_THREAD
+    rules.insert(
+        libc::SYS_clone,
+        vec![SeccompRule::new(vec![SeccompCondition::new(
+            0,
+            SeccompCmpArgLen::Qword,
+            SeccompCmpOp::MaskedEq(libc::CLONE_THREAD as u64),
+            0,
+        )?])?],
+    );
+    let arch: seccompiler::TargetArch = std::env::consts::ARCH
+        .try_into()
+        .context("seccomp is not supported on this architecture")?;
+    let filter = SeccompFilter::new(
+        rules,
+        SeccompAction::Allow,
+        SeccompAction::Errno(libc::EPERM as u32),
+        arch,
+    )?;
+    // The flags of clone3 are out of a filter's reach, in memory, so it is
+    // reported as unimplemented, which makes the C library fall back to
+    // clone
+    let clone3 = SeccompFilter::new(
+        [(libc::SYS_clone3, vec![])].into(),
+        SeccompAction::Allow,
+        SeccompAction::Errno(libc::ENOSYS as u32),
+        arch,
+    )?;
+    for filter in [filter, clone3] {
+        let program: BpfProgram = filter.try_into()?;
+        seccompiler::apply_filter_all_threads(&program)
+            .context("Failed to apply seccomp filter")?;
+    }
+    Ok(())
+}
+
+#[cfg(not(target_os = "linux"))]
+pub(crate) fn restrict_syscalls(_policy: &SyscallPolicy) -> Result<()> {
+    anyhow::bail!("--sandbox is only supported on Linux")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn app_files_are_readable() {
+        let dir = tempfile::tempdir().unwrap();
+        let locked = serde_json::json!({
+            "spin_lock_version": 1,
+            "triggers": [],
+            "components": [{
+                "id": "hello",
+                "source": {
+                    "content_type": "application/wasm",
+                    "source": "file:///app/hello.wasm",
+                },
+                "files": [{ "source": "file:///app/static", "path": "/" }],
+            }],
+        });
+        let locked_path = dir.path().join("spin.lock");
+        std::fs::write(&locked_path, locked.to_string()).unwrap();
+        let locked_url = url::Url::from_file_path(&locked_path).unwrap();
+
+        let paths = SandboxPaths::new(
+            "http",
+            dir.path().to_str().unwrap(),
+            locked_url.as_str(),
+            None,
+            &RuntimeConfig::new(None),
+        )
+        .unwrap();
+        assert!(paths.read_only.contains(&locked_path));
+        assert!(paths.read_only.contains(&PathBuf::from("/app/hello.wasm")));
+        assert!(paths.read_only.contains(&PathBuf::from("/app/static")));
+        assert!(paths.read_write.contains(&dir.path().to_owned()));
+    }
+}
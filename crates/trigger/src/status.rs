@@ -0,0 +1,525 @@
+//! Structured status of a running trigger, for operators.
+//!
+//! The [`TriggerStatus`] of a trigger records what each of its components is
+//! subscribed to, how many executions are running and waiting for a
+//! concurrency slot, the last error, and where the time to start it went.
+//! Executions are counted by the [`TriggerAppEngine`](crate::TriggerAppEngine)
+//! as permits are acquired, and timings recorded as components are prepared
+//! and instantiated; subscriptions and errors are reported by the trigger. With
+//! `--status-listen`, the status is served as JSON, along with any sections
+//! a trigger adds about itself, such as the HTTP trigger's TLS certificates.
+//! Executions and errors are
+//! also recorded as component metrics, which are exported over OTLP when it
+//! is configured, and emitted as [`RuntimeEvent`]s to any event hooks.
+
+use std::collections::BTreeMap;
+use std::convert::Infallible;
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use anyhow::{Context, Result};
+use http_body_util::Full;
+use hyper::{body::Bytes, server::conn::http1, service::service_fn, Method, Response, StatusCode};
+use hyper_util::rt::TokioIo;
+use serde::Serialize;
+use spin_core::Trap;
+use spin_telemetry::events::RuntimeEvent;
+use tokio::net::TcpListener;
+
+/// The path at which the status is served.
+pub const STATUS_PATH: &str = "/status";
+
+/// The status of a trigger's components, shared between the trigger and the
+/// status listener.
+#[derive(Clone)]
+pub struct TriggerStatus {
+    trigger_type: &'static str,
+    started: Instant,
+    components: Arc<Mutex<BTreeMap<String, ComponentState>>>,
+    sections: Arc<Mutex<BTreeMap<&'static str, StatusSection>>>,
+    // Set once the trigger refuses new executions before stopping
+    draining: Arc<AtomicBool>,
+}
+
+/// Reports part of a trigger's status when the status is requested.
+type StatusSection = Arc<dyn Fn() -> serde_json::Value + Send + Sync>;
+
+#[derive(Default)]
+struct ComponentState {
+    subscriptions: Vec<String>,
+    in_flight: usize,
+    queued: usize,
+    executions: u64,
+    last_error: Option<LastError>,
+    timing: ComponentTiming,
+}
+
+/// A snapshot of a trigger's status.
+#[derive(Debug, Serialize)]
+pub struct StatusReport {
+    pub trigger: String,
+    pub uptime_secs: u64,
+    /// Whether new executions are refused, so that the trigger can stop
+    pub draining: bool,
+    pub components: BTreeMap<String, ComponentStatus>,
+    /// Sections the trigger adds about itself, by name
+    #[serde(flatten)]
+    pub sections: BTreeMap<String, serde_json::Value>,
+}
+
+/// A snapshot of a component's status.
+#[derive(Debug, Serialize)]
+pub struct ComponentStatus {
+    /// What the component is invoked for, e.g. channels or routes
+    pub subscriptions: Vec<String>,
+    /// Executions which are running
+    pub in_flight: usize,
+    /// Executions waiting for a concurrency slot
+    pub queued: usize,
+    /// Executions which have finished since the trigger started
+    pub executions: u64,
+    pub last_error: Option<LastError>,
+    pub timing: ComponentTiming,
+}
+
+/// Where the time to start a component went, for attributing cold-start
+/// latency. Times are in milliseconds, and unset until measured.
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct ComponentTiming {
+    /// Compiling the component, or loading it from the cache
+    pub compile_ms: Option<f64>,
+    /// Linking the compiled component's imports
+    pub link_ms: Option<f64>,
+    /// Preparing the component for instantiation, including compiling and
+    /// linking it
+    pub pre_instantiation_ms: Option<f64>,
+    /// Instantiating the component for its first execution
+    pub first_instantiation_ms: Option<f64>,
+    /// Instantiating the component, on average over all its executions.
+    /// This is mostly initializing its memory, so shows the effect of
+    /// copy-on-write memory initialization
+    pub mean_instantiation_ms: Option<f64>,
+    /// The number of times the component has been instantiated
+    pub instantiations: u64,
+}
+
+/// The last error reported for a component.
+#[derive(Clone, Debug, Serialize)]
+pub struct LastError {
+    pub message: String,
+    /// When the error was reported, in seconds since the Unix epoch
+    pub at: u64,
+}
+
+impl TriggerStatus {
+    pub fn new(trigger_type: &'static str) -> Self {
+        Self {
+            trigger_type,
+            started: Instant::now(),
+            components: Default::default(),
+            sections: Default::default(),
+            draining: Default::default(),
+        }
+    }
+
+    /// Adds a section to the status report, such as the status of the
+    /// trigger's listeners, which `report` is called for each time the
+    /// status is requested.
+    pub fn add_section(
+        &self,
+        name: &'static str,
+        report: impl Fn() -> serde_json::Value + Send + Sync + 'static,
+    ) {
+        self.sections.lock().unwrap().insert(name, Arc::new(report));
+    }
+
+    /// Adds a component, so that it is reported before it is first executed.
+    pub fn add_component(&self, component: &str) {
+        self.update(component, |_| ());
+    }
+
+    /// Records something a component is invoked for, such as a channel,
+    /// queue or route.
+    pub fn add_subscription(&self, component: &str, subscription: impl Into<String>) {
+        let subscription = subscription.into();
+        self.update(component, |state| {
+            if !state.subscriptions.contains(&subscription) {
+                state.subscriptions.push(subscription);
+            }
+        });
+    }
+
+    /// Records an error handling an event for a component.
+    pub fn record_error(&self, component: &str, error: &anyhow::Error) {
+        let at = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        let message = format!("{error:#}");
+        spin_telemetry::metrics::record_error(self.trigger_type, component);
+        self.emit_error(component, error);
+        self.update(component, |state| {
+            state.last_error = Some(LastError { message, at })
+        });
+    }
+
+    /// Records the error of a failed execution for a component, passing the
+    /// result through.
+    pub fn record<T>(&self, component: &str, result: Result<T>) -> Result<T> {
+        if let Err(err) = &result {
+            self.record_error(component, err);
+        }
+        result
+    }
+
+    /// Records the time taken to prepare a component for instantiation, of
+    /// which `compilation` was spent compiling it.
+    pub(crate) fn record_preparation(
+        &self,
+        component: &str,
+        compilation: Duration,
+        pre_instantiation: Duration,
+    ) {
+        let linking = pre_instantiation.saturating_sub(compilation);
+        spin_telemetry::metrics::record_compilation(self.trigger_type, component, compilation);
+        spin_telemetry::metrics::record_linking(self.trigger_type, component, linking);
+        self.update(component, |state| {
+            state.timing.compile_ms = Some(millis(compilation));
+            state.timing.link_ms = Some(millis(linking));
+            state.timing.pre_instantiation_ms = Some(millis(pre_instantiation));
+        });
+    }
+
+    /// Records the time taken to instantiate a component, returning true if
+    /// this was its first instantiation.
+    pub(crate) fn record_instantiation(&self, component: &str, duration: Duration) -> bool {
+        let mut first = false;
+        self.update(component, |state| {
+            let timing = &mut state.timing;
+            if timing.first_instantiation_ms.is_none() {
+                timing.first_instantiation_ms = Some(millis(duration));
+                first = true;
+            }
+            timing.instantiations += 1;
+            let mean = timing.mean_instantiation_ms.unwrap_or_default();
+            timing.mean_instantiation_ms =
+                Some(mean + (millis(duration) - mean) / timing.instantiations as f64);
+        });
+        first
+    }
+
+    /// Refuses new executions from now on, so that the trigger can stop once
+    /// the executions already running or queued have finished.
+    pub fn drain(&self) {
+        self.draining.store(true, Ordering::Release);
+    }
+
+    /// Whether new executions are refused.
+    pub fn is_draining(&self) -> bool {
+        self.draining.load(Ordering::Acquire)
+    }
+
+    /// Returns the number of executions running or waiting for a slot.
+    pub fn pending_executions(&self) -> usize {
+        let components = self.components.lock().unwrap();
+        components
+            .values()
+            .map(|state| state.in_flight + state.queued)
+            .sum()
+    }
+
+    /// Returns the startup timing of each component.
+    pub fn timings(&self) -> BTreeMap<String, ComponentTiming> {
+        let components = self.components.lock().unwrap();
+        components
+            .iter()
+            .map(|(id, state)| (id.clone(), state.timing.clone()))
+            .collect()
+    }
+
+    /// Counts an execution as waiting for a concurrency slot until the
+    /// returned guard is dropped.
+    pub(crate) fn queue(&self, component: &str) -> Queued {
+        self.update(component, |state| state.queued += 1);
+        Queued {
+            status: self.clone(),
+            component: component.to_owned(),
+        }
+    }
+
+    /// Counts an execution as running until the returned guard is dropped.
+    pub(crate) fn start(&self, component: &str) -> InFlight {
+        spin_telemetry::events::emit(RuntimeEvent::HandlerStarted {
+            trigger_type: self.trigger_type,
+            component_id: component,
+        });
+        self.update(component, |state| state.in_flight += 1);
+        InFlight {
+            status: self.clone(),
+            component: component.to_owned(),
+            started: Instant::now(),
+        }
+    }
+
+    /// Returns a snapshot of the status.
+    pub fn report(&self) -> StatusReport {
+        // Sections are reported unlocked, as they may take locks of their own
+        let sections: Vec<_> = self
+            .sections
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(name, report)| (*name, report.clone()))
+            .collect();
+        let sections = sections
+            .into_iter()
+            .map(|(name, report)| (name.to_owned(), report()))
+            .collect();
+        let components = self.components.lock().unwrap();
+        StatusReport {
+            trigger: self.trigger_type.to_owned(),
+            uptime_secs: self.started.elapsed().as_secs(),
+            draining: self.is_draining(),
+            components: components
+                .iter()
+                .map(|(id, state)| {
+                    let status = ComponentStatus {
+                        subscriptions: state.subscriptions.clone(),
+                        in_flight: state.in_flight,
+                        queued: state.queued,
+                        executions: state.executions,
+                        last_error: state.last_error.clone(),
+                        timing: state.timing.clone(),
+                    };
+                    (id.clone(), status)
+                })
+                .collect(),
+            sections,
+        }
+    }
+
+    // Emits an event for an error which is a guest trap.
+    fn emit_error(&self, component: &str, error: &anyhow::Error) {
+        let event = match error.downcast_ref::<Trap>() {
+            Some(Trap::Interrupt) => RuntimeEvent::DeadlineExceeded {
+                trigger_type: self.trigger_type,
+                component_id: component,
+            },
+            Some(_) => RuntimeEvent::Trapped {
+                trigger_type: self.trigger_type,
+                component_id: component,
+                error,
+            },
+            None => return,
+        };
+        spin_telemetry::events::emit(event);
+    }
+
+    fn update(&self, component: &str, f: impl FnOnce(&mut ComponentState)) {
+        let mut components = self.components.lock().unwrap();
+        f(components.entry(component.to_owned()).or_default())
+    }
+
+    /// Serves the status as JSON at [`STATUS_PATH`] until the trigger stops.
+    pub async fn serve(self, addr: SocketAddr) -> Result<()> {
+        let listener = TcpListener::bind(addr)
+            .await
+            .with_context(|| format!("Unable to listen for status requests on {addr}"))?;
+        let addr = listener.local_addr()?;
+        terminal::text!(
+            "Serving {} trigger status on http://{addr}{STATUS_PATH}",
+            self.trigger_type
+        );
+        loop {
+            let (stream, _) = listener.accept().await?;
+            let status = self.clone();
+            tokio::spawn(async move {
+                let service = service_fn(move |req| {
+                    let response = status.respond(req.method(), req.uri().path());
+                    async move { Ok::<_, Infallible>(response) }
+                });
+                if let Err(err) = http1::Builder::new()
+                    .serve_connection(TokioIo::new(stream), service)
+                    .await
+                {
+                    tracing::debug!("Error serving status request: {err}");
+                }
+            });
+        }
+    }
+
+    fn respond(&self, method: &Method, path: &str) -> Response<Full<Bytes>> {
+        // Each section is also served on its own, e.g. at /status/tls
+        let section = path
+            .strip_prefix(STATUS_PATH)
+            .and_then(|rest| rest.strip_prefix('/'))
+            .map(|name| self.sections.lock().unwrap().get(name).cloned());
+        let (status, body) = match (method, path, section) {
+            (&Method::GET, STATUS_PATH, _) => json_body(&self.report()),
+            (&Method::GET, _, Some(Some(report))) => json_body(&report()),
+            (_, STATUS_PATH, _) | (_, _, Some(Some(_))) => (StatusCode::METHOD_NOT_ALLOWED, vec![]),
+            _ => (StatusCode::NOT_FOUND, vec![]),
+        };
+        let mut response = Response::new(Full::new(body.into()));
+        *response.status_mut() = status;
+        if status == StatusCode::OK {
+            response.headers_mut().insert(
+                hyper::header::CONTENT_TYPE,
+                hyper::header::HeaderValue::from_static("application/json"),
+            );
+        }
+        response
+    }
+}
+
+fn json_body(value: &impl Serialize) -> (StatusCode, Vec<u8>) {
+    match serde_json::to_vec_pretty(value) {
+        Ok(json) => (StatusCode::OK, json),
+        Err(_) => (StatusCode::INTERNAL_SERVER_ERROR, vec![]),
+    }
+}
+
+fn millis(duration: Duration) -> f64 {
+    duration.as_secs_f64() * 1000.0
+}
+
+/// An execution waiting for a concurrency slot.
+pub(crate) struct Queued {
+    status: TriggerStatus,
+    component: String,
+}
+
+impl Drop for Queued {
+    fn drop(&mut self) {
+        self.status
+            .update(&self.component, |state| state.queued -= 1);
+    }
+}
+
+/// A running execution.
+pub(crate) struct InFlight {
+    status: TriggerStatus,
+    component: String,
+    started: Instant,
+}
+
+impl Drop for InFlight {
+    fn drop(&mut self) {
+        let duration = self.started.elapsed();
+        spin_telemetry::metrics::record_execution(
+            self.status.trigger_type,
+            &self.component,
+            duration,
+        );
+        spin_telemetry::events::emit(RuntimeEvent::HandlerCompleted {
+            trigger_type: self.status.trigger_type,
+            component_id: &self.component,
+            duration,
+        });
+        self.status.update(&self.component, |state| {
+            state.in_flight -= 1;
+            state.executions += 1;
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn report_counts_executions_and_keeps_last_error() {
+        let status = TriggerStatus::new("redis");
+        status.add_component("idle");
+        status.add_subscription("orders", "redis://localhost:6379/orders");
+
+        let queued = status.queue("orders");
+        let running = status.start("orders");
+        let report = status.report();
+        assert_eq!(report.trigger, "redis");
+        assert_eq!(report.components["orders"].queued, 1);
+        assert_eq!(report.components["orders"].in_flight, 1);
+        assert_eq!(report.components["idle"].in_flight, 0);
+
+        drop(queued);
+        drop(running);
+        status.record_error("orders", &anyhow::anyhow!("boom"));
+        let report = status.report();
+        let orders = &report.components["orders"];
+        assert_eq!(orders.queued, 0);
+        assert_eq!(orders.in_flight, 0);
+        assert_eq!(orders.executions, 1);
+        assert_eq!(orders.subscriptions, ["redis://localhost:6379/orders"]);
+        assert_eq!(orders.last_error.as_ref().unwrap().message, "boom");
+    }
+
+    #[test]
+    fn draining_counts_pending_executions() {
+        let status = TriggerStatus::new("http");
+        let queued = status.queue("hello");
+        let running = status.start("hello");
+        status.drain();
+        assert!(status.is_draining());
+        assert!(status.report().draining);
+        assert_eq!(status.pending_executions(), 2);
+        drop(queued);
+        drop(running);
+        assert_eq!(status.pending_executions(), 0);
+    }
+
+    #[test]
+    fn timing_keeps_first_instantiation() {
+        let status = TriggerStatus::new("http");
+        status.record_preparation(
+            "hello",
+            Duration::from_millis(30),
+            Duration::from_millis(40),
+        );
+        assert!(status.record_instantiation("hello", Duration::from_millis(5)));
+        assert!(!status.record_instantiation("hello", Duration::from_millis(1)));
+
+        let timing = &status.timings()["hello"];
+        assert_eq!(timing.compile_ms, Some(30.0));
+        assert_eq!(timing.link_ms, Some(10.0));
+        assert_eq!(timing.first_instantiation_ms, Some(5.0));
+        assert_eq!(timing.mean_instantiation_ms, Some(3.0));
+        assert_eq!(timing.instantiations, 2);
+    }
+
+    #[test]
+    fn status_is_served_only_at_its_path() {
+        let status = TriggerStatus::new("http");
+        let response = status.respond(&Method::GET, STATUS_PATH);
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(
+            response.headers()[hyper::header::CONTENT_TYPE],
+            "application/json"
+        );
+        assert_eq!(
+            status.respond(&Method::POST, STATUS_PATH).status(),
+            StatusCode::METHOD_NOT_ALLOWED
+        );
+        assert_eq!(
+            status.respond(&Method::GET, "/").status(),
+            StatusCode::NOT_FOUND
+        );
+    }
+
+    #[test]
+    fn sections_are_reported_and_served() {
+        let status = TriggerStatus::new("http");
+        status.add_section("tls", || serde_json::json!({ "certificates": [] }));
+        let report = serde_json::to_value(status.report()).unwrap();
+        assert_eq!(report["tls"]["certificates"], serde_json::json!([]));
+        assert_eq!(
+            status.respond(&Method::GET, "/status/tls").status(),
+            StatusCode::OK
+        );
+        assert_eq!(
+            status.respond(&Method::GET, "/status/other").status(),
+            StatusCode::NOT_FOUND
+        );
+    }
+}
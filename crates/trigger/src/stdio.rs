@@ -1,10 +1,12 @@
 use std::{
     collections::HashSet,
+    io::Write,
     path::{Path, PathBuf},
     task::Poll,
 };
 
 use anyhow::{Context, Result};
+use chrono::{DateTime, FixedOffset, SecondsFormat, Utc};
 use spin_common::ui::quoted_path;
 use tokio::io::AsyncWrite;
 
@@ -55,13 +57,20 @@ impl StdioLoggingTriggerHooks {
     fn component_stdio_writer(
         &self,
         component_id: &str,
-        log_suffix: &str,
+        log_suffix: &'static str,
         log_dir: &Path,
     ) -> Result<ComponentStdioWriter> {
-        let sanitized_component_id = sanitize_filename::sanitize(component_id);
-        let log_path = log_dir.join(format!("{sanitized_component_id}_{log_suffix}.txt"));
+        let log_path = component_log_path(log_dir, component_id, log_suffix);
         let follow = self.follow_components.should_follow(component_id);
-        ComponentStdioWriter::new(&log_path, follow)
+        let writer = if follow && spin_telemetry::logs::log_format().is_json() {
+            ComponentStdioWriter::new(&log_path, false).map(|writer| {
+                writer.with_json_follow(JsonStdioWriter::new(component_id, log_suffix))
+            })
+        } else {
+            ComponentStdioWriter::new(&log_path, follow)
+        };
+        writer
+            .map(|writer| writer.with_forwarder(OutputForwarder::new(component_id, log_suffix)))
             .with_context(|| format!("Failed to open log file {}", quoted_path(&log_path)))
     }
 
@@ -101,7 +110,7 @@ impl TriggerHooks for StdioLoggingTriggerHooks {
             std::fs::create_dir_all(dir)
                 .with_context(|| format!("Failed to create log dir {}", quoted_path(dir)))?;
 
-            println!("Logging component stdio to {}", quoted_path(dir.join("")))
+            terminal::text!("Logging component stdio to {}", quoted_path(dir.join("")))
         }
 
         Ok(())
@@ -117,6 +126,22 @@ impl TriggerHooks for StdioLoggingTriggerHooks {
                 builder.stdout_pipe(self.component_stdio_writer(component.id(), "stdout", l)?);
                 builder.stderr_pipe(self.component_stdio_writer(component.id(), "stderr", l)?);
             }
+            None if spin_telemetry::logs::log_format().is_json() => {
+                // Component output is still inherited, but as JSON log lines
+                // on stderr
+                builder.stdout_pipe(
+                    JsonStdioWriter::new(component.id(), "stdout")
+                        .with_forwarder(OutputForwarder::new(component.id(), "stdout")),
+                );
+                builder.stderr_pipe(
+                    JsonStdioWriter::new(component.id(), "stderr")
+                        .with_forwarder(OutputForwarder::new(component.id(), "stderr")),
+                );
+            }
+            None if OutputForwarder::wanted() => {
+                builder.stdout_pipe(InheritStdioWriter::new(component.id(), "stdout"));
+                builder.stderr_pipe(InheritStdioWriter::new(component.id(), "stderr"));
+            }
             None => {
                 builder.inherit_stdout();
                 builder.inherit_stderr();
@@ -127,12 +152,59 @@ impl TriggerHooks for StdioLoggingTriggerHooks {
     }
 }
 
-/// ComponentStdioWriter forwards output to a log file and (optionally) stderr
+/// The log streams written for each component.
+pub const COMPONENT_LOG_STREAMS: [&str; 2] = ["stdout", "stderr"];
+
+/// The path of the log file for a component's `stream` (stdout or stderr).
+pub fn component_log_path(log_dir: &Path, component_id: &str, stream: &str) -> PathBuf {
+    let sanitized_component_id = sanitize_filename::sanitize(component_id);
+    log_dir.join(format!("{sanitized_component_id}_{stream}.txt"))
+}
+
+/// Splits a line of a component log file into the time it was written and
+/// its text. Lines written by older versions of Spin have no time.
+pub fn parse_log_line(line: &str) -> (Option<DateTime<FixedOffset>>, &str) {
+    if let Some((timestamp, text)) = line.split_once(' ') {
+        if let Ok(time) = DateTime::parse_from_rfc3339(timestamp) {
+            return (Some(time), text);
+        }
+    }
+    (None, line)
+}
+
+/// Prefixes each line started in `buf` with the current time.
+fn timestamp_lines(buf: &[u8], line_start: &mut bool) -> Vec<u8> {
+    let timestamp = Utc::now().to_rfc3339_opts(SecondsFormat::Millis, true);
+    let mut timestamped = Vec::with_capacity(buf.len() + timestamp.len() + 1);
+    for line in buf.split_inclusive(|b| *b == b'\n') {
+        if *line_start {
+            timestamped.extend_from_slice(timestamp.as_bytes());
+            timestamped.push(b' ');
+        }
+        timestamped.extend_from_slice(line);
+        *line_start = line.ends_with(b"\n");
+    }
+    timestamped
+}
+
+/// ComponentStdioWriter forwards output to a log file and (optionally) stderr.
+///
+/// Each line in the log file starts with the time it was written, so that
+/// `spin logs` can merge and filter the logs of different components.
 pub struct ComponentStdioWriter {
     sync_file: std::fs::File,
     async_file: tokio::fs::File,
     state: ComponentStdioWriterState,
     follow: bool,
+    // Whether the next output starts a line
+    line_start: bool,
+    // Timestamped output not yet written to the file, and the length of the
+    // output it came from
+    pending: Option<(Vec<u8>, usize)>,
+    // Writes followed output as JSON log lines, instead of as it is
+    json_follow: Option<JsonStdioWriter>,
+    // Forwards output to the log sinks and debug capture
+    forwarder: Option<OutputForwarder>,
 }
 
 #[derive(Debug)]
@@ -156,8 +228,24 @@ impl ComponentStdioWriter {
             sync_file,
             state: ComponentStdioWriterState::File,
             follow,
+            line_start: true,
+            pending: None,
+            json_follow: None,
+            forwarder: None,
         })
     }
+
+    /// Follows the output as JSON log lines on stderr.
+    pub fn with_json_follow(mut self, json_follow: JsonStdioWriter) -> Self {
+        self.json_follow = Some(json_follow);
+        self
+    }
+
+    /// Also forwards the output to the log sinks and debug capture.
+    pub fn with_forwarder(mut self, forwarder: Option<OutputForwarder>) -> Self {
+        self.forwarder = forwarder;
+        self
+    }
 }
 
 impl AsyncWrite for ComponentStdioWriter {
@@ -170,13 +258,30 @@ impl AsyncWrite for ComponentStdioWriter {
         loop {
             match &this.state {
                 ComponentStdioWriterState::File => {
-                    let written = futures::ready!(
-                        std::pin::Pin::new(&mut this.async_file).poll_write(cx, buf)
-                    );
-                    let written = match written {
-                        Ok(e) => e,
-                        Err(e) => return Poll::Ready(Err(e)),
-                    };
+                    let line_start = &mut this.line_start;
+                    let (pending, consumed) = this
+                        .pending
+                        .get_or_insert_with(|| (timestamp_lines(buf, line_start), buf.len()));
+                    while !pending.is_empty() {
+                        let written = futures::ready!(
+                            std::pin::Pin::new(&mut this.async_file).poll_write(cx, pending)
+                        );
+                        let written = match written {
+                            Ok(0) => return Poll::Ready(Err(std::io::ErrorKind::WriteZero.into())),
+                            Ok(e) => e,
+                            Err(e) => return Poll::Ready(Err(e)),
+                        };
+                        pending.drain(..written);
+                    }
+                    let written = *consumed;
+                    this.pending = None;
+                    if let Some(forwarder) = &mut this.forwarder {
+                        forwarder.write(&buf[..written]);
+                    }
+                    if let Some(json_follow) = &mut this.json_follow {
+                        json_follow.write_all(&buf[..written])?;
+                        return Poll::Ready(Ok(written));
+                    }
                     if this.follow {
                         this.state = ComponentStdioWriterState::Follow(0..written);
                     } else {
@@ -236,11 +341,17 @@ impl AsyncWrite for ComponentStdioWriter {
 
 impl std::io::Write for ComponentStdioWriter {
     fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
-        let written = self.sync_file.write(buf)?;
-        if self.follow {
-            std::io::stderr().write_all(&buf[..written])?;
+        let timestamped = timestamp_lines(buf, &mut self.line_start);
+        self.sync_file.write_all(&timestamped)?;
+        if let Some(forwarder) = &mut self.forwarder {
+            forwarder.write(buf);
+        }
+        if let Some(json_follow) = &mut self.json_follow {
+            json_follow.write_all(buf)?;
+        } else if self.follow {
+            std::io::stderr().write_all(buf)?;
         }
-        Ok(written)
+        Ok(buf.len())
     }
 
     fn flush(&mut self) -> std::io::Result<()> {
@@ -252,6 +363,255 @@ impl std::io::Write for ComponentStdioWriter {
     }
 }
 
+/// JsonStdioWriter writes a component's output to stderr as JSON log lines,
+/// one for each line of output.
+pub struct JsonStdioWriter {
+    component_id: String,
+    stream: &'static str,
+    // Output since the last complete line
+    partial: Vec<u8>,
+    // Forwards output to the log sinks and debug capture
+    forwarder: Option<OutputForwarder>,
+}
+
+impl JsonStdioWriter {
+    pub fn new(component_id: &str, stream: &'static str) -> Self {
+        Self {
+            component_id: component_id.to_owned(),
+            stream,
+            partial: vec![],
+            forwarder: None,
+        }
+    }
+
+    /// Also forwards the output to the log sinks and debug capture.
+    pub fn with_forwarder(mut self, forwarder: Option<OutputForwarder>) -> Self {
+        self.forwarder = forwarder;
+        self
+    }
+
+    fn log_line(&self, line: &[u8]) -> String {
+        spin_telemetry::logs::JsonLogLine::new("INFO", line_text(line))
+            .component(Some(&self.component_id))
+            .trace_id(spin_telemetry::current_trace_id())
+            .field("stream", self.stream)
+            .to_string()
+    }
+
+    /// Writes the complete lines in `buf`, keeping any partial line for
+    /// later.
+    fn write_lines(&mut self, buf: &[u8]) -> std::io::Result<()> {
+        if let Some(forwarder) = &mut self.forwarder {
+            forwarder.write(buf);
+        }
+        let Some(complete) = take_complete_lines(&mut self.partial, buf) else {
+            return Ok(());
+        };
+        let mut out = String::new();
+        for line in complete.split_inclusive(|b| *b == b'\n') {
+            out.push_str(&self.log_line(line));
+            out.push('\n');
+        }
+        std::io::stderr().write_all(out.as_bytes())
+    }
+
+    fn write_partial(&mut self) -> std::io::Result<()> {
+        if self.partial.is_empty() {
+            return Ok(());
+        }
+        let line = self.log_line(&std::mem::take(&mut self.partial));
+        writeln!(std::io::stderr(), "{line}")
+    }
+}
+
+impl std::io::Write for JsonStdioWriter {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.write_lines(buf)?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        std::io::stderr().flush()
+    }
+}
+
+impl AsyncWrite for JsonStdioWriter {
+    fn poll_write(
+        self: std::pin::Pin<&mut Self>,
+        _cx: &mut std::task::Context<'_>,
+        buf: &[u8],
+    ) -> Poll<std::result::Result<usize, std::io::Error>> {
+        Poll::Ready(std::io::Write::write(self.get_mut(), buf))
+    }
+
+    fn poll_flush(
+        self: std::pin::Pin<&mut Self>,
+        _cx: &mut std::task::Context<'_>,
+    ) -> Poll<std::result::Result<(), std::io::Error>> {
+        Poll::Ready(std::io::Write::flush(self.get_mut()))
+    }
+
+    fn poll_shutdown(
+        self: std::pin::Pin<&mut Self>,
+        _cx: &mut std::task::Context<'_>,
+    ) -> Poll<std::result::Result<(), std::io::Error>> {
+        Poll::Ready(self.get_mut().write_partial())
+    }
+}
+
+impl Drop for JsonStdioWriter {
+    fn drop(&mut self) {
+        _ = self.write_partial();
+    }
+}
+
+/// InheritStdioWriter writes a component's output to the same stream of
+/// the host, as if it were inherited, and forwards it with an
+/// [`OutputForwarder`].
+pub struct InheritStdioWriter {
+    stream: &'static str,
+    forwarder: Option<OutputForwarder>,
+}
+
+impl InheritStdioWriter {
+    pub fn new(component_id: &str, stream: &'static str) -> Self {
+        Self {
+            stream,
+            forwarder: OutputForwarder::new(component_id, stream),
+        }
+    }
+}
+
+impl std::io::Write for InheritStdioWriter {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        match self.stream {
+            "stdout" => std::io::stdout().write_all(buf)?,
+            _ => std::io::stderr().write_all(buf)?,
+        }
+        if let Some(forwarder) = &mut self.forwarder {
+            forwarder.write(buf);
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        match self.stream {
+            "stdout" => std::io::stdout().flush(),
+            _ => std::io::stderr().flush(),
+        }
+    }
+}
+
+impl AsyncWrite for InheritStdioWriter {
+    fn poll_write(
+        self: std::pin::Pin<&mut Self>,
+        _cx: &mut std::task::Context<'_>,
+        buf: &[u8],
+    ) -> Poll<std::result::Result<usize, std::io::Error>> {
+        Poll::Ready(std::io::Write::write(self.get_mut(), buf))
+    }
+
+    fn poll_flush(
+        self: std::pin::Pin<&mut Self>,
+        _cx: &mut std::task::Context<'_>,
+    ) -> Poll<std::result::Result<(), std::io::Error>> {
+        Poll::Ready(std::io::Write::flush(self.get_mut()))
+    }
+
+    fn poll_shutdown(
+        self: std::pin::Pin<&mut Self>,
+        _cx: &mut std::task::Context<'_>,
+    ) -> Poll<std::result::Result<(), std::io::Error>> {
+        if let Some(forwarder) = &mut self.get_mut().forwarder {
+            forwarder.finish();
+        }
+        Poll::Ready(Ok(()))
+    }
+}
+
+/// OutputForwarder forwards a component's output to the log sinks configured
+/// in the runtime config, one record per line, and to the debug capture of the
+/// request being handled, if there is one.
+pub struct OutputForwarder {
+    component_id: String,
+    stream: &'static str,
+    // Output since the last complete line
+    partial: Vec<u8>,
+}
+
+impl OutputForwarder {
+    /// Returns a forwarder for the component's `stream`, or `None` if there
+    /// is nothing to forward to.
+    pub fn new(component_id: &str, stream: &'static str) -> Option<Self> {
+        Self::wanted().then(|| Self {
+            component_id: component_id.to_owned(),
+            stream,
+            partial: vec![],
+        })
+    }
+
+    /// Whether any sink takes component output, or requests may be captured.
+    fn wanted() -> bool {
+        spin_telemetry::capture::enabled() || Self::to_sinks()
+    }
+
+    /// Whether any sink takes component output. Output on stderr is logged
+    /// at warning level, which is the most any sink could want.
+    fn to_sinks() -> bool {
+        spin_telemetry::sinks::enabled(tracing::Level::WARN, true)
+    }
+
+    fn write(&mut self, buf: &[u8]) {
+        spin_telemetry::capture::record_output(&self.component_id, self.stream, buf);
+        if !Self::to_sinks() {
+            return;
+        }
+        let Some(complete) = take_complete_lines(&mut self.partial, buf) else {
+            return;
+        };
+        for line in complete.split_inclusive(|b| *b == b'\n') {
+            self.forward(line);
+        }
+    }
+
+    /// Forwards any partial line left at the end of the output.
+    fn finish(&mut self) {
+        if !self.partial.is_empty() && Self::to_sinks() {
+            let line = std::mem::take(&mut self.partial);
+            self.forward(&line);
+        }
+    }
+
+    fn forward(&self, line: &[u8]) {
+        spin_telemetry::sinks::forward_component_line(
+            &self.component_id,
+            self.stream,
+            &line_text(line),
+        );
+    }
+}
+
+impl Drop for OutputForwarder {
+    fn drop(&mut self) {
+        self.finish();
+    }
+}
+
+/// Adds `buf` to the output since the last complete line, and takes the
+/// complete lines from it, if there are any.
+fn take_complete_lines(partial: &mut Vec<u8>, buf: &[u8]) -> Option<Vec<u8>> {
+    partial.extend_from_slice(buf);
+    let end = partial.iter().rposition(|b| *b == b'\n')?;
+    Some(partial.drain(..=end).collect())
+}
+
+/// The text of a line of output, without its line ending.
+fn line_text(line: &[u8]) -> String {
+    let text = String::from_utf8_lossy(line);
+    let text = text.strip_suffix('\n').unwrap_or(&text);
+    text.strip_suffix('\r').unwrap_or(text).to_owned()
+}
+
 fn bullet_list<S: std::fmt::Display>(items: impl IntoIterator<Item = S>) -> String {
     items
         .into_iter()
@@ -259,3 +619,33 @@ fn bullet_list<S: std::fmt::Display>(items: impl IntoIterator<Item = S>) -> Stri
         .collect::<Vec<_>>()
         .join("\n")
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn log_lines_are_timestamped_where_they_start() {
+        let mut line_start = true;
+        let first = String::from_utf8(timestamp_lines(b"one\ntw", &mut line_start)).unwrap();
+        let rest = String::from_utf8(timestamp_lines(b"o\n", &mut line_start)).unwrap();
+        assert!(line_start);
+
+        let lines: Vec<_> = (first + &rest).lines().map(parse_log_line).collect();
+        assert_eq!(lines.len(), 2);
+        assert!(lines.iter().all(|(time, _)| time.is_some()));
+        assert_eq!(lines[0].1, "one");
+        assert_eq!(lines[1].1, "two");
+
+        assert_eq!(parse_log_line("plain old line"), (None, "plain old line"));
+    }
+
+    #[test]
+    fn json_lines_name_component_and_stream() {
+        let writer = JsonStdioWriter::new("hello", "stderr");
+        let line: serde_json::Value = serde_json::from_str(&writer.log_line(b"oops\r\n")).unwrap();
+        assert_eq!(line["message"], "oops");
+        assert_eq!(line["component"], "hello");
+        assert_eq!(line["stream"], "stderr");
+    }
+}
@@ -6,7 +6,7 @@ use spin_app::{AppComponent, DynamicHostComponent};
 use spin_core::{async_trait, HostComponent};
 use spin_world::v2::variables;
 
-use spin_expressions::{Error, Key, Provider, Resolver};
+use spin_expressions::{Error, Key, Provider, Resolver, SecretString};
 
 pub struct VariablesHostComponent {
     providers: Mutex<Vec<Box<dyn Provider>>>,
@@ -39,6 +39,11 @@ impl HostComponent for VariablesHostComponent {
             component_id: None,
         }
     }
+
+    fn reset_data(&self, data: &mut Self::Data) -> bool {
+        data.component_id = None;
+        true
+    }
 }
 
 impl DynamicHostComponent for VariablesHostComponent {
@@ -86,6 +91,7 @@ impl variables::Host for ComponentVariables {
                 .unwrap()
                 .resolve(component_id, key)
                 .await
+                .map(SecretString::into_exposed)
                 .map_err(as_wit)
         }
         .await)
@@ -3,7 +3,7 @@ use std::{collections::HashMap, path::PathBuf, sync::Mutex};
 use anyhow::{Context, Result};
 use async_trait::async_trait;
 
-use spin_expressions::{Key, Provider};
+use spin_expressions::{Key, Provider, SecretString};
 use tracing::{instrument, Level};
 
 const DEFAULT_ENV_PREFIX: &str = "SPIN_VARIABLE";
@@ -14,7 +14,7 @@ const LEGACY_ENV_PREFIX: &str = "SPIN_CONFIG";
 pub struct EnvProvider {
     prefix: Option<String>,
     dotenv_path: Option<PathBuf>,
-    dotenv_cache: Mutex<Option<HashMap<String, String>>>,
+    dotenv_cache: Mutex<Option<HashMap<String, SecretString>>>,
 }
 
 impl EnvProvider {
@@ -27,16 +27,16 @@ impl EnvProvider {
         }
     }
 
-    fn query_env(&self, env_key: &str) -> Result<Option<String>> {
+    fn query_env(&self, env_key: &str) -> Result<Option<SecretString>> {
         match std::env::var(env_key) {
             Err(std::env::VarError::NotPresent) => self.get_dotenv(env_key),
             other => other
-                .map(Some)
+                .map(|value| Some(value.into()))
                 .with_context(|| format!("failed to resolve env var {env_key}")),
         }
     }
 
-    fn get_sync(&self, key: &Key) -> Result<Option<String>> {
+    fn get_sync(&self, key: &Key) -> Result<Option<SecretString>> {
         let prefix = self
             .prefix
             .clone()
@@ -59,7 +59,7 @@ impl EnvProvider {
         }
     }
 
-    fn get_dotenv(&self, key: &str) -> Result<Option<String>> {
+    fn get_dotenv(&self, key: &str) -> Result<Option<SecretString>> {
         if self.dotenv_path.is_none() {
             return Ok(None);
         }
@@ -74,19 +74,20 @@ impl EnvProvider {
         Ok(cache.get(key).cloned())
     }
 
-    fn load_dotenv(&self) -> Result<HashMap<String, String>> {
+    fn load_dotenv(&self) -> Result<HashMap<String, SecretString>> {
         let path = self.dotenv_path.as_deref().unwrap();
         Ok(dotenvy::from_path_iter(path)
             .into_iter()
             .flatten()
-            .collect::<Result<HashMap<String, String>, _>>()?)
+            .map(|item| item.map(|(key, value)| (key, value.into())))
+            .collect::<Result<HashMap<_, _>, _>>()?)
     }
 }
 
 #[async_trait]
 impl Provider for EnvProvider {
     #[instrument(name = "spin_variables.get_from_env", skip(self), err(level = Level::INFO))]
-    async fn get(&self, key: &Key) -> Result<Option<String>> {
+    async fn get(&self, key: &Key) -> Result<Option<SecretString>> {
         tokio::task::block_in_place(|| self.get_sync(key))
     }
 }
@@ -110,7 +111,7 @@ mod test {
             EnvProvider::new(Some("TESTING_SPIN"), None)
                 .get_sync(&key1)
                 .unwrap(),
-            Some("val".to_string())
+            Some("val".into())
         );
     }
 
@@ -124,7 +125,7 @@ mod test {
             EnvProvider::new(Some("TESTING_SPIN"), Some(dotenv_path))
                 .get_sync(&key)
                 .unwrap(),
-            Some("dotenv_val".to_string())
+            Some("dotenv_val".into())
         );
     }
 
@@ -8,13 +8,13 @@ use vaultrs::{
     kv2,
 };
 
-use spin_expressions::{Key, Provider};
+use spin_expressions::{Key, Provider, SecretString};
 
 /// A config Provider that uses HashiCorp Vault.
 #[derive(Debug)]
 pub struct VaultProvider {
     url: String,
-    token: String,
+    token: SecretString,
     mount: String,
     prefix: Option<String>,
 }
@@ -28,7 +28,7 @@ impl VaultProvider {
     ) -> Self {
         Self {
             url: url.into(),
-            token: token.into(),
+            token: SecretString::new(token),
             mount: mount.into(),
             prefix: prefix.map(Into::into),
         }
@@ -43,11 +43,11 @@ struct Secret {
 #[async_trait]
 impl Provider for VaultProvider {
     #[instrument(name = "spin_variables.get_from_vault", skip(self), err(level = Level::INFO), fields(otel.kind = "client"))]
-    async fn get(&self, key: &Key) -> Result<Option<String>> {
+    async fn get(&self, key: &Key) -> Result<Option<SecretString>> {
         let client = VaultClient::new(
             VaultClientSettingsBuilder::default()
                 .address(&self.url)
-                .token(&self.token)
+                .token(self.token.expose())
                 .build()?,
         )?;
         let path = match &self.prefix {
@@ -55,7 +55,7 @@ impl Provider for VaultProvider {
             None => key.as_str().to_string(),
         };
         match kv2::read::<Secret>(&client, &self.mount, &path).await {
-            Ok(secret) => Ok(Some(secret.value)),
+            Ok(secret) => Ok(Some(secret.value.into())),
             // Vault doesn't have this entry so pass along the chain
             Err(ClientError::APIError { code: 404, .. }) => Ok(None),
             // Other Vault error so bail rather than looking elsewhere
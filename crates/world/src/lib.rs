@@ -6,6 +6,12 @@ wasmtime::component::bindgen!({
     world host {
         include fermyon:spin/host;
         include fermyon:spin/platform@2.0.0;
+        export fermyon:spin/inbound-redis-stream@2.0.0;
+        export fermyon:spin/inbound-kafka@2.0.0;
+        export fermyon:spin/inbound-sqs@2.0.0;
+        export fermyon:spin/inbound-nats@2.0.0;
+        export fermyon:spin/inbound-file-watch@2.0.0;
+        export fermyon:spin/inbound-grpc@2.0.0;
     }
     "#,
     path: "../../wit",
@@ -5,39 +5,80 @@ use spin_cli::commands::external::predefined_externals;
 use spin_cli::commands::{
     build::BuildCommand,
     cloud::{DeployCommand, LoginCommand},
+    completion::CompletionCommand,
+    daemon::{StatusCommand, StopCommand},
+    deploy_local::DeployLocalCommand,
     doctor::DoctorCommand,
     external::execute_external_subcommand,
+    inspect::InspectCommand,
+    lock::LockCommand,
+    logs::LogsCommand,
+    man::ManCommand,
+    manifest::ManifestCommands,
     new::{AddCommand, NewCommand},
     plugins::PluginCommands,
     registry::RegistryCommands,
+    scaffold::ScaffoldCommands,
+    self_update::SelfUpdateCommand,
+    state::StateCommands,
     templates::TemplateCommands,
+    test::TestCommand,
     up::UpCommand,
     watch::WatchCommand,
 };
-use spin_cli::{build_info::*, subprocess::ExitStatusError};
+use spin_cli::{build_info::*, output::ErrorOutput, subprocess::ExitStatusError};
 use spin_trigger::cli::help::HelpArgsOnlyTrigger;
 use spin_trigger::cli::TriggerExecutorCommand;
+use spin_trigger_command::CommandTrigger;
+use spin_trigger_file_watch::FileWatchTrigger;
+use spin_trigger_grpc::GrpcTrigger;
 use spin_trigger_http::HttpTrigger;
+#[cfg(feature = "kafka")]
+use spin_trigger_kafka::KafkaTrigger;
+use spin_trigger_nats::NatsTrigger;
 use spin_trigger_redis::RedisTrigger;
+use spin_trigger_sqs::SqsTrigger;
+
+fn main() {
+    // A sandboxed trigger executes itself again to enter its sandbox, which
+    // must happen before the async runtime creates any threads
+    if let Err(err) = spin_trigger::sandbox::enter_from_env() {
+        exit_with_error(err)
+    }
+    run()
+}
 
 #[tokio::main]
-async fn main() {
+async fn run() {
     if let Err(err) = _main().await {
-        let code = match err.downcast_ref::<ExitStatusError>() {
-            // If we encounter an `ExitStatusError` it means a subprocess has already
-            // exited unsuccessfully and thus already printed error messages. No need
-            // to print anything additional.
-            Some(e) => e.code(),
-            // Otherwise we print the error chain.
-            None => {
-                terminal::error!("{err}");
-                print_error_chain(err);
-                1
+        exit_with_error(err)
+    }
+}
+
+fn exit_with_error(err: anyhow::Error) -> ! {
+    let code = match err.downcast_ref::<ExitStatusError>() {
+        // If we encounter an `ExitStatusError` it means a subprocess has already
+        // exited unsuccessfully and thus already printed error messages. No need
+        // to print anything additional.
+        Some(e) => e.code(),
+        // Otherwise we print the error chain.
+        None if terminal::output_format().is_json() => {
+            let code = 1;
+            let output = ErrorOutput::new(&err, code);
+            match serde_json::to_string(&output) {
+                Ok(json) => println!("{json}"),
+                Err(_) => terminal::error!("{err}"),
             }
-        };
+            code
+        }
+        None => {
+            terminal::error!("{err}");
+            print_error_chain(err);
+            1
+        }
+    };
 
-        std::process::exit(code)
-    }
+    std::process::exit(code)
 }
 
 async fn _main() -> anyhow::Result<()> {
@@ -114,9 +155,11 @@ enum SpinApp {
     Add(AddCommand),
     #[clap(alias = "u")]
     Up(UpCommand),
+    Test(TestCommand),
     // acts as a cross-level subcommand shortcut -> `spin cloud deploy`
     #[clap(alias = "d")]
     Deploy(DeployCommand),
+    DeployLocal(DeployLocalCommand),
     // acts as a cross-level subcommand shortcut -> `spin cloud login`
     Login(LoginCommand),
     #[clap(subcommand, alias = "oci")]
@@ -132,12 +175,33 @@ enum SpinApp {
     #[clap(alias = "w")]
     Watch(WatchCommand),
     Doctor(DoctorCommand),
+    Inspect(InspectCommand),
+    Lock(LockCommand),
+    Stop(StopCommand),
+    Status(StatusCommand),
+    Logs(LogsCommand),
+    Completion(CompletionCommand),
+    Man(ManCommand),
+    #[clap(subcommand)]
+    Manifest(ManifestCommands),
+    #[clap(subcommand)]
+    Scaffold(ScaffoldCommands),
+    #[clap(subcommand)]
+    State(StateCommands),
+    SelfUpdate(SelfUpdateCommand),
 }
 
 #[derive(Subcommand)]
 enum TriggerCommands {
     Http(TriggerExecutorCommand<HttpTrigger>),
     Redis(TriggerExecutorCommand<RedisTrigger>),
+    #[cfg(feature = "kafka")]
+    Kafka(TriggerExecutorCommand<KafkaTrigger>),
+    Sqs(TriggerExecutorCommand<SqsTrigger>),
+    Nats(TriggerExecutorCommand<NatsTrigger>),
+    Command(TriggerExecutorCommand<CommandTrigger>),
+    FileWatch(TriggerExecutorCommand<FileWatchTrigger>),
+    Grpc(TriggerExecutorCommand<GrpcTrigger>),
     #[clap(name = spin_cli::HELP_ARGS_ONLY_TRIGGER_TYPE, hide = true)]
     HelpArgsOnly(TriggerExecutorCommand<HelpArgsOnlyTrigger>),
 }
@@ -148,19 +212,39 @@ impl SpinApp {
         match self {
             Self::Templates(cmd) => cmd.run().await,
             Self::Up(cmd) => cmd.run().await,
+            Self::Test(cmd) => cmd.run().await,
             Self::New(cmd) => cmd.run().await,
             Self::Add(cmd) => cmd.run().await,
             Self::Deploy(cmd) => cmd.run(SpinApp::command()).await,
+            Self::DeployLocal(cmd) => cmd.run().await,
             Self::Login(cmd) => cmd.run(SpinApp::command()).await,
             Self::Registry(cmd) => cmd.run().await,
             Self::Build(cmd) => cmd.run().await,
             Self::Trigger(TriggerCommands::Http(cmd)) => cmd.run().await,
             Self::Trigger(TriggerCommands::Redis(cmd)) => cmd.run().await,
+            #[cfg(feature = "kafka")]
+            Self::Trigger(TriggerCommands::Kafka(cmd)) => cmd.run().await,
+            Self::Trigger(TriggerCommands::Sqs(cmd)) => cmd.run().await,
+            Self::Trigger(TriggerCommands::Nats(cmd)) => cmd.run().await,
+            Self::Trigger(TriggerCommands::Command(cmd)) => cmd.run().await,
+            Self::Trigger(TriggerCommands::FileWatch(cmd)) => cmd.run().await,
+            Self::Trigger(TriggerCommands::Grpc(cmd)) => cmd.run().await,
             Self::Trigger(TriggerCommands::HelpArgsOnly(cmd)) => cmd.run().await,
             Self::Plugins(cmd) => cmd.run().await,
             Self::External(cmd) => execute_external_subcommand(cmd, app).await,
             Self::Watch(cmd) => cmd.run().await,
             Self::Doctor(cmd) => cmd.run().await,
+            Self::Inspect(cmd) => cmd.run().await,
+            Self::Lock(cmd) => cmd.run().await,
+            Self::Stop(cmd) => cmd.run().await,
+            Self::Status(cmd) => cmd.run().await,
+            Self::Logs(cmd) => cmd.run().await,
+            Self::Completion(cmd) => cmd.run(app).await,
+            Self::Man(cmd) => cmd.run(app).await,
+            Self::Manifest(cmd) => cmd.run().await,
+            Self::Scaffold(cmd) => cmd.run().await,
+            Self::State(cmd) => cmd.run().await,
+            Self::SelfUpdate(cmd) => cmd.run().await,
         }
     }
 }
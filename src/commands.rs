@@ -4,18 +4,42 @@
 pub mod build;
 /// Commands for publishing applications to the Fermyon Platform.
 pub mod cloud;
+/// Command for generating shell completions.
+pub mod completion;
+/// Commands for managing applications running in the background.
+pub mod daemon;
+/// Command for upgrading an application running in a fleet in place.
+pub mod deploy_local;
 /// Command for running the Spin Doctor.
 pub mod doctor;
 /// Commands for external subcommands (i.e. plugins)
 pub mod external;
+/// Command for showing what an application contains.
+pub mod inspect;
+/// Command for generating an application's lock file.
+pub mod lock;
+/// Command for showing the logs of an application.
+pub mod logs;
+/// Command for generating man pages.
+pub mod man;
+/// Commands for working with application manifests.
+pub mod manifest;
 /// Command for creating a new application.
 pub mod new;
 /// Command for adding a plugin to Spin
 pub mod plugins;
 /// Commands for working with OCI registries.
 pub mod registry;
+/// Commands for generating container and Kubernetes deployment files.
+pub mod scaffold;
+/// Command for updating Spin itself.
+pub mod self_update;
+/// Commands for snapshotting and restoring the local state of applications.
+pub mod state;
 /// Commands for working with templates.
 pub mod templates;
+/// Command for running an application's test components.
+pub mod test;
 /// Commands for starting the runtime.
 pub mod up;
 /// Command for rebuilding and restarting a Spin app when files change.
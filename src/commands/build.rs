@@ -2,8 +2,10 @@ use std::{ffi::OsString, path::PathBuf};
 
 use anyhow::Result;
 use clap::Parser;
+use serde::Serialize;
 
 use crate::opts::{APP_MANIFEST_FILE_OPT, BUILD_UP_OPT, DEFAULT_MANIFEST_FILE};
+use crate::output::{print_json, OutputArgs};
 
 use super::up::UpCommand;
 
@@ -27,6 +29,25 @@ pub struct BuildCommand {
     #[clap(short = 'c', long, multiple = true)]
     pub component_id: Vec<String>,
 
+    /// The maximum number of build commands to run at once. The default is
+    /// the number of available CPUs.
+    #[clap(short = 'j', long = "jobs")]
+    pub jobs: Option<usize>,
+
+    /// Carry on building other components after a build fails. By default
+    /// the build stops at the first failure.
+    #[clap(long = "keep-going")]
+    pub keep_going: bool,
+
+    /// The build profile to use, such as "release". Components which don't
+    /// define the profile use their default build command. `--target` is
+    /// another name for this option.
+    #[clap(long = "profile", visible_alias = "target")]
+    pub profile: Option<String>,
+
+    #[clap(flatten)]
+    pub output: OutputArgs,
+
     /// Run the application after building.
     #[clap(name = BUILD_UP_OPT, short = 'u', long = "up")]
     pub up: bool,
@@ -37,8 +58,28 @@ pub struct BuildCommand {
 
 impl BuildCommand {
     pub async fn run(self) -> Result<()> {
+        let output = self.output.apply();
         let manifest_file = spin_common::paths::resolve_manifest_file_path(&self.app_source)?;
-        spin_build::build(&manifest_file, &self.component_id).await?;
+        let mut options = spin_build::BuildOptions {
+            keep_going: self.keep_going,
+            profile: self.profile,
+            ..Default::default()
+        };
+        if let Some(jobs) = self.jobs {
+            options.jobs = jobs;
+        }
+        let built =
+            spin_build::build_with_options(&manifest_file, &self.component_id, &options).await?;
+        if !built.is_empty() {
+            spin_loader::world::check_manifest_file(&manifest_file, &built)?;
+        }
+        if output.is_json() {
+            print_json(&BuildOutput {
+                manifest: &manifest_file,
+                profile: options.profile.as_deref(),
+                built,
+            })?;
+        }
 
         if self.up {
             let mut cmd = UpCommand::parse_from(
@@ -55,3 +96,25 @@ impl BuildCommand {
         }
     }
 }
+
+/// The result of `spin build --output json`.
+#[derive(Serialize)]
+struct BuildOutput<'a> {
+    manifest: &'a std::path::Path,
+    profile: Option<&'a str>,
+    /// The components whose build commands were run
+    built: Vec<String>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn target_selects_a_profile() {
+        for flag in ["--profile", "--target"] {
+            let cmd = BuildCommand::try_parse_from(["build", flag, "release"]).unwrap();
+            assert_eq!(cmd.profile.as_deref(), Some("release"));
+        }
+    }
+}
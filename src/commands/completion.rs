@@ -0,0 +1,239 @@
+use anyhow::{Context, Result};
+use clap::{builder::PossibleValuesParser, Command, Parser, ValueEnum};
+use clap_complete::Shell;
+use spin_plugins::manager::PluginManager;
+use spin_templates::TemplateManager;
+
+/// The value completed by a generated script from `spin completion --list`.
+/// Each is given as the single possible value of the arguments it applies
+/// to, and replaced in the generated script by the shell's way of running
+/// the list command.
+const PLUGINS_PLACEHOLDER: &str = "__spin_installed_plugins__";
+const TEMPLATES_PLACEHOLDER: &str = "__spin_installed_templates__";
+
+/// Generate shell completions for Spin.
+#[derive(Parser, Debug)]
+#[clap(
+    about = "Generate shell completions for Spin",
+    long_about = "Generate shell completions for Spin.
+
+The script is written to standard output. For example, to enable completions in bash:
+
+    spin completion bash > ~/.local/share/bash-completion/completions/spin
+
+Installed plugins and templates are completed by name as they are installed and uninstalled, in bash, zsh and fish."
+)]
+pub struct CompletionCommand {
+    /// The shell to generate completions for.
+    #[clap(value_enum, required_unless_present = "list")]
+    pub shell: Option<Shell>,
+
+    /// List names to complete. This is used by the generated scripts.
+    #[clap(value_enum, long = "list", hide = true)]
+    pub list: Option<Completable>,
+}
+
+/// The names which are completed when the completion script runs, rather
+/// than when it is generated.
+#[derive(ValueEnum, Clone, Copy, Debug)]
+pub enum Completable {
+    Plugins,
+    Templates,
+}
+
+impl CompletionCommand {
+    pub async fn run(self, app: Command<'_>) -> Result<()> {
+        if let Some(completable) = self.list {
+            for name in completable.names().await? {
+                println!("{name}");
+            }
+            return Ok(());
+        }
+        let Some(shell) = self.shell else {
+            return Ok(());
+        };
+
+        let mut app = completable_app(app, shell);
+        let mut script = vec![];
+        clap_complete::generate(shell, &mut app, "spin", &mut script);
+        let script = String::from_utf8(script).context("Generated completions are not UTF-8")?;
+        print!("{}", substitute_placeholders(&script, shell));
+        Ok(())
+    }
+}
+
+impl Completable {
+    async fn names(self) -> Result<Vec<String>> {
+        let mut names = match self {
+            Self::Plugins => PluginManager::try_default()?
+                .store()
+                .installed_manifests()?
+                .iter()
+                .map(|manifest| manifest.name())
+                .collect::<Vec<_>>(),
+            Self::Templates => TemplateManager::try_default()
+                .context("Failed to construct template directory path")?
+                .list()
+                .await?
+                .templates
+                .iter()
+                .map(|template| template.id().to_owned())
+                .collect(),
+        };
+        names.sort();
+        Ok(names)
+    }
+
+    fn placeholder(self) -> &'static str {
+        match self {
+            Self::Plugins => PLUGINS_PLACEHOLDER,
+            Self::Templates => TEMPLATES_PLACEHOLDER,
+        }
+    }
+
+    fn list_arg(self) -> &'static str {
+        match self {
+            Self::Plugins => "plugins",
+            Self::Templates => "templates",
+        }
+    }
+}
+
+/// The arguments which take the name of an installed plugin or template, by
+/// subcommand path.
+const COMPLETED_ARGS: &[(&[&str], &str, Completable)] = &[
+    (&["new"], "template-id", Completable::Templates),
+    (&["add"], "template-id", Completable::Templates),
+    (
+        &["templates", "uninstall"],
+        "template-id",
+        Completable::Templates,
+    ),
+    (&["plugins", "uninstall"], "name", Completable::Plugins),
+    (&["plugins", "upgrade"], "PLUGIN_NAME", Completable::Plugins),
+    (&["plugins", "verify"], "name", Completable::Plugins),
+];
+
+/// Prepares the CLI definition for generating completions: plugin
+/// subcommands are named without the marker shown in help, and arguments
+/// which take installed names are given placeholder values where the shell
+/// supports running a command to complete them.
+fn completable_app(mut app: Command<'_>, shell: Shell) -> Command<'_> {
+    for subcommand in app.get_subcommands_mut() {
+        if let Some(name) = subcommand.get_name().strip_suffix('*') {
+            let name = name.to_owned();
+            *subcommand = std::mem::take(subcommand).name(name);
+        }
+    }
+    if matches!(shell, Shell::Bash | Shell::Zsh | Shell::Fish) {
+        for (path, arg, completable) in COMPLETED_ARGS {
+            app = with_placeholder(app, path, arg, *completable);
+        }
+    }
+    app
+}
+
+fn with_placeholder<'help>(
+    mut app: Command<'help>,
+    path: &[&str],
+    arg: &'static str,
+    completable: Completable,
+) -> Command<'help> {
+    match path.split_first() {
+        None => {
+            // `mut_arg` would add the argument if it did not exist
+            if app.get_arguments().any(|a| a.get_id() == arg) {
+                app = app.mut_arg(arg, |a| {
+                    a.value_parser(PossibleValuesParser::new([completable.placeholder()]))
+                });
+            }
+        }
+        Some((name, rest)) => {
+            if let Some(subcommand) = app.find_subcommand_mut(name) {
+                *subcommand = with_placeholder(std::mem::take(subcommand), rest, arg, completable);
+            }
+        }
+    }
+    app
+}
+
+/// Replaces the placeholder values in a generated script with a command to
+/// list the installed names.
+fn substitute_placeholders(script: &str, shell: Shell) -> String {
+    let mut script = script.to_owned();
+    for completable in [Completable::Plugins, Completable::Templates] {
+        let placeholder = completable.placeholder();
+        let list = format!("spin completion --list {}", completable.list_arg());
+        script = match shell {
+            // compgen -W "<values>"
+            Shell::Bash => script.replace(placeholder, &format!("$({list} 2>/dev/null)")),
+            // :VALUE_NAME:(<values>)
+            Shell::Zsh => script.replace(
+                &format!("({placeholder})"),
+                &format!("_spin_list {}", completable.list_arg()),
+            ),
+            // -a "{<value>\t<help>,...}"
+            Shell::Fish => script.replace(
+                &format!("\"{{{placeholder}\t}}\""),
+                &format!("\"({list} 2>/dev/null)\""),
+            ),
+            _ => script,
+        };
+    }
+    if shell == Shell::Zsh {
+        // The function must be defined before the script runs the completion
+        script = script.replacen(
+            "#compdef spin\n",
+            &format!("#compdef spin\n{ZSH_LIST_FUNCTION}"),
+            1,
+        );
+    }
+    script
+}
+
+const ZSH_LIST_FUNCTION: &str = r#"
+(( $+functions[_spin_list] )) ||
+_spin_list() {
+    local -a names
+    names=(${(f)"$(spin completion --list $1 2>/dev/null)"})
+    compadd -a names
+}
+"#;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::Arg;
+
+    fn app() -> Command<'static> {
+        Command::new("spin")
+            .subcommand(Command::new("new").arg(Arg::new("template-id").long("template")))
+            .subcommand(
+                Command::new("plugins").subcommand(Command::new("uninstall").arg(Arg::new("name"))),
+            )
+            .subcommand(Command::new("cloud*"))
+    }
+
+    fn generate(shell: Shell) -> String {
+        let mut app = completable_app(app(), shell);
+        assert!(app.find_subcommand("cloud").is_some());
+        let mut script = vec![];
+        clap_complete::generate(shell, &mut app, "spin", &mut script);
+        substitute_placeholders(&String::from_utf8(script).unwrap(), shell)
+    }
+
+    #[test]
+    fn installed_names_are_completed_by_running_spin() {
+        let bash = generate(Shell::Bash);
+        assert!(bash.contains("$(spin completion --list templates 2>/dev/null)"));
+        assert!(!bash.contains(TEMPLATES_PLACEHOLDER));
+
+        let zsh = generate(Shell::Zsh);
+        assert!(zsh.contains("_spin_list templates"));
+        assert!(!zsh.contains(TEMPLATES_PLACEHOLDER));
+
+        let powershell = generate(Shell::PowerShell);
+        assert!(!powershell.contains(TEMPLATES_PLACEHOLDER));
+        assert!(!powershell.contains(PLUGINS_PLACEHOLDER));
+    }
+}
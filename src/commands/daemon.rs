@@ -0,0 +1,635 @@
+use std::{
+    path::{Path, PathBuf},
+    process::Stdio,
+    time::{Duration, SystemTime},
+};
+
+use anyhow::{bail, Context, Result};
+use clap::Parser;
+use serde::{Deserialize, Serialize};
+use spin_common::ui::quoted_path;
+
+use crate::opts::*;
+use crate::output::{print_json, OutputArgs};
+
+/// The state directory of an app, in which the files of a detached `spin up`
+/// are kept.
+const STATE_DIR: &str = ".spin";
+const PID_FILE: &str = "spin-up.pid";
+const LOG_FILE: &str = "spin-up.log";
+const TRIGGERS_FILE: &str = "spin-up.triggers.json";
+const CONTROL_SOCKET: &str = "spin-up.sock";
+
+/// Set for the background `spin up` started by [`detach`], which is run with
+/// the same arguments, so that it runs the application rather than detaching
+/// again.
+const DETACHED_ENV: &str = "SPIN_UP_DETACHED";
+
+/// How long `spin stop` waits for the application to exit.
+const STOP_TIMEOUT: Duration = Duration::from_secs(10);
+/// How often `spin stop` checks whether the application has stopped.
+const POLL_INTERVAL: Duration = Duration::from_millis(250);
+/// How long `spin stop` and `spin status` wait for the control socket.
+const CONTROL_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// The files through which a detached `spin up` is managed.
+#[derive(Clone)]
+pub(crate) struct DaemonFiles {
+    dir: PathBuf,
+}
+
+impl DaemonFiles {
+    /// The files for the app in `app_dir`. Apps which are not local use the
+    /// current directory.
+    pub fn new(app_dir: Option<&Path>) -> Self {
+        Self {
+            dir: app_dir.unwrap_or(Path::new("")).join(STATE_DIR),
+        }
+    }
+
+    /// The files for the app given by a `--from` option, defaulting to the
+    /// current directory.
+    pub fn from_option(app_source: Option<&Path>) -> Result<Self> {
+        match app_source {
+            Some(source) => {
+                let manifest_file = spin_common::paths::resolve_manifest_file_path(source)?;
+                Ok(Self::new(manifest_file.parent()))
+            }
+            None => Ok(Self::new(None)),
+        }
+    }
+
+    /// The app's state directory.
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    fn pid_file(&self) -> PathBuf {
+        self.dir.join(PID_FILE)
+    }
+
+    fn triggers_file(&self) -> PathBuf {
+        self.dir.join(TRIGGERS_FILE)
+    }
+
+    fn control_socket(&self) -> PathBuf {
+        self.dir.join(CONTROL_SOCKET)
+    }
+
+    /// The output of the detached `spin up`.
+    pub fn log_file(&self) -> PathBuf {
+        self.dir.join(LOG_FILE)
+    }
+
+    /// Returns the process ID of the detached `spin up`, if it is running.
+    /// A pidfile left behind by a process which has exited is removed, as is
+    /// one whose process ID has since been reused by another program.
+    pub fn running_pid(&self) -> Result<Option<u32>> {
+        let Some(pid) = self.recorded_pid()? else {
+            return Ok(None);
+        };
+        if is_running(pid) && is_spin_up(pid) {
+            Ok(Some(pid))
+        } else {
+            self.remove_files();
+            Ok(None)
+        }
+    }
+
+    /// Returns the process ID in the pidfile, whether or not it is running.
+    fn recorded_pid(&self) -> Result<Option<u32>> {
+        let pid_file = self.pid_file();
+        let pid = match std::fs::read_to_string(&pid_file) {
+            Ok(pid) => pid,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(err) => {
+                return Err(err)
+                    .with_context(|| format!("Failed to read {}", quoted_path(&pid_file)))
+            }
+        };
+        let pid = pid
+            .trim()
+            .parse()
+            .with_context(|| format!("Invalid process ID in {}", quoted_path(&pid_file)))?;
+        Ok(Some(pid))
+    }
+
+    /// Records the trigger processes started by this process, by name, if it
+    /// is the detached `spin up`, so that `spin status` can report on them.
+    pub fn record_triggers<'a>(
+        &self,
+        triggers: impl IntoIterator<Item = (&'a str, u32)>,
+    ) -> Result<()> {
+        let pid = std::process::id();
+        if self.recorded_pid()? != Some(pid) {
+            return Ok(());
+        }
+        let record = TriggerProcesses {
+            pid,
+            triggers: triggers
+                .into_iter()
+                .map(|(name, pid)| TriggerProcess {
+                    name: name.to_owned(),
+                    pid,
+                })
+                .collect(),
+        };
+        let triggers_file = self.triggers_file();
+        std::fs::write(&triggers_file, serde_json::to_vec(&record)?)
+            .with_context(|| format!("Failed to write {}", quoted_path(&triggers_file)))
+    }
+
+    /// The trigger processes started by the detached `spin up` running as
+    /// `pid`, as reported by its control socket, or failing that as
+    /// recorded.
+    async fn running_triggers(&self, pid: u32) -> Vec<TriggerProcess> {
+        let reported = self
+            .control("status")
+            .await
+            .ok()
+            .and_then(|json| serde_json::from_str::<TriggerProcesses>(&json).ok())
+            .filter(|record| record.pid == pid);
+        match reported {
+            Some(record) => record.triggers,
+            None => self.triggers(pid),
+        }
+    }
+
+    /// The trigger processes recorded by the detached `spin up` running as
+    /// `pid`.
+    fn triggers(&self, pid: u32) -> Vec<TriggerProcess> {
+        std::fs::read(self.triggers_file())
+            .ok()
+            .and_then(|json| serde_json::from_slice::<TriggerProcesses>(&json).ok())
+            .filter(|record| record.pid == pid)
+            .map(|record| record.triggers)
+            .unwrap_or_default()
+    }
+
+    /// Removes the files of a detached `spin up` which has exited.
+    fn remove_files(&self) {
+        _ = std::fs::remove_file(self.pid_file());
+        _ = std::fs::remove_file(self.triggers_file());
+        _ = std::fs::remove_file(self.control_socket());
+    }
+
+    /// Listens on the control socket, if this process is the detached
+    /// `spin up`, for requests from `spin status` and `spin stop`. Each
+    /// request is a line, `status` or `stop`, and is answered with a line of
+    /// JSON.
+    #[cfg(not(windows))]
+    pub fn serve_control(&self) -> Result<()> {
+        use std::os::unix::fs::PermissionsExt;
+
+        if self.recorded_pid()? != Some(std::process::id()) {
+            return Ok(());
+        }
+        let socket = self.control_socket();
+        _ = std::fs::remove_file(&socket);
+        let listener = tokio::net::UnixListener::bind(&socket)
+            .with_context(|| format!("Failed to listen on {}", quoted_path(&socket)))?;
+        std::fs::set_permissions(&socket, std::fs::Permissions::from_mode(0o600))
+            .with_context(|| format!("Failed to restrict access to {}", quoted_path(&socket)))?;
+
+        let files = self.clone();
+        tokio::spawn(async move {
+            loop {
+                let stream = match listener.accept().await {
+                    Ok((stream, _)) => stream,
+                    Err(e) => {
+                        tracing::warn!("Control socket failed: {e}");
+                        return;
+                    }
+                };
+                if let Err(e) = files.respond(stream).await {
+                    tracing::warn!("Failed to answer control request: {e:#}");
+                }
+            }
+        });
+        Ok(())
+    }
+
+    #[cfg(windows)]
+    pub fn serve_control(&self) -> Result<()> {
+        Ok(())
+    }
+
+    #[cfg(not(windows))]
+    async fn respond(&self, stream: tokio::net::UnixStream) -> Result<()> {
+        use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+
+        let (reader, mut writer) = stream.into_split();
+        let mut request = String::new();
+        BufReader::new(reader.take(64))
+            .read_line(&mut request)
+            .await?;
+        let pid = std::process::id();
+        let response = match request.trim() {
+            "status" => serde_json::to_vec(&TriggerProcesses {
+                pid,
+                triggers: self.triggers(pid),
+            })?,
+            "stop" => serde_json::to_vec(&serde_json::json!({ "pid": pid }))?,
+            other => bail!("Unknown control request {other:?}"),
+        };
+        writer.write_all(&response).await?;
+        writer.write_all(b"\n").await?;
+        writer.shutdown().await?;
+        if request.trim() == "stop" {
+            // Stop as for `kill`, which also stops the trigger processes
+            nix::sys::signal::raise(nix::sys::signal::SIGTERM)?;
+        }
+        Ok(())
+    }
+
+    /// Sends a request to the control socket of the detached `spin up`,
+    /// returning its response.
+    #[cfg(not(windows))]
+    async fn control(&self, request: &str) -> Result<String> {
+        use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+
+        let exchange = async {
+            let mut stream = tokio::net::UnixStream::connect(self.control_socket()).await?;
+            stream.write_all(format!("{request}\n").as_bytes()).await?;
+            let mut response = String::new();
+            BufReader::new(stream).read_line(&mut response).await?;
+            anyhow::Ok(response)
+        };
+        tokio::time::timeout(CONTROL_TIMEOUT, exchange)
+            .await
+            .context("Timed out waiting for the control socket")?
+    }
+
+    #[cfg(windows)]
+    async fn control(&self, _request: &str) -> Result<String> {
+        bail!("Running applications in the background is not supported on Windows")
+    }
+
+    /// When the detached `spin up` was started.
+    fn started(&self) -> Option<SystemTime> {
+        std::fs::metadata(self.pid_file())
+            .and_then(|m| m.modified())
+            .ok()
+    }
+}
+
+/// The trigger processes of a detached `spin up`.
+#[derive(Serialize, Deserialize)]
+struct TriggerProcesses {
+    /// The process ID of the `spin up` which started the triggers
+    pid: u32,
+    triggers: Vec<TriggerProcess>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct TriggerProcess {
+    name: String,
+    pid: u32,
+}
+
+/// Whether this process is the background `spin up` started by [`detach`].
+/// The variable saying so is cleared, so that it doesn't reach the trigger
+/// processes or any command run by the app.
+pub(crate) fn take_detached() -> bool {
+    let detached = std::env::var_os(DETACHED_ENV).is_some();
+    std::env::remove_var(DETACHED_ENV);
+    detached
+}
+
+/// Runs `spin up` in the background, with the same arguments, recording its
+/// process ID and logging its output to the app's state directory. It is
+/// told not to detach again by an environment variable rather than by
+/// removing `--detach` from its arguments, which could be given in more than
+/// one form, or be passed on to the trigger.
+pub(crate) fn detach(files: &DaemonFiles) -> Result<()> {
+    if cfg!(windows) {
+        bail!("Running applications in the background is not supported on Windows");
+    }
+    if let Some(pid) = files.running_pid()? {
+        bail!("The application is already running in the background (process {pid}). Run `spin stop` to stop it.");
+    }
+
+    std::fs::create_dir_all(&files.dir)
+        .with_context(|| format!("Failed to create {}", quoted_path(&files.dir)))?;
+    let log_file = files.log_file();
+    let log = std::fs::File::create(&log_file)
+        .with_context(|| format!("Failed to create {}", quoted_path(&log_file)))?;
+
+    let mut cmd = std::process::Command::new(std::env::current_exe()?);
+    cmd.args(std::env::args_os().skip(1))
+        .env(DETACHED_ENV, "1")
+        .stdin(Stdio::null())
+        .stdout(log.try_clone()?)
+        .stderr(log);
+    #[cfg(not(windows))]
+    {
+        // Keep terminal signals such as Ctrl+C from reaching the application
+        use std::os::unix::process::CommandExt;
+        cmd.process_group(0);
+    }
+    let child = cmd.spawn().context("Failed to start application")?;
+
+    let pid_file = files.pid_file();
+    std::fs::write(&pid_file, child.id().to_string())
+        .with_context(|| format!("Failed to write {}", quoted_path(&pid_file)))?;
+
+    terminal::step!(
+        "Started",
+        "application in the background (process {})",
+        child.id()
+    );
+    println!("Logging to {}", quoted_path(&log_file));
+    println!("Use `spin status`, `spin logs` and `spin stop` to manage it.");
+    Ok(())
+}
+
+/// Stop an application running in the background.
+#[derive(Parser, Debug)]
+#[clap(about = "Stop an application started with `spin up --detach`")]
+pub struct StopCommand {
+    /// The application to stop. This may be a manifest (spin.toml) file, or a
+    /// directory containing a spin.toml file. If omitted, it is the
+    /// application in the current directory.
+    #[clap(name = APP_MANIFEST_FILE_OPT, short = 'f', long = "from", alias = "file")]
+    pub app_source: Option<PathBuf>,
+}
+
+impl StopCommand {
+    pub async fn run(self) -> Result<()> {
+        let files = DaemonFiles::from_option(self.app_source.as_deref())?;
+        let Some(pid) = files.running_pid()? else {
+            println!("The application is not running in the background.");
+            return Ok(());
+        };
+
+        // Ask the application to stop through its control socket, or failing
+        // that with a signal
+        if let Err(e) = files.control("stop").await {
+            tracing::debug!("Control socket unavailable, signalling instead: {e:#}");
+            terminate(pid)?;
+        }
+        let deadline = tokio::time::Instant::now() + STOP_TIMEOUT;
+        while is_running(pid) {
+            if tokio::time::Instant::now() >= deadline {
+                bail!("The application (process {pid}) did not stop within {STOP_TIMEOUT:?}");
+            }
+            tokio::time::sleep(POLL_INTERVAL).await;
+        }
+        files.remove_files();
+
+        terminal::step!("Stopped", "application (process {pid})");
+        Ok(())
+    }
+}
+
+/// Show whether an application is running in the background.
+#[derive(Parser, Debug)]
+#[clap(about = "Show whether an application started with `spin up --detach` is running")]
+pub struct StatusCommand {
+    /// The application to check. This may be a manifest (spin.toml) file, or
+    /// a directory containing a spin.toml file. If omitted, it is the
+    /// application in the current directory.
+    #[clap(name = APP_MANIFEST_FILE_OPT, short = 'f', long = "from", alias = "file")]
+    pub app_source: Option<PathBuf>,
+
+    #[clap(flatten)]
+    pub output: OutputArgs,
+}
+
+/// The result of `spin status --output json`.
+#[derive(Serialize)]
+struct StatusOutput {
+    running: bool,
+    pid: Option<u32>,
+    /// When the application was started, in seconds since the Unix epoch
+    started: Option<u64>,
+    /// The trigger processes of the application, and whether each is still
+    /// running
+    triggers: Vec<TriggerStatus>,
+    log_file: PathBuf,
+}
+
+#[derive(Serialize)]
+struct TriggerStatus {
+    name: String,
+    pid: u32,
+    running: bool,
+}
+
+impl StatusCommand {
+    pub async fn run(self) -> Result<()> {
+        let output = self.output.apply();
+        let files = DaemonFiles::from_option(self.app_source.as_deref())?;
+        let pid = files.running_pid()?;
+        let started = pid.and(files.started());
+        let triggers = match pid {
+            Some(pid) => files.running_triggers(pid).await,
+            None => vec![],
+        };
+        let triggers: Vec<_> = triggers
+            .into_iter()
+            .map(|trigger| TriggerStatus {
+                running: is_running(trigger.pid),
+                name: trigger.name,
+                pid: trigger.pid,
+            })
+            .collect();
+
+        if output.is_json() {
+            return print_json(&StatusOutput {
+                running: pid.is_some(),
+                pid,
+                started: started
+                    .and_then(|t| t.duration_since(SystemTime::UNIX_EPOCH).ok())
+                    .map(|d| d.as_secs()),
+                triggers,
+                log_file: files.log_file(),
+            });
+        }
+
+        match pid {
+            Some(pid) => {
+                let uptime = started
+                    .and_then(|t| t.elapsed().ok())
+                    .map(|d| format!(" for {}s", d.as_secs()))
+                    .unwrap_or_default();
+                println!("Running in the background (process {pid}){uptime}");
+                for trigger in &triggers {
+                    let state = if trigger.running { "running" } else { "exited" };
+                    println!(
+                        "  {} trigger: {state} (process {})",
+                        trigger.name, trigger.pid
+                    );
+                }
+                println!("Logging to {}", quoted_path(files.log_file()));
+            }
+            None => println!("Not running in the background"),
+        }
+        Ok(())
+    }
+}
+
+#[cfg(not(windows))]
+pub(crate) fn is_running(pid: u32) -> bool {
+    // Signal 0 only checks that the process exists
+    nix::sys::signal::kill(nix::unistd::Pid::from_raw(pid as i32), None).is_ok()
+}
+
+#[cfg(windows)]
+pub(crate) fn is_running(_pid: u32) -> bool {
+    false
+}
+
+/// Whether `pid` is a `spin up` run by this Spin executable, rather than an
+/// unrelated process which has been given the ID of one which has exited.
+#[cfg(not(windows))]
+fn is_spin_up(pid: u32) -> bool {
+    let executable = std::env::current_exe().ok();
+    let executable = executable.as_deref().and_then(Path::file_name);
+    process_args(pid).is_some_and(|args| is_spin_up_args(&args, executable))
+}
+
+#[cfg(windows)]
+fn is_spin_up(_pid: u32) -> bool {
+    false
+}
+
+#[cfg(not(windows))]
+fn is_spin_up_args(args: &[String], executable: Option<&std::ffi::OsStr>) -> bool {
+    let Some((program, args)) = args.split_first() else {
+        return false;
+    };
+    Path::new(program).file_name() == executable && args.iter().any(|arg| arg == "up" || arg == "u")
+}
+
+#[cfg(target_os = "linux")]
+fn process_args(pid: u32) -> Option<Vec<String>> {
+    let cmdline = std::fs::read(format!("/proc/{pid}/cmdline")).ok()?;
+    let args = cmdline
+        .split(|b| *b == 0)
+        .filter(|arg| !arg.is_empty())
+        .map(|arg| String::from_utf8_lossy(arg).into_owned())
+        .collect();
+    Some(args)
+}
+
+#[cfg(all(not(windows), not(target_os = "linux")))]
+fn process_args(pid: u32) -> Option<Vec<String>> {
+    let output = std::process::Command::new("ps")
+        .args(["-o", "command=", "-p", &pid.to_string()])
+        .output()
+        .ok()?;
+    let args = String::from_utf8_lossy(&output.stdout)
+        .split_whitespace()
+        .map(str::to_owned)
+        .collect();
+    output.status.success().then_some(args)
+}
+
+#[cfg(not(windows))]
+fn terminate(pid: u32) -> Result<()> {
+    // `spin up` stops its trigger processes when it is terminated
+    nix::sys::signal::kill(
+        nix::unistd::Pid::from_raw(pid as i32),
+        nix::sys::signal::SIGTERM,
+    )
+    .with_context(|| format!("Failed to stop process {pid}"))
+}
+
+#[cfg(windows)]
+fn terminate(_pid: u32) -> Result<()> {
+    bail!("Running applications in the background is not supported on Windows")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(not(windows))]
+    #[test]
+    fn stale_pidfiles_are_removed() {
+        let dir = tempfile::tempdir().unwrap();
+        let files = DaemonFiles::new(Some(dir.path()));
+        assert_eq!(files.running_pid().unwrap(), None);
+
+        // Process IDs are well below this on all supported platforms
+        std::fs::create_dir_all(&files.dir).unwrap();
+        std::fs::write(files.pid_file(), "2147483646").unwrap();
+        assert_eq!(files.running_pid().unwrap(), None);
+        assert!(!files.pid_file().exists());
+    }
+
+    #[cfg(not(windows))]
+    #[test]
+    fn pidfiles_naming_other_programs_are_removed() {
+        let dir = tempfile::tempdir().unwrap();
+        let files = DaemonFiles::new(Some(dir.path()));
+
+        // This test process is running, but is not `spin up`
+        std::fs::create_dir_all(&files.dir).unwrap();
+        std::fs::write(files.pid_file(), std::process::id().to_string()).unwrap();
+        assert_eq!(files.running_pid().unwrap(), None);
+        assert!(!files.pid_file().exists());
+    }
+
+    #[cfg(not(windows))]
+    #[test]
+    fn spin_up_is_recognised_by_its_arguments() {
+        let args = |args: &[&str]| args.iter().map(|arg| arg.to_string()).collect::<Vec<_>>();
+        let spin = Some(std::ffi::OsStr::new("spin"));
+        assert!(is_spin_up_args(
+            &args(&["/usr/local/bin/spin", "up", "--from", "app"]),
+            spin
+        ));
+        assert!(is_spin_up_args(&args(&["spin", "u"]), spin));
+        assert!(!is_spin_up_args(&args(&["/usr/bin/sleep", "up"]), spin));
+        assert!(!is_spin_up_args(&args(&["spin", "build"]), spin));
+        assert!(!is_spin_up_args(&[], spin));
+    }
+
+    #[cfg(not(windows))]
+    #[test]
+    fn triggers_are_recorded_by_the_detached_process() {
+        let dir = tempfile::tempdir().unwrap();
+        let files = DaemonFiles::new(Some(dir.path()));
+        std::fs::create_dir_all(&files.dir).unwrap();
+
+        // Another process is the detached `spin up`
+        std::fs::write(files.pid_file(), "1").unwrap();
+        files.record_triggers([("http", 10)]).unwrap();
+        assert!(!files.triggers_file().exists());
+
+        let pid = std::process::id();
+        std::fs::write(files.pid_file(), pid.to_string()).unwrap();
+        files
+            .record_triggers([("http", 10), ("redis", 11)])
+            .unwrap();
+        let triggers = files.triggers(pid);
+        assert_eq!(triggers.len(), 2);
+        assert_eq!((triggers[1].name.as_str(), triggers[1].pid), ("redis", 11));
+        assert!(files.triggers(pid + 1).is_empty());
+    }
+
+    #[cfg(not(windows))]
+    #[tokio::test]
+    async fn control_socket_reports_status() {
+        let dir = tempfile::tempdir().unwrap();
+        let files = DaemonFiles::new(Some(dir.path()));
+        std::fs::create_dir_all(&files.dir).unwrap();
+
+        // Only the detached `spin up` listens
+        std::fs::write(files.pid_file(), "1").unwrap();
+        files.serve_control().unwrap();
+        assert!(!files.control_socket().exists());
+
+        let pid = std::process::id();
+        std::fs::write(files.pid_file(), pid.to_string()).unwrap();
+        files.record_triggers([("http", 10)]).unwrap();
+        files.serve_control().unwrap();
+        let triggers = files.running_triggers(pid).await;
+        assert_eq!((triggers[0].name.as_str(), triggers[0].pid), ("http", 10));
+
+        let response = files.control("restart").await.unwrap();
+        assert!(response.is_empty());
+    }
+}
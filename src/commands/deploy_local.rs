@@ -0,0 +1,187 @@
+use std::net::SocketAddr;
+use std::path::PathBuf;
+
+use anyhow::{bail, Context, Result};
+use clap::Parser;
+use percent_encoding::{utf8_percent_encode, AsciiSet, NON_ALPHANUMERIC};
+use reqwest::StatusCode;
+use serde::Deserialize;
+use spin_common::ui::quoted_path;
+use spin_trigger::cli::env;
+
+use crate::opts::*;
+
+/// Deploy a new version of an application to a running `spin up --fleet`.
+#[derive(Parser, Debug)]
+#[clap(
+    about = "Deploy a new version of an application to a running `spin up --app` or `spin up --fleet` without downtime",
+    long_about = "Deploy a new version of an application to a running `spin up --app` or `spin up --fleet` without downtime.
+
+The new version is loaded alongside the running one, which keeps serving until the new version is ready. HTTP requests then switch to the new version at once, on the same listener, and the old version is stopped once it has finished the requests it was handling. If the fleet has no application of that name, the application is added to it."
+)]
+pub struct DeployLocalCommand {
+    /// The new version of the application. This may be a manifest
+    /// (spin.toml) file, or a directory containing a spin.toml file.
+    /// If omitted, it defaults to "spin.toml".
+    #[clap(
+        name = APP_MANIFEST_FILE_OPT,
+        default_value = DEFAULT_MANIFEST_FILE
+    )]
+    pub app_source: PathBuf,
+
+    /// The address of the fleet's admin API, as given to
+    /// `spin up --admin-listen`.
+    #[clap(long = "admin", value_name = "ADDRESS", env = env::ADMIN_LISTEN)]
+    pub admin: SocketAddr,
+
+    /// The token of the fleet's admin API, as given to `spin up` in
+    /// SPIN_ADMIN_TOKEN. If omitted, it is the token `spin up` generated in
+    /// .spin/admin-token under the current directory.
+    #[clap(
+        long = "admin-token",
+        value_name = "TOKEN",
+        env = env::ADMIN_TOKEN,
+        hide_env_values = true
+    )]
+    pub admin_token: Option<String>,
+
+    /// The name of the application in the fleet. Defaults to the name in its
+    /// manifest.
+    #[clap(long = "name")]
+    pub name: Option<String>,
+}
+
+/// The characters escaped in a path segment; the unreserved characters of
+/// RFC 3986 are left as they are.
+const PATH_SEGMENT: &AsciiSet = &NON_ALPHANUMERIC
+    .remove(b'-')
+    .remove(b'.')
+    .remove(b'_')
+    .remove(b'~');
+
+#[derive(Deserialize)]
+struct AdminError {
+    error: String,
+}
+
+impl DeployLocalCommand {
+    pub async fn run(self) -> Result<()> {
+        let manifest_file = spin_common::paths::resolve_manifest_file_path(&self.app_source)?;
+        let manifest_file = dunce::canonicalize(&manifest_file)
+            .with_context(|| format!("Failed to find manifest {}", quoted_path(&manifest_file)))?;
+        let name = match self.name {
+            Some(name) => name,
+            None => {
+                spin_manifest::manifest_from_file(&manifest_file)?
+                    .application
+                    .name
+            }
+        };
+
+        if matches!(name.as_str(), "" | "." | "..") {
+            bail!("The app name {name:?} can't be used in an admin API path; give another with --name");
+        }
+
+        let client = reqwest::Client::new();
+        let admin = self.admin;
+        let token = match self.admin_token {
+            Some(token) => Some(token),
+            None => generated_token()?,
+        };
+        let token = token.as_deref();
+        let upgrade = serde_json::json!({ "manifest": manifest_file });
+        let segment = utf8_percent_encode(&name, PATH_SEGMENT);
+        let response = post(
+            &client,
+            &format!("http://{admin}/apps/{segment}/upgrade"),
+            token,
+            &upgrade,
+        )
+        .await?;
+        if response.status() != StatusCode::NOT_FOUND {
+            check_response(response).await?;
+            terminal::step!(
+                "Upgraded",
+                "app {name:?} to {}",
+                quoted_path(&manifest_file)
+            );
+            return Ok(());
+        }
+
+        let deploy = serde_json::json!({ "name": name, "manifest": manifest_file });
+        let response = post(&client, &format!("http://{admin}/apps"), token, &deploy).await?;
+        if response.status() == StatusCode::NOT_FOUND {
+            bail!("The admin API on {admin} doesn't deploy apps; it must be served by `spin up --app` or `spin up --fleet`");
+        }
+        check_response(response).await?;
+        terminal::step!(
+            "Deployed",
+            "app {name:?} from {}",
+            quoted_path(&manifest_file)
+        );
+        Ok(())
+    }
+}
+
+/// Reads the token generated by a `spin up --app` or `--fleet` run in the
+/// current directory, if there is one.
+fn generated_token() -> Result<Option<String>> {
+    let path = crate::commands::daemon::DaemonFiles::new(None)
+        .dir()
+        .join(spin_trigger::admin::TOKEN_FILE);
+    match std::fs::read_to_string(&path) {
+        Ok(token) => Ok(Some(token.trim().to_owned())),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e).with_context(|| format!("Failed to read {}", quoted_path(&path))),
+    }
+}
+
+async fn post(
+    client: &reqwest::Client,
+    url: &str,
+    token: Option<&str>,
+    body: &serde_json::Value,
+) -> Result<reqwest::Response> {
+    let mut request = client
+        .post(url)
+        .header(reqwest::header::CONTENT_TYPE, "application/json")
+        .body(serde_json::to_vec(body)?);
+    if let Some(token) = token {
+        request = request.bearer_auth(token);
+    }
+    request
+        .send()
+        .await
+        .with_context(|| format!("Failed to reach the admin API at {url}"))
+}
+
+async fn check_response(response: reqwest::Response) -> Result<()> {
+    let status = response.status();
+    if status.is_success() {
+        return Ok(());
+    }
+    if status == StatusCode::UNAUTHORIZED {
+        bail!(
+            "The admin API refused the token; give the one `spin up` was given or wrote to .spin/admin-token with --admin-token or {}",
+            env::ADMIN_TOKEN
+        );
+    }
+    let body = response.bytes().await.unwrap_or_default();
+    match serde_json::from_slice::<AdminError>(&body) {
+        Ok(AdminError { error }) => bail!("{error}"),
+        Err(_) => bail!("The admin API responded with {status}"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn app_names_are_escaped_in_paths() {
+        let escape = |name| utf8_percent_encode(name, PATH_SEGMENT).to_string();
+        assert_eq!(escape("shop-v2.1"), "shop-v2.1");
+        assert_eq!(escape("a/b?c#d"), "a%2Fb%3Fc%23d");
+        assert_eq!(escape("caf\u{e9} app"), "caf%C3%A9%20app");
+    }
+}
@@ -3,9 +3,15 @@ use std::{fmt::Debug, path::PathBuf};
 use anyhow::Result;
 use clap::Parser;
 use dialoguer::{console::Emoji, Confirm, Select};
-use spin_doctor::{Diagnosis, DryRunNotSupported, PatientDiagnosis};
+use serde::Serialize;
+use spin_doctor::{
+    external::ExternalDiagnostic, Checkup, Diagnosis, DryRunNotSupported, PatientDiagnosis,
+};
+use spin_plugins::manager::PluginManager;
 
+use crate::commands::external::get_env_vars_map;
 use crate::opts::{APP_MANIFEST_FILE_OPT, DEFAULT_MANIFEST_FILE};
+use crate::output::{print_json, OutputArgs};
 
 #[derive(Parser, Debug)]
 #[clap(about = "Detect and fix problems with Spin applications")]
@@ -21,11 +27,23 @@ pub struct DoctorCommand {
         default_value = DEFAULT_MANIFEST_FILE
     )]
     pub app_source: PathBuf,
+
+    /// Apply all available fixes without prompting.
+    #[clap(long = "fix")]
+    pub fix: bool,
+
+    // With JSON output, problems are listed but no treatments are offered
+    // unless --fix is given
+    #[clap(flatten)]
+    pub output: OutputArgs,
 }
 
 impl DoctorCommand {
     pub async fn run(self) -> Result<()> {
         let manifest_file = spin_common::paths::resolve_manifest_file_path(&self.app_source)?;
+        if self.output.apply().is_json() {
+            return list_diagnoses(manifest_file, self.fix).await;
+        }
 
         println!("{icon}The Spin Doctor is in.", icon = Emoji("📟 ", ""));
         println!(
@@ -34,7 +52,7 @@ impl DoctorCommand {
             icon = Emoji("🩺 ", "")
         );
 
-        let mut checkup = spin_doctor::Checkup::new(manifest_file)?;
+        let mut checkup = new_checkup(manifest_file)?;
         let mut has_problems = false;
         while let Some(PatientDiagnosis { diagnosis, patient }) = checkup.next_diagnosis().await? {
             show_diagnosis(&*diagnosis);
@@ -52,11 +70,15 @@ impl DoctorCommand {
                     }
                 };
 
-                let should_treat =
+                let should_treat = if self.fix {
+                    println!("{icon}{}", treatment.summary(), icon = Emoji("🩹 ", ""));
+                    true
+                } else {
                     prompt_treatment(treatment.summary(), dry_run).unwrap_or_else(|err| {
                         show_error("Prompt error: ", err);
                         false
-                    });
+                    })
+                };
 
                 if should_treat {
                     match treatment.treat(patient).await {
@@ -76,13 +98,86 @@ impl DoctorCommand {
                 }
             }
         }
-        if has_problems {
+        if !has_problems {
             println!("{icon}No problems found.", icon = Emoji("❤  ", ""));
         }
         Ok(())
     }
 }
 
+/// The result of `spin doctor --output json`.
+#[derive(Serialize)]
+struct DoctorOutput {
+    manifest: PathBuf,
+    diagnoses: Vec<DiagnosisOutput>,
+}
+
+#[derive(Serialize)]
+struct DiagnosisOutput {
+    description: String,
+    critical: bool,
+    /// A summary of the treatment `spin doctor` can apply, if any
+    treatment: Option<String>,
+    /// With --fix, whether the treatment was applied successfully
+    #[serde(skip_serializing_if = "Option::is_none")]
+    fixed: Option<bool>,
+}
+
+/// Prints the problems found with the app as JSON, treating them if `fix`
+/// is set.
+async fn list_diagnoses(manifest_file: PathBuf, fix: bool) -> Result<()> {
+    let mut checkup = new_checkup(manifest_file.clone())?;
+    let mut diagnoses = vec![];
+    while let Some(PatientDiagnosis { diagnosis, patient }) = checkup.next_diagnosis().await? {
+        let treatment = diagnosis.treatment();
+        let fixed = match treatment {
+            Some(treatment) if fix => Some(match treatment.treat(patient).await {
+                Ok(()) => true,
+                Err(err) => {
+                    tracing::warn!("Treatment failed: {err:?}");
+                    false
+                }
+            }),
+            _ => None,
+        };
+        diagnoses.push(DiagnosisOutput {
+            description: diagnosis.description(),
+            critical: diagnosis.is_critical(),
+            treatment: treatment.map(|t| t.summary()),
+            fixed,
+        });
+    }
+    print_json(&DoctorOutput {
+        manifest: manifest_file,
+        diagnoses,
+    })
+}
+
+/// Returns a checkup with the built-in diagnoses, followed by those of any
+/// installed plugins which provide diagnoses.
+fn new_checkup(manifest_file: PathBuf) -> Result<Checkup> {
+    let mut checkup = Checkup::new(manifest_file)?;
+    if let Err(err) = add_plugin_diagnostics(&mut checkup) {
+        tracing::debug!("Couldn't add plugin diagnoses: {err:?}");
+    }
+    Ok(checkup)
+}
+
+fn add_plugin_diagnostics(checkup: &mut Checkup) -> Result<()> {
+    let manager = PluginManager::try_default()?;
+    let store = manager.store();
+    let envs = get_env_vars_map()?;
+    for manifest in store.installed_manifests()? {
+        if manifest.provides_diagnoses() {
+            let name = manifest.name();
+            let diagnostic = ExternalDiagnostic::new(&name, store.installed_binary_path(&name))
+                .with_envs(envs.clone());
+            checkup.add_diagnostic_with(diagnostic);
+        }
+    }
+    Ok(())
+}
+
 fn show_diagnosis(diagnosis: &dyn Diagnosis) {
     let icon = if diagnosis.is_critical() {
         Emoji("❗ ", "")
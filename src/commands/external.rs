@@ -315,7 +315,7 @@ fn undecorate(decorated_name: &str) -> String {
     }
 }
 
-fn get_env_vars_map() -> Result<HashMap<String, String>> {
+pub(crate) fn get_env_vars_map() -> Result<HashMap<String, String>> {
     let map: HashMap<String, String> = vec![
         ("SPIN_VERSION", SPIN_VERSION),
         ("SPIN_VERSION_MAJOR", SPIN_VERSION_MAJOR),
@@ -0,0 +1,679 @@
+use std::{collections::BTreeMap, path::PathBuf};
+
+use anyhow::{bail, Context, Result};
+use clap::Parser;
+use comfy_table::Table;
+use serde::Serialize;
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use spin_common::ui::quoted_path;
+use spin_expressions::Template;
+use spin_loader::FilesMountStrategy;
+use spin_locked_app::locked::{LockedApp, LockedComponent, LockedTrigger};
+use spin_locked_app::{MetadataExt, MetadataKey};
+use spin_manifest::schema::v2::{AppManifest, ComponentResources, WasiFilesMount};
+use spin_oci::OciLoader;
+use tempfile::TempDir;
+
+use crate::opts::*;
+use crate::output::{print_json, OutputArgs};
+
+use super::up::app_source::AppSource;
+
+/// Show what a Spin application contains, without running it.
+#[derive(Parser, Debug)]
+#[clap(about = "Show the components, triggers and settings of a Spin application")]
+pub struct InspectCommand {
+    /// The application to inspect. This may be a manifest (spin.toml) file, a
+    /// directory containing a spin.toml file, or a remote registry reference.
+    /// If omitted, it defaults to "spin.toml".
+    #[clap(name = APP_MANIFEST_FILE_OPT, default_value = DEFAULT_MANIFEST_FILE)]
+    pub app_source: String,
+
+    /// Ignore server certificate errors from a registry
+    #[clap(
+        name = INSECURE_OPT,
+        short = 'k',
+        long = "insecure",
+        takes_value = false,
+    )]
+    pub insecure: bool,
+
+    /// Cache directory for downloaded components and assets.
+    #[clap(long)]
+    pub cache_dir: Option<PathBuf>,
+
+    /// Instead of the application's contents, show what each component can
+    /// access at runtime: outbound hosts, key-value stores, SQLite databases,
+    /// files, variables and AI models. Use with `--output json` to feed the
+    /// report to review tooling.
+    #[clap(long = "capabilities", takes_value = false)]
+    pub capabilities: bool,
+
+    #[clap(flatten)]
+    pub output: OutputArgs,
+}
+
+impl InspectCommand {
+    pub async fn run(self) -> Result<()> {
+        let output = self.output.apply();
+        // Holds any files copied while loading until we're done
+        let working_dir = TempDir::with_prefix("spin-inspect-")?;
+
+        let (source, locked_app, manifest) = match AppSource::infer_source(&self.app_source) {
+            AppSource::File(manifest_path) => {
+                let manifest = spin_loader::manifest_from_file(&manifest_path, None)?;
+                let locked_app = spin_loader::from_file(
+                    &manifest_path,
+                    FilesMountStrategy::Copy(working_dir.path().join("assets")),
+                    self.cache_dir.clone(),
+                )
+                .await
+                .with_context(|| {
+                    format!(
+                        "Failed to load manifest from {}",
+                        quoted_path(&manifest_path)
+                    )
+                })?;
+                let source = manifest_path.display().to_string();
+                (source, locked_app, Some(manifest))
+            }
+            AppSource::OciRegistry(reference) => {
+                let mut client = spin_oci::Client::new(self.insecure, self.cache_dir.clone())
+                    .await
+                    .context("cannot create registry client")?;
+                let locked_app = OciLoader::new(working_dir.path())
+                    .load_app(&mut client, &reference)
+                    .await?;
+                (reference, locked_app, None)
+            }
+            AppSource::Wasm(_) => {
+                bail!("A Wasm file is not an application; inspect a manifest or registry reference")
+            }
+            AppSource::Unresolvable(err) => bail!("{err}"),
+            AppSource::None => bail!("No application to inspect"),
+        };
+
+        if self.capabilities {
+            let report = CapabilityReport::new(source, &locked_app);
+            if output.is_json() {
+                return print_json(&report);
+            }
+            report.print_table();
+            return Ok(());
+        }
+
+        let inspection = AppInspection::new(source, &locked_app, manifest.as_ref());
+        if output.is_json() {
+            print_json(&inspection)
+        } else {
+            inspection.print_tables();
+            Ok(())
+        }
+    }
+}
+
+/// The resolved application, as shown by `spin inspect`.
+#[derive(Debug, Serialize)]
+struct AppInspection {
+    source: String,
+    name: Option<String>,
+    version: Option<String>,
+    description: Option<String>,
+    variables: Vec<VariableInspection>,
+    components: Vec<ComponentInspection>,
+    triggers: Vec<TriggerInspection>,
+}
+
+#[derive(Debug, Serialize)]
+struct VariableInspection {
+    name: String,
+    required: bool,
+    secret: bool,
+    /// The default value, unless the variable is secret
+    default: Option<String>,
+    description: Option<String>,
+    example: Option<String>,
+    /// A regular expression which the whole value must match
+    pattern: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+struct ComponentInspection {
+    id: String,
+    source: Option<String>,
+    digest: Option<String>,
+    allowed_outbound_hosts: Vec<String>,
+    /// The files made available to the component
+    files: Vec<String>,
+    /// Component variables, with the templates which set them
+    variables: BTreeMap<String, String>,
+    resources: Option<ComponentResources>,
+}
+
+#[derive(Debug, Serialize)]
+struct TriggerInspection {
+    id: String,
+    trigger_type: String,
+    component: Option<String>,
+    config: Value,
+}
+
+/// What each component of an application can access at runtime, as shown by
+/// `spin inspect --capabilities`.
+#[derive(Debug, Serialize)]
+struct CapabilityReport {
+    source: String,
+    name: Option<String>,
+    version: Option<String>,
+    components: Vec<ComponentCapabilities>,
+}
+
+#[derive(Debug, Serialize)]
+struct ComponentCapabilities {
+    id: String,
+    /// Hosts the component may connect to. These may contain variables,
+    /// which are resolved at runtime.
+    allowed_outbound_hosts: Vec<String>,
+    /// Labels of the key-value stores the component may use
+    key_value_stores: Vec<String>,
+    /// Labels of the SQLite databases the component may use
+    sqlite_databases: Vec<String>,
+    files: Vec<FileCapability>,
+    variables: Vec<VariableCapability>,
+    ai_models: Vec<String>,
+}
+
+#[derive(Debug, Serialize)]
+struct FileCapability {
+    /// Where the files are mounted in the component's filesystem
+    path: String,
+    /// Always `ro`: mounts are read-only, unless the trigger is run with
+    /// `--allow-transient-write`, when writes are discarded once the
+    /// component finishes.
+    access: &'static str,
+}
+
+#[derive(Debug, Serialize)]
+struct VariableCapability {
+    name: String,
+    /// Whether the value refers to a secret application variable
+    secret: bool,
+}
+
+const KEY_VALUE_STORES_KEY: MetadataKey<Vec<String>> = MetadataKey::new("key_value_stores");
+const DATABASES_KEY: MetadataKey<Vec<String>> = MetadataKey::new("databases");
+const AI_MODELS_KEY: MetadataKey<Vec<String>> = MetadataKey::new("ai_models");
+const ALLOWED_OUTBOUND_HOSTS_KEY: MetadataKey<Vec<String>> =
+    MetadataKey::new("allowed_outbound_hosts");
+
+impl CapabilityReport {
+    fn new(source: String, locked_app: &LockedApp) -> Self {
+        let metadata = |key: &str| {
+            locked_app
+                .metadata
+                .get(key)
+                .and_then(Value::as_str)
+                .map(str::to_owned)
+        };
+        let components = locked_app
+            .components
+            .iter()
+            .map(|component| ComponentCapabilities::new(component, locked_app))
+            .collect();
+        Self {
+            source,
+            name: metadata("name"),
+            version: metadata("version"),
+            components,
+        }
+    }
+
+    fn print_table(&self) {
+        let mut table = new_table([
+            "Component",
+            "Allowed outbound hosts",
+            "Key-value stores",
+            "SQLite databases",
+            "Files",
+            "Variables",
+            "AI models",
+        ]);
+        for component in &self.components {
+            let files = component.files.iter().map(|file| {
+                let FileCapability { path, access } = file;
+                format!("{path} ({access})")
+            });
+            let variables = component.variables.iter().map(|variable| {
+                if variable.secret {
+                    format!("{} (secret)", variable.name)
+                } else {
+                    variable.name.clone()
+                }
+            });
+            table.add_row([
+                component.id.clone(),
+                component.allowed_outbound_hosts.join("\n"),
+                component.key_value_stores.join("\n"),
+                component.sqlite_databases.join("\n"),
+                files.collect::<Vec<_>>().join("\n"),
+                variables.collect::<Vec<_>>().join("\n"),
+                component.ai_models.join("\n"),
+            ]);
+        }
+        println!("{table}");
+    }
+}
+
+impl ComponentCapabilities {
+    fn new(component: &LockedComponent, locked_app: &LockedApp) -> Self {
+        let list = |key| {
+            component
+                .metadata
+                .get_typed(key)
+                .ok()
+                .flatten()
+                .unwrap_or_default()
+        };
+        let files = component
+            .files
+            .iter()
+            .map(|file| FileCapability {
+                path: file.path.display().to_string(),
+                access: "ro",
+            })
+            .collect();
+        let variables = component
+            .config
+            .iter()
+            .map(|(name, value)| {
+                // Templates were validated when the app was loaded
+                let secret = Template::new(value.as_str()).is_ok_and(|template| {
+                    template.variables().any(|var| {
+                        locked_app
+                            .variables
+                            .get(var)
+                            .is_some_and(|variable| variable.secret)
+                    })
+                });
+                VariableCapability {
+                    name: name.clone(),
+                    secret,
+                }
+            })
+            .collect();
+        Self {
+            id: component.id.clone(),
+            allowed_outbound_hosts: list(ALLOWED_OUTBOUND_HOSTS_KEY),
+            key_value_stores: list(KEY_VALUE_STORES_KEY),
+            sqlite_databases: list(DATABASES_KEY),
+            files,
+            variables,
+            ai_models: list(AI_MODELS_KEY),
+        }
+    }
+}
+
+impl AppInspection {
+    /// Describes a loaded app. For local apps, the manifest describes file
+    /// mounts better than the copies made by loading it.
+    fn new(source: String, locked_app: &LockedApp, manifest: Option<&AppManifest>) -> Self {
+        let metadata = |key: &str| {
+            locked_app
+                .metadata
+                .get(key)
+                .and_then(Value::as_str)
+                .map(str::to_owned)
+        };
+        let variables = locked_app
+            .variables
+            .iter()
+            .map(|(name, variable)| VariableInspection {
+                name: name.clone(),
+                required: variable.default.is_none(),
+                secret: variable.secret,
+                default: variable.default.clone().filter(|_| !variable.secret),
+                description: variable.description.clone(),
+                example: variable.example.clone(),
+                pattern: variable.pattern.clone(),
+            })
+            .collect();
+        let components = locked_app
+            .components
+            .iter()
+            .map(|component| ComponentInspection::new(component, manifest))
+            .collect();
+        let triggers = locked_app
+            .triggers
+            .iter()
+            .map(TriggerInspection::new)
+            .collect();
+
+        Self {
+            source,
+            name: metadata("name"),
+            version: metadata("version"),
+            description: metadata("description"),
+            variables,
+            components,
+            triggers,
+        }
+    }
+
+    fn print_tables(&self) {
+        let name = self.name.as_deref().unwrap_or("<unnamed>");
+        match &self.version {
+            Some(version) => println!("Application: {name} {version}"),
+            None => println!("Application: {name}"),
+        }
+        println!("Source: {}", self.source);
+        if let Some(description) = &self.description {
+            println!("Description: {description}");
+        }
+
+        println!("\nComponents:");
+        let mut table = new_table([
+            "Component",
+            "Source",
+            "Allowed outbound hosts",
+            "Files",
+            "Resources",
+        ]);
+        for component in &self.components {
+            let mut source = component.source.clone().unwrap_or_default();
+            if let Some(digest) = &component.digest {
+                source = format!("{source}\n{digest}");
+            }
+            table.add_row([
+                component.id.clone(),
+                source,
+                component.allowed_outbound_hosts.join("\n"),
+                component.files.join("\n"),
+                component
+                    .resources
+                    .as_ref()
+                    .map(describe_resources)
+                    .unwrap_or_default(),
+            ]);
+        }
+        println!("{table}");
+
+        println!("\nTriggers:");
+        let mut table = new_table(["Type", "Component", "Config"]);
+        for trigger in &self.triggers {
+            table.add_row([
+                trigger.trigger_type.clone(),
+                trigger.component.clone().unwrap_or_default(),
+                trigger.config.to_string(),
+            ]);
+        }
+        println!("{table}");
+
+        if !self.variables.is_empty() {
+            println!("\nVariables:");
+            let mut table = new_table([
+                "Variable",
+                "Required",
+                "Secret",
+                "Default",
+                "Description",
+                "Example",
+                "Pattern",
+            ]);
+            for variable in &self.variables {
+                table.add_row([
+                    variable.name.clone(),
+                    yes_no(variable.required),
+                    yes_no(variable.secret),
+                    variable.default.clone().unwrap_or_default(),
+                    variable.description.clone().unwrap_or_default(),
+                    variable.example.clone().unwrap_or_default(),
+                    variable.pattern.clone().unwrap_or_default(),
+                ]);
+            }
+            println!("{table}");
+        }
+    }
+}
+
+impl ComponentInspection {
+    fn new(component: &LockedComponent, manifest: Option<&AppManifest>) -> Self {
+        let content = &component.source.content;
+        let digest = content.digest.clone().or_else(|| {
+            let path = spin_common::url::parse_file_url(content.source.as_deref()?).ok()?;
+            let bytes = std::fs::read(path).ok()?;
+            Some(format!("sha256:{:x}", Sha256::digest(bytes)))
+        });
+        let allowed_outbound_hosts = component
+            .metadata
+            .get("allowed_outbound_hosts")
+            .and_then(Value::as_array)
+            .map(|hosts| {
+                hosts
+                    .iter()
+                    .filter_map(Value::as_str)
+                    .map(str::to_owned)
+                    .collect()
+            })
+            .unwrap_or_default();
+        let manifest_component = manifest.and_then(|manifest| {
+            manifest
+                .components
+                .iter()
+                .find(|(id, _)| id.as_ref() == component.id)
+                .map(|(_, c)| c)
+        });
+        let files = match manifest_component {
+            Some(c) => c
+                .files
+                .iter()
+                .map(|mount| match mount {
+                    WasiFilesMount::Pattern(pattern) => pattern.clone(),
+                    WasiFilesMount::Placement {
+                        source,
+                        destination,
+                        exclude,
+                        skip_hidden,
+                    } => {
+                        let mut mount = format!("{source} -> {destination}");
+                        for pattern in exclude {
+                            mount.push_str(&format!(" (excluding {pattern})"));
+                        }
+                        if *skip_hidden {
+                            mount.push_str(" (excluding hidden files)");
+                        }
+                        mount
+                    }
+                })
+                .chain(c.exclude_files.iter().map(|p| format!("(excluding {p})")))
+                .collect(),
+            None => component
+                .files
+                .iter()
+                .map(|file| file.path.display().to_string())
+                .collect(),
+        };
+
+        let resources = component
+            .metadata
+            .get_typed(spin_trigger::RESOURCES_KEY)
+            .ok()
+            .flatten();
+
+        Self {
+            id: component.id.clone(),
+            source: content.source.clone(),
+            digest,
+            allowed_outbound_hosts,
+            files,
+            variables: component.config.clone(),
+            resources,
+        }
+    }
+}
+
+impl TriggerInspection {
+    fn new(trigger: &LockedTrigger) -> Self {
+        let mut config = trigger.trigger_config.clone();
+        let component = config
+            .as_object_mut()
+            .and_then(|config| config.remove("component"))
+            .and_then(|component| component.as_str().map(str::to_owned));
+        Self {
+            id: trigger.id.clone(),
+            trigger_type: trigger.trigger_type.clone(),
+            component,
+            config,
+        }
+    }
+}
+
+fn describe_resources(resources: &ComponentResources) -> String {
+    let mut limits = vec![];
+    if let Some(max_memory_mb) = resources.max_memory_mb {
+        limits.push(format!("memory: {max_memory_mb} MiB"));
+    }
+    if let Some(timeout_ms) = resources.timeout_ms {
+        limits.push(format!("timeout: {timeout_ms} ms"));
+    }
+    if let Some(max_concurrent) = resources.max_concurrent {
+        limits.push(format!("concurrency: {max_concurrent}"));
+    }
+    limits.join("\n")
+}
+
+fn new_table<const N: usize>(header: [&str; N]) -> Table {
+    let mut table = Table::new();
+    table.set_header(header);
+    table.load_preset(comfy_table::presets::ASCII_BORDERS_ONLY_CONDENSED);
+    table
+}
+
+fn yes_no(value: bool) -> String {
+    if value { "yes" } else { "no" }.to_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn inspection_hides_secrets_and_separates_trigger_components() {
+        let locked_app: LockedApp = serde_json::from_value(serde_json::json!({
+            "spin_lock_version": 1,
+            "metadata": { "name": "shop", "version": "1.2.0" },
+            "variables": {
+                "api_key": { "default": "hunter2", "secret": true },
+                "region": {
+                    "default": "eu",
+                    "description": "Where to store data",
+                    "pattern": "[a-z]+",
+                },
+                "token": { "secret": true },
+            },
+            "triggers": [{
+                "id": "trigger--cart",
+                "trigger_type": "http",
+                "trigger_config": { "component": "cart", "route": "/cart/..." },
+            }],
+            "components": [{
+                "id": "cart",
+                "metadata": {
+                    "allowed_outbound_hosts": ["https://payments.example.com"],
+                    "resources": { "max_memory_mb": 64, "max_concurrent": 4 },
+                },
+                "source": {
+                    "content_type": "application/wasm",
+                    "source": "https://example.com/cart.wasm",
+                    "digest": "sha256:abc",
+                },
+                "files": [{ "path": "/static" }],
+                "config": { "api_key": "{{ api_key }}" },
+            }],
+        }))
+        .unwrap();
+
+        let inspection = AppInspection::new("ghcr.io/shop:1.2.0".to_owned(), &locked_app, None);
+        assert_eq!(inspection.name.as_deref(), Some("shop"));
+
+        let variables: Vec<_> = inspection
+            .variables
+            .iter()
+            .map(|v| (v.name.as_str(), v.required, v.secret, v.default.as_deref()))
+            .collect();
+        assert_eq!(
+            variables,
+            [
+                ("api_key", false, true, None),
+                ("region", false, false, Some("eu")),
+                ("token", true, true, None),
+            ]
+        );
+        assert_eq!(
+            inspection.variables[1].description.as_deref(),
+            Some("Where to store data")
+        );
+        assert_eq!(inspection.variables[1].pattern.as_deref(), Some("[a-z]+"));
+
+        let cart = &inspection.components[0];
+        assert_eq!(cart.digest.as_deref(), Some("sha256:abc"));
+        assert_eq!(
+            cart.allowed_outbound_hosts,
+            ["https://payments.example.com"]
+        );
+        assert_eq!(cart.files, ["/static"]);
+        assert_eq!(
+            describe_resources(cart.resources.as_ref().unwrap()),
+            "memory: 64 MiB\nconcurrency: 4"
+        );
+
+        let trigger = &inspection.triggers[0];
+        assert_eq!(trigger.component.as_deref(), Some("cart"));
+        assert_eq!(trigger.config, serde_json::json!({ "route": "/cart/..." }));
+    }
+
+    #[test]
+    fn capabilities_mark_secret_variables() {
+        let locked_app: LockedApp = serde_json::from_value(serde_json::json!({
+            "spin_lock_version": 1,
+            "variables": {
+                "api_key": { "secret": true },
+                "region": { "default": "eu" },
+            },
+            "triggers": [],
+            "components": [{
+                "id": "cart",
+                "metadata": {
+                    "allowed_outbound_hosts": ["https://payments.example.com"],
+                    "key_value_stores": ["default"],
+                    "databases": ["orders"],
+                    "ai_models": ["llama2-chat"],
+                },
+                "source": { "content_type": "application/wasm" },
+                "files": [{ "path": "/static" }],
+                "config": {
+                    "auth": "Bearer {{ api_key }}",
+                    "region": "{{ region }}",
+                },
+            }],
+        }))
+        .unwrap();
+
+        let report = CapabilityReport::new("spin.toml".to_owned(), &locked_app);
+        let cart = &report.components[0];
+        assert_eq!(
+            cart.allowed_outbound_hosts,
+            ["https://payments.example.com"]
+        );
+        assert_eq!(cart.key_value_stores, ["default"]);
+        assert_eq!(cart.sqlite_databases, ["orders"]);
+        assert_eq!(cart.ai_models, ["llama2-chat"]);
+        assert_eq!(cart.files[0].path, "/static");
+        let variables: Vec<_> = cart
+            .variables
+            .iter()
+            .map(|v| (v.name.as_str(), v.secret))
+            .collect();
+        assert_eq!(variables, [("auth", true), ("region", false)]);
+    }
+}
@@ -0,0 +1,323 @@
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{bail, Context, Result};
+use clap::Parser;
+use serde::{Deserialize, Serialize};
+use spin_common::ui::quoted_path;
+use spin_loader::FilesMountStrategy;
+use spin_locked_app::locked::{LockedApp, LockedComponent};
+use tempfile::TempDir;
+
+use crate::opts::*;
+
+/// The lock file of an application, next to its manifest.
+pub(crate) const LOCK_FILE: &str = "spin.lock";
+const LOCK_VERSION: u32 = 1;
+
+/// Generate a lock file for a Spin application.
+#[derive(Parser, Debug)]
+#[clap(
+    about = "Record the exact components, variables and triggers of the application in spin.lock",
+    long_about = "Record the exact components, variables and triggers of the application in spin.lock.
+
+The lock file is meant to be committed alongside the manifest. `spin up --locked` refuses to run the application if it no longer matches the lock file, so that a deployment runs exactly the components which were locked."
+)]
+pub struct LockCommand {
+    /// The application to lock. This may be a manifest (spin.toml) file, or a
+    /// directory containing a spin.toml file.
+    /// If omitted, it defaults to "spin.toml".
+    #[clap(
+        name = APP_MANIFEST_FILE_OPT,
+        short = 'f',
+        long = "from",
+        alias = "file",
+        default_value = DEFAULT_MANIFEST_FILE
+    )]
+    pub app_source: PathBuf,
+
+    /// Check that the lock file is up to date, instead of writing it.
+    #[clap(long = "check", takes_value = false)]
+    pub check: bool,
+
+    /// Cache directory for downloaded components and assets.
+    #[clap(long)]
+    pub cache_dir: Option<PathBuf>,
+}
+
+impl LockCommand {
+    pub async fn run(self) -> Result<()> {
+        let manifest_file = spin_common::paths::resolve_manifest_file_path(&self.app_source)?;
+        // Holds any files copied while loading until we're done
+        let working_dir = TempDir::with_prefix("spin-lock-")?;
+        let locked_app = spin_loader::from_file(
+            &manifest_file,
+            FilesMountStrategy::Copy(working_dir.path().join("assets")),
+            self.cache_dir.clone(),
+        )
+        .await
+        .with_context(|| {
+            format!(
+                "Failed to load manifest from {}",
+                quoted_path(&manifest_file)
+            )
+        })?;
+        let lock = AppLock::new(&locked_app)?;
+        let lock_file = lock_file_path(&manifest_file);
+
+        if self.check {
+            check_lock(&lock, &lock_file)?;
+            println!("{} is up to date", quoted_path(&lock_file));
+            return Ok(());
+        }
+
+        let mut contents = serde_json::to_string_pretty(&lock)?;
+        contents.push('\n');
+        std::fs::write(&lock_file, contents)
+            .with_context(|| format!("Failed to write {}", quoted_path(&lock_file)))?;
+        terminal::step!("Locked", "{}", quoted_path(&lock_file));
+        Ok(())
+    }
+}
+
+/// The lock file of the application with the given manifest.
+pub(crate) fn lock_file_path(manifest_file: &Path) -> PathBuf {
+    manifest_file.with_file_name(LOCK_FILE)
+}
+
+/// Fails unless the lock file exists and matches the loaded application.
+pub(crate) fn check_lock(lock: &AppLock, lock_file: &Path) -> Result<()> {
+    let contents = match std::fs::read_to_string(lock_file) {
+        Ok(contents) => contents,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => {
+            bail!(
+                "{} not found. Run `spin lock` to create it.",
+                quoted_path(lock_file)
+            )
+        }
+        Err(err) => {
+            return Err(err).with_context(|| format!("Failed to read {}", quoted_path(lock_file)))
+        }
+    };
+    let locked: AppLock = serde_json::from_str(&contents)
+        .with_context(|| format!("Invalid lock file {}", quoted_path(lock_file)))?;
+    let differences = locked.differences(lock);
+    if !differences.is_empty() {
+        bail!(
+            "The application does not match {}:\n{}\nRun `spin lock` to update it.",
+            quoted_path(lock_file),
+            differences
+                .iter()
+                .map(|d| format!("  - {d}"))
+                .collect::<Vec<_>>()
+                .join("\n")
+        );
+    }
+    Ok(())
+}
+
+/// The contents of a lock file.
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub(crate) struct AppLock {
+    lock_version: u32,
+    components: BTreeMap<String, ComponentLock>,
+    variables: BTreeMap<String, VariableLock>,
+    triggers: Vec<TriggerLock>,
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+struct ComponentLock {
+    /// The SHA-256 digest of the component's Wasm, as "sha256:<hex>"
+    digest: String,
+    /// The registry reference the component's Wasm was fetched from, if any
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    reference: Option<String>,
+    /// The component's variables, as templates of application variables
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    variables: BTreeMap<String, String>,
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+struct VariableLock {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    default: Option<String>,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    secret: bool,
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+struct TriggerLock {
+    id: String,
+    #[serde(rename = "type")]
+    trigger_type: String,
+    config: serde_json::Value,
+}
+
+impl AppLock {
+    pub fn new(locked_app: &LockedApp) -> Result<Self> {
+        let components = locked_app
+            .components
+            .iter()
+            .map(|component| {
+                let lock = ComponentLock {
+                    digest: component_digest(component).with_context(|| {
+                        format!("Failed to find digest of component `{}`", component.id)
+                    })?,
+                    reference: component
+                        .metadata
+                        .get(spin_locked_app::locked::SOURCE_REFERENCE_KEY)
+                        .and_then(|reference| reference.as_str())
+                        .map(str::to_owned),
+                    variables: component.config.clone(),
+                };
+                Ok((component.id.clone(), lock))
+            })
+            .collect::<Result<_>>()?;
+        let variables = locked_app
+            .variables
+            .iter()
+            .map(|(name, variable)| {
+                let lock = VariableLock {
+                    default: variable.default.clone(),
+                    secret: variable.secret,
+                };
+                (name.clone(), lock)
+            })
+            .collect();
+        let triggers = locked_app
+            .triggers
+            .iter()
+            .map(|trigger| TriggerLock {
+                id: trigger.id.clone(),
+                trigger_type: trigger.trigger_type.clone(),
+                config: trigger.trigger_config.clone(),
+            })
+            .collect();
+        Ok(Self {
+            lock_version: LOCK_VERSION,
+            components,
+            variables,
+            triggers,
+        })
+    }
+
+    /// Describes how `other` differs from this lock.
+    fn differences(&self, other: &AppLock) -> Vec<String> {
+        let mut differences = vec![];
+        if self.lock_version != other.lock_version {
+            differences.push(format!(
+                "the lock file has version {}, but this Spin uses version {}",
+                self.lock_version, other.lock_version
+            ));
+        }
+        differences.extend(map_differences(
+            "component",
+            &self.components,
+            &other.components,
+        ));
+        differences.extend(map_differences(
+            "variable",
+            &self.variables,
+            &other.variables,
+        ));
+        if self.triggers != other.triggers {
+            differences.push("the triggers have changed".to_owned());
+        }
+        differences
+    }
+}
+
+fn map_differences<T: PartialEq>(
+    kind: &str,
+    locked: &BTreeMap<String, T>,
+    current: &BTreeMap<String, T>,
+) -> Vec<String> {
+    let mut differences = vec![];
+    for (name, value) in current {
+        match locked.get(name) {
+            None => differences.push(format!("{kind} `{name}` has been added")),
+            Some(locked) if locked != value => {
+                differences.push(format!("{kind} `{name}` has changed"))
+            }
+            Some(_) => (),
+        }
+    }
+    for name in locked.keys().filter(|name| !current.contains_key(*name)) {
+        differences.push(format!("{kind} `{name}` has been removed"));
+    }
+    differences
+}
+
+fn component_digest(component: &LockedComponent) -> Result<String> {
+    let content = &component.source.content;
+    if let Some(digest) = &content.digest {
+        return Ok(digest.clone());
+    }
+    let hex = if let Some(inline) = &content.inline {
+        spin_common::sha256::hex_digest_from_bytes(inline)
+    } else if let Some(source) = &content.source {
+        let path = spin_common::url::parse_file_url(source)?;
+        spin_common::sha256::hex_digest_from_file(&path)
+            .with_context(|| format!("Failed to read {}", quoted_path(&path)))?
+    } else {
+        bail!("Component has no source");
+    };
+    Ok(format!("sha256:{hex}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn locked_app(wasm: &[u8], route: &str) -> LockedApp {
+        let json = serde_json::json!({
+            "spin_lock_version": 1,
+            "variables": { "api_key": { "secret": true } },
+            "triggers": [{
+                "id": "trigger-web",
+                "trigger_type": "http",
+                "trigger_config": { "route": route, "component": "web" }
+            }],
+            "components": [{
+                "id": "web",
+                "source": {
+                    "content_type": "application/wasm",
+                    "inline": inline_base64(wasm)
+                },
+                "config": { "api_key": "{{ api_key }}" }
+            }]
+        });
+        serde_json::from_value(json).unwrap()
+    }
+
+    fn inline_base64(bytes: &[u8]) -> String {
+        use base64::Engine;
+        base64::engine::general_purpose::STANDARD_NO_PAD.encode(bytes)
+    }
+
+    #[test]
+    fn changes_are_detected() {
+        let lock = AppLock::new(&locked_app(b"\0asm", "/...")).unwrap();
+        assert_eq!(
+            lock.components["web"].digest,
+            format!(
+                "sha256:{}",
+                spin_common::sha256::hex_digest_from_bytes(b"\0asm")
+            )
+        );
+        assert!(lock
+            .differences(&AppLock::new(&locked_app(b"\0asm", "/...")).unwrap())
+            .is_empty());
+
+        let changed = AppLock::new(&locked_app(b"\0asm\x01", "/api/...")).unwrap();
+        assert_eq!(
+            lock.differences(&changed),
+            [
+                "component `web` has changed".to_owned(),
+                "the triggers have changed".to_owned()
+            ]
+        );
+    }
+}
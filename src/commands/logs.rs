@@ -0,0 +1,449 @@
+use std::{
+    io::{Read, Seek, SeekFrom},
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use anyhow::{bail, Context, Result};
+use chrono::{DateTime, FixedOffset, Local, Utc};
+use clap::Parser;
+use serde::Serialize;
+use spin_common::ui::quoted_path;
+use spin_trigger::runtime_config::DEFAULT_LOGS_DIR;
+use spin_trigger::stdio::{component_log_path, parse_log_line, COMPONENT_LOG_STREAMS};
+use spin_trigger_http::ACCESS_LOG_FILE;
+
+use crate::opts::*;
+use crate::output::{print_json, OutputArgs};
+
+use super::daemon::{is_running, DaemonFiles};
+
+/// How often `spin logs --follow` checks for new output.
+const POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Show the logs of an application.
+#[derive(Parser, Debug)]
+#[clap(about = "Show the logs of an application run with `spin up`")]
+pub struct LogsCommand {
+    /// The application whose logs to show. This may be a manifest (spin.toml)
+    /// file, or a directory containing a spin.toml file. If omitted, it is
+    /// the application in the current directory.
+    #[clap(name = APP_MANIFEST_FILE_OPT, short = 'f', long = "from", alias = "file")]
+    pub app_source: Option<PathBuf>,
+
+    /// The directory the application logged to, if not the default
+    /// (.spin/logs in the application directory).
+    #[clap(long = "log-dir")]
+    pub log_dir: Option<PathBuf>,
+
+    /// Show only the logs of this component. This may be given multiple
+    /// times.
+    #[clap(long = "component", multiple_occurrences = true)]
+    pub components: Vec<String>,
+
+    /// Show only logs written within this long ago, e.g. 30s, 10m, 2h or 1d.
+    #[clap(long = "since", parse(try_from_str = parse_since))]
+    pub since: Option<Duration>,
+
+    /// Keep showing logs as they are written.
+    #[clap(long = "follow", takes_value = false)]
+    pub follow: bool,
+
+    /// Show the output of `spin up --detach` itself rather than the
+    /// application's logs.
+    #[clap(
+        long = "detached",
+        takes_value = false,
+        conflicts_with_all = &["log-dir", "component", "since"],
+    )]
+    pub detached: bool,
+
+    #[clap(flatten)]
+    pub output: OutputArgs,
+}
+
+impl LogsCommand {
+    pub async fn run(self) -> Result<()> {
+        let output = self.output.apply();
+        let files = DaemonFiles::from_option(self.app_source.as_deref())?;
+        if self.detached {
+            return show_detached_output(&files, self.follow).await;
+        }
+
+        let log_dir = self
+            .log_dir
+            .clone()
+            .unwrap_or_else(|| files.dir().join(DEFAULT_LOGS_DIR));
+        if !log_dir.is_dir() {
+            bail!(
+                "No logs found in {}. Logs are written there when the application is run with `spin up`.",
+                quoted_path(&log_dir)
+            );
+        }
+        let since = self
+            .since
+            .and_then(|since| chrono::Duration::from_std(since).ok())
+            .map(|since| Utc::now() - since);
+
+        let mut logs = LogFiles::new(log_dir, self.components.clone());
+        let print = |entries: Vec<LogEntry>| -> Result<()> {
+            for entry in entries {
+                if since.is_some() && entry.time.map(|t| t.with_timezone(&Utc)) < since {
+                    continue;
+                }
+                if output.is_json() {
+                    print_json(&entry.to_output())?;
+                } else {
+                    println!("{entry}");
+                }
+            }
+            Ok(())
+        };
+
+        print(logs.read_new()?)?;
+        while self.follow {
+            tokio::time::sleep(POLL_INTERVAL).await;
+            print(logs.read_new()?)?;
+        }
+        Ok(())
+    }
+}
+
+/// The log files of an application, and how much of each has been read.
+struct LogFiles {
+    dir: PathBuf,
+    /// The components to show, or all of them if empty
+    components: Vec<String>,
+    sources: Vec<LogSource>,
+}
+
+impl LogFiles {
+    fn new(dir: PathBuf, components: Vec<String>) -> Self {
+        Self {
+            dir,
+            components,
+            sources: vec![],
+        }
+    }
+
+    /// Reads the lines written since the last read, in the order they were
+    /// written.
+    fn read_new(&mut self) -> Result<Vec<LogEntry>> {
+        self.add_new_sources()?;
+        let mut entries = vec![];
+        for source in &mut self.sources {
+            source.read_new(&self.components, &mut entries)?;
+        }
+        // A stable sort keeps the lines of each file in order
+        entries.sort_by_key(|entry| entry.time.map(|t| t.with_timezone(&Utc)));
+        Ok(entries)
+    }
+
+    /// Picks up the log files of components which have started logging.
+    fn add_new_sources(&mut self) -> Result<()> {
+        let mut paths = vec![(
+            self.dir.join(ACCESS_LOG_FILE),
+            LogKind::Access,
+            "http".to_owned(),
+            "access",
+        )];
+        if self.components.is_empty() {
+            let entries = std::fs::read_dir(&self.dir)
+                .with_context(|| format!("Failed to read {}", quoted_path(&self.dir)))?;
+            for entry in entries {
+                let path = entry?.path();
+                let Some(name) = path.file_name().and_then(|n| n.to_str()) else {
+                    continue;
+                };
+                for stream in COMPONENT_LOG_STREAMS {
+                    if let Some(component) = name.strip_suffix(&format!("_{stream}.txt")) {
+                        let component = component.to_owned();
+                        paths.push((path.clone(), LogKind::Component, component, stream));
+                    }
+                }
+            }
+        } else {
+            for component in &self.components {
+                for stream in COMPONENT_LOG_STREAMS {
+                    let path = component_log_path(&self.dir, component, stream);
+                    paths.push((path, LogKind::Component, component.clone(), stream));
+                }
+            }
+        }
+
+        for (path, kind, source, stream) in paths {
+            if path.is_file() && !self.sources.iter().any(|s| s.path == path) {
+                self.sources.push(LogSource {
+                    path,
+                    kind,
+                    source,
+                    stream,
+                    position: 0,
+                    partial: vec![],
+                    last_time: None,
+                });
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum LogKind {
+    /// A component's stdout or stderr
+    Component,
+    /// The HTTP access log
+    Access,
+}
+
+struct LogSource {
+    path: PathBuf,
+    kind: LogKind,
+    /// The component, or trigger for the access log, which wrote the file
+    source: String,
+    stream: &'static str,
+    position: u64,
+    /// Any line which has not yet been completely written
+    partial: Vec<u8>,
+    /// The time of the last line read, for lines with no time of their own
+    last_time: Option<DateTime<FixedOffset>>,
+}
+
+impl LogSource {
+    fn read_new(&mut self, components: &[String], entries: &mut Vec<LogEntry>) -> Result<()> {
+        let mut file = std::fs::File::open(&self.path)
+            .with_context(|| format!("Failed to open {}", quoted_path(&self.path)))?;
+        // A file smaller than what has been read was rotated or truncated
+        if file.metadata()?.len() < self.position {
+            self.position = 0;
+            self.partial.clear();
+        }
+        file.seek(SeekFrom::Start(self.position))?;
+        let read = file.read_to_end(&mut self.partial)?;
+        self.position += read as u64;
+
+        let Some(end) = self.partial.iter().rposition(|b| *b == b'\n') else {
+            return Ok(());
+        };
+        let complete: Vec<u8> = self.partial.drain(..=end).collect();
+        for line in String::from_utf8_lossy(&complete).lines() {
+            let (time, text, component) = match self.kind {
+                LogKind::Component => {
+                    let (time, text) = parse_log_line(line);
+                    (time, text, None)
+                }
+                LogKind::Access => {
+                    let (time, component) = parse_access_line(line);
+                    (time, line, component)
+                }
+            };
+            if self.kind == LogKind::Access && !components.is_empty() {
+                // Only the JSON format records the component
+                match &component {
+                    Some(c) if components.contains(c) => {}
+                    _ => continue,
+                }
+            }
+            let time = time.or(self.last_time);
+            self.last_time = time;
+            entries.push(LogEntry {
+                time,
+                source: self.source.clone(),
+                stream: self.stream,
+                line: text.to_owned(),
+            });
+        }
+        Ok(())
+    }
+}
+
+/// Gets the time and component of an access log line, which may be in the
+/// common or JSON format.
+fn parse_access_line(line: &str) -> (Option<DateTime<FixedOffset>>, Option<String>) {
+    if let Ok(serde_json::Value::Object(entry)) = serde_json::from_str(line) {
+        let time = entry
+            .get("time")
+            .and_then(|t| t.as_str())
+            .and_then(|t| DateTime::parse_from_rfc3339(t).ok());
+        let component = entry
+            .get("component")
+            .and_then(|c| c.as_str())
+            .map(str::to_owned);
+        return (time, component);
+    }
+    let time = line
+        .split_once('[')
+        .and_then(|(_, rest)| rest.split_once(']'))
+        .and_then(|(time, _)| DateTime::parse_from_str(time, "%d/%b/%Y:%H:%M:%S %z").ok());
+    (time, None)
+}
+
+struct LogEntry {
+    time: Option<DateTime<FixedOffset>>,
+    source: String,
+    stream: &'static str,
+    line: String,
+}
+
+/// A line of `spin logs --output json`.
+#[derive(Serialize)]
+struct LogEntryOutput<'a> {
+    time: Option<String>,
+    source: &'a str,
+    stream: &'a str,
+    line: &'a str,
+}
+
+impl LogEntry {
+    fn to_output(&self) -> LogEntryOutput<'_> {
+        LogEntryOutput {
+            time: self.time.map(|t| t.to_rfc3339()),
+            source: &self.source,
+            stream: self.stream,
+            line: &self.line,
+        }
+    }
+}
+
+impl std::fmt::Display for LogEntry {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let time = match self.time {
+            Some(time) => time
+                .with_timezone(&Local)
+                .format("%Y-%m-%d %H:%M:%S%.3f")
+                .to_string(),
+            None => format!("{:23}", "-"),
+        };
+        write!(f, "{time} [{} {}] {}", self.source, self.stream, self.line)
+    }
+}
+
+/// Parses a duration such as 30s, 10m, 2h or 1d.
+fn parse_since(since: &str) -> Result<Duration, String> {
+    let invalid = || format!("invalid duration '{since}': expected e.g. 30s, 10m, 2h or 1d");
+    let unit_start = since
+        .find(|c: char| !c.is_ascii_digit())
+        .ok_or_else(invalid)?;
+    let (count, unit) = since.split_at(unit_start);
+    let count: u64 = count.parse().map_err(|_| invalid())?;
+    let unit_secs = match unit {
+        "s" => 1,
+        "m" => 60,
+        "h" => 60 * 60,
+        "d" => 24 * 60 * 60,
+        _ => return Err(invalid()),
+    };
+    Ok(Duration::from_secs(count * unit_secs))
+}
+
+/// Shows the output of a detached `spin up`, which is not timestamped.
+async fn show_detached_output(files: &DaemonFiles, follow: bool) -> Result<()> {
+    let log_file = files.log_file();
+    let mut log = match std::fs::File::open(&log_file) {
+        Ok(log) => log,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => {
+            bail!("No application has been run in the background here. Run `spin up --detach` to start one.")
+        }
+        Err(err) => {
+            return Err(err).with_context(|| format!("Failed to open {}", quoted_path(&log_file)))
+        }
+    };
+
+    let mut stdout = std::io::stdout();
+    std::io::copy(&mut log, &mut stdout)?;
+    if !follow {
+        return Ok(());
+    }
+
+    let Some(pid) = files.running_pid()? else {
+        return Ok(());
+    };
+    loop {
+        tokio::time::sleep(POLL_INTERVAL).await;
+        // A restarted application starts a new log
+        let position = log.stream_position()?;
+        if file_len(&log_file) < position {
+            log.seek(SeekFrom::Start(0))?;
+        }
+        std::io::copy(&mut log, &mut stdout)?;
+        if !is_running(pid) {
+            return Ok(());
+        }
+    }
+}
+
+fn file_len(path: &Path) -> u64 {
+    std::fs::metadata(path).map(|m| m.len()).unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn since_durations_are_parsed() {
+        assert_eq!(parse_since("30s"), Ok(Duration::from_secs(30)));
+        assert_eq!(parse_since("10m"), Ok(Duration::from_secs(600)));
+        assert_eq!(parse_since("2h"), Ok(Duration::from_secs(7200)));
+        assert_eq!(parse_since("1d"), Ok(Duration::from_secs(86400)));
+        assert!(parse_since("10").is_err());
+        assert!(parse_since("m").is_err());
+        assert!(parse_since("1w").is_err());
+    }
+
+    #[test]
+    fn logs_are_merged_chronologically() {
+        let dir = tempfile::tempdir().unwrap();
+        let write = |name: &str, content: &str| std::fs::write(dir.path().join(name), content);
+        write(
+            "api_stdout.txt",
+            "2024-03-01T12:00:01.000Z handling\ncontinued\n2024-03-01T12:00:03.000Z done\n",
+        )
+        .unwrap();
+        write(
+            "web_stderr.txt",
+            "2024-03-01T12:00:02.000Z oops\n2024-03-01T12:00:04.000Z part",
+        )
+        .unwrap();
+        write(
+            ACCESS_LOG_FILE,
+            "{\"time\":\"2024-03-01T12:00:05.000+00:00\",\"component\":\"api\"}\n\
+             127.0.0.1 - - [01/Mar/2024:12:00:06 +0000] \"GET / HTTP/1.1\" 200 5\n",
+        )
+        .unwrap();
+
+        let lines = |entries: Vec<LogEntry>| -> Vec<String> {
+            entries
+                .iter()
+                .map(|e| format!("{} {}", e.source, e.line))
+                .collect()
+        };
+
+        let mut logs = LogFiles::new(dir.path().to_owned(), vec![]);
+        let entries = logs.read_new().unwrap();
+        assert_eq!(
+            lines(entries),
+            [
+                "api handling",
+                "api continued",
+                "web oops",
+                "api done",
+                "http {\"time\":\"2024-03-01T12:00:05.000+00:00\",\"component\":\"api\"}",
+                "http 127.0.0.1 - - [01/Mar/2024:12:00:06 +0000] \"GET / HTTP/1.1\" 200 5",
+            ]
+        );
+
+        // Lines are read once they are complete
+        write(
+            "web_stderr.txt",
+            "2024-03-01T12:00:02.000Z oops\n2024-03-01T12:00:04.000Z partial\n",
+        )
+        .unwrap();
+        assert_eq!(lines(logs.read_new().unwrap()), ["web partial"]);
+
+        let mut logs = LogFiles::new(dir.path().to_owned(), vec!["api".to_owned()]);
+        let entries = logs.read_new().unwrap();
+        assert_eq!(entries.len(), 4);
+        assert!(entries.iter().all(|e| e.line != "oops"));
+    }
+}
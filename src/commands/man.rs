@@ -0,0 +1,92 @@
+use std::path::PathBuf;
+
+use anyhow::{Context, Result};
+use clap::{Command, Parser};
+use spin_common::ui::quoted_path;
+
+/// Generate man pages for Spin.
+#[derive(Parser, Debug)]
+#[clap(about = "Generate man pages for Spin")]
+pub struct ManCommand {
+    /// The directory to write a page for each command to, named e.g. spin.1
+    /// and spin-up.1. If omitted, the page for `spin` is written to standard
+    /// output.
+    #[clap(long = "dir")]
+    pub dir: Option<PathBuf>,
+}
+
+impl ManCommand {
+    pub async fn run(self, app: Command<'_>) -> Result<()> {
+        let Some(dir) = &self.dir else {
+            return render(app, &mut std::io::stdout());
+        };
+
+        std::fs::create_dir_all(dir)
+            .with_context(|| format!("Failed to create {}", quoted_path(dir)))?;
+        let pages = man_pages(app);
+        let count = pages.len();
+        for (name, page) in pages {
+            let path = dir.join(format!("{name}.1"));
+            let mut file = std::fs::File::create(&path)
+                .with_context(|| format!("Failed to create {}", quoted_path(&path)))?;
+            render(page, &mut file)
+                .with_context(|| format!("Failed to write {}", quoted_path(&path)))?;
+        }
+        terminal::step!("Wrote", "{count} man pages to {}", quoted_path(dir));
+        Ok(())
+    }
+}
+
+fn render(page: Command<'_>, out: &mut impl std::io::Write) -> Result<()> {
+    clap_mangen::Man::new(page).render(out)?;
+    Ok(())
+}
+
+/// The pages for a command and its visible subcommands, by page name. Plugin
+/// subcommands, which are marked in help with a trailing `*`, are left to
+/// the plugins to document.
+fn man_pages(app: Command<'_>) -> Vec<(String, Command<'_>)> {
+    let mut pages = vec![];
+    add_pages(app.get_name().to_owned(), app, &mut pages);
+    pages
+}
+
+fn add_pages<'help>(
+    name: String,
+    command: Command<'help>,
+    pages: &mut Vec<(String, Command<'help>)>,
+) {
+    for subcommand in command.get_subcommands() {
+        if subcommand.is_hide_set() || subcommand.get_name().ends_with('*') {
+            continue;
+        }
+        let subcommand_name = format!("{name}-{}", subcommand.get_name());
+        let subcommand = subcommand.clone().name(&subcommand_name);
+        add_pages(subcommand_name, subcommand, pages);
+    }
+    pages.push((name, command));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pages_are_written_for_visible_subcommands() {
+        let app = Command::new("spin")
+            .subcommand(Command::new("up"))
+            .subcommand(Command::new("plugins").subcommand(Command::new("install")))
+            .subcommand(Command::new("trigger").hide(true))
+            .subcommand(Command::new("cloud*"));
+        let mut names: Vec<_> = man_pages(app).into_iter().map(|(name, _)| name).collect();
+        names.sort();
+        assert_eq!(
+            names,
+            ["spin", "spin-plugins", "spin-plugins-install", "spin-up"]
+        );
+
+        let mut page = vec![];
+        render(Command::new("spin").about("The Spin CLI"), &mut page).unwrap();
+        assert!(String::from_utf8(page).unwrap().contains("The Spin CLI"));
+    }
+}
@@ -0,0 +1,94 @@
+use std::path::PathBuf;
+
+use anyhow::{Context, Result};
+use clap::{Parser, Subcommand};
+use spin_common::ui::quoted_path;
+
+use crate::opts::{APP_MANIFEST_FILE_OPT, DEFAULT_MANIFEST_FILE};
+use crate::output::{print_json, OutputArgs};
+
+/// Commands for working with application manifests.
+#[derive(Subcommand, Debug)]
+pub enum ManifestCommands {
+    /// Print the JSON Schema of the application manifest (spin.toml), for
+    /// editors to complete and validate manifests with.
+    Schema(SchemaCommand),
+    /// List the deprecated settings an application manifest uses, and how to
+    /// replace them.
+    Deprecations(DeprecationsCommand),
+}
+
+impl ManifestCommands {
+    pub async fn run(self) -> Result<()> {
+        match self {
+            ManifestCommands::Schema(cmd) => cmd.run().await,
+            ManifestCommands::Deprecations(cmd) => cmd.run().await,
+        }
+    }
+}
+
+#[derive(Parser, Debug)]
+pub struct SchemaCommand {
+    /// Write the schema to this file instead of printing it.
+    #[clap(long = "to", value_name = "FILE")]
+    pub to: Option<PathBuf>,
+}
+
+impl SchemaCommand {
+    pub async fn run(self) -> Result<()> {
+        let schema = serde_json::to_string_pretty(&spin_manifest::json_schema())?;
+        match &self.to {
+            Some(path) => std::fs::write(path, schema + "\n")
+                .with_context(|| format!("Failed to write {}", quoted_path(path))),
+            None => {
+                println!("{schema}");
+                Ok(())
+            }
+        }
+    }
+}
+
+#[derive(Parser, Debug)]
+pub struct DeprecationsCommand {
+    /// The application to check. This may be a manifest (spin.toml) file, or a
+    /// directory containing a spin.toml file.
+    /// If omitted, it defaults to "spin.toml".
+    #[clap(
+        name = APP_MANIFEST_FILE_OPT,
+        short = 'f',
+        long = "from",
+        alias = "file",
+        default_value = DEFAULT_MANIFEST_FILE
+    )]
+    pub app_source: PathBuf,
+
+    /// The environment whose overlay, e.g. `spin.prod.toml` for `prod`, to
+    /// check along with the manifest.
+    #[clap(long = "environment", value_name = "NAME")]
+    pub environment: Option<String>,
+
+    #[clap(flatten)]
+    pub output: OutputArgs,
+}
+
+impl DeprecationsCommand {
+    pub async fn run(self) -> Result<()> {
+        let output = self.output.apply();
+        let manifest_file = spin_common::paths::resolve_manifest_file_path(&self.app_source)?;
+        let deprecations =
+            spin_loader::manifest_deprecations(&manifest_file, self.environment.as_deref())?;
+        if output.is_json() {
+            return print_json(&deprecations);
+        }
+        if deprecations.is_empty() {
+            println!(
+                "{} uses no deprecated settings",
+                quoted_path(&manifest_file)
+            );
+        }
+        for deprecation in &deprecations {
+            println!("{}: {}", deprecation.key, deprecation.message);
+        }
+        Ok(())
+    }
+}
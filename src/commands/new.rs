@@ -66,6 +66,12 @@ pub struct TemplateNewCommandCore {
     /// An optional argument that allows to skip creating .gitignore
     #[clap(long = "no-vcs", takes_value = false)]
     pub no_vcs: bool,
+
+    /// Fail instead of prompting for anything not given on the command line,
+    /// such as the template, name or parameter values. Use this to run
+    /// non-interactively, for example in CI.
+    #[clap(long = "no-prompt", takes_value = false)]
+    pub no_prompt: bool,
 }
 
 /// Scaffold a new application based on a template.
@@ -88,11 +94,19 @@ pub struct AddCommand {
         long = "file",
     )]
     pub app: Option<PathBuf>,
+
+    /// The route for the new component's HTTP trigger, in place of the
+    /// template's. If the route is already in use, you will be prompted for
+    /// another (or, with --no-prompt, the command fails).
+    #[clap(long = "route")]
+    pub route: Option<String>,
 }
 
 impl NewCommand {
     pub async fn run(&self) -> Result<()> {
-        self.options.run(TemplateVariantInfo::NewApplication).await
+        self.options
+            .run(TemplateVariantInfo::NewApplication, None)
+            .await
     }
 }
 
@@ -118,13 +132,16 @@ impl AddCommand {
             );
         }
         self.options
-            .run(TemplateVariantInfo::AddComponent { manifest_path })
+            .run(
+                TemplateVariantInfo::AddComponent { manifest_path },
+                self.route.clone(),
+            )
             .await
     }
 }
 
 impl TemplateNewCommandCore {
-    pub async fn run(&self, variant: TemplateVariantInfo) -> Result<()> {
+    pub async fn run(&self, variant: TemplateVariantInfo, route: Option<String>) -> Result<()> {
         let template_manager = TemplateManager::try_default()
             .context("Failed to construct template directory path")?;
 
@@ -136,6 +153,9 @@ impl TemplateNewCommandCore {
                 .with_context(|| format!("Error retrieving template {}", template_id))?
             {
                 Some(template) => template,
+                None if self.no_prompt => bail!(
+                    "Template '{template_id}' is not installed. Run `spin templates list` to see the installed templates."
+                ),
                 None => match prompt_template(&template_manager, &variant, &[template_id.clone()])
                     .await?
                 {
@@ -143,6 +163,9 @@ impl TemplateNewCommandCore {
                     None => return Ok(()),
                 },
             },
+            None if self.no_prompt => {
+                bail!("No template given. Use `--template` to choose one; run `spin templates list` to see the installed templates.")
+            }
             None => match prompt_template(&template_manager, &variant, &self.tags).await? {
                 Some(template) => template,
                 None => return Ok(()),
@@ -150,6 +173,13 @@ impl TemplateNewCommandCore {
         };
 
         if !template.supports_variant(&variant) {
+            if self.no_prompt {
+                bail!(
+                    "Template {} doesn't support the '{}' operation",
+                    template.id(),
+                    variant.description()
+                );
+            }
             println!(
                 "Template {} doesn't support the '{}' operation",
                 template.id(),
@@ -160,6 +190,9 @@ impl TemplateNewCommandCore {
 
         let name = match &name {
             Some(name) => name.to_owned(),
+            None if self.no_prompt => {
+                bail!("No name given for the new {}", variant.prompt_noun())
+            }
             None => prompt_name(&variant).await?,
         };
 
@@ -184,9 +217,15 @@ impl TemplateNewCommandCore {
             values,
             accept_defaults: self.accept_defaults,
             no_vcs: self.no_vcs,
+            route,
         };
 
-        template.run(options).interactive().await
+        let run = template.run(options);
+        if self.no_prompt {
+            run.silent().await
+        } else {
+            run.interactive().await
+        }
     }
 
     // Try to guess if the user is using v1 or v2 syntax, and fix things up so
@@ -6,6 +6,7 @@ use clap::{Parser, Subcommand};
 use semver::Version;
 use spin_plugins::{
     error::Error,
+    lockfile::Verification,
     lookup::{fetch_plugins_repo, plugins_repo_url, PluginLookup},
     manager::{self, InstallAction, ManifestLocation, PluginManager},
     manifest::{PluginManifest, PluginPackage},
@@ -40,6 +41,10 @@ pub enum PluginCommands {
 
     /// Fetch the latest Spin plugins from the spin-plugins repository.
     Update,
+
+    /// Check installed plugin binaries against the checksums recorded when
+    /// they were installed.
+    Verify(Verify),
 }
 
 impl PluginCommands {
@@ -51,6 +56,7 @@ impl PluginCommands {
             PluginCommands::Uninstall(cmd) => cmd.run().await,
             PluginCommands::Upgrade(cmd) => cmd.run().await,
             PluginCommands::Update => update().await,
+            PluginCommands::Verify(cmd) => cmd.run(),
         }
     }
 }
@@ -58,7 +64,8 @@ impl PluginCommands {
 /// Install plugins from remote source
 #[derive(Parser, Debug)]
 pub struct Install {
-    /// Name of Spin plugin.
+    /// Name of Spin plugin, optionally with an exact version to install
+    /// (e.g. js2wasm@0.6.1).
     #[clap(
         name = PLUGIN_NAME_OPT,
         conflicts_with = PLUGIN_REMOTE_PLUGIN_MANIFEST_OPT,
@@ -105,6 +112,13 @@ pub struct Install {
         requires(PLUGIN_NAME_OPT)
     )]
     pub version: Option<Version>,
+
+    /// Install from a plugin package (.tar.gz) which has already been
+    /// downloaded, instead of the package URL in the manifest. The package
+    /// must match the manifest's checksum. Use with --file to install on a
+    /// machine without network access.
+    #[clap(long = "tarball", value_name = "PATH")]
+    pub tarball: Option<PathBuf>,
 }
 
 impl Install {
@@ -112,7 +126,10 @@ impl Install {
         let manifest_location = match (&self.local_manifest_src, &self.remote_manifest_src, &self.name) {
             (Some(path), None, None) => ManifestLocation::Local(path.to_path_buf()),
             (None, Some(url), None) => ManifestLocation::Remote(url.clone()),
-            (None, None, Some(name)) => ManifestLocation::PluginsRepository(PluginLookup::new(name, self.version.clone())),
+            (None, None, Some(name)) => {
+                let (name, version) = parse_pinned_name(name, self.version.as_ref())?;
+                ManifestLocation::PluginsRepository(PluginLookup::new(&name, version))
+            }
             _ => return Err(anyhow::anyhow!("For plugin lookup, must provide exactly one of: plugin name, url to manifest, local path to manifest")),
         };
         let manager = PluginManager::try_default()?;
@@ -125,15 +142,101 @@ impl Install {
                 SPIN_VERSION,
             )
             .await?;
-        try_install(
-            &manifest,
-            &manager,
-            self.yes_to_all,
-            self.override_compatibility_check,
-            downgrade,
-            &manifest_location,
-        )
-        .await?;
+        match &self.tarball {
+            Some(tarball) => {
+                try_install_tarball(
+                    &manifest,
+                    &manager,
+                    tarball,
+                    self.override_compatibility_check,
+                    &manifest_location,
+                )?;
+            }
+            None => {
+                try_install(
+                    &manifest,
+                    &manager,
+                    self.yes_to_all,
+                    self.override_compatibility_check,
+                    downgrade,
+                    &manifest_location,
+                )
+                .await?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Splits a `name@version` plugin reference. A version given as part of the
+/// name may not also be given with --version.
+fn parse_pinned_name(name: &str, version: Option<&Version>) -> Result<(String, Option<Version>)> {
+    match name.split_once('@') {
+        None => Ok((name.to_owned(), version.cloned())),
+        Some(_) if version.is_some() => {
+            anyhow::bail!(
+                "The version of plugin '{name}' can't be given both with '@' and --version"
+            )
+        }
+        Some((name, version)) => {
+            let version = Version::parse(version)
+                .with_context(|| format!("Invalid version '{version}' for plugin '{name}'"))?;
+            Ok((name.to_owned(), Some(version)))
+        }
+    }
+}
+
+/// Checks installed plugins against the lockfile.
+#[derive(Parser, Debug)]
+pub struct Verify {
+    /// Name of the plugin to check. If omitted, all installed plugins are checked.
+    pub name: Option<String>,
+}
+
+impl Verify {
+    pub fn run(self) -> Result<()> {
+        let manager = PluginManager::try_default()?;
+        let names = match self.name {
+            Some(name) => vec![name],
+            None => {
+                let mut names: Vec<_> = manager
+                    .store()
+                    .installed_manifests()?
+                    .iter()
+                    .map(|m| m.name())
+                    .collect();
+                names.sort();
+                names
+            }
+        };
+        if names.is_empty() {
+            println!("No plugins installed");
+            return Ok(());
+        }
+
+        let mut failed = vec![];
+        for name in names {
+            match manager.verify(&name)? {
+                Verification::Verified => println!("{name}: verified"),
+                Verification::NotRecorded => {
+                    println!("{name}: no checksum recorded; reinstall the plugin to record one")
+                }
+                Verification::Missing => {
+                    println!("{name}: binary is missing");
+                    failed.push(name);
+                }
+                Verification::Modified { expected, actual } => {
+                    println!("{name}: binary has been modified (expected sha256 {expected}, found {actual})");
+                    failed.push(name);
+                }
+            }
+        }
+        if !failed.is_empty() {
+            anyhow::bail!(
+                "Verification failed for {}. Reinstall with `spin plugins install`.",
+                failed.join(", ")
+            );
+        }
         Ok(())
     }
 }
@@ -635,6 +738,21 @@ pub(crate) async fn update_silent() -> Result<()> {
     Ok(())
 }
 
+fn try_install_tarball(
+    manifest: &PluginManifest,
+    manager: &PluginManager,
+    tarball: &Path,
+    override_compatibility_check: bool,
+    source: &ManifestLocation,
+) -> Result<()> {
+    // Reinstalling is allowed, as the package may differ from the installed one
+    manager.check_manifest(manifest, SPIN_VERSION, override_compatibility_check, false)?;
+    let package = manager::get_package(manifest)?;
+    let installed = manager.install_tarball(manifest, package, tarball, source)?;
+    println!("Plugin '{installed}' was installed successfully!");
+    Ok(())
+}
+
 fn continue_to_install(
     manifest: &PluginManifest,
     package: &PluginPackage,
@@ -1,7 +1,9 @@
 use crate::opts::*;
+use crate::output::{print_json, OutputArgs};
 use anyhow::{Context, Result};
 use clap::{Parser, Subcommand};
 use indicatif::{ProgressBar, ProgressStyle};
+use serde::Serialize;
 use spin_common::arg_parser::parse_kv;
 use spin_oci::Client;
 use std::{io::Read, path::PathBuf, time::Duration};
@@ -67,10 +69,14 @@ pub struct Push {
     /// Any existing value will be overwritten. Can be used multiple times.
     #[clap(long = "annotation", parse(try_from_str = parse_kv))]
     pub annotations: Vec<(String, String)>,
+
+    #[clap(flatten)]
+    pub output: OutputArgs,
 }
 
 impl Push {
     pub async fn run(self) -> Result<()> {
+        let output = self.output.apply();
         let app_file = spin_common::paths::resolve_manifest_file_path(&self.app_source)?;
         if self.build {
             spin_build::build(&app_file, &[]).await?;
@@ -87,6 +93,13 @@ impl Push {
         let _spinner = create_dotted_spinner(2000, "Pushing app to the Registry".to_owned());
 
         let digest = client.push(&app_file, &self.reference, annotations).await?;
+        if output.is_json() {
+            print_json(&RegistryOutput {
+                reference: &self.reference,
+                digest: digest.as_deref(),
+            })?;
+            return Ok(());
+        }
         match digest {
             Some(digest) => println!("Pushed with digest {digest}"),
             None => println!("Pushed; the registry did not return the digest"),
@@ -115,16 +128,26 @@ pub struct Pull {
     /// Cache directory for downloaded registry data.
     #[clap(long)]
     pub cache_dir: Option<PathBuf>,
+
+    #[clap(flatten)]
+    pub output: OutputArgs,
 }
 
 impl Pull {
     /// Pull a Spin application from an OCI registry
     pub async fn run(self) -> Result<()> {
+        let output = self.output.apply();
         let mut client = spin_oci::Client::new(self.insecure, self.cache_dir.clone()).await?;
 
         let _spinner = create_dotted_spinner(2000, "Pulling app from the Registry".to_owned());
 
-        client.pull(&self.reference).await?;
+        let digest = client.pull(&self.reference).await?;
+        if output.is_json() {
+            return print_json(&RegistryOutput {
+                reference: &self.reference,
+                digest: Some(&digest),
+            });
+        }
         println!("Successfully pulled the app from the registry");
         Ok(())
     }
@@ -199,6 +222,14 @@ impl Login {
     }
 }
 
+/// The result of `spin registry push/pull --output json`.
+#[derive(Serialize)]
+struct RegistryOutput<'a> {
+    reference: &'a str,
+    /// The digest of the app's OCI manifest, if known
+    digest: Option<&'a str>,
+}
+
 fn create_dotted_spinner(interval: u64, message: String) -> ProgressBar {
     let spinner = ProgressBar::new_spinner();
     spinner.enable_steady_tick(Duration::from_millis(interval));
@@ -0,0 +1,586 @@
+use std::{
+    fmt::Write,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{bail, Context, Result};
+use clap::{Parser, Subcommand};
+use spin_common::ui::quoted_path;
+use spin_manifest::schema::v2::AppManifest;
+use spin_trigger::cli::env;
+
+use crate::build_info::SPIN_VERSION;
+use crate::opts::*;
+
+/// The directory of the application in its container image.
+const CONTAINER_APP_DIR: &str = "/app";
+/// The state directory of the application in its container, in which
+/// key-value stores and SQLite databases are kept by default.
+const CONTAINER_STATE_DIR: &str = "/app/.spin";
+/// The prefix of environment variables which set application variables.
+const VARIABLE_ENV_PREFIX: &str = "SPIN_VARIABLE_";
+
+/// Commands for generating deployment files for an application.
+#[derive(Subcommand, Debug)]
+pub enum ScaffoldCommands {
+    /// Write a Dockerfile which runs the application with Spin.
+    Docker(ScaffoldDocker),
+    /// Write Kubernetes manifests which run the application.
+    #[clap(alias = "k8s")]
+    Kubernetes(ScaffoldKubernetes),
+}
+
+impl ScaffoldCommands {
+    pub async fn run(self) -> Result<()> {
+        match self {
+            ScaffoldCommands::Docker(cmd) => cmd.run().await,
+            ScaffoldCommands::Kubernetes(cmd) => cmd.run().await,
+        }
+    }
+}
+
+#[derive(Parser, Debug)]
+pub struct ScaffoldDocker {
+    /// The application to scaffold. This may be a manifest (spin.toml) file,
+    /// or a directory containing a spin.toml file.
+    /// If omitted, it defaults to "spin.toml".
+    #[clap(
+        name = APP_MANIFEST_FILE_OPT,
+        short = 'f',
+        long = "from",
+        alias = "file",
+        default_value = DEFAULT_MANIFEST_FILE
+    )]
+    pub app_source: PathBuf,
+
+    /// Overwrite files which already exist.
+    #[clap(long = "force", takes_value = false)]
+    pub force: bool,
+}
+
+impl ScaffoldDocker {
+    pub async fn run(self) -> Result<()> {
+        let (manifest_file, deployment) = AppDeployment::load(&self.app_source)?;
+        let app_dir = manifest_file.parent().unwrap_or(Path::new("."));
+        let manifest_name = manifest_file
+            .file_name()
+            .context("Manifest path has no file name")?
+            .to_string_lossy();
+        write_files(
+            app_dir,
+            &[
+                ("Dockerfile", deployment.dockerfile(&manifest_name)),
+                (".dockerignore", DOCKERIGNORE.to_owned()),
+            ],
+            self.force,
+        )?;
+        println!(
+            "Build the image with `docker build -t {} {}`.",
+            deployment.name,
+            app_dir.display()
+        );
+        Ok(())
+    }
+}
+
+#[derive(Parser, Debug)]
+pub struct ScaffoldKubernetes {
+    /// The application to scaffold. This may be a manifest (spin.toml) file,
+    /// or a directory containing a spin.toml file.
+    /// If omitted, it defaults to "spin.toml".
+    #[clap(
+        name = APP_MANIFEST_FILE_OPT,
+        short = 'f',
+        long = "from",
+        alias = "file",
+        default_value = DEFAULT_MANIFEST_FILE
+    )]
+    pub app_source: PathBuf,
+
+    /// The image to deploy. For a Deployment, this is a container image
+    /// built from the Dockerfile written by `spin scaffold docker`; for a
+    /// SpinApp, it is a registry reference pushed with `spin registry push`.
+    /// If omitted, a placeholder based on the application name is used.
+    #[clap(long = "image")]
+    pub image: Option<String>,
+
+    /// Write a SpinApp resource for the Spin Operator, instead of a
+    /// Deployment and Service.
+    #[clap(long = "spinapp", takes_value = false)]
+    pub spinapp: bool,
+
+    /// The number of replicas to run.
+    #[clap(long = "replicas", default_value = "1")]
+    pub replicas: u32,
+
+    /// The directory to write the manifests to. If omitted, it is the
+    /// "deploy" directory of the application.
+    #[clap(long = "dir")]
+    pub dir: Option<PathBuf>,
+
+    /// Overwrite files which already exist.
+    #[clap(long = "force", takes_value = false)]
+    pub force: bool,
+}
+
+impl ScaffoldKubernetes {
+    pub async fn run(self) -> Result<()> {
+        let (manifest_file, deployment) = AppDeployment::load(&self.app_source)?;
+        let dir = match self.dir {
+            Some(dir) => dir,
+            None => manifest_file
+                .parent()
+                .unwrap_or(Path::new("."))
+                .join("deploy"),
+        };
+        let image = self
+            .image
+            .unwrap_or_else(|| deployment.placeholder_image(self.spinapp));
+
+        let mut files = vec![];
+        if self.spinapp {
+            files.push(("spinapp.yaml", deployment.spinapp(&image, self.replicas)));
+        } else {
+            files.push((
+                "deployment.yaml",
+                deployment.deployment(&image, self.replicas),
+            ));
+            if !deployment.ports.is_empty() {
+                files.push(("service.yaml", deployment.service()));
+            }
+        }
+        if deployment.variables.iter().any(|v| v.secret) {
+            files.push(("secret.yaml", deployment.secret()));
+        }
+        write_files(&dir, &files, self.force)?;
+        println!(
+            "Apply the manifests with `kubectl apply -f {}`.",
+            dir.display()
+        );
+        Ok(())
+    }
+}
+
+/// What running an application needs from its container or pod.
+#[derive(Debug)]
+struct AppDeployment {
+    /// The application name, as a DNS label
+    name: String,
+    version: String,
+    ports: Vec<ContainerPort>,
+    /// The application variables which have no default
+    variables: Vec<RequiredVariable>,
+    /// Whether the application keeps state in its state directory
+    has_state: bool,
+    /// The most memory the components may use at once, if every component
+    /// declares its memory and concurrency limits in `resources`
+    peak_memory_mb: Option<u64>,
+}
+
+/// A port on which a trigger listens.
+#[derive(Debug, PartialEq)]
+struct ContainerPort {
+    name: &'static str,
+    port: u16,
+    /// The environment variable which sets the trigger's listen address
+    listen_env: &'static str,
+}
+
+#[derive(Debug, PartialEq)]
+struct RequiredVariable {
+    name: String,
+    secret: bool,
+}
+
+impl RequiredVariable {
+    /// The environment variable which sets the variable.
+    fn env(&self) -> String {
+        format!("{VARIABLE_ENV_PREFIX}{}", self.name.to_uppercase())
+    }
+}
+
+impl AppDeployment {
+    fn load(app_source: &Path) -> Result<(PathBuf, Self)> {
+        let manifest_file = spin_common::paths::resolve_manifest_file_path(app_source)?;
+        let mut manifest = spin_manifest::manifest_from_file(&manifest_file)?;
+        spin_manifest::normalize::normalize_manifest(&mut manifest);
+        Ok((manifest_file, Self::new(&manifest)))
+    }
+
+    fn new(manifest: &AppManifest) -> Self {
+        let ports = manifest
+            .triggers
+            .keys()
+            .filter_map(|trigger_type| match trigger_type.as_str() {
+                "http" => Some(ContainerPort {
+                    name: "http",
+                    port: 80,
+                    listen_env: env::HTTP_LISTEN,
+                }),
+                "grpc" => Some(ContainerPort {
+                    name: "grpc",
+                    port: 50051,
+                    listen_env: env::GRPC_LISTEN,
+                }),
+                _ => None,
+            })
+            .collect();
+        let variables = manifest
+            .variables
+            .iter()
+            .filter(|(_, variable)| variable.required)
+            .map(|(name, variable)| RequiredVariable {
+                name: name.to_string(),
+                secret: variable.secret,
+            })
+            .collect();
+        let has_state = manifest
+            .components
+            .values()
+            .any(|c| !c.key_value_stores.is_empty() || !c.sqlite_databases.is_empty());
+        let peak_memory_mb = manifest
+            .components
+            .values()
+            .map(|c| {
+                let resources = c.resources.as_ref()?;
+                let max_concurrent = u64::try_from(resources.max_concurrent?).ok()?;
+                resources.max_memory_mb?.checked_mul(max_concurrent)
+            })
+            .sum::<Option<u64>>()
+            .filter(|mb| *mb > 0);
+        let version = match manifest.application.version.as_str() {
+            "" => "latest".to_owned(),
+            version => version.to_owned(),
+        };
+        Self {
+            name: dns_label(&manifest.application.name),
+            version,
+            ports,
+            variables,
+            has_state,
+            peak_memory_mb,
+        }
+    }
+
+    /// A comment on the memory the application may need, for sizing its
+    /// container.
+    fn capacity_comment(&self, indent: &str) -> Option<String> {
+        let peak_memory_mb = self.peak_memory_mb?;
+        Some(format!(
+            "{indent}# The components' declared resources allow them to use up to {peak_memory_mb} MiB of memory at once, plus Spin's own.\n"
+        ))
+    }
+
+    fn placeholder_image(&self, spinapp: bool) -> String {
+        if spinapp {
+            format!("registry.example.com/{}:{}", self.name, self.version)
+        } else {
+            format!("{}:{}", self.name, self.version)
+        }
+    }
+
+    fn secret_name(&self) -> String {
+        format!("{}-variables", self.name)
+    }
+
+    fn dockerfile(&self, manifest_name: &str) -> String {
+        let mut out = String::new();
+        _ = writeln!(out, "# Generated by `spin scaffold docker`.");
+        _ = writeln!(out, "FROM debian:bookworm-slim");
+        _ = writeln!(out, "ARG SPIN_VERSION=v{SPIN_VERSION}");
+        _ = writeln!(out, "RUN apt-get update \\");
+        _ = writeln!(
+            out,
+            "    && apt-get install -y --no-install-recommends ca-certificates curl \\"
+        );
+        _ = writeln!(out, "    && curl -fsSL https://developer.fermyon.com/downloads/install.sh | bash -s -- -v ${{SPIN_VERSION}} \\");
+        _ = writeln!(out, "    && mv spin /usr/local/bin/spin \\");
+        _ = writeln!(out, "    && rm -rf /var/lib/apt/lists/*");
+        _ = writeln!(out);
+        _ = writeln!(out, "WORKDIR {CONTAINER_APP_DIR}");
+        _ = writeln!(out, "COPY . .");
+        for port in &self.ports {
+            _ = writeln!(out);
+            _ = writeln!(out, "ENV {}=0.0.0.0:{}", port.listen_env, port.port);
+            _ = writeln!(out, "EXPOSE {}", port.port);
+        }
+        if !self.variables.is_empty() {
+            _ = writeln!(out);
+            _ = writeln!(out, "# Required variables, to set with `docker run --env`:");
+            for variable in &self.variables {
+                _ = writeln!(out, "#   {}", variable.env());
+            }
+        }
+        if self.has_state {
+            _ = writeln!(out);
+            _ = writeln!(out, "# Key-value stores and SQLite databases");
+            _ = writeln!(out, "VOLUME {CONTAINER_STATE_DIR}");
+        }
+        _ = writeln!(out);
+        _ = writeln!(
+            out,
+            "CMD [\"spin\", \"up\", \"--from\", {}]",
+            quoted(manifest_name)
+        );
+        out
+    }
+
+    fn deployment(&self, image: &str, replicas: u32) -> String {
+        let name = quoted(&self.name);
+        let mut out = String::new();
+        _ = writeln!(out, "# Generated by `spin scaffold kubernetes`.");
+        _ = writeln!(out, "apiVersion: apps/v1");
+        _ = writeln!(out, "kind: Deployment");
+        _ = writeln!(out, "metadata:");
+        _ = writeln!(out, "  name: {name}");
+        _ = writeln!(out, "spec:");
+        _ = writeln!(out, "  replicas: {replicas}");
+        _ = writeln!(out, "  selector:");
+        _ = writeln!(out, "    matchLabels:");
+        _ = writeln!(out, "      app: {name}");
+        _ = writeln!(out, "  template:");
+        _ = writeln!(out, "    metadata:");
+        _ = writeln!(out, "      labels:");
+        _ = writeln!(out, "        app: {name}");
+        _ = writeln!(out, "    spec:");
+        _ = writeln!(out, "      containers:");
+        _ = writeln!(out, "        - name: {name}");
+        _ = writeln!(out, "          image: {}", quoted(image));
+        if let Some(comment) = self.capacity_comment("          ") {
+            out.push_str(&comment);
+        }
+        if !self.ports.is_empty() {
+            _ = writeln!(out, "          ports:");
+            for port in &self.ports {
+                _ = writeln!(out, "            - name: {}", port.name);
+                _ = writeln!(out, "              containerPort: {}", port.port);
+            }
+        }
+        if !self.ports.is_empty() || !self.variables.is_empty() {
+            _ = writeln!(out, "          env:");
+            for port in &self.ports {
+                _ = writeln!(out, "            - name: {}", port.listen_env);
+                _ = writeln!(out, "              value: \"0.0.0.0:{}\"", port.port);
+            }
+            for variable in &self.variables {
+                _ = writeln!(out, "            - name: {}", variable.env());
+                if variable.secret {
+                    _ = writeln!(out, "              valueFrom:");
+                    _ = writeln!(out, "                secretKeyRef:");
+                    _ = writeln!(
+                        out,
+                        "                  name: {}",
+                        quoted(&self.secret_name())
+                    );
+                    _ = writeln!(out, "                  key: {}", quoted(&variable.name));
+                } else {
+                    _ = writeln!(
+                        out,
+                        "              value: \"\" # TODO: set {}",
+                        variable.name
+                    );
+                }
+            }
+        }
+        if self.has_state {
+            _ = writeln!(out, "          volumeMounts:");
+            _ = writeln!(out, "            - name: state");
+            _ = writeln!(out, "              mountPath: {CONTAINER_STATE_DIR}");
+            _ = writeln!(out, "      volumes:");
+            _ = writeln!(
+                out,
+                "        # Key-value stores and SQLite databases. Use a PersistentVolumeClaim to keep them across restarts."
+            );
+            _ = writeln!(out, "        - name: state");
+            _ = writeln!(out, "          emptyDir: {{}}");
+        }
+        out
+    }
+
+    fn service(&self) -> String {
+        let name = quoted(&self.name);
+        let mut out = String::new();
+        _ = writeln!(out, "# Generated by `spin scaffold kubernetes`.");
+        _ = writeln!(out, "apiVersion: v1");
+        _ = writeln!(out, "kind: Service");
+        _ = writeln!(out, "metadata:");
+        _ = writeln!(out, "  name: {name}");
+        _ = writeln!(out, "spec:");
+        _ = writeln!(out, "  selector:");
+        _ = writeln!(out, "    app: {name}");
+        _ = writeln!(out, "  ports:");
+        for port in &self.ports {
+            _ = writeln!(out, "    - name: {}", port.name);
+            _ = writeln!(out, "      port: {}", port.port);
+            _ = writeln!(out, "      targetPort: {}", port.name);
+        }
+        out
+    }
+
+    fn spinapp(&self, image: &str, replicas: u32) -> String {
+        let mut out = String::new();
+        _ = writeln!(out, "# Generated by `spin scaffold kubernetes --spinapp`.");
+        _ = writeln!(out, "apiVersion: core.spinoperator.dev/v1alpha1");
+        _ = writeln!(out, "kind: SpinApp");
+        _ = writeln!(out, "metadata:");
+        _ = writeln!(out, "  name: {}", quoted(&self.name));
+        _ = writeln!(out, "spec:");
+        _ = writeln!(out, "  image: {}", quoted(image));
+        _ = writeln!(out, "  executor: containerd-shim-spin");
+        _ = writeln!(out, "  replicas: {replicas}");
+        if let Some(comment) = self.capacity_comment("  ") {
+            out.push_str(&comment);
+        }
+        if !self.variables.is_empty() {
+            _ = writeln!(out, "  variables:");
+            for variable in &self.variables {
+                _ = writeln!(out, "    - name: {}", quoted(&variable.name));
+                if variable.secret {
+                    _ = writeln!(out, "      valueFrom:");
+                    _ = writeln!(out, "        secretKeyRef:");
+                    _ = writeln!(out, "          name: {}", quoted(&self.secret_name()));
+                    _ = writeln!(out, "          key: {}", quoted(&variable.name));
+                } else {
+                    _ = writeln!(out, "      value: \"\" # TODO: set {}", variable.name);
+                }
+            }
+        }
+        out
+    }
+
+    fn secret(&self) -> String {
+        let mut out = String::new();
+        _ = writeln!(out, "# Generated by `spin scaffold kubernetes`.");
+        _ = writeln!(out, "apiVersion: v1");
+        _ = writeln!(out, "kind: Secret");
+        _ = writeln!(out, "metadata:");
+        _ = writeln!(out, "  name: {}", quoted(&self.secret_name()));
+        _ = writeln!(out, "stringData:");
+        for variable in self.variables.iter().filter(|v| v.secret) {
+            _ = writeln!(out, "  {}: \"\" # TODO: set", variable.name);
+        }
+        out
+    }
+}
+
+const DOCKERIGNORE: &str = "# Generated by `spin scaffold docker`.
+.spin/
+target/
+deploy/
+";
+
+/// Quotes a string for YAML or a Dockerfile exec form. JSON strings are
+/// valid in both.
+fn quoted(s: &str) -> String {
+    serde_json::Value::from(s).to_string()
+}
+
+/// Converts an application name to a Kubernetes resource name.
+fn dns_label(name: &str) -> String {
+    let label: String = name
+        .to_lowercase()
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '-' })
+        .collect();
+    let label = label.trim_matches('-');
+    let label = label[..label.len().min(63)].trim_end_matches('-');
+    if label.is_empty() {
+        "spin-app".to_owned()
+    } else {
+        label.to_owned()
+    }
+}
+
+fn write_files(dir: &Path, files: &[(&str, String)], force: bool) -> Result<()> {
+    if !force {
+        if let Some((name, _)) = files.iter().find(|(name, _)| dir.join(name).exists()) {
+            bail!(
+                "{} already exists. Use `--force` to overwrite it.",
+                quoted_path(dir.join(name))
+            );
+        }
+    }
+    std::fs::create_dir_all(dir)
+        .with_context(|| format!("Failed to create {}", quoted_path(dir)))?;
+    for (name, content) in files {
+        let path = dir.join(name);
+        std::fs::write(&path, content)
+            .with_context(|| format!("Failed to write {}", quoted_path(&path)))?;
+        terminal::step!("Wrote", "{}", quoted_path(&path));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MANIFEST: &str = r#"spin_manifest_version = 2
+
+[application]
+name = "My App"
+version = "1.2.0"
+
+[variables]
+api_key = { required = true, secret = true }
+region = { required = true }
+greeting = { default = "hello" }
+
+[[trigger.http]]
+route = "/..."
+component = "web"
+
+[component.web]
+source = "web.wasm"
+key_value_stores = ["default"]
+
+[component.web.resources]
+max_memory_mb = 64
+max_concurrent = 4
+"#;
+
+    fn deployment() -> AppDeployment {
+        let manifest = spin_manifest::manifest_from_str(MANIFEST).unwrap();
+        AppDeployment::new(&manifest)
+    }
+
+    #[test]
+    fn deployment_is_derived_from_manifest() {
+        let deployment = deployment();
+        assert_eq!(deployment.name, "my-app");
+        assert_eq!(deployment.ports.len(), 1);
+        assert_eq!(deployment.ports[0].listen_env, env::HTTP_LISTEN);
+        assert_eq!(
+            deployment.variables,
+            [
+                RequiredVariable {
+                    name: "api_key".to_owned(),
+                    secret: true,
+                },
+                RequiredVariable {
+                    name: "region".to_owned(),
+                    secret: false,
+                },
+            ]
+        );
+        assert!(deployment.has_state);
+
+        let manifests = deployment.deployment("my-app:1.2.0", 2);
+        assert!(manifests.contains("containerPort: 80"));
+        assert!(manifests.contains("- name: SPIN_VARIABLE_API_KEY"));
+        assert!(manifests.contains("name: \"my-app-variables\""));
+        assert!(manifests.contains(&format!("mountPath: {CONTAINER_STATE_DIR}")));
+        assert!(manifests.contains("up to 256 MiB of memory at once"));
+
+        let dockerfile = deployment.dockerfile("spin.toml");
+        assert!(dockerfile.contains("ENV SPIN_HTTP_LISTEN=0.0.0.0:80"));
+        assert!(dockerfile.contains("CMD [\"spin\", \"up\", \"--from\", \"spin.toml\"]"));
+    }
+
+    #[test]
+    fn names_are_valid_dns_labels() {
+        assert_eq!(dns_label("Hello_World!"), "hello-world");
+        assert_eq!(dns_label("---"), "spin-app");
+        assert_eq!(dns_label(&"a".repeat(80)).len(), 63);
+    }
+}
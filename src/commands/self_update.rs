@@ -0,0 +1,282 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::{anyhow, bail, Context, Result};
+use clap::{Parser, ValueEnum};
+use serde::Deserialize;
+use spin_common::ui::quoted_path;
+use tempfile::TempDir;
+
+use crate::build_info::SPIN_VERSION;
+
+const RELEASES_API_URL: &str = "https://api.github.com/repos/fermyon/spin/releases/latest";
+const RELEASES_DOWNLOAD_URL: &str = "https://github.com/fermyon/spin/releases/download";
+/// The identity which signs release artifacts: the release workflow.
+const SIGNER_IDENTITY_REGEXP: &str =
+    r"^https://github\.com/fermyon/spin/\.github/workflows/release\.yml@refs/.+$";
+const SIGNER_OIDC_ISSUER: &str = "https://token.actions.githubusercontent.com";
+
+/// Update Spin to a newer release.
+#[derive(Parser, Debug)]
+#[clap(
+    about = "Update Spin to the latest release, or to a given version",
+    long_about = "Update Spin to the latest release, or to a given version.
+
+The release archive is checked against the release checksums and, using cosign (https://docs.sigstore.dev/), against its signature by the Spin release workflow, before the running Spin executable is replaced."
+)]
+pub struct SelfUpdateCommand {
+    /// The release channel to update from.
+    #[clap(value_enum, long = "channel", default_value = "stable")]
+    pub channel: Channel,
+
+    /// The version to install, such as 2.4.0, instead of the latest
+    /// release of the channel.
+    #[clap(long = "version", conflicts_with = "channel")]
+    pub version: Option<String>,
+
+    /// Reinstall even if the version is already installed.
+    #[clap(long = "force", takes_value = false)]
+    pub force: bool,
+
+    /// Skip verifying the signature of the release. The checksum is still
+    /// verified. Use this only if cosign is not available.
+    #[clap(long = "skip-signature", takes_value = false)]
+    pub skip_signature: bool,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq)]
+pub enum Channel {
+    /// The latest release
+    Stable,
+    /// A build of the latest commit, which may be unstable
+    Canary,
+}
+
+#[derive(Deserialize)]
+struct Release {
+    tag_name: String,
+}
+
+impl SelfUpdateCommand {
+    pub async fn run(self) -> Result<()> {
+        if cfg!(windows) {
+            bail!("`spin self-update` is not supported on Windows. Download the new release from https://github.com/fermyon/spin/releases instead.");
+        }
+        let client = reqwest::Client::builder()
+            .user_agent(format!("spin/{SPIN_VERSION}"))
+            .build()?;
+
+        let tag = match (&self.version, self.channel) {
+            (Some(version), _) => version_tag(version),
+            (None, Channel::Canary) => "canary".to_owned(),
+            (None, Channel::Stable) => latest_release(&client).await?,
+        };
+        let current = format!("v{SPIN_VERSION}");
+        if tag == current && !self.force {
+            println!("Spin {current} is already installed.");
+            return Ok(());
+        }
+
+        let asset = asset_name(&tag)?;
+        let temp_dir = TempDir::with_prefix("spin-self-update-")?;
+        terminal::step!("Downloading", "Spin {tag}");
+        let archive = download(&client, &tag, &asset, temp_dir.path()).await?;
+        let checksums = download(
+            &client,
+            &tag,
+            &format!("checksums-{tag}.txt"),
+            temp_dir.path(),
+        )
+        .await?;
+        verify_checksum(&archive, &asset, &checksums)?;
+
+        if self.skip_signature {
+            terminal::warn!("Skipping signature verification of the Spin {tag} release");
+        } else {
+            let signature =
+                download(&client, &tag, &format!("{asset}.sig"), temp_dir.path()).await?;
+            let certificate =
+                download(&client, &tag, &format!("{asset}.pem"), temp_dir.path()).await?;
+            verify_signature(&archive, &signature, &certificate)?;
+        }
+
+        let new_exe = unpack_spin(&archive, temp_dir.path())?;
+        let exe = std::env::current_exe()
+            .and_then(|exe| exe.canonicalize())
+            .context("Cannot find the running Spin executable")?;
+        replace_exe(&exe, &new_exe)?;
+        terminal::step!("Updated", "{} to Spin {tag}", quoted_path(&exe));
+        Ok(())
+    }
+}
+
+/// The release tag of a version, which may be given with or without its
+/// leading "v".
+fn version_tag(version: &str) -> String {
+    if version.starts_with('v') || version == "canary" {
+        version.to_owned()
+    } else {
+        format!("v{version}")
+    }
+}
+
+async fn latest_release(client: &reqwest::Client) -> Result<String> {
+    let release: Release = client
+        .get(RELEASES_API_URL)
+        .send()
+        .await
+        .and_then(|response| response.error_for_status())
+        .context("Failed to find the latest Spin release")?
+        .json()
+        .await
+        .context("Failed to read the latest Spin release")?;
+    Ok(release.tag_name)
+}
+
+/// The name of the release archive for this platform.
+fn asset_name(tag: &str) -> Result<String> {
+    let os = match std::env::consts::OS {
+        "linux" => "linux",
+        "macos" => "macos",
+        os => bail!("There are no Spin releases for {os}"),
+    };
+    let arch = match std::env::consts::ARCH {
+        "x86_64" => "amd64",
+        "aarch64" => "aarch64",
+        arch => bail!("There are no Spin releases for {arch}"),
+    };
+    Ok(format!("spin-{tag}-{os}-{arch}.tar.gz"))
+}
+
+async fn download(client: &reqwest::Client, tag: &str, name: &str, dir: &Path) -> Result<PathBuf> {
+    let url = format!("{RELEASES_DOWNLOAD_URL}/{tag}/{name}");
+    let response = client
+        .get(&url)
+        .send()
+        .await
+        .with_context(|| format!("Failed to download {url}"))?;
+    match response.status() {
+        reqwest::StatusCode::NOT_FOUND => bail!("Spin release {tag} has no file {name}"),
+        status if !status.is_success() => bail!("HTTP error {status} when downloading {url}"),
+        _ => (),
+    }
+    let bytes = response
+        .bytes()
+        .await
+        .with_context(|| format!("Failed to download {url}"))?;
+    let path = dir.join(name);
+    std::fs::write(&path, bytes)
+        .with_context(|| format!("Failed to write {}", quoted_path(&path)))?;
+    Ok(path)
+}
+
+fn verify_checksum(archive: &Path, asset: &str, checksums: &Path) -> Result<()> {
+    let checksums = std::fs::read_to_string(checksums)
+        .with_context(|| format!("Failed to read {}", quoted_path(checksums)))?;
+    let expected = expected_checksum(&checksums, asset)
+        .ok_or_else(|| anyhow!("The release checksums do not include {asset}"))?;
+    let actual = spin_common::sha256::hex_digest_from_file(archive)
+        .with_context(|| format!("Failed to read {}", quoted_path(archive)))?;
+    if actual != expected {
+        bail!("Checksum of {asset} did not match the release checksums; not updating");
+    }
+    Ok(())
+}
+
+/// Finds a file's checksum in `sha256sum` output.
+fn expected_checksum<'a>(checksums: &'a str, file_name: &str) -> Option<&'a str> {
+    checksums.lines().find_map(|line| {
+        let (digest, name) = line.split_once(char::is_whitespace)?;
+        (name.trim_start().trim_start_matches('*') == file_name).then_some(digest)
+    })
+}
+
+fn verify_signature(archive: &Path, signature: &Path, certificate: &Path) -> Result<()> {
+    let output = std::process::Command::new("cosign")
+        .arg("verify-blob")
+        .arg("--signature")
+        .arg(signature)
+        .arg("--certificate")
+        .arg(certificate)
+        .args(["--certificate-identity-regexp", SIGNER_IDENTITY_REGEXP])
+        .args(["--certificate-oidc-issuer", SIGNER_OIDC_ISSUER])
+        .arg(archive)
+        .output();
+    let output = match output {
+        Ok(output) => output,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => bail!(
+            "Verifying the release signature requires cosign (https://docs.sigstore.dev/system_config/installation/). Install it, or use `--skip-signature` to rely on the checksum alone."
+        ),
+        Err(err) => return Err(err).context("Failed to run cosign"),
+    };
+    if !output.status.success() {
+        bail!(
+            "The release signature could not be verified; not updating:\n{}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    Ok(())
+}
+
+/// Extracts the `spin` executable from a release archive.
+fn unpack_spin(archive: &Path, dir: &Path) -> Result<PathBuf> {
+    let file = std::fs::File::open(archive)
+        .with_context(|| format!("Failed to open {}", quoted_path(archive)))?;
+    let unpack_dir = dir.join("unpacked");
+    tar::Archive::new(flate2::read::GzDecoder::new(file))
+        .unpack(&unpack_dir)
+        .with_context(|| format!("Failed to unpack {}", quoted_path(archive)))?;
+    let exe = unpack_dir.join("spin");
+    if !exe.is_file() {
+        bail!("The release archive does not contain a spin executable");
+    }
+    Ok(exe)
+}
+
+/// Replaces the running executable. The new executable is first copied
+/// next to it, so that the final rename is on one filesystem.
+fn replace_exe(exe: &Path, new_exe: &Path) -> Result<()> {
+    let staged = exe.with_extension("new");
+    std::fs::copy(new_exe, &staged)
+        .with_context(|| format!("Failed to write {}", quoted_path(&staged)))?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        std::fs::set_permissions(&staged, std::fs::Permissions::from_mode(0o755))
+            .with_context(|| format!("Failed to make {} executable", quoted_path(&staged)))?;
+    }
+    std::fs::rename(&staged, exe).with_context(|| {
+        _ = std::fs::remove_file(&staged);
+        format!("Failed to replace {}", quoted_path(exe))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn versions_are_release_tags() {
+        assert_eq!(version_tag("2.4.0"), "v2.4.0");
+        assert_eq!(version_tag("v2.4.0"), "v2.4.0");
+        assert_eq!(version_tag("canary"), "canary");
+    }
+
+    #[test]
+    fn checksums_are_found_by_file_name() {
+        let checksums = "\
+            1111  spin-v2.4.0-linux-amd64.tar.gz\n\
+            2222 *spin-v2.4.0-macos-aarch64.tar.gz\n";
+        assert_eq!(
+            expected_checksum(checksums, "spin-v2.4.0-linux-amd64.tar.gz"),
+            Some("1111")
+        );
+        assert_eq!(
+            expected_checksum(checksums, "spin-v2.4.0-macos-aarch64.tar.gz"),
+            Some("2222")
+        );
+        assert_eq!(
+            expected_checksum(checksums, "spin-v2.4.0-linux-aarch64.tar.gz"),
+            None
+        );
+    }
+}
@@ -0,0 +1,395 @@
+use std::{
+    collections::BTreeMap,
+    ffi::OsString,
+    fs::File,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{bail, Context, Result};
+use clap::{Args, Parser, Subcommand};
+use serde::{Deserialize, Serialize};
+use spin_common::ui::quoted_path;
+use spin_trigger::{cli::env, RuntimeConfig};
+use tempfile::TempDir;
+
+use crate::commands::daemon::DaemonFiles;
+use crate::opts::*;
+
+/// The description of a snapshot, at the root of its archive.
+const INDEX_FILE: &str = "spin-state.json";
+const SNAPSHOT_VERSION: u32 = 1;
+/// The files SQLite may keep next to a database, by the suffix of their
+/// names, which are copied along with it.
+const SQLITE_SIDECAR_SUFFIXES: &[&str] = &["-wal", "-shm", "-journal"];
+
+/// Commands for snapshotting and restoring the local state of an application.
+#[derive(Subcommand, Debug)]
+pub enum StateCommands {
+    /// Bundle the application's local key-value stores and SQLite databases
+    /// into an archive, for sharing a dataset or returning to it later.
+    Export(ExportCommand),
+    /// Restore the application's local key-value stores and SQLite databases
+    /// from an archive made by `spin state export`, replacing their contents.
+    Import(ImportCommand),
+}
+
+impl StateCommands {
+    pub async fn run(self) -> Result<()> {
+        match self {
+            StateCommands::Export(cmd) => cmd.run(),
+            StateCommands::Import(cmd) => cmd.run(),
+        }
+    }
+}
+
+/// Which application, and which of its runtime config, to use.
+#[derive(Args, Debug)]
+pub struct StateOptions {
+    /// The application whose state to use. This may be a manifest
+    /// (spin.toml) file, or a directory containing a spin.toml file.
+    /// If omitted, it defaults to "spin.toml".
+    #[clap(
+        name = APP_MANIFEST_FILE_OPT,
+        short = 'f',
+        long = "from",
+        alias = "file",
+        default_value = DEFAULT_MANIFEST_FILE
+    )]
+    pub app_source: PathBuf,
+
+    /// The runtime config file the application is run with, which may keep
+    /// key-value stores and databases in other files.
+    #[clap(long = "runtime-config-file", env = env::RUNTIME_CONFIG_FILE)]
+    pub runtime_config_file: Option<PathBuf>,
+
+    /// The state directory the application is run with, if not the default
+    /// `.spin` directory next to the manifest.
+    #[clap(long = "state-dir", env = env::STATE_DIR)]
+    pub state_dir: Option<String>,
+}
+
+impl StateOptions {
+    // The runtime config the application is run with. Fails if the
+    // application is running in the background, as its files may change
+    // while they are copied.
+    fn runtime_config(&self) -> Result<RuntimeConfig> {
+        let manifest_file = spin_common::paths::resolve_manifest_file_path(&self.app_source)?;
+        let app_dir = manifest_file.parent();
+        if let Some(pid) = DaemonFiles::new(app_dir).running_pid()? {
+            bail!("The application is running (process {pid}). Stop it with `spin stop` first.");
+        }
+        let mut config = RuntimeConfig::new(app_dir.map(Path::to_owned));
+        if let Some(state_dir) = &self.state_dir {
+            config.set_state_dir(state_dir);
+        }
+        if let Some(file) = &self.runtime_config_file {
+            config.merge_config_file(file)?;
+        }
+        Ok(config)
+    }
+}
+
+#[derive(Parser, Debug)]
+pub struct ExportCommand {
+    #[clap(flatten)]
+    pub options: StateOptions,
+
+    /// The archive (.tar.gz) to write.
+    #[clap(value_name = "ARCHIVE")]
+    pub archive: PathBuf,
+}
+
+#[derive(Parser, Debug)]
+pub struct ImportCommand {
+    #[clap(flatten)]
+    pub options: StateOptions,
+
+    /// The archive (.tar.gz) to restore, made by `spin state export`.
+    #[clap(value_name = "ARCHIVE")]
+    pub archive: PathBuf,
+}
+
+/// What a snapshot archive holds.
+#[derive(Debug, Deserialize, Serialize)]
+struct Snapshot {
+    version: u32,
+    stores: Vec<SnapshotStore>,
+}
+
+/// A store or database in a snapshot.
+#[derive(Debug, Deserialize, Serialize)]
+struct SnapshotStore {
+    kind: StoreKind,
+    name: String,
+    /// The suffixes of the store's files, the first being the store's own,
+    /// empty. A store with no files didn't exist when the snapshot was
+    /// taken, and is removed when it is restored.
+    suffixes: Vec<String>,
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+enum StoreKind {
+    KeyValue,
+    Sqlite,
+}
+
+impl StoreKind {
+    fn describe(self) -> &'static str {
+        match self {
+            StoreKind::KeyValue => "key-value store",
+            StoreKind::Sqlite => "SQLite database",
+        }
+    }
+
+    fn local_files(self, config: &RuntimeConfig) -> Result<BTreeMap<String, PathBuf>> {
+        match self {
+            StoreKind::KeyValue => config.key_value_store_files(),
+            StoreKind::Sqlite => config.sqlite_database_files(),
+        }
+    }
+}
+
+impl SnapshotStore {
+    // Checks that the store's files are within the archive, and are
+    // restored next to the store.
+    fn validate(&self) -> Result<()> {
+        if self.name.is_empty() || self.name.contains(['/', '\\', '.']) {
+            bail!("Invalid store name {:?}", self.name);
+        }
+        for suffix in &self.suffixes {
+            if !suffix.is_empty() && !SQLITE_SIDECAR_SUFFIXES.contains(&suffix.as_str()) {
+                bail!("Invalid file suffix {suffix:?} for store {:?}", self.name);
+            }
+        }
+        Ok(())
+    }
+
+    // The path in the archive of the store's file with `suffix`.
+    fn archive_path(&self, suffix: &str) -> String {
+        let dir = match self.kind {
+            StoreKind::KeyValue => "key_value",
+            StoreKind::Sqlite => "sqlite",
+        };
+        format!("{dir}/{}/store{suffix}", self.name)
+    }
+}
+
+// The path of a store's file with `suffix`, e.g. its write-ahead log.
+fn with_suffix(path: &Path, suffix: &str) -> PathBuf {
+    let mut path = OsString::from(path);
+    path.push(suffix);
+    path.into()
+}
+
+impl ExportCommand {
+    pub fn run(self) -> Result<()> {
+        let config = self.options.runtime_config()?;
+        let file = File::create(&self.archive)
+            .with_context(|| format!("Failed to create {}", quoted_path(&self.archive)))?;
+        let mut builder = tar::Builder::new(flate2::write::GzEncoder::new(
+            file,
+            flate2::Compression::default(),
+        ));
+
+        let mut snapshot = Snapshot {
+            version: SNAPSHOT_VERSION,
+            stores: vec![],
+        };
+        for kind in [StoreKind::KeyValue, StoreKind::Sqlite] {
+            for (name, path) in kind.local_files(&config)? {
+                let mut store = SnapshotStore {
+                    kind,
+                    name,
+                    suffixes: vec![],
+                };
+                if path.exists() {
+                    let suffixes =
+                        std::iter::once("").chain(SQLITE_SIDECAR_SUFFIXES.iter().copied());
+                    for suffix in suffixes {
+                        let file = with_suffix(&path, suffix);
+                        if !file.exists() {
+                            continue;
+                        }
+                        builder
+                            .append_path_with_name(&file, store.archive_path(suffix))
+                            .with_context(|| format!("Failed to archive {}", quoted_path(&file)))?;
+                        store.suffixes.push(suffix.to_owned());
+                    }
+                    terminal::step!(
+                        "Exporting",
+                        "{} {:?} from {}",
+                        kind.describe(),
+                        store.name,
+                        quoted_path(&path)
+                    );
+                }
+                snapshot.stores.push(store);
+            }
+        }
+
+        let index = serde_json::to_vec_pretty(&snapshot)?;
+        let mut header = tar::Header::new_gnu();
+        header.set_size(index.len() as u64);
+        header.set_mode(0o644);
+        header.set_cksum();
+        builder.append_data(&mut header, INDEX_FILE, index.as_slice())?;
+        builder
+            .into_inner()
+            .and_then(|encoder| encoder.finish())
+            .with_context(|| format!("Failed to write {}", quoted_path(&self.archive)))?;
+
+        terminal::step!("Exported", "state to {}", quoted_path(&self.archive));
+        Ok(())
+    }
+}
+
+impl ImportCommand {
+    pub fn run(self) -> Result<()> {
+        let config = self.options.runtime_config()?;
+        let unpacked = TempDir::with_prefix("spin-state-")?;
+        let file = File::open(&self.archive)
+            .with_context(|| format!("Failed to open {}", quoted_path(&self.archive)))?;
+        tar::Archive::new(flate2::read::GzDecoder::new(file))
+            .unpack(unpacked.path())
+            .with_context(|| format!("Failed to unpack {}", quoted_path(&self.archive)))?;
+
+        let index = std::fs::read(unpacked.path().join(INDEX_FILE)).with_context(|| {
+            format!(
+                "{} is not an archive made by `spin state export`",
+                quoted_path(&self.archive)
+            )
+        })?;
+        let snapshot: Snapshot = serde_json::from_slice(&index)
+            .with_context(|| format!("Invalid {INDEX_FILE} in {}", quoted_path(&self.archive)))?;
+        if snapshot.version != SNAPSHOT_VERSION {
+            bail!(
+                "{} has snapshot version {}, but this version of Spin only imports version {SNAPSHOT_VERSION}",
+                quoted_path(&self.archive),
+                snapshot.version
+            );
+        }
+
+        let key_value_files = StoreKind::KeyValue.local_files(&config)?;
+        let sqlite_files = StoreKind::Sqlite.local_files(&config)?;
+        for store in &snapshot.stores {
+            store
+                .validate()
+                .with_context(|| format!("Invalid snapshot {}", quoted_path(&self.archive)))?;
+            let local_files = match store.kind {
+                StoreKind::KeyValue => &key_value_files,
+                StoreKind::Sqlite => &sqlite_files,
+            };
+            let Some(path) = local_files.get(&store.name) else {
+                terminal::warn!(
+                    "Skipping {} {:?}, which the runtime config doesn't keep in a local file.",
+                    store.kind.describe(),
+                    store.name
+                );
+                continue;
+            };
+            restore(store, path, unpacked.path())?;
+        }
+
+        terminal::step!("Imported", "state from {}", quoted_path(&self.archive));
+        Ok(())
+    }
+}
+
+// Replaces the files of `store` at `path` with those unpacked in
+// `unpacked`, or removes them if the store didn't exist in the snapshot.
+fn restore(store: &SnapshotStore, path: &Path, unpacked: &Path) -> Result<()> {
+    let suffixes = std::iter::once("").chain(SQLITE_SIDECAR_SUFFIXES.iter().copied());
+    for suffix in suffixes {
+        let file = with_suffix(path, suffix);
+        match std::fs::remove_file(&file) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => {
+                return Err(e).with_context(|| format!("Failed to remove {}", quoted_path(&file)));
+            }
+            _ => {}
+        }
+    }
+    if store.suffixes.is_empty() {
+        terminal::step!(
+            "Removed",
+            "{} {:?}, which didn't exist in the snapshot",
+            store.kind.describe(),
+            store.name
+        );
+        return Ok(());
+    }
+
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)
+            .with_context(|| format!("Failed to create {}", quoted_path(dir)))?;
+    }
+    for suffix in &store.suffixes {
+        let from = unpacked.join(store.archive_path(suffix));
+        let to = with_suffix(path, suffix);
+        std::fs::copy(&from, &to)
+            .with_context(|| format!("Failed to restore {}", quoted_path(&to)))?;
+    }
+    terminal::step!(
+        "Restored",
+        "{} {:?} to {}",
+        store.kind.describe(),
+        store.name,
+        quoted_path(path)
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn options(app_dir: &Path) -> StateOptions {
+        StateOptions {
+            app_source: app_dir.join("spin.toml"),
+            runtime_config_file: None,
+            state_dir: None,
+        }
+    }
+
+    #[test]
+    fn import_restores_exported_state() -> Result<()> {
+        let app_dir = tempfile::tempdir()?;
+        std::fs::write(
+            app_dir.path().join("spin.toml"),
+            "spin_manifest_version = 2",
+        )?;
+        let state_dir = app_dir.path().join(".spin");
+        std::fs::create_dir_all(&state_dir)?;
+        let kv_file = state_dir.join("sqlite_key_value.db");
+        std::fs::write(&kv_file, "snapshot")?;
+        std::fs::write(with_suffix(&kv_file, "-wal"), "log")?;
+        let archive = app_dir.path().join("state.tar.gz");
+
+        ExportCommand {
+            options: options(app_dir.path()),
+            archive: archive.clone(),
+        }
+        .run()?;
+
+        // Change the state after the snapshot
+        std::fs::write(&kv_file, "changed")?;
+        std::fs::remove_file(with_suffix(&kv_file, "-wal"))?;
+        let sqlite_file = state_dir.join("sqlite_db.db");
+        std::fs::write(&sqlite_file, "created")?;
+
+        ImportCommand {
+            options: options(app_dir.path()),
+            archive,
+        }
+        .run()?;
+
+        assert_eq!(std::fs::read_to_string(&kv_file)?, "snapshot");
+        assert_eq!(
+            std::fs::read_to_string(with_suffix(&kv_file, "-wal"))?,
+            "log"
+        );
+        // The database didn't exist when the snapshot was taken
+        assert!(!sqlite_file.exists());
+        Ok(())
+    }
+}
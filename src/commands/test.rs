@@ -0,0 +1,431 @@
+use std::{
+    collections::BTreeSet,
+    path::{Path, PathBuf},
+    process::Stdio,
+    time::{Duration, Instant},
+};
+
+use anyhow::{anyhow, bail, Context, Result};
+use clap::Parser;
+use reqwest::Url;
+use serde::Serialize;
+use spin_common::ui::quoted_path;
+use spin_loader::FilesMountStrategy;
+use spin_locked_app::locked::{LockedApp, LockedTrigger};
+use spin_manifest::schema::v2::{AppManifest, ComponentSpec};
+use spin_trigger::cli::{SPIN_LOCAL_APP_DIR, SPIN_LOCKED_URL, SPIN_WORKING_DIR};
+use tempfile::TempDir;
+
+use crate::opts::*;
+use crate::output::{print_json, OutputArgs};
+use crate::subprocess::ExitStatusError;
+
+/// The trigger through which test components are run.
+const TEST_TRIGGER_TYPE: &str = "command";
+
+/// Run the test components of a Spin application.
+#[derive(Parser, Debug)]
+#[clap(
+    about = "Run the test components of the Spin application",
+    long_about = "Run the test components of the Spin application.
+
+A test is a component with a `[component.<id>.test]` section, or a component which no trigger uses whose ID starts with `test-` or ends with `-test` or `-tests`. Each test is run as a command (through its `wasi:cli/run` export) against the Spin runtime, with a fresh state directory and in-memory key-value stores and SQLite databases. A test passes if it exits successfully."
+)]
+pub struct TestCommand {
+    /// The application to test. This may be a manifest (spin.toml) file, or a
+    /// directory containing a spin.toml file.
+    /// If omitted, it defaults to "spin.toml".
+    #[clap(
+        name = APP_MANIFEST_FILE_OPT,
+        short = 'f',
+        long = "from",
+        alias = "file",
+        default_value = DEFAULT_MANIFEST_FILE
+    )]
+    pub app_source: PathBuf,
+
+    /// Run only the tests whose component IDs contain this text.
+    pub filter: Option<String>,
+
+    /// Build the application before running the tests.
+    #[clap(long = "build", takes_value = false)]
+    pub build: bool,
+
+    /// Show the output of passing tests as well as failing ones.
+    #[clap(long = "show-output", takes_value = false)]
+    pub show_output: bool,
+
+    /// Cache directory for downloaded components and assets.
+    #[clap(long)]
+    pub cache_dir: Option<PathBuf>,
+
+    #[clap(flatten)]
+    pub output: OutputArgs,
+}
+
+/// A test component, and the arguments it is run with.
+#[derive(Debug, PartialEq)]
+struct TestCase {
+    component_id: String,
+    args: Vec<String>,
+}
+
+/// The result of running a test, as reported by `spin test --output json`.
+#[derive(Debug, Serialize)]
+struct TestResult {
+    name: String,
+    passed: bool,
+    /// The exit code of the test, if it exited normally
+    exit_code: Option<i32>,
+    /// How long the test took to run, in milliseconds
+    duration_ms: u128,
+    /// The standard output and standard error of the test
+    output: String,
+}
+
+/// The result of `spin test --output json`.
+#[derive(Debug, Serialize)]
+struct TestOutput {
+    passed: usize,
+    failed: usize,
+    tests: Vec<TestResult>,
+}
+
+impl TestCommand {
+    pub async fn run(self) -> Result<()> {
+        let output = self.output.apply();
+        let manifest_file = spin_common::paths::resolve_manifest_file_path(&self.app_source)?;
+        let app_dir = manifest_file
+            .parent()
+            .context("Manifest file has no parent directory")?
+            .to_owned();
+
+        if self.build {
+            spin_build::build(&manifest_file, &[]).await?;
+        }
+
+        let mut manifest = spin_loader::manifest_from_file(&manifest_file, None)?;
+        spin_manifest::normalize::normalize_manifest(&mut manifest);
+        let tests: Vec<_> = test_cases(&manifest)
+            .into_iter()
+            .filter(|test| match &self.filter {
+                Some(filter) => test.component_id.contains(filter.as_str()),
+                None => true,
+            })
+            .collect();
+        if tests.is_empty() {
+            if output.is_json() {
+                return print_json(&TestOutput {
+                    passed: 0,
+                    failed: 0,
+                    tests: vec![],
+                });
+            }
+            println!("No tests found in {}", quoted_path(&manifest_file));
+            return Ok(());
+        }
+
+        // Holds the loaded app and the tests' state until we're done
+        let working_dir_holder = TempDir::with_prefix("spin-test-")?;
+        let working_dir = working_dir_holder
+            .path()
+            .canonicalize()
+            .context("Could not canonicalize working directory")?;
+
+        let locked_app = spin_loader::from_file(
+            &manifest_file,
+            FilesMountStrategy::Copy(working_dir.join("assets")),
+            self.cache_dir.clone(),
+        )
+        .await
+        .with_context(|| {
+            format!(
+                "Failed to load manifest from {}",
+                quoted_path(&manifest_file)
+            )
+        })?;
+        let runtime_config_file = working_dir.join("runtime-config.toml");
+        std::fs::write(&runtime_config_file, in_memory_runtime_config(&manifest))
+            .with_context(|| format!("Failed to write {}", quoted_path(&runtime_config_file)))?;
+
+        let mut results = vec![];
+        for test in &tests {
+            if !output.is_json() {
+                terminal::step!("Running", "test {}", test.component_id);
+            }
+            let result = self
+                .run_test(
+                    test,
+                    &locked_app,
+                    &working_dir,
+                    &app_dir,
+                    &runtime_config_file,
+                )
+                .await
+                .with_context(|| format!("Failed to run test `{}`", test.component_id))?;
+            if !output.is_json() {
+                self.print_result(&result);
+            }
+            results.push(result);
+        }
+
+        let passed = results.iter().filter(|r| r.passed).count();
+        let failed = results.len() - passed;
+        if output.is_json() {
+            print_json(&TestOutput {
+                passed,
+                failed,
+                tests: results,
+            })?;
+        } else {
+            println!();
+            println!("{passed} passed, {failed} failed");
+        }
+        if failed > 0 {
+            if output.is_json() {
+                return Err(ExitStatusError::with_code(1).into());
+            }
+            bail!("{failed} of {} tests failed", passed + failed);
+        }
+        Ok(())
+    }
+
+    /// Runs a test component through the command trigger, in a state
+    /// directory of its own.
+    async fn run_test(
+        &self,
+        test: &TestCase,
+        locked_app: &LockedApp,
+        working_dir: &Path,
+        app_dir: &Path,
+        runtime_config_file: &Path,
+    ) -> Result<TestResult> {
+        let test_dir = working_dir.join("tests").join(&test.component_id);
+        let state_dir = test_dir.join("state");
+        std::fs::create_dir_all(&state_dir)
+            .with_context(|| format!("Failed to create {}", quoted_path(&state_dir)))?;
+
+        let locked_app = test_app(locked_app, test);
+        let locked_path = test_dir.join("spin.lock");
+        let locked_app_contents =
+            serde_json::to_vec_pretty(&locked_app).context("failed to serialize locked app")?;
+        std::fs::write(&locked_path, locked_app_contents)
+            .with_context(|| format!("failed to write {}", quoted_path(&locked_path)))?;
+        let locked_url = Url::from_file_path(&locked_path)
+            .map_err(|_| anyhow!("cannot convert to file URL: {}", quoted_path(&locked_path)))?;
+
+        let mut cmd = tokio::process::Command::new(std::env::current_exe()?);
+        cmd.args(["trigger", TEST_TRIGGER_TYPE])
+            .arg("--state-dir")
+            .arg(&state_dir)
+            .arg("--runtime-config-file")
+            .arg(runtime_config_file)
+            .env(SPIN_LOCKED_URL, locked_url.as_str())
+            .env(SPIN_WORKING_DIR, working_dir)
+            .env(SPIN_LOCAL_APP_DIR, app_dir)
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true);
+        tracing::trace!("Running test: {:?}", cmd);
+
+        let start = Instant::now();
+        let out = cmd.output().await.context("Failed to execute trigger")?;
+        let duration = start.elapsed();
+
+        let mut output = String::from_utf8_lossy(&out.stdout).into_owned();
+        output.push_str(&String::from_utf8_lossy(&out.stderr));
+        Ok(TestResult {
+            name: test.component_id.clone(),
+            passed: out.status.success(),
+            exit_code: out.status.code(),
+            duration_ms: duration.as_millis(),
+            output,
+        })
+    }
+
+    fn print_result(&self, result: &TestResult) {
+        let duration = format_duration(Duration::from_millis(result.duration_ms as u64));
+        if result.passed {
+            println!("test {} ... ok ({duration})", result.name);
+        } else {
+            let status = match result.exit_code {
+                Some(code) => format!("exit code {code}"),
+                None => "terminated".to_owned(),
+            };
+            println!("test {} ... FAILED ({status}, {duration})", result.name);
+        }
+        if (!result.passed || self.show_output) && !result.output.is_empty() {
+            for line in result.output.lines() {
+                println!("    {line}");
+            }
+        }
+    }
+}
+
+/// The tests of an application: the components with a `test` section, and
+/// the components which are named as tests and which no trigger uses.
+fn test_cases(manifest: &AppManifest) -> Vec<TestCase> {
+    let triggered: BTreeSet<&str> = manifest
+        .triggers
+        .values()
+        .flatten()
+        .flat_map(|trigger| {
+            trigger
+                .component
+                .iter()
+                .chain(trigger.components.values().flat_map(|specs| specs.0.iter()))
+        })
+        .filter_map(|spec| match spec {
+            ComponentSpec::Reference(id) => Some(id.as_ref()),
+            ComponentSpec::Inline(_) => None,
+        })
+        .collect();
+
+    manifest
+        .components
+        .iter()
+        .filter_map(|(id, component)| {
+            let id: &str = id.as_ref();
+            let args = match &component.test {
+                Some(test) => test.args.clone(),
+                None if is_test_name(id) && !triggered.contains(id) => vec![],
+                None => return None,
+            };
+            Some(TestCase {
+                component_id: id.to_owned(),
+                args,
+            })
+        })
+        .collect()
+}
+
+fn is_test_name(id: &str) -> bool {
+    id.starts_with("test-") || id.ends_with("-test") || id.ends_with("-tests")
+}
+
+/// Returns the app with its triggers replaced by a single command trigger
+/// for the test component.
+fn test_app(locked_app: &LockedApp, test: &TestCase) -> LockedApp {
+    let mut app = locked_app.clone();
+    app.triggers = vec![LockedTrigger {
+        id: format!("test-{}", test.component_id),
+        trigger_type: TEST_TRIGGER_TYPE.to_owned(),
+        trigger_config: serde_json::json!({
+            "component": test.component_id,
+            "args": test.args,
+        }),
+    }];
+    app.metadata.remove("trigger");
+    app.metadata.insert(
+        "triggers".into(),
+        serde_json::json!({ TEST_TRIGGER_TYPE: {} }),
+    );
+    app
+}
+
+/// A runtime config which declares every key-value store and SQLite
+/// database the application uses, with no path, so that they are kept in
+/// memory.
+fn in_memory_runtime_config(manifest: &AppManifest) -> String {
+    let mut key_value_stores = BTreeSet::from(["default"]);
+    let mut sqlite_databases = BTreeSet::from(["default"]);
+    for component in manifest.components.values() {
+        key_value_stores.extend(component.key_value_stores.iter().map(String::as_str));
+        sqlite_databases.extend(component.sqlite_databases.iter().map(String::as_str));
+    }
+
+    let mut config = String::new();
+    for label in key_value_stores {
+        config.push_str(&format!("[key_value_store.{label}]\ntype = \"spin\"\n\n"));
+    }
+    for label in sqlite_databases {
+        config.push_str(&format!("[sqlite_database.{label}]\ntype = \"spin\"\n\n"));
+    }
+    config
+}
+
+fn format_duration(duration: Duration) -> String {
+    if duration.as_secs() > 0 {
+        format!("{:.2}s", duration.as_secs_f64())
+    } else {
+        format!("{}ms", duration.as_millis())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MANIFEST: &str = r#"spin_manifest_version = 2
+
+[application]
+name = "tested"
+
+[[trigger.http]]
+route = "/..."
+component = "web"
+
+[[trigger.http]]
+route = "/api/..."
+component = "api-test"
+
+[component.web]
+source = "web.wasm"
+key_value_stores = ["cache"]
+
+[component.api-test]
+source = "api.wasm"
+
+[component.test-web]
+source = "test-web.wasm"
+sqlite_databases = ["records"]
+
+[component.smoke]
+source = "smoke.wasm"
+[component.smoke.test]
+args = ["--quick"]
+"#;
+
+    fn manifest() -> AppManifest {
+        let mut manifest = spin_manifest::manifest_from_str(MANIFEST).unwrap();
+        spin_manifest::normalize::normalize_manifest(&mut manifest);
+        manifest
+    }
+
+    #[test]
+    fn tests_are_discovered_by_section_or_name() {
+        let tests = test_cases(&manifest());
+        assert_eq!(
+            tests,
+            [
+                TestCase {
+                    component_id: "smoke".to_owned(),
+                    args: vec!["--quick".to_owned()],
+                },
+                TestCase {
+                    component_id: "test-web".to_owned(),
+                    args: vec![],
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn stores_are_kept_in_memory() {
+        let config: toml::Value = toml::from_str(&in_memory_runtime_config(&manifest())).unwrap();
+        for label in ["default", "cache"] {
+            assert_eq!(
+                config["key_value_store"][label]["type"].as_str(),
+                Some("spin")
+            );
+            assert!(config["key_value_store"][label].get("path").is_none());
+        }
+        for label in ["default", "records"] {
+            assert_eq!(
+                config["sqlite_database"][label]["type"].as_str(),
+                Some("spin")
+            );
+        }
+    }
+}
@@ -1,29 +1,49 @@
-mod app_source;
+pub(crate) mod app_source;
+mod fleet;
+mod supervisor;
+mod variables;
 
 use std::{
     collections::HashMap,
     ffi::OsString,
     fmt::Debug,
+    net::SocketAddr,
     path::{Path, PathBuf},
     process::Stdio,
+    sync::Arc,
 };
 
 use anyhow::{anyhow, bail, Context, Result};
 use clap::{CommandFactory, Parser};
 use reqwest::Url;
-use spin_app::locked::LockedApp;
+use spin_app::{locked::LockedApp, MetadataKey};
 use spin_common::ui::quoted_path;
 use spin_loader::FilesMountStrategy;
+use spin_manifest::schema::v2::{HookFailurePolicy, LifecycleHook};
 use spin_oci::OciLoader;
+use spin_telemetry::logs::{LogFormat, LOG_FORMAT_ENV};
+use spin_trigger::admin::AdminAuth;
 use spin_trigger::cli::{LaunchMetadata, SPIN_LOCAL_APP_DIR, SPIN_LOCKED_URL, SPIN_WORKING_DIR};
+use spin_trigger_command::{ON_START_KEY, ON_STOP_KEY};
 use tempfile::TempDir;
 
 use crate::opts::*;
+use crate::output::{print_json, OutputArgs};
+use crate::subprocess::ExitStatusError;
 
-use self::app_source::{AppSource, ResolvedAppSource};
+use super::daemon::{detach, take_detached, DaemonFiles};
+use super::lock::{check_lock, lock_file_path, AppLock};
+
+use self::app_source::{wasm_manifest, AppSource, ResolvedAppSource, DEFAULT_WASM_ROUTE};
+use self::fleet::{read_fleet_file, Fleet, FleetAppConfig};
+use self::supervisor::TriggerSupervisor;
+use self::variables::{missing_variables_env, SavedVariables};
 
 const APPLICATION_OPT: &str = "APPLICATION";
 
+/// The prefix of environment variables which set application variables.
+const VARIABLE_ENV_PREFIX: &str = "SPIN_VARIABLE_";
+
 // If multiple triggers start very close together, there is a race condition
 // where if one trigger fails during startup, other external triggers may
 // not have their cancellation hooked up.  (kill_on_drop doesn't fully solve
@@ -47,7 +67,8 @@ pub struct UpCommand {
     pub help: bool,
 
     /// The application to run. This may be a manifest (spin.toml) file, a
-    /// directory containing a spin.toml file, or a remote registry reference.
+    /// directory containing a spin.toml file, a Wasm (.wasm) file to serve
+    /// over HTTP, or a remote registry reference.
     /// If omitted, it defaults to "spin.toml".
     #[clap(
         name = APPLICATION_OPT,
@@ -78,6 +99,48 @@ pub struct UpCommand {
     )]
     pub registry_source: Option<String>,
 
+    /// Run a single Wasm component as an HTTP application, without a
+    /// manifest. This is the same as `--from` with a .wasm file. The
+    /// component may make any outbound request and read the variables set
+    /// by SPIN_VARIABLE_<NAME> environment variables.
+    #[clap(long = "wasm", value_name = "FILE", group = "source")]
+    pub wasm_source: Option<PathBuf>,
+
+    /// The route on which to serve a Wasm file run without a manifest. The
+    /// default is "/...".
+    #[clap(long = "route", value_name = "ROUTE")]
+    pub route: Option<String>,
+
+    /// Run several applications in this one process, sharing a Wasm engine.
+    /// Give a manifest for each application; each serves HTTP on its own
+    /// port, counting up from 3000, and keeps its state in the .spin
+    /// directory next to its manifest. Options for a single application,
+    /// such as --env or trigger options, do not apply.
+    #[clap(
+        long = "app",
+        value_name = "MANIFEST",
+        multiple_occurrences = true,
+        group = "source"
+    )]
+    pub apps: Vec<PathBuf>,
+
+    /// Run the applications listed in a fleet file in this one process,
+    /// as with --app, choosing each one's name, listen address, state
+    /// directory and runtime config file.
+    #[clap(long = "fleet", value_name = "FILE", group = "source")]
+    pub fleet_file: Option<PathBuf>,
+
+    /// Serve an admin API on this address. For a single application, it
+    /// reports the trigger's status, components and runtime config, changes
+    /// the log level, and drains or shuts down the trigger. With --app or
+    /// --fleet, it also deploys, undeploys, stops, starts and restarts apps.
+    /// The address must be a loopback address, and requests must carry the
+    /// token in SPIN_ADMIN_TOKEN, or else the one generated at startup and
+    /// written to admin-token in the state directory (.spin) of the
+    /// application, or of the current directory with --app or --fleet.
+    #[clap(long = "admin-listen", value_name = "ADDRESS")]
+    pub admin_listen: Option<SocketAddr>,
+
     /// Ignore server certificate errors from a registry
     #[clap(
         name = INSECURE_OPT,
@@ -88,23 +151,48 @@ pub struct UpCommand {
     pub insecure: bool,
 
     /// Pass an environment variable (key=value) to all components of the application.
+    /// This overrides any value from --env-file.
     #[clap(short = 'e', long = "env", parse(try_from_str = parse_env_var))]
     pub env: Vec<(String, String)>,
 
+    /// Pass the environment variables in a file of KEY=VALUE lines, such as
+    /// .env.local, to all components of the application. Variables named
+    /// SPIN_VARIABLE_<NAME> also set application variables, unless already
+    /// set in Spin's environment. Can be used multiple times; later files
+    /// override earlier ones.
+    #[clap(long = "env-file", value_name = "FILE", multiple_occurrences = true)]
+    pub env_files: Vec<PathBuf>,
+
+    /// For local apps, the environment to run in. The manifest's overlay for
+    /// the environment, e.g. `spin.prod.toml` for `prod`, is merged over it.
+    #[clap(long = "environment", value_name = "NAME", env = UP_ENVIRONMENT_ENV)]
+    pub environment: Option<String>,
+
+    /// For local apps, refuse to run if the manifest has keys Spin doesn't
+    /// know, listing each one with the key it was likely meant to be.
+    #[clap(long = "strict", takes_value = false)]
+    pub strict: bool,
+
     /// Temporary directory for the static assets of the components.
-    #[clap(long = "temp", alias = "tmp")]
+    #[clap(long = "temp", alias = "tmp", env = UP_TEMP_DIR_ENV)]
     pub tmp: Option<PathBuf>,
 
     /// Cache directory for downloaded components and assets.
-    #[clap(long)]
+    #[clap(long, env = UP_CACHE_DIR_ENV)]
     pub cache_dir: Option<PathBuf>,
 
+    /// For local apps, fail rather than download component sources which
+    /// aren't in the cache. Registry sources without a pinned digest are
+    /// found by the digest recorded in the lock file (spin.lock).
+    #[clap(long = "offline", takes_value = false)]
+    pub offline: bool,
+
     /// For local apps with directory mounts and no excluded files, mount them directly instead of using a temporary
     /// directory.
     ///
     /// This allows you to update the assets on the host filesystem such that the updates are visible to the guest
     /// without a restart.  This cannot be used with registry apps or apps which use file patterns and/or exclusions.
-    #[clap(long, takes_value = false)]
+    #[clap(long, takes_value = false, env = UP_DIRECT_MOUNTS_ENV)]
     pub direct_mounts: bool,
 
     /// For local apps, specifies to perform `spin build` before running the application.
@@ -113,6 +201,43 @@ pub struct UpCommand {
     #[clap(long, takes_value = false, env = ALWAYS_BUILD_ENV)]
     pub build: bool,
 
+    /// Refuse to run the application unless it matches its lock file
+    /// (spin.lock), as written by `spin lock`.
+    #[clap(long = "locked", takes_value = false)]
+    pub locked: bool,
+
+    /// Refuse to run the application if it violates the rules of this
+    /// policy file, such as a limit on components' memory. Each violation
+    /// is reported.
+    #[clap(long = "policy-file", value_name = "FILE", env = UP_POLICY_FILE_ENV)]
+    pub policy_file: Option<PathBuf>,
+
+    /// Prompt for the values of required variables which have none, hiding
+    /// the input of secrets, and offer to save them in the application's
+    /// .spin directory. Saved values are used by later runs, with or
+    /// without this flag; secrets are saved encrypted.
+    #[clap(long = "prompt-variables", takes_value = false)]
+    pub prompt_variables: bool,
+
+    /// Run the application in the background. Use `spin status`, `spin logs`
+    /// and `spin stop` to manage it.
+    #[clap(long = DETACH_FLAG, takes_value = false)]
+    pub detach: bool,
+
+    #[clap(flatten)]
+    pub output: OutputArgs,
+
+    /// The format of the host's logs: tracing events, HTTP access logs and
+    /// followed component output. With "json", each log line is a JSON
+    /// object with timestamp, level, message, component and trace_id fields.
+    #[clap(
+        long = "log-format",
+        env = LOG_FORMAT_ENV,
+        default_value = "text",
+        possible_values = ["text", "json"]
+    )]
+    pub log_format: LogFormat,
+
     /// All other args, to be passed through to the trigger
     #[clap(hide = true)]
     pub trigger_args: Vec<OsString>,
@@ -123,6 +248,9 @@ impl UpCommand {
         // For displaying help, first print `spin up`'s own usage text, then
         // attempt to load an app and print trigger-type-specific usage.
         let help = self.help;
+        // Trigger processes print the startup summary
+        self.output.apply();
+        std::env::set_var(LOG_FORMAT_ENV, self.log_format.to_string());
         if help {
             Self::command()
                 .name("spin-up")
@@ -141,6 +269,9 @@ impl UpCommand {
     }
 
     async fn run_inner(self) -> Result<()> {
+        if !self.help && (!self.apps.is_empty() || self.fleet_file.is_some()) {
+            return self.run_fleet().await;
+        }
         let app_source = self.app_source();
 
         if app_source == AppSource::None {
@@ -155,6 +286,10 @@ impl UpCommand {
             }
         }
 
+        if self.detach && !self.help && !take_detached() {
+            return detach(&DaemonFiles::new(app_source.local_app_dir()));
+        }
+
         // Get working dir holder and hold on to it for the rest of the function.
         // If the working dir is a temporary dir it will be deleted on drop.
         let working_dir_holder = self.get_canonical_working_dir()?;
@@ -186,55 +321,117 @@ impl UpCommand {
             return Ok(());
         }
 
+        if is_multi && self.admin_listen.is_some() {
+            bail!("The `--admin-listen` option can't be used with an application with more than one trigger type, except with `--app` or `--fleet`");
+        }
+
         if self.build {
             app_source.build().await?;
         }
 
+        let file_env = self.load_env_files()?;
+
         let mut locked_app = self
             .load_resolved_app_source(resolved_app_source, &working_dir)
             .await?;
 
-        self.update_locked_app(&mut locked_app);
-        let locked_url = self.write_locked_app(&locked_app, &working_dir).await?;
+        if self.locked {
+            let AppSource::File(manifest_file) = &app_source else {
+                bail!("The `--locked` option can only be used with a local application");
+            };
+            let lock = AppLock::new(&locked_app)?;
+            check_lock(&lock, &lock_file_path(manifest_file))?;
+        }
+
+        if let Some(policy_file) = &self.policy_file {
+            check_policy(&locked_app, policy_file)?;
+        }
+
+        self.update_locked_app(&mut locked_app, &file_env);
+        let locked_url = self
+            .write_locked_app(&locked_app, &working_dir, "spin.lock")
+            .await?;
+
+        let local_app_dir: Option<PathBuf> = app_source.local_app_dir().map(Into::into);
 
-        let local_app_dir = app_source.local_app_dir().map(Into::into);
+        let mut variables_env = self.variables_env(&file_env);
+        let saved_variables = match &local_app_dir {
+            Some(dir) => Some(SavedVariables::new(DaemonFiles::new(Some(dir)).dir())?),
+            None => None,
+        };
+        let missing_env = missing_variables_env(
+            &locked_app.variables,
+            &variables_env,
+            saved_variables.as_ref(),
+            self.prompt_variables,
+        )?;
+        variables_env.extend(missing_env);
 
         let run_opts = RunTriggerOpts {
             locked_url,
             working_dir,
             local_app_dir,
+            variables_env,
         };
 
-        let trigger_processes = self.start_trigger_processes(trigger_cmds, run_opts).await?;
-        let pids = get_pids(&trigger_processes);
+        self.run_hook(ON_START_KEY, &locked_app, &run_opts).await?;
 
-        set_kill_on_ctrl_c(&pids)?;
+        let supervisor = self
+            .start_trigger_processes(trigger_cmds, run_opts.clone())
+            .await?;
+        let pids = get_pids(supervisor.children());
+        let daemon_files = DaemonFiles::new(app_source.local_app_dir());
+        if let Err(e) = daemon_files.record_triggers(supervisor.processes()) {
+            tracing::warn!("Failed to record trigger processes for `spin status`: {e:#}");
+        }
+        if let Err(e) = daemon_files.serve_control() {
+            tracing::warn!("Failed to open the control socket for `spin stop`: {e:#}");
+        }
 
-        let trigger_tasks = trigger_processes
-            .into_iter()
-            .map(|mut ch| tokio::task::spawn(async move { ch.wait().await }))
-            .collect::<Vec<_>>();
+        set_kill_on_ctrl_c(&pids)?;
 
         if is_multi {
             tokio::time::sleep(MULTI_TRIGGER_LET_ALL_START).await;
         }
 
-        let (first_to_finish, _index, _rest) = futures::future::select_all(trigger_tasks).await;
-
-        if let Ok(process_result) = first_to_finish {
-            let status = process_result?;
-            if !status.success() {
-                if is_multi {
-                    println!("A trigger exited unexpectedly. Terminating.");
-                    kill_child_processes(&pids);
-                }
-                return Err(crate::subprocess::ExitStatusError::new(status).into());
-            }
+        let (_, status) = supervisor.run().await?;
+        self.run_hook(ON_STOP_KEY, &locked_app, &run_opts).await?;
+        if !status.success() {
+            return Err(crate::subprocess::ExitStatusError::new(status).into());
         }
 
         Ok(())
     }
 
+    /// Runs the applications given with `--app` or `--fleet` in this process.
+    async fn run_fleet(self) -> Result<()> {
+        let apps = match &self.fleet_file {
+            Some(path) => read_fleet_file(path)?,
+            None => self
+                .apps
+                .iter()
+                .map(FleetAppConfig::from_manifest)
+                .collect(),
+        };
+        let fleet = Arc::new(Fleet::new(apps).await?);
+        fleet.start_all().await?;
+        let admin = match self.admin_listen {
+            Some(addr) => {
+                let listener = spin_trigger::admin::bind(addr).await?;
+                // A generated token is kept in the state directory of the
+                // current directory, where `spin deploy-local` looks for it
+                let auth = AdminAuth::from_env(Some(DaemonFiles::new(None).dir()))?;
+                Some(tokio::spawn(fleet.clone().serve_admin(listener, auth)))
+            }
+            None => None,
+        };
+        let result = fleet.run().await;
+        if let Some(admin) = admin {
+            admin.abort();
+        }
+        result
+    }
+
     fn get_canonical_working_dir(&self) -> Result<WorkingDirectory, anyhow::Error> {
         let working_dir_holder = match &self.tmp {
             None => WorkingDirectory::Temporary(TempDir::with_prefix("spinup-")?),
@@ -271,10 +468,10 @@ impl UpCommand {
     }
 
     async fn start_trigger_processes(
-        self,
+        &self,
         trigger_cmds: Vec<Vec<String>>,
         run_opts: RunTriggerOpts,
-    ) -> anyhow::Result<Vec<tokio::process::Child>> {
+    ) -> anyhow::Result<TriggerSupervisor> {
         let is_multi = trigger_cmds.len() > 1;
 
         let trigger_args = self.group_trigger_args();
@@ -291,6 +488,10 @@ impl UpCommand {
         };
 
         if let Some(trigger_metas) = trigger_metas.as_ref() {
+            for (trigger_cmd, meta) in trigger_metas {
+                meta.ensure_compatible()
+                    .with_context(|| format!("Cannot run trigger {:?}", trigger_cmd.join(" ")))?;
+            }
             for group in &trigger_args {
                 let is_accepted = trigger_metas.values().any(|m| m.is_group_match(group));
                 if !is_accepted {
@@ -299,7 +500,7 @@ impl UpCommand {
             }
         }
 
-        let mut trigger_processes = Vec::with_capacity(trigger_cmds.len());
+        let mut supervisor = TriggerSupervisor::default();
 
         for cmd in trigger_cmds {
             let meta = trigger_metas.as_ref().and_then(|ms| ms.get(&cmd));
@@ -311,7 +512,7 @@ impl UpCommand {
                 .start_trigger(cmd.clone(), Some(run_opts.clone()), &trigger_args)
                 .await
                 .context("Failed to start trigger process")?;
-            trigger_processes.push(child);
+            supervisor.add(trigger_name(&cmd), child);
 
             if is_multi {
                 // Allow time for the child `spin` process to launch the trigger
@@ -321,7 +522,49 @@ impl UpCommand {
             }
         }
 
-        Ok(trigger_processes)
+        Ok(supervisor)
+    }
+
+    /// Runs the app's `on_start` or `on_stop` hook component, if it has one,
+    /// to completion with the command trigger. Fails if the component fails
+    /// and its failure policy is to abort.
+    async fn run_hook(
+        &self,
+        key: MetadataKey<LifecycleHook>,
+        locked_app: &LockedApp,
+        run_opts: &RunTriggerOpts,
+    ) -> Result<()> {
+        let Some(hook) = locked_app.get_metadata(key)? else {
+            return Ok(());
+        };
+        let name = key.as_ref();
+        let hook_app = spin_trigger_command::hook_app(locked_app, &hook);
+        let locked_url = self
+            .write_locked_app(&hook_app, &run_opts.working_dir, &format!("{name}.lock"))
+            .await?;
+        let opts = RunTriggerOpts {
+            locked_url,
+            ..run_opts.clone()
+        };
+
+        let status = self
+            .start_trigger(trigger_command("command"), Some(opts), &[])
+            .await
+            .with_context(|| format!("Failed to start `{name}` component"))?
+            .wait()
+            .await?;
+        if status.success() {
+            return Ok(());
+        }
+        match hook.on_failure {
+            HookFailurePolicy::Abort => {
+                bail!("`{name}` component `{}` failed ({status})", hook.component)
+            }
+            HookFailurePolicy::Continue => {
+                terminal::warn!("`{name}` component `{}` failed ({status})", hook.component);
+                Ok(())
+            }
+        }
     }
 
     async fn start_trigger(
@@ -339,15 +582,23 @@ impl UpCommand {
             locked_url,
             working_dir,
             local_app_dir,
+            variables_env,
         }) = opts
         {
             cmd.env(SPIN_LOCKED_URL, locked_url)
                 .env(SPIN_WORKING_DIR, &working_dir)
+                .envs(variables_env)
                 .args(trigger_args);
 
             if let Some(local_app_dir) = local_app_dir {
                 cmd.env(SPIN_LOCAL_APP_DIR, local_app_dir);
             }
+            if let Some(admin_listen) = self.admin_listen {
+                cmd.env(
+                    spin_trigger::cli::env::ADMIN_LISTEN,
+                    admin_listen.to_string(),
+                );
+            }
 
             cmd.kill_on_drop(true);
         } else {
@@ -361,12 +612,25 @@ impl UpCommand {
     }
 
     fn app_source(&self) -> AppSource {
-        match (&self.app_source, &self.file_source, &self.registry_source) {
-            (None, None, None) => self.default_manifest_or_none(),
-            (Some(source), None, None) => AppSource::infer_source(source),
-            (None, Some(file), None) => AppSource::infer_file_source(file.to_owned()),
-            (None, None, Some(reference)) => AppSource::OciRegistry(reference.to_owned()),
+        let source = match (
+            &self.app_source,
+            &self.file_source,
+            &self.registry_source,
+            &self.wasm_source,
+        ) {
+            (None, None, None, None) => self.default_manifest_or_none(),
+            (Some(source), None, None, None) => AppSource::infer_source(source),
+            (None, Some(file), None, None) => AppSource::infer_file_source(file.to_owned()),
+            (None, None, Some(reference), None) => AppSource::OciRegistry(reference.to_owned()),
+            (None, None, None, Some(wasm)) => AppSource::infer_wasm_source(wasm.to_owned()),
             _ => AppSource::unresolvable("More than one application source was specified"),
+        };
+        match source {
+            AppSource::Wasm(_) | AppSource::Unresolvable(_) => source,
+            _ if self.route.is_some() => AppSource::unresolvable(
+                "The `--route` option can only be used when running a Wasm file",
+            ),
+            _ => source,
         }
     }
 
@@ -396,8 +660,9 @@ impl UpCommand {
         &self,
         locked_app: &LockedApp,
         working_dir: &Path,
+        file_name: &str,
     ) -> Result<String, anyhow::Error> {
-        let locked_path = working_dir.join("spin.lock");
+        let locked_path = working_dir.join(file_name);
         let locked_app_contents =
             serde_json::to_vec_pretty(&locked_app).context("failed to serialize locked app")?;
         tokio::fs::write(&locked_path, locked_app_contents)
@@ -418,13 +683,32 @@ impl UpCommand {
         working_dir: &Path,
     ) -> anyhow::Result<ResolvedAppSource> {
         Ok(match &app_source {
-            AppSource::File(path) => ResolvedAppSource::File {
-                manifest_path: path.clone(),
-                manifest: spin_manifest::manifest_from_file(path)?,
-            },
+            AppSource::File(path) => {
+                if self.strict {
+                    spin_loader::check_manifest_strictly(path, self.environment.as_deref())?;
+                }
+                ResolvedAppSource::File {
+                    manifest_path: path.clone(),
+                    manifest: spin_loader::manifest_from_file(path, self.environment.as_deref())?,
+                }
+            }
+            AppSource::Wasm(wasm) => {
+                let route = self.route.as_deref().unwrap_or(DEFAULT_WASM_ROUTE);
+                let manifest = wasm_manifest(wasm, route, self.variable_names()?)?;
+                let manifest_path = working_dir.join("spin.toml");
+                std::fs::write(&manifest_path, &manifest)
+                    .with_context(|| format!("Failed to write {}", quoted_path(&manifest_path)))?;
+                ResolvedAppSource::File {
+                    manifest_path,
+                    manifest: spin_manifest::manifest_from_str(&manifest)?,
+                }
+            }
             // TODO: We could make the `--help` experience a little faster if
             // we could fetch just the locked app JSON at this stage.
             AppSource::OciRegistry(reference) => {
+                if self.offline {
+                    bail!("Registry applications cannot be run with `--offline`");
+                }
                 let mut client = spin_oci::Client::new(self.insecure, self.cache_dir.clone())
                     .await
                     .context("cannot create registry client")?;
@@ -452,26 +736,80 @@ impl UpCommand {
                 } else {
                     FilesMountStrategy::Copy(working_dir.join("assets"))
                 };
-                spin_loader::from_file(&manifest_path, files_mount_strategy, self.cache_dir.clone())
-                    .await
-                    .with_context(|| {
-                        format!(
-                            "Failed to load manifest from {}",
-                            quoted_path(&manifest_path)
-                        )
-                    })
+                let options = spin_loader::LoadOptions {
+                    environment: self.environment.as_deref(),
+                    offline: self.offline,
+                };
+                spin_loader::from_file_with_options(
+                    &manifest_path,
+                    files_mount_strategy,
+                    self.cache_dir.clone(),
+                    options,
+                )
+                .await
+                .with_context(|| {
+                    format!(
+                        "Failed to load manifest from {}",
+                        quoted_path(&manifest_path)
+                    )
+                })
             }
             ResolvedAppSource::OciRegistry { locked_app } => Ok(locked_app),
         }
     }
 
-    fn update_locked_app(&self, locked_app: &mut LockedApp) {
-        // Apply --env to component environments
-        if !self.env.is_empty() {
-            for component in locked_app.components.iter_mut() {
-                component.env.extend(self.env.iter().cloned());
+    /// Loads the variables from --env-file, in order of increasing
+    /// precedence.
+    fn load_env_files(&self) -> Result<Vec<(String, String)>> {
+        let mut vars = vec![];
+        for path in &self.env_files {
+            let iter = dotenvy::from_path_iter(path)
+                .with_context(|| format!("Failed to read env file {}", quoted_path(path)))?;
+            for var in iter {
+                vars.push(
+                    var.with_context(|| format!("Failed to parse env file {}", quoted_path(path)))?,
+                );
             }
         }
+        Ok(vars)
+    }
+
+    fn update_locked_app(&self, locked_app: &mut LockedApp, file_env: &[(String, String)]) {
+        // Apply --env-file and then --env to component environments
+        let env = file_env.iter().chain(&self.env);
+        for component in locked_app.components.iter_mut() {
+            component.env.extend(env.clone().cloned());
+        }
+    }
+
+    /// The variables from --env-file and --env which the trigger's
+    /// environment variable provider should resolve. --env takes precedence
+    /// over Spin's environment, which takes precedence over files.
+    fn variables_env(&self, file_env: &[(String, String)]) -> Vec<(String, String)> {
+        let from_files = file_env
+            .iter()
+            .filter(|(key, _)| std::env::var_os(key).is_none());
+        from_files
+            .chain(&self.env)
+            .filter(|(key, _)| key.starts_with(VARIABLE_ENV_PREFIX))
+            .cloned()
+            .collect()
+    }
+
+    /// The names of the application variables set by SPIN_VARIABLE_<NAME>
+    /// environment variables, in Spin's environment, --env-file or --env.
+    fn variable_names(&self) -> Result<Vec<String>> {
+        let file_env = self.load_env_files()?;
+        let mut names: Vec<String> = std::env::vars()
+            .chain(self.variables_env(&file_env))
+            .filter_map(|(key, _)| {
+                key.strip_prefix(VARIABLE_ENV_PREFIX)
+                    .map(|name| name.to_lowercase())
+            })
+            .collect();
+        names.sort();
+        names.dedup();
+        Ok(names)
     }
 
     fn group_trigger_args(&self) -> Vec<Vec<&OsString>> {
@@ -504,6 +842,35 @@ impl UpCommand {
     }
 }
 
+/// Refuses an app which violates the policy in `policy_file`, reporting each
+/// violation.
+fn check_policy(locked_app: &LockedApp, policy_file: &Path) -> Result<()> {
+    #[derive(serde::Serialize)]
+    struct PolicyOutput {
+        policy_violations: Vec<spin_policy::Violation>,
+    }
+
+    let policy = spin_policy::Policy::from_file(policy_file)?;
+    let violations = policy.evaluate(locked_app)?;
+    if violations.is_empty() {
+        return Ok(());
+    }
+    if terminal::output_format().is_json() {
+        print_json(&PolicyOutput {
+            policy_violations: violations,
+        })?;
+        return Err(ExitStatusError::with_code(1).into());
+    }
+    for violation in &violations {
+        terminal::error!("{violation}");
+    }
+    bail!(
+        "The application violates {} rule(s) of the policy in {}",
+        violations.len(),
+        quoted_path(policy_file)
+    )
+}
+
 fn is_flag_arg(arg: &OsString) -> bool {
     if let Some(s) = arg.to_str() {
         s.starts_with('-')
@@ -527,16 +894,17 @@ fn set_kill_on_ctrl_c(pids: &[nix::unistd::Pid]) -> Result<(), anyhow::Error> {
 }
 
 #[cfg(windows)]
-fn get_pids(_trigger_processes: &[tokio::process::Child]) -> Vec<usize> {
+fn get_pids<'a>(_trigger_processes: impl Iterator<Item = &'a tokio::process::Child>) -> Vec<usize> {
     vec![]
 }
 
 #[cfg(not(windows))]
-fn get_pids(trigger_processes: &[tokio::process::Child]) -> Vec<nix::unistd::Pid> {
+fn get_pids<'a>(
+    trigger_processes: impl Iterator<Item = &'a tokio::process::Child>,
+) -> Vec<nix::unistd::Pid> {
     use itertools::Itertools;
     // https://github.com/nix-rust/nix/issues/656
     trigger_processes
-        .iter()
         .flat_map(|child| child.id().map(|id| nix::unistd::Pid::from_raw(id as i32)))
         .collect_vec()
 }
@@ -559,6 +927,8 @@ struct RunTriggerOpts {
     locked_url: String,
     working_dir: PathBuf,
     local_app_dir: Option<PathBuf>,
+    /// Environment variables for application variables
+    variables_env: Vec<(String, String)>,
 }
 
 enum WorkingDirectory {
@@ -621,6 +991,12 @@ fn trigger_command(trigger_type: &str) -> Vec<String> {
     vec!["trigger".to_owned(), trigger_type.to_owned()]
 }
 
+/// The name by which a trigger process is reported: the trigger type for
+/// built-in triggers, or the plugin name.
+fn trigger_name(trigger_cmd: &[String]) -> &str {
+    trigger_cmd.last().map(String::as_str).unwrap_or_default()
+}
+
 fn trigger_command_for_resolved_app_source(
     resolved: &ResolvedAppSource,
 ) -> Result<Vec<Vec<String>>> {
@@ -628,7 +1004,10 @@ fn trigger_command_for_resolved_app_source(
     trigger_type
         .iter()
         .map(|&t| match t {
-            "http" | "redis" => Ok(trigger_command(t)),
+            "http" | "redis" | "sqs" | "nats" | "command" | "file-watch" | "grpc" => {
+                Ok(trigger_command(t))
+            }
+            "kafka" if cfg!(feature = "kafka") => Ok(trigger_command(t)),
             _ => {
                 let cmd = resolve_trigger_plugin(t)?;
                 Ok(vec![cmd])
@@ -758,6 +1137,53 @@ mod test {
         assert!(matches!(source, AppSource::Unresolvable(_)));
     }
 
+    #[test]
+    fn can_run_wasm_files_without_manifest() {
+        let dir = tempfile::tempdir().unwrap();
+        let wasm = dir.path().join("My_Handler.wasm");
+        std::fs::write(&wasm, b"\0asm").unwrap();
+
+        let source = UpCommand {
+            app_source: Some(wasm.to_string_lossy().into_owned()),
+            ..Default::default()
+        }
+        .app_source();
+        assert_eq!(AppSource::Wasm(wasm.clone()), source);
+
+        let source = UpCommand {
+            wasm_source: Some(wasm.clone()),
+            route: Some("/hello/...".to_owned()),
+            ..Default::default()
+        }
+        .app_source();
+        assert_eq!(AppSource::Wasm(wasm.clone()), source);
+
+        let manifest = wasm_manifest(&wasm, "/hello/...", ["api_key".to_owned()]).unwrap();
+        let manifest = spin_manifest::manifest_from_str(&manifest).unwrap();
+        let id = spin_manifest::schema::v2::KebabId::try_from("my-handler".to_owned()).unwrap();
+        let variable = spin_manifest::schema::v2::SnakeId::try_from("api_key".to_owned()).unwrap();
+        assert_eq!(
+            manifest.components[&id].variables[&variable],
+            "{{ api_key }}"
+        );
+        assert!(manifest.variables[&variable].required);
+        assert_eq!(
+            manifest.triggers["http"][0].config["route"].as_str(),
+            Some("/hello/...")
+        );
+    }
+
+    #[test]
+    fn route_requires_wasm_source() {
+        let source = UpCommand {
+            app_source: Some(repo_path("examples/http-rust")),
+            route: Some("/...".to_owned()),
+            ..Default::default()
+        }
+        .app_source();
+        assert!(matches!(source, AppSource::Unresolvable(_)));
+    }
+
     #[test]
     fn parses_untyped_source() {
         UpCommand::try_parse_from(["up", "-f", "ghcr.io/example/test:v1"])
@@ -804,6 +1230,50 @@ mod test {
             .expect("Failed to parse implicit source with trigger option");
     }
 
+    #[test]
+    fn later_env_files_and_env_flags_take_precedence() {
+        let dir = tempfile::tempdir().unwrap();
+        let base = dir.path().join(".env");
+        let local = dir.path().join(".env.local");
+        std::fs::write(
+            &base,
+            "GREETING=hello\nSPIN_VARIABLE_TEST_ENV_FILE_A=base\n",
+        )
+        .unwrap();
+        std::fs::write(
+            &local,
+            "# Local overrides\nGREETING=hi\nSPIN_VARIABLE_TEST_ENV_FILE_B=\"local\"\n",
+        )
+        .unwrap();
+
+        let cmd = UpCommand::try_parse_from([
+            "up",
+            "--env-file",
+            base.to_str().unwrap(),
+            "--env-file",
+            local.to_str().unwrap(),
+            "-e",
+            "SPIN_VARIABLE_TEST_ENV_FILE_B=flag",
+        ])
+        .unwrap();
+        let file_env = cmd.load_env_files().unwrap();
+        let env: HashMap<_, _> = file_env.iter().chain(&cmd.env).cloned().collect();
+        assert_eq!(env["GREETING"], "hi");
+        assert_eq!(env["SPIN_VARIABLE_TEST_ENV_FILE_B"], "flag");
+
+        let variables_env = cmd.variables_env(&file_env);
+        let variables: HashMap<_, _> = variables_env.into_iter().collect();
+        assert_eq!(variables["SPIN_VARIABLE_TEST_ENV_FILE_A"], "base");
+        assert_eq!(variables["SPIN_VARIABLE_TEST_ENV_FILE_B"], "flag");
+        assert!(!variables.contains_key("GREETING"));
+    }
+
+    #[test]
+    fn missing_env_files_are_errors() {
+        let cmd = UpCommand::try_parse_from(["up", "--env-file", "/no/such/.env"]).unwrap();
+        assert!(cmd.load_env_files().is_err());
+    }
+
     #[test]
     fn group_no_args_is_empty() {
         let cmd = UpCommand::try_parse_from(["up"]).unwrap();
@@ -3,15 +3,20 @@ use std::{
     path::{Path, PathBuf},
 };
 
-use anyhow::ensure;
+use anyhow::{ensure, Context};
 use spin_common::ui::quoted_path;
 use spin_locked_app::locked::LockedApp;
-use spin_manifest::schema::v2::AppManifest;
+use spin_manifest::schema::v2::{AppManifest, KebabId, SnakeId};
+
+/// The route of a Wasm file run without a manifest, unless given.
+pub const DEFAULT_WASM_ROUTE: &str = "/...";
 
 /// A source from which an App may be loaded.
 #[derive(Debug, PartialEq, Eq)]
 pub enum AppSource {
     File(PathBuf),
+    /// A single HTTP component, run without a manifest
+    Wasm(PathBuf),
     OciRegistry(String),
     Unresolvable(String),
     None,
@@ -30,12 +35,25 @@ impl AppSource {
     }
 
     pub fn infer_file_source(path: impl Into<PathBuf>) -> Self {
-        match spin_common::paths::resolve_manifest_file_path(path.into()) {
+        let path = path.into();
+        if path.is_file() && path.extension().is_some_and(|ext| ext == "wasm") {
+            return Self::Wasm(path);
+        }
+        match spin_common::paths::resolve_manifest_file_path(path) {
             Ok(file) => Self::File(file),
             Err(e) => Self::Unresolvable(e.to_string()),
         }
     }
 
+    pub fn infer_wasm_source(path: impl Into<PathBuf>) -> Self {
+        let path = path.into();
+        if path.is_file() {
+            Self::Wasm(path)
+        } else {
+            Self::Unresolvable(format!("Wasm file {} not found", quoted_path(&path)))
+        }
+    }
+
     pub fn unresolvable(message: impl Into<String>) -> Self {
         Self::Unresolvable(message.into())
     }
@@ -62,6 +80,7 @@ impl std::fmt::Display for AppSource {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             Self::File(path) => write!(f, "local app {}", quoted_path(path)),
+            Self::Wasm(path) => write!(f, "Wasm file {}", quoted_path(path)),
             Self::OciRegistry(reference) => write!(f, "remote app {reference:?}"),
             Self::Unresolvable(s) => write!(f, "unknown app source: {s:?}"),
             Self::None => write!(f, "<no source>"),
@@ -69,6 +88,85 @@ impl std::fmt::Display for AppSource {
     }
 }
 
+/// Synthesizes the manifest of an app which serves a Wasm file on a route.
+/// The component may make any outbound request, use the default key-value
+/// store and SQLite database, and read the application variables set by
+/// SPIN_VARIABLE_<NAME> environment variables.
+pub fn wasm_manifest(
+    wasm: &Path,
+    route: &str,
+    variable_names: impl IntoIterator<Item = String>,
+) -> anyhow::Result<String> {
+    let source = wasm
+        .canonicalize()
+        .with_context(|| format!("Failed to resolve {}", quoted_path(wasm)))?;
+    let stem = wasm
+        .file_stem()
+        .map(|s| s.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let id = kebab_id(&stem);
+
+    let variables: Vec<String> = variable_names
+        .into_iter()
+        .filter(|name| SnakeId::try_from(name.clone()).is_ok())
+        .collect();
+
+    let mut manifest = format!(
+        "spin_manifest_version = 2\n\n\
+        [application]\n\
+        name = {name}\n\n",
+        name = toml_string(&id),
+    );
+    if !variables.is_empty() {
+        manifest.push_str("[variables]\n");
+        for name in &variables {
+            manifest.push_str(&format!("{name} = {{ required = true }}\n"));
+        }
+        manifest.push('\n');
+    }
+    manifest.push_str(&format!(
+        "[[trigger.http]]\n\
+        route = {route}\n\
+        component = {id_str}\n\n\
+        [component.{id}]\n\
+        source = {source}\n\
+        allowed_outbound_hosts = [\"*://*:*\"]\n\
+        key_value_stores = [\"default\"]\n\
+        sqlite_databases = [\"default\"]\n",
+        route = toml_string(route),
+        id_str = toml_string(&id),
+        source = toml_string(&source.to_string_lossy()),
+    ));
+    if !variables.is_empty() {
+        manifest.push_str(&format!("[component.{id}.variables]\n"));
+        for name in &variables {
+            manifest.push_str(&format!("{name} = \"{{{{ {name} }}}}\"\n"));
+        }
+    }
+    Ok(manifest)
+}
+
+/// A component ID based on a file name.
+fn kebab_id(name: &str) -> String {
+    let mut id = String::new();
+    for c in name.to_lowercase().chars() {
+        if c.is_ascii_lowercase() || c.is_ascii_digit() {
+            id.push(c);
+        } else if !id.is_empty() && !id.ends_with('-') {
+            id.push('-');
+        }
+    }
+    let id = id.trim_end_matches('-').to_owned();
+    match KebabId::try_from(id) {
+        Ok(id) => id.into(),
+        Err(_) => "handler".to_owned(),
+    }
+}
+
+fn toml_string(s: &str) -> String {
+    toml::Value::String(s.to_owned()).to_string()
+}
+
 /// This represents a "partially loaded" source which has enough information to
 /// dispatch to the correct trigger executor but hasn't (necessarily) gone
 /// through full validation / loading yet.
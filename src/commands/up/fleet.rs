@@ -0,0 +1,846 @@
+//! Running several applications in one process, with `spin up --app` or a
+//! fleet file.
+//!
+//! The apps' triggers share one Wasm engine, and so its pool of instance
+//! memory and its compiled code, while each app keeps its own state
+//! directory and HTTP listen address. With `--admin-listen`, apps can be
+//! deployed, undeployed, stopped, started and restarted without restarting
+//! the process:
+//!
+//! - `GET /apps` lists the apps and their status, and `GET /apps/<name>` one
+//!   app's status
+//! - `POST /apps` deploys and starts an app, given as a JSON object with the
+//!   fields of an app in a fleet file; relative paths are relative to the
+//!   current directory
+//! - `DELETE /apps/<name>` stops an app and undeploys it
+//! - `POST /apps/<name>/stop` stops an app's triggers
+//! - `POST /apps/<name>/start` loads an app from its manifest and starts its
+//!   triggers
+//! - `POST /apps/<name>/restart` stops an app and starts it again, picking up
+//!   any changes to its manifest or components
+//! - `POST /apps/<name>/upgrade` loads a new version of an app, from the
+//!   `manifest` in an optional JSON body or else from its manifest, and
+//!   switches its HTTP requests to it once it is ready. Requests already
+//!   being handled by the old version are given time to finish, and
+//!   connections stay open throughout. Other triggers are restarted.
+//! - `GET /log-level` and `PUT /log-level` read and replace the filter of the
+//!   logs written to stderr, with a body in the `RUST_LOG` format
+//! - `POST /shutdown` stops every app and exits
+//!
+//! As with a single app's admin API, requests must carry the admin token
+//! from `SPIN_ADMIN_TOKEN` as a bearer token.
+//!
+//! A fleet file lists the apps to run as TOML:
+//!
+//! ```toml
+//! [[app]]
+//! name = "shop"                      # defaults to the app's name
+//! manifest = "shop/spin.toml"
+//! listen = "127.0.0.1:3001"          # defaults to the next free port from 3000
+//! state_dir = "/var/lib/spin/shop"   # defaults to .spin next to the manifest
+//! runtime_config_file = "shop/runtime-config.toml"
+//! ```
+//!
+//! Relative paths are relative to the fleet file's directory.
+
+use std::collections::{BTreeMap, HashSet};
+use std::convert::Infallible;
+use std::net::{Ipv4Addr, SocketAddr};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use anyhow::{bail, Context, Result};
+use http_body_util::Full;
+use hyper::{
+    body::{Bytes, Incoming},
+    server::conn::http1,
+    service::service_fn,
+    Method, Request, Response, StatusCode,
+};
+use hyper_util::rt::TokioIo;
+use percent_encoding::percent_decode_str;
+use serde::{Deserialize, Serialize};
+use spin_common::ui::quoted_path;
+use spin_runtime::{
+    App, HttpServer, HttpTrigger, RedisTrigger, RunningTrigger, RuntimeConfig, SharedEngine,
+};
+use spin_trigger::admin::{read_body, AdminAuth};
+use tokio::net::TcpListener;
+use tokio::sync::{Mutex, Notify};
+use tokio::time::Duration;
+
+/// The first port given to apps without a listen address.
+const FIRST_DEFAULT_PORT: u16 = 3000;
+
+/// How often apps are checked for triggers which have stopped by themselves.
+const REAP_INTERVAL: Duration = Duration::from_secs(1);
+
+/// How long the old version of an upgraded app is given to finish the
+/// requests it is handling.
+const DRAIN_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// The apps to run, as listed in a fleet file.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct FleetFile {
+    #[serde(rename = "app", default)]
+    apps: Vec<FleetAppConfig>,
+}
+
+/// An app to run, as listed in a fleet file or given with `--app`.
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct FleetAppConfig {
+    name: Option<String>,
+    manifest: PathBuf,
+    listen: Option<SocketAddr>,
+    state_dir: Option<PathBuf>,
+    runtime_config_file: Option<PathBuf>,
+}
+
+impl FleetAppConfig {
+    pub fn from_manifest(manifest: impl Into<PathBuf>) -> Self {
+        Self {
+            manifest: manifest.into(),
+            ..Default::default()
+        }
+    }
+
+    fn resolve_paths(mut self, base: &Path) -> Self {
+        self.manifest = base.join(&self.manifest);
+        self.state_dir = self.state_dir.map(|dir| base.join(dir));
+        self.runtime_config_file = self.runtime_config_file.map(|file| base.join(file));
+        self
+    }
+}
+
+/// Reads the apps listed in a fleet file.
+pub(crate) fn read_fleet_file(path: &Path) -> Result<Vec<FleetAppConfig>> {
+    let contents = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read fleet file {}", quoted_path(path)))?;
+    let fleet: FleetFile = toml::from_str(&contents)
+        .with_context(|| format!("Failed to parse fleet file {}", quoted_path(path)))?;
+    let base = path.parent().unwrap_or(Path::new("."));
+    Ok(fleet
+        .apps
+        .into_iter()
+        .map(|app| app.resolve_paths(base))
+        .collect())
+}
+
+/// The apps running in this process.
+pub(crate) struct Fleet {
+    engine: SharedEngine,
+    apps: std::sync::Mutex<Apps>,
+    // Notified when a shutdown is requested through the admin API
+    shutdown: Notify,
+}
+
+type Apps = BTreeMap<String, Arc<FleetApp>>;
+
+/// An app in a fleet, with everything it needs to be started again.
+struct FleetApp {
+    // Replaced when the app is upgraded from another manifest
+    manifest: std::sync::Mutex<PathBuf>,
+    listen: SocketAddr,
+    state_dir: PathBuf,
+    runtime_config_file: Option<PathBuf>,
+    state: Mutex<AppState>,
+}
+
+enum AppState {
+    Running(Triggers),
+    Stopped,
+    Failed(String),
+}
+
+/// The triggers of a running app. The HTTP trigger is kept apart so that
+/// the app it serves can be replaced.
+struct Triggers {
+    http: Option<HttpServer>,
+    others: Vec<RunningTrigger>,
+}
+
+/// An app's triggers, built but not yet started.
+struct BuiltApp {
+    http: Option<HttpTrigger>,
+    redis: Option<RedisTrigger>,
+}
+
+/// The body of an upgrade request.
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Upgrade {
+    manifest: Option<PathBuf>,
+}
+
+/// The status of an app, as reported by the admin API.
+#[derive(Debug, Serialize)]
+pub(crate) struct AppStatus {
+    name: String,
+    manifest: PathBuf,
+    listen: SocketAddr,
+    state_dir: PathBuf,
+    status: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+impl Fleet {
+    /// Adds the apps, without starting them. Apps without a listen address
+    /// are given the next port from 3000 which no other app listens on.
+    pub async fn new(configs: Vec<FleetAppConfig>) -> Result<Self> {
+        if configs.is_empty() {
+            bail!("No applications to run");
+        }
+        let reserved_ports: HashSet<u16> = configs
+            .iter()
+            .filter_map(|config| Some(config.listen?.port()))
+            .collect();
+        let mut apps = Apps::new();
+        for config in configs {
+            let (name, config) = config.resolve().await?;
+            insert_app(&mut apps, name, config, &reserved_ports)?;
+        }
+        Ok(Self {
+            engine: SharedEngine::new()?,
+            apps: std::sync::Mutex::new(apps),
+            shutdown: Notify::new(),
+        })
+    }
+
+    fn app(&self, name: &str) -> Option<Arc<FleetApp>> {
+        self.apps.lock().unwrap().get(name).cloned()
+    }
+
+    fn apps(&self) -> Vec<(String, Arc<FleetApp>)> {
+        let apps = self.apps.lock().unwrap();
+        apps.iter()
+            .map(|(name, app)| (name.clone(), app.clone()))
+            .collect()
+    }
+
+    /// Starts every app, failing if any fails to start.
+    pub async fn start_all(&self) -> Result<()> {
+        for (name, app) in self.apps() {
+            app.start(&name, &self.engine)
+                .await
+                .with_context(|| format!("Failed to start app {name:?}"))?;
+        }
+        Ok(())
+    }
+
+    /// Stops every app.
+    pub async fn stop_all(&self) -> Result<()> {
+        for (name, app) in self.apps() {
+            app.stop(&name).await?;
+        }
+        Ok(())
+    }
+
+    /// Adds an app to the running fleet and starts it. The app is removed
+    /// again if it fails to start.
+    pub async fn deploy(&self, config: FleetAppConfig) -> Result<AppStatus> {
+        let (name, config) = config.resolve().await?;
+        let app = insert_app(
+            &mut self.apps.lock().unwrap(),
+            name.clone(),
+            config,
+            &HashSet::new(),
+        )?;
+        let result = app.start(&name, &self.engine).await;
+        if result.is_err() {
+            self.apps.lock().unwrap().remove(&name);
+        }
+        result
+    }
+
+    /// Stops an app and removes it from the fleet. Returns `None` if there
+    /// is no such app.
+    pub async fn undeploy(&self, name: &str) -> Option<Result<AppStatus>> {
+        let app = self.apps.lock().unwrap().remove(name)?;
+        Some(app.stop(name).await)
+    }
+
+    /// Starts an app's triggers, if it isn't running. Returns `None` if
+    /// there is no such app.
+    pub async fn start(&self, name: &str) -> Option<Result<AppStatus>> {
+        Some(self.app(name)?.start(name, &self.engine).await)
+    }
+
+    /// Stops an app's triggers, if it is running. Returns `None` if there is
+    /// no such app.
+    pub async fn stop(&self, name: &str) -> Option<Result<AppStatus>> {
+        Some(self.app(name)?.stop(name).await)
+    }
+
+    /// Stops an app and starts it again from its manifest. Returns `None` if
+    /// there is no such app.
+    pub async fn restart(&self, name: &str) -> Option<Result<AppStatus>> {
+        let app = self.app(name)?;
+        if let Err(err) = app.stop(name).await {
+            return Some(Err(err));
+        }
+        Some(app.start(name, &self.engine).await)
+    }
+
+    /// Loads a new version of an app and switches to it without closing its
+    /// listener, from `manifest` if given. Returns `None` if there is no
+    /// such app.
+    pub async fn upgrade(
+        &self,
+        name: &str,
+        manifest: Option<PathBuf>,
+    ) -> Option<Result<AppStatus>> {
+        Some(self.app(name)?.upgrade(name, manifest, &self.engine).await)
+    }
+
+    /// Returns the status of every app.
+    pub async fn status(&self) -> Vec<AppStatus> {
+        let mut statuses = vec![];
+        for (name, app) in self.apps() {
+            statuses.push(app.status(&name).await);
+        }
+        statuses
+    }
+
+    /// Watches the apps until Ctrl+C or a shutdown through the admin API,
+    /// then stops them.
+    pub async fn run(self: Arc<Self>) -> Result<()> {
+        let mut reap = tokio::time::interval(REAP_INTERVAL);
+        loop {
+            tokio::select! {
+                _ = reap.tick() => {
+                    for (name, app) in self.apps() {
+                        app.reap(&name).await;
+                    }
+                }
+                result = tokio::signal::ctrl_c() => {
+                    result.context("Failed to listen for Ctrl+C")?;
+                    break;
+                }
+                _ = self.shutdown.notified() => {
+                    tracing::info!("Shutdown requested through the admin API");
+                    break;
+                }
+            }
+        }
+        self.stop_all().await
+    }
+
+    /// Serves the admin API on `listener`, from `spin_trigger::admin::bind`,
+    /// to clients holding the token of `auth`.
+    pub async fn serve_admin(
+        self: Arc<Self>,
+        listener: TcpListener,
+        auth: AdminAuth,
+    ) -> Result<()> {
+        let addr = listener.local_addr()?;
+        terminal::text!("Serving the fleet admin API on http://{addr}/apps");
+        loop {
+            let (stream, _) = listener.accept().await?;
+            let fleet = self.clone();
+            let auth = auth.clone();
+            tokio::spawn(async move {
+                let service = service_fn(move |req| {
+                    let fleet = fleet.clone();
+                    let auth = auth.clone();
+                    async move {
+                        Ok::<_, Infallible>(match auth.check(&req) {
+                            Ok(()) => fleet.respond(req).await,
+                            Err(refusal) => refusal,
+                        })
+                    }
+                });
+                if let Err(err) = http1::Builder::new()
+                    .serve_connection(TokioIo::new(stream), service)
+                    .await
+                {
+                    tracing::debug!("Error serving admin request: {err}");
+                }
+            });
+        }
+    }
+
+    async fn respond(&self, req: Request<Incoming>) -> Response<Full<Bytes>> {
+        let method = req.method().clone();
+        let path = req.uri().path().to_owned();
+        // App names are percent-encoded, as they may contain any character
+        let segments: Vec<String> = path
+            .trim_matches('/')
+            .split('/')
+            .map(|segment| percent_decode_str(segment).decode_utf8_lossy().into_owned())
+            .collect();
+        let segments: Vec<&str> = segments.iter().map(String::as_str).collect();
+        let result = match (&method, segments.as_slice()) {
+            (&Method::GET, ["apps"]) => return json_response(StatusCode::OK, &self.status().await),
+            (&Method::POST, ["apps"]) => {
+                let deployed = match read_body(req).await {
+                    Ok(body) => match serde_json::from_slice(&body) {
+                        Ok(config) => self.deploy(config).await,
+                        Err(err) => return error_response(StatusCode::BAD_REQUEST, &err.into()),
+                    },
+                    Err(err) => Err(err),
+                };
+                return match deployed {
+                    Ok(status) => json_response(StatusCode::CREATED, &status),
+                    Err(err) => error_response(StatusCode::UNPROCESSABLE_ENTITY, &err),
+                };
+            }
+            (&Method::GET, ["apps", name]) => match self.app(name) {
+                Some(app) => Some(Ok(app.status(name).await)),
+                None => None,
+            },
+            (&Method::DELETE, ["apps", name]) => self.undeploy(name).await,
+            (&Method::POST, ["apps", name, "start"]) => self.start(name).await,
+            (&Method::POST, ["apps", name, "stop"]) => self.stop(name).await,
+            (&Method::POST, ["apps", name, "restart"]) => self.restart(name).await,
+            (&Method::POST, ["apps", name, "upgrade"]) => {
+                let name = name.to_string();
+                let upgrade = match read_body(req).await {
+                    Ok(body) if body.is_empty() => Upgrade::default(),
+                    Ok(body) => match serde_json::from_slice::<Upgrade>(&body) {
+                        Ok(upgrade) => upgrade,
+                        Err(err) => return error_response(StatusCode::BAD_REQUEST, &err.into()),
+                    },
+                    Err(err) => return error_response(StatusCode::BAD_REQUEST, &err),
+                };
+                self.upgrade(&name, upgrade.manifest).await
+            }
+            (&Method::GET, ["log-level"]) => {
+                return json_response(
+                    StatusCode::OK,
+                    &serde_json::json!({ "filter": spin_telemetry::log_filter() }),
+                )
+            }
+            (&Method::PUT, ["log-level"]) => {
+                let result = read_body(req).await.and_then(|body| {
+                    let filter =
+                        String::from_utf8(body.to_vec()).context("Log filter is not UTF-8")?;
+                    spin_telemetry::set_log_filter(filter.trim())
+                });
+                return match result {
+                    Ok(()) => json_response(
+                        StatusCode::OK,
+                        &serde_json::json!({ "filter": spin_telemetry::log_filter() }),
+                    ),
+                    Err(err) => error_response(StatusCode::BAD_REQUEST, &err),
+                };
+            }
+            (&Method::POST, ["shutdown"]) => {
+                self.shutdown.notify_one();
+                return json_response(StatusCode::ACCEPTED, &serde_json::json!({}));
+            }
+            (
+                _,
+                ["apps"]
+                | ["apps", _]
+                | ["apps", _, "start" | "stop" | "restart" | "upgrade"]
+                | ["log-level"]
+                | ["shutdown"],
+            ) => return empty_response(StatusCode::METHOD_NOT_ALLOWED),
+            _ => return empty_response(StatusCode::NOT_FOUND),
+        };
+        match result {
+            Some(Ok(status)) => json_response(StatusCode::OK, &status),
+            Some(Err(err)) => error_response(StatusCode::INTERNAL_SERVER_ERROR, &err),
+            None => empty_response(StatusCode::NOT_FOUND),
+        }
+    }
+}
+
+impl FleetAppConfig {
+    /// Finds the app's manifest, and its name if it wasn't given.
+    async fn resolve(mut self) -> Result<(String, Self)> {
+        self.manifest = dunce::canonicalize(&self.manifest)
+            .with_context(|| format!("Failed to find manifest {}", quoted_path(&self.manifest)))?;
+        let name = match self.name.take() {
+            Some(name) => name,
+            None => App::load(self.manifest.as_path())
+                .await?
+                .name()
+                .context("App has no name; give it one in the fleet file")?
+                .to_owned(),
+        };
+        Ok((name, self))
+    }
+}
+
+/// Adds an app to `apps`, checking that it shares no name, listen address
+/// or state directory with another. An app without a listen address is
+/// given the first port from 3000 which is neither in use nor reserved.
+fn insert_app(
+    apps: &mut Apps,
+    name: String,
+    config: FleetAppConfig,
+    reserved_ports: &HashSet<u16>,
+) -> Result<Arc<FleetApp>> {
+    if apps.contains_key(&name) {
+        bail!("More than one app is named {name:?}");
+    }
+    let listen = match config.listen {
+        Some(listen) => listen,
+        None => {
+            let port = (FIRST_DEFAULT_PORT..)
+                .find(|port| {
+                    !reserved_ports.contains(port)
+                        && !apps.values().any(|app| app.listen.port() == *port)
+                })
+                .context("No free port for the app to listen on")?;
+            (Ipv4Addr::LOCALHOST, port).into()
+        }
+    };
+    let state_dir = match config.state_dir {
+        Some(dir) => dir,
+        None => config
+            .manifest
+            .parent()
+            .unwrap_or(Path::new("."))
+            .join(".spin"),
+    };
+    if apps.values().any(|app| app.listen == listen) {
+        bail!("More than one app listens on {listen}");
+    }
+    if apps.values().any(|app| app.state_dir == state_dir) {
+        bail!(
+            "More than one app keeps its state in {}; give each its own `state_dir`",
+            quoted_path(&state_dir)
+        );
+    }
+    let app = Arc::new(FleetApp {
+        manifest: std::sync::Mutex::new(config.manifest),
+        listen,
+        state_dir,
+        runtime_config_file: config.runtime_config_file,
+        state: Mutex::new(AppState::Stopped),
+    });
+    apps.insert(name, app.clone());
+    Ok(app)
+}
+
+impl FleetApp {
+    fn manifest(&self) -> PathBuf {
+        self.manifest.lock().unwrap().clone()
+    }
+
+    /// Loads the app from its manifest and starts its triggers, if it isn't
+    /// running.
+    async fn start(&self, name: &str, engine: &SharedEngine) -> Result<AppStatus> {
+        let mut state = self.state.lock().await;
+        if let AppState::Running(_) = *state {
+            return Ok(self.report(name, &state));
+        }
+        let started = match self.build(&self.manifest(), engine).await {
+            Ok(built) => built.start(name, self.listen).await,
+            Err(err) => Err(err),
+        };
+        match started {
+            Ok(triggers) => {
+                *state = AppState::Running(triggers);
+                Ok(self.report(name, &state))
+            }
+            Err(err) => {
+                *state = AppState::Failed(format!("{err:#}"));
+                Err(err)
+            }
+        }
+    }
+
+    /// Loads the app from `manifest` and builds its triggers.
+    async fn build(&self, manifest: &Path, engine: &SharedEngine) -> Result<BuiltApp> {
+        let app = App::load(manifest).await?;
+        let mut config = RuntimeConfig::new()
+            .state_dir(&self.state_dir)
+            .engine(engine);
+        if let Some(file) = &self.runtime_config_file {
+            config = config.file(file);
+        }
+        let mut built = BuiltApp {
+            http: None,
+            redis: None,
+        };
+        for trigger_type in app.trigger_types() {
+            match trigger_type {
+                "http" => built.http = Some(HttpTrigger::build(&app, &config).await?),
+                "redis" => built.redis = Some(RedisTrigger::build(&app, &config).await?),
+                _ => bail!("The app has a {trigger_type:?} trigger, but only HTTP and Redis triggers can run in a fleet"),
+            }
+        }
+        Ok(built)
+    }
+
+    /// Loads a new version of the app and switches its HTTP requests to it,
+    /// restarting its other triggers. The old version is dropped once it has
+    /// finished its requests. If the app isn't running, the new version is
+    /// started.
+    async fn upgrade(
+        &self,
+        name: &str,
+        manifest: Option<PathBuf>,
+        engine: &SharedEngine,
+    ) -> Result<AppStatus> {
+        let manifest = match manifest {
+            Some(manifest) => dunce::canonicalize(&manifest)
+                .with_context(|| format!("Failed to find manifest {}", quoted_path(&manifest)))?,
+            None => self.manifest(),
+        };
+        // The old version keeps serving while the new one is built
+        let built = self.build(&manifest, engine).await?;
+
+        let mut state = self.state.lock().await;
+        let AppState::Running(old) = std::mem::replace(&mut *state, AppState::Stopped) else {
+            let triggers = built.start(name, self.listen).await?;
+            *self.manifest.lock().unwrap() = manifest;
+            *state = AppState::Running(triggers);
+            return Ok(self.report(name, &state));
+        };
+        // Triggers other than HTTP can't run two versions side by side, as
+        // both would receive the same messages
+        let mut stopped = vec![];
+        for trigger in old.others {
+            stopped.push(trigger.stop().await);
+        }
+        let mut retired = None;
+        let http = match (old.http, built.http) {
+            (Some(server), Some(trigger)) => {
+                retired = Some(server.replace(trigger));
+                Some(server)
+            }
+            (Some(server), None) => {
+                stopped.push(server.stop().await);
+                None
+            }
+            (None, Some(trigger)) => match serve(name, trigger, self.listen).await {
+                Ok(server) => Some(server),
+                Err(err) => {
+                    *state = AppState::Failed(format!("{err:#}"));
+                    return Err(err);
+                }
+            },
+            (None, None) => None,
+        };
+        for err in stopped.into_iter().filter_map(Result::err) {
+            tracing::warn!("Error stopping the previous version of app {name:?}: {err:#}");
+        }
+        *state = AppState::Running(Triggers {
+            http,
+            others: built.redis.into_iter().map(RedisTrigger::start).collect(),
+        });
+        *self.manifest.lock().unwrap() = manifest;
+        terminal::step!("Upgraded", "app {name:?}");
+
+        if let Some(retired) = retired {
+            let name = name.to_owned();
+            tokio::spawn(async move {
+                if !retired.drain(DRAIN_TIMEOUT).await {
+                    terminal::warn!(
+                        "The previous version of app {name:?} was stopped before finishing its requests"
+                    );
+                }
+            });
+        }
+        Ok(self.report(name, &state))
+    }
+
+    /// Stops the app's triggers, if it is running.
+    async fn stop(&self, name: &str) -> Result<AppStatus> {
+        let mut state = self.state.lock().await;
+        if let AppState::Running(triggers) = std::mem::replace(&mut *state, AppState::Stopped) {
+            let result = triggers.stop().await;
+            terminal::step!("Stopped", "app {name:?}");
+            result?;
+        }
+        Ok(self.report(name, &state))
+    }
+
+    /// Marks the app as failed if any of its triggers has stopped by itself,
+    /// stopping the rest.
+    async fn reap(&self, name: &str) {
+        let mut state = self.state.lock().await;
+        let AppState::Running(triggers) = &*state else {
+            return;
+        };
+        if !triggers.any_finished() {
+            return;
+        }
+        let AppState::Running(triggers) = std::mem::replace(&mut *state, AppState::Stopped) else {
+            unreachable!()
+        };
+        let mut error = None;
+        if let Some(http) = triggers.http {
+            if let Err(err) = http.stop().await {
+                error.get_or_insert(format!("{err:#}"));
+            }
+        }
+        for trigger in triggers.others {
+            let result = if trigger.is_finished() {
+                trigger.wait().await
+            } else {
+                trigger.stop().await
+            };
+            if let Err(err) = result {
+                error.get_or_insert(format!("{err:#}"));
+            }
+        }
+        let error = error.unwrap_or_else(|| "A trigger stopped unexpectedly".to_owned());
+        terminal::error!("App {name:?} stopped: {error}");
+        *state = AppState::Failed(error);
+    }
+
+    async fn status(&self, name: &str) -> AppStatus {
+        self.report(name, &*self.state.lock().await)
+    }
+
+    fn report(&self, name: &str, state: &AppState) -> AppStatus {
+        let (status, error) = match state {
+            AppState::Running(_) => ("running", None),
+            AppState::Stopped => ("stopped", None),
+            AppState::Failed(error) => ("failed", Some(error.clone())),
+        };
+        AppStatus {
+            name: name.to_owned(),
+            manifest: self.manifest(),
+            listen: self.listen,
+            state_dir: self.state_dir.clone(),
+            status,
+            error,
+        }
+    }
+}
+
+impl BuiltApp {
+    async fn start(self, name: &str, listen: SocketAddr) -> Result<Triggers> {
+        let http = match self.http {
+            Some(trigger) => Some(serve(name, trigger, listen).await?),
+            None => None,
+        };
+        Ok(Triggers {
+            http,
+            others: self.redis.into_iter().map(RedisTrigger::start).collect(),
+        })
+    }
+}
+
+impl Triggers {
+    fn any_finished(&self) -> bool {
+        self.http.as_ref().is_some_and(HttpServer::is_finished)
+            || self.others.iter().any(RunningTrigger::is_finished)
+    }
+
+    async fn stop(self) -> Result<()> {
+        let mut result = Ok(());
+        if let Some(http) = self.http {
+            result = result.and(http.stop().await);
+        }
+        for trigger in self.others {
+            result = result.and(trigger.stop().await);
+        }
+        result
+    }
+}
+
+async fn serve(name: &str, trigger: HttpTrigger, listen: SocketAddr) -> Result<HttpServer> {
+    let server = trigger.serve_replaceable(listen).await?;
+    terminal::step!("Serving", "http://{} for app {name:?}", server.local_addr());
+    Ok(server)
+}
+
+fn json_response(status: StatusCode, value: &impl Serialize) -> Response<Full<Bytes>> {
+    let Ok(body) = serde_json::to_vec_pretty(value) else {
+        return empty_response(StatusCode::INTERNAL_SERVER_ERROR);
+    };
+    let mut response = Response::new(Full::new(body.into()));
+    *response.status_mut() = status;
+    response.headers_mut().insert(
+        hyper::header::CONTENT_TYPE,
+        hyper::header::HeaderValue::from_static("application/json"),
+    );
+    response
+}
+
+fn error_response(status: StatusCode, err: &anyhow::Error) -> Response<Full<Bytes>> {
+    json_response(status, &serde_json::json!({ "error": format!("{err:#}") }))
+}
+
+fn empty_response(status: StatusCode) -> Response<Full<Bytes>> {
+    let mut response = Response::new(Full::new(Bytes::new()));
+    *response.status_mut() = status;
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fleet_file_paths_are_relative_to_the_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("fleet.toml");
+        std::fs::write(
+            &path,
+            r#"
+            [[app]]
+            manifest = "shop/spin.toml"
+            listen = "127.0.0.1:3001"
+
+            [[app]]
+            name = "blog"
+            manifest = "/srv/blog/spin.toml"
+            state_dir = "state/blog"
+            "#,
+        )
+        .unwrap();
+        let apps = read_fleet_file(&path).unwrap();
+        assert_eq!(apps.len(), 2);
+        assert_eq!(apps[0].manifest, dir.path().join("shop/spin.toml"));
+        assert_eq!(apps[0].listen, Some("127.0.0.1:3001".parse().unwrap()));
+        assert_eq!(apps[1].name.as_deref(), Some("blog"));
+        assert_eq!(apps[1].manifest, PathBuf::from("/srv/blog/spin.toml"));
+        assert_eq!(apps[1].state_dir, Some(dir.path().join("state/blog")));
+    }
+
+    #[tokio::test]
+    async fn apps_may_not_share_state() {
+        let dir = tempfile::tempdir().unwrap();
+        let manifest = dir.path().join("spin.toml");
+        std::fs::write(&manifest, "").unwrap();
+        let app = |name: &str| FleetAppConfig {
+            name: Some(name.to_owned()),
+            ..FleetAppConfig::from_manifest(&manifest)
+        };
+        let err = Fleet::new(vec![app("a"), app("b")]).await.err().unwrap();
+        assert!(err.to_string().contains("state"), "{err}");
+    }
+
+    #[test]
+    fn apps_are_given_free_ports() {
+        let mut apps = Apps::new();
+        let app = |name: &str, listen: Option<&str>| FleetAppConfig {
+            listen: listen.map(|listen| listen.parse().unwrap()),
+            state_dir: Some(PathBuf::from("/state").join(name)),
+            ..FleetAppConfig::from_manifest("/apps/spin.toml")
+        };
+        let reserved = HashSet::from([3001]);
+        insert_app(&mut apps, "a".into(), app("a", None), &reserved).unwrap();
+        insert_app(&mut apps, "b".into(), app("b", None), &reserved).unwrap();
+        insert_app(
+            &mut apps,
+            "c".into(),
+            app("c", Some("127.0.0.1:3001")),
+            &reserved,
+        )
+        .unwrap();
+        let ports: Vec<_> = apps.values().map(|app| app.listen.port()).collect();
+        assert_eq!(ports, [3000, 3002, 3001]);
+
+        let err = insert_app(
+            &mut apps,
+            "d".into(),
+            app("d", Some("127.0.0.1:3000")),
+            &reserved,
+        )
+        .err()
+        .unwrap();
+        assert!(err.to_string().contains("listens"), "{err}");
+    }
+}
@@ -0,0 +1,174 @@
+//! Supervision of the trigger processes started by `spin up`.
+//!
+//! Each trigger type in an application runs in its own process. The
+//! supervisor watches them as a group: when any trigger exits, the others are
+//! asked to stop and given a grace period before being killed, so that the
+//! application starts and stops as a unit.
+
+use std::process::ExitStatus;
+
+use anyhow::{Context, Result};
+use futures::future::select_all;
+use tokio::process::Child;
+use tokio::time::Duration;
+
+/// How long triggers are given to stop before being killed.
+const SHUTDOWN_GRACE_PERIOD: Duration = Duration::from_secs(5);
+
+/// The trigger processes of an application.
+#[derive(Default)]
+pub(crate) struct TriggerSupervisor {
+    triggers: Vec<SupervisedTrigger>,
+}
+
+struct SupervisedTrigger {
+    name: String,
+    child: Child,
+    exit: Option<ExitStatus>,
+}
+
+/// The state of a trigger process.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum TriggerHealth {
+    Running,
+    Exited(ExitStatus),
+}
+
+impl TriggerSupervisor {
+    pub fn add(&mut self, name: impl Into<String>, child: Child) {
+        self.triggers.push(SupervisedTrigger {
+            name: name.into(),
+            child,
+            exit: None,
+        });
+    }
+
+    pub fn children(&self) -> impl Iterator<Item = &Child> {
+        self.triggers.iter().map(|trigger| &trigger.child)
+    }
+
+    /// Returns the name and process ID of each trigger which has not been
+    /// waited for.
+    pub fn processes(&self) -> impl Iterator<Item = (&str, u32)> {
+        self.triggers
+            .iter()
+            .filter_map(|trigger| Some((trigger.name.as_str(), trigger.child.id()?)))
+    }
+
+    /// Returns the state of each trigger.
+    pub fn health(&mut self) -> Result<Vec<(&str, TriggerHealth)>> {
+        self.triggers
+            .iter_mut()
+            .map(|trigger| {
+                if trigger.exit.is_none() {
+                    trigger.exit = trigger.child.try_wait()?;
+                }
+                let health = match trigger.exit {
+                    Some(status) => TriggerHealth::Exited(status),
+                    None => TriggerHealth::Running,
+                };
+                Ok((trigger.name.as_str(), health))
+            })
+            .collect()
+    }
+
+    /// Waits until any trigger exits, then stops the others. Returns the name
+    /// and exit status of the trigger which exited first.
+    pub async fn run(mut self) -> Result<(String, ExitStatus)> {
+        let (status, index, rest) = select_all(
+            self.triggers
+                .iter_mut()
+                .map(|trigger| Box::pin(trigger.child.wait())),
+        )
+        .await;
+        drop(rest);
+        let status = status.context("Failed to wait for trigger process")?;
+        self.triggers[index].exit = Some(status);
+        let name = self.triggers[index].name.clone();
+
+        if self.triggers.len() > 1 {
+            if !status.success() {
+                println!("The {name} trigger exited unexpectedly. Terminating.");
+            }
+            self.shutdown().await;
+            for (other, health) in self.health()? {
+                if let TriggerHealth::Exited(status) = health {
+                    tracing::debug!("Trigger {other} exited with {status}");
+                }
+            }
+        }
+        Ok((name, status))
+    }
+
+    /// Asks all running triggers to stop, killing those which are still
+    /// running after the grace period.
+    async fn shutdown(&mut self) {
+        let running = self
+            .triggers
+            .iter()
+            .filter(|trigger| trigger.exit.is_none())
+            .map(|trigger| &trigger.child);
+        super::kill_child_processes(&super::get_pids(running));
+        let deadline = tokio::time::Instant::now() + SHUTDOWN_GRACE_PERIOD;
+        for trigger in &mut self.triggers {
+            if trigger.exit.is_some() {
+                continue;
+            }
+            let status = match tokio::time::timeout_at(deadline, trigger.child.wait()).await {
+                Ok(status) => status,
+                Err(_) => {
+                    tracing::warn!(
+                        "Trigger {} did not stop within {SHUTDOWN_GRACE_PERIOD:?}; killing it",
+                        trigger.name
+                    );
+                    _ = trigger.child.start_kill();
+                    trigger.child.wait().await
+                }
+            };
+            match status {
+                Ok(status) => trigger.exit = Some(status),
+                Err(e) => tracing::warn!("Failed to wait for trigger {}: {e}", trigger.name),
+            }
+        }
+    }
+}
+
+#[cfg(all(test, not(windows)))]
+mod tests {
+    use super::*;
+
+    fn spawn(script: &str) -> Child {
+        tokio::process::Command::new("sh")
+            .args(["-c", script])
+            .kill_on_drop(true)
+            .spawn()
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn first_exit_stops_the_other_triggers() {
+        let mut supervisor = TriggerSupervisor::default();
+        supervisor.add("http", spawn("sleep 30"));
+        supervisor.add("redis", spawn("exit 3"));
+
+        let started = std::time::Instant::now();
+        let (name, status) = supervisor.run().await.unwrap();
+        assert_eq!(name, "redis");
+        assert_eq!(status.code(), Some(3));
+        assert!(started.elapsed() < SHUTDOWN_GRACE_PERIOD);
+    }
+
+    #[tokio::test]
+    async fn health_reports_exited_triggers() {
+        let mut supervisor = TriggerSupervisor::default();
+        supervisor.add("http", spawn("sleep 30"));
+        supervisor.add("command", spawn("exit 0"));
+        tokio::time::sleep(Duration::from_millis(200)).await;
+
+        let health = supervisor.health().unwrap();
+        assert_eq!(health[0], ("http", TriggerHealth::Running));
+        assert!(
+            matches!(health[1], ("command", TriggerHealth::Exited(status)) if status.success())
+        );
+    }
+}
@@ -0,0 +1,316 @@
+//! Values for required application variables which nothing else provides:
+//! prompted for with `spin up --prompt-variables`, and optionally saved in
+//! the application's state directory for later runs. Secret values are
+//! saved encrypted, with a key kept in the user's configuration directory.
+
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{Context, Result};
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use is_terminal::IsTerminal;
+use spin_common::ui::quoted_path;
+use spin_keys::{provider::file::FileKeyProvider, DataKey};
+use spin_locked_app::locked::Variable;
+
+use super::VARIABLE_ENV_PREFIX;
+
+/// The saved values of non-secret variables, in the state directory.
+const VARIABLES_FILE: &str = "variables.toml";
+/// The saved values of secret variables, in the state directory.
+const SECRETS_FILE: &str = "secrets.json";
+/// The key which encrypts saved secrets, in the Spin configuration directory.
+const SECRETS_KEY_FILE: &str = "secrets.key";
+
+/// Variable values saved in an application's state directory.
+pub(crate) struct SavedVariables {
+    state_dir: PathBuf,
+    key_file: PathBuf,
+}
+
+impl SavedVariables {
+    pub fn new(state_dir: impl Into<PathBuf>) -> Result<Self> {
+        let config_dir = dirs::config_dir().context("Cannot find configuration directory")?;
+        Ok(Self::with_key_file(
+            state_dir,
+            config_dir.join("fermyon").join(SECRETS_KEY_FILE),
+        ))
+    }
+
+    fn with_key_file(state_dir: impl Into<PathBuf>, key_file: PathBuf) -> Self {
+        Self {
+            state_dir: state_dir.into(),
+            key_file,
+        }
+    }
+
+    fn variables_file(&self) -> PathBuf {
+        self.state_dir.join(VARIABLES_FILE)
+    }
+
+    fn secrets_file(&self) -> PathBuf {
+        self.state_dir.join(SECRETS_FILE)
+    }
+
+    /// Loads the saved values, decrypting any secrets.
+    pub fn load(&self) -> Result<BTreeMap<String, String>> {
+        let mut values = self.load_plain()?;
+        let secrets = self.load_secrets()?;
+        if !secrets.is_empty() {
+            let key = self.key(false)?;
+            for (name, sealed) in secrets {
+                let value = open(&key, &name, &sealed).with_context(|| {
+                    format!(
+                        "Cannot decrypt saved secret `{name}` in {}. Remove it to be prompted again.",
+                        quoted_path(self.secrets_file())
+                    )
+                })?;
+                values.insert(name, value);
+            }
+        }
+        Ok(values)
+    }
+
+    /// Saves the values of variables, encrypting those which are secret.
+    pub fn save(&self, values: &[(String, String, bool)]) -> Result<()> {
+        std::fs::create_dir_all(&self.state_dir)
+            .with_context(|| format!("Failed to create {}", quoted_path(&self.state_dir)))?;
+
+        let mut plain = self.load_plain()?;
+        let mut secrets = self.load_secrets()?;
+        let key = if values.iter().any(|(_, _, secret)| *secret) {
+            Some(self.key(true)?)
+        } else {
+            None
+        };
+        for (name, value, secret) in values {
+            match &key {
+                Some(key) if *secret => {
+                    plain.remove(name);
+                    secrets.insert(name.clone(), seal(key, name, value)?);
+                }
+                _ => {
+                    secrets.remove(name);
+                    plain.insert(name.clone(), value.clone());
+                }
+            }
+        }
+
+        let variables_file = self.variables_file();
+        std::fs::write(&variables_file, toml::to_string(&plain)?)
+            .with_context(|| format!("Failed to write {}", quoted_path(&variables_file)))?;
+        let secrets_file = self.secrets_file();
+        std::fs::write(&secrets_file, serde_json::to_vec_pretty(&secrets)?)
+            .with_context(|| format!("Failed to write {}", quoted_path(&secrets_file)))?;
+        Ok(())
+    }
+
+    fn load_plain(&self) -> Result<BTreeMap<String, String>> {
+        match read_optional(&self.variables_file())? {
+            Some(text) => toml::from_str(&text)
+                .with_context(|| format!("Invalid {}", quoted_path(self.variables_file()))),
+            None => Ok(BTreeMap::new()),
+        }
+    }
+
+    fn load_secrets(&self) -> Result<BTreeMap<String, String>> {
+        match read_optional(&self.secrets_file())? {
+            Some(text) => serde_json::from_str(&text)
+                .with_context(|| format!("Invalid {}", quoted_path(self.secrets_file()))),
+            None => Ok(BTreeMap::new()),
+        }
+    }
+
+    /// Reads the encryption key, creating it if `create` is set.
+    fn key(&self, create: bool) -> Result<DataKey> {
+        let provider = FileKeyProvider::new(&self.key_file);
+        if create {
+            provider.create_if_missing().load()
+        } else {
+            provider.load()
+        }
+    }
+}
+
+/// Returns the SPIN_VARIABLE_<NAME> environment variables for the app's
+/// required variables which are not already set: from saved values, or,
+/// if `prompt` is set, by asking the user. Variables which are still unset
+/// are left to the trigger's other variable providers.
+pub(crate) fn missing_variables_env(
+    variables: &BTreeMap<String, Variable>,
+    variables_env: &[(String, String)],
+    saved: Option<&SavedVariables>,
+    prompt: bool,
+) -> Result<Vec<(String, String)>> {
+    let missing: Vec<(&String, &Variable)> = variables
+        .iter()
+        .filter(|(_, variable)| variable.default.is_none())
+        .filter(|(name, _)| {
+            let key = variable_env_key(name);
+            std::env::var_os(&key).is_none() && !variables_env.iter().any(|(k, _)| *k == key)
+        })
+        .collect();
+    if missing.is_empty() {
+        return Ok(vec![]);
+    }
+
+    let saved_values = match saved {
+        Some(saved) => saved.load()?,
+        None => BTreeMap::new(),
+    };
+    let mut env = vec![];
+    let mut answers = vec![];
+    for (name, variable) in missing {
+        if let Some(value) = saved_values.get(name) {
+            env.push((variable_env_key(name), value.clone()));
+        } else if prompt {
+            let value = prompt_for(name, variable)?;
+            env.push((variable_env_key(name), value.clone()));
+            answers.push((name.clone(), value, variable.secret));
+        }
+    }
+
+    if let Some(saved) = saved {
+        if !answers.is_empty()
+            && dialoguer::Confirm::new()
+                .with_prompt(format!(
+                    "Save these values in {} for future runs?",
+                    quoted_path(&saved.state_dir)
+                ))
+                .default(false)
+                .interact_opt()?
+                .unwrap_or(false)
+        {
+            saved.save(&answers)?;
+        }
+    }
+    Ok(env)
+}
+
+fn prompt_for(name: &str, variable: &Variable) -> Result<String> {
+    if !std::io::stdin().is_terminal() {
+        bail!("Variable `{name}` has no value, and cannot be prompted for because the input is not a terminal");
+    }
+    let mut prompt = if variable.secret {
+        format!("Value for secret variable `{name}`")
+    } else {
+        format!("Value for variable `{name}`")
+    };
+    let hints: Vec<_> = [
+        variable.description.clone(),
+        variable
+            .example
+            .as_ref()
+            .map(|example| format!("e.g. {example}")),
+    ]
+    .into_iter()
+    .flatten()
+    .collect();
+    if !hints.is_empty() {
+        prompt = format!("{prompt} ({})", hints.join("; "));
+    }
+    let pattern = variable
+        .pattern
+        .as_ref()
+        .map(|pattern| regex::Regex::new(&format!("^(?:{pattern})$")))
+        .transpose()
+        .with_context(|| format!("Variable `{name}` has an invalid pattern"))?;
+    let validate = |value: &String| match &pattern {
+        Some(regex) if !regex.is_match(value) => Err(format!(
+            "The value must match the pattern {:?}",
+            variable.pattern.as_deref().unwrap_or_default()
+        )),
+        _ => Ok(()),
+    };
+    let value = if variable.secret {
+        loop {
+            let value = dialoguer::Password::new().with_prompt(&prompt).interact()?;
+            match validate(&value) {
+                Ok(()) => break value,
+                Err(err) => eprintln!("{err}"),
+            }
+        }
+    } else {
+        dialoguer::Input::<String>::new()
+            .with_prompt(prompt)
+            .validate_with(validate)
+            .interact_text()?
+    };
+    Ok(value)
+}
+
+fn variable_env_key(name: &str) -> String {
+    format!("{VARIABLE_ENV_PREFIX}{}", name.to_uppercase())
+}
+
+/// Encrypts a value, binding it to the variable name.
+fn seal(key: &DataKey, name: &str, value: &str) -> Result<String> {
+    Ok(BASE64.encode(key.seal(name.as_bytes(), value.as_bytes())?))
+}
+
+fn open(key: &DataKey, name: &str, sealed: &str) -> Result<String> {
+    let value = key.open(name.as_bytes(), &BASE64.decode(sealed)?)?;
+    Ok(String::from_utf8(value)?)
+}
+
+fn read_optional(path: &Path) -> Result<Option<String>> {
+    match std::fs::read_to_string(path) {
+        Ok(text) => Ok(Some(text)),
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(None),
+        Err(err) => Err(err).with_context(|| format!("Failed to read {}", quoted_path(path))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn saved_secrets_are_encrypted() {
+        let dir = tempfile::tempdir().unwrap();
+        let saved = SavedVariables::with_key_file(
+            dir.path().join(".spin"),
+            dir.path().join("config").join(SECRETS_KEY_FILE),
+        );
+        saved
+            .save(&[
+                ("region".to_owned(), "eu-west".to_owned(), false),
+                ("api_key".to_owned(), "hunter2".to_owned(), true),
+            ])
+            .unwrap();
+
+        let secrets = std::fs::read_to_string(saved.secrets_file()).unwrap();
+        assert!(secrets.contains("api_key"));
+        assert!(!secrets.contains("hunter2"));
+
+        let values = saved.load().unwrap();
+        assert_eq!(values["region"], "eu-west");
+        assert_eq!(values["api_key"], "hunter2");
+
+        let variables = BTreeMap::from([
+            (
+                "api_key".to_owned(),
+                Variable {
+                    default: None,
+                    secret: true,
+                    ..Default::default()
+                },
+            ),
+            (
+                "greeting".to_owned(),
+                Variable {
+                    default: Some("hello".to_owned()),
+                    secret: false,
+                    ..Default::default()
+                },
+            ),
+        ]);
+        let env = missing_variables_env(&variables, &[], Some(&saved), false).unwrap();
+        assert_eq!(
+            env,
+            [("SPIN_VARIABLE_API_KEY".to_owned(), "hunter2".to_owned())]
+        );
+    }
+}
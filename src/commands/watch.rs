@@ -1,6 +1,7 @@
 use std::{
+    collections::HashSet,
     path::{Path, PathBuf},
-    sync::Arc,
+    sync::{Arc, Mutex},
     time::Duration,
 };
 
@@ -14,7 +15,7 @@ use watchexec::Watchexec;
 
 use crate::opts::{
     APP_MANIFEST_FILE_OPT, DEFAULT_MANIFEST_FILE, WATCH_CLEAR_OPT, WATCH_DEBOUNCE_OPT,
-    WATCH_SKIP_BUILD_OPT,
+    WATCH_HOT_RELOAD_OPT, WATCH_SKIP_BUILD_OPT,
 };
 
 mod buildifier;
@@ -26,6 +27,9 @@ use buildifier::Buildifier;
 use filters::{ArtifactFilterFactory, BuildFilterFactory, FilterFactory, ManifestFilterFactory};
 use uppificator::{Pause, Uppificator};
 
+/// Paths which have changed, collected by a watcher for its consumer.
+pub(crate) type ChangedPaths = Arc<Mutex<HashSet<PathBuf>>>;
+
 /// Build and run the Spin application, rebuilding and restarting it when files change.
 #[derive(Parser, Debug)]
 #[clap(
@@ -66,6 +70,12 @@ pub struct WatchCommand {
     #[clap(name = WATCH_SKIP_BUILD_OPT, long = "skip-build")]
     pub skip_build: bool,
 
+    /// Reload rebuilt components into the running application instead of
+    /// restarting it. Changes to the manifest or component files still
+    /// restart the application.
+    #[clap(name = WATCH_HOT_RELOAD_OPT, long = "hot-reload")]
+    pub hot_reload: bool,
+
     /// Arguments to be passed through to spin up.
     #[clap()]
     pub up_args: Vec<String>,
@@ -80,6 +90,8 @@ impl WatchCommand {
         //   * If `spin up` crashes, the Uppificator restarts it.  BUT APART FROM THAT THAT'S ALL IT DOES OKAY.
         // * The Buildifier, if in play, watches the manifest and component.build.watch collections. When it detects a
         //   change, it PAUSES the Uppificator, does the build, then unpauses the Uppificator.
+        //   * It builds only the components whose watch globs match the changed files, or all components if the
+        //     manifest changed.
         //   * It is on the Uppificator to recognise if any interesting files have changed when it unpauses.
         // * The Reconfiguriser watches the manifest *only*. When it detects a change, it reconfigures the `watchexec`
         //   instances that underlie the Uppificator and Buildifier. There is no need to trigger a reload as
@@ -87,6 +99,8 @@ impl WatchCommand {
         //   * Reconfiguration is supported by the ReconfigurableWatcher, which holds the watchexec instance,
         //     and the RuntimeConfigFactory, which holds the information needed to re-read the manifest
         //     and create a new configuration for the watchexec instances.
+        // * In hot_reload configurations, the Uppificator does not watch component sources: `spin up` is told to
+        //   reload rebuilt components itself, so listeners stay up across builds.
         // * In skip_build configurations, the Buildifier is not present.
         // * In clear configurations, both the Buildifier and the Uppificator clear the screen on a change.
         //   * There is a slight twist here that the Uppificator does _not_ clear the screen if the Buildifier
@@ -106,6 +120,7 @@ impl WatchCommand {
         let (source_code_tx, source_code_rx) = tokio::sync::watch::channel(Uuid::new_v4());
         let (manifest_tx, manifest_rx) = tokio::sync::watch::channel(Uuid::new_v4());
         let (stop_tx, stop_rx) = tokio::sync::watch::channel(Uuid::new_v4());
+        let source_code_paths = ChangedPaths::default();
 
         let mut buildifier = Buildifier {
             spin_bin: spin_bin.clone(),
@@ -113,13 +128,18 @@ impl WatchCommand {
             clear_screen: self.clear,
             has_ever_built: false,
             watched_changes: source_code_rx,
+            changed_paths: source_code_paths.clone(),
             uppificator_pauser: pause_tx,
         };
 
+        let mut up_args = self.up_args.clone();
+        if self.hot_reload {
+            up_args.push("--hot-reload".to_owned());
+        }
         let mut uppificator = Uppificator {
             spin_bin: spin_bin.clone(),
             manifest: manifest_file.clone(),
-            up_args: self.up_args.clone(),
+            up_args,
             clear_screen: self.clear,
             watched_changes: artifact_rx,
             pause_feed: pause_rx,
@@ -139,6 +159,7 @@ impl WatchCommand {
         let artifact_filterer = Box::new(ArtifactFilterFactory {
             skip_build: self.skip_build,
             skip_assets: contains_direct_mounts,
+            skip_sources: self.hot_reload,
         });
         let (artifact_watcher, artifact_watcher_handle) = self
             .spawn_watchexec(
@@ -146,6 +167,7 @@ impl WatchCommand {
                 &manifest_dir,
                 artifact_filterer,
                 artifact_tx,
+                None,
                 "reload",
             )
             .await
@@ -160,6 +182,7 @@ impl WatchCommand {
                 &manifest_dir,
                 build_filterer,
                 source_code_tx,
+                Some(source_code_paths),
                 "build",
             )
             .await
@@ -179,6 +202,7 @@ impl WatchCommand {
                 &manifest_dir,
                 manifest_filterer,
                 manifest_tx,
+                None,
                 "reconfigure",
             )
             .await
@@ -240,6 +264,7 @@ impl WatchCommand {
         manifest_dir: &Path,
         filter_factory: Box<dyn FilterFactory>,
         notifier: Arc<tokio::sync::watch::Sender<Uuid>>,
+        changed_paths: Option<ChangedPaths>,
         impact_description: &'static str,
     ) -> anyhow::Result<(ReconfigurableWatcher, tokio::task::JoinHandle<()>)> {
         let rtf = RuntimeConfigFactory {
@@ -247,6 +272,7 @@ impl WatchCommand {
             manifest_dir: manifest_dir.to_owned(),
             filter_factory,
             notifier,
+            changed_paths,
             impact_description,
             debounce: Duration::from_millis(self.debounce),
         };
@@ -270,6 +296,7 @@ pub struct RuntimeConfigFactory {
     manifest_dir: PathBuf,
     filter_factory: Box<dyn FilterFactory>,
     notifier: Arc<tokio::sync::watch::Sender<Uuid>>,
+    changed_paths: Option<ChangedPaths>,
     impact_description: &'static str,
     debounce: Duration,
 }
@@ -283,7 +310,11 @@ impl RuntimeConfigFactory {
             .build_filter(&self.manifest_file, &self.manifest_dir, &manifest)
             .await?;
 
-        let handler = NotifyOnFileChange::new(self.notifier.clone(), self.impact_description);
+        let handler = NotifyOnFileChange::new(
+            self.notifier.clone(),
+            self.changed_paths.clone(),
+            self.impact_description,
+        );
 
         let mut rt = watchexec::config::RuntimeConfig::default();
         rt.pathset([&self.manifest_dir]);
@@ -302,17 +333,20 @@ impl RuntimeConfigFactory {
 struct NotifyOnFileChange {
     despurifier: despurifier::Despurifier,
     notifier: Arc<tokio::sync::watch::Sender<Uuid>>,
+    changed_paths: Option<ChangedPaths>,
     impact_description: &'static str,
 }
 
 impl NotifyOnFileChange {
     fn new(
         notifier: Arc<tokio::sync::watch::Sender<Uuid>>,
+        changed_paths: Option<ChangedPaths>,
         impact_description: &'static str,
     ) -> Self {
         Self {
             despurifier: despurifier::Despurifier::new(),
             notifier,
+            changed_paths,
             impact_description,
         }
     }
@@ -331,6 +365,13 @@ impl watchexec::handler::Handler<watchexec::action::Action> for NotifyOnFileChan
                 self.impact_description,
                 paths_of(&action)
             );
+            if let Some(changed_paths) = &self.changed_paths {
+                let paths = action.events.iter().filter_map(path_of_event);
+                changed_paths
+                    .lock()
+                    .unwrap()
+                    .extend(paths.map(Path::to_owned));
+            }
             _ = self.notifier.send(Uuid::new_v4());
         }
         action.outcome(watchexec::action::Outcome::DoNothing);
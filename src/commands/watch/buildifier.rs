@@ -1,18 +1,25 @@
 use command_group::AsyncCommandGroup;
-use std::path::PathBuf;
+use std::collections::BTreeSet;
+use std::path::{Path, PathBuf};
 use uuid::Uuid;
 
+use super::filters::component_source_globs;
 use super::uppificator::Pause;
+use super::ChangedPaths;
 
 pub(crate) struct Buildifier {
     pub spin_bin: PathBuf,
     pub manifest: PathBuf,
     pub clear_screen: bool,
     pub has_ever_built: bool,
-    pub watched_changes: tokio::sync::watch::Receiver<Uuid>, // TODO: refine which component(s) a change affects
+    pub watched_changes: tokio::sync::watch::Receiver<Uuid>,
+    pub changed_paths: ChangedPaths,
     pub uppificator_pauser: tokio::sync::mpsc::Sender<Pause>,
 }
 
+/// The components to build: `None` means all of them.
+type ComponentSet = Option<BTreeSet<String>>;
+
 impl Buildifier {
     #[allow(clippy::collapsible_if)]
     pub(crate) async fn run(&mut self) {
@@ -28,7 +35,8 @@ impl Buildifier {
                 break;
             }
 
-            let build_result = self.build_once().await;
+            let components = self.take_affected_components().await;
+            let build_result = self.build_once(components).await;
             if !self.has_ever_built {
                 self.has_ever_built = matches!(build_result, Ok(true));
             }
@@ -45,10 +53,16 @@ impl Buildifier {
         }
     }
 
-    pub(crate) async fn build_once(&mut self) -> std::io::Result<bool> {
+    pub(crate) async fn build_once(
+        &mut self,
+        mut components: ComponentSet,
+    ) -> std::io::Result<bool> {
         loop {
             let mut cmd = tokio::process::Command::new(&self.spin_bin);
             cmd.arg("build").arg("-f").arg(&self.manifest);
+            for component in components.iter().flatten() {
+                cmd.arg("-c").arg(component);
+            }
             let mut child = cmd.group_spawn()?;
 
             tokio::select! {
@@ -63,10 +77,121 @@ impl Buildifier {
                     if self.clear_screen {
                         _ = clearscreen::clear();
                     }
+                    let more = self.take_affected_components().await;
+                    components = components.zip(more).map(|(mut a, b)| {
+                        a.extend(b);
+                        a
+                    });
                     continue;
                 }
 
             }
         }
     }
+
+    /// Takes the paths which have changed since the last build and works out
+    /// which components they affect. Until a build has succeeded, and
+    /// whenever that can't be worked out, all components are built.
+    async fn take_affected_components(&self) -> ComponentSet {
+        let paths = std::mem::take(&mut *self.changed_paths.lock().unwrap());
+        if !self.has_ever_built || paths.is_empty() {
+            return None;
+        }
+        let manifest_str = tokio::fs::read_to_string(&self.manifest).await.ok()?;
+        let manifest = spin_manifest::manifest_from_str(&manifest_str).ok()?;
+        let manifest_dir = self.manifest.parent()?;
+        let components = affected_components(&self.manifest, manifest_dir, &manifest, &paths)?;
+        tracing::debug!("spin watch rebuilding components: {components:?}");
+        Some(components)
+    }
+}
+
+/// Returns the components whose build watches any of `paths`, or `None` if a
+/// path is the manifest or is not watched by any component.
+fn affected_components<'a>(
+    manifest_file: &Path,
+    manifest_dir: &Path,
+    manifest: &spin_manifest::schema::v2::AppManifest,
+    paths: impl IntoIterator<Item = &'a PathBuf>,
+) -> ComponentSet {
+    let watches: Vec<(&str, Vec<glob::Pattern>)> = manifest
+        .components
+        .iter()
+        .map(|(id, component)| {
+            let patterns = component_source_globs(component)
+                .iter()
+                .filter_map(|glob| glob::Pattern::new(glob).ok())
+                .collect();
+            (id.as_ref(), patterns)
+        })
+        .collect();
+
+    let mut affected = BTreeSet::new();
+    for path in paths {
+        if path == manifest_file {
+            return None;
+        }
+        let relative = path.strip_prefix(manifest_dir).ok()?;
+        let mut watched = false;
+        for (id, patterns) in &watches {
+            if patterns.iter().any(|p| p.matches_path(relative)) {
+                affected.insert(id.to_string());
+                watched = true;
+            }
+        }
+        if !watched {
+            return None;
+        }
+    }
+    Some(affected)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MANIFEST: &str = r#"
+        spin_manifest_version = 2
+        [application]
+        name = "app"
+        [[trigger.http]]
+        route = "/api/..."
+        component = "api"
+        [[trigger.http]]
+        route = "/..."
+        component = "web"
+        [component.api]
+        source = "api/target/api.wasm"
+        [component.api.build]
+        command = "cargo build"
+        workdir = "api"
+        watch = ["src/**/*.rs"]
+        [component.web]
+        source = "web.wasm"
+        [component.web.build]
+        command = "npm run build"
+        watch = ["web/**/*.js"]
+    "#;
+
+    #[test]
+    fn changes_rebuild_only_the_components_watching_them() {
+        let manifest = spin_manifest::manifest_from_str(MANIFEST).unwrap();
+        let dir = Path::new("/app");
+        let manifest_file = dir.join("spin.toml");
+        let affected = |paths: &[&str]| {
+            let paths: Vec<PathBuf> = paths.iter().map(|p| dir.join(p)).collect();
+            affected_components(&manifest_file, dir, &manifest, &paths)
+        };
+
+        assert_eq!(
+            affected(&["api/src/handlers/mod.rs"]),
+            Some(BTreeSet::from(["api".to_owned()]))
+        );
+        assert_eq!(
+            affected(&["api/src/lib.rs", "web/pages/index.js"]),
+            Some(BTreeSet::from(["api".to_owned(), "web".to_owned()]))
+        );
+        assert_eq!(affected(&["spin.toml", "api/src/lib.rs"]), None);
+        assert_eq!(affected(&["README.md"]), None);
+    }
 }
@@ -20,6 +20,9 @@ pub(crate) trait FilterFactory: Send + Sync {
 pub(crate) struct ArtifactFilterFactory {
     pub skip_build: bool,
     pub skip_assets: bool,
+    /// Component sources are reloaded by the running application rather
+    /// than restarting it.
+    pub skip_sources: bool,
 }
 
 pub(crate) struct BuildFilterFactory;
@@ -33,7 +36,7 @@ impl FilterFactory for ArtifactFilterFactory {
         manifest_dir: &Path,
         manifest: &v2::AppManifest,
     ) -> anyhow::Result<Arc<watchexec_filterer_globset::GlobsetFilterer>> {
-        let manifest_glob = if self.skip_build {
+        let manifest_glob = if self.skip_build || self.skip_sources {
             vec![stringize_path(manifest_file)?]
         } else {
             vec![] // In this case, manifest changes trigger a rebuild, which will poke the uppificator anyway
@@ -41,6 +44,7 @@ impl FilterFactory for ArtifactFilterFactory {
         let wasm_globs = manifest
             .components
             .values()
+            .filter(|_| !self.skip_sources)
             .filter_map(|c| match &c.source {
                 v2::ComponentSource::Local(path) => Some(path.clone()),
                 _ => None,
@@ -122,13 +126,18 @@ impl FilterFactory for BuildFilterFactory {
 }
 
 fn create_source_globs(cid: &str, c: &v2::Component) -> Vec<String> {
-    let Some(build) = &c.build else {
-        return vec![];
-    };
-    if build.watch.is_empty() {
+    if matches!(&c.build, Some(build) if build.watch.is_empty()) {
         eprintln!(
             "You haven't configured what to watch for the component: '{cid}'. Learn how to configure Spin watch at https://developer.fermyon.com/common/cli-reference#watch"
         );
+    }
+    component_source_globs(c)
+}
+
+/// The globs, relative to the manifest directory, of the source files from
+/// which a component is built.
+pub(crate) fn component_source_globs(c: &v2::Component) -> Vec<String> {
+    let Some(build) = &c.build else {
         return vec![];
     };
     build
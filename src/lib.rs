@@ -1,6 +1,7 @@
 pub mod build_info;
 pub mod commands;
 pub(crate) mod opts;
+pub mod output;
 pub mod subprocess;
 
 pub use opts::HELP_ARGS_ONLY_TRIGGER_TYPE;
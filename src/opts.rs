@@ -11,5 +11,12 @@ pub const HELP_ARGS_ONLY_TRIGGER_TYPE: &str = "provide-help-args-no-app";
 pub const FROM_REGISTRY_OPT: &str = "REGISTRY_REFERENCE";
 pub const WATCH_CLEAR_OPT: &str = "CLEAR";
 pub const WATCH_DEBOUNCE_OPT: &str = "DEBOUNCE";
+pub const WATCH_HOT_RELOAD_OPT: &str = "HOT_RELOAD";
 pub const WATCH_SKIP_BUILD_OPT: &str = "SKIP_BUILD";
 pub const ALWAYS_BUILD_ENV: &str = "SPIN_ALWAYS_BUILD";
+pub const UP_TEMP_DIR_ENV: &str = "SPIN_TEMP_DIR";
+pub const UP_CACHE_DIR_ENV: &str = "SPIN_CACHE_DIR";
+pub const UP_DIRECT_MOUNTS_ENV: &str = "SPIN_DIRECT_MOUNTS";
+pub const UP_ENVIRONMENT_ENV: &str = "SPIN_ENVIRONMENT";
+pub const UP_POLICY_FILE_ENV: &str = "SPIN_POLICY_FILE";
+pub const DETACH_FLAG: &str = "detach";
@@ -0,0 +1,91 @@
+//! Machine-readable command output.
+//!
+//! Commands which support `--output json` print a single JSON document on
+//! stdout describing their result; human-readable progress messages go to
+//! stderr instead. The format is passed on to subprocesses, such as trigger
+//! processes, through the `SPIN_OUTPUT` environment variable.
+
+use anyhow::Result;
+use clap::Args;
+use serde::Serialize;
+use terminal::{OutputFormat, OUTPUT_FORMAT_ENV};
+
+/// The `--output` option.
+#[derive(Args, Debug, Default)]
+pub struct OutputArgs {
+    /// The format of the command's output. With "json", results are printed
+    /// to stdout as JSON and all other messages go to stderr.
+    #[clap(
+        long = "output",
+        env = OUTPUT_FORMAT_ENV,
+        default_value = "text",
+        possible_values = ["text", "json"]
+    )]
+    pub output: OutputFormat,
+}
+
+impl OutputArgs {
+    /// Uses the output format for this process and any it starts.
+    pub fn apply(&self) -> OutputFormat {
+        terminal::set_output_format(self.output);
+        std::env::set_var(OUTPUT_FORMAT_ENV, self.output.to_string());
+        self.output
+    }
+}
+
+/// Prints a command's result as a line of JSON.
+pub fn print_json(result: &impl Serialize) -> Result<()> {
+    println!("{}", serde_json::to_string(result)?);
+    Ok(())
+}
+
+/// A failed command, as reported in JSON output.
+#[derive(Debug, Serialize)]
+pub struct ErrorOutput {
+    pub error: ErrorDetails,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ErrorDetails {
+    /// The process exit code
+    pub code: i32,
+    pub message: String,
+    /// The chain of underlying errors, outermost first
+    pub causes: Vec<String>,
+}
+
+impl ErrorOutput {
+    pub fn new(err: &anyhow::Error, code: i32) -> Self {
+        Self {
+            error: ErrorDetails {
+                code,
+                message: err.to_string(),
+                causes: err.chain().skip(1).map(|e| e.to_string()).collect(),
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::Context;
+
+    #[test]
+    fn errors_report_their_causes() {
+        let err = Err::<(), _>(anyhow::anyhow!("connection refused"))
+            .context("cannot reach registry")
+            .unwrap_err();
+        let json = serde_json::to_value(ErrorOutput::new(&err, 1)).unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!({
+                "error": {
+                    "code": 1,
+                    "message": "cannot reach registry",
+                    "causes": ["connection refused"],
+                }
+            })
+        );
+    }
+}
@@ -15,6 +15,12 @@ impl ExitStatusError {
         }
     }
 
+    /// An error for a command which has already reported its failure, and
+    /// should exit with the given code.
+    pub(crate) fn with_code(code: i32) -> Self {
+        Self { status: Some(code) }
+    }
+
     pub fn code(&self) -> i32 {
         self.status.unwrap_or(1)
     }